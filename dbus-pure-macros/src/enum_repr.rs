@@ -0,0 +1,114 @@
+//! Parsing of fieldless enums for `#[derive(ToVariant)]` and `#[derive(FromVariant)]`
+
+use super::ResultExt;
+
+/// How the enum is represented on the wire, chosen with the `#[dbus(repr = "...")]` container attribute
+pub(super) enum Repr {
+	/// `#[dbus(repr = "string")]`: The variant's name, or the name set with `#[dbus(rename = "...")]`
+	String,
+
+	/// `#[dbus(repr = "u32")]`: The variant's discriminant
+	U32,
+}
+
+pub(super) struct Enum<'a> {
+	pub(super) repr: Repr,
+
+	/// The variants' idents, and the value they're represented as.
+	pub(super) variants: Vec<(&'a syn::Ident, proc_macro2::TokenStream)>,
+}
+
+impl Enum<'_> {
+	pub(super) fn signature(&self) -> proc_macro2::TokenStream {
+		match self.repr {
+			Repr::String => quote::quote! { dbus_pure::proto::Signature::String },
+			Repr::U32 => quote::quote! { dbus_pure::proto::Signature::U32 },
+		}
+	}
+}
+
+pub(super) fn parse<'a>(
+	derive_name: &str,
+	attrs: &[syn::Attribute],
+	data: &'a syn::DataEnum,
+	tokens: &proc_macro2::TokenStream,
+) -> Result<Enum<'a>, syn::Error> {
+	let mut repr = None;
+
+	for attr in attrs {
+		if !attr.path().is_ident("dbus") {
+			continue;
+		}
+
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("repr") {
+				let value: syn::LitStr = meta.value()?.parse()?;
+				repr = Some(match &*value.value() {
+					"string" => Repr::String,
+					"u32" => Repr::U32,
+					_ => return Err(r#"unknown repr; expected `"string"` or `"u32"`"#).spanning(&value),
+				});
+				Ok(())
+			}
+			else {
+				Err(meta.error(r#"unknown attribute; expected `#[dbus(repr = "...")]`"#))
+			}
+		})?;
+	}
+
+	let repr =
+		repr
+		.ok_or_else(|| format!(r#"#[derive({derive_name})] on an enum requires a `#[dbus(repr = "string")]` or `#[dbus(repr = "u32")]` attribute"#))
+		.spanning(tokens)?;
+
+	let mut names = std::collections::BTreeSet::new();
+
+	let mut variants = vec![];
+
+	for variant in &data.variants {
+		if !matches!(variant.fields, syn::Fields::Unit) {
+			return Err(format!("#[derive({derive_name})] can only be used on enums whose variants have no fields")).spanning(variant);
+		}
+
+		let ident = &variant.ident;
+
+		let mut rename = None;
+
+		for attr in &variant.attrs {
+			if !attr.path().is_ident("dbus") {
+				continue;
+			}
+
+			attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("rename") {
+					rename = Some(meta.value()?.parse::<syn::LitStr>()?);
+					Ok(())
+				}
+				else {
+					Err(meta.error(r#"unknown attribute; expected `#[dbus(rename = "...")]`"#))
+				}
+			})?;
+		}
+
+		let value = match repr {
+			Repr::String => {
+				let name = rename.unwrap_or_else(|| syn::LitStr::new(&ident.to_string(), ident.span()));
+				if !names.insert(name.value()) {
+					return Err("duplicate variant name").spanning(&name);
+				}
+				quote::quote! { #name }
+			},
+
+			Repr::U32 => {
+				if let Some(rename) = rename {
+					return Err(r#"`#[dbus(rename = "...")]` can only be used with `#[dbus(repr = "string")]`"#).spanning(&rename);
+				}
+				quote::quote! { Self::#ident as u32 }
+			},
+		};
+
+		variants.push((ident, value));
+	}
+
+	Ok(Enum { repr, variants })
+}
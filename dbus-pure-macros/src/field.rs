@@ -0,0 +1,149 @@
+//! Parsing of struct fields and their `#[dbus(...)]` attributes for `#[derive(ToVariant)]` and `#[derive(FromVariant)]`
+
+use super::ResultExt;
+
+pub(super) struct Field<'a> {
+	pub(super) member: syn::Member,
+
+	/// `#[dbus(skip)]`: The field is not converted to or from the variant.
+	pub(super) skip: bool,
+
+	ty: &'a syn::Type,
+
+	conversion: Conversion,
+}
+
+enum Conversion {
+	/// Use the field type's `ToVariant` and `FromVariant` impls.
+	Default,
+
+	/// `#[dbus(variant)]`: The field is wrapped in a `Variant::Variant`
+	Variant,
+
+	/// `#[dbus(with = "path::to::module")]`: The field is converted by the `signature`, `to_variant` and `from_variant` functions in the given module.
+	With(syn::Path),
+}
+
+impl Field<'_> {
+	/// The name of the field in the path of a `FromVariantError::Field`
+	pub(super) fn name(&self) -> String {
+		match &self.member {
+			syn::Member::Named(ident) => ident.to_string().trim_start_matches("r#").to_owned(),
+			syn::Member::Unnamed(index) => index.index.to_string(),
+		}
+	}
+
+	/// An expression that evaluates to the `dbus_pure::proto::Signature` of the field.
+	pub(super) fn signature_tokens(&self) -> proc_macro2::TokenStream {
+		let ty = self.ty;
+
+		match &self.conversion {
+			Conversion::Default => quote::quote! { <#ty as dbus_pure::proto::ToVariant>::signature() },
+			Conversion::Variant => quote::quote! { dbus_pure::proto::Signature::Variant },
+			Conversion::With(path) => quote::quote! { #path::signature() },
+		}
+	}
+
+	/// An expression that converts `value`, a reference to the field, to a `dbus_pure::proto::Variant`
+	pub(super) fn to_variant_tokens(&self, value: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+		match &self.conversion {
+			Conversion::Default => quote::quote! { <_ as dbus_pure::proto::ToVariant>::to_variant(#value) },
+			Conversion::Variant => quote::quote! {
+				dbus_pure::proto::Variant::Variant(Box::new(<_ as dbus_pure::proto::ToVariant>::to_variant(#value)).into())
+			},
+			Conversion::With(path) => quote::quote! { #path::to_variant(#value) },
+		}
+	}
+
+	/// An expression that converts `variant`, a `&dbus_pure::proto::Variant`, to a `Result` of the field's type.
+	pub(super) fn parse_variant_tokens(&self, variant: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+		match &self.conversion {
+			Conversion::Default => quote::quote! { <_ as dbus_pure::proto::FromVariant>::from_variant(#variant) },
+			Conversion::Variant => quote::quote! {
+				match #variant {
+					dbus_pure::proto::Variant::Variant(inner) => <_ as dbus_pure::proto::FromVariant>::from_variant(inner),
+					variant => Err(dbus_pure::proto::FromVariantError::SignatureMismatch {
+						expected: dbus_pure::proto::Signature::Variant,
+						actual: variant.signature(),
+					}),
+				}
+			},
+			Conversion::With(path) => quote::quote! { #path::from_variant(#variant) },
+		}
+	}
+}
+
+/// Adds a `T: #bound` bound for every type parameter `T` that's used in the type of a field that's converted with its type's impls.
+///
+/// Type parameters that are only used in skipped fields or fields with `#[dbus(with = "...")]` don't need the bound.
+pub(super) fn with_bounds(generics: &syn::Generics, fields: &[Field<'_>], bound: &proc_macro2::TokenStream) -> syn::Generics {
+	let mut generics = generics.clone();
+
+	let type_params: Vec<_> = generics.type_params().map(|param| param.ident.clone()).collect();
+
+	for type_param in type_params {
+		let used =
+			fields.iter()
+			.filter(|field| !field.skip && !matches!(field.conversion, Conversion::With(_)))
+			.any(|field| uses_ident(quote::ToTokens::into_token_stream(field.ty), &type_param));
+		if used {
+			generics.make_where_clause().predicates.push(syn::parse_quote! { #type_param: #bound });
+		}
+	}
+
+	generics
+}
+
+fn uses_ident(tokens: proc_macro2::TokenStream, ident: &syn::Ident) -> bool {
+	tokens.into_iter().any(|token| match token {
+		proc_macro2::TokenTree::Ident(token) => token == *ident,
+		proc_macro2::TokenTree::Group(group) => uses_ident(group.stream(), ident),
+		proc_macro2::TokenTree::Punct(_) | proc_macro2::TokenTree::Literal(_) => false,
+	})
+}
+
+pub(super) fn parse(fields: &syn::Fields) -> Result<Vec<Field<'_>>, syn::Error> {
+	fields.iter().zip(fields.members()).map(|(field, member)| {
+		let mut skip = false;
+		let mut conversion = Conversion::Default;
+
+		for attr in &field.attrs {
+			if !attr.path().is_ident("dbus") {
+				continue;
+			}
+
+			attr.parse_nested_meta(|meta| {
+				let new_conversion =
+					if meta.path.is_ident("skip") {
+						skip = true;
+						None
+					}
+					else if meta.path.is_ident("variant") {
+						Some(Conversion::Variant)
+					}
+					else if meta.path.is_ident("with") {
+						let path: syn::LitStr = meta.value()?.parse()?;
+						Some(Conversion::With(path.parse()?))
+					}
+					else {
+						return Err(meta.error(r#"unknown attribute; expected `#[dbus(skip)]`, `#[dbus(variant)]` or `#[dbus(with = "...")]`"#));
+					};
+
+				if let Some(new_conversion) = new_conversion {
+					if !matches!(conversion, Conversion::Default) {
+						return Err(meta.error("`#[dbus(variant)]` and `#[dbus(with = \"...\")]` cannot be used together"));
+					}
+					conversion = new_conversion;
+				}
+
+				Ok(())
+			})?;
+		}
+
+		if skip && !matches!(conversion, Conversion::Default) {
+			return Err("`#[dbus(skip)]` cannot be used with other attributes").spanning(field);
+		}
+
+		Ok(Field { member, skip, ty: &field.ty, conversion })
+	}).collect()
+}
@@ -0,0 +1,145 @@
+use super::ResultExt;
+
+pub(super) fn run(input: proc_macro::TokenStream) -> Result<proc_macro2::TokenStream, syn::Error> {
+	let input: proc_macro2::TokenStream = input.into();
+
+	let tokens = input.clone();
+
+	let input: syn::DeriveInput = syn::parse2(input)?;
+
+	let ident = &input.ident;
+	let struct_fields = match &input.data {
+		syn::Data::Struct(syn::DataStruct { fields, .. }) => super::field::parse(fields)?,
+		syn::Data::Enum(_) | syn::Data::Union(_) => vec![],
+	};
+
+	let generics = super::field::with_bounds(&input.generics, &struct_fields, &quote::quote! { dbus_pure::proto::FromVariant });
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+	let from_variant_body = match &input.data {
+		syn::Data::Struct(syn::DataStruct { fields: fields @ syn::Fields::Unnamed(_), .. }) if fields.len() == 1 => {
+			// Delegate to the wrapped type's impl
+
+			let fields = &struct_fields;
+			let field = &fields[0];
+			if field.skip {
+				return Err("#[derive(FromVariant)] cannot skip the only field of a tuple struct").spanning(&tokens);
+			}
+
+			let field_from_variant = field.parse_variant_tokens(&quote::quote! { variant });
+
+			quote::quote! {
+				Ok(#ident(#field_from_variant?))
+			}
+		},
+
+		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Unnamed(syn::FieldsUnnamed { unnamed: fields, .. }), .. }) if fields.is_empty() =>
+			return Err("#[derive(FromVariant)] cannot be used on tuple structs with no fields").spanning(&tokens),
+
+		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(_) | syn::Fields::Unnamed(_), .. }) => {
+			// Variant::Struct
+
+			let fields = &struct_fields;
+
+			let num_fields = fields.iter().filter(|field| !field.skip).count();
+			let expected = format!("struct with {num_fields} fields");
+
+			let mut i = 0_usize;
+			let fields_from_variant: Vec<_> =
+				fields.iter()
+				.map(|field| {
+					let member = &field.member;
+
+					if field.skip {
+						return quote::quote! { #member: Default::default() };
+					}
+
+					let field_from_variant = field.parse_variant_tokens(&quote::quote! { &fields[#i] });
+					i += 1;
+
+					let name = field.name();
+					quote::quote! {
+						#member: #field_from_variant.map_err(|err| err.in_field(#name))?
+					}
+				})
+				.collect();
+
+			quote::quote! {
+				let variant: &dbus_pure::proto::Variant<'_> = match variant {
+					dbus_pure::proto::Variant::Variant(inner) => inner,
+					variant => variant,
+				};
+
+				let fields = match variant {
+					dbus_pure::proto::Variant::Struct { fields } if fields.len() == #num_fields => fields,
+					variant => return Err(dbus_pure::proto::FromVariantError::InvalidValue {
+						expected: #expected.into(),
+						actual: variant.signature().to_string(),
+					}),
+				};
+
+				Ok(#ident {
+					#(#fields_from_variant ,)*
+				})
+			}
+		},
+
+		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Unit, .. }) =>
+			return Err("#[derive(FromVariant)] cannot be used on unit structs").spanning(&tokens),
+
+		syn::Data::Enum(data) => {
+			// Variant::String or Variant::U32
+
+			let data = super::enum_repr::parse("FromVariant", &input.attrs, data, &tokens)?;
+
+			let (idents, values): (Vec<_>, Vec<_>) = data.variants.into_iter().unzip();
+
+			match data.repr {
+				super::enum_repr::Repr::String => {
+					let expected = format!("one of {}", values.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "));
+
+					quote::quote! {
+						let value: std::borrow::Cow<'_, str> = std::convert::TryFrom::try_from(variant)?;
+						match &*value {
+							#(#values => Ok(Self::#idents) ,)*
+							value => Err(dbus_pure::proto::FromVariantError::InvalidValue {
+								expected: #expected.into(),
+								actual: format!("{value:?}"),
+							}),
+						}
+					}
+				},
+
+				super::enum_repr::Repr::U32 => {
+					let expected = format!("a value of {ident}");
+
+					quote::quote! {
+						let value: u32 = std::convert::TryFrom::try_from(variant)?;
+						#(
+							if value == #values {
+								return Ok(Self::#idents);
+							}
+						)*
+						Err(dbus_pure::proto::FromVariantError::InvalidValue {
+							expected: #expected.into(),
+							actual: value.to_string(),
+						})
+					}
+				},
+			}
+		},
+
+		syn::Data::Union(_) =>
+			return Err("#[derive(FromVariant)] can only be used on structs and enums").spanning(&tokens),
+	};
+
+	let result = quote::quote! {
+		impl #impl_generics dbus_pure::proto::FromVariant for #ident #ty_generics #where_clause {
+			fn from_variant(variant: &dbus_pure::proto::Variant<'_>) -> Result<Self, dbus_pure::proto::FromVariantError> {
+				#from_variant_body
+			}
+		}
+	};
+
+	Ok(result)
+}
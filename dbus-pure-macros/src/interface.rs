@@ -11,6 +11,8 @@ pub(super) fn run(attr: proc_macro::TokenStream, item: proc_macro::TokenStream)
 
 	let mut impl_body = vec![];
 
+	let mut method_descriptors = vec![];
+
 	for item in &input.items {
 		let (attrs, sig) = match item {
 			syn::TraitItem::Fn(syn::TraitItemFn { attrs, sig, .. }) => (attrs, sig),
@@ -30,6 +32,11 @@ pub(super) fn run(attr: proc_macro::TokenStream, item: proc_macro::TokenStream)
 
 		let fn_name = &sig.ident;
 
+		let mut arg_descriptors = vec![];
+
+		// Statements that collect the file descriptors to send along with the method call, in the order of the parameters that refer to them.
+		let mut fd_pushes = vec![];
+
 		let args = &sig.inputs;
 		let args_variant =
 			if args.is_empty() {
@@ -46,91 +53,198 @@ pub(super) fn run(attr: proc_macro::TokenStream, item: proc_macro::TokenStream)
 						syn::Pat::Ident(ident) => ident,
 						_ => return Err("fn parameters can only be idents, not arbitrary patterns").spanning(arg),
 					};
-					let arg = match ty {
-						syn::Type::Reference(_) => quote::quote!(#ident),
-						_ => quote::quote!(&#ident),
+					let (arg, signature_ty) = match ty {
+						syn::Type::Reference(syn::TypeReference { elem, .. }) => (quote::quote!(#ident), &**elem),
+						_ => (quote::quote!(&#ident), ty),
 					};
-					arg_variants.push(arg);
+
+					let signature =
+						if let Some(fd_type) = fd_type(signature_ty) {
+							// The parameter is sent as an index into the file descriptors sent along with the method call.
+							// An `OwnedFd` passed by value is sent as-is, and anything else is duplicated first.
+							let index: u32 = fd_pushes.len().try_into().map_err(|_| "too many file descriptor parameters").spanning(ty)?;
+							let fd =
+								if matches!(fd_type, FdType::Owned) && !matches!(ty, syn::Type::Reference(_)) {
+									quote::quote! { #ident }
+								}
+								else {
+									quote::quote! {
+										std::os::fd::AsFd::as_fd(#arg).try_clone_to_owned()
+										.map_err(|err| dbus_pure::MethodCallError::SendRequest(dbus_pure::SendError::Io(err)))?
+									}
+								};
+							fd_pushes.push(quote::quote! { fds.push(#fd); });
+							arg_variants.push(quote::quote! { dbus_pure::proto::Variant::UnixFd(dbus_pure::proto::UnixFd(#index)) });
+
+							quote::quote! { || dbus_pure::proto::Signature::UnixFd }
+						}
+						else {
+							arg_variants.push(quote::quote! { <_ as dbus_pure::proto::ToVariant>::to_variant(#arg) });
+
+							quote::quote! { <#signature_ty as dbus_pure::proto::ToVariant>::signature }
+						};
+
+					let arg_name = ident.ident.to_string();
+					arg_descriptors.push(quote::quote! {
+						dbus_pure::proto::introspect::Arg {
+							name: Some(#arg_name),
+							signature: #signature,
+							direction: dbus_pure::proto::introspect::Direction::In,
+						}
+					});
 				}
 
 				quote::quote! {
 					Some(&dbus_pure::proto::Variant::Tuple {
 						elements: (&[
-							#(<_ as dbus_pure::proto::ToVariant>::to_variant(#arg_variants),)*
+							#(#arg_variants,)*
 						][..]).into(),
 					})
 				}
 			};
 
-		let (return_ty, return_expr) = match &sig.output {
+		let returns_fd = match &sig.output {
+			syn::ReturnType::Default => false,
+			syn::ReturnType::Type(_, ty) => match fd_type(ty) {
+				Some(FdType::Owned) => true,
+				Some(FdType::Borrowed) => return Err("fn cannot return a `BorrowedFd`, use `OwnedFd` instead").spanning(ty),
+				None => false,
+			},
+		};
+
+		// Methods that send or receive file descriptors are called with `method_call_with_fds`, and its result is destructured
+		// into `(body, fds)` before the body is used. The received file descriptors are only used if the method returns one.
+		let with_fds = !fd_pushes.is_empty() || returns_fd;
+
+		let body_expr =
+			if with_fds {
+				quote::quote! { let (body, _) = body; }
+			}
+			else {
+				quote::quote! {}
+			};
+
+		// The signature that the response body is checked against before it's used, if any.
+		let (return_ty, expected_signature, return_expr) = match &sig.output {
 			syn::ReturnType::Default => (
 				quote::quote! { () },
+				quote::quote! { None },
 				quote::quote! {
+					#body_expr
 					let _ = body;
 					Ok(())
 				},
 			),
 
+			syn::ReturnType::Type(_, ty) if returns_fd => {
+				arg_descriptors.push(quote::quote! {
+					dbus_pure::proto::introspect::Arg {
+						name: None,
+						signature: || dbus_pure::proto::Signature::UnixFd,
+						direction: dbus_pure::proto::introspect::Direction::Out,
+					}
+				});
+
+				let return_expr = quote::quote! {
+					let (body, fds) = body;
+					let mut fds: std::vec::Vec<_> = fds.into_iter().map(Some).collect();
+					let fd =
+						body
+						.and_then(|body| body.take_fd(&mut fds))
+						.ok_or_else(|| dbus_pure::MethodCallError::UnexpectedResponse(None))?;
+					Ok(fd)
+				};
+
+				(quote::quote! { #ty }, quote::quote! { Some(&dbus_pure::proto::Signature::UnixFd) }, return_expr)
+			},
+
 			syn::ReturnType::Type(_, ty) => {
-				let return_ty = quote::quote! { #ty };
-
-				// If return type is `dbus_pure::proto::Variant`, return it as-is
-				let is_variant = match &**ty {
-					syn::Type::Path(syn::TypePath { path, .. }) => {
-						let segments: Vec<_> =
-							path.segments.iter()
-							.take(3)
-							.map(|path_segment| &path_segment.ident)
-							.collect();
-						let is_variant =
-							segments.len() == 3 &&
-							segments[0] == "dbus_pure" &&
-							segments[1] == "proto" &&
-							segments[2] == "Variant";
-						is_variant
-					},
-					_ => false,
+				// See `dbus_pure::private::Response` for how the return type picks its expected signature and how it's converted from the response body.
+				let response = quote::quote! {
+					#[allow(unused_imports)]
+					use dbus_pure::private::{AnySignature as _, ConvertDeserialize as _, ConvertVariant as _, KnownSignature as _};
+					let response = &&dbus_pure::private::Response::<#ty>(std::marker::PhantomData);
 				};
 
-				let return_expr =
-					if is_variant {
-						quote::quote! {
-							let body =
-								body
-								.ok_or_else(|| dbus_pure::MethodCallError::UnexpectedResponse(None))?;
-							Ok(body)
-						}
+				// A return type without an expected signature, like a `Variant` or a type that can only be deserialized with serde,
+				// is described as a variant.
+				arg_descriptors.push(quote::quote! {
+					dbus_pure::proto::introspect::Arg {
+						name: None,
+						signature: || {
+							#response
+							response.expected_signature().unwrap_or(dbus_pure::proto::Signature::Variant)
+						},
+						direction: dbus_pure::proto::introspect::Direction::Out,
 					}
-					else {
-						quote::quote! {
-							let body =
-								body
-								.ok_or_else(|| dbus_pure::MethodCallError::UnexpectedResponse(None))?;
-							let body =
-								serde::Deserialize::deserialize(body)
-								.map_err(|err| dbus_pure::MethodCallError::UnexpectedResponse(Some(err)))?;
-							Ok(body)
-						}
-					};
+				});
 
-				(return_ty, return_expr)
-			}
+				let expected_signature = quote::quote! {
+					{
+						#response
+						response.expected_signature()
+					}.as_ref()
+				};
+
+				let return_expr = quote::quote! {
+					#body_expr
+					#response
+					response.convert_body(body)
+				};
+
+				(quote::quote! { #ty }, expected_signature, return_expr)
+			},
 		};
 
+		// Check the response signature first so that a response of the wrong type fails with a descriptive error
+		// rather than whatever error serde happens to hit first.
+		let call_expr =
+			if with_fds {
+				quote::quote! {
+					{
+						let mut fds: std::vec::Vec<std::os::fd::OwnedFd> = vec![];
+						#(#fd_pushes)*
+						dbus_pure::private::method_call_with_fds(
+							client,
+							self.name(),
+							self.path(),
+							#interface_name,
+							#dbus_fn_name,
+							#args_variant,
+							fds,
+							#expected_signature,
+						)
+					}
+				}
+			}
+			else {
+				quote::quote! {
+					dbus_pure::private::method_call(
+						client,
+						self.name(),
+						self.path(),
+						#interface_name,
+						#dbus_fn_name,
+						#args_variant,
+						#expected_signature,
+					)
+				}
+			};
+
+		method_descriptors.push(quote::quote! {
+			dbus_pure::proto::introspect::Method {
+				name: #dbus_fn_name,
+				args: &[#(#arg_descriptors ,)*],
+			}
+		});
+
 		impl_body.push(quote::quote! {
 			fn #fn_name(
 				&self,
 				client: &mut dbus_pure::Client,
 				#args
 			) -> std::result::Result<#return_ty, dbus_pure::MethodCallError> {
-				let body =
-					client.method_call(
-						self.name(),
-						self.path(),
-						#interface_name,
-						#dbus_fn_name,
-						#args_variant,
-					)?;
+				let body = #call_expr?;
 				#return_expr
 			}
 		});
@@ -138,7 +252,37 @@ pub(super) fn run(attr: proc_macro::TokenStream, item: proc_macro::TokenStream)
 
 	Ok(quote::quote! {
 		#vis trait #struct_name: dbus_pure::proto::Object {
+			/// The description of this interface, for introspection.
+			const DESCRIPTOR: dbus_pure::proto::introspect::Interface = dbus_pure::proto::introspect::Interface {
+				name: #interface_name,
+				methods: &[#(#method_descriptors ,)*],
+				signals: &[],
+				properties: &[],
+			};
+
 			#(#impl_body)*
 		}
 	})
 }
+
+enum FdType {
+	Borrowed,
+	Owned,
+}
+
+/// Whether the given type is `OwnedFd` or `BorrowedFd`, going by the last segment of its path so that it can be written with any path.
+fn fd_type(ty: &syn::Type) -> Option<FdType> {
+	let syn::Type::Path(syn::TypePath { qself: None, path }) = ty else {
+		return None;
+	};
+	let last_segment = path.segments.last()?;
+	if last_segment.ident == "OwnedFd" {
+		Some(FdType::Owned)
+	}
+	else if last_segment.ident == "BorrowedFd" {
+		Some(FdType::Borrowed)
+	}
+	else {
+		None
+	}
+}
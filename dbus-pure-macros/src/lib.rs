@@ -9,12 +9,22 @@
 #[allow(unused_extern_crates)] // Needed for stable 1.40.0 but not for nightly
 extern crate proc_macro;
 
+mod enum_repr;
+
+mod field;
+
+mod from_variant;
+
 mod interface;
 
 mod object;
 
+mod sig;
+
 mod to_variant;
 
+mod variant;
+
 fn run(result: Result<proc_macro2::TokenStream, syn::Error>) -> proc_macro::TokenStream {
 	let token_stream = match result {
 		Ok(token_stream) => token_stream,
@@ -57,10 +67,28 @@ impl<T, E> ResultExt<T> for Result<T, E> where E: std::fmt::Display {
 /// - Every `fn` in the trait is modified to have a default implementation. This default implementation uses the client
 ///   to invoke the method and parse its response.
 ///
+///   A return type of `dbus_pure::proto::Variant<'static>` is the response body as-is. Any other return type is deserialized
+///   from the response body with serde. If it implements `dbus_pure::proto::ExpectedSignature`, the signature of the response body
+///   is checked first, so that a response of the wrong type fails with `dbus_pure::MethodCallError::SignatureMismatch`.
+///
+/// - A `DESCRIPTOR` associated const is added to the trait. It describes the interface's methods for introspection,
+///   with argument names taken from the fn parameters and argument signatures from the `dbus_pure::proto::ToVariant` impls of their types.
+///   The return type is described with its `dbus_pure::proto::ExpectedSignature`, or as a variant if it doesn't have one.
+///
 /// Thus, the above example will be (approximately) emitted as:
 ///
 /// ```rust,ignore
 /// trait OrgFreeDesktopDbusInterface: dbus_pure::proto::Object {
+///     const DESCRIPTOR: dbus_pure::proto::introspect::Interface = dbus_pure::proto::introspect::Interface {
+///         name: "org.freedesktop.DBus",
+///         methods: &[
+///             dbus_pure::proto::introspect::Method { name: "AddMatch", args: &[...] },
+///             dbus_pure::proto::introspect::Method { name: "ListNames", args: &[...] },
+///         ],
+///         signals: &[],
+///         properties: &[],
+///     };
+///
 ///     fn add_match(client: &mut dbus_pure::Client, rule: &str) -> Result<(), dbus_pure::MethodCallError> {
 ///         ...
 ///     }
@@ -72,6 +100,54 @@ impl<T, E> ResultExt<T> for Result<T, E> where E: std::fmt::Display {
 /// ```
 ///
 /// To use this trait, consider defining an object using the `#[dbus_pure_macros::object]` macro in this crate.
+///
+/// The `DESCRIPTOR` can be used to build the introspection XML of an object that implements the interface:
+///
+/// ```rust
+/// # #[dbus_pure_macros::interface("org.freedesktop.DBus")]
+/// # trait OrgFreeDesktopDbusInterface {
+/// #     #[name = "AddMatch"]
+/// #     fn add_match(rule: &str);
+/// #
+/// #     #[name = "ListNames"]
+/// #     fn list_names() -> Vec<String>;
+/// # }
+/// #
+/// #[dbus_pure_macros::object(OrgFreeDesktopDbusInterface)]
+/// struct OrgFreeDesktopDbusObject;
+///
+/// let xml = dbus_pure::proto::introspect::to_xml(&[&OrgFreeDesktopDbusObject::DESCRIPTOR], &[]);
+/// assert!(xml.contains(r#"<arg name="rule" type="s" direction="in"/>"#));
+/// assert!(xml.contains(r#"<arg type="as" direction="out"/>"#));
+/// ```
+///
+/// Parameters of type `std::os::fd::OwnedFd` or `std::os::fd::BorrowedFd` (or references to them) are sent as unix file descriptors
+/// along with the method call, and a return type of `std::os::fd::OwnedFd` receives one from the response. Such methods are invoked with
+/// `dbus_pure::Client::method_call_with_fds`. An `OwnedFd` passed by value is sent as-is, and any other file descriptor is duplicated first.
+///
+/// ```rust
+/// #[dbus_pure_macros::interface("org.freedesktop.login1.Manager")]
+/// trait OrgFreeDesktopLogin1ManagerInterface {
+///     #[name = "Inhibit"]
+///     fn inhibit(what: &str, who: &str, why: &str, mode: &str) -> std::os::fd::OwnedFd;
+/// }
+///
+/// #[dbus_pure_macros::interface("org.example.Logger")]
+/// trait OrgExampleLoggerInterface {
+///     #[name = "LogTo"]
+///     fn log_to(name: &str, fd: std::os::fd::BorrowedFd<'_>);
+/// }
+///
+/// #[dbus_pure_macros::object(OrgFreeDesktopLogin1ManagerInterface, OrgExampleLoggerInterface)]
+/// struct Object;
+///
+/// let xml = dbus_pure::proto::introspect::to_xml(
+///     &[&<Object as OrgFreeDesktopLogin1ManagerInterface>::DESCRIPTOR, &<Object as OrgExampleLoggerInterface>::DESCRIPTOR],
+///     &[],
+/// );
+/// assert!(xml.contains(r#"<arg type="h" direction="out"/>"#));
+/// assert!(xml.contains(r#"<arg name="fd" type="h" direction="in"/>"#));
+/// ```
 #[proc_macro_attribute]
 pub fn interface(attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
 	run(interface::run(attr, item))
@@ -126,6 +202,83 @@ pub fn object(attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> p
 	run(object::run(attr, item))
 }
 
+/// Parses a signature string at compile time and emits the `dbus_pure::proto::Signature` value for it.
+///
+/// ```rust
+/// let signature = dbus_pure_macros::sig!("a{sv}");
+/// assert_eq!(signature, "a{sv}".parse().unwrap());
+/// assert_eq!(signature, dbus_pure::proto::Signature::Array {
+///     element: Box::new(dbus_pure::proto::Signature::DictEntry {
+///         key: Box::new(dbus_pure::proto::Signature::String),
+///         value: Box::new(dbus_pure::proto::Signature::Variant),
+///     }),
+/// });
+///
+/// assert_eq!(dbus_pure_macros::sig!("(ya(ob)h)x"), "(ya(ob)h)x".parse().unwrap());
+/// assert_eq!(dbus_pure_macros::sig!(""), dbus_pure::proto::Signature::Tuple { elements: vec![] });
+/// ```
+///
+/// An invalid signature is a compile error:
+///
+/// ```rust,compile_fail
+/// let signature = dbus_pure_macros::sig!("a{sz}"); // unexpected character 'z' at offset 3
+/// ```
+///
+/// ```rust,compile_fail
+/// let signature = dbus_pure_macros::sig!("(si"); // struct at offset 0 is not terminated
+/// ```
+///
+/// ```rust,compile_fail
+/// let signature = dbus_pure_macros::sig!("a{(i)s}"); // dict entry key at offset 2 is not a basic type
+/// ```
+#[proc_macro]
+pub fn sig(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	run(sig::run(input))
+}
+
+/// Builds a `dbus_pure::proto::Variant` from a JSON-like literal, checking its types at compile time.
+///
+/// - `{ "key": value, ... }` is a dict of type `a{sv}`. Every value is wrapped in a `Variant::Variant`.
+/// - `[value, ...]` is an array. All its elements must have the same signature.
+/// - `(value, ...)` is a struct.
+/// - `true` and `false` are `b`, `1.5` is `d`, `"foo"` is `s` and `b"foo"` is `ay`.
+/// - Integer literals must have a suffix that picks their type, like `5u32` for `u` or `-5i64` for `x`.
+/// - `"/foo" as path` is `o`, `"a{sv}" as signature` is `g` and `3 as unixfd` is `h`. Object paths and signatures are validated at compile time.
+///
+/// ```rust
+/// let hints = dbus_pure_macros::variant!({ "Interval": 5u32, "Names": ["a", "b"] });
+/// assert_eq!(hints.signature().to_string(), "a{sv}");
+/// assert_eq!(
+///     hints.get("Names").and_then(|names| names.as_array(&dbus_pure::proto::Signature::String)),
+///     Some(&[dbus_pure::proto::Variant::String("a".into()), dbus_pure::proto::Variant::String("b".into())][..]),
+/// );
+///
+/// let s = dbus_pure_macros::variant!((5u32, "/obj" as path, [(-1i16, b"\x00\x01")], "(yv)" as signature, 3 as unixfd));
+/// assert_eq!(s.signature().to_string(), "(uoa(nay)gh)");
+/// ```
+///
+/// Integer literals without a suffix are ambiguous and are a compile error:
+///
+/// ```rust,compile_fail
+/// let value = dbus_pure_macros::variant!({ "Interval": 5 });
+/// ```
+///
+/// ```rust,compile_fail
+/// let value = dbus_pure_macros::variant!([]); // cannot infer the element type of an empty array
+/// ```
+///
+/// ```rust,compile_fail
+/// let value = dbus_pure_macros::variant!([5u32, "a"]); // array element has signature "s" but the first element has signature "u"
+/// ```
+///
+/// ```rust,compile_fail
+/// let value = dbus_pure_macros::variant!("/foo/" as path); // object path has a trailing '/'
+/// ```
+#[proc_macro]
+pub fn variant(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	run(variant::run(input))
+}
+
 /// Derives `dbus_pure_proto::ToVariant` on the type.
 ///
 /// # Example
@@ -137,7 +290,263 @@ pub fn object(attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> p
 ///     bar: std::borrow::Cow<'a, str>,
 /// }
 /// ```
-#[proc_macro_derive(ToVariant)]
+///
+/// Structs with named fields, and tuple structs with more than one field, are converted to a `Variant::Struct` of their fields in order.
+/// Tuple structs with a single field delegate to the wrapped type's impl.
+///
+/// ```rust
+/// use dbus_pure::proto::{FromVariant, ToVariant};
+///
+/// #[derive(Debug, PartialEq, dbus_pure_macros::FromVariant, dbus_pure_macros::ToVariant)]
+/// struct Pair(String, u32);
+///
+/// #[derive(Debug, PartialEq, dbus_pure_macros::FromVariant, dbus_pure_macros::ToVariant)]
+/// struct Name(String);
+///
+/// assert_eq!(Pair::signature(), dbus_pure_macros::sig!("(su)"));
+/// assert_eq!(Name::signature(), dbus_pure_macros::sig!("s"));
+///
+/// // Round-trip a `Pair` through a message body
+/// let pair = Pair("a".to_owned(), 5);
+/// let header = dbus_pure::proto::MessageHeader {
+///     r#type: dbus_pure::proto::MessageType::MethodReturn { reply_serial: 1 },
+///     flags: dbus_pure::proto::message_flags::NO_REPLY_EXPECTED,
+///     body_len: 0,
+///     serial: 2,
+///     fields: vec![].into(),
+/// };
+/// let mut message = vec![];
+/// dbus_pure::proto::serialize_message(&header, Some(&pair.to_variant()), &mut message, dbus_pure::proto::Endianness::Little).unwrap();
+/// let (_, body, _) = dbus_pure::proto::deserialize_message(&message).unwrap();
+/// let body = body.unwrap();
+/// assert_eq!(body.signature().to_string(), "(su)");
+/// assert_eq!(Pair::from_variant(&body).unwrap(), pair);
+/// ```
+///
+/// ```rust,compile_fail
+/// #[derive(dbus_pure_macros::ToVariant)]
+/// struct Empty(); // #[derive(ToVariant)] cannot be used on tuple structs with no fields
+/// ```
+///
+/// # Field attributes
+///
+/// - `#[dbus(skip)]` omits the field from the signature and the variant. `#[derive(FromVariant)]` sets it to its `Default` value.
+///
+/// - `#[dbus(variant)]` wraps the field's variant in a `Variant::Variant`, so its signature is `v`.
+///
+/// - `#[dbus(with = "path::to::module")]` converts the field with the functions in the given module instead of its type's impls.
+///   The module must have `fn signature() -> Signature` and `fn to_variant(value: &T) -> Variant<'_>` for `#[derive(ToVariant)]`,
+///   and `fn from_variant(variant: &Variant<'_>) -> Result<T, FromVariantError>` for `#[derive(FromVariant)]`
+///
+/// ```rust
+/// use dbus_pure::proto::{FromVariant, ToVariant};
+///
+/// mod seconds {
+///     use dbus_pure::proto::{FromVariantError, Signature, Variant};
+///
+///     pub(crate) fn signature() -> Signature {
+///         Signature::U64
+///     }
+///
+///     pub(crate) fn to_variant(value: &std::time::Duration) -> Variant<'_> {
+///         Variant::U64(value.as_secs())
+///     }
+///
+///     pub(crate) fn from_variant(variant: &Variant<'_>) -> Result<std::time::Duration, FromVariantError> {
+///         Ok(std::time::Duration::from_secs(variant.try_into()?))
+///     }
+/// }
+///
+/// #[derive(Debug, PartialEq, dbus_pure_macros::FromVariant, dbus_pure_macros::ToVariant)]
+/// struct Request {
+///     name: String,
+///     #[dbus(variant)]
+///     value: u32,
+///     #[dbus(with = "seconds")]
+///     timeout: std::time::Duration,
+///     #[dbus(skip)]
+///     retries: usize,
+/// }
+///
+/// assert_eq!(Request::signature(), dbus_pure_macros::sig!("(svt)"));
+///
+/// // Round-trip a `Request` through a message body
+/// let request = Request { name: "a".to_owned(), value: 5, timeout: std::time::Duration::from_secs(30), retries: 3 };
+/// let header = dbus_pure::proto::MessageHeader {
+///     r#type: dbus_pure::proto::MessageType::MethodReturn { reply_serial: 1 },
+///     flags: dbus_pure::proto::message_flags::NO_REPLY_EXPECTED,
+///     body_len: 0,
+///     serial: 2,
+///     fields: vec![].into(),
+/// };
+/// let mut message = vec![];
+/// dbus_pure::proto::serialize_message(&header, Some(&request.to_variant()), &mut message, dbus_pure::proto::Endianness::Little).unwrap();
+/// let (_, body, _) = dbus_pure::proto::deserialize_message(&message).unwrap();
+/// let body = body.unwrap();
+/// assert_eq!(body.signature().to_string(), "(svt)");
+/// assert_eq!(Request::from_variant(&body).unwrap(), Request { retries: 0, ..request });
+/// ```
+///
+/// ```rust,compile_fail
+/// #[derive(dbus_pure_macros::ToVariant)]
+/// struct Request {
+///     #[dbus(skip, variant)] // `#[dbus(skip)]` cannot be used with other attributes
+///     value: u32,
+/// }
+/// ```
+///
+/// # Generics
+///
+/// Every type parameter that's used in a field gets a `ToVariant` bound (or `FromVariant` bound for `#[derive(FromVariant)]`)
+/// in addition to any bounds it already has. Type parameters that are only used in fields with `#[dbus(skip)]` or `#[dbus(with = "...")]` do not.
+///
+/// ```rust
+/// use dbus_pure::proto::{FromVariant, ToVariant};
+///
+/// #[derive(Debug, dbus_pure_macros::ToVariant)]
+/// struct Wrapper<'a, T> {
+///     inner: T,
+///     label: std::borrow::Cow<'a, str>,
+/// }
+///
+/// assert_eq!(<Wrapper<'_, u32>>::signature(), dbus_pure_macros::sig!("(us)"));
+/// let wrapper = Wrapper { inner: vec![1u8], label: "a".into() };
+/// assert_eq!(wrapper.to_variant().signature(), dbus_pure_macros::sig!("(ays)"));
+///
+/// #[derive(Debug, dbus_pure_macros::ToVariant)]
+/// struct Bounded<T> where T: Clone {
+///     inner: Vec<T>,
+/// }
+///
+/// assert_eq!(<Bounded<String>>::signature(), dbus_pure_macros::sig!("(as)"));
+///
+/// // `Local` doesn't implement `ToVariant` or `FromVariant`, but it's only used in a skipped field.
+/// #[derive(Debug, Default)]
+/// struct Local;
+///
+/// #[derive(Debug, dbus_pure_macros::FromVariant, dbus_pure_macros::ToVariant)]
+/// struct WithLocal<T> {
+///     id: u32,
+///     #[dbus(skip)]
+///     local: Option<T>,
+/// }
+///
+/// let with_local = WithLocal { id: 5, local: Some(Local) };
+/// assert_eq!(with_local.to_variant().signature(), dbus_pure_macros::sig!("(u)"));
+/// let with_local: WithLocal<Local> = FromVariant::from_variant(&with_local.to_variant()).unwrap();
+/// assert!(with_local.local.is_none());
+/// ```
+///
+/// # Enums
+///
+/// Enums whose variants have no fields can also be derived. They need a `#[dbus(repr = "...")]` attribute to choose how they're represented:
+///
+/// - `#[dbus(repr = "string")]` represents each variant as a string (`s`) of its name. Use `#[dbus(rename = "...")]` on a variant to use a different name.
+///
+/// - `#[dbus(repr = "u32")]` represents each variant as a `u32` (`u`) of its discriminant.
+///
+/// `#[derive(FromVariant)]` accepts the same attributes.
+///
+/// ```rust
+/// use dbus_pure::proto::{FromVariant, ToVariant};
+///
+/// #[derive(Debug, PartialEq, dbus_pure_macros::FromVariant, dbus_pure_macros::ToVariant)]
+/// #[dbus(repr = "string")]
+/// enum PlaybackStatus {
+///     Playing,
+///     Paused,
+///     #[dbus(rename = "Stopped")]
+///     NotPlaying,
+/// }
+///
+/// assert_eq!(PlaybackStatus::signature(), dbus_pure::proto::Signature::String);
+/// assert_eq!(PlaybackStatus::NotPlaying.to_variant(), dbus_pure::proto::Variant::String("Stopped".into()));
+/// assert_eq!(PlaybackStatus::from_variant(&dbus_pure_macros::variant!("Paused")).unwrap(), PlaybackStatus::Paused);
+/// assert!(PlaybackStatus::from_variant(&dbus_pure_macros::variant!("NotPlaying")).is_err());
+///
+/// #[derive(Debug, PartialEq, dbus_pure_macros::FromVariant, dbus_pure_macros::ToVariant)]
+/// #[dbus(repr = "u32")]
+/// enum PowerProfile {
+///     PowerSaver = 1,
+///     Balanced = 2,
+///     Performance = 4,
+/// }
+///
+/// assert_eq!(PowerProfile::signature(), dbus_pure::proto::Signature::U32);
+/// assert_eq!(PowerProfile::Performance.to_variant(), dbus_pure::proto::Variant::U32(4));
+/// assert_eq!(PowerProfile::from_variant(&dbus_pure_macros::variant!(2u32)).unwrap(), PowerProfile::Balanced);
+/// assert!(PowerProfile::from_variant(&dbus_pure_macros::variant!(3u32)).is_err());
+/// ```
+///
+/// Enums with fields are not supported:
+///
+/// ```rust,compile_fail
+/// #[derive(dbus_pure_macros::ToVariant)]
+/// #[dbus(repr = "string")]
+/// enum Status {
+///     Playing { position: u64 }, // #[derive(ToVariant)] can only be used on enums whose variants have no fields
+///     Stopped,
+/// }
+/// ```
+///
+/// ```rust,compile_fail
+/// #[derive(dbus_pure_macros::FromVariant)]
+/// #[dbus(repr = "u32")]
+/// enum Status {
+///     Playing(u64), // #[derive(FromVariant)] can only be used on enums whose variants have no fields
+///     Stopped,
+/// }
+/// ```
+///
+/// ```rust,compile_fail
+/// #[derive(dbus_pure_macros::ToVariant)] // #[derive(ToVariant)] on an enum requires a `#[dbus(repr = "string")]` or `#[dbus(repr = "u32")]` attribute
+/// enum Status {
+///     Playing,
+///     Stopped,
+/// }
+/// ```
+///
+/// ```rust,compile_fail
+/// #[derive(dbus_pure_macros::ToVariant)]
+/// #[dbus(repr = "u32")]
+/// enum Status {
+///     #[dbus(rename = "playing")] // `#[dbus(rename = "...")]` can only be used with `#[dbus(repr = "string")]`
+///     Playing,
+///     Stopped,
+/// }
+/// ```
+#[proc_macro_derive(ToVariant, attributes(dbus))]
 pub fn to_variant(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 	run(to_variant::run(input))
 }
+
+/// Derives `dbus_pure_proto::FromVariant` on the type. This is the converse of `#[derive(ToVariant)]`
+///
+/// Structs with named fields, and tuple structs with more than one field, are converted from a `Variant::Struct`
+/// with the same number of fields, in the same order. Tuple structs with a single field delegate to the wrapped type's impl.
+/// Enums whose variants have no fields are converted from strings or `u32`s, as described in the docs of `#[derive(ToVariant)]`.
+///
+/// If a field cannot be converted, the error is a `FromVariantError::Field` with the path to that field.
+///
+/// # Example
+///
+/// ```rust
+/// #[derive(Debug, dbus_pure_macros::FromVariant)]
+/// struct Device {
+///     name: String,
+///     addresses: Vec<u32>,
+/// }
+///
+/// let variant = dbus_pure_macros::variant!(("hci0", [1u32, 2u32]));
+/// let device: Device = dbus_pure::proto::FromVariant::from_variant(&variant).unwrap();
+/// assert_eq!(device.name, "hci0");
+/// assert_eq!(device.addresses, [1, 2]);
+///
+/// let variant = dbus_pure_macros::variant!([("hci0", [1i32, 2i32])]);
+/// let err = <Vec<Device> as dbus_pure::proto::FromVariant>::from_variant(&variant).unwrap_err();
+/// assert_eq!(err.to_string(), "could not convert field [0].addresses[0]");
+/// ```
+#[proc_macro_derive(FromVariant, attributes(dbus))]
+pub fn from_variant(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	run(from_variant::run(input))
+}
@@ -0,0 +1,62 @@
+use super::ResultExt;
+
+pub(super) fn run(input: proc_macro::TokenStream) -> Result<proc_macro2::TokenStream, syn::Error> {
+	let lit: syn::LitStr = syn::parse(input)?;
+
+	let signature: dbus_pure_proto::Signature = lit.value().parse().spanning(&lit)?;
+
+	Ok(to_tokens(&signature))
+}
+
+pub(super) fn to_tokens(signature: &dbus_pure_proto::Signature) -> proc_macro2::TokenStream {
+	match signature {
+		dbus_pure_proto::Signature::Array { element } => {
+			let element = to_tokens(element);
+			quote::quote! { dbus_pure::proto::Signature::Array { element: Box::new(#element) } }
+		},
+
+		dbus_pure_proto::Signature::Bool => quote::quote! { dbus_pure::proto::Signature::Bool },
+
+		dbus_pure_proto::Signature::DictEntry { key, value } => {
+			let key = to_tokens(key);
+			let value = to_tokens(value);
+			quote::quote! { dbus_pure::proto::Signature::DictEntry { key: Box::new(#key), value: Box::new(#value) } }
+		},
+
+		dbus_pure_proto::Signature::F64 => quote::quote! { dbus_pure::proto::Signature::F64 },
+
+		dbus_pure_proto::Signature::I16 => quote::quote! { dbus_pure::proto::Signature::I16 },
+
+		dbus_pure_proto::Signature::I32 => quote::quote! { dbus_pure::proto::Signature::I32 },
+
+		dbus_pure_proto::Signature::I64 => quote::quote! { dbus_pure::proto::Signature::I64 },
+
+		dbus_pure_proto::Signature::ObjectPath => quote::quote! { dbus_pure::proto::Signature::ObjectPath },
+
+		dbus_pure_proto::Signature::Signature => quote::quote! { dbus_pure::proto::Signature::Signature },
+
+		dbus_pure_proto::Signature::String => quote::quote! { dbus_pure::proto::Signature::String },
+
+		dbus_pure_proto::Signature::Struct { fields } => {
+			let fields = fields.iter().map(to_tokens);
+			quote::quote! { dbus_pure::proto::Signature::Struct { fields: vec![#(#fields ,)*] } }
+		},
+
+		dbus_pure_proto::Signature::Tuple { elements } => {
+			let elements = elements.iter().map(to_tokens);
+			quote::quote! { dbus_pure::proto::Signature::Tuple { elements: vec![#(#elements ,)*] } }
+		},
+
+		dbus_pure_proto::Signature::U8 => quote::quote! { dbus_pure::proto::Signature::U8 },
+
+		dbus_pure_proto::Signature::U16 => quote::quote! { dbus_pure::proto::Signature::U16 },
+
+		dbus_pure_proto::Signature::U32 => quote::quote! { dbus_pure::proto::Signature::U32 },
+
+		dbus_pure_proto::Signature::U64 => quote::quote! { dbus_pure::proto::Signature::U64 },
+
+		dbus_pure_proto::Signature::UnixFd => quote::quote! { dbus_pure::proto::Signature::UnixFd },
+
+		dbus_pure_proto::Signature::Variant => quote::quote! { dbus_pure::proto::Signature::Variant },
+	}
+}
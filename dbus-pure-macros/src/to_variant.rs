@@ -8,19 +8,47 @@ pub(super) fn run(input: proc_macro::TokenStream) -> Result<proc_macro2::TokenSt
 	let input: syn::DeriveInput = syn::parse2(input)?;
 
 	let ident = &input.ident;
-	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+	let struct_fields = match &input.data {
+		syn::Data::Struct(syn::DataStruct { fields, .. }) => super::field::parse(fields)?,
+		syn::Data::Enum(_) | syn::Data::Union(_) => vec![],
+	};
+
+	let generics = super::field::with_bounds(&input.generics, &struct_fields, &quote::quote! { dbus_pure::proto::ToVariant });
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+	let (signature_body, to_variant_body) = match &input.data {
+		syn::Data::Struct(syn::DataStruct { fields: fields @ syn::Fields::Unnamed(_), .. }) if fields.len() == 1 => {
+			// Delegate to the wrapped type's impl
 
-	let (signature_body, to_variant_body) = match input.data {
-		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(syn::FieldsNamed { named: fields, .. }), .. }) => {
+			let fields = &struct_fields;
+			let field = &fields[0];
+			if field.skip {
+				return Err("#[derive(ToVariant)] cannot skip the only field of a tuple struct").spanning(&tokens);
+			}
+
+			(
+				field.signature_tokens(),
+				field.to_variant_tokens(&quote::quote! { &self.0 }),
+			)
+		},
+
+		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Unnamed(syn::FieldsUnnamed { unnamed: fields, .. }), .. }) if fields.is_empty() =>
+			return Err("#[derive(ToVariant)] cannot be used on tuple structs with no fields").spanning(&tokens),
+
+		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(_) | syn::Fields::Unnamed(_), .. }) => {
 			// Variant::Struct
 
-			let fields_signature =
-				fields.iter()
-				.map(|syn::Field { ty, .. }| quote::quote! { <#ty as dbus_pure::proto::ToVariant>::signature() });
+			let fields = &struct_fields;
+			let fields: Vec<_> = fields.iter().filter(|field| !field.skip).collect();
+
+			let fields_signature = fields.iter().map(|field| field.signature_tokens());
 
 			let fields_to_variant =
 				fields.iter()
-				.map(|syn::Field { ident, .. }| quote::quote! { <_ as dbus_pure::proto::ToVariant>::to_variant(&self.#ident) });
+				.map(|field| {
+					let member = &field.member;
+					field.to_variant_tokens(&quote::quote! { &self.#member })
+				});
 
 			(
 				quote::quote! {
@@ -36,29 +64,31 @@ pub(super) fn run(input: proc_macro::TokenStream) -> Result<proc_macro2::TokenSt
 			)
 		},
 
-		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Unnamed(syn::FieldsUnnamed { unnamed: fields, .. }), .. }) if fields.len() == 1 => {
-			// Delegate to the wrapped type's impl
+		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Unit, .. }) =>
+			return Err("#[derive(ToVariant)] cannot be used on unit structs").spanning(&tokens),
 
-			let syn::Field { ty, .. } = fields.into_iter().next().unwrap();
+		syn::Data::Enum(data) => {
+			// Variant::String or Variant::U32
+
+			let data = super::enum_repr::parse("ToVariant", &input.attrs, data, &tokens)?;
+
+			let variants_to_variant = data.variants.iter().map(|(ident, value)| match data.repr {
+				super::enum_repr::Repr::String => quote::quote! { Self::#ident => dbus_pure::proto::Variant::String(#value.into()) },
+				super::enum_repr::Repr::U32 => quote::quote! { Self::#ident => dbus_pure::proto::Variant::U32(#value) },
+			});
 
 			(
+				data.signature(),
 				quote::quote! {
-					<#ty as dbus_pure::proto::ToVariant>::signature()
-				},
-				quote::quote! {
-					<dbus_pure::proto::ToVariant>::to_variant(&self.0)
+					match self {
+						#(#variants_to_variant ,)*
+					}
 				},
 			)
 		},
 
-		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Unnamed(..), .. }) =>
-			return Err("#[derive(ToVariant)] cannot be used on tuple structs with one field").spanning(&tokens),
-
-		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Unit, .. }) =>
-			return Err("#[derive(ToVariant)] cannot be used on unit structs").spanning(&tokens),
-
-		syn::Data::Enum(_) | syn::Data::Union(_) =>
-			return Err("#[derive(ToVariant)] can only be used on structs").spanning(&tokens),
+		syn::Data::Union(_) =>
+			return Err("#[derive(ToVariant)] can only be used on structs and enums").spanning(&tokens),
 	};
 
 	let result = quote::quote! {
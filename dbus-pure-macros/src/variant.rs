@@ -0,0 +1,293 @@
+use super::ResultExt;
+
+pub(super) fn run(input: proc_macro::TokenStream) -> Result<proc_macro2::TokenStream, syn::Error> {
+	let value: Value = syn::parse(input)?;
+
+	let (tokens, _) = value.to_variant_tokens()?;
+	Ok(tokens)
+}
+
+enum Value {
+	/// `{ "key": value, ... }`
+	Dict {
+		brace: syn::token::Brace,
+		entries: syn::punctuated::Punctuated<DictEntry, syn::Token![,]>,
+	},
+
+	/// `[value, ...]`
+	Array {
+		bracket: syn::token::Bracket,
+		elements: syn::punctuated::Punctuated<Value, syn::Token![,]>,
+	},
+
+	/// `(value, ...)`
+	Struct {
+		paren: syn::token::Paren,
+		fields: syn::punctuated::Punctuated<Value, syn::Token![,]>,
+	},
+
+	/// `5u32`, `-5i32`, `"/obj" as path`, etc
+	Lit {
+		neg: Option<syn::Token![-]>,
+		lit: syn::Lit,
+		cast: Option<syn::Ident>,
+	},
+}
+
+struct DictEntry {
+	key: syn::LitStr,
+	value: Value,
+}
+
+impl syn::parse::Parse for Value {
+	fn parse(input: syn::parse::ParseStream<'_>) -> syn::Result<Self> {
+		if input.peek(syn::token::Brace) {
+			let content;
+			let brace = syn::braced!(content in input);
+			let entries = content.parse_terminated(syn::parse::Parse::parse, syn::Token![,])?;
+			Ok(Value::Dict { brace, entries })
+		}
+		else if input.peek(syn::token::Bracket) {
+			let content;
+			let bracket = syn::bracketed!(content in input);
+			let elements = content.parse_terminated(syn::parse::Parse::parse, syn::Token![,])?;
+			Ok(Value::Array { bracket, elements })
+		}
+		else if input.peek(syn::token::Paren) {
+			let content;
+			let paren = syn::parenthesized!(content in input);
+			let fields = content.parse_terminated(syn::parse::Parse::parse, syn::Token![,])?;
+			Ok(Value::Struct { paren, fields })
+		}
+		else {
+			let neg = input.parse()?;
+			let lit = input.parse()?;
+			let cast =
+				if input.peek(syn::Token![as]) {
+					let _: syn::Token![as] = input.parse()?;
+					Some(input.parse()?)
+				}
+				else {
+					None
+				};
+			Ok(Value::Lit { neg, lit, cast })
+		}
+	}
+}
+
+impl syn::parse::Parse for DictEntry {
+	fn parse(input: syn::parse::ParseStream<'_>) -> syn::Result<Self> {
+		let key = input.parse()?;
+		let _: syn::Token![:] = input.parse()?;
+		let value = input.parse()?;
+		Ok(DictEntry { key, value })
+	}
+}
+
+impl Value {
+	/// Returns the expression that constructs the `Variant`, and the signature of that `Variant`.
+	fn to_variant_tokens(&self) -> Result<(proc_macro2::TokenStream, dbus_pure_proto::Signature), syn::Error> {
+		match self {
+			Value::Dict { brace: _, entries } => {
+				let mut keys = std::collections::BTreeSet::new();
+
+				let entries: Result<Vec<_>, syn::Error> =
+					entries.iter()
+					.map(|DictEntry { key, value }| {
+						if !keys.insert(key.value()) {
+							return Err("duplicate key in dict").spanning(key);
+						}
+
+						let (value, _) = value.to_variant_tokens()?;
+						Ok(quote::quote! {
+							dbus_pure::proto::Variant::DictEntry {
+								key: Box::new(dbus_pure::proto::Variant::String(#key.into())).into(),
+								value: Box::new(dbus_pure::proto::Variant::Variant(Box::new(#value).into())).into(),
+							}
+						})
+					})
+					.collect();
+				let entries = entries?;
+
+				let element_signature = dbus_pure_proto::Signature::DictEntry {
+					key: Box::new(dbus_pure_proto::Signature::String),
+					value: Box::new(dbus_pure_proto::Signature::Variant),
+				};
+				let element_signature_tokens = super::sig::to_tokens(&element_signature);
+
+				Ok((
+					quote::quote! {
+						dbus_pure::proto::Variant::Array {
+							element_signature: #element_signature_tokens,
+							elements: vec![#(#entries ,)*].into(),
+						}
+					},
+					dbus_pure_proto::Signature::Array { element: Box::new(element_signature) },
+				))
+			},
+
+			Value::Array { bracket, elements } => {
+				let mut element_signature = None;
+
+				let elements: Result<Vec<_>, syn::Error> =
+					elements.iter()
+					.map(|element| {
+						let (tokens, signature) = element.to_variant_tokens()?;
+						match &element_signature {
+							None => element_signature = Some(signature),
+							Some(element_signature) if *element_signature == signature => (),
+							Some(element_signature) =>
+								return Err(format!(r#"array element has signature "{signature}" but the first element has signature "{element_signature}""#))
+									.spanning(element),
+						}
+						Ok(tokens)
+					})
+					.collect();
+				let elements = elements?;
+
+				let Some(element_signature) = element_signature else {
+					return Err(syn::Error::new(bracket.span.join(), "cannot infer the element type of an empty array"));
+				};
+				let element_signature_tokens = super::sig::to_tokens(&element_signature);
+
+				Ok((
+					quote::quote! {
+						dbus_pure::proto::Variant::Array {
+							element_signature: #element_signature_tokens,
+							elements: vec![#(#elements ,)*].into(),
+						}
+					},
+					dbus_pure_proto::Signature::Array { element: Box::new(element_signature) },
+				))
+			},
+
+			Value::Struct { paren, fields } => {
+				if fields.is_empty() {
+					return Err(syn::Error::new(paren.span.join(), "struct must have at least one field"));
+				}
+
+				let fields: Result<Vec<_>, syn::Error> = fields.iter().map(Value::to_variant_tokens).collect();
+				let (fields, fields_signature): (Vec<_>, Vec<_>) = fields?.into_iter().unzip();
+
+				Ok((
+					quote::quote! {
+						dbus_pure::proto::Variant::Struct {
+							fields: vec![#(#fields ,)*].into(),
+						}
+					},
+					dbus_pure_proto::Signature::Struct { fields: fields_signature },
+				))
+			},
+
+			Value::Lit { neg, lit, cast } => lit_to_tokens(neg.as_ref(), lit, cast.as_ref()),
+		}
+	}
+}
+
+impl quote::ToTokens for Value {
+	fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+		match self {
+			Value::Dict { brace, entries } => brace.surround(tokens, |tokens| {
+				for pair in entries.pairs() {
+					let DictEntry { key, value } = pair.value();
+					key.to_tokens(tokens);
+					<syn::Token![:]>::default().to_tokens(tokens);
+					value.to_tokens(tokens);
+					pair.punct().to_tokens(tokens);
+				}
+			}),
+
+			Value::Array { bracket, elements } => bracket.surround(tokens, |tokens| elements.to_tokens(tokens)),
+
+			Value::Struct { paren, fields } => paren.surround(tokens, |tokens| fields.to_tokens(tokens)),
+
+			Value::Lit { neg, lit, cast } => {
+				neg.to_tokens(tokens);
+				lit.to_tokens(tokens);
+				if let Some(cast) = cast {
+					<syn::Token![as]>::default().to_tokens(tokens);
+					cast.to_tokens(tokens);
+				}
+			},
+		}
+	}
+}
+
+fn lit_to_tokens(
+	neg: Option<&syn::Token![-]>,
+	lit: &syn::Lit,
+	cast: Option<&syn::Ident>,
+) -> Result<(proc_macro2::TokenStream, dbus_pure_proto::Signature), syn::Error> {
+	if neg.is_some() && !matches!(lit, syn::Lit::Int(_) | syn::Lit::Float(_)) {
+		return Err("only numeric literals can be negated").spanning(neg);
+	}
+
+	let cast_name = cast.map(ToString::to_string);
+
+	let (variant, signature) = match (lit, cast_name.as_deref()) {
+		(syn::Lit::Bool(_), None) =>
+			(quote::quote! { Bool(#lit) }, dbus_pure_proto::Signature::Bool),
+
+		(syn::Lit::ByteStr(_), None) =>
+			(quote::quote! { ArrayU8((&#lit[..]).into()) }, dbus_pure_proto::Signature::Array { element: Box::new(dbus_pure_proto::Signature::U8) }),
+
+		(syn::Lit::Float(float), None) => match float.suffix() {
+			"" | "f64" => (quote::quote! { F64(#neg #lit) }, dbus_pure_proto::Signature::F64),
+			suffix => return Err(format!("unsupported float literal suffix {suffix:?}; D-Bus only has `f64`")).spanning(lit),
+		},
+
+		(syn::Lit::Int(int), None) => {
+			let (variant, signature) = match int.suffix() {
+				"" => return Err("ambiguous integer literal; add a suffix like `5u32` to choose its D-Bus type").spanning(lit),
+				"i16" => (quote::quote! { I16 }, dbus_pure_proto::Signature::I16),
+				"i32" => (quote::quote! { I32 }, dbus_pure_proto::Signature::I32),
+				"i64" => (quote::quote! { I64 }, dbus_pure_proto::Signature::I64),
+				"u8" => (quote::quote! { U8 }, dbus_pure_proto::Signature::U8),
+				"u16" => (quote::quote! { U16 }, dbus_pure_proto::Signature::U16),
+				"u32" => (quote::quote! { U32 }, dbus_pure_proto::Signature::U32),
+				"u64" => (quote::quote! { U64 }, dbus_pure_proto::Signature::U64),
+				suffix => return Err(format!("unsupported integer literal suffix {suffix:?}")).spanning(lit),
+			};
+			if neg.is_some() && int.suffix().starts_with('u') {
+				return Err("unsigned integer literals cannot be negated").spanning(quote::quote! { #neg #lit });
+			}
+			(quote::quote! { #variant(#neg #lit) }, signature)
+		},
+
+		(syn::Lit::Int(int), Some("unixfd")) => {
+			if neg.is_some() {
+				return Err("unix fds cannot be negative").spanning(quote::quote! { #neg #lit });
+			}
+			let fd: u32 = int.base10_parse()?;
+			(quote::quote! { UnixFd(dbus_pure::proto::UnixFd(#fd)) }, dbus_pure_proto::Signature::UnixFd)
+		},
+
+		(syn::Lit::Str(_), None) =>
+			(quote::quote! { String(#lit.into()) }, dbus_pure_proto::Signature::String),
+
+		(syn::Lit::Str(s), Some("path")) => {
+			let _ = dbus_pure_proto::ObjectPath::new(s.value()).spanning(lit)?;
+			(quote::quote! { ObjectPath(dbus_pure::proto::ObjectPath(#lit.into())) }, dbus_pure_proto::Signature::ObjectPath)
+		},
+
+		(syn::Lit::Str(s), Some("signature")) => {
+			let value: dbus_pure_proto::Signature = s.value().parse().spanning(lit)?;
+			let value = super::sig::to_tokens(&value);
+			(quote::quote! { Signature(#value) }, dbus_pure_proto::Signature::Signature)
+		},
+
+		(_, Some(cast @ ("path" | "signature"))) =>
+			return Err(format!("only string literals can be cast to `{cast}`")).spanning(lit),
+
+		(_, Some("unixfd")) =>
+			return Err("only integer literals can be cast to `unixfd`").spanning(lit),
+
+		(_, Some(_)) =>
+			return Err("unknown cast; expected `path`, `signature` or `unixfd`").spanning(cast),
+
+		(_, None) =>
+			return Err("unsupported literal").spanning(lit),
+	};
+
+	Ok((quote::quote! { dbus_pure::proto::Variant::#variant }, signature))
+}
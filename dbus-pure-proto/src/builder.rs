@@ -0,0 +1,383 @@
+//! Builders for the headers of messages to send.
+//!
+//! They take care of the details of [`crate::MessageHeader`] that are easy to get wrong, like not inserting the header fields
+//! that [`crate::serialize_message`] inserts itself, and they validate names and paths up front.
+
+/// A builder for a `METHOD_CALL` message.
+///
+/// ```rust
+/// let (header, body) =
+///     dbus_pure_proto::MethodCallBuilder::new(dbus_pure_proto::ObjectPath("/org/freedesktop/DBus".into()), "GetNameOwner")
+///     .destination("org.freedesktop.DBus")
+///     .interface("org.freedesktop.DBus")
+///     .body(dbus_pure_proto::Variant::String("org.freedesktop.Notifications".into()))
+///     .build()
+///     .unwrap();
+/// assert_eq!(header.member(), Some("GetNameOwner"));
+/// assert_eq!(header.destination(), Some("org.freedesktop.DBus"));
+/// ```
+#[derive(Debug)]
+pub struct MethodCallBuilder<'a> {
+	path: crate::ObjectPath<'a>,
+	member: std::borrow::Cow<'a, str>,
+	destination: Option<std::borrow::Cow<'a, str>>,
+	interface: Option<std::borrow::Cow<'a, str>>,
+	flags: crate::MessageFlags,
+	body: Option<crate::Variant<'a>>,
+}
+
+impl<'a> MethodCallBuilder<'a> {
+	/// Start building a call of the given method on the object with the given path.
+	pub fn new(path: crate::ObjectPath<'a>, member: impl Into<std::borrow::Cow<'a, str>>) -> Self {
+		MethodCallBuilder {
+			path,
+			member: member.into(),
+			destination: None,
+			interface: None,
+			flags: crate::message_flags::NONE,
+			body: None,
+		}
+	}
+
+	/// Set the bus name of the connection that the method call is sent to.
+	#[must_use]
+	pub fn destination(mut self, destination: impl Into<std::borrow::Cow<'a, str>>) -> Self {
+		self.destination = Some(destination.into());
+		self
+	}
+
+	/// Set the interface of the method.
+	#[must_use]
+	pub fn interface(mut self, interface: impl Into<std::borrow::Cow<'a, str>>) -> Self {
+		self.interface = Some(interface.into());
+		self
+	}
+
+	/// Set the message flags.
+	#[must_use]
+	pub fn flags(mut self, flags: crate::MessageFlags) -> Self {
+		self.flags = flags;
+		self
+	}
+
+	/// Set the message body, ie the parameters of the method call. Use a `Variant::Tuple` for multiple parameters.
+	#[must_use]
+	pub fn body(mut self, body: crate::Variant<'a>) -> Self {
+		self.body = Some(body);
+		self
+	}
+
+	/// Validate the names and path, and build the message header and body.
+	pub fn build(self) -> Result<(crate::MessageHeader<'a>, Option<crate::Variant<'a>>), BuildMessageError> {
+		validate_path(&self.path)?;
+		validate_name("member", &self.member, crate::validate::validate_member_name)?;
+
+		let mut fields = vec![];
+
+		if let Some(destination) = self.destination {
+			validate_name("destination", &destination, crate::validate::validate_bus_name)?;
+			fields.push(crate::MessageHeaderField::Destination(destination));
+		}
+
+		if let Some(interface) = self.interface {
+			validate_name("interface", &interface, crate::validate::validate_interface_name)?;
+			fields.push(crate::MessageHeaderField::Interface(interface));
+		}
+
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::MethodCall { member: self.member, path: self.path },
+			flags: self.flags,
+			body_len: 0,
+			serial: 0,
+			fields: fields.into(),
+		};
+		Ok((header, self.body))
+	}
+}
+
+/// A builder for a `SIGNAL` message.
+///
+/// ```rust
+/// let (header, body) =
+///     dbus_pure_proto::SignalBuilder::new(dbus_pure_proto::ObjectPath("/org/example/Player".into()), "org.example.Player", "Seeked")
+///     .body(dbus_pure_proto::Variant::I64(5_000_000))
+///     .build()
+///     .unwrap();
+/// assert_eq!(header.interface(), Some("org.example.Player"));
+/// assert_eq!(header.destination(), None);
+/// ```
+#[derive(Debug)]
+pub struct SignalBuilder<'a> {
+	path: crate::ObjectPath<'a>,
+	interface: std::borrow::Cow<'a, str>,
+	member: std::borrow::Cow<'a, str>,
+	destination: Option<std::borrow::Cow<'a, str>>,
+	flags: crate::MessageFlags,
+	body: Option<crate::Variant<'a>>,
+}
+
+impl<'a> SignalBuilder<'a> {
+	/// Start building a signal with the given interface and name, emitted by the object with the given path.
+	pub fn new(
+		path: crate::ObjectPath<'a>,
+		interface: impl Into<std::borrow::Cow<'a, str>>,
+		member: impl Into<std::borrow::Cow<'a, str>>,
+	) -> Self {
+		SignalBuilder {
+			path,
+			interface: interface.into(),
+			member: member.into(),
+			destination: None,
+			// Signals never have replies.
+			flags: crate::message_flags::NO_REPLY_EXPECTED,
+			body: None,
+		}
+	}
+
+	/// Set the bus name of the connection that the signal is sent to. By default the signal is broadcast.
+	#[must_use]
+	pub fn destination(mut self, destination: impl Into<std::borrow::Cow<'a, str>>) -> Self {
+		self.destination = Some(destination.into());
+		self
+	}
+
+	/// Set the message flags.
+	#[must_use]
+	pub fn flags(mut self, flags: crate::MessageFlags) -> Self {
+		self.flags = flags;
+		self
+	}
+
+	/// Set the message body, ie the arguments of the signal. Use a `Variant::Tuple` for multiple arguments.
+	#[must_use]
+	pub fn body(mut self, body: crate::Variant<'a>) -> Self {
+		self.body = Some(body);
+		self
+	}
+
+	/// Validate the names and path, and build the message header and body.
+	pub fn build(self) -> Result<(crate::MessageHeader<'a>, Option<crate::Variant<'a>>), BuildMessageError> {
+		validate_path(&self.path)?;
+		validate_name("interface", &self.interface, crate::validate::validate_interface_name)?;
+		validate_name("member", &self.member, crate::validate::validate_member_name)?;
+
+		let mut fields = vec![];
+
+		if let Some(destination) = self.destination {
+			validate_name("destination", &destination, crate::validate::validate_bus_name)?;
+			fields.push(crate::MessageHeaderField::Destination(destination));
+		}
+
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::Signal { interface: self.interface, member: self.member, path: self.path },
+			flags: self.flags,
+			body_len: 0,
+			serial: 0,
+			fields: fields.into(),
+		};
+		Ok((header, self.body))
+	}
+}
+
+/// A builder for an `ERROR` message in reply to a method call.
+///
+/// ```rust
+/// # let request = dbus_pure_proto::MessageHeader {
+/// #     r#type: dbus_pure_proto::MessageType::MethodCall { member: "Play".into(), path: dbus_pure_proto::ObjectPath("/org/example/Player".into()) },
+/// #     flags: dbus_pure_proto::message_flags::NONE,
+/// #     body_len: 0,
+/// #     serial: 5,
+/// #     fields: vec![dbus_pure_proto::MessageHeaderField::Sender(":1.7".into())].into(),
+/// # };
+/// let (header, body) =
+///     dbus_pure_proto::ErrorBuilder::new("org.example.Player.Error.NoTrack", &request)
+///     .body(dbus_pure_proto::Variant::String("no track is loaded".into()))
+///     .build()
+///     .unwrap();
+/// assert_eq!(header.reply_serial(), Some(5));
+/// assert_eq!(header.destination(), Some(":1.7"));
+/// ```
+#[derive(Debug)]
+pub struct ErrorBuilder<'a> {
+	name: std::borrow::Cow<'a, str>,
+	reply_serial: u32,
+	destination: Option<std::borrow::Cow<'a, str>>,
+	flags: crate::MessageFlags,
+	body: Option<crate::Variant<'a>>,
+}
+
+impl<'a> ErrorBuilder<'a> {
+	/// Start building an error with the given name in reply to the given method call.
+	///
+	/// The error is sent to the sender of the method call, if the method call has a sender.
+	pub fn new(name: impl Into<std::borrow::Cow<'a, str>>, reply_to: &crate::MessageHeader<'_>) -> Self {
+		ErrorBuilder {
+			name: name.into(),
+			reply_serial: reply_to.serial,
+			destination: reply_to.sender().map(|sender| sender.to_owned().into()),
+			flags: crate::message_flags::NO_REPLY_EXPECTED,
+			body: None,
+		}
+	}
+
+	/// Set the message body. Conventionally this is a string that describes the error.
+	#[must_use]
+	pub fn body(mut self, body: crate::Variant<'a>) -> Self {
+		self.body = Some(body);
+		self
+	}
+
+	/// Set the message flags.
+	#[must_use]
+	pub fn flags(mut self, flags: crate::MessageFlags) -> Self {
+		self.flags = flags;
+		self
+	}
+
+	/// Validate the error name, and build the message header and body.
+	pub fn build(self) -> Result<(crate::MessageHeader<'a>, Option<crate::Variant<'a>>), BuildMessageError> {
+		validate_name("name", &self.name, crate::validate::validate_error_name)?;
+
+		let mut fields = vec![];
+
+		if let Some(destination) = self.destination {
+			validate_name("destination", &destination, crate::validate::validate_bus_name)?;
+			fields.push(crate::MessageHeaderField::Destination(destination));
+		}
+
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::Error { name: self.name, reply_serial: self.reply_serial },
+			flags: self.flags,
+			body_len: 0,
+			serial: 0,
+			fields: fields.into(),
+		};
+		Ok((header, self.body))
+	}
+}
+
+fn validate_name(
+	field: &'static str,
+	name: &str,
+	validate: fn(&str) -> Result<(), crate::validate::InvalidName>,
+) -> Result<(), BuildMessageError> {
+	validate(name).map_err(|reason| BuildMessageError::InvalidName { field, reason })
+}
+
+fn validate_path(path: &crate::ObjectPath<'_>) -> Result<(), BuildMessageError> {
+	crate::validate::validate_object_path(&path.0).map_err(BuildMessageError::InvalidPath)
+}
+
+/// An error from building a message.
+#[derive(Debug)]
+pub enum BuildMessageError {
+	/// The given field is not a valid name.
+	InvalidName { field: &'static str, reason: crate::validate::InvalidName },
+
+	InvalidPath(crate::InvalidObjectPath),
+}
+
+impl std::fmt::Display for BuildMessageError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			BuildMessageError::InvalidName { field, reason: _ } => write!(f, "{field} is not a valid name"),
+			BuildMessageError::InvalidPath(_) => f.write_str("path is not a valid object path"),
+		}
+	}
+}
+
+impl std::error::Error for BuildMessageError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			BuildMessageError::InvalidName { field: _, reason } => Some(reason),
+			BuildMessageError::InvalidPath(err) => Some(err),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_build() {
+		let (mut header, body) =
+			super::MethodCallBuilder::new(crate::ObjectPath("/org/freedesktop/DBus".into()), "ListNames")
+			.destination("org.freedesktop.DBus")
+			.interface("org.freedesktop.DBus")
+			.flags(crate::message_flags::NO_AUTO_START)
+			.build()
+			.unwrap();
+		assert_eq!(header, crate::MessageHeader {
+			r#type: crate::MessageType::MethodCall { member: "ListNames".into(), path: crate::ObjectPath("/org/freedesktop/DBus".into()) },
+			flags: crate::message_flags::NO_AUTO_START,
+			body_len: 0,
+			serial: 0,
+			fields: vec![
+				crate::MessageHeaderField::Destination("org.freedesktop.DBus".into()),
+				crate::MessageHeaderField::Interface("org.freedesktop.DBus".into()),
+			].into(),
+		});
+		assert_eq!(body, None);
+
+		// The built message can be serialized as-is.
+		header.serial = 1;
+		let mut buf = vec![];
+		crate::serialize_message(&header, body.as_ref(), &mut buf, crate::Endianness::Little).unwrap();
+		let (request, _, _) = crate::deserialize_message(&buf).unwrap();
+		assert_eq!(request.member(), Some("ListNames"));
+		assert_eq!(request.interface(), Some("org.freedesktop.DBus"));
+
+		let (header, body) =
+			super::SignalBuilder::new(crate::ObjectPath("/org/example".into()), "org.example.Foo", "Changed")
+			.body(crate::Variant::U32(5))
+			.build()
+			.unwrap();
+		assert_eq!(header, crate::MessageHeader {
+			r#type: crate::MessageType::Signal { interface: "org.example.Foo".into(), member: "Changed".into(), path: crate::ObjectPath("/org/example".into()) },
+			flags: crate::message_flags::NO_REPLY_EXPECTED,
+			body_len: 0,
+			serial: 0,
+			fields: vec![].into(),
+		});
+		assert_eq!(body, Some(crate::Variant::U32(5)));
+
+		let request = crate::MessageHeader {
+			fields: vec![crate::MessageHeaderField::Sender(":1.5".into())].into(),
+			serial: 1,
+			..request.into_owned()
+		};
+		let (header, body) = super::ErrorBuilder::new("org.example.Error.Failed", &request).build().unwrap();
+		assert_eq!(header, crate::MessageHeader {
+			r#type: crate::MessageType::Error { name: "org.example.Error.Failed".into(), reply_serial: 1 },
+			flags: crate::message_flags::NO_REPLY_EXPECTED,
+			body_len: 0,
+			serial: 0,
+			fields: vec![crate::MessageHeaderField::Destination(":1.5".into())].into(),
+		});
+		assert_eq!(body, None);
+	}
+
+	#[test]
+	fn test_build_invalid() {
+		let err = super::MethodCallBuilder::new(crate::ObjectPath("/org/example".into()), "Foo.Bar").build().unwrap_err();
+		assert!(matches!(
+			err,
+			super::BuildMessageError::InvalidName { field: "member", reason: crate::validate::InvalidName::InvalidCharacter { character: '.', offset: 3 } },
+		), "{err:?}");
+
+		let err = super::MethodCallBuilder::new(crate::ObjectPath("org/example".into()), "Foo").build().unwrap_err();
+		assert!(matches!(err, super::BuildMessageError::InvalidPath(crate::InvalidObjectPath::MissingLeadingSlash)), "{err:?}");
+
+		let err = super::SignalBuilder::new(crate::ObjectPath("/org/example".into()), "Foo", "Changed").build().unwrap_err();
+		assert!(matches!(err, super::BuildMessageError::InvalidName { field: "interface", reason: crate::validate::InvalidName::TooFewElements }), "{err:?}");
+
+		let request = crate::MessageHeader {
+			r#type: crate::MessageType::MethodCall { member: "Foo".into(), path: crate::ObjectPath("/".into()) },
+			flags: crate::message_flags::NONE,
+			body_len: 0,
+			serial: 1,
+			fields: vec![].into(),
+		};
+		let err = super::ErrorBuilder::new("Failed", &request).build().unwrap_err();
+		assert!(matches!(err, super::BuildMessageError::InvalidName { field: "name", reason: crate::validate::InvalidName::TooFewElements }), "{err:?}");
+	}
+}
@@ -3,6 +3,7 @@ pub(crate) struct Deserializer<'de> {
 	buf: &'de [u8],
 	pos: usize,
 	endianness: crate::Endianness,
+	max_array_len: usize,
 }
 
 impl<'de> Deserializer<'de> {
@@ -11,6 +12,7 @@ impl<'de> Deserializer<'de> {
 			buf,
 			pos,
 			endianness,
+			max_array_len: crate::MessageLimits::SPEC.max_array_len,
 		}
 	}
 
@@ -40,40 +42,71 @@ impl<'de> Deserializer<'de> {
 		self.endianness = endianness;
 	}
 
+	pub(crate) fn set_max_array_len(&mut self, max_array_len: usize) {
+		self.max_array_len = max_array_len;
+	}
+
+	fn deserialize_array_len(&mut self) -> Result<usize, DeserializeError> {
+		let data_len = self.deserialize_u32()?;
+		let data_len: usize = data_len.try_into().map_err(crate::DeserializeError::ExceedsNumericLimits)?;
+		if data_len > self.max_array_len {
+			return Err(DeserializeError::ArrayTooLong { len: data_len, max: self.max_array_len });
+		}
+		Ok(data_len)
+	}
+
 	pub(crate) fn deserialize_array<T>(
 		&mut self,
 		element_alignment: usize,
 		mut f: impl FnMut(&mut Deserializer<'de>) -> Result<T, DeserializeError>,
 	) -> Result<Vec<T>, DeserializeError> {
-		let data_len = self.deserialize_u32()?;
-		let data_len: usize = data_len.try_into().map_err(crate::DeserializeError::ExceedsNumericLimits)?;
+		let mut inner = self.deserialize_array_start(element_alignment)?;
+
+		let mut result = vec![];
+
+		while !inner.is_at_end() {
+			result.push(f(&mut inner)?);
+		}
+
+		self.deserialize_array_end(&inner);
+
+		Ok(result)
+	}
+
+	/// Start deserializing an array whose elements are deserialized one at a time by the caller.
+	///
+	/// Returns a deserializer for the elements that ends at the end of the array.
+	/// Once the caller is done with it, pass it to [`Deserializer::deserialize_array_end`].
+	pub(crate) fn deserialize_array_start(&mut self, element_alignment: usize) -> Result<Deserializer<'de>, DeserializeError> {
+		let data_len = self.deserialize_array_len()?;
 
 		self.pad_to(element_alignment)?;
 
-		let data_end_pos = self.pos + data_len;
+		let data_end_pos = self.pos.checked_add(data_len).ok_or(DeserializeError::LengthOverflow)?;
 
-		let mut inner = Deserializer {
+		Ok(Deserializer {
 			buf: self.buf.get(..data_end_pos).ok_or(DeserializeError::EndOfInput)?,
 			pos: self.pos,
 			endianness: self.endianness,
-		};
-
-		let mut result = vec![];
-
-		while inner.pos != inner.buf.len() {
-			result.push(f(&mut inner)?);
-		}
+			max_array_len: self.max_array_len,
+		})
+	}
 
-		self.pos = inner.pos;
+	/// Finish deserializing an array started with [`Deserializer::deserialize_array_start`].
+	///
+	/// Any elements that were not deserialized are skipped.
+	pub(crate) fn deserialize_array_end(&mut self, inner: &Deserializer<'de>) {
+		self.pos = inner.buf.len();
+	}
 
-		Ok(result)
+	pub(crate) fn is_at_end(&self) -> bool {
+		self.pos == self.buf.len()
 	}
 
 	pub(crate) fn deserialize_array_u8(&mut self) -> Result<&'de [u8], DeserializeError> {
-		let data_len = self.deserialize_u32()?;
-		let data_len: usize = data_len.try_into().map_err(crate::DeserializeError::ExceedsNumericLimits)?;
+		let data_len = self.deserialize_array_len()?;
 
-		let data_end_pos = self.pos + data_len;
+		let data_end_pos = self.pos.checked_add(data_len).ok_or(DeserializeError::LengthOverflow)?;
 
 		let result = self.buf.get(self.pos..data_end_pos).ok_or(DeserializeError::EndOfInput)?;
 
@@ -242,27 +275,54 @@ impl<'de> Deserializer<'de> {
 /// An error from deserializing a value using the D-Bus binary protocol.
 #[derive(Debug)]
 pub enum DeserializeError {
+	ArrayTooLong { len: usize, max: usize },
+	ContainerDepthExceeded,
+
+	/// An error raised by a [`serde::Deserialize`] impl while deserializing a value with [`crate::from_slice_with_signature`]
+	Custom(String),
+
+	DuplicateHeaderField { code: u8 },
 	EndOfInput,
 	ExceedsNumericLimits(std::num::TryFromIntError),
+	InvalidBody { signature: crate::Signature, offset: usize, err: Box<DeserializeError> },
+	InvalidHeader { offset: usize, field: Option<u8>, err: Box<DeserializeError> },
+	InvalidSignature { signature: String, err: crate::SignatureParseError },
 	InvalidUtf8(std::str::Utf8Error),
 	InvalidValue { expected: std::borrow::Cow<'static, str>, actual: String },
+	LengthOverflow,
+	MessageTooLong { len: usize, max: usize },
 	MissingRequiredMessageHeaderField { method_name: &'static str, header_field_name: &'static str },
 	NonZeroPadding { start: usize, end: usize },
 	StringMissingNulTerminator,
+	TrailingBodyBytes { unread: usize },
 }
 
 impl std::fmt::Display for DeserializeError {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		#[allow(clippy::match_same_arms)]
 		match self {
+			DeserializeError::ArrayTooLong { len, max } => write!(f, "array is {len} bytes long which exceeds the maximum of {max} bytes"),
+			DeserializeError::ContainerDepthExceeded =>
+				write!(f, "value exceeds the maximum container nesting depth of {}", crate::Variant::MAX_DEPTH),
+			DeserializeError::Custom(message) => f.write_str(message),
+			DeserializeError::DuplicateHeaderField { code } => write!(f, "message header contains header field {code} more than once"),
 			DeserializeError::EndOfInput => f.write_str("end of input"),
 			DeserializeError::ExceedsNumericLimits(_) => f.write_str("value exceeds numeric limits"),
+			DeserializeError::InvalidBody { signature, offset, err: _ } =>
+				write!(f, r#"could not deserialize message body with signature "{signature}" at offset {offset}"#),
+			DeserializeError::InvalidHeader { offset, field: Some(field), err: _ } =>
+				write!(f, "could not deserialize header field {field} of message header at offset {offset}"),
+			DeserializeError::InvalidHeader { offset, field: None, err: _ } => write!(f, "could not deserialize message header at offset {offset}"),
+			DeserializeError::InvalidSignature { signature, err: _ } => write!(f, "{signature:?} is not a valid signature"),
 			DeserializeError::InvalidUtf8(_) => f.write_str("deserialized string is not valid UTF-8"),
 			DeserializeError::InvalidValue { expected, actual } => write!(f, "expected {expected} but got {actual}"),
+			DeserializeError::LengthOverflow => f.write_str("length exceeds the addressable size of the input"),
+			DeserializeError::MessageTooLong { len, max } => write!(f, "message is {len} bytes long which exceeds the maximum of {max} bytes"),
 			DeserializeError::MissingRequiredMessageHeaderField { method_name, header_field_name } =>
 				write!(f, "{method_name} message is missing {header_field_name} required header field"),
 			DeserializeError::NonZeroPadding { start, end } => write!(f, "padding contains a byte other than 0x00 between positions {start} and {end}"),
 			DeserializeError::StringMissingNulTerminator => f.write_str("deserialized string is not nul-terminated"),
+			DeserializeError::TrailingBodyBytes { unread } => write!(f, "message body has {unread} bytes left over after deserializing it according to its signature"),
 		}
 	}
 }
@@ -271,13 +331,29 @@ impl std::error::Error for DeserializeError {
 	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
 		#[allow(clippy::match_same_arms)]
 		match self {
+			DeserializeError::ArrayTooLong { len: _, max: _ } => None,
+			DeserializeError::ContainerDepthExceeded => None,
+			DeserializeError::Custom(_) => None,
+			DeserializeError::DuplicateHeaderField { code: _ } => None,
 			DeserializeError::EndOfInput => None,
 			DeserializeError::ExceedsNumericLimits(err) => Some(err),
+			DeserializeError::InvalidBody { signature: _, offset: _, err } => Some(&**err),
+			DeserializeError::InvalidHeader { offset: _, field: _, err } => Some(&**err),
+			DeserializeError::InvalidSignature { signature: _, err } => Some(err),
 			DeserializeError::InvalidUtf8(err) => Some(err),
 			DeserializeError::InvalidValue { expected: _, actual: _ } => None,
+			DeserializeError::LengthOverflow => None,
+			DeserializeError::MessageTooLong { len: _, max: _ } => None,
 			DeserializeError::MissingRequiredMessageHeaderField { method_name: _, header_field_name: _ } => None,
 			DeserializeError::NonZeroPadding { start: _, end: _ } => None,
 			DeserializeError::StringMissingNulTerminator => None,
+			DeserializeError::TrailingBodyBytes { unread: _ } => None,
 		}
 	}
 }
+
+impl serde::de::Error for DeserializeError {
+	fn custom<T>(msg: T) -> Self where T: std::fmt::Display {
+		DeserializeError::Custom(msg.to_string())
+	}
+}
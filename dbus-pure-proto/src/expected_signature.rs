@@ -0,0 +1,44 @@
+/// A trait for the types that the body of a method response is deserialized into, to check the signature of the body before deserializing it.
+///
+/// This is implemented with the same signature for every type that implements [`crate::ToVariant`], and for `Option<T>` with the signature of `T`.
+/// A type that can only be deserialized with serde can implement it with the default [`ExpectedSignature::expected_signature`]
+/// to skip the check.
+pub trait ExpectedSignature {
+	/// The signature that a body must have to be deserialized into this type, or `None` to accept a body of any signature.
+	fn expected_signature() -> Option<crate::Signature> {
+		None
+	}
+}
+
+impl<T> ExpectedSignature for T where T: crate::ToVariant + ?Sized {
+	fn expected_signature() -> Option<crate::Signature> {
+		Some(<T as crate::ToVariant>::signature())
+	}
+}
+
+/// `None` is deserialized from a response without a body, which doesn't have a signature to check.
+impl<T> ExpectedSignature for Option<T> where T: ExpectedSignature {
+	fn expected_signature() -> Option<crate::Signature> {
+		<T as ExpectedSignature>::expected_signature()
+	}
+}
+
+/// A variant holds a body of any signature as-is.
+impl ExpectedSignature for crate::Variant<'_> {}
+
+#[cfg(test)]
+mod tests {
+	use super::ExpectedSignature;
+
+	#[test]
+	fn test_expected_signature() {
+		struct Custom;
+		impl ExpectedSignature for Custom {}
+
+		assert_eq!(u32::expected_signature(), Some(crate::Signature::U32));
+		assert_eq!(<Option<Vec<String>>>::expected_signature(), Some(crate::Signature::Array { element: Box::new(crate::Signature::String) }));
+		assert_eq!(<()>::expected_signature(), Some(crate::Signature::Tuple { elements: vec![] }));
+		assert_eq!(crate::Variant::expected_signature(), None);
+		assert_eq!(Custom::expected_signature(), None);
+	}
+}
@@ -0,0 +1,470 @@
+//! `TryFrom<Variant>` conversions and the [`FromVariant`] trait to convert `Variant`s to Rust types.
+//!
+//! All the conversions unwrap one level of [`crate::Variant::Variant`], so that a property value of type `v`
+//! can be converted directly to the type it holds.
+
+/// A trait to convert a [`crate::Variant`] to a Rust value. This is the converse of [`crate::ToVariant`].
+///
+/// This is useful to convert a message body into this type.
+///
+/// Consider using `#[derive(dbus_pure_macros::FromVariant)]` to implement this trait for your custom struct types.
+pub trait FromVariant: Sized {
+	/// Convert the given variant to a value of this type.
+	fn from_variant(variant: &crate::Variant<'_>) -> Result<Self, FromVariantError>;
+}
+
+/// An error from converting a [`crate::Variant`] to a Rust value.
+#[derive(Debug)]
+pub enum FromVariantError {
+	/// Converting a field or element of the variant failed. `path` is the path to the field from the outermost value, like `devices[2].name`
+	Field { path: String, err: Box<FromVariantError> },
+
+	InvalidValue { expected: std::borrow::Cow<'static, str>, actual: String },
+
+	SignatureMismatch { expected: crate::Signature, actual: crate::Signature },
+}
+
+impl FromVariantError {
+	/// Wrap this error to indicate that it happened while converting the field with the given name, or the element with the given index.
+	///
+	/// Pass names like `name` for struct fields and `[2]` for elements, so that the path of nested errors reads like `devices[2].name`
+	#[must_use]
+	pub fn in_field(self, name: impl std::fmt::Display) -> Self {
+		match self {
+			FromVariantError::Field { path, err } => {
+				let separator = if path.starts_with('[') { "" } else { "." };
+				FromVariantError::Field { path: format!("{name}{separator}{path}"), err }
+			},
+
+			err => FromVariantError::Field { path: name.to_string(), err: Box::new(err) },
+		}
+	}
+}
+
+impl std::fmt::Display for FromVariantError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			FromVariantError::Field { path, err: _ } => write!(f, "could not convert field {path}"),
+			FromVariantError::InvalidValue { expected, actual } => write!(f, "expected {expected} but got {actual}"),
+			FromVariantError::SignatureMismatch { expected, actual } =>
+				write!(f, r#"expected variant with signature "{expected}" but got "{actual}""#),
+		}
+	}
+}
+
+impl std::error::Error for FromVariantError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		#[allow(clippy::match_same_arms)]
+		match self {
+			FromVariantError::Field { path: _, err } => Some(err),
+			FromVariantError::InvalidValue { expected: _, actual: _ } => None,
+			FromVariantError::SignatureMismatch { expected: _, actual: _ } => None,
+		}
+	}
+}
+
+fn signature_mismatch<T>(actual: &crate::Variant<'_>) -> FromVariantError where T: crate::ToVariant + ?Sized {
+	FromVariantError::SignatureMismatch { expected: T::signature(), actual: actual.signature() }
+}
+
+fn unwrap_variant<'b, 'a>(variant: &'b crate::Variant<'a>) -> &'b crate::Variant<'a> {
+	match variant {
+		crate::Variant::Variant(inner) => inner,
+		variant => variant,
+	}
+}
+
+fn into_unwrapped_variant(variant: crate::Variant<'_>) -> crate::Variant<'_> {
+	match variant {
+		crate::Variant::Variant(inner) => inner.into_owned(),
+		variant => variant,
+	}
+}
+
+macro_rules! try_from_variant_copy {
+	($($ty:ty => $variant:ident ,)*) => {
+		$(
+			impl TryFrom<crate::Variant<'_>> for $ty {
+				type Error = FromVariantError;
+
+				fn try_from(variant: crate::Variant<'_>) -> Result<Self, Self::Error> {
+					Self::try_from(&variant)
+				}
+			}
+
+			impl TryFrom<&crate::Variant<'_>> for $ty {
+				type Error = FromVariantError;
+
+				fn try_from(variant: &crate::Variant<'_>) -> Result<Self, Self::Error> {
+					match unwrap_variant(variant) {
+						crate::Variant::$variant(value) => Ok(*value),
+						variant => Err(signature_mismatch::<$ty>(variant)),
+					}
+				}
+			}
+		)*
+	};
+}
+
+try_from_variant_copy! {
+	bool => Bool,
+	f64 => F64,
+	i16 => I16,
+	i32 => I32,
+	i64 => I64,
+	u8 => U8,
+	u16 => U16,
+	u32 => U32,
+	u64 => U64,
+	crate::UnixFd => UnixFd,
+}
+
+impl<'a> TryFrom<crate::Variant<'a>> for std::borrow::Cow<'a, str> {
+	type Error = FromVariantError;
+
+	fn try_from(variant: crate::Variant<'a>) -> Result<Self, Self::Error> {
+		match into_unwrapped_variant(variant) {
+			crate::Variant::String(value) => Ok(value),
+			variant => Err(signature_mismatch::<str>(&variant)),
+		}
+	}
+}
+
+impl<'a> TryFrom<&crate::Variant<'a>> for std::borrow::Cow<'a, str> {
+	type Error = FromVariantError;
+
+	fn try_from(variant: &crate::Variant<'a>) -> Result<Self, Self::Error> {
+		match unwrap_variant(variant) {
+			crate::Variant::String(value) => Ok(value.clone()),
+			variant => Err(signature_mismatch::<str>(variant)),
+		}
+	}
+}
+
+impl<'a> TryFrom<crate::Variant<'a>> for crate::ObjectPath<'a> {
+	type Error = FromVariantError;
+
+	fn try_from(variant: crate::Variant<'a>) -> Result<Self, Self::Error> {
+		match into_unwrapped_variant(variant) {
+			crate::Variant::ObjectPath(value) => Ok(value),
+			variant => Err(signature_mismatch::<crate::ObjectPath<'_>>(&variant)),
+		}
+	}
+}
+
+impl<'a> TryFrom<&crate::Variant<'a>> for crate::ObjectPath<'a> {
+	type Error = FromVariantError;
+
+	fn try_from(variant: &crate::Variant<'a>) -> Result<Self, Self::Error> {
+		match unwrap_variant(variant) {
+			crate::Variant::ObjectPath(value) => Ok(value.clone()),
+			variant => Err(signature_mismatch::<crate::ObjectPath<'_>>(variant)),
+		}
+	}
+}
+
+impl TryFrom<crate::Variant<'_>> for crate::Signature {
+	type Error = FromVariantError;
+
+	fn try_from(variant: crate::Variant<'_>) -> Result<Self, Self::Error> {
+		match into_unwrapped_variant(variant) {
+			crate::Variant::Signature(value) => Ok(value),
+			variant => Err(signature_mismatch::<crate::Signature>(&variant)),
+		}
+	}
+}
+
+impl TryFrom<&crate::Variant<'_>> for crate::Signature {
+	type Error = FromVariantError;
+
+	fn try_from(variant: &crate::Variant<'_>) -> Result<Self, Self::Error> {
+		match unwrap_variant(variant) {
+			crate::Variant::Signature(value) => Ok(value.clone()),
+			variant => Err(signature_mismatch::<crate::Signature>(variant)),
+		}
+	}
+}
+
+impl TryFrom<crate::Variant<'_>> for String {
+	type Error = FromVariantError;
+
+	fn try_from(variant: crate::Variant<'_>) -> Result<Self, Self::Error> {
+		let value: std::borrow::Cow<'_, str> = variant.try_into()?;
+		Ok(value.into_owned())
+	}
+}
+
+impl TryFrom<&crate::Variant<'_>> for String {
+	type Error = FromVariantError;
+
+	fn try_from(variant: &crate::Variant<'_>) -> Result<Self, Self::Error> {
+		match unwrap_variant(variant) {
+			crate::Variant::String(value) => Ok((**value).to_owned()),
+			variant => Err(signature_mismatch::<str>(variant)),
+		}
+	}
+}
+
+// `Vec<u8>` and `Vec<String>` also accept the generic `Variant::Array` form, since that's what `ToVariant` creates for `Vec<String>`
+// and what hand-built variants may use.
+
+impl TryFrom<crate::Variant<'_>> for Vec<u8> {
+	type Error = FromVariantError;
+
+	fn try_from(variant: crate::Variant<'_>) -> Result<Self, Self::Error> {
+		match into_unwrapped_variant(variant) {
+			crate::Variant::ArrayU8(elements) => Ok(elements.into_owned()),
+			variant => Self::try_from(&variant),
+		}
+	}
+}
+
+impl TryFrom<&crate::Variant<'_>> for Vec<u8> {
+	type Error = FromVariantError;
+
+	fn try_from(variant: &crate::Variant<'_>) -> Result<Self, Self::Error> {
+		match unwrap_variant(variant) {
+			crate::Variant::ArrayU8(elements) => Ok(elements.to_vec()),
+
+			variant @ crate::Variant::Array { element_signature: crate::Signature::U8, elements } =>
+				elements.iter().map(|element| element.as_u8().ok_or_else(|| signature_mismatch::<Vec<u8>>(variant))).collect(),
+
+			variant => Err(signature_mismatch::<Vec<u8>>(variant)),
+		}
+	}
+}
+
+impl TryFrom<crate::Variant<'_>> for Vec<String> {
+	type Error = FromVariantError;
+
+	fn try_from(variant: crate::Variant<'_>) -> Result<Self, Self::Error> {
+		match into_unwrapped_variant(variant) {
+			crate::Variant::ArrayString(elements) => Ok(elements.iter().map(|element| (**element).to_owned()).collect()),
+			variant => Self::try_from(&variant),
+		}
+	}
+}
+
+impl TryFrom<&crate::Variant<'_>> for Vec<String> {
+	type Error = FromVariantError;
+
+	fn try_from(variant: &crate::Variant<'_>) -> Result<Self, Self::Error> {
+		match unwrap_variant(variant) {
+			crate::Variant::ArrayString(elements) => Ok(elements.iter().map(|element| (**element).to_owned()).collect()),
+
+			variant @ crate::Variant::Array { element_signature: crate::Signature::String, elements } =>
+				elements.iter().map(|element| element.as_string().map(ToOwned::to_owned).ok_or_else(|| signature_mismatch::<Vec<String>>(variant))).collect(),
+
+			variant => Err(signature_mismatch::<Vec<String>>(variant)),
+		}
+	}
+}
+
+macro_rules! from_variant_via_try_from {
+	($($ty:ty ,)*) => {
+		$(
+			impl FromVariant for $ty {
+				fn from_variant(variant: &crate::Variant<'_>) -> Result<Self, FromVariantError> {
+					variant.try_into()
+				}
+			}
+		)*
+	};
+}
+
+from_variant_via_try_from! {
+	bool,
+	f64,
+	i16,
+	i32,
+	i64,
+	u8,
+	u16,
+	u32,
+	u64,
+	crate::Signature,
+	crate::UnixFd,
+	String,
+}
+
+impl FromVariant for crate::ObjectPath<'static> {
+	fn from_variant(variant: &crate::Variant<'_>) -> Result<Self, FromVariantError> {
+		let path: crate::ObjectPath<'_> = variant.try_into()?;
+		Ok(path.into_owned())
+	}
+}
+
+/// Clones the variant as-is, without unwrapping a `Variant::Variant`
+impl FromVariant for crate::Variant<'static> {
+	fn from_variant(variant: &crate::Variant<'_>) -> Result<Self, FromVariantError> {
+		Ok(variant.clone().into_owned())
+	}
+}
+
+impl<T> FromVariant for Vec<T> where T: FromVariant {
+	fn from_variant(variant: &crate::Variant<'_>) -> Result<Self, FromVariantError> {
+		let variant = unwrap_variant(variant);
+		let elements = variant.iter_array().ok_or_else(|| FromVariantError::InvalidValue { expected: "array".into(), actual: variant.signature().to_string() })?;
+		elements
+			.enumerate()
+			.map(|(i, element)| T::from_variant(&element).map_err(|err| err.in_field(format_args!("[{i}]"))))
+			.collect()
+	}
+}
+
+impl<K, V, S> FromVariant for std::collections::HashMap<K, V, S>
+where
+	K: FromVariant + Eq + std::hash::Hash,
+	V: FromVariant,
+	S: std::hash::BuildHasher + Default,
+{
+	fn from_variant(variant: &crate::Variant<'_>) -> Result<Self, FromVariantError> {
+		let variant = unwrap_variant(variant);
+		let entries = variant.as_dict().ok_or_else(|| FromVariantError::InvalidValue { expected: "array of dict entries".into(), actual: variant.signature().to_string() })?;
+		entries
+			.enumerate()
+			.map(|(i, (key, value))| {
+				let key = K::from_variant(key).map_err(|err| err.in_field(format_args!("[{i}]")))?;
+				let value = V::from_variant(value).map_err(|err| err.in_field(format_args!("[{i}]")))?;
+				Ok((key, value))
+			})
+			.collect()
+	}
+}
+
+/// Get the fields of the given variant, which must be a struct (or a tuple, like a message body) with the given number of fields.
+fn struct_fields<'b, 'a>(variant: &'b crate::Variant<'a>, len: usize) -> Result<&'b [crate::Variant<'a>], FromVariantError> {
+	let variant = unwrap_variant(variant);
+	match variant {
+		crate::Variant::Struct { fields } | crate::Variant::Tuple { elements: fields } if fields.len() == len => Ok(fields),
+		variant => Err(FromVariantError::InvalidValue { expected: format!("struct with {len} fields").into(), actual: variant.signature().to_string() }),
+	}
+}
+
+macro_rules! from_variant_tuple {
+	($($len:literal => ($($ty:ident $index:tt),*),)*) => {
+		$(
+			/// Converts a struct, or a tuple like a message body with multiple arguments
+			impl<$($ty),*> FromVariant for ($($ty,)*) where $($ty: FromVariant),* {
+				fn from_variant(variant: &crate::Variant<'_>) -> Result<Self, FromVariantError> {
+					let fields = struct_fields(variant, $len)?;
+					Ok(($(
+						$ty::from_variant(&fields[$index]).map_err(|err| err.in_field($index))?,
+					)*))
+				}
+			}
+		)*
+	};
+}
+
+from_variant_tuple! {
+	1 => (A 0),
+	2 => (A 0, B 1),
+	3 => (A 0, B 1, C 2),
+	4 => (A 0, B 1, C 2, D 3),
+	5 => (A 0, B 1, C 2, D 3, E 4),
+	6 => (A 0, B 1, C 2, D 3, E 4, F 5),
+	7 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6),
+	8 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::FromVariantError;
+
+	#[test]
+	fn test_try_from() {
+		use crate::Variant;
+
+		fn wrap(variant: Variant<'static>) -> Variant<'static> {
+			Variant::Variant(Box::new(variant).into())
+		}
+
+		let n: u32 = Variant::U32(5).try_into().unwrap();
+		assert_eq!(n, 5);
+		let n: u32 = wrap(Variant::U32(5)).try_into().unwrap();
+		assert_eq!(n, 5);
+		let n = u32::try_from(&wrap(Variant::U32(5))).unwrap();
+		assert_eq!(n, 5);
+
+		assert!(bool::try_from(wrap(Variant::Bool(true))).unwrap());
+		assert_eq!(i64::try_from(Variant::I64(-1)).unwrap(), -1);
+		assert_eq!(crate::UnixFd::try_from(&Variant::UnixFd(crate::UnixFd(3))).unwrap(), crate::UnixFd(3));
+
+		assert_eq!(String::try_from(wrap(Variant::String("a".into()))).unwrap(), "a");
+		assert_eq!(String::try_from(&Variant::String("a".into())).unwrap(), "a");
+
+		let s = "a".to_owned();
+		let variant = Variant::String((&*s).into());
+		let cow = std::borrow::Cow::try_from(&variant).unwrap();
+		assert!(matches!(cow, std::borrow::Cow::Borrowed("a")));
+
+		let path = crate::ObjectPath::try_from(wrap(Variant::ObjectPath(crate::ObjectPath("/org".into())))).unwrap();
+		assert_eq!(path, crate::ObjectPath("/org".into()));
+
+		let signature = crate::Signature::try_from(&Variant::Signature(crate::Signature::U8)).unwrap();
+		assert_eq!(signature, crate::Signature::U8);
+
+		assert_eq!(Vec::<u8>::try_from(wrap(Variant::ArrayU8(vec![1, 2].into()))).unwrap(), [1, 2]);
+		assert_eq!(Vec::<u8>::try_from(&crate::ToVariant::to_variant(&vec![1_u8, 2])).unwrap(), [1, 2]);
+
+		assert_eq!(Vec::<String>::try_from(Variant::ArrayString(vec!["a".into()].into())).unwrap(), ["a"]);
+		assert_eq!(Vec::<String>::try_from(wrap(crate::ToVariant::to_variant(&vec!["a".to_owned()]).into_owned())).unwrap(), ["a"]);
+
+		// Only one level of `Variant::Variant` is unwrapped.
+		let err = u32::try_from(wrap(wrap(Variant::U32(5)))).unwrap_err();
+		assert!(matches!(
+			err,
+			crate::FromVariantError::SignatureMismatch { expected: crate::Signature::U32, actual: crate::Signature::Variant },
+		), "{err:?}");
+
+		let err = u32::try_from(Variant::U16(5)).unwrap_err();
+		assert_eq!(err.to_string(), r#"expected variant with signature "u" but got "q""#);
+
+		let err = Vec::<u8>::try_from(Variant::ArrayU16(vec![1].into())).unwrap_err();
+		assert_eq!(err.to_string(), r#"expected variant with signature "ay" but got "aq""#);
+	}
+
+	#[test]
+	fn test_from_variant() {
+		use crate::{FromVariant, Variant};
+
+		type Objects = std::collections::HashMap<crate::ObjectPath<'static>, std::collections::HashMap<String, std::collections::HashMap<String, Variant<'static>>>>;
+
+		// The response of `org.freedesktop.DBus.ObjectManager.GetManagedObjects`, of type `a{oa{sa{sv}}}`
+		let objects = Variant::from_map_iter(crate::Signature::ObjectPath, "a{sa{sv}}".parse().unwrap(), [(
+			Variant::ObjectPath(crate::ObjectPath("/org/bluez/hci0".into())),
+			Variant::from_map_iter(crate::Signature::String, "a{sv}".parse().unwrap(), [(
+				Variant::String("org.bluez.Adapter1".into()),
+				Variant::from_map_iter(crate::Signature::String, crate::Signature::Variant, [
+					(Variant::String("Powered".into()), Variant::Variant(Box::new(Variant::Bool(true)).into())),
+					(Variant::String("UUIDs".into()), Variant::Variant(Box::new(Variant::ArrayString(vec!["a".into()].into())).into())),
+				]).unwrap(),
+			)]).unwrap(),
+		)]).unwrap();
+
+		let objects = Objects::from_variant(&objects).unwrap();
+		let properties = &objects[&crate::ObjectPath("/org/bluez/hci0".into())]["org.bluez.Adapter1"];
+		assert!(bool::from_variant(&properties["Powered"]).unwrap());
+		assert_eq!(Vec::<String>::from_variant(&properties["UUIDs"]).unwrap(), ["a"]);
+
+		let body = Variant::tuple([Variant::U32(1), Variant::ArrayU8(vec![1, 2].into())]);
+		let (n, bytes) = <(u32, Vec<u8>)>::from_variant(&body).unwrap();
+		assert_eq!((n, bytes), (1, vec![1, 2]));
+
+		let err = <(u32, Vec<u8>)>::from_variant(&Variant::structure([Variant::U32(1)])).unwrap_err();
+		assert_eq!(err.to_string(), "expected struct with 2 fields but got (u)");
+
+		let nested = Variant::array("(sai)".parse().unwrap(), [
+			Variant::structure([Variant::String("a".into()), Variant::ArrayI32(vec![].into())]),
+			Variant::structure([Variant::String("b".into()), Variant::array(crate::Signature::I32, [Variant::I32(1), Variant::I32(2)]).unwrap()]),
+		]).unwrap();
+		let err = Vec::<(String, Vec<u32>)>::from_variant(&nested).unwrap_err();
+		let FromVariantError::Field { path, err } = &err else { panic!("{err:?}") };
+		assert_eq!(path, "[1].1[0]");
+		assert!(matches!(
+			**err,
+			FromVariantError::SignatureMismatch { expected: crate::Signature::U32, actual: crate::Signature::I32 },
+		), "{err:?}");
+	}
+}
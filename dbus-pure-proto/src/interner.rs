@@ -0,0 +1,129 @@
+/// A bounded cache of strings shared between received messages.
+///
+/// Names like `:1.7`, `org.freedesktop.DBus.Properties` and `PropertiesChanged` repeat in almost every message on a busy bus.
+/// [`crate::MessageHeader::into_owned_interned`] uses this cache so that all the owned headers share one allocation per distinct string
+/// instead of allocating a fresh `String` every time.
+///
+/// Every distinct string is allocated once as an `Arc<str>` held by the interner, and interned strings borrow it for as long as the interner lives.
+/// The strings are freed when the interner is dropped. Owned headers are `'static`, so the interner that they borrow from must be `'static` too.
+/// It is usually a `static`:
+///
+/// ```rust
+/// static INTERNER: dbus_pure_proto::Interner = dbus_pure_proto::Interner::new(1024);
+///
+/// let mut reader = dbus_pure_proto::MessageReader::new(std::io::empty());
+/// reader.set_interner(Some(&INTERNER));
+/// ```
+///
+/// The cache holds at most `capacity` strings of at most [`Interner::MAX_LEN`] bytes each, so that a peer can't make it grow without bound
+/// by sending many different names. Strings that do not fit are still converted to owned strings, just without being shared.
+#[derive(Debug)]
+pub struct Interner {
+	strings: std::sync::Mutex<std::collections::BTreeSet<std::sync::Arc<str>>>,
+	capacity: usize,
+}
+
+impl Interner {
+	/// The maximum length of a string that will be interned. This is the maximum length of a bus name, interface name or member name.
+	pub const MAX_LEN: usize = 255;
+
+	/// Create an interner that will hold at most `capacity` strings.
+	pub const fn new(capacity: usize) -> Self {
+		Interner {
+			strings: std::sync::Mutex::new(std::collections::BTreeSet::new()),
+			capacity,
+		}
+	}
+
+	/// Convert the given string to one that borrows from this interner, sharing a previously interned copy of it if there is one.
+	pub fn intern(&self, s: std::borrow::Cow<'_, str>) -> std::borrow::Cow<'_, str> {
+		let mut strings = self.strings.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+		let interned: *const str =
+			if let Some(interned) = strings.get(&*s) {
+				std::ptr::from_ref(&**interned)
+			}
+			else if s.len() > Self::MAX_LEN || strings.len() >= self.capacity {
+				return std::borrow::Cow::Owned(s.into_owned());
+			}
+			else {
+				let interned: std::sync::Arc<str> = (*s).into();
+				let ptr = std::ptr::from_ref(&*interned);
+				let _ = strings.insert(interned);
+				ptr
+			};
+
+		// SAFETY: The string is the heap allocation of an `Arc<str>` in `self.strings`, which does not move when the set changes.
+		// Strings are never removed from the set, so the allocation lives until `self` is dropped, which can't happen while `self` is borrowed.
+		let interned: &str = unsafe { &*interned };
+		std::borrow::Cow::Borrowed(interned)
+	}
+
+	/// The number of strings currently interned.
+	pub fn len(&self) -> usize {
+		self.strings.lock().unwrap_or_else(std::sync::PoisonError::into_inner).len()
+	}
+
+	/// Returns true if no strings have been interned yet.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_interner() {
+		static INTERNER: super::Interner = super::Interner::new(3);
+
+		fn recv(buf: &[u8], interner: &'static super::Interner) -> crate::MessageHeader<'static> {
+			let (header, _, _) = crate::deserialize_message(buf).unwrap();
+			header.into_owned_interned(interner)
+		}
+
+		let mut buf = vec![];
+		crate::serialize_message(
+			&crate::MessageHeader {
+				r#type: crate::MessageType::Signal {
+					interface: "org.freedesktop.DBus.Properties".into(),
+					member: "PropertiesChanged".into(),
+					path: crate::ObjectPath("/org/mpris/MediaPlayer2".into()),
+				},
+				flags: crate::message_flags::NONE,
+				body_len: 0,
+				serial: 1,
+				fields: (&[crate::MessageHeaderField::Sender(":1.7".into())][..]).into(),
+			},
+			None,
+			&mut buf,
+			crate::Endianness::Little,
+		).unwrap();
+
+		let first = recv(&buf, &INTERNER);
+		let second = recv(&buf, &INTERNER);
+		assert_eq!(INTERNER.len(), 3);
+
+		let (
+			crate::MessageType::Signal { interface: first_interface, member: first_member, path: first_path },
+			crate::MessageType::Signal { interface: second_interface, member: second_member, path: second_path },
+		) = (&first.r#type, &second.r#type) else { panic!() };
+		assert!(matches!(first_interface, std::borrow::Cow::Borrowed(_)));
+		assert_eq!(first_interface.as_ptr(), second_interface.as_ptr());
+		assert_eq!(first_member.as_ptr(), second_member.as_ptr());
+		assert_eq!(first_path.0.as_ptr(), second_path.0.as_ptr());
+
+		// The interner is full, so the sender does not get interned.
+		let ([crate::MessageHeaderField::Sender(first_sender)], [crate::MessageHeaderField::Sender(second_sender)]) = (&*first.fields, &*second.fields) else { panic!() };
+		assert!(matches!(first_sender, std::borrow::Cow::Owned(_)));
+		assert_eq!(first_sender, second_sender);
+		assert_ne!(first_sender.as_ptr(), second_sender.as_ptr());
+
+		// An interner that isn't `'static` shares strings for as long as it lives.
+		let interner = super::Interner::new(1);
+		let first = interner.intern("org.example".into());
+		let second = interner.intern(String::from("org.example").into());
+		assert_eq!(first.as_ptr(), second.as_ptr());
+		let third = interner.intern("org.example.Other".into());
+		assert!(matches!(third, std::borrow::Cow::Owned(_)));
+	}
+}
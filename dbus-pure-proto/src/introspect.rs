@@ -0,0 +1,236 @@
+//! Machine-readable descriptions of D-Bus interfaces, used to answer `org.freedesktop.DBus.Introspectable.Introspect`.
+//!
+//! The `#[dbus_pure_macros::interface]` macro emits an [`Interface`] for every interface trait as its `DESCRIPTOR` associated const.
+
+/// A description of a D-Bus interface.
+#[derive(Clone, Copy, Debug)]
+pub struct Interface {
+	/// The name of the interface, like `org.freedesktop.DBus`
+	pub name: &'static str,
+
+	/// The methods of the interface.
+	pub methods: &'static [Method],
+
+	/// The signals of the interface.
+	pub signals: &'static [Signal],
+
+	/// The properties of the interface.
+	pub properties: &'static [Property],
+}
+
+/// A description of a method of a D-Bus interface.
+#[derive(Clone, Copy, Debug)]
+pub struct Method {
+	/// The name of the method.
+	pub name: &'static str,
+
+	/// The arguments of the method.
+	///
+	/// An `out` argument whose signature is a [`crate::Signature::Tuple`] is described as one `out` argument per element of the tuple.
+	pub args: &'static [Arg],
+}
+
+/// A description of a signal of a D-Bus interface.
+#[derive(Clone, Copy, Debug)]
+pub struct Signal {
+	/// The name of the signal.
+	pub name: &'static str,
+
+	/// The arguments of the signal. Their directions are ignored.
+	pub args: &'static [Arg],
+}
+
+/// A description of a property of a D-Bus interface.
+#[derive(Clone, Copy, Debug)]
+pub struct Property {
+	/// The name of the property.
+	pub name: &'static str,
+
+	/// The signature of the property.
+	pub signature: fn() -> crate::Signature,
+
+	/// Whether the property can be read, written, or both.
+	pub access: Access,
+}
+
+/// A description of an argument of a method or signal.
+#[derive(Clone, Copy, Debug)]
+pub struct Arg {
+	/// The name of the argument, if it has one.
+	pub name: Option<&'static str>,
+
+	/// The signature of the argument.
+	///
+	/// This is a function rather than a [`crate::Signature`] so that it can be initialized from
+	/// [`crate::ToVariant::signature`] in a `const` context.
+	pub signature: fn() -> crate::Signature,
+
+	/// Whether the argument is an input or an output of the method.
+	pub direction: Direction,
+}
+
+/// The direction of an [`Arg`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+	In,
+	Out,
+}
+
+/// The access of a [`Property`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Access {
+	Read,
+	ReadWrite,
+	Write,
+}
+
+impl Interface {
+	/// Append the `<interface>` element describing this interface to the given XML string.
+	pub fn write_xml(&self, xml: &mut String) {
+		use std::fmt::Write;
+
+		writeln!(xml, r#"  <interface name="{}">"#, self.name).expect("cannot fail");
+
+		for method in self.methods {
+			writeln!(xml, r#"    <method name="{}">"#, method.name).expect("cannot fail");
+			for arg in method.args {
+				arg.write_xml(xml, true);
+			}
+			xml.push_str("    </method>\n");
+		}
+
+		for signal in self.signals {
+			writeln!(xml, r#"    <signal name="{}">"#, signal.name).expect("cannot fail");
+			for arg in signal.args {
+				arg.write_xml(xml, false);
+			}
+			xml.push_str("    </signal>\n");
+		}
+
+		for property in self.properties {
+			let access = match property.access {
+				Access::Read => "read",
+				Access::ReadWrite => "readwrite",
+				Access::Write => "write",
+			};
+			writeln!(xml, r#"    <property name="{}" type="{}" access="{access}"/>"#, property.name, (property.signature)()).expect("cannot fail");
+		}
+
+		xml.push_str("  </interface>\n");
+	}
+}
+
+impl Arg {
+	fn write_xml(&self, xml: &mut String, with_direction: bool) {
+		use std::fmt::Write;
+
+		let direction = match (with_direction, self.direction) {
+			(false, _) => "",
+			(true, Direction::In) => r#" direction="in""#,
+			(true, Direction::Out) => r#" direction="out""#,
+		};
+
+		let signature = (self.signature)();
+		let signatures = match (self.direction, signature) {
+			(Direction::Out, crate::Signature::Tuple { elements }) => elements,
+			(_, signature) => vec![signature],
+		};
+
+		for signature in signatures {
+			xml.push_str("      <arg");
+			if let Some(name) = self.name {
+				write!(xml, r#" name="{name}""#).expect("cannot fail");
+			}
+			writeln!(xml, r#" type="{signature}"{direction}/>"#).expect("cannot fail");
+		}
+	}
+}
+
+/// Build the introspection XML document of an object that implements the given interfaces and has the given child nodes.
+pub fn to_xml(interfaces: &[&Interface], children: &[&str]) -> String {
+	let mut xml = String::from(concat!(
+		r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN""#, "\n",
+		r#" "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">"#, "\n",
+		"<node>\n",
+	));
+
+	for interface in interfaces {
+		interface.write_xml(&mut xml);
+	}
+
+	for child in children {
+		xml.push_str(r#"  <node name=""#);
+		xml.push_str(child);
+		xml.push_str("\"/>\n");
+	}
+
+	xml.push_str("</node>\n");
+
+	xml
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_to_xml() {
+		const INTERFACE: super::Interface = super::Interface {
+			name: "org.freedesktop.DBus.Properties",
+			methods: &[
+				super::Method {
+					name: "Get",
+					args: &[
+						super::Arg { name: Some("interface_name"), signature: <str as crate::ToVariant>::signature, direction: super::Direction::In },
+						super::Arg { name: Some("property_name"), signature: <str as crate::ToVariant>::signature, direction: super::Direction::In },
+						super::Arg { name: None, signature: || crate::Signature::Variant, direction: super::Direction::Out },
+					],
+				},
+				super::Method {
+					name: "Pair",
+					args: &[
+						super::Arg {
+							name: None,
+							signature: || crate::Signature::Tuple { elements: vec![crate::Signature::String, crate::Signature::U32] },
+							direction: super::Direction::Out,
+						},
+					],
+				},
+			],
+			signals: &[
+				super::Signal {
+					name: "PropertiesChanged",
+					args: &[
+						super::Arg { name: Some("interface_name"), signature: <str as crate::ToVariant>::signature, direction: super::Direction::Out },
+					],
+				},
+			],
+			properties: &[
+				super::Property { name: "Version", signature: <u32 as crate::ToVariant>::signature, access: super::Access::Read },
+			],
+		};
+
+		assert_eq!(
+			super::to_xml(&[&INTERFACE], &["child"]),
+			r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="org.freedesktop.DBus.Properties">
+    <method name="Get">
+      <arg name="interface_name" type="s" direction="in"/>
+      <arg name="property_name" type="s" direction="in"/>
+      <arg type="v" direction="out"/>
+    </method>
+    <method name="Pair">
+      <arg type="s" direction="out"/>
+      <arg type="u" direction="out"/>
+    </method>
+    <signal name="PropertiesChanged">
+      <arg name="interface_name" type="s"/>
+    </signal>
+    <property name="Version" type="u" access="read"/>
+  </interface>
+  <node name="child"/>
+</node>
+"#,
+		);
+	}
+}
@@ -0,0 +1,866 @@
+/// Reads messages from a [`std::io::Read`], like a socket connected to a message bus.
+///
+/// Each message is read in two steps. First the fixed part of its header is read, which has the lengths of the rest of the header and of the body.
+/// Then exactly that many more bytes are read, and the message is deserialized once. So a message that exceeds the limits set by
+/// [`MessageReader::set_limits`] is rejected without reading the rest of it, and no bytes beyond the end of the message are read.
+///
+/// The buffer that messages are read into grows to fit each message, and is shrunk back to [`MessageReader::set_max_read_buffer`]
+/// once a larger message has been read.
+///
+/// If reading a message fails, the position in the underlying reader is undefined, so no more messages can be read from it.
+/// The exception is an IO error of kind [`std::io::ErrorKind::WouldBlock`] or [`std::io::ErrorKind::TimedOut`],
+/// like the error from a socket whose read timeout elapsed. The bytes of the message that were read before the error are kept,
+/// and the next read continues the message.
+#[derive(Debug)]
+pub struct MessageReader<R> {
+	reader: R,
+	buf: Vec<u8>,
+	max_read_buffer: usize,
+	limits: crate::MessageLimits,
+	interner: Option<&'static crate::Interner>,
+
+	/// Whether `buf` has the start of a message whose read failed with a timeout, which the next read continues.
+	partial: bool,
+
+	/// The length of the last message that was read.
+	message_len: usize,
+}
+
+/// The length of the part of the header that has the lengths of the rest of the header and of the body.
+const FIXED_HEADER_LEN: usize = 16;
+
+/// The default for [`MessageReader::set_max_read_buffer`]
+const DEFAULT_MAX_READ_BUFFER: usize = 64 * 1024;
+
+impl<R> MessageReader<R> where R: std::io::Read {
+	/// Create a reader that reads messages from the given reader.
+	pub fn new(reader: R) -> Self {
+		MessageReader {
+			reader,
+			buf: vec![],
+			max_read_buffer: DEFAULT_MAX_READ_BUFFER,
+			limits: crate::MessageLimits::SPEC,
+			interner: None,
+			partial: false,
+			message_len: 0,
+		}
+	}
+
+	/// Get a reference to the underlying reader.
+	pub fn get_ref(&self) -> &R {
+		&self.reader
+	}
+
+	/// Get a mutable reference to the underlying reader.
+	///
+	/// Reading from it directly would leave this reader in the middle of a message.
+	pub fn get_mut(&mut self) -> &mut R {
+		&mut self.reader
+	}
+
+	/// Set the capacity of the read buffer that is kept between messages.
+	///
+	/// Reading a message larger than this grows the buffer to fit it, and the buffer is shrunk back to this capacity afterwards.
+	/// To bound the size of the messages themselves, use [`MessageReader::set_limits`].
+	///
+	/// Defaults to 64 KiB.
+	pub fn set_max_read_buffer(&mut self, max_read_buffer: usize) {
+		self.max_read_buffer = max_read_buffer;
+		self.shrink_buf();
+	}
+
+	/// The current capacity of the read buffer. See [`MessageReader::set_max_read_buffer`]
+	pub fn buffer_capacity(&self) -> usize {
+		self.buf.capacity()
+	}
+
+	/// The length in bytes of the last message that was read, or zero if no message has been read yet.
+	pub fn last_message_len(&self) -> usize {
+		self.message_len
+	}
+
+	/// Set the limits on the sizes of read messages.
+	///
+	/// By default, the reader uses [`crate::MessageLimits::SPEC`].
+	pub fn set_limits(&mut self, limits: crate::MessageLimits) {
+		self.limits = limits;
+	}
+
+	/// Set the interner used to share the names and paths of read message headers.
+	///
+	/// By default, the reader does not intern anything and every read header gets freshly allocated strings.
+	pub fn set_interner(&mut self, interner: Option<&'static crate::Interner>) {
+		self.interner = interner;
+	}
+
+	/// Read the next message.
+	///
+	/// Returns the message header, the message body if any, and the endianness that the sender used for the message.
+	pub fn read_message(
+		&mut self,
+	) -> Result<(crate::MessageHeader<'static>, Option<crate::Variant<'static>>, crate::Endianness), ReadMessageError> {
+		let result = self.read_message_inner();
+		self.shrink_buf();
+		result
+	}
+
+	fn read_message_inner(
+		&mut self,
+	) -> Result<(crate::MessageHeader<'static>, Option<crate::Variant<'static>>, crate::Endianness), ReadMessageError> {
+		let endianness = self.read_message_bytes()?;
+
+		let (header, body, _) = crate::deserialize_message_with_limits(&self.buf, &self.limits).map_err(ReadMessageError::Deserialize)?;
+		let header = match self.interner {
+			Some(interner) => header.into_owned_interned(interner),
+			None => header.into_owned(),
+		};
+		let body = body.map(crate::Variant::into_owned);
+		Ok((header, body, endianness))
+	}
+
+	/// Read the next message, without copying its strings and arrays out of the read buffer.
+	///
+	/// This avoids allocating for every string in messages that are only inspected and then discarded.
+	/// The returned message borrows the reader, so it must be dropped or converted with [`MessageRef::into_owned`]
+	/// before the next message can be read.
+	///
+	/// The interner set by [`MessageReader::set_interner`] is not used. Since the read buffer is still borrowed when this function returns,
+	/// it is only shrunk back to [`MessageReader::set_max_read_buffer`] when the next message is read.
+	pub fn read_message_ref(&mut self) -> Result<MessageRef<'_>, ReadMessageError> {
+		self.shrink_buf();
+
+		let endianness = self.read_message_bytes()?;
+
+		let (header, body, _) = crate::deserialize_message_with_limits(&self.buf, &self.limits).map_err(ReadMessageError::Deserialize)?;
+		Ok(MessageRef { header, body, endianness, len: self.message_len })
+	}
+
+	/// Read the bytes of the next message into the buffer, and return the endianness of the message.
+	fn read_message_bytes(&mut self) -> Result<crate::Endianness, ReadMessageError> {
+		if !std::mem::take(&mut self.partial) {
+			self.buf.clear();
+		}
+
+		self.fill_buf_to(FIXED_HEADER_LEN)?;
+
+		let endianness = crate::message_endianness(&self.buf).map_err(ReadMessageError::Deserialize)?;
+
+		let (header_len, body_len) =
+			crate::message::header_and_body_len(&self.buf, endianness)
+			.ok_or(ReadMessageError::Deserialize(crate::DeserializeError::EndOfInput))?;
+		let message_len = header_len.saturating_add(body_len);
+		if message_len > self.limits.max_message_len {
+			return Err(ReadMessageError::Deserialize(crate::DeserializeError::MessageTooLong { len: message_len, max: self.limits.max_message_len }));
+		}
+
+		self.fill_buf_to(message_len)?;
+		self.message_len = message_len;
+
+		Ok(endianness)
+	}
+
+	/// Append bytes from the reader to the buffer until it has at least `len` bytes.
+	///
+	/// The buffer is grown by exactly as many bytes as are missing if it doesn't have the capacity for them already,
+	/// and the bytes are read into its spare capacity rather than into zero-filled space.
+	///
+	/// If the read fails with a timeout, the bytes that were read before it are kept in the buffer for the next read to continue from.
+	fn fill_buf_to(&mut self, len: usize) -> Result<(), ReadMessageError> {
+		use std::io::Read;
+
+		let len = len.saturating_sub(self.buf.len());
+		self.buf.reserve_exact(len);
+
+		let read = match (&mut self.reader).take(len as u64).read_to_end(&mut self.buf) {
+			Ok(read) => read,
+			Err(err) => {
+				self.partial = matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut);
+				return Err(ReadMessageError::Io(err));
+			},
+		};
+		if read != len {
+			return Err(ReadMessageError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+		}
+
+		Ok(())
+	}
+
+	fn shrink_buf(&mut self) {
+		// The buffer has the start of a message that the next read continues.
+		if self.partial {
+			return;
+		}
+
+		if self.buf.capacity() > self.max_read_buffer {
+			self.buf.clear();
+			self.buf.shrink_to(self.max_read_buffer);
+		}
+	}
+}
+
+/// A message read by [`MessageReader::read_message_ref`], whose strings and arrays borrow from the reader's buffer.
+#[derive(Debug)]
+pub struct MessageRef<'a> {
+	pub header: crate::MessageHeader<'a>,
+	pub body: Option<crate::Variant<'a>>,
+
+	/// The endianness that the sender used for the message.
+	pub endianness: crate::Endianness,
+
+	/// The length of the message in bytes.
+	pub len: usize,
+}
+
+impl MessageRef<'_> {
+	/// Copy the header and body out of the reader's buffer.
+	pub fn into_owned(self) -> (crate::MessageHeader<'static>, Option<crate::Variant<'static>>) {
+		(self.header.into_owned(), self.body.map(crate::Variant::into_owned))
+	}
+}
+
+/// An error from reading a message using a [`MessageReader`].
+#[derive(Debug)]
+pub enum ReadMessageError {
+	Deserialize(crate::DeserializeError),
+	Io(std::io::Error),
+}
+
+impl std::fmt::Display for ReadMessageError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ReadMessageError::Deserialize(_) => f.write_str("could not deserialize message"),
+			ReadMessageError::Io(_) => f.write_str("could not read message"),
+		}
+	}
+}
+
+impl std::error::Error for ReadMessageError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			ReadMessageError::Deserialize(err) => Some(err),
+			ReadMessageError::Io(err) => Some(err),
+		}
+	}
+}
+
+/// Writes messages to a [`std::io::Write`], like a socket connected to a message bus.
+///
+/// Each message is serialized completely before any of it is written, so a message that cannot be serialized is not partially written.
+/// The header and the body are serialized into separate buffers and written with [`std::io::Write::write_vectored`],
+/// so a large body is not copied again to put it after the header.
+///
+/// If the underlying writer fails with [`std::io::ErrorKind::WouldBlock`], like a non-blocking socket whose send buffer is full,
+/// what happens depends on how much of the message was written:
+///
+/// - If none of it was written, writing the message fails with that error, and it can be written again later.
+///
+/// - If some of it was written, the rest of it is kept and writing the message succeeds. The rest is written by [`MessageWriter::flush`],
+///   and writing any other message fails with [`std::io::ErrorKind::WouldBlock`] until it has been.
+#[derive(Debug)]
+pub struct MessageWriter<W> {
+	writer: W,
+	header_buf: Vec<u8>,
+	body_buf: Vec<u8>,
+	limits: crate::MessageLimits,
+
+	/// The rest of a message that was only partially written
+	pending: Vec<u8>,
+}
+
+impl<W> MessageWriter<W> where W: std::io::Write {
+	/// Create a writer that writes messages to the given writer.
+	pub fn new(writer: W) -> Self {
+		MessageWriter {
+			writer,
+			header_buf: vec![],
+			body_buf: vec![],
+			limits: crate::MessageLimits::SPEC,
+			pending: vec![],
+		}
+	}
+
+	/// Get a reference to the underlying writer.
+	pub fn get_ref(&self) -> &W {
+		&self.writer
+	}
+
+	/// Get a mutable reference to the underlying writer.
+	pub fn get_mut(&mut self) -> &mut W {
+		&mut self.writer
+	}
+
+	/// The length in bytes of the last message that was written, or zero if no message has been written yet.
+	///
+	/// This includes a message that was only partially written, the rest of which is pending. See [`MessageWriter::has_pending_write`]
+	/// If the last message could not be serialized, the value is unspecified.
+	pub fn last_message_len(&self) -> usize {
+		self.header_buf.len() + self.body_buf.len()
+	}
+
+	/// Set the limits on the sizes of written messages.
+	///
+	/// By default, the writer uses [`crate::MessageLimits::SPEC`].
+	pub fn set_limits(&mut self, limits: crate::MessageLimits) {
+		self.limits = limits;
+	}
+
+	/// Write a message with the given header and body using the given endianness, and flush the underlying writer.
+	///
+	/// See [`crate::serialize_message`] for details.
+	pub fn write_message(
+		&mut self,
+		header: &crate::MessageHeader<'_>,
+		body: Option<&crate::Variant<'_>>,
+		endianness: crate::Endianness,
+	) -> Result<(), WriteMessageError> {
+		self.header_buf.clear();
+		self.body_buf.clear();
+
+		let () =
+			crate::message::serialize_message_split(header, body, &mut self.header_buf, &mut self.body_buf, endianness, &self.limits)
+			.map_err(WriteMessageError::Serialize)?;
+
+		self.write_bufs()
+	}
+
+	/// Write a message with the given header using the given endianness, and flush the underlying writer.
+	///
+	/// The body is serialized directly from `body` with the given signature, as with [`crate::to_writer_with_signature`].
+	pub fn write_message_serialized<T>(
+		&mut self,
+		header: &crate::MessageHeader<'_>,
+		body: &T,
+		body_signature: &crate::Signature,
+		endianness: crate::Endianness,
+	) -> Result<(), WriteMessageError> where T: serde::Serialize + ?Sized {
+		self.header_buf.clear();
+		self.body_buf.clear();
+
+		let () =
+			crate::message::serialize_message_split_with(
+				header,
+				Some(body_signature.clone()),
+				|body_serializer| crate::signature_serializer::serialize_with_signature(body, body_signature, body_serializer),
+				&mut self.header_buf,
+				&mut self.body_buf,
+				endianness,
+				&self.limits,
+			)
+			.map_err(WriteMessageError::Serialize)?;
+
+		self.write_bufs()
+	}
+
+	/// Write the rest of a partially written message, if any, and flush the underlying writer.
+	///
+	/// Fails with [`std::io::ErrorKind::WouldBlock`] if the underlying writer does, in which case this should be called again later.
+	pub fn flush(&mut self) -> std::io::Result<()> {
+		while !self.pending.is_empty() {
+			match self.writer.write(&self.pending) {
+				Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+				Ok(written) => { let _ = self.pending.drain(..written); },
+				Err(err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+				Err(err) => return Err(err),
+			}
+		}
+
+		self.writer.flush()
+	}
+
+	/// Whether a message was only partially written, and the rest of it needs to be written by [`MessageWriter::flush`]
+	pub fn has_pending_write(&self) -> bool {
+		!self.pending.is_empty()
+	}
+
+	fn write_bufs(&mut self) -> Result<(), WriteMessageError> {
+		// The previous message must be completely written before this one can be started.
+		let () = self.flush().map_err(WriteMessageError::Io)?;
+
+		let mut bufs = [std::io::IoSlice::new(&self.header_buf), std::io::IoSlice::new(&self.body_buf)];
+		match write_all_vectored(&mut self.writer, &mut bufs) {
+			Ok(()) => (),
+
+			Err((written, err)) if err.kind() == std::io::ErrorKind::WouldBlock && written > 0 => {
+				// Keep the rest of the message for `flush`, since the reader on the other end has already seen the start of it.
+				self.pending.extend(self.header_buf.iter().chain(&self.body_buf).skip(written));
+				return Ok(());
+			},
+
+			Err((_, err)) => return Err(WriteMessageError::Io(err)),
+		}
+
+		let () = self.writer.flush().map_err(WriteMessageError::Io)?;
+
+		Ok(())
+	}
+}
+
+/// Write all of the given buffers, retrying after short writes.
+///
+/// If writing fails, returns the number of bytes that were written before the error along with the error.
+///
+/// TODO(rustup): Use `std::io::Write::write_all_vectored` when that is stabilized.
+fn write_all_vectored(writer: &mut impl std::io::Write, mut bufs: &mut [std::io::IoSlice<'_>]) -> Result<(), (usize, std::io::Error)> {
+	// Skip empty buffers, like the body of a message without one.
+	std::io::IoSlice::advance_slices(&mut bufs, 0);
+
+	let mut total_written = 0;
+
+	while !bufs.is_empty() {
+		match writer.write_vectored(bufs) {
+			Ok(0) => return Err((total_written, std::io::ErrorKind::WriteZero.into())),
+			Ok(written) => {
+				std::io::IoSlice::advance_slices(&mut bufs, written);
+				total_written += written;
+			},
+			Err(err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+			Err(err) => return Err((total_written, err)),
+		}
+	}
+
+	Ok(())
+}
+
+/// An error from writing a message using a [`MessageWriter`].
+#[derive(Debug)]
+pub enum WriteMessageError {
+	Io(std::io::Error),
+	Serialize(crate::SerializeError),
+}
+
+impl std::fmt::Display for WriteMessageError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			WriteMessageError::Io(_) => f.write_str("could not write message"),
+			WriteMessageError::Serialize(_) => f.write_str("could not serialize message"),
+		}
+	}
+}
+
+impl std::error::Error for WriteMessageError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			WriteMessageError::Io(err) => Some(err),
+			WriteMessageError::Serialize(err) => Some(err),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	/// A reader that returns at most `chunk_len` bytes at a time, like a socket that receives a large message in pieces.
+	struct ChunkReader<'a> {
+		buf: &'a [u8],
+		chunk_len: usize,
+		reads: usize,
+	}
+
+	impl<'a> ChunkReader<'a> {
+		fn new(buf: &'a [u8], chunk_len: usize) -> Self {
+			ChunkReader { buf, chunk_len, reads: 0 }
+		}
+	}
+
+	impl std::io::Read for ChunkReader<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+			let len = buf.len().min(self.buf.len()).min(self.chunk_len);
+			let (chunk, rest) = self.buf.split_at(len);
+			buf[..len].copy_from_slice(chunk);
+			self.buf = rest;
+			self.reads += 1;
+			Ok(len)
+		}
+	}
+
+	/// A writer that accepts at most `max_write_len` bytes per write, and fails the first write with `ErrorKind::Interrupted`,
+	/// like a socket whose send buffer is nearly full.
+	struct ShortWriter {
+		buf: Vec<u8>,
+		max_write_len: usize,
+		writes: usize,
+		interrupted: bool,
+	}
+
+	impl ShortWriter {
+		fn new(max_write_len: usize) -> Self {
+			ShortWriter { buf: vec![], max_write_len, writes: 0, interrupted: false }
+		}
+	}
+
+	impl std::io::Write for ShortWriter {
+		fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+			self.write_vectored(&[std::io::IoSlice::new(buf)])
+		}
+
+		fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+			if !self.interrupted {
+				self.interrupted = true;
+				return Err(std::io::ErrorKind::Interrupted.into());
+			}
+
+			self.writes += 1;
+
+			let mut written = 0;
+			for buf in bufs {
+				let len = buf.len().min(self.max_write_len - written);
+				self.buf.extend_from_slice(&buf[..len]);
+				written += len;
+			}
+			Ok(written)
+		}
+
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
+		}
+	}
+
+	fn header(serial: u32) -> crate::MessageHeader<'static> {
+		crate::MessageHeader {
+			r#type: crate::MessageType::Signal { interface: "org.foo".into(), member: "Changed".into(), path: crate::ObjectPath("/org/foo".into()) },
+			flags: crate::message_flags::NO_REPLY_EXPECTED,
+			body_len: 0,
+			serial,
+			fields: vec![].into(),
+		}
+	}
+
+	#[test]
+	fn test_read_write() {
+		let mut writer = super::MessageWriter::new(vec![]);
+		writer.write_message(&header(1), Some(&crate::Variant::String("a".into())), crate::Endianness::Little).unwrap();
+		let first_message_len = writer.last_message_len();
+		writer.write_message(&header(2), None, crate::Endianness::Big).unwrap();
+		writer.write_message(&header(3), Some(&crate::Variant::U64(5)), crate::Endianness::Big).unwrap();
+
+		// A message that cannot be serialized is not written at all.
+		let err = writer.write_message(&header(0), None, crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, super::WriteMessageError::Serialize(crate::SerializeError::ZeroSerial)), "{err:?}");
+
+		let mut reader = super::MessageReader::new(ChunkReader::new(&writer.writer, 1));
+
+		assert_eq!(reader.last_message_len(), 0);
+		let (received_header, body, endianness) = reader.read_message().unwrap();
+		assert_eq!(received_header.serial, 1);
+		assert_eq!(body, Some(crate::Variant::String("a".into())));
+		assert_eq!(endianness, crate::Endianness::Little);
+		assert_eq!(reader.last_message_len(), first_message_len);
+
+		let (received_header, body, endianness) = reader.read_message().unwrap();
+		assert_eq!(received_header.serial, 2);
+		assert_eq!(body, None);
+		assert_eq!(endianness, crate::Endianness::Big);
+
+		let (received_header, body, endianness) = reader.read_message().unwrap();
+		assert_eq!(received_header.serial, 3);
+		assert_eq!(body, Some(crate::Variant::U64(5)));
+		assert_eq!(endianness, crate::Endianness::Big);
+
+		let err = reader.read_message().unwrap_err();
+		assert!(matches!(err, super::ReadMessageError::Io(ref err) if err.kind() == std::io::ErrorKind::UnexpectedEof), "{err:?}");
+	}
+
+	#[test]
+	fn test_read_timeout() {
+		/// A reader that returns the given chunks in order, where `None` fails with `ErrorKind::WouldBlock`
+		/// like a socket whose read timeout elapsed.
+		struct TimeoutReader(std::collections::VecDeque<Option<Vec<u8>>>);
+
+		impl std::io::Read for TimeoutReader {
+			fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+				match self.0.pop_front() {
+					Some(Some(mut chunk)) => {
+						let len = buf.len().min(chunk.len());
+						buf[..len].copy_from_slice(&chunk[..len]);
+						let rest = chunk.split_off(len);
+						if !rest.is_empty() {
+							self.0.push_front(Some(rest));
+						}
+						Ok(len)
+					},
+					Some(None) => Err(std::io::ErrorKind::WouldBlock.into()),
+					None => Ok(0),
+				}
+			}
+		}
+
+		let mut writer = super::MessageWriter::new(vec![]);
+		writer.write_message(&header(1), Some(&crate::Variant::ArrayU8((&[5_u8; 64][..]).into())), crate::Endianness::Little).unwrap();
+		let first_len = writer.writer.len();
+		writer.write_message(&header(2), None, crate::Endianness::Little).unwrap();
+		let buf = &writer.writer;
+
+		// Time out before the message, in the fixed part of the header, in the rest of the header, and in the body.
+		let mut reader = super::MessageReader::new(TimeoutReader(vec![
+			None,
+			Some(buf[..5].to_owned()),
+			None,
+			Some(buf[5..20].to_owned()),
+			None,
+			Some(buf[20..(first_len - 10)].to_owned()),
+			None,
+			Some(buf[(first_len - 10)..].to_owned()),
+		].into()));
+		reader.set_max_read_buffer(0);
+
+		for _ in 0..4 {
+			let err = reader.read_message().unwrap_err();
+			assert!(matches!(err, super::ReadMessageError::Io(ref err) if err.kind() == std::io::ErrorKind::WouldBlock), "{err:?}");
+		}
+
+		let (received_header, body, _) = reader.read_message().unwrap();
+		assert_eq!(received_header.serial, 1);
+		assert_eq!(body, Some(crate::Variant::ArrayU8((&[5_u8; 64][..]).into())));
+
+		let (received_header, body, _) = reader.read_message().unwrap();
+		assert_eq!(received_header.serial, 2);
+		assert_eq!(body, None);
+	}
+
+	#[test]
+	fn test_read_too_long() {
+		let mut writer = super::MessageWriter::new(vec![]);
+		writer.write_message(&header(1), Some(&crate::Variant::ArrayU8((&[0_u8; 64][..]).into())), crate::Endianness::Little).unwrap();
+
+		let mut reader = super::MessageReader::new(ChunkReader::new(&writer.writer, 1));
+		reader.set_limits(crate::MessageLimits { max_message_len: 64, max_array_len: 64 });
+		let err = reader.read_message().unwrap_err();
+		assert!(matches!(err, super::ReadMessageError::Deserialize(crate::DeserializeError::MessageTooLong { len: _, max: 64 })), "{err:?}");
+
+		// Only the fixed part of the header was read.
+		assert_eq!(reader.reader.buf.len(), writer.writer.len() - 16);
+	}
+
+	#[test]
+	fn test_read_large_in_chunks() {
+		const CHUNK_LEN: usize = 64 * 1024;
+
+		let body = crate::Variant::ArrayU8(vec![0xAA_u8; 5 * 1024 * 1024].into());
+
+		let mut writer = super::MessageWriter::new(vec![]);
+		writer.write_message(&header(1), Some(&body), crate::Endianness::Little).unwrap();
+		writer.write_message(&header(2), None, crate::Endianness::Little).unwrap();
+
+		let mut reader = super::MessageReader::new(ChunkReader::new(&writer.writer, CHUNK_LEN));
+
+		let (received_header, received_body, _) = reader.read_message().unwrap();
+		assert_eq!(received_header.serial, 1);
+		assert_eq!(received_body.as_ref(), Some(&body));
+
+		// The fixed part of the header is read first, then the rest of the message in about as few reads as the stream allows
+		// (`Read::read_to_end` starts with a few smaller reads), and nothing of the next message is read.
+		let message_len = writer.writer.len() - reader.reader.buf.len();
+		let min_reads = 1 + (message_len - 16).div_ceil(CHUNK_LEN);
+		assert!((min_reads..min_reads + 4).contains(&reader.reader.reads), "{} reads", reader.reader.reads);
+
+		let (received_header, received_body, _) = reader.read_message().unwrap();
+		assert_eq!(received_header.serial, 2);
+		assert_eq!(received_body, None);
+	}
+
+	#[test]
+	fn test_read_buffer() {
+		let mut writer = super::MessageWriter::new(vec![]);
+		writer.write_message(&header(1), Some(&crate::Variant::ArrayU8(vec![0_u8; 100].into())), crate::Endianness::Little).unwrap();
+		writer.write_message(&header(2), Some(&crate::Variant::ArrayU8(vec![0_u8; 100].into())), crate::Endianness::Little).unwrap();
+		writer.write_message(&header(3), Some(&crate::Variant::ArrayU8(vec![0_u8; 10_000].into())), crate::Endianness::Little).unwrap();
+		writer.write_message(&header(4), None, crate::Endianness::Little).unwrap();
+
+		let mut reader = super::MessageReader::new(ChunkReader::new(&writer.writer, 1000));
+		reader.set_max_read_buffer(1024);
+
+		// The buffer grows to fit exactly the first message ...
+		let _ = reader.read_message().unwrap();
+		let capacity = reader.buf.capacity();
+		assert_eq!(capacity, reader.buf.len());
+		let ptr = reader.buf.as_ptr();
+
+		// ... and is reused for the next message that fits in it.
+		let _ = reader.read_message().unwrap();
+		assert_eq!(reader.buf.capacity(), capacity);
+		assert_eq!(reader.buf.as_ptr(), ptr);
+
+		// A larger message grows the buffer beyond the maximum, after which it is shrunk back.
+		let (received_header, body, _) = reader.read_message().unwrap();
+		assert_eq!(received_header.serial, 3);
+		assert_eq!(body, Some(crate::Variant::ArrayU8(vec![0_u8; 10_000].into())));
+		assert_eq!(reader.buf.capacity(), 1024);
+
+		let (received_header, _, _) = reader.read_message().unwrap();
+		assert_eq!(received_header.serial, 4);
+		assert_eq!(reader.buf.capacity(), 1024);
+
+		// Lowering the maximum shrinks the buffer immediately.
+		reader.set_max_read_buffer(0);
+		assert_eq!(reader.buf.capacity(), 0);
+	}
+
+	#[test]
+	fn test_write_vectored() {
+		let messages = [
+			(header(1), None),
+			(header(2), Some(crate::Variant::String("a".into()))),
+			(header(3), Some(crate::Variant::tuple([crate::Variant::U8(1), crate::Variant::ArrayU64(vec![2, 3].into())]))),
+			(header(4), Some(crate::Variant::ArrayU8(vec![0xAA_u8; 10_000].into()))),
+		];
+
+		for (header, body) in &messages {
+			for endianness in [crate::Endianness::Little, crate::Endianness::Big] {
+				let mut expected = vec![];
+				crate::serialize_message(header, body.as_ref(), &mut expected, endianness).unwrap();
+
+				// A writer that accepts everything gets the whole message in one write.
+				let mut writer = super::MessageWriter::new(ShortWriter::new(usize::MAX));
+				writer.write_message(header, body.as_ref(), endianness).unwrap();
+				assert_eq!(writer.writer.buf, expected);
+				assert_eq!(writer.writer.writes, 1);
+
+				// Short writes, including ones that end in the middle of the header or the body, are continued.
+				for max_write_len in [1, 3, 16, 100, 4096] {
+					let mut writer = super::MessageWriter::new(ShortWriter::new(max_write_len));
+					writer.write_message(header, body.as_ref(), endianness).unwrap();
+					assert_eq!(writer.writer.buf, expected, "max_write_len = {max_write_len}");
+					assert_eq!(writer.writer.writes, expected.len().div_ceil(max_write_len), "max_write_len = {max_write_len}");
+				}
+			}
+		}
+
+		// A message that exceeds the limits is not written.
+		let mut writer = super::MessageWriter::new(ShortWriter::new(usize::MAX));
+		writer.set_limits(crate::MessageLimits { max_message_len: 100, max_array_len: 100 });
+		let err = writer.write_message(&header(1), Some(&crate::Variant::ArrayU8(vec![0_u8; 100].into())), crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, super::WriteMessageError::Serialize(crate::SerializeError::MessageTooLong { len: _, max: 100 })), "{err:?}");
+		assert!(writer.writer.buf.is_empty());
+
+		// A writer that stops accepting bytes fails the write.
+		let mut writer = super::MessageWriter::new(ShortWriter::new(0));
+		let err = writer.write_message(&header(1), None, crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, super::WriteMessageError::Io(ref err) if err.kind() == std::io::ErrorKind::WriteZero), "{err:?}");
+	}
+
+	#[test]
+	fn test_write_would_block() {
+		/// A writer that accepts at most `capacity` bytes before failing with `ErrorKind::WouldBlock`,
+		/// like a non-blocking socket whose send buffer is full. If `interrupt` is set, the next write fails with `ErrorKind::Interrupted` instead,
+		/// like a socket whose write was interrupted by a signal.
+		struct FullWriter {
+			buf: Vec<u8>,
+			capacity: usize,
+			interrupt: bool,
+		}
+
+		impl std::io::Write for FullWriter {
+			fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+				if std::mem::take(&mut self.interrupt) {
+					return Err(std::io::ErrorKind::Interrupted.into());
+				}
+
+				let len = buf.len().min(self.capacity - self.buf.len());
+				if len == 0 && !buf.is_empty() {
+					return Err(std::io::ErrorKind::WouldBlock.into());
+				}
+				self.buf.extend_from_slice(&buf[..len]);
+				Ok(len)
+			}
+
+			fn flush(&mut self) -> std::io::Result<()> {
+				Ok(())
+			}
+		}
+
+		let body = crate::Variant::ArrayU8((&[5_u8; 64][..]).into());
+
+		let mut writer = super::MessageWriter::new(FullWriter { buf: vec![], capacity: 0, interrupt: false });
+
+		// A message that could not be written at all fails.
+		let err = writer.write_message(&header(1), Some(&body), crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, super::WriteMessageError::Io(ref err) if err.kind() == std::io::ErrorKind::WouldBlock), "{err:?}");
+		assert!(!writer.has_pending_write());
+
+		// A message that was partially written succeeds, and the rest is kept.
+		writer.writer.capacity = 20;
+		writer.write_message(&header(1), Some(&body), crate::Endianness::Little).unwrap();
+		assert!(writer.has_pending_write());
+		assert_eq!(writer.writer.buf.len(), 20);
+
+		// Other messages can't be written until the rest of it is.
+		let err = writer.write_message(&header(2), None, crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, super::WriteMessageError::Io(ref err) if err.kind() == std::io::ErrorKind::WouldBlock), "{err:?}");
+		let err = writer.flush().unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+
+		// The rest of the message is written in pieces, and interrupted writes are retried.
+		writer.writer.capacity = 40;
+		writer.writer.interrupt = true;
+		let err = writer.flush().unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+		assert!(!writer.writer.interrupt);
+		assert_eq!(writer.writer.buf.len(), 40);
+		assert!(writer.has_pending_write());
+
+		writer.writer.capacity = usize::MAX;
+		writer.writer.interrupt = true;
+		writer.flush().unwrap();
+		assert!(!writer.has_pending_write());
+		writer.write_message(&header(2), None, crate::Endianness::Little).unwrap();
+
+		let mut reader = super::MessageReader::new(&writer.writer.buf[..]);
+		let (received_header, received_body, _) = reader.read_message().unwrap();
+		assert_eq!(received_header.serial, 1);
+		assert_eq!(received_body, Some(body));
+		let (received_header, received_body, _) = reader.read_message().unwrap();
+		assert_eq!(received_header.serial, 2);
+		assert_eq!(received_body, None);
+	}
+
+	#[test]
+	fn test_write_message_serialized() {
+		let body = crate::Variant::tuple([crate::Variant::String("a".into()), crate::Variant::ArrayU64(vec![2, 3].into())]);
+
+		for endianness in [crate::Endianness::Little, crate::Endianness::Big] {
+			let mut expected = vec![];
+			crate::serialize_message(&header(1), Some(&body), &mut expected, endianness).unwrap();
+
+			let mut writer = super::MessageWriter::new(ShortWriter::new(usize::MAX));
+			writer.write_message_serialized(&header(1), &("a", [2_u64, 3]), &body.signature(), endianness).unwrap();
+			assert_eq!(writer.writer.buf, expected);
+		}
+
+		// A body that doesn't match its signature is not written.
+		let mut writer = super::MessageWriter::new(ShortWriter::new(usize::MAX));
+		let err = writer.write_message_serialized(&header(1), &("a", 2_u64), &body.signature(), crate::Endianness::Little).unwrap_err();
+		let super::WriteMessageError::Serialize(err) = err else { panic!("{err:?}") };
+		assert_eq!(err.to_string(), r#"value is a u64 but the signature is "at""#);
+		assert!(writer.writer.buf.is_empty());
+	}
+
+	#[test]
+	fn test_read_message_ref() {
+		let names: Vec<String> = (0..10_000).map(|i| format!("org.foo.Name{i}")).collect();
+		let body = crate::ToVariant::to_variant(&names);
+
+		let mut writer = super::MessageWriter::new(vec![]);
+		writer.write_message(&header(1), Some(&body), crate::Endianness::Little).unwrap();
+		writer.write_message(&header(2), Some(&body), crate::Endianness::Big).unwrap();
+
+		let mut reader = super::MessageReader::new(ChunkReader::new(&writer.writer, 4096));
+
+		let message = reader.read_message_ref().unwrap();
+		assert_eq!(message.header.serial, 1);
+		assert_eq!(message.endianness, crate::Endianness::Little);
+		let Some(crate::Variant::ArrayString(elements)) = &message.body else { panic!("{:?}", message.body) };
+		assert_eq!(elements.len(), names.len());
+		for (element, name) in elements.iter().zip(&names) {
+			assert!(matches!(element, std::borrow::Cow::Borrowed(element) if element == name), "{element:?}");
+		}
+
+		let (received_header, received_body) = message.into_owned();
+		assert_eq!(received_header.serial, 1);
+		assert_eq!(Vec::<String>::try_from(received_body.unwrap()).unwrap(), names);
+
+		let message = reader.read_message_ref().unwrap();
+		assert_eq!(message.header.serial, 2);
+		assert_eq!(message.endianness, crate::Endianness::Big);
+		let Some(crate::Variant::ArrayString(elements)) = &message.body else { panic!("{:?}", message.body) };
+		assert!(elements.iter().all(|element| matches!(element, std::borrow::Cow::Borrowed(_))));
+	}
+}
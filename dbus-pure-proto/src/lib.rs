@@ -13,21 +13,68 @@
 //! Use [`deserialize_message`] to parse a D-Bus message from raw bytes, and [`serialize_message`] to convert a D-Bus message to raw bytes.
 //!
 //! To actually connect to a bus and communicate with it, see the `dbus-pure` crate.
+//!
+//!
+//! # Thread safety
+//!
+//! The owned forms of the message types - [`MessageHeader<'static>`](MessageHeader), [`Variant<'static>`](Variant), [`Signature`]
+//! and [`ObjectPath<'static>`](ObjectPath) - as well as the error types are `Send` and `Sync`,
+//! so received messages can be handed off to other threads.
+
+mod builder;
+pub use builder::{
+	BuildMessageError,
+	ErrorBuilder,
+	MethodCallBuilder,
+	SignalBuilder,
+};
 
 pub(crate) mod de;
 pub use de::{
 	DeserializeError,
 };
 
+mod expected_signature;
+pub use expected_signature::{
+	ExpectedSignature,
+};
+
+mod from_variant;
+pub use from_variant::{
+	FromVariant,
+	FromVariantError,
+};
+
+pub mod introspect;
+
+mod interner;
+pub use interner::{
+	Interner,
+};
+
+mod io;
+pub use io::{
+	MessageReader,
+	MessageRef,
+	MessageWriter,
+	ReadMessageError,
+	WriteMessageError,
+};
+
 pub(crate) mod message;
 pub use message::{
 	deserialize_message,
+	deserialize_message_with_limits,
 	flags as message_flags,
 	MessageFlags,
 	MessageHeader,
 	MessageHeaderField,
+	message_endianness,
+	MessageLimits,
 	MessageType,
 	serialize_message,
+	serialize_message_unchecked,
+	serialize_message_with_limits,
 };
 
 pub(crate) mod ser;
@@ -35,16 +82,30 @@ pub use ser::{
 	SerializeError,
 };
 
+mod signature_deserializer;
+pub use signature_deserializer::{
+	from_slice_with_signature,
+};
+
+mod signature_serializer;
+pub use signature_serializer::{
+	to_writer_with_signature,
+};
+
 pub mod std2;
 
 mod to_variant;
 pub use to_variant::{
+	SortedDict,
 	ToVariant,
 };
 
+pub mod validate;
+
 mod variant;
 pub use variant::{
 	Variant,
+	VariantArrayIter,
 };
 
 mod variant_deserializer;
@@ -52,7 +113,7 @@ pub use variant_deserializer::{
 	VariantDeserializeError,
 };
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Endianness {
 	Big,
 	Little,
@@ -114,7 +175,9 @@ endianness_to_bytes! {
 }
 
 /// An object path.
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+///
+/// Object paths compare, hash and display the same as their string form, so a map keyed by `ObjectPath` can be queried with a `&str`.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct ObjectPath<'a>(pub std::borrow::Cow<'a, str>);
 
 impl<'de> ObjectPath<'de> {
@@ -125,18 +188,182 @@ impl<'de> ObjectPath<'de> {
 	fn into_owned(self) -> ObjectPath<'static> {
 		ObjectPath(self.0.into_owned().into())
 	}
+
+	fn into_owned_interned(self, interner: &'static crate::Interner) -> ObjectPath<'static> {
+		ObjectPath(interner.intern(self.0))
+	}
+}
+
+impl<'a> ObjectPath<'a> {
+	/// Create an object path from the given string, after checking that it is a valid object path.
+	///
+	/// A valid object path starts with `/`, and consists of elements separated by `/`. Each element must be non-empty
+	/// and only contain the characters `[A-Za-z0-9_]`. The path must not end with `/` unless it is the root path `/`.
+	pub fn new(path: impl Into<std::borrow::Cow<'a, str>>) -> Result<Self, InvalidObjectPath> {
+		let path = path.into();
+		crate::validate::validate_object_path(&path)?;
+		Ok(ObjectPath(path))
+	}
+
+	/// Create an object path from the given string without checking that it is a valid object path.
+	///
+	/// This is equivalent to constructing the `ObjectPath` directly. It is useful for paths that are known to be valid,
+	/// and for tests that deliberately send malformed paths.
+	pub fn new_unchecked(path: impl Into<std::borrow::Cow<'a, str>>) -> Self {
+		ObjectPath(path.into())
+	}
 }
 
 impl ObjectPath<'_> {
+	/// The parent of this object path, or `None` if this is the root path `/`.
+	///
+	/// For example, the parent of `/org/freedesktop` is `/org`, and the parent of `/org` is `/`.
+	pub fn parent(&self) -> Option<ObjectPath<'_>> {
+		if self.0 == "/" {
+			return None;
+		}
+
+		let parent = match self.0.rfind('/')? {
+			0 => "/",
+			i => &self.0[..i],
+		};
+		Some(ObjectPath(parent.into()))
+	}
+
+	/// The object path of the child of this object path with the given element.
+	///
+	/// For example, the child of `/org` with the element `freedesktop` is `/org/freedesktop`.
+	///
+	/// Returns an error if the element is empty or contains characters that are not valid in an object path, including `/`.
+	pub fn child(&self, element: &str) -> Result<ObjectPath<'static>, InvalidObjectPath> {
+		if element.is_empty() {
+			return Err(InvalidObjectPath::EmptyChildElement);
+		}
+
+		let mut path = String::with_capacity(self.0.len() + 1 + element.len());
+		path.push_str(&self.0);
+		if !path.ends_with('/') {
+			path.push('/');
+		}
+		let element_start = path.len();
+		path.push_str(element);
+
+		if let Some(i) = element.find('/') {
+			return Err(InvalidObjectPath::InvalidCharacter { character: '/', offset: element_start + i });
+		}
+
+		ObjectPath::new(path)
+	}
+
+	/// Iterate over the elements of this object path.
+	///
+	/// For example, the elements of `/org/freedesktop` are `org` and `freedesktop`. The root path `/` has no elements.
+	pub fn components(&self) -> impl Iterator<Item = &str> {
+		self.0.split('/').filter(|component| !component.is_empty())
+	}
+
+	/// Returns true if this object path is the given object path or one of its descendants.
+	///
+	/// Unlike `str::starts_with`, this only matches whole elements. For example, `/org/freedesktop` starts with `/org` but not with `/or`.
+	pub fn starts_with(&self, other: &ObjectPath<'_>) -> bool {
+		if other.0 == "/" {
+			return self.0.starts_with('/');
+		}
+
+		match self.0.strip_prefix(&*other.0) {
+			Some(rest) => rest.is_empty() || rest.starts_with('/'),
+			None => false,
+		}
+	}
+
 	fn serialize(&self, serializer: &mut crate::ser::Serializer<'_>) -> Result<(), crate::SerializeError> {
+		if serializer.validate_object_paths() {
+			crate::validate::validate_object_path(&self.0).map_err(|err| crate::SerializeError::InvalidObjectPath { path: self.0.clone().into_owned(), err })?;
+		}
+
 		serializer.serialize_string(&self.0)
 	}
 }
 
+impl AsRef<str> for ObjectPath<'_> {
+	fn as_ref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl std::borrow::Borrow<str> for ObjectPath<'_> {
+	fn borrow(&self) -> &str {
+		&self.0
+	}
+}
+
+impl std::fmt::Display for ObjectPath<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+impl<'a> TryFrom<&'a str> for ObjectPath<'a> {
+	type Error = InvalidObjectPath;
+
+	fn try_from(path: &'a str) -> Result<Self, Self::Error> {
+		ObjectPath::new(path)
+	}
+}
+
+impl TryFrom<String> for ObjectPath<'static> {
+	type Error = InvalidObjectPath;
+
+	fn try_from(path: String) -> Result<Self, Self::Error> {
+		ObjectPath::new(path)
+	}
+}
+
+/// An error from creating an [`ObjectPath`] from a string that is not a valid object path.
+///
+/// Offsets are byte offsets into the string.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvalidObjectPath {
+	/// The element given to [`ObjectPath::child`] is empty.
+	EmptyChildElement,
+
+	/// The `/` at the given offset immediately follows another `/`.
+	EmptyElement { offset: usize },
+
+	/// The character at the given offset is not one of `[A-Za-z0-9_/]`.
+	InvalidCharacter { character: char, offset: usize },
+
+	/// The path does not start with `/`.
+	MissingLeadingSlash,
+
+	/// The path ends with a `/` at the given offset, and is not the root path.
+	TrailingSlash { offset: usize },
+}
+
+impl std::fmt::Display for InvalidObjectPath {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			InvalidObjectPath::EmptyChildElement => f.write_str("object path child element is empty"),
+			InvalidObjectPath::EmptyElement { offset } => write!(f, "object path has an empty element at offset {offset}"),
+			InvalidObjectPath::InvalidCharacter { character, offset } => write!(f, "object path has invalid character {character:?} at offset {offset}"),
+			InvalidObjectPath::MissingLeadingSlash => f.write_str("object path does not start with '/'"),
+			InvalidObjectPath::TrailingSlash { offset } => write!(f, "object path has a trailing '/' at offset {offset}"),
+		}
+	}
+}
+
+impl std::error::Error for InvalidObjectPath {
+}
+
 /// A signature.
 ///
 /// Use `.to_string()` to get the string representation of the signature.
-#[derive(Clone, Debug, Eq, PartialEq)]
+///
+/// Signatures are ordered by the order of the variants of this enum, then by their contents.
+/// This is consistent with `Eq` but not with the ordering of their string representations.
+/// For example, `Signature::Array { .. }` (`a...`) is less than `Signature::Bool` (`b`), but `Signature::F64` (`d`) is less than `Signature::I16` (`n`)
+/// only because `F64` is declared before `I16`.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Signature {
 	Array { element: Box<Signature> },
 	Bool,
@@ -159,7 +386,125 @@ pub enum Signature {
 }
 
 impl Signature {
-	fn alignment(&self) -> usize {
+	/// The maximum number of arrays that a signature can be nested in, as specified by the D-Bus spec.
+	pub const MAX_ARRAY_DEPTH: usize = 32;
+
+	/// The maximum number of structs and dict entries that a signature can be nested in, as specified by the D-Bus spec.
+	pub const MAX_STRUCT_DEPTH: usize = 32;
+
+	/// The maximum length in bytes of the string representation of a signature, as specified by the D-Bus spec.
+	pub const MAX_LEN: usize = 255;
+
+	/// Returns true if this is the signature of a basic type, ie a type that is not a container.
+	///
+	/// Only basic types can be used as the keys of dict entries.
+	pub fn is_basic(&self) -> bool {
+		#[allow(clippy::match_same_arms)]
+		match self {
+			Signature::Array { .. } => false,
+			Signature::Bool => true,
+			Signature::DictEntry { .. } => false,
+			Signature::F64 => true,
+			Signature::I16 => true,
+			Signature::I32 => true,
+			Signature::I64 => true,
+			Signature::ObjectPath => true,
+			Signature::Signature => true,
+			Signature::String => true,
+			Signature::Struct { .. } => false,
+			Signature::Tuple { .. } => false,
+			Signature::U8 => true,
+			Signature::U16 => true,
+			Signature::U32 => true,
+			Signature::U64 => true,
+			Signature::UnixFd => true,
+			Signature::Variant => false,
+		}
+	}
+
+	/// Returns true if this is the signature of a container type, ie an array, dict entry, struct or variant.
+	///
+	/// A [`Signature::Tuple`] is neither basic nor a container, since it is a sequence of complete types rather than a single one.
+	pub fn is_container(&self) -> bool {
+		#[allow(clippy::match_same_arms)]
+		match self {
+			Signature::Array { .. } => true,
+			Signature::DictEntry { .. } => true,
+			Signature::Struct { .. } => true,
+			Signature::Tuple { .. } => false,
+			Signature::Variant => true,
+			_ => false,
+		}
+	}
+
+	/// The size in bytes of any value of this type when it is serialized, if all values of this type have the same size.
+	///
+	/// Strings, object paths, signatures, arrays and variants do not have a fixed size. Structs, dict entries and tuples have a fixed size
+	/// if all their fields do. Since the padding between their fields depends on where they start,
+	/// the size is computed for a value that starts at an offset aligned to 8 bytes, such as the start of a message body or of a struct.
+	pub fn fixed_size(&self) -> Option<usize> {
+		fn fields_size<'a>(fields: impl IntoIterator<Item = &'a Signature>) -> Option<usize> {
+			let mut size: usize = 0;
+			for field in fields {
+				let alignment = field.alignment();
+				size = size.div_ceil(alignment) * alignment;
+				size += field.fixed_size()?;
+			}
+			Some(size)
+		}
+
+		#[allow(clippy::match_same_arms)]
+		match self {
+			Signature::Array { .. } => None,
+			Signature::Bool => Some(4),
+			Signature::DictEntry { key, value } => fields_size([&**key, &**value]),
+			Signature::F64 => Some(8),
+			Signature::I16 => Some(2),
+			Signature::I32 => Some(4),
+			Signature::I64 => Some(8),
+			Signature::ObjectPath => None,
+			Signature::Signature => None,
+			Signature::String => None,
+			Signature::Struct { fields } => fields_size(fields),
+			Signature::Tuple { elements } => fields_size(elements),
+			Signature::U8 => Some(1),
+			Signature::U16 => Some(2),
+			Signature::U32 => Some(4),
+			Signature::U64 => Some(8),
+			Signature::UnixFd => Some(4),
+			Signature::Variant => None,
+		}
+	}
+
+	/// Iterate over the single complete types in this signature.
+	///
+	/// For a [`Signature::Tuple`], such as the signature of a message body, this yields its elements. Nested tuples are flattened,
+	/// since they have the same string representation as their elements. For any other signature, this yields the signature itself.
+	pub fn iter(&self) -> impl Iterator<Item = &Signature> {
+		let mut stack = vec![std::slice::from_ref(self).iter()];
+		std::iter::from_fn(move || loop {
+			match stack.last_mut()?.next() {
+				Some(Signature::Tuple { elements }) => stack.push(elements.iter()),
+				Some(signature) => return Some(signature),
+				None => { let _ = stack.pop(); },
+			}
+		})
+	}
+
+	/// The number of single complete types in this signature, ie the number of elements yielded by [`Signature::iter`].
+	///
+	/// For the signature of a method call's body, this is the number of parameters of the method.
+	pub fn arity(&self) -> usize {
+		self.iter().count()
+	}
+
+	/// The `n`th single complete type in this signature, ie the `n`th element yielded by [`Signature::iter`].
+	pub fn nth(&self, n: usize) -> Option<&Signature> {
+		self.iter().nth(n)
+	}
+
+	/// The alignment in bytes of values of this type when they are serialized.
+	pub fn alignment(&self) -> usize {
 		#[allow(clippy::match_same_arms)]
 		match self {
 			Signature::Array { .. } => 4,
@@ -258,13 +603,24 @@ impl std::fmt::Display for Signature {
 }
 
 impl std::str::FromStr for Signature {
-	type Err = ();
+	type Err = SignatureParseError;
 
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		fn from_inner(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Result<Signature, ()> {
-			match chars.next().ok_or(())? {
+		fn from_inner(
+			chars: &mut std::str::CharIndices<'_>,
+			(offset, c): (usize, char),
+			array_depth: usize,
+			struct_depth: usize,
+		) -> Result<Signature, SignatureParseError> {
+			match c {
 				'a' => {
-					let element = from_inner(chars)?;
+					let array_depth = array_depth + 1;
+					if array_depth > Signature::MAX_ARRAY_DEPTH {
+						return Err(SignatureParseError::ArrayDepthExceeded { offset });
+					}
+
+					let next = chars.next().ok_or(SignatureParseError::MissingArrayElement { offset })?;
+					let element = from_inner(chars, next, array_depth, struct_depth)?;
 					Ok(Signature::Array { element: Box::new(element) })
 				},
 
@@ -297,16 +653,24 @@ impl std::str::FromStr for Signature {
 				'y' => Ok(Signature::U8),
 
 				'(' => {
+					let struct_depth = struct_depth + 1;
+					if struct_depth > Signature::MAX_STRUCT_DEPTH {
+						return Err(SignatureParseError::StructDepthExceeded { offset });
+					}
+
 					let mut fields = vec![];
 
 					loop {
-						let next = chars.peek().copied();
-						if next == Some(')') {
-							let _ = chars.next();
+						let next = chars.next().ok_or(SignatureParseError::UnterminatedStruct { offset })?;
+						if next.1 == ')' {
+							if fields.is_empty() {
+								return Err(SignatureParseError::EmptyStruct { offset });
+							}
+
 							break;
 						}
 
-						let field = from_inner(chars)?;
+						let field = from_inner(chars, next, array_depth, struct_depth)?;
 						fields.push(field);
 					}
 
@@ -314,41 +678,163 @@ impl std::str::FromStr for Signature {
 				},
 
 				'{' => {
-					let key = from_inner(chars)?;
+					let struct_depth = struct_depth + 1;
+					if struct_depth > Signature::MAX_STRUCT_DEPTH {
+						return Err(SignatureParseError::StructDepthExceeded { offset });
+					}
 
-					let value = from_inner(chars)?;
+					let next = chars.next().ok_or(SignatureParseError::UnterminatedDictEntry { offset })?;
+					let key = from_inner(chars, next, array_depth, struct_depth)?;
+					if !key.is_basic() {
+						return Err(SignatureParseError::NonBasicDictEntryKey { offset: next.0 });
+					}
 
-					let next = chars.next();
-					if next != Some('}') {
-						return Err(());
+					let next = chars.next().ok_or(SignatureParseError::UnterminatedDictEntry { offset })?;
+					let value = from_inner(chars, next, array_depth, struct_depth)?;
+
+					match chars.next() {
+						Some((_, '}')) => (),
+						Some((offset, character)) => return Err(SignatureParseError::UnexpectedCharacter { character, offset }),
+						None => return Err(SignatureParseError::UnterminatedDictEntry { offset }),
 					}
 
 					Ok(Signature::DictEntry { key: Box::new(key), value: Box::new(value) })
 				},
 
-				_ => Err(()),
+				character => Err(SignatureParseError::UnexpectedCharacter { character, offset }),
 			}
 		}
 
-		let mut chars = s.chars().peekable();
-		if chars.peek().is_none() {
-			return Ok(Signature::Tuple { elements: vec![] });
+		if s.len() > Signature::MAX_LEN {
+			return Err(SignatureParseError::SignatureTooLong { len: s.len() });
 		}
 
-		let first = from_inner(&mut chars)?;
-		if chars.peek().is_some() {
-			let mut elements = vec![first];
-			while chars.peek().is_some() {
-				elements.push(from_inner(&mut chars)?);
+		let mut chars = s.char_indices();
+		let mut elements = vec![];
+		while let Some(next) = chars.next() {
+			if let (offset, ')' | '}') = next {
+				return Err(SignatureParseError::UnmatchedClosingBracket { offset });
 			}
+
+			elements.push(from_inner(&mut chars, next, 0, 0)?);
+		}
+
+		if elements.len() == 1 {
+			Ok(elements.pop().expect("elements has one element"))
+		}
+		else {
 			Ok(Signature::Tuple { elements })
 		}
+	}
+}
+
+/// Serializes the signature as its string form for human-readable formats, and as the bytes of its string form otherwise.
+///
+/// This is unrelated to how signatures are serialized in D-Bus messages.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Signature {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: serde::Serializer {
+		let signature = self.to_string();
+		if serializer.is_human_readable() {
+			serializer.serialize_str(&signature)
+		}
+		else {
+			serializer.serialize_bytes(signature.as_bytes())
+		}
+	}
+}
+
+/// Deserializes the signature from its string form. See the `serde::Serialize` impl.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Signature {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: serde::Deserializer<'de> {
+		struct Visitor;
+
+		impl serde::de::Visitor<'_> for Visitor {
+			type Value = Signature;
+
+			fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+				formatter.write_str("a D-Bus signature string")
+			}
+
+			fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> where E: serde::de::Error {
+				v.parse().map_err(|err| serde::de::Error::custom(format_args!("invalid signature {v:?}: {err}")))
+			}
+
+			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: serde::de::Error {
+				let v = std::str::from_utf8(v).map_err(|_| serde::de::Error::invalid_value(serde::de::Unexpected::Bytes(v), &self))?;
+				self.visit_str(v)
+			}
+		}
+
+		if deserializer.is_human_readable() {
+			deserializer.deserialize_str(Visitor)
+		}
 		else {
-			Ok(first)
+			deserializer.deserialize_bytes(Visitor)
+		}
+	}
+}
+
+/// An error from parsing a [`Signature`] from a string.
+///
+/// Offsets are byte offsets into the string.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SignatureParseError {
+	/// The array at the given offset is nested in more than [`Signature::MAX_ARRAY_DEPTH`] arrays.
+	ArrayDepthExceeded { offset: usize },
+
+	/// The struct at the given offset has no fields.
+	EmptyStruct { offset: usize },
+
+	/// An array type code at the given offset is not followed by the type of its elements.
+	MissingArrayElement { offset: usize },
+
+	/// The key of a dict entry, which starts at the given offset, is not a basic type.
+	NonBasicDictEntryKey { offset: usize },
+
+	/// The signature is longer than [`Signature::MAX_LEN`] bytes.
+	SignatureTooLong { len: usize },
+
+	/// The struct or dict entry at the given offset is nested in more than [`Signature::MAX_STRUCT_DEPTH`] structs and dict entries.
+	StructDepthExceeded { offset: usize },
+
+	/// The character at the given offset is not a valid type code in this position.
+	UnexpectedCharacter { character: char, offset: usize },
+
+	/// A closing `)` or `}` at the given offset does not have a matching opening `(` or `{`.
+	UnmatchedClosingBracket { offset: usize },
+
+	/// The dict entry that starts at the given offset is missing its closing `}`.
+	UnterminatedDictEntry { offset: usize },
+
+	/// The struct that starts at the given offset is missing its closing `)`.
+	UnterminatedStruct { offset: usize },
+}
+
+impl std::fmt::Display for SignatureParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SignatureParseError::ArrayDepthExceeded { offset } =>
+				write!(f, "array at offset {offset} exceeds the maximum array nesting depth of {}", Signature::MAX_ARRAY_DEPTH),
+			SignatureParseError::EmptyStruct { offset } => write!(f, "struct at offset {offset} has no fields"),
+			SignatureParseError::MissingArrayElement { offset } => write!(f, "array at offset {offset} is missing its element type"),
+			SignatureParseError::NonBasicDictEntryKey { offset } => write!(f, "dict entry key at offset {offset} is not a basic type"),
+			SignatureParseError::SignatureTooLong { len } =>
+				write!(f, "signature is {len} bytes long which exceeds the maximum of {} bytes", Signature::MAX_LEN),
+			SignatureParseError::StructDepthExceeded { offset } =>
+				write!(f, "struct at offset {offset} exceeds the maximum struct nesting depth of {}", Signature::MAX_STRUCT_DEPTH),
+			SignatureParseError::UnexpectedCharacter { character, offset } => write!(f, "unexpected character {character:?} at offset {offset}"),
+			SignatureParseError::UnmatchedClosingBracket { offset } => write!(f, "unmatched closing bracket at offset {offset}"),
+			SignatureParseError::UnterminatedDictEntry { offset } => write!(f, "dict entry at offset {offset} is not terminated"),
+			SignatureParseError::UnterminatedStruct { offset } => write!(f, "struct at offset {offset} is not terminated"),
 		}
 	}
 }
 
+impl std::error::Error for SignatureParseError {
+}
+
 impl Signature {
 	fn deserialize(deserializer: &mut crate::de::Deserializer<'_>) -> Result<Self, crate::DeserializeError> {
 		let len = deserializer.deserialize_u8()?;
@@ -366,13 +852,19 @@ impl Signature {
 
 		let signature =
 			signature.parse()
-			.map_err(|()| crate::DeserializeError::InvalidValue { expected: "a signature".into(), actual: signature })?;
+			.map_err(|err| crate::DeserializeError::InvalidSignature { signature, err })?;
 		Ok(signature)
 	}
 
 	fn serialize(&self, serializer: &mut crate::ser::Serializer<'_>) -> Result<(), crate::SerializeError> {
+		self.validate()?;
+
 		let signature_string = self.to_string();
 
+		if signature_string.len() > Signature::MAX_LEN {
+			return Err(crate::SerializeError::SignatureTooLong { len: signature_string.len() });
+		}
+
 		let len: u8 = signature_string.len().try_into().map_err(crate::SerializeError::ExceedsNumericLimits)?;
 
 		let data = std::iter::once(len).chain(signature_string.as_bytes().iter().copied()).chain(std::iter::once(b'\0'));
@@ -383,6 +875,33 @@ impl Signature {
 
 		Ok(())
 	}
+
+	/// Checks the constraints of the D-Bus spec that the `Signature` type itself cannot enforce.
+	fn validate(&self) -> Result<(), crate::SerializeError> {
+		match self {
+			Signature::Array { element } => element.validate(),
+
+			Signature::DictEntry { key, value } => {
+				if !key.is_basic() {
+					return Err(crate::SerializeError::NonBasicDictEntryKey { key: (**key).clone() });
+				}
+
+				value.validate()
+			},
+
+			Signature::Struct { fields } => {
+				if fields.is_empty() {
+					return Err(crate::SerializeError::EmptyStruct);
+				}
+
+				fields.iter().try_for_each(Signature::validate)
+			},
+
+			Signature::Tuple { elements } => elements.iter().try_for_each(Signature::validate),
+
+			_ => Ok(()),
+		}
+	}
 }
 
 /// An index into an array of file descriptors.
@@ -415,3 +934,1030 @@ impl UsizeAsU32 {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_object_path_new() {
+		for path in ["/", "/org", "/org/freedesktop/DBus", "/a_b/C0"] {
+			assert_eq!(crate::ObjectPath::new(path).unwrap().0, path);
+			assert_eq!(crate::ObjectPath::try_from(path.to_owned()).unwrap().0, path);
+		}
+
+		for (path, expected_err, expected_message) in [
+			("", crate::InvalidObjectPath::MissingLeadingSlash, "object path does not start with '/'"),
+			("org/freedesktop", crate::InvalidObjectPath::MissingLeadingSlash, "object path does not start with '/'"),
+			("/org/", crate::InvalidObjectPath::TrailingSlash { offset: 4 }, "object path has a trailing '/' at offset 4"),
+			("//", crate::InvalidObjectPath::EmptyElement { offset: 1 }, "object path has an empty element at offset 1"),
+			("/a//b", crate::InvalidObjectPath::EmptyElement { offset: 3 }, "object path has an empty element at offset 3"),
+			("/a-b", crate::InvalidObjectPath::InvalidCharacter { character: '-', offset: 2 }, "object path has invalid character '-' at offset 2"),
+			("/a/é", crate::InvalidObjectPath::InvalidCharacter { character: 'é', offset: 3 }, "object path has invalid character 'é' at offset 3"),
+		] {
+			let err = crate::ObjectPath::try_from(path).unwrap_err();
+			assert_eq!(err, expected_err, "{path}");
+			assert_eq!(err.to_string(), expected_message, "{path}");
+		}
+
+		assert_eq!(crate::ObjectPath::new_unchecked("not a path").0, "not a path");
+	}
+
+	#[test]
+	fn test_object_path_tree() {
+		let root = crate::ObjectPath::new("/").unwrap();
+		let org = crate::ObjectPath::new("/org").unwrap();
+		let freedesktop = crate::ObjectPath::new("/org/freedesktop").unwrap();
+
+		assert_eq!(root.parent(), None);
+		assert_eq!(org.parent(), Some(root.clone()));
+		assert_eq!(freedesktop.parent(), Some(org.clone()));
+		assert!(matches!(freedesktop.parent().unwrap().0, std::borrow::Cow::Borrowed(_)));
+
+		assert_eq!(root.child("org").unwrap(), org);
+		assert_eq!(org.child("freedesktop").unwrap(), freedesktop);
+		assert_eq!(org.child("").unwrap_err(), crate::InvalidObjectPath::EmptyChildElement);
+		assert_eq!(root.child("").unwrap_err(), crate::InvalidObjectPath::EmptyChildElement);
+		assert_eq!(org.child("a/b").unwrap_err(), crate::InvalidObjectPath::InvalidCharacter { character: '/', offset: 6 });
+		assert_eq!(root.child("a-b").unwrap_err(), crate::InvalidObjectPath::InvalidCharacter { character: '-', offset: 2 });
+
+		assert_eq!(root.components().count(), 0);
+		assert_eq!(freedesktop.components().collect::<Vec<_>>(), ["org", "freedesktop"]);
+
+		assert!(root.starts_with(&root));
+		assert!(freedesktop.starts_with(&root));
+		assert!(freedesktop.starts_with(&org));
+		assert!(freedesktop.starts_with(&freedesktop));
+		assert!(!org.starts_with(&freedesktop));
+		assert!(!root.starts_with(&org));
+		assert!(!freedesktop.starts_with(&crate::ObjectPath::new("/or").unwrap()));
+	}
+
+	#[test]
+	fn test_object_path_map_key() {
+		let mut btree_map = std::collections::BTreeMap::new();
+		let mut hash_map = std::collections::HashMap::new();
+		for (i, path) in ["/org/foo", "/org", "/org/bar", "/"].into_iter().enumerate() {
+			let path: crate::ObjectPath<'static> = path.to_owned().try_into().unwrap();
+			let _ = btree_map.insert(path.clone(), i);
+			let _ = hash_map.insert(path, i);
+		}
+
+		assert_eq!(btree_map.get("/org/foo"), Some(&0));
+		assert_eq!(hash_map.get("/org/foo"), Some(&0));
+		assert_eq!(hash_map.get("/org/baz"), None);
+
+		let paths: Vec<_> = btree_map.keys().map(ToString::to_string).collect();
+		assert_eq!(paths, ["/", "/org", "/org/bar", "/org/foo"]);
+	}
+
+	#[test]
+	fn test_serialize_invalid_object_path() {
+		fn header(path: &'static str) -> crate::MessageHeader<'static> {
+			crate::MessageHeader {
+				r#type: crate::MessageType::MethodCall { member: "Ping".into(), path: crate::ObjectPath::new_unchecked(path) },
+				flags: crate::message_flags::NONE,
+				body_len: 0,
+				serial: 1,
+				fields: (&[][..]).into(),
+			}
+		}
+
+		for path in ["foo", "/a//b"] {
+			let err = crate::serialize_message(&header(path), None, &mut vec![], crate::Endianness::Little).unwrap_err();
+			assert!(matches!(err, crate::SerializeError::InvalidObjectPath { path: ref actual, err: _ } if actual == path), "{err:?}");
+
+			let body = crate::Variant::ObjectPath(crate::ObjectPath::new_unchecked(path));
+			let err = crate::serialize_message(&header("/"), Some(&body), &mut vec![], crate::Endianness::Little).unwrap_err();
+			assert!(matches!(err, crate::SerializeError::InvalidObjectPath { path: ref actual, err: _ } if actual == path), "{err:?}");
+
+			let mut buf = vec![];
+			crate::serialize_message_unchecked(&header(path), Some(&body), &mut buf, crate::Endianness::Little).unwrap();
+			let (header, body, _) = crate::deserialize_message(&buf).unwrap();
+			assert!(matches!(header.r#type, crate::MessageType::MethodCall { path: crate::ObjectPath(actual), .. } if actual == path));
+			assert_eq!(body, Some(crate::Variant::ObjectPath(crate::ObjectPath::new_unchecked(path))));
+		}
+	}
+
+	#[test]
+	fn test_serialize_nul_in_string() {
+		fn header(name: &'static str) -> crate::MessageHeader<'static> {
+			crate::MessageHeader {
+				r#type: crate::MessageType::Error { name: name.into(), reply_serial: 1 },
+				flags: crate::message_flags::NONE,
+				body_len: 0,
+				serial: 2,
+				fields: (&[][..]).into(),
+			}
+		}
+
+		let body = crate::Variant::String("a\0b".into());
+		let err = crate::serialize_message(&header("org.freedesktop.DBus.Error.Failed"), Some(&body), &mut vec![], crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, crate::SerializeError::NulInString { position: 1 }), "{err:?}");
+		assert_eq!(err.to_string(), "string contains a nul byte at position 1");
+
+		let err = crate::serialize_message(&header("org.freedesktop\0.Failed"), None, &mut vec![], crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, crate::SerializeError::NulInString { position: 15 }), "{err:?}");
+	}
+
+	#[test]
+	fn test_reserved_local() {
+		fn header(interface: &'static str, path: &'static str) -> crate::MessageHeader<'static> {
+			crate::MessageHeader {
+				r#type: crate::MessageType::Signal { interface: interface.into(), member: "Disconnected".into(), path: crate::ObjectPath(path.into()) },
+				flags: crate::message_flags::NONE,
+				body_len: 0,
+				serial: 1,
+				fields: (&[][..]).into(),
+			}
+		}
+
+		let err = crate::serialize_message(&header(crate::validate::LOCAL_INTERFACE, "/org/foo"), None, &mut vec![], crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, crate::SerializeError::ReservedLocalInterface), "{err:?}");
+
+		let err = crate::serialize_message(&header("org.foo", crate::validate::LOCAL_PATH), None, &mut vec![], crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, crate::SerializeError::ReservedLocalPath), "{err:?}");
+
+		// Messages that use them can still be received.
+		let mut buf = vec![];
+		crate::serialize_message_unchecked(
+			&header(crate::validate::LOCAL_INTERFACE, crate::validate::LOCAL_PATH),
+			None,
+			&mut buf,
+			crate::Endianness::Little,
+		).unwrap();
+		let (received, _, _) = crate::deserialize_message(&buf).unwrap();
+		assert_eq!(received.into_owned(), header(crate::validate::LOCAL_INTERFACE, crate::validate::LOCAL_PATH));
+	}
+
+	#[test]
+	fn test_body_len_mismatch() {
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::MethodReturn { reply_serial: 1 },
+			flags: crate::message_flags::NONE,
+			body_len: 0,
+			serial: 2,
+			fields: vec![].into(),
+		};
+
+		let set_body_len = |buf: &mut [u8], body_len: u32| buf[4..8].copy_from_slice(&body_len.to_le_bytes());
+
+		// The signature describes more bytes than the body has.
+		let mut buf = vec![];
+		crate::serialize_message(&header, Some(&crate::Variant::U64(5)), &mut buf, crate::Endianness::Little).unwrap();
+		set_body_len(&mut buf, 4);
+		buf.truncate(buf.len() - 4);
+		let err = crate::deserialize_message(&buf).unwrap_err();
+		let crate::DeserializeError::InvalidBody { signature: crate::Signature::U64, offset: 32, err } = err else { panic!("{err:?}"); };
+		assert!(matches!(*err, crate::DeserializeError::EndOfInput), "{err:?}");
+
+		// The signature describes a body, but there is none.
+		let mut buf = vec![];
+		crate::serialize_message(&header, Some(&crate::Variant::U32(5)), &mut buf, crate::Endianness::Little).unwrap();
+		set_body_len(&mut buf, 0);
+		buf.truncate(buf.len() - 4);
+		let err = crate::deserialize_message(&buf).unwrap_err();
+		let crate::DeserializeError::InvalidBody { signature: crate::Signature::U32, offset: 32, err } = err else { panic!("{err:?}"); };
+		assert!(matches!(*err, crate::DeserializeError::EndOfInput), "{err:?}");
+
+		// An empty signature does not describe a body.
+		let empty_signature_header = crate::MessageHeader {
+			fields: vec![crate::MessageHeaderField::Signature(crate::Signature::Tuple { elements: vec![] })].into(),
+			..header.clone()
+		};
+		let mut buf = vec![];
+		crate::serialize_message(&empty_signature_header, None, &mut buf, crate::Endianness::Little).unwrap();
+		let (deserialized, body, read) = crate::deserialize_message(&buf).unwrap();
+		assert_eq!(deserialized.signature(), Some(&crate::Signature::Tuple { elements: vec![] }));
+		assert_eq!(body, None);
+		assert_eq!(read, buf.len());
+
+		// The signature describes fewer bytes than the body has.
+		let mut buf = vec![];
+		crate::serialize_message(&header, Some(&crate::Variant::U32(5)), &mut buf, crate::Endianness::Little).unwrap();
+		set_body_len(&mut buf, 8);
+		buf.extend_from_slice(&[0; 4]);
+		let err = crate::deserialize_message(&buf).unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::TrailingBodyBytes { unread: 4 }), "{err:?}");
+
+		// Round-trip with the correct length
+		set_body_len(&mut buf, 4);
+		buf.truncate(buf.len() - 4);
+		let (_, body, read) = crate::deserialize_message(&buf).unwrap();
+		assert_eq!(body, Some(crate::Variant::U32(5)));
+		assert_eq!(read, buf.len());
+	}
+
+	#[test]
+	fn test_message_endianness() {
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::MethodReturn { reply_serial: 1 },
+			flags: crate::message_flags::NONE,
+			body_len: 0,
+			serial: 2,
+			fields: vec![].into(),
+		};
+
+		for endianness in [crate::Endianness::Big, crate::Endianness::Little] {
+			let mut buf = vec![];
+			crate::serialize_message(&header, Some(&crate::Variant::U32(0x0102_0304)), &mut buf, endianness).unwrap();
+			assert_eq!(crate::message_endianness(&buf).unwrap(), endianness);
+
+			let (_, body, _) = crate::deserialize_message(&buf).unwrap();
+			assert_eq!(body, Some(crate::Variant::U32(0x0102_0304)));
+		}
+
+		let err = crate::message_endianness(b"x").unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::InvalidValue { .. }), "{err:?}");
+		let err = crate::message_endianness(b"").unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::EndOfInput), "{err:?}");
+	}
+
+	#[test]
+	fn test_duplicate_header_field() {
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::MethodCall { member: "Foo".into(), path: crate::ObjectPath("/org/foo".into()) },
+			flags: crate::message_flags::NONE,
+			body_len: 0,
+			serial: 1,
+			fields: vec![crate::MessageHeaderField::Path(crate::ObjectPath("/org/bar".into()))].into(),
+		};
+		let mut buf = vec![];
+		crate::serialize_message_unchecked(&header, None, &mut buf, crate::Endianness::Little).unwrap();
+		let err = crate::deserialize_message(&buf).unwrap_err();
+		let crate::DeserializeError::InvalidHeader { offset: _, field: None, err } = err else { panic!("{err:?}"); };
+		assert!(matches!(*err, crate::DeserializeError::DuplicateHeaderField { code: 0x01 }), "{err:?}");
+		assert_eq!(err.to_string(), "message header contains header field 1 more than once");
+	}
+
+	#[test]
+	fn test_invalid_header_field() {
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::Signal { interface: "org.foo".into(), member: "Changed".into(), path: crate::ObjectPath("/org/foo".into()) },
+			flags: crate::message_flags::NONE,
+			body_len: 0,
+			serial: 1,
+			fields: vec![crate::MessageHeaderField::Unknown { code: 0x05, value: crate::Variant::String("5".into()) }].into(),
+		};
+		let mut buf = vec![];
+		crate::serialize_message(&header, None, &mut buf, crate::Endianness::Little).unwrap();
+
+		// The REPLY_SERIAL field is the first field, so its value ends at offset 16 (the fixed part of the header) + 1 (the field code)
+		// + 3 (the signature "s") + 4 (the string length) + 2 (the string "5" and its nul terminator).
+		let err = crate::deserialize_message(&buf).unwrap_err();
+		assert_eq!(err.to_string(), "could not deserialize header field 5 of message header at offset 26");
+		let crate::DeserializeError::InvalidHeader { offset: 26, field: Some(0x05), err } = err else { panic!("{err:?}"); };
+		assert!(matches!(*err, crate::DeserializeError::InvalidValue { .. }), "{err:?}");
+		assert_eq!(err.to_string(), r#"expected a u32 but got String("5")"#);
+
+		// A header that is cut short by its fields array length is malformed rather than incomplete.
+		let mut buf = vec![];
+		crate::serialize_message(&header, None, &mut buf, crate::Endianness::Little).unwrap();
+		buf[12..16].copy_from_slice(&4_u32.to_le_bytes());
+		let err = crate::deserialize_message(&buf).unwrap_err();
+		let crate::DeserializeError::InvalidHeader { offset: _, field: Some(0x05), err } = err else { panic!("{err:?}"); };
+		assert!(matches!(*err, crate::DeserializeError::EndOfInput), "{err:?}");
+	}
+
+	#[test]
+	fn test_unknown_message_type() {
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::Unknown { r#type: 0x05 },
+			flags: crate::message_flags::NONE,
+			body_len: 0,
+			serial: 1,
+			fields: vec![crate::MessageHeaderField::Member("Foo".into())].into(),
+		};
+		let mut buf = vec![];
+		crate::serialize_message(&header, Some(&crate::Variant::U32(5)), &mut buf, crate::Endianness::Little).unwrap();
+		assert_eq!(buf[1], 0x05);
+
+		// Messages of unknown types are deserialized with all their header fields, so that they can be ignored.
+		let (deserialized, body, _) = crate::deserialize_message(&buf).unwrap();
+		assert!(matches!(deserialized.r#type, crate::MessageType::Unknown { r#type: 0x05 }), "{deserialized:?}");
+		assert_eq!(deserialized.member(), Some("Foo"));
+		assert_eq!(body, Some(crate::Variant::U32(5)));
+
+		// The message type 0 is invalid rather than unknown.
+		buf[1] = 0x00;
+		let err = crate::deserialize_message(&buf).unwrap_err();
+		let crate::DeserializeError::InvalidHeader { offset: _, field: None, err } = err else { panic!("{err:?}"); };
+		assert!(matches!(*err, crate::DeserializeError::InvalidValue { .. }), "{err:?}");
+		assert_eq!(err.to_string(), "expected a non-zero message type but got 0x00");
+	}
+
+	#[test]
+	fn test_zero_serial() {
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::MethodReturn { reply_serial: 1 },
+			flags: crate::message_flags::NONE,
+			body_len: 0,
+			serial: 0,
+			fields: vec![].into(),
+		};
+
+		let err = crate::serialize_message(&header, None, &mut vec![], crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, crate::SerializeError::ZeroSerial), "{err:?}");
+
+		let mut buf = vec![];
+		crate::serialize_message_unchecked(&header, None, &mut buf, crate::Endianness::Little).unwrap();
+		let err = crate::deserialize_message(&buf).unwrap_err();
+		assert_eq!(err.to_string(), "could not deserialize message header at offset 12");
+		let crate::DeserializeError::InvalidHeader { offset: 12, field: None, err } = err else { panic!("{err:?}"); };
+		assert!(matches!(*err, crate::DeserializeError::InvalidValue { .. }), "{err:?}");
+		assert_eq!(err.to_string(), "expected a non-zero serial but got 0");
+	}
+
+	#[test]
+	fn test_max_length_prefix() {
+		let no_limits = crate::MessageLimits { max_message_len: usize::MAX, max_array_len: usize::MAX };
+
+		// On 32-bit targets, adding the length to the position overflows. On 64-bit targets, the input is merely too short.
+		// Either way, deserializing must fail without panicking.
+		let mut buf = vec![0; 8];
+		buf[4..].copy_from_slice(&u32::MAX.to_le_bytes());
+		let deserializer = |buf| {
+			let mut deserializer = crate::de::Deserializer::new(buf, 4, crate::Endianness::Little);
+			deserializer.set_max_array_len(no_limits.max_array_len);
+			deserializer
+		};
+
+		let err = deserializer(&buf).deserialize_array_u8().unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::EndOfInput | crate::DeserializeError::LengthOverflow), "{err:?}");
+
+		let err = deserializer(&buf).deserialize_string().unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::EndOfInput | crate::DeserializeError::LengthOverflow), "{err:?}");
+
+		let err = deserializer(&buf).deserialize_array(1, crate::de::Deserializer::deserialize_u8).unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::EndOfInput | crate::DeserializeError::LengthOverflow), "{err:?}");
+
+		// With the default limits, the length is rejected outright.
+		let err = crate::de::Deserializer::new(&buf, 4, crate::Endianness::Little).deserialize_string().unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::ArrayTooLong { len: 0xffff_ffff, max: _ }), "{err:?}");
+
+		// A header with the maximum body length and header fields length
+		let mut buf = b"l\x02\x00\x01".to_vec();
+		buf.extend_from_slice(&u32::MAX.to_le_bytes());
+		buf.extend_from_slice(&1_u32.to_le_bytes());
+		buf.extend_from_slice(&u32::MAX.to_le_bytes());
+		buf.extend_from_slice(&[0; 16]);
+		let err = crate::deserialize_message_with_limits(&buf, &no_limits).unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::EndOfInput | crate::DeserializeError::LengthOverflow), "{err:?}");
+		let err = crate::deserialize_message(&buf).unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::MessageTooLong { .. }), "{err:?}");
+	}
+
+	#[test]
+	fn test_limits() {
+		// A METHOD_RETURN header that declares a 3 GiB body. The message is rejected as soon as the fixed part of the header is available.
+		let mut buf = b"l\x02\x01\x01".to_vec();
+		buf.extend_from_slice(&0xc000_0000_u32.to_le_bytes());
+		buf.extend_from_slice(&1_u32.to_le_bytes());
+		buf.extend_from_slice(&0_u32.to_le_bytes());
+		let err = crate::deserialize_message(&buf).unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::MessageTooLong { len: 0xc000_0010, max: 0x0800_0000 }), "{err:?}");
+
+		// Until then, more input is needed.
+		let err = crate::deserialize_message(&buf[..12]).unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::EndOfInput), "{err:?}");
+
+		// Arrays that declare a length of 500 MB
+		let mut buf = 500_000_000_u32.to_le_bytes().to_vec();
+		buf.extend_from_slice(&[0; 4]);
+		let signature = crate::Signature::Array { element: Box::new(crate::Signature::U8) };
+		let err = crate::Variant::deserialize(&mut crate::de::Deserializer::new(&buf, 0, crate::Endianness::Little), &signature).unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::ArrayTooLong { len: 500_000_000, max: 0x0400_0000 }), "{err:?}");
+		let signature = crate::Signature::Array { element: Box::new(crate::Signature::U32) };
+		let err = crate::Variant::deserialize(&mut crate::de::Deserializer::new(&buf, 0, crate::Endianness::Little), &signature).unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::ArrayTooLong { len: 500_000_000, max: 0x0400_0000 }), "{err:?}");
+
+		// Custom limits
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::MethodReturn { reply_serial: 1 },
+			flags: crate::message_flags::NONE,
+			body_len: 0,
+			serial: 2,
+			fields: vec![].into(),
+		};
+		let body = crate::Variant::ArrayU8((&[0_u8; 100][..]).into());
+		let mut buf = vec![];
+		crate::serialize_message(&header, Some(&body), &mut buf, crate::Endianness::Little).unwrap();
+		let message_len = buf.len();
+
+		let limits = crate::MessageLimits { max_message_len: message_len, max_array_len: 100 };
+		let _ = crate::deserialize_message_with_limits(&buf, &limits).unwrap();
+
+		let limits = crate::MessageLimits { max_message_len: message_len - 1, max_array_len: 100 };
+		let err = crate::deserialize_message_with_limits(&buf, &limits).unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::MessageTooLong { len, max } if len == message_len && max == message_len - 1), "{err:?}");
+
+		let mut short_buf = b"prefix".to_vec();
+		let err = crate::serialize_message_with_limits(&header, Some(&body), &mut short_buf, crate::Endianness::Little, &limits).unwrap_err();
+		assert!(matches!(err, crate::SerializeError::MessageTooLong { len, max } if len == message_len && max == message_len - 1), "{err:?}");
+		assert_eq!(short_buf, b"prefix");
+
+		let limits = crate::MessageLimits { max_message_len: message_len, max_array_len: 99 };
+		let err = crate::deserialize_message_with_limits(&buf, &limits).unwrap_err();
+		let crate::DeserializeError::InvalidBody { signature: _, offset: _, err } = err else { panic!("{err:?}"); };
+		assert!(matches!(*err, crate::DeserializeError::ArrayTooLong { len: 100, max: 99 }), "{err:?}");
+	}
+
+	#[test]
+	fn test_serialize_message_twice() {
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::MethodCall { member: "Foo".into(), path: crate::ObjectPath("/org/foo".into()) },
+			flags: crate::message_flags::NONE,
+			body_len: 0,
+			serial: 1,
+			fields: vec![crate::MessageHeaderField::Destination("org.foo".into())].into(),
+		};
+		let original = header.clone();
+		let body = crate::Variant::String("bar".into());
+
+		let mut first = vec![];
+		crate::serialize_message(&header, Some(&body), &mut first, crate::Endianness::Little).unwrap();
+		assert_eq!(header, original);
+
+		let mut second = vec![];
+		crate::serialize_message(&header, Some(&body), &mut second, crate::Endianness::Little).unwrap();
+		assert_eq!(first, second);
+
+		let (received, _, _) = crate::deserialize_message(&second).unwrap();
+		assert_eq!(received.body_len, 8);
+		assert_eq!(received.fields.len(), 2);
+		assert_eq!(received.member(), Some("Foo"));
+	}
+
+	/// Messages of every type, with bodies that need padding at various alignments.
+	fn serialize_corpus() -> Vec<(crate::MessageHeader<'static>, Option<crate::Variant<'static>>, crate::Endianness)> {
+		vec![
+			(
+				crate::MessageHeader {
+					r#type: crate::MessageType::MethodCall { member: "GetAll".into(), path: crate::ObjectPath("/org/foo".into()) },
+					flags: crate::message_flags::NONE,
+					body_len: 0,
+					serial: 1,
+					fields: vec![
+						crate::MessageHeaderField::Destination("org.foo".into()),
+						crate::MessageHeaderField::Interface("org.freedesktop.DBus.Properties".into()),
+					].into(),
+				},
+				Some(crate::Variant::tuple([crate::Variant::String("org.foo.Bar".into()), crate::Variant::U32(5)])),
+				crate::Endianness::Little,
+			),
+			(
+				crate::MessageHeader {
+					r#type: crate::MessageType::MethodReturn { reply_serial: 1 },
+					flags: crate::message_flags::NO_REPLY_EXPECTED,
+					body_len: 0,
+					serial: 2,
+					fields: vec![crate::MessageHeaderField::Destination(":1.5".into())].into(),
+				},
+				None,
+				crate::Endianness::Little,
+			),
+			(
+				crate::MessageHeader {
+					r#type: crate::MessageType::Error { name: "org.foo.Error.Failed".into(), reply_serial: 3 },
+					flags: crate::message_flags::NO_REPLY_EXPECTED,
+					body_len: 0,
+					serial: 4,
+					fields: vec![].into(),
+				},
+				Some(crate::Variant::String("it failed".into())),
+				crate::Endianness::Big,
+			),
+			(
+				crate::MessageHeader {
+					r#type: crate::MessageType::Signal { interface: "org.foo".into(), member: "Changed".into(), path: crate::ObjectPath("/org/foo".into()) },
+					flags: crate::message_flags::NO_REPLY_EXPECTED,
+					body_len: 0,
+					serial: 5,
+					fields: vec![].into(),
+				},
+				Some(crate::Variant::tuple([
+					crate::Variant::from_map_iter(crate::Signature::String, crate::Signature::Variant, [
+						(crate::Variant::String("a".into()), crate::Variant::Variant(Box::new(crate::Variant::U8(1)).into())),
+						(crate::Variant::String("b".into()), crate::Variant::Variant(Box::new(crate::Variant::U64(2)).into())),
+					]).unwrap(),
+					crate::Variant::structure([crate::Variant::U8(3), crate::Variant::U64(4)]),
+				])),
+				crate::Endianness::Big,
+			),
+		]
+	}
+
+	#[test]
+	fn test_serialize_message_corpus() {
+		// The output of the implementation that serialized the body into a separate buffer and then copied it after the header.
+		let expected: [&[u8]; 4] = [
+			b"l\x01\x00\x01\x14\x00\x00\x00\x01\x00\x00\x00h\x00\x00\x00\x06\x01s\x00\x07\x00\x00\x00org.foo\x00\x02\x01s\x00\x1f\x00\x00\x00org.freedesktop.DBus.Properties\x00\x03\x01s\x00\x06\x00\x00\x00GetAll\x00\x00\x01\x01o\x00\x08\x00\x00\x00/org/foo\x00\x00\x00\x00\x00\x00\x00\x00\x08\x01g\x00\x02su\x00\x0b\x00\x00\x00org.foo.Bar\x00\x05\x00\x00\x00",
+			b"l\x02\x01\x01\x00\x00\x00\x00\x02\x00\x00\x00\x18\x00\x00\x00\x06\x01s\x00\x04\x00\x00\x00:1.5\x00\x00\x00\x00\x05\x01u\x00\x01\x00\x00\x00",
+			b"B\x03\x01\x01\x00\x00\x00\x0e\x00\x00\x00\x04\x00\x00\x00/\x04\x01s\x00\x00\x00\x00\x14org.foo.Error.Failed\x00\x00\x00\x00\x05\x01u\x00\x00\x00\x00\x03\x08\x01g\x00\x01s\x00\x00\x00\x00\x00\tit failed\x00",
+			b"B\x04\x01\x01\x00\x00\x00@\x00\x00\x00\x05\x00\x00\x00G\x02\x01s\x00\x00\x00\x00\x07org.foo\x00\x03\x01s\x00\x00\x00\x00\x07Changed\x00\x01\x01o\x00\x00\x00\x00\x08/org/foo\x00\x00\x00\x00\x00\x00\x00\x00\x08\x01g\x00\ta{sv}(yt)\x00\x00\x00\x00\x00(\x00\x00\x00\x00\x00\x00\x00\x01a\x00\x01y\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01b\x00\x01t\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x04",
+		];
+
+		let corpus = serialize_corpus();
+		assert_eq!(corpus.len(), expected.len());
+
+		for ((header, body, endianness), expected) in corpus.into_iter().zip(expected) {
+			let mut buf = vec![];
+			crate::serialize_message(&header, body.as_ref(), &mut buf, endianness).unwrap();
+			assert_eq!(buf, expected, "{:?}", buf.escape_ascii().to_string());
+
+			// Also when appended to a buffer that doesn't end at a multiple of 8
+			let mut buf = b"prefix".to_vec();
+			crate::serialize_message(&header, body.as_ref(), &mut buf, endianness).unwrap();
+			assert_eq!(&buf[b"prefix".len()..], expected);
+		}
+	}
+
+	#[test]
+	fn test_serialize_message_body_in_place() {
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::MethodReturn { reply_serial: 1 },
+			flags: crate::message_flags::NO_REPLY_EXPECTED,
+			body_len: 0,
+			serial: 2,
+			fields: vec![].into(),
+		};
+		let body = crate::Variant::ArrayU8(vec![0xAA_u8; 1024 * 1024].into());
+
+		// The buffer is grown exactly once to fit the body after the header, rather than repeatedly as the body is written,
+		// so its capacity is exactly the length of the message.
+		let mut buf = vec![];
+		crate::serialize_message(&header, Some(&body), &mut buf, crate::Endianness::Little).unwrap();
+		assert_eq!(buf.capacity(), buf.len());
+
+		let (received, received_body, _) = crate::deserialize_message(&buf).unwrap();
+		assert_eq!(received.body_len, 4 + 1024 * 1024);
+		assert_eq!(received_body, Some(body));
+
+		// A body that fails to serialize leaves the buffer as it was.
+		let body = crate::Variant::tuple([crate::Variant::U32(1), crate::Variant::String("a\0b".into())]);
+		let mut buf = b"prefix".to_vec();
+		let _ = crate::serialize_message(&header, Some(&body), &mut buf, crate::Endianness::Little).unwrap_err();
+		assert_eq!(buf, b"prefix");
+	}
+
+	#[test]
+	fn test_message_header_accessors() {
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::Signal { interface: "org.foo".into(), member: "Changed".into(), path: crate::ObjectPath("/org/foo".into()) },
+			flags: crate::message_flags::NONE,
+			body_len: 0,
+			serial: 1,
+			fields: vec![
+				crate::MessageHeaderField::Sender(":1.5".into()),
+				crate::MessageHeaderField::Unknown { code: 0x7f, value: crate::Variant::U8(3) },
+			].into(),
+		};
+
+		// The interface, member and path are only in `r#type`
+		assert_eq!(header.interface(), Some("org.foo"));
+		assert_eq!(header.member(), Some("Changed"));
+		assert_eq!(header.path(), Some(&crate::ObjectPath("/org/foo".into())));
+		assert_eq!(header.sender(), Some(":1.5"));
+		assert_eq!(header.destination(), None);
+		assert_eq!(header.reply_serial(), None);
+		assert_eq!(header.signature(), None);
+		assert_eq!(header.field(0x7f), Some(&crate::Variant::U8(3)));
+		assert_eq!(header.field(0x7e), None);
+
+		let mut buf = vec![];
+		crate::serialize_message(&header, Some(&crate::Variant::U32(5)), &mut buf, crate::Endianness::Little).unwrap();
+
+		// After deserialization, they're still only in `r#type`
+		let (header, _, _) = crate::deserialize_message(&buf).unwrap();
+		assert!(!header.fields.iter().any(|field| matches!(field, crate::MessageHeaderField::Interface(_))));
+		assert_eq!(header.interface(), Some("org.foo"));
+		assert_eq!(header.member(), Some("Changed"));
+		assert_eq!(header.path(), Some(&crate::ObjectPath("/org/foo".into())));
+		assert_eq!(header.sender(), Some(":1.5"));
+		assert_eq!(header.signature(), Some(&crate::Signature::U32));
+		assert_eq!(header.unix_fds(), None);
+		assert_eq!(header.field(0x7f), Some(&crate::Variant::U8(3)));
+
+		let header = crate::MessageHeader {
+			r#type: crate::MessageType::Error { name: "org.foo.Error".into(), reply_serial: 3 },
+			flags: crate::message_flags::NONE,
+			body_len: 0,
+			serial: 4,
+			fields: vec![crate::MessageHeaderField::Destination(":1.5".into()), crate::MessageHeaderField::UnixFds(2)].into(),
+		};
+		assert_eq!(header.error_name(), Some("org.foo.Error"));
+		assert_eq!(header.reply_serial(), Some(3));
+		assert_eq!(header.destination(), Some(":1.5"));
+		assert_eq!(header.unix_fds(), Some(2));
+		assert_eq!(header.member(), None);
+		assert_eq!(header.path(), None);
+	}
+
+	#[test]
+	fn test_message_flags() {
+		let mut flags = crate::message_flags::NO_REPLY_EXPECTED | crate::message_flags::ALLOW_INTERACTIVE_AUTHORIZATION;
+		assert_eq!(flags.bits(), 0x05);
+		assert!(flags.contains(crate::message_flags::NO_REPLY_EXPECTED));
+		assert!(!flags.contains(crate::message_flags::NO_AUTO_START));
+		assert!(!flags.contains(crate::message_flags::NO_REPLY_EXPECTED | crate::message_flags::NO_AUTO_START));
+		assert!(flags.contains(crate::message_flags::NONE));
+		assert_eq!(flags.to_string(), "NO_REPLY_EXPECTED | ALLOW_INTERACTIVE_AUTHORIZATION");
+
+		flags.insert(crate::message_flags::NO_AUTO_START);
+		assert_eq!(flags.to_string(), "NO_REPLY_EXPECTED | NO_AUTO_START | ALLOW_INTERACTIVE_AUTHORIZATION");
+
+		flags.remove(crate::message_flags::NO_REPLY_EXPECTED | crate::message_flags::ALLOW_INTERACTIVE_AUTHORIZATION);
+		assert_eq!(flags, crate::message_flags::NO_AUTO_START);
+		assert_eq!(flags.to_string(), "NO_AUTO_START");
+
+		flags.remove(crate::message_flags::NO_AUTO_START);
+		assert!(flags.is_empty());
+		assert_eq!(flags, crate::message_flags::NONE);
+		assert_eq!(flags.to_string(), "NONE");
+
+		assert_eq!(crate::MessageFlags::from_bits(0x03), Some(crate::message_flags::NO_REPLY_EXPECTED | crate::message_flags::NO_AUTO_START));
+		assert_eq!(crate::MessageFlags::from_bits(0x81), None);
+
+		let flags = crate::MessageFlags::from_bits_retain(0x81);
+		assert_eq!(flags.unknown_bits(), 0x80);
+		assert_eq!(flags.to_string(), "NO_REPLY_EXPECTED | 0x80");
+		assert_eq!(crate::MessageFlags::from_bits_retain(0x40).to_string(), "0x40");
+
+		// Unknown bits in received messages are preserved.
+		let mut buf = vec![];
+		crate::serialize_message(
+			&crate::MessageHeader {
+				r#type: crate::MessageType::MethodReturn { reply_serial: 1 },
+				flags,
+				body_len: 0,
+				serial: 2,
+				fields: vec![].into(),
+			},
+			None,
+			&mut buf,
+			crate::Endianness::Little,
+		).unwrap();
+		let (header, _, _) = crate::deserialize_message(&buf).unwrap();
+		assert_eq!(header.flags, flags);
+	}
+
+	#[test]
+	fn test_signature_parse_error() {
+		for (signature, expected_err, expected_message) in [
+			("a", crate::SignatureParseError::MissingArrayElement { offset: 0 }, "array at offset 0 is missing its element type"),
+			("sa", crate::SignatureParseError::MissingArrayElement { offset: 1 }, "array at offset 1 is missing its element type"),
+			("s)", crate::SignatureParseError::UnmatchedClosingBracket { offset: 1 }, "unmatched closing bracket at offset 1"),
+			("}", crate::SignatureParseError::UnmatchedClosingBracket { offset: 0 }, "unmatched closing bracket at offset 0"),
+			("sz", crate::SignatureParseError::UnexpectedCharacter { character: 'z', offset: 1 }, "unexpected character 'z' at offset 1"),
+			("{su)", crate::SignatureParseError::UnexpectedCharacter { character: ')', offset: 3 }, "unexpected character ')' at offset 3"),
+			("(s}", crate::SignatureParseError::UnexpectedCharacter { character: '}', offset: 2 }, "unexpected character '}' at offset 2"),
+			("a{s", crate::SignatureParseError::UnterminatedDictEntry { offset: 1 }, "dict entry at offset 1 is not terminated"),
+			("a{sv", crate::SignatureParseError::UnterminatedDictEntry { offset: 1 }, "dict entry at offset 1 is not terminated"),
+			("(s(u)", crate::SignatureParseError::UnterminatedStruct { offset: 0 }, "struct at offset 0 is not terminated"),
+		] {
+			let err = signature.parse::<crate::Signature>().unwrap_err();
+			assert_eq!(err, expected_err, "{signature}");
+			assert_eq!(err.to_string(), expected_message, "{signature}");
+		}
+	}
+
+	#[test]
+	fn test_signature_hash_ord() {
+		let mut signatures: Vec<crate::Signature> = ["a{sv}", "s", "as", "(su)", "b", "(si)", "s"].iter().map(|s| s.parse().unwrap()).collect();
+
+		let mut map = std::collections::HashMap::new();
+		for (i, signature) in signatures.iter().enumerate() {
+			let _ = map.insert(signature.clone(), i);
+		}
+		assert_eq!(map.len(), 6);
+		assert_eq!(map[&crate::Signature::String], 6);
+		assert_eq!(map[&"a{sv}".parse().unwrap()], 0);
+
+		signatures.sort();
+		let signatures: Vec<_> = signatures.iter().map(ToString::to_string).collect();
+		// `a{sv}` sorts before `as` because `DictEntry` is declared before `String`.
+		assert_eq!(signatures, ["a{sv}", "as", "b", "s", "s", "(si)", "(su)"]);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn test_signature_serde() {
+		#[derive(Debug, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
+		struct Config {
+			signature: crate::Signature,
+		}
+
+		let config = Config { signature: "a{sv}".parse().unwrap() };
+		let json = serde_json::to_string(&config).unwrap();
+		assert_eq!(json, r#"{"signature":"a{sv}"}"#);
+		assert_eq!(serde_json::from_str::<Config>(&json).unwrap(), config);
+
+		let err = serde_json::from_str::<Config>(r#"{"signature":"a{sz}"}"#).unwrap_err();
+		assert_eq!(err.to_string(), r#"invalid signature "a{sz}": unexpected character 'z' at offset 3 at line 1 column 20"#);
+
+		// D-Bus message bodies deserialize signatures from their string form too.
+		let body = crate::Variant::Signature("a{sv}".parse().unwrap());
+		let signature: crate::Signature = serde::Deserialize::deserialize(body).unwrap();
+		assert_eq!(signature, config.signature);
+	}
+
+	#[test]
+	fn test_signature_iter() {
+		let signature: crate::Signature = "sa{sv}(iu)".parse().unwrap();
+		assert_eq!(signature.iter().map(ToString::to_string).collect::<Vec<_>>(), ["s", "a{sv}", "(iu)"]);
+		assert_eq!(signature.arity(), 3);
+		assert_eq!(signature.nth(1), Some(&"a{sv}".parse().unwrap()));
+		assert_eq!(signature.nth(3), None);
+
+		let signature: crate::Signature = "(iu)".parse().unwrap();
+		assert_eq!(signature.iter().collect::<Vec<_>>(), [&signature]);
+		assert_eq!(signature.arity(), 1);
+
+		let signature: crate::Signature = "".parse().unwrap();
+		assert_eq!(signature.iter().next(), None);
+		assert_eq!(signature.arity(), 0);
+		assert_eq!(signature.nth(0), None);
+
+		let signature = crate::Signature::Tuple {
+			elements: vec![
+				crate::Signature::Tuple { elements: vec![crate::Signature::String, crate::Signature::Tuple { elements: vec![] }] },
+				crate::Signature::U8,
+				crate::Signature::Tuple { elements: vec![crate::Signature::U32] },
+			],
+		};
+		assert_eq!(signature.to_string(), "syu");
+		assert_eq!(signature.iter().collect::<Vec<_>>(), [&crate::Signature::String, &crate::Signature::U8, &crate::Signature::U32]);
+		assert_eq!(signature.arity(), 3);
+		assert_eq!(signature.nth(2), Some(&crate::Signature::U32));
+	}
+
+	#[test]
+	fn test_signature_fixed_size() {
+		for (signature, body, expected_fixed_size) in [
+			("y", crate::Variant::U8(1), Some(1)),
+			("b", crate::Variant::Bool(true), Some(4)),
+			("n", crate::Variant::I16(1), Some(2)),
+			("h", crate::Variant::UnixFd(crate::UnixFd(0)), Some(4)),
+			("d", crate::Variant::F64(1.), Some(8)),
+			("s", crate::Variant::String("a".into()), None),
+			("au", crate::Variant::ArrayU32((&[1][..]).into()), None),
+			("v", crate::Variant::Variant((&crate::Variant::U8(1)).into()), None),
+			("(yu)", crate::Variant::Struct { fields: (&[crate::Variant::U8(1), crate::Variant::U32(1)][..]).into() }, Some(8)),
+			("(uy)", crate::Variant::Struct { fields: (&[crate::Variant::U32(1), crate::Variant::U8(1)][..]).into() }, Some(5)),
+			("(ys)", crate::Variant::Struct { fields: (&[crate::Variant::U8(1), crate::Variant::String("a".into())][..]).into() }, None),
+			(
+				"(y(yt))",
+				crate::Variant::Struct { fields: (&[
+					crate::Variant::U8(1),
+					crate::Variant::Struct { fields: (&[crate::Variant::U8(1), crate::Variant::U64(1)][..]).into() },
+				][..]).into() },
+				Some(24),
+			),
+			("{yt}", crate::Variant::DictEntry { key: (&crate::Variant::U8(1)).into(), value: (&crate::Variant::U64(1)).into() }, Some(16)),
+			("qy", crate::Variant::Tuple { elements: (&[crate::Variant::U16(1), crate::Variant::U8(1)][..]).into() }, Some(3)),
+		] {
+			let signature: crate::Signature = signature.parse().unwrap();
+			assert_eq!(body.signature(), signature);
+			assert_eq!(signature.fixed_size(), expected_fixed_size, "{signature}");
+
+			let mut buf = vec![];
+			body.serialize(&mut crate::ser::Serializer::new(&mut buf, crate::Endianness::Little)).unwrap();
+			if let Some(expected_fixed_size) = expected_fixed_size {
+				assert_eq!(buf.len(), expected_fixed_size, "{signature}");
+			}
+
+			// After a single byte, the value is padded to start at its alignment.
+			// This does not apply to tuples, since their elements are padded individually.
+			let mut buf = vec![];
+			let mut serializer = crate::ser::Serializer::new(&mut buf, crate::Endianness::Little);
+			serializer.serialize_u8(0);
+			body.serialize(&mut serializer).unwrap();
+			if let (Some(expected_fixed_size), false) = (expected_fixed_size, matches!(signature, crate::Signature::Tuple { .. })) {
+				assert_eq!(buf.len(), signature.alignment() + expected_fixed_size, "{signature}");
+			}
+
+			assert_eq!(signature.is_basic(), !signature.is_container() && !matches!(signature, crate::Signature::Tuple { .. }), "{signature}");
+		}
+	}
+
+	#[test]
+	fn test_empty_struct() {
+		assert_eq!("()".parse::<crate::Signature>().unwrap_err(), crate::SignatureParseError::EmptyStruct { offset: 0 });
+		assert_eq!("a()".parse::<crate::Signature>().unwrap_err(), crate::SignatureParseError::EmptyStruct { offset: 1 });
+		assert_eq!("(()i)".parse::<crate::Signature>().unwrap_err(), crate::SignatureParseError::EmptyStruct { offset: 1 });
+
+		let mut buf = vec![];
+
+		let err = crate::Variant::Struct { fields: (&[][..]).into() }.serialize(&mut crate::ser::Serializer::new(&mut buf, crate::Endianness::Little)).unwrap_err();
+		assert!(matches!(err, crate::SerializeError::EmptyStruct), "{err:?}");
+
+		let err =
+			crate::Signature::Array { element: Box::new(crate::Signature::Struct { fields: vec![] }) }
+			.serialize(&mut crate::ser::Serializer::new(&mut buf, crate::Endianness::Little))
+			.unwrap_err();
+		assert!(matches!(err, crate::SerializeError::EmptyStruct), "{err:?}");
+	}
+
+	#[test]
+	fn test_non_basic_dict_entry_key() {
+		assert_eq!("a{(i)s}".parse::<crate::Signature>().unwrap_err(), crate::SignatureParseError::NonBasicDictEntryKey { offset: 2 });
+		assert_eq!("a{vs}".parse::<crate::Signature>().unwrap_err(), crate::SignatureParseError::NonBasicDictEntryKey { offset: 2 });
+
+		let key_signature = crate::Signature::Struct { fields: vec![crate::Signature::I32] };
+		let element_signature = crate::Signature::DictEntry { key: Box::new(key_signature.clone()), value: Box::new(crate::Signature::String) };
+		assert_eq!(element_signature.to_string(), "{(i)s}");
+
+		let serialize = |elements: Vec<crate::Variant<'static>>| {
+			let body = crate::Variant::Array { element_signature: element_signature.clone(), elements: elements.into() };
+			crate::serialize_message(
+				&crate::MessageHeader {
+					r#type: crate::MessageType::MethodReturn { reply_serial: 1 },
+					flags: crate::message_flags::NONE,
+					body_len: 0,
+					serial: 2,
+					fields: (&[][..]).into(),
+				},
+				Some(&body),
+				&mut vec![],
+				crate::Endianness::Little,
+			).unwrap_err()
+		};
+
+		// The key is rejected when serializing the dict entry itself...
+		let err = serialize(vec![crate::Variant::DictEntry {
+			key: Box::new(crate::Variant::Struct { fields: vec![crate::Variant::I32(5)].into() }).into(),
+			value: Box::new(crate::Variant::String("a".into())).into(),
+		}]);
+		assert!(matches!(err, crate::SerializeError::NonBasicDictEntryKey { ref key } if *key == key_signature), "{err:?}");
+
+		// ... and when serializing the body signature, even if the array is empty.
+		let err = serialize(vec![]);
+		assert!(matches!(err, crate::SerializeError::NonBasicDictEntryKey { ref key } if *key == key_signature), "{err:?}");
+	}
+
+	#[test]
+	fn test_array_element_signature_mismatch() {
+		let mut buf = vec![];
+
+		let err =
+			crate::Variant::Array {
+				element_signature: crate::Signature::Struct { fields: vec![crate::Signature::U32] },
+				elements: vec![
+					crate::Variant::structure([crate::Variant::U32(1)]),
+					crate::Variant::structure([crate::Variant::U32(2)]),
+					crate::Variant::structure([crate::Variant::I32(3)]),
+					crate::Variant::structure([crate::Variant::U32(4)]),
+				].into(),
+			}
+			.serialize(&mut crate::ser::Serializer::new(&mut buf, crate::Endianness::Little))
+			.unwrap_err();
+		assert!(matches!(
+			err,
+			crate::SerializeError::ArrayElementSignatureMismatch { ref expected, ref actual, index: 2 }
+			if expected.to_string() == "(u)" && actual.to_string() == "(i)"
+		), "{err:?}");
+
+		let err =
+			crate::Variant::Array {
+				element_signature: "{sv}".parse().unwrap(),
+				elements: vec![
+					crate::Variant::dict_entry("a".into(), crate::Variant::Variant(Box::new(crate::Variant::U32(1)).into())),
+					crate::Variant::dict_entry("b".into(), crate::Variant::U32(2)),
+				].into(),
+			}
+			.serialize(&mut crate::ser::Serializer::new(&mut buf, crate::Endianness::Little))
+			.unwrap_err();
+		assert!(matches!(
+			err,
+			crate::SerializeError::ArrayElementSignatureMismatch { ref expected, ref actual, index: 1 }
+			if expected.to_string() == "{sv}" && actual.to_string() == "{su}"
+		), "{err:?}");
+	}
+
+	#[test]
+	fn test_signature_too_long() {
+		// (yy)(yy)...(yy)yy
+		let fields: Vec<_> =
+			std::iter::repeat_n(crate::Signature::Struct { fields: vec![crate::Signature::U8, crate::Signature::U8] }, 63)
+			.chain([crate::Signature::U8, crate::Signature::U8])
+			.collect();
+		let signature = crate::Signature::Struct { fields };
+		let signature_string = signature.to_string();
+		assert_eq!(signature_string.len(), 256);
+
+		assert_eq!(signature_string.parse::<crate::Signature>().unwrap_err(), crate::SignatureParseError::SignatureTooLong { len: 256 });
+		// Without the outer parens, it fits.
+		let _: crate::Signature = signature_string[1..(signature_string.len() - 1)].parse().unwrap();
+
+		let mut buf = vec![];
+		let err = signature.serialize(&mut crate::ser::Serializer::new(&mut buf, crate::Endianness::Little)).unwrap_err();
+		assert!(matches!(err, crate::SerializeError::SignatureTooLong { len: 256 }), "{err:?}");
+	}
+
+	#[test]
+	fn test_signature_depth_limits() {
+		// Too long to be valid anyway, but must not overflow the stack.
+		let signature = format!("{}u", "a".repeat(1000));
+		assert_eq!(signature.parse::<crate::Signature>().unwrap_err(), crate::SignatureParseError::SignatureTooLong { len: 1001 });
+
+		let signature = format!("{}u", "a".repeat(200));
+		assert_eq!(signature.parse::<crate::Signature>().unwrap_err(), crate::SignatureParseError::ArrayDepthExceeded { offset: 32 });
+
+		let signature = format!("{}u", "a".repeat(32));
+		let _: crate::Signature = signature.parse().unwrap();
+
+		let signature = format!("{}u{}", "(".repeat(33), ")".repeat(33));
+		assert_eq!(signature.parse::<crate::Signature>().unwrap_err(), crate::SignatureParseError::StructDepthExceeded { offset: 32 });
+
+		let signature = format!("{}{{su}}{}", "(".repeat(31), ")".repeat(31));
+		let _: crate::Signature = signature.parse().unwrap();
+
+		let signature = format!("{}{{su}}{}", "(".repeat(32), ")".repeat(32));
+		assert_eq!(signature.parse::<crate::Signature>().unwrap_err(), crate::SignatureParseError::StructDepthExceeded { offset: 32 });
+
+		// 32 arrays and 32 structs together are allowed.
+		let signature = format!("{}{}u{}", "a".repeat(32), "(".repeat(32), ")".repeat(32));
+		let _: crate::Signature = signature.parse().unwrap();
+
+		// A message body signature is rejected before the body is deserialized.
+		let mut buf = vec![254_u8];
+		buf.extend(std::iter::repeat_n(b'a', 253));
+		buf.extend_from_slice(b"u\x00");
+		let err = crate::Variant::deserialize(&mut crate::de::Deserializer::new(&buf, 0, crate::Endianness::Little), &crate::Signature::Signature).unwrap_err();
+		assert!(matches!(
+			err,
+			crate::DeserializeError::InvalidSignature { signature: _, err: crate::SignatureParseError::ArrayDepthExceeded { offset: 32 } },
+		), "{err:?}");
+	}
+
+	#[test]
+	fn test_deserialize_invalid_signature() {
+		let err = crate::Variant::deserialize(&mut crate::de::Deserializer::new(b"\x02a{\x00", 0, crate::Endianness::Little), &crate::Signature::Signature).unwrap_err();
+		assert!(matches!(
+			err,
+			crate::DeserializeError::InvalidSignature { ref signature, err: crate::SignatureParseError::UnterminatedDictEntry { offset: 1 } } if signature == "a{",
+		), "{err:?}");
+	}
+
+	#[test]
+	fn test_container_depth_exceeded() {
+		fn nested_variants(depth: usize) -> crate::Variant<'static> {
+			(0..depth).fold(crate::Variant::U8(5), |value, _| crate::Variant::Variant(Box::new(value).into()))
+		}
+
+		let serialize = |body: &crate::Variant<'_>| crate::serialize_message(
+			&crate::MessageHeader {
+				r#type: crate::MessageType::MethodReturn { reply_serial: 1 },
+				flags: crate::message_flags::NONE,
+				body_len: 0,
+				serial: 2,
+				fields: (&[][..]).into(),
+			},
+			Some(body),
+			&mut vec![],
+			crate::Endianness::Little,
+		);
+
+		serialize(&nested_variants(crate::Variant::MAX_DEPTH)).unwrap();
+
+		let err = serialize(&nested_variants(100)).unwrap_err();
+		assert!(matches!(err, crate::SerializeError::ContainerDepthExceeded), "{err:?}");
+
+		// A METHOD_RETURN whose body is `depth` nested variants around a byte, built by hand since the serializer refuses to create it.
+		let message = |depth: usize| {
+			let mut body = b"\x01v\x00".repeat(depth);
+			body.extend_from_slice(b"\x01y\x00\x05");
+
+			let mut buf = vec![];
+			buf.extend_from_slice(b"l\x02\x00\x01");
+			buf.extend_from_slice(&u32::try_from(body.len()).unwrap().to_le_bytes());
+			buf.extend_from_slice(b"\x01\x00\x00\x00");
+			buf.extend_from_slice(b"\x0F\x00\x00\x00");
+			buf.extend_from_slice(b"\x05\x01u\x00\x01\x00\x00\x00");
+			buf.extend_from_slice(b"\x08\x01g\x00\x01v\x00");
+			buf.extend_from_slice(b"\x00");
+			buf.extend_from_slice(&body);
+			buf
+		};
+
+		let buf = message(crate::Variant::MAX_DEPTH - 1);
+		let (_, body, _) = crate::deserialize_message(&buf).unwrap();
+		assert_eq!(body.map(crate::Variant::into_owned), Some(nested_variants(crate::Variant::MAX_DEPTH)));
+
+		let err = crate::deserialize_message(&message(100)).unwrap_err();
+		let crate::DeserializeError::InvalidBody { signature: crate::Signature::Variant, offset: _, err } = err else { panic!("{err:?}"); };
+		assert!(matches!(*err, crate::DeserializeError::ContainerDepthExceeded), "{err:?}");
+	}
+
+	#[test]
+	fn test_send_sync() {
+		fn assert_send_sync<T>() where T: Send + Sync {}
+
+		assert_send_sync::<crate::DeserializeError>();
+		assert_send_sync::<crate::Interner>();
+		assert_send_sync::<crate::MessageHeader<'static>>();
+		assert_send_sync::<crate::ObjectPath<'static>>();
+		assert_send_sync::<crate::SerializeError>();
+		assert_send_sync::<crate::Signature>();
+		assert_send_sync::<crate::Variant<'static>>();
+		assert_send_sync::<crate::VariantDeserializeError>();
+	}
+}
@@ -1,5 +1,5 @@
 /// Message header.
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct MessageHeader<'a> {
 	/// The message type.
 	pub r#type: MessageType<'a>,
@@ -17,117 +17,411 @@ pub struct MessageHeader<'a> {
 	pub fields: std::borrow::Cow<'a, [MessageHeaderField<'a>]>,
 }
 
+/// Limits on the sizes of messages.
+///
+/// The default limits are the maximums defined by the D-Bus specification, which are also the limits enforced by message buses.
+/// Connections to peers that are not message buses may want to use different limits.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MessageLimits {
+	/// The maximum length of a whole message, including its header, in bytes.
+	pub max_message_len: usize,
+
+	/// The maximum length of the elements of a single array, in bytes.
+	pub max_array_len: usize,
+}
+
+impl MessageLimits {
+	/// The limits defined by the D-Bus specification, ie 128 MiB for messages and 64 MiB for arrays.
+	pub const SPEC: Self = MessageLimits {
+		max_message_len: 128 * 1024 * 1024,
+		max_array_len: 64 * 1024 * 1024,
+	};
+}
+
+impl Default for MessageLimits {
+	fn default() -> Self {
+		MessageLimits::SPEC
+	}
+}
+
+/// Deserialize a message from the start of `buf`, enforcing the [`MessageLimits::SPEC`] limits.
+///
+/// Returns the message header, the message body if any, and the number of bytes of `buf` that the message occupied.
+///
+/// Returns [`crate::DeserializeError::EndOfInput`] if `buf` does not contain the whole message yet. Errors in a malformed message are wrapped in
+/// [`crate::DeserializeError::InvalidHeader`] or [`crate::DeserializeError::InvalidBody`], with the offset in the message where deserialization failed.
 pub fn deserialize_message(buf: &[u8]) -> Result<(MessageHeader<'_>, Option<crate::Variant<'_>>, usize), crate::DeserializeError> {
+	deserialize_message_with_limits(buf, &MessageLimits::SPEC)
+}
+
+/// Like [`deserialize_message`], but enforces the given limits.
+///
+/// The length of the message is checked as soon as the fixed part of the header is available,
+/// so a message that exceeds the limit is rejected before the rest of it is received.
+pub fn deserialize_message_with_limits<'de>(
+	buf: &'de [u8],
+	limits: &MessageLimits,
+) -> Result<(MessageHeader<'de>, Option<crate::Variant<'de>>, usize), crate::DeserializeError> {
 	// Arbitrarily pick `Endianness::Little` to initialize the deserializer. It'll be overridden as soon as the endianness marker is parsed.
 	let mut deserializer = crate::de::Deserializer::new(buf, 0, crate::Endianness::Little);
+	deserializer.set_max_array_len(limits.max_array_len);
 
-	let EndiannessMarker(endianness) = EndiannessMarker::deserialize(&mut deserializer)?;
+	let EndiannessMarker(endianness) = EndiannessMarker::deserialize(&mut deserializer).map_err(|err| match err {
+		crate::DeserializeError::EndOfInput => err,
+		err => crate::DeserializeError::InvalidHeader { offset: 0, field: None, err: Box::new(err) },
+	})?;
 	deserializer.set_endianness(endianness);
 
-	let message_header = MessageHeader::deserialize(&mut deserializer)?;
+	let (header_len, body_len) = header_and_body_len(buf, endianness).ok_or(crate::DeserializeError::EndOfInput)?;
+	let message_len = header_len.saturating_add(body_len);
+	if message_len > limits.max_message_len {
+		return Err(crate::DeserializeError::MessageTooLong { len: message_len, max: limits.max_message_len });
+	}
 
-	deserializer.pad_to(8)?;
+	// The header is only deserialized once it's complete, so that any error while deserializing it, even `EndOfInput`,
+	// means that the header is malformed.
+	if buf.len() < header_len {
+		return Err(crate::DeserializeError::EndOfInput);
+	}
+
+	let message_header = match MessageHeader::deserialize(&mut deserializer).and_then(|header| {
+		deserializer.pad_to(8)?;
+		Ok(header)
+	}) {
+		Ok(message_header) => message_header,
+		Err(err @ crate::DeserializeError::InvalidHeader { .. }) => return Err(err),
+		Err(err) => return Err(crate::DeserializeError::InvalidHeader { offset: deserializer.pos(), field: None, err: Box::new(err) }),
+	};
 
 	let (message_body, read) =
-		if message_header.body_len > 0 {
+		if body_len > 0 {
 			let body_start_pos = deserializer.pos();
-			let body_len = message_header.body_len;
-			let body_end_pos = body_start_pos + body_len;
+			let body_end_pos = body_start_pos.checked_add(body_len).ok_or(crate::DeserializeError::LengthOverflow)?;
 
 			if buf.len() < body_end_pos {
 				return Err(crate::DeserializeError::EndOfInput);
 			}
 
-			let signature =
-				message_header.fields.iter()
-				.find_map(|message_header_field| match message_header_field {
-					MessageHeaderField::Signature(signature) => Some(signature),
-					_ => None,
-				})
-				.ok_or(crate::DeserializeError::MissingRequiredMessageHeaderField { method_name: "body-containing", header_field_name: "SIGNATURE" })?;
+			let signature = message_header.signature().ok_or_else(|| crate::DeserializeError::InvalidHeader {
+				offset: body_start_pos,
+				field: None,
+				err: Box::new(crate::DeserializeError::MissingRequiredMessageHeaderField { method_name: "body-containing", header_field_name: "SIGNATURE" }),
+			})?;
 
 			let mut deserializer = crate::de::Deserializer::new(&buf[..body_end_pos], body_start_pos, endianness);
-
-			let message_body = crate::Variant::deserialize(&mut deserializer, signature)?;
+			deserializer.set_max_array_len(limits.max_array_len);
+
+			// The body is already known to be complete, so an error here, even `EndOfInput`, means that the body does not match its signature.
+			let message_body =
+				crate::Variant::deserialize(&mut deserializer, signature)
+				.map_err(|err| crate::DeserializeError::InvalidBody {
+					signature: signature.clone(),
+					offset: deserializer.pos(),
+					err: Box::new(err),
+				})?;
+
+			if deserializer.pos() != body_end_pos {
+				return Err(crate::DeserializeError::TrailingBodyBytes { unread: body_end_pos - deserializer.pos() });
+			}
 
 			(Some(message_body), body_end_pos)
 		}
 		else {
+			// Every non-empty signature describes at least one byte, so a signature with an empty body means that the body is missing.
+			match message_header.signature() {
+				Some(crate::Signature::Tuple { elements }) if elements.is_empty() => (),
+				Some(signature) => return Err(crate::DeserializeError::InvalidBody {
+					signature: signature.clone(),
+					offset: deserializer.pos(),
+					err: Box::new(crate::DeserializeError::EndOfInput),
+				}),
+				None => (),
+			}
+
 			(None, deserializer.pos())
 		};
 
 	Ok((message_header, message_body, read))
 }
 
+/// The endianness of the message that starts at the beginning of `buf`, as declared by its endianness marker.
+///
+/// [`deserialize_message`] handles the endianness of the message transparently. This function is useful to know what endianness a peer used,
+/// for example to reply to a message with the same endianness as the D-Bus specification recommends.
+pub fn message_endianness(buf: &[u8]) -> Result<crate::Endianness, crate::DeserializeError> {
+	let mut deserializer = crate::de::Deserializer::new(buf, 0, crate::Endianness::Little);
+	let EndiannessMarker(endianness) = EndiannessMarker::deserialize(&mut deserializer)?;
+	Ok(endianness)
+}
+
+/// The lengths of the header and body of the message that starts at the beginning of `buf`, computed from the fixed part of its header.
+/// The header length includes the padding between the header and the body.
+///
+/// Returns `None` if `buf` does not contain the fixed part of the header yet.
+pub(crate) fn header_and_body_len(buf: &[u8], endianness: crate::Endianness) -> Option<(usize, usize)> {
+	let body_len: &[_; 4] = buf.get(4..8)?.try_into().expect("infallible");
+	let body_len = endianness.u32_from_bytes(*body_len);
+
+	let fields_len: &[_; 4] = buf.get(12..16)?.try_into().expect("infallible");
+	let fields_len = endianness.u32_from_bytes(*fields_len);
+
+	// The header is padded to a multiple of 8 bytes before the body.
+	let header_len = (16 + u64::from(fields_len)).div_ceil(8) * 8;
+	Some((header_len.try_into().unwrap_or(usize::MAX), body_len.try_into().unwrap_or(usize::MAX)))
+}
+
+/// Serialize a message with the given header and body into `buf`, enforcing the [`MessageLimits::SPEC`] limits.
+///
+/// The header fields corresponding to the required properties of the message type, and the `SIGNATURE` header field if there is a body,
+/// are written to the message without being inserted into `header.fields`. `header.body_len` is ignored and the actual length of the body
+/// is written instead. So the same header can be serialized again with the same result.
+///
+/// The message is appended to `buf` as it is serialized, body included, without any intermediate buffers.
+/// If serialization fails, including because the message exceeds the limits, `buf` is left as it was.
 pub fn serialize_message(
-	header: &mut MessageHeader<'_>,
+	header: &MessageHeader<'_>,
+	body: Option<&crate::Variant<'_>>,
+	buf: &mut Vec<u8>,
+	endianness: crate::Endianness,
+) -> Result<(), crate::SerializeError> {
+	serialize_message_inner(header, body, buf, endianness, Some(&MessageLimits::SPEC))
+}
+
+/// Like [`serialize_message`], but enforces the given limits.
+pub fn serialize_message_with_limits(
+	header: &MessageHeader<'_>,
 	body: Option<&crate::Variant<'_>>,
 	buf: &mut Vec<u8>,
 	endianness: crate::Endianness,
+	limits: &MessageLimits,
 ) -> Result<(), crate::SerializeError> {
-	let header_fields = header.fields.to_mut();
+	serialize_message_inner(header, body, buf, endianness, Some(limits))
+}
+
+/// Like [`serialize_message`], but does not check that the object paths in the message are valid,
+/// nor that the message does not use the reserved [`crate::validate::LOCAL_PATH`] or [`crate::validate::LOCAL_INTERFACE`],
+/// nor that the message does not exceed the [`MessageLimits::SPEC`] limits.
+///
+/// This is only useful for testing how a peer handles malformed messages.
+pub fn serialize_message_unchecked(
+	header: &MessageHeader<'_>,
+	body: Option<&crate::Variant<'_>>,
+	buf: &mut Vec<u8>,
+	endianness: crate::Endianness,
+) -> Result<(), crate::SerializeError> {
+	serialize_message_inner(header, body, buf, endianness, None)
+}
 
-	match &mut header.r#type {
+fn serialize_message_inner(
+	header: &MessageHeader<'_>,
+	body: Option<&crate::Variant<'_>>,
+	buf: &mut Vec<u8>,
+	endianness: crate::Endianness,
+	limits: Option<&MessageLimits>,
+) -> Result<(), crate::SerializeError> {
+	let extra_fields = checked_extra_fields(header, body.map(crate::Variant::signature), limits.is_some())?;
+
+	let message_start_pos = buf.len();
+
+	let result = serialize_header_and_body(header, &extra_fields, body, buf, endianness, limits);
+	if result.is_err() {
+		buf.truncate(message_start_pos);
+	}
+
+	result
+}
+
+/// Like [`serialize_message_with_limits`], but appends the header (including the padding after it) to `header_buf`
+/// and the body to `body_buf`, so that they can be written out with a single vectored write without being concatenated first.
+///
+/// If serialization fails, both buffers are left as they were.
+pub(crate) fn serialize_message_split(
+	header: &MessageHeader<'_>,
+	body: Option<&crate::Variant<'_>>,
+	header_buf: &mut Vec<u8>,
+	body_buf: &mut Vec<u8>,
+	endianness: crate::Endianness,
+	limits: &MessageLimits,
+) -> Result<(), crate::SerializeError> {
+	serialize_message_split_with(
+		header,
+		body.map(crate::Variant::signature),
+		|body_serializer| {
+			if let Some(body) = body {
+				body_serializer.reserve(body.serialized_len(0));
+				body.serialize(body_serializer)?;
+			}
+
+			Ok(())
+		},
+		header_buf,
+		body_buf,
+		endianness,
+		limits,
+	)
+}
+
+/// Like [`serialize_message_split`], but the body is serialized by `serialize_body` instead of from a [`crate::Variant`].
+///
+/// `body_signature` is the signature of what `serialize_body` writes, or `None` if the message has no body.
+pub(crate) fn serialize_message_split_with(
+	header: &MessageHeader<'_>,
+	body_signature: Option<crate::Signature>,
+	serialize_body: impl FnOnce(&mut crate::ser::Serializer<'_>) -> Result<(), crate::SerializeError>,
+	header_buf: &mut Vec<u8>,
+	body_buf: &mut Vec<u8>,
+	endianness: crate::Endianness,
+	limits: &MessageLimits,
+) -> Result<(), crate::SerializeError> {
+	let extra_fields = checked_extra_fields(header, body_signature, true)?;
+
+	let header_start_pos = header_buf.len();
+	let body_start_pos = body_buf.len();
+
+	let result = serialize_header_and_body_split(header, &extra_fields, serialize_body, header_buf, body_buf, endianness, limits);
+	if result.is_err() {
+		header_buf.truncate(header_start_pos);
+		body_buf.truncate(body_start_pos);
+	}
+
+	result
+}
+
+/// The header fields implied by the message type and the body, after checking that the message can be sent if `validate` is set.
+fn checked_extra_fields<'a>(
+	header: &'a MessageHeader<'_>,
+	body_signature: Option<crate::Signature>,
+	validate: bool,
+) -> Result<Vec<MessageHeaderField<'a>>, crate::SerializeError> {
+	if validate && header.serial == 0 {
+		return Err(crate::SerializeError::ZeroSerial);
+	}
+
+	let mut extra_fields = extra_fields(header);
+
+	if validate {
+		for header_field in &*header.fields {
+			header_field.validate_not_local()?;
+		}
+
+		for header_field in &extra_fields {
+			header_field.validate_not_local()?;
+		}
+	}
+
+	if let Some(body_signature) = body_signature {
+		extra_fields.push(MessageHeaderField::Signature(body_signature));
+	}
+
+	Ok(extra_fields)
+}
+
+/// The header fields implied by the message type.
+///
+/// These are collected separately, borrowing from the header, so that the header itself is not modified.
+fn extra_fields<'a>(header: &'a MessageHeader<'_>) -> Vec<MessageHeaderField<'a>> {
+	let mut extra_fields = vec![];
+
+	match &header.r#type {
 		MessageType::Error { name, reply_serial } => {
-			header_fields.push(MessageHeaderField::ErrorName(std::mem::take(name)));
-			header_fields.push(MessageHeaderField::ReplySerial(*reply_serial));
+			extra_fields.push(MessageHeaderField::ErrorName((&**name).into()));
+			extra_fields.push(MessageHeaderField::ReplySerial(*reply_serial));
 		},
 
 		MessageType::MethodCall { member, path } => {
-			header_fields.push(MessageHeaderField::Member(std::mem::take(member)));
-			header_fields.push(MessageHeaderField::Path(std::mem::take(path)));
+			extra_fields.push(MessageHeaderField::Member((&**member).into()));
+			extra_fields.push(MessageHeaderField::Path(crate::ObjectPath((&*path.0).into())));
 		},
 
 		MessageType::MethodReturn { reply_serial } => {
-			header_fields.push(MessageHeaderField::ReplySerial(*reply_serial));
+			extra_fields.push(MessageHeaderField::ReplySerial(*reply_serial));
 		},
 
 		MessageType::Signal { interface, member, path } => {
-			header_fields.push(MessageHeaderField::Interface(std::mem::take(interface)));
-			header_fields.push(MessageHeaderField::Member(std::mem::take(member)));
-			header_fields.push(MessageHeaderField::Path(std::mem::take(path)));
+			extra_fields.push(MessageHeaderField::Interface((&**interface).into()));
+			extra_fields.push(MessageHeaderField::Member((&**member).into()));
+			extra_fields.push(MessageHeaderField::Path(crate::ObjectPath((&*path.0).into())));
 		},
+
+		MessageType::Unknown { r#type: _ } => (),
 	}
 
-	let body =
-		if let Some(body) = body {
-			let mut body_serialized = vec![];
-			let mut body_serializer = crate::ser::Serializer::new(&mut body_serialized, endianness);
-			body.serialize(&mut body_serializer)?;
+	extra_fields
+}
 
-			let body_len = body_serialized.len();
+/// Serialize the header and then the body directly after it, so that the body doesn't have to be serialized into a separate buffer first.
+///
+/// The length of the body is written as 0 in the header and then overwritten with the actual length once the body has been serialized.
+fn serialize_header_and_body(
+	header: &MessageHeader<'_>,
+	extra_fields: &[MessageHeaderField<'_>],
+	body: Option<&crate::Variant<'_>>,
+	buf: &mut Vec<u8>,
+	endianness: crate::Endianness,
+	limits: Option<&MessageLimits>,
+) -> Result<(), crate::SerializeError> {
+	let message_start_pos = buf.len();
+
+	let mut message_serializer = crate::ser::Serializer::new(buf, endianness);
+	message_serializer.set_validate_object_paths(limits.is_some());
+
+	EndiannessMarker(endianness).serialize(&mut message_serializer);
+
+	header.serialize(0, extra_fields, &mut message_serializer)?;
+
+	message_serializer.pad_to(8);
 
-			let body_signature = body.inner_signature();
+	let body_start_pos = message_serializer.pos();
+	let body_serialized_len = body.map_or(0, |body| body.serialized_len(body_start_pos));
 
-			Some((body_serialized, body_len, body_signature))
+	if let Some(limits) = limits {
+		let message_len = body_start_pos.saturating_add(body_serialized_len);
+		if message_len > limits.max_message_len {
+			return Err(crate::SerializeError::MessageTooLong { len: message_len, max: limits.max_message_len });
 		}
-		else {
-			None
-		};
+	}
 
-	if let Some((body_serialized, body_len, body_signature)) = body {
-		header.body_len = body_len;
+	if let Some(body) = body {
+		message_serializer.reserve(body_serialized_len);
+		body.serialize(&mut message_serializer)?;
+	}
+
+	let body_len = message_serializer.pos() - body_start_pos;
+	let body_len: u32 = body_len.try_into().map_err(crate::SerializeError::ExceedsNumericLimits)?;
+	buf[(message_start_pos + 4)..][..4].copy_from_slice(&endianness.u32_to_bytes(body_len));
 
-		header_fields.push(MessageHeaderField::Signature(body_signature));
+	Ok(())
+}
 
-		let mut message_serializer = crate::ser::Serializer::new(buf, endianness);
+/// Serialize the body into `body_buf` with `serialize_body` and then the header into `header_buf`.
+///
+/// The header is padded to a multiple of 8 bytes, so the body in `body_buf` can be aligned relative to its own start.
+fn serialize_header_and_body_split(
+	header: &MessageHeader<'_>,
+	extra_fields: &[MessageHeaderField<'_>],
+	serialize_body: impl FnOnce(&mut crate::ser::Serializer<'_>) -> Result<(), crate::SerializeError>,
+	header_buf: &mut Vec<u8>,
+	body_buf: &mut Vec<u8>,
+	endianness: crate::Endianness,
+	limits: &MessageLimits,
+) -> Result<(), crate::SerializeError> {
+	let mut body_serializer = crate::ser::Serializer::new(body_buf, endianness);
 
-		EndiannessMarker(endianness).serialize(&mut message_serializer);
+	serialize_body(&mut body_serializer)?;
 
-		header.serialize(&mut message_serializer)?;
+	let body_len = body_serializer.pos();
 
-		message_serializer.pad_to(8);
+	let mut header_serializer = crate::ser::Serializer::new(header_buf, endianness);
 
-		buf.extend_from_slice(&body_serialized);
-	}
-	else {
-		let mut message_serializer = crate::ser::Serializer::new(buf, endianness);
+	EndiannessMarker(endianness).serialize(&mut header_serializer);
 
-		EndiannessMarker(endianness).serialize(&mut message_serializer);
+	header.serialize(body_len, extra_fields, &mut header_serializer)?;
 
-		header.serialize(&mut message_serializer)?;
+	header_serializer.pad_to(8);
 
-		message_serializer.pad_to(8);
+	let message_len = header_serializer.pos().saturating_add(body_len);
+	if message_len > limits.max_message_len {
+		return Err(crate::SerializeError::MessageTooLong { len: message_len, max: limits.max_message_len });
 	}
 
 	Ok(())
@@ -148,6 +442,9 @@ impl<'de> MessageHeader<'de> {
 		let body_len: usize = body_len.try_into().map_err(crate::DeserializeError::ExceedsNumericLimits)?;
 
 		let serial = deserializer.deserialize_u32()?;
+		if serial == 0 {
+			return Err(crate::DeserializeError::InvalidValue { expected: "a non-zero serial".into(), actual: serial.to_string() });
+		}
 
 		let fields = deserializer.deserialize_array(8, MessageHeaderField::deserialize)?;
 
@@ -171,32 +468,165 @@ impl<'de> MessageHeader<'de> {
 			fields: self.fields.iter().cloned().map(MessageHeaderField::into_owned).collect::<Vec<_>>().into(),
 		}
 	}
+
+	/// Like [`MessageHeader::into_owned`], except the names and paths in the header are converted using the given [`crate::Interner`].
+	///
+	/// The header borrows the interned strings from the interner, which is why it must be `'static`.
+	pub fn into_owned_interned(self, interner: &'static crate::Interner) -> MessageHeader<'static> {
+		MessageHeader {
+			r#type: self.r#type.into_owned_interned(interner),
+			flags: self.flags,
+			body_len: self.body_len,
+			serial: self.serial,
+			fields: self.fields.iter().cloned().map(|field| field.into_owned_interned(interner)).collect::<Vec<_>>().into(),
+		}
+	}
+}
+
+impl<'a> MessageHeader<'a> {
+	// The names, paths and serials that are part of the message type are stored in `r#type` after the header is deserialized,
+	// but a header that was constructed by hand might also have them in `fields`. So these accessors look in `fields` first, then in `r#type`.
+
+	/// The `DESTINATION` header field.
+	pub fn destination(&self) -> Option<&str> {
+		self.find_field(|field| match field {
+			MessageHeaderField::Destination(destination) => Some(&**destination),
+			_ => None,
+		})
+	}
+
+	/// The `ERROR_NAME` header field, or the error name of an `Error` message.
+	pub fn error_name(&self) -> Option<&str> {
+		self.find_field(|field| match field {
+			MessageHeaderField::ErrorName(name) => Some(&**name),
+			_ => None,
+		}).or(match &self.r#type {
+			MessageType::Error { name, reply_serial: _ } => Some(&**name),
+			_ => None,
+		})
+	}
+
+	/// The `INTERFACE` header field, or the interface of a `Signal` message.
+	pub fn interface(&self) -> Option<&str> {
+		self.find_field(|field| match field {
+			MessageHeaderField::Interface(interface) => Some(&**interface),
+			_ => None,
+		}).or(match &self.r#type {
+			MessageType::Signal { interface, member: _, path: _ } => Some(&**interface),
+			_ => None,
+		})
+	}
+
+	/// The `MEMBER` header field, or the member of a `MethodCall` or `Signal` message.
+	pub fn member(&self) -> Option<&str> {
+		self.find_field(|field| match field {
+			MessageHeaderField::Member(member) => Some(&**member),
+			_ => None,
+		}).or(match &self.r#type {
+			MessageType::MethodCall { member, path: _ } |
+			MessageType::Signal { interface: _, member, path: _ } => Some(&**member),
+			_ => None,
+		})
+	}
+
+	/// The `PATH` header field, or the path of a `MethodCall` or `Signal` message.
+	pub fn path(&self) -> Option<&crate::ObjectPath<'a>> {
+		self.find_field(|field| match field {
+			MessageHeaderField::Path(path) => Some(path),
+			_ => None,
+		}).or(match &self.r#type {
+			MessageType::MethodCall { member: _, path } |
+			MessageType::Signal { interface: _, member: _, path } => Some(path),
+			_ => None,
+		})
+	}
+
+	/// The `REPLY_SERIAL` header field, or the reply serial of an `Error` or `MethodReturn` message.
+	pub fn reply_serial(&self) -> Option<u32> {
+		self.find_field(|field| match field {
+			MessageHeaderField::ReplySerial(reply_serial) => Some(*reply_serial),
+			_ => None,
+		}).or(match &self.r#type {
+			MessageType::Error { name: _, reply_serial } |
+			MessageType::MethodReturn { reply_serial } => Some(*reply_serial),
+			_ => None,
+		})
+	}
+
+	/// The `SENDER` header field.
+	pub fn sender(&self) -> Option<&str> {
+		self.find_field(|field| match field {
+			MessageHeaderField::Sender(sender) => Some(&**sender),
+			_ => None,
+		})
+	}
+
+	/// The `SIGNATURE` header field, ie the signature of the message body.
+	pub fn signature(&self) -> Option<&crate::Signature> {
+		self.find_field(|field| match field {
+			MessageHeaderField::Signature(signature) => Some(signature),
+			_ => None,
+		})
+	}
+
+	/// The `UNIX_FDS` header field, ie the number of unix fds that accompany the message.
+	pub fn unix_fds(&self) -> Option<u32> {
+		self.find_field(|field| match field {
+			MessageHeaderField::UnixFds(num_unix_fds) => Some(*num_unix_fds),
+			_ => None,
+		})
+	}
+
+	/// The value of the header field with the given code that this crate doesn't know about.
+	///
+	/// Use the other accessors for the known header fields.
+	pub fn field(&self, code: u8) -> Option<&crate::Variant<'a>> {
+		self.find_field(|field| match field {
+			MessageHeaderField::Unknown { code: field_code, value } if *field_code == code => Some(value),
+			_ => None,
+		})
+	}
+
+	fn find_field<'b, T>(&'b self, f: impl FnMut(&'b MessageHeaderField<'a>) -> Option<T>) -> Option<T> {
+		self.fields.iter().find_map(f)
+	}
 }
 
 impl MessageHeader<'_> {
-	fn serialize(&self, serializer: &mut crate::ser::Serializer<'_>) -> Result<(), crate::SerializeError> {
+	fn serialize(
+		&self,
+		body_len: usize,
+		extra_fields: &[MessageHeaderField<'_>],
+		serializer: &mut crate::ser::Serializer<'_>,
+	) -> Result<(), crate::SerializeError> {
 		self.r#type.serialize(serializer);
 
 		self.flags.serialize(serializer);
 
 		serializer.serialize_u8(0x01_u8);
 
-		crate::UsizeAsU32(self.body_len).serialize(serializer)?;
+		crate::UsizeAsU32(body_len).serialize(serializer)?;
 
 		serializer.serialize_u32(self.serial);
 
-		serializer.serialize_array(
-			1,
-			&self.fields,
-			MessageHeaderField::serialize,
-		)?;
+		serializer.serialize_array_with(1, |serializer| {
+			for field in &*self.fields {
+				field.serialize(serializer)?;
+			}
+
+			for field in extra_fields {
+				field.serialize(serializer)?;
+			}
+
+			Ok(())
+		})?;
 
 		Ok(())
 	}
 }
 
 /// Message type.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum MessageType<'a> {
 	Error {
 		name: std::borrow::Cow<'a, str>,
@@ -217,6 +647,13 @@ pub enum MessageType<'a> {
 		member: std::borrow::Cow<'a, str>,
 		path: crate::ObjectPath<'a>,
 	},
+
+	/// A message type that is not defined by the D-Bus specification, possibly from a newer version of it.
+	///
+	/// The specification requires such messages to be ignored, so [`crate::MessageHeader::fields`] holds all their header fields as-is.
+	Unknown {
+		r#type: u8,
+	},
 }
 
 impl<'a> MessageType<'a> {
@@ -224,6 +661,15 @@ impl<'a> MessageType<'a> {
 		r#type: u8,
 		fields: Vec<MessageHeaderField<'a>>,
 	) -> Result<(Self, Vec<MessageHeaderField<'a>>), crate::DeserializeError> {
+		// A header must not contain the same field more than once.
+		let mut seen_codes = [false; 256];
+		for field in &fields {
+			let code = field.code();
+			if std::mem::replace(&mut seen_codes[usize::from(code)], true) {
+				return Err(crate::DeserializeError::DuplicateHeaderField { code });
+			}
+		}
+
 		// TODO: Use `Vec::drain_filter` when that stabilizes to mutate `fields` in place
 
 		let mut other_fields = vec![];
@@ -331,7 +777,9 @@ impl<'a> MessageType<'a> {
 				}
 			},
 
-			v => return Err(crate::DeserializeError::InvalidValue { expected: "one of 0x01, 0x02, 0x03, 0x04".into(), actual: v.to_string() }),
+			0x00 => return Err(crate::DeserializeError::InvalidValue { expected: "a non-zero message type".into(), actual: "0x00".to_owned() }),
+
+			r#type => MessageType::Unknown { r#type },
 		};
 
 		Ok((r#type, other_fields))
@@ -358,6 +806,38 @@ impl<'a> MessageType<'a> {
 				member: member.into_owned().into(),
 				path: path.into_owned(),
 			},
+
+			MessageType::Unknown { r#type } => MessageType::Unknown {
+				r#type,
+			},
+		}
+	}
+
+	fn into_owned_interned(self, interner: &'static crate::Interner) -> MessageType<'static> {
+		match self {
+			MessageType::Error { name, reply_serial } => MessageType::Error {
+				name: interner.intern(name),
+				reply_serial,
+			},
+
+			MessageType::MethodCall { member, path } => MessageType::MethodCall {
+				member: interner.intern(member),
+				path: path.into_owned_interned(interner),
+			},
+
+			MessageType::MethodReturn { reply_serial } => MessageType::MethodReturn {
+				reply_serial,
+			},
+
+			MessageType::Signal { interface, member, path } => MessageType::Signal {
+				interface: interner.intern(interface),
+				member: interner.intern(member),
+				path: path.into_owned_interned(interner),
+			},
+
+			MessageType::Unknown { r#type } => MessageType::Unknown {
+				r#type,
+			},
 		}
 	}
 }
@@ -369,6 +849,7 @@ impl MessageType<'_> {
 			MessageType::MethodCall { .. } => 0x01,
 			MessageType::MethodReturn { .. } => 0x02,
 			MessageType::Signal { .. } => 0x04,
+			MessageType::Unknown { r#type } => *r#type,
 		};
 		serializer.serialize_u8(r#type);
 	}
@@ -377,7 +858,10 @@ impl MessageType<'_> {
 /// Message flags.
 ///
 /// Bit-wise OR of the [`flags`] constants.
-#[derive(Clone, Copy, Debug)]
+///
+/// Received messages may have bits set that are not defined by any of the [`flags`] constants. These are preserved as-is,
+/// since the D-Bus specification requires them to be ignored. Use [`MessageFlags::unknown_bits`] to detect them.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct MessageFlags(u8);
 
 impl std::ops::BitOr for MessageFlags {
@@ -389,6 +873,53 @@ impl std::ops::BitOr for MessageFlags {
 }
 
 impl MessageFlags {
+	const KNOWN_BITS: u8 = flags::NO_REPLY_EXPECTED.0 | flags::NO_AUTO_START.0 | flags::ALLOW_INTERACTIVE_AUTHORIZATION.0;
+
+	/// Create flags from their raw representation. Returns `None` if any of the bits are not defined by the [`flags`] constants.
+	pub const fn from_bits(bits: u8) -> Option<Self> {
+		if bits & !Self::KNOWN_BITS == 0 {
+			Some(MessageFlags(bits))
+		}
+		else {
+			None
+		}
+	}
+
+	/// Create flags from their raw representation, including any bits that are not defined by the [`flags`] constants.
+	pub const fn from_bits_retain(bits: u8) -> Self {
+		MessageFlags(bits)
+	}
+
+	/// The raw representation of these flags.
+	pub const fn bits(self) -> u8 {
+		self.0
+	}
+
+	/// Returns true if no flags are set.
+	pub const fn is_empty(self) -> bool {
+		self.0 == 0
+	}
+
+	/// Returns true if all the given flags are set.
+	pub const fn contains(self, other: Self) -> bool {
+		self.0 & other.0 == other.0
+	}
+
+	/// Set the given flags.
+	pub fn insert(&mut self, other: Self) {
+		self.0 |= other.0;
+	}
+
+	/// Clear the given flags.
+	pub fn remove(&mut self, other: Self) {
+		self.0 &= !other.0;
+	}
+
+	/// The bits that are set but are not defined by the [`flags`] constants.
+	pub const fn unknown_bits(self) -> u8 {
+		self.0 & !Self::KNOWN_BITS
+	}
+
 	fn deserialize(deserializer: &mut crate::de::Deserializer<'_>) -> Result<Self, crate::DeserializeError> {
 		Ok(MessageFlags(deserializer.deserialize_u8()?))
 	}
@@ -398,6 +929,35 @@ impl MessageFlags {
 	}
 }
 
+/// Formats the names of the set flags separated by ` | `, like `NO_REPLY_EXPECTED | NO_AUTO_START`, or `NONE` if no flags are set.
+/// Unknown bits are formatted in hex.
+impl std::fmt::Display for MessageFlags {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.is_empty() {
+			return f.write_str("NONE");
+		}
+
+		let mut separator = "";
+		for (flag, name) in [
+			(flags::NO_REPLY_EXPECTED, "NO_REPLY_EXPECTED"),
+			(flags::NO_AUTO_START, "NO_AUTO_START"),
+			(flags::ALLOW_INTERACTIVE_AUTHORIZATION, "ALLOW_INTERACTIVE_AUTHORIZATION"),
+		] {
+			if self.contains(flag) {
+				write!(f, "{separator}{name}")?;
+				separator = " | ";
+			}
+		}
+
+		let unknown_bits = self.unknown_bits();
+		if unknown_bits != 0 {
+			write!(f, "{separator}{unknown_bits:#04x}")?;
+		}
+
+		Ok(())
+	}
+}
+
 pub mod flags {
 	pub const NONE: super::MessageFlags = super::MessageFlags(0x00);
 	pub const NO_REPLY_EXPECTED: super::MessageFlags = super::MessageFlags(0x01);
@@ -406,7 +966,7 @@ pub mod flags {
 }
 
 /// A message header field.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum MessageHeaderField<'a> {
 	Destination(std::borrow::Cow<'a, str>),
 
@@ -437,62 +997,67 @@ impl<'de> MessageHeaderField<'de> {
 		deserializer.deserialize_struct(|deserializer| {
 			let code = deserializer.deserialize_u8()?;
 
-			let signature = crate::Signature::deserialize(deserializer)?;
-			let value = crate::Variant::deserialize(deserializer, &signature)?;
-
-			#[allow(clippy::match_same_arms)]
-			match (code, value) {
-				(0x01, crate::Variant::ObjectPath(object_path)) =>
-					Ok(MessageHeaderField::Path(object_path)),
-				(0x01, value) =>
-					Err(crate::DeserializeError::InvalidValue { expected: "an object path".into(), actual: format!("{value:?}") }),
-
-				(0x02, crate::Variant::String(name)) =>
-					Ok(MessageHeaderField::Interface(name)),
-				(0x02, value) =>
-					Err(crate::DeserializeError::InvalidValue { expected: "a string".into(), actual: format!("{value:?}") }),
-
-				(0x03, crate::Variant::String(name)) =>
-					Ok(MessageHeaderField::Member(name)),
-				(0x03, value) =>
-					Err(crate::DeserializeError::InvalidValue { expected: "a string".into(), actual: format!("{value:?}") }),
-
-				(0x04, crate::Variant::String(name)) =>
-					Ok(MessageHeaderField::ErrorName(name)),
-				(0x04, value) =>
-					Err(crate::DeserializeError::InvalidValue { expected: "a string".into(), actual: format!("{value:?}") }),
-
-				(0x05, crate::Variant::U32(serial)) =>
-					Ok(MessageHeaderField::ReplySerial(serial)),
-				(0x05, value) =>
-					Err(crate::DeserializeError::InvalidValue { expected: "a string".into(), actual: format!("{value:?}") }),
-
-				(0x06, crate::Variant::String(name)) =>
-					Ok(MessageHeaderField::Destination(name)),
-				(0x06, value) =>
-					Err(crate::DeserializeError::InvalidValue { expected: "a string".into(), actual: format!("{value:?}") }),
-
-				(0x07, crate::Variant::String(name)) =>
-					Ok(MessageHeaderField::Sender(name)),
-				(0x07, value) =>
-					Err(crate::DeserializeError::InvalidValue { expected: "a string".into(), actual: format!("{value:?}") }),
-
-				(0x08, crate::Variant::Signature(signature)) =>
-					Ok(MessageHeaderField::Signature(signature)),
-				(0x08, value) =>
-					Err(crate::DeserializeError::InvalidValue { expected: "a signature".into(), actual: format!("{value:?}") }),
-
-				(0x09, crate::Variant::U32(num_unix_fds)) =>
-					Ok(MessageHeaderField::UnixFds(num_unix_fds)),
-				(0x09, value) =>
-					Err(crate::DeserializeError::InvalidValue { expected: "a u32".into(), actual: format!("{value:?}") }),
-
-				(code, value) =>
-					Ok(MessageHeaderField::Unknown { code, value }),
-			}
+			Self::deserialize_value(code, deserializer)
+				.map_err(|err| crate::DeserializeError::InvalidHeader { offset: deserializer.pos(), field: Some(code), err: Box::new(err) })
 		})
 	}
 
+	fn deserialize_value(code: u8, deserializer: &mut crate::de::Deserializer<'de>) -> Result<Self, crate::DeserializeError> {
+		let signature = crate::Signature::deserialize(deserializer)?;
+		let value = crate::Variant::deserialize(deserializer, &signature)?;
+
+		#[allow(clippy::match_same_arms)]
+		match (code, value) {
+			(0x01, crate::Variant::ObjectPath(object_path)) =>
+				Ok(MessageHeaderField::Path(object_path)),
+			(0x01, value) =>
+				Err(crate::DeserializeError::InvalidValue { expected: "an object path".into(), actual: format!("{value:?}") }),
+
+			(0x02, crate::Variant::String(name)) =>
+				Ok(MessageHeaderField::Interface(name)),
+			(0x02, value) =>
+				Err(crate::DeserializeError::InvalidValue { expected: "a string".into(), actual: format!("{value:?}") }),
+
+			(0x03, crate::Variant::String(name)) =>
+				Ok(MessageHeaderField::Member(name)),
+			(0x03, value) =>
+				Err(crate::DeserializeError::InvalidValue { expected: "a string".into(), actual: format!("{value:?}") }),
+
+			(0x04, crate::Variant::String(name)) =>
+				Ok(MessageHeaderField::ErrorName(name)),
+			(0x04, value) =>
+				Err(crate::DeserializeError::InvalidValue { expected: "a string".into(), actual: format!("{value:?}") }),
+
+			(0x05, crate::Variant::U32(serial)) =>
+				Ok(MessageHeaderField::ReplySerial(serial)),
+			(0x05, value) =>
+				Err(crate::DeserializeError::InvalidValue { expected: "a u32".into(), actual: format!("{value:?}") }),
+
+			(0x06, crate::Variant::String(name)) =>
+				Ok(MessageHeaderField::Destination(name)),
+			(0x06, value) =>
+				Err(crate::DeserializeError::InvalidValue { expected: "a string".into(), actual: format!("{value:?}") }),
+
+			(0x07, crate::Variant::String(name)) =>
+				Ok(MessageHeaderField::Sender(name)),
+			(0x07, value) =>
+				Err(crate::DeserializeError::InvalidValue { expected: "a string".into(), actual: format!("{value:?}") }),
+
+			(0x08, crate::Variant::Signature(signature)) =>
+				Ok(MessageHeaderField::Signature(signature)),
+			(0x08, value) =>
+				Err(crate::DeserializeError::InvalidValue { expected: "a signature".into(), actual: format!("{value:?}") }),
+
+			(0x09, crate::Variant::U32(num_unix_fds)) =>
+				Ok(MessageHeaderField::UnixFds(num_unix_fds)),
+			(0x09, value) =>
+				Err(crate::DeserializeError::InvalidValue { expected: "a u32".into(), actual: format!("{value:?}") }),
+
+			(code, value) =>
+				Ok(MessageHeaderField::Unknown { code, value }),
+		}
+	}
+
 	fn into_owned(self) -> MessageHeaderField<'static> {
 		match self {
 			MessageHeaderField::Destination(name) => MessageHeaderField::Destination(name.into_owned().into()),
@@ -519,9 +1084,53 @@ impl<'de> MessageHeaderField<'de> {
 			},
 		}
 	}
+
+	fn into_owned_interned(self, interner: &'static crate::Interner) -> MessageHeaderField<'static> {
+		match self {
+			MessageHeaderField::Destination(name) => MessageHeaderField::Destination(interner.intern(name)),
+
+			MessageHeaderField::ErrorName(name) => MessageHeaderField::ErrorName(interner.intern(name)),
+
+			MessageHeaderField::Interface(name) => MessageHeaderField::Interface(interner.intern(name)),
+
+			MessageHeaderField::Member(name) => MessageHeaderField::Member(interner.intern(name)),
+
+			MessageHeaderField::Path(object_path) => MessageHeaderField::Path(object_path.into_owned_interned(interner)),
+
+			MessageHeaderField::Sender(name) => MessageHeaderField::Sender(interner.intern(name)),
+
+			field => field.into_owned(),
+		}
+	}
 }
 
 impl MessageHeaderField<'_> {
+	/// The code that identifies this field in the serialized header.
+	fn code(&self) -> u8 {
+		match self {
+			MessageHeaderField::Destination(_) => 0x06,
+			MessageHeaderField::ErrorName(_) => 0x04,
+			MessageHeaderField::Interface(_) => 0x02,
+			MessageHeaderField::Member(_) => 0x03,
+			MessageHeaderField::Path(_) => 0x01,
+			MessageHeaderField::ReplySerial(_) => 0x05,
+			MessageHeaderField::Sender(_) => 0x07,
+			MessageHeaderField::Signature(_) => 0x08,
+			MessageHeaderField::UnixFds(_) => 0x09,
+			MessageHeaderField::Unknown { code, value: _ } => *code,
+		}
+	}
+
+	fn validate_not_local(&self) -> Result<(), crate::SerializeError> {
+		match self {
+			MessageHeaderField::Interface(interface) if interface == crate::validate::LOCAL_INTERFACE =>
+				Err(crate::SerializeError::ReservedLocalInterface),
+			MessageHeaderField::Path(path) if path.0 == crate::validate::LOCAL_PATH =>
+				Err(crate::SerializeError::ReservedLocalPath),
+			_ => Ok(()),
+		}
+	}
+
 	fn serialize(&self, serializer: &mut crate::ser::Serializer<'_>) -> Result<(), crate::SerializeError> {
 		let (code, value) = match self {
 			MessageHeaderField::Destination(name) =>
@@ -558,7 +1167,7 @@ impl MessageHeaderField<'_> {
 		serializer.serialize_struct(|serializer| {
 			serializer.serialize_u8(code);
 
-			let signature = value.inner_signature();
+			let signature = value.signature();
 			signature.serialize(serializer)?;
 
 			value.serialize(serializer)?;
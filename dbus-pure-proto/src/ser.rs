@@ -3,6 +3,7 @@ pub(crate) struct Serializer<'ser> {
 	buf: &'ser mut Vec<u8>,
 	start: usize,
 	endianness: crate::Endianness,
+	validate_object_paths: bool,
 }
 
 impl<'ser> Serializer<'ser> {
@@ -12,9 +13,28 @@ impl<'ser> Serializer<'ser> {
 			buf,
 			start,
 			endianness,
+			validate_object_paths: true,
 		}
 	}
 
+	pub(crate) fn set_validate_object_paths(&mut self, validate_object_paths: bool) {
+		self.validate_object_paths = validate_object_paths;
+	}
+
+	pub(crate) fn validate_object_paths(&self) -> bool {
+		self.validate_object_paths
+	}
+
+	/// The position of the end of the serialized data, relative to where the serializer started.
+	pub(crate) fn pos(&self) -> usize {
+		self.buf.len() - self.start
+	}
+
+	/// Reserve space for at least `additional` more bytes in the buffer.
+	pub(crate) fn reserve(&mut self, additional: usize) {
+		self.buf.reserve(additional);
+	}
+
 	pub(crate) fn pad_to(&mut self, alignment: usize) {
 		let pos = self.buf.len() - self.start;
 		// TODO(rustup): Use `pos.next_multiple_of(alignment)` when that is stabilized.
@@ -29,6 +49,31 @@ impl<'ser> Serializer<'ser> {
 		v: &[T],
 		mut f: impl FnMut(&T, &mut Self) -> Result<(), SerializeError>,
 	) -> Result<(), SerializeError> {
+		self.serialize_array_with(element_alignment, |serializer| {
+			for v in v {
+				f(v, serializer)?;
+			}
+
+			Ok(())
+		})
+	}
+
+	/// Like [`Serializer::serialize_array`], but the elements are written by `f` rather than taken from a slice.
+	pub(crate) fn serialize_array_with(
+		&mut self,
+		element_alignment: usize,
+		f: impl FnOnce(&mut Self) -> Result<(), SerializeError>,
+	) -> Result<(), SerializeError> {
+		let start = self.serialize_array_start(element_alignment);
+		f(self)?;
+		self.serialize_array_end(start)
+	}
+
+	/// Writes a placeholder for the length of an array whose elements will be serialized next,
+	/// followed by the padding before its first element.
+	///
+	/// The length is filled in by [`Serializer::serialize_array_end`].
+	pub(crate) fn serialize_array_start(&mut self, element_alignment: usize) -> ArrayStart {
 		self.serialize_u32(0);
 		let data_len_pos = self.buf.len() - 4;
 
@@ -36,9 +81,11 @@ impl<'ser> Serializer<'ser> {
 
 		let data_start_pos = self.buf.len();
 
-		for v in v {
-			f(v, self)?;
-		}
+		ArrayStart { data_len_pos, data_start_pos }
+	}
+
+	pub(crate) fn serialize_array_end(&mut self, start: ArrayStart) -> Result<(), SerializeError> {
+		let ArrayStart { data_len_pos, data_start_pos } = start;
 
 		let data_end_pos = self.buf.len();
 
@@ -86,6 +133,11 @@ impl<'ser> Serializer<'ser> {
 	}
 
 	pub(crate) fn serialize_string(&mut self, v: &str) -> Result<(), SerializeError> {
+		// The string is terminated by a nul byte, so it must not contain one itself.
+		if let Some(position) = v.bytes().position(|b| b == b'\0') {
+			return Err(SerializeError::NulInString { position });
+		}
+
 		self.serialize_array_u8(v.as_bytes())?;
 		self.serialize_u8(b'\0');
 		Ok(())
@@ -124,21 +176,95 @@ impl<'ser> Serializer<'ser> {
 /// An error from serializing a value using the D-Bus binary protocol.
 #[derive(Debug)]
 pub enum SerializeError {
+	ArrayElementSignatureMismatch { expected: crate::Signature, actual: crate::Signature, index: usize },
+	ContainerDepthExceeded,
+
+	/// An error raised by a [`serde::Serialize`] impl while serializing a value with [`crate::to_writer_with_signature`]
+	Custom(String),
+
+	EmptyStruct,
 	ExceedsNumericLimits(std::num::TryFromIntError),
+	InvalidObjectPath { path: String, err: crate::InvalidObjectPath },
+	InvalidSignature { signature: String, err: crate::SignatureParseError },
+	MessageTooLong { len: usize, max: usize },
+	NonBasicDictEntryKey { key: crate::Signature },
+	NulInString { position: usize },
+
+	/// The INTERFACE header field is `org.freedesktop.DBus.Local`, which is reserved for the local D-Bus implementation.
+	ReservedLocalInterface,
+
+	/// The PATH header field is `/org/freedesktop/DBus/Local`, which is reserved for the local D-Bus implementation.
+	ReservedLocalPath,
+
+	SignatureMismatch { expected: crate::Signature, actual: crate::Signature },
+	SignatureTooLong { len: usize },
+
+	/// A value serialized with [`crate::to_writer_with_signature`] does not have the shape that the signature requires.
+	ValueSignatureMismatch { expected: crate::Signature, actual: std::borrow::Cow<'static, str> },
+
+	ZeroSerial,
 }
 
 impl std::fmt::Display for SerializeError {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match self {
+			SerializeError::ArrayElementSignatureMismatch { expected, actual, index } =>
+				write!(f, r#"array element {index} has signature "{actual}" but the array's element signature is "{expected}""#),
+			SerializeError::ContainerDepthExceeded =>
+				write!(f, "value exceeds the maximum container nesting depth of {}", crate::Variant::MAX_DEPTH),
+			SerializeError::Custom(message) => f.write_str(message),
+			SerializeError::EmptyStruct => f.write_str("struct has no fields"),
 			SerializeError::ExceedsNumericLimits(_) => f.write_str("value exceeds numeric limits"),
+			SerializeError::InvalidObjectPath { path, err: _ } => write!(f, "{path:?} is not a valid object path"),
+			SerializeError::InvalidSignature { signature, err: _ } => write!(f, "{signature:?} is not a valid signature"),
+			SerializeError::MessageTooLong { len, max } => write!(f, "message is {len} bytes long which exceeds the maximum of {max} bytes"),
+			SerializeError::NonBasicDictEntryKey { key } => write!(f, "dict entry key has signature {key} which is not a basic type"),
+			SerializeError::NulInString { position } => write!(f, "string contains a nul byte at position {position}"),
+			SerializeError::ReservedLocalInterface =>
+				f.write_str("INTERFACE header field has a value that is reserved for the local D-Bus implementation"),
+			SerializeError::ReservedLocalPath => f.write_str("PATH header field has a value that is reserved for the local D-Bus implementation"),
+			SerializeError::SignatureMismatch { expected, actual } => write!(f, r#"value has signature "{actual}" but expected "{expected}""#),
+			SerializeError::SignatureTooLong { len } =>
+				write!(f, "signature is {len} bytes long which exceeds the maximum of {} bytes", crate::Signature::MAX_LEN),
+			SerializeError::ValueSignatureMismatch { expected, actual } => write!(f, r#"value is {actual} but the signature is "{expected}""#),
+			SerializeError::ZeroSerial => f.write_str("message has a serial of 0"),
 		}
 	}
 }
 
 impl std::error::Error for SerializeError {
 	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		#[allow(clippy::match_same_arms)]
 		match self {
+			SerializeError::ArrayElementSignatureMismatch { expected: _, actual: _, index: _ } => None,
+			SerializeError::ContainerDepthExceeded => None,
+			SerializeError::Custom(_) => None,
+			SerializeError::EmptyStruct => None,
 			SerializeError::ExceedsNumericLimits(err) => Some(err),
+			SerializeError::InvalidObjectPath { path: _, err } => Some(err),
+			SerializeError::InvalidSignature { signature: _, err } => Some(err),
+			SerializeError::MessageTooLong { len: _, max: _ } => None,
+			SerializeError::NonBasicDictEntryKey { key: _ } => None,
+			SerializeError::NulInString { position: _ } => None,
+			SerializeError::ReservedLocalInterface => None,
+			SerializeError::ReservedLocalPath => None,
+			SerializeError::SignatureMismatch { expected: _, actual: _ } => None,
+			SerializeError::SignatureTooLong { len: _ } => None,
+			SerializeError::ValueSignatureMismatch { expected: _, actual: _ } => None,
+			SerializeError::ZeroSerial => None,
 		}
 	}
 }
+
+impl serde::ser::Error for SerializeError {
+	fn custom<T>(msg: T) -> Self where T: std::fmt::Display {
+		SerializeError::Custom(msg.to_string())
+	}
+}
+
+/// The positions recorded by [`Serializer::serialize_array_start`] for [`Serializer::serialize_array_end`]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ArrayStart {
+	data_len_pos: usize,
+	data_start_pos: usize,
+}
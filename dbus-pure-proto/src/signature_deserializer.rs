@@ -0,0 +1,413 @@
+/// Deserialize a value of type `T` from `buf`, which holds a value with the given signature in the D-Bus binary format,
+/// like the body of a message.
+///
+/// This gives the same result as deserializing a [`crate::Variant`] from `buf` and then deserializing `T` from that `Variant`,
+/// but walks the bytes only once and does not build the intermediate `Variant`. Strings are borrowed from `buf` where `T` allows it.
+///
+/// As with the `Variant` deserializer, object paths and signatures are deserialized as strings, unix fds as their `u32` index,
+/// arrays of dict entries as maps, and variants as the value they hold. Since D-Bus has no null value, `Option`s are always `Some`.
+///
+/// `buf` must start at a position that is 8-byte aligned in the message, like the start of the body, and must be consumed completely.
+pub fn from_slice_with_signature<'de, T>(
+	buf: &'de [u8],
+	signature: &crate::Signature,
+	endianness: crate::Endianness,
+) -> Result<T, crate::DeserializeError> where T: serde::Deserialize<'de> {
+	let mut deserializer = crate::de::Deserializer::new(buf, 0, endianness);
+
+	let value = T::deserialize(SignatureDeserializer {
+		deserializer: &mut deserializer,
+		signature,
+		depth: 0,
+	})?;
+
+	let unread = buf.len() - deserializer.pos();
+	if unread > 0 {
+		return Err(crate::DeserializeError::TrailingBodyBytes { unread });
+	}
+
+	Ok(value)
+}
+
+struct SignatureDeserializer<'a, 'de> {
+	deserializer: &'a mut crate::de::Deserializer<'de>,
+	signature: &'a crate::Signature,
+	depth: usize,
+}
+
+impl<'de> serde::Deserializer<'de> for SignatureDeserializer<'_, 'de> {
+	type Error = crate::DeserializeError;
+
+	fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error> where V: serde::de::Visitor<'de> {
+		let SignatureDeserializer { deserializer, signature, depth } = self;
+
+		let depth = if signature.is_container() { depth + 1 } else { depth };
+		if depth > crate::Variant::MAX_DEPTH {
+			return Err(crate::DeserializeError::ContainerDepthExceeded);
+		}
+
+		match signature {
+			crate::Signature::Array { element } => {
+				let mut inner = deserializer.deserialize_array_start(element.alignment())?;
+
+				let value =
+					if let crate::Signature::DictEntry { key, value } = &**element {
+						// Call visit_map, since that's what serde's Deserialize impls for `std::collections::*Map` want.
+						visitor.visit_map(MapAccess {
+							deserializer: &mut inner,
+							key,
+							value,
+							depth,
+						})?
+					}
+					else {
+						visitor.visit_seq(ArrayAccess {
+							deserializer: &mut inner,
+							element,
+							depth,
+						})?
+					};
+
+				deserializer.deserialize_array_end(&inner);
+
+				Ok(value)
+			},
+
+			crate::Signature::Bool =>
+				visitor.visit_bool(deserializer.deserialize_bool()?),
+
+			crate::Signature::DictEntry { key, value } =>
+				deserializer.deserialize_struct(|deserializer| visit_fields(deserializer, [&**key, &**value].into_iter(), depth, visitor)),
+
+			crate::Signature::F64 =>
+				visitor.visit_f64(deserializer.deserialize_f64()?),
+
+			crate::Signature::I16 =>
+				visitor.visit_i16(deserializer.deserialize_i16()?),
+
+			crate::Signature::I32 =>
+				visitor.visit_i32(deserializer.deserialize_i32()?),
+
+			crate::Signature::I64 =>
+				visitor.visit_i64(deserializer.deserialize_i64()?),
+
+			crate::Signature::ObjectPath | crate::Signature::String =>
+				visitor.visit_borrowed_str(deserializer.deserialize_string()?),
+
+			crate::Signature::Signature =>
+				visitor.visit_string(crate::Signature::deserialize(deserializer)?.to_string()),
+
+			crate::Signature::Struct { fields } =>
+				deserializer.deserialize_struct(|deserializer| visit_fields(deserializer, fields.iter(), depth, visitor)),
+
+			crate::Signature::Tuple { .. } =>
+				visit_fields(deserializer, signature.iter(), depth, visitor),
+
+			crate::Signature::U8 =>
+				visitor.visit_u8(deserializer.deserialize_u8()?),
+
+			crate::Signature::U16 =>
+				visitor.visit_u16(deserializer.deserialize_u16()?),
+
+			crate::Signature::U32 =>
+				visitor.visit_u32(deserializer.deserialize_u32()?),
+
+			crate::Signature::U64 =>
+				visitor.visit_u64(deserializer.deserialize_u64()?),
+
+			crate::Signature::UnixFd =>
+				visitor.visit_u32(crate::UnixFd::deserialize(deserializer)?.0),
+
+			crate::Signature::Variant => {
+				let signature = crate::Signature::deserialize(deserializer)?;
+				SignatureDeserializer { deserializer, signature: &signature, depth }.deserialize_any(visitor)
+			},
+		}
+	}
+
+	fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error> where V: serde::de::Visitor<'de> {
+		match self.signature {
+			crate::Signature::Array { element } if **element == crate::Signature::U8 =>
+				visitor.visit_borrowed_bytes(self.deserializer.deserialize_array_u8()?),
+
+			crate::Signature::Variant => {
+				let depth = self.depth + 1;
+				if depth > crate::Variant::MAX_DEPTH {
+					return Err(crate::DeserializeError::ContainerDepthExceeded);
+				}
+
+				let signature = crate::Signature::deserialize(self.deserializer)?;
+				SignatureDeserializer { deserializer: self.deserializer, signature: &signature, depth }.deserialize_bytes(visitor)
+			},
+
+			_ => self.deserialize_any(visitor),
+		}
+	}
+
+	fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error> where V: serde::de::Visitor<'de> {
+		self.deserialize_bytes(visitor)
+	}
+
+	/// D-Bus has no null value, so every value deserializes as `Some`.
+	fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error> where V: serde::de::Visitor<'de> {
+		visitor.visit_some(self)
+	}
+
+	fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error> where V: serde::de::Visitor<'de> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	serde::forward_to_deserialize_any! {
+		bool
+		i8 i16 i32 i64 i128
+		u8 u16 u32 u64 u128
+		f32 f64
+		char
+		str string
+		unit unit_struct
+		seq tuple tuple_struct
+		map
+		struct
+		enum
+		identifier
+		ignored_any
+	}
+
+	fn is_human_readable(&self) -> bool {
+		false
+	}
+}
+
+/// Visit the fields of a struct or dict entry, or the elements of a tuple, as a sequence.
+///
+/// Fields that the visitor does not consume are deserialized and discarded, since their lengths are not known without deserializing them.
+fn visit_fields<'a, 'de, V>(
+	deserializer: &mut crate::de::Deserializer<'de>,
+	fields: impl Iterator<Item = &'a crate::Signature>,
+	depth: usize,
+	visitor: V,
+) -> Result<V::Value, crate::DeserializeError> where V: serde::de::Visitor<'de> {
+	let mut fields = FieldsAccess {
+		deserializer,
+		fields,
+		depth,
+	};
+
+	let value = visitor.visit_seq(&mut fields)?;
+
+	for signature in fields.fields {
+		let serde::de::IgnoredAny = serde::Deserialize::deserialize(SignatureDeserializer { deserializer: fields.deserializer, signature, depth })?;
+	}
+
+	Ok(value)
+}
+
+struct FieldsAccess<'a, 'de, I> {
+	deserializer: &'a mut crate::de::Deserializer<'de>,
+	fields: I,
+	depth: usize,
+}
+
+impl<'a, 'de, I> serde::de::SeqAccess<'de> for FieldsAccess<'_, 'de, I> where I: Iterator<Item = &'a crate::Signature> {
+	type Error = crate::DeserializeError;
+
+	fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error> where T: serde::de::DeserializeSeed<'de> {
+		self.fields.next()
+			.map(|signature| seed.deserialize(SignatureDeserializer { deserializer: self.deserializer, signature, depth: self.depth }))
+			.transpose()
+	}
+}
+
+struct ArrayAccess<'a, 'de> {
+	deserializer: &'a mut crate::de::Deserializer<'de>,
+	element: &'a crate::Signature,
+	depth: usize,
+}
+
+impl<'de> serde::de::SeqAccess<'de> for ArrayAccess<'_, 'de> {
+	type Error = crate::DeserializeError;
+
+	fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error> where T: serde::de::DeserializeSeed<'de> {
+		if self.deserializer.is_at_end() {
+			return Ok(None);
+		}
+
+		let element = seed.deserialize(SignatureDeserializer { deserializer: self.deserializer, signature: self.element, depth: self.depth })?;
+		Ok(Some(element))
+	}
+}
+
+struct MapAccess<'a, 'de> {
+	deserializer: &'a mut crate::de::Deserializer<'de>,
+	key: &'a crate::Signature,
+	value: &'a crate::Signature,
+	depth: usize,
+}
+
+impl<'de> serde::de::MapAccess<'de> for MapAccess<'_, 'de> {
+	type Error = crate::DeserializeError;
+
+	fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> where K: serde::de::DeserializeSeed<'de> {
+		if self.deserializer.is_at_end() {
+			return Ok(None);
+		}
+
+		// The dict entry is a container of its own.
+		let depth = self.depth + 1;
+		if depth > crate::Variant::MAX_DEPTH {
+			return Err(crate::DeserializeError::ContainerDepthExceeded);
+		}
+
+		self.deserializer.pad_to(8)?;
+
+		let key = seed.deserialize(SignatureDeserializer { deserializer: self.deserializer, signature: self.key, depth })?;
+		Ok(Some(key))
+	}
+
+	fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error> where V: serde::de::DeserializeSeed<'de> {
+		seed.deserialize(SignatureDeserializer { deserializer: self.deserializer, signature: self.value, depth: self.depth + 1 })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	/// Serialize the given variant in both endiannesses, then check that deserializing `T` from the bytes gives the expected value,
+	/// and the same value as deserializing `T` from the variant.
+	fn test<T>(variant: &crate::Variant<'_>, expected: &T) where T: std::fmt::Debug + PartialEq + serde::de::DeserializeOwned {
+		let signature = variant.signature();
+
+		for endianness in [crate::Endianness::Little, crate::Endianness::Big] {
+			let mut buf = vec![];
+			variant.serialize(&mut crate::ser::Serializer::new(&mut buf, endianness)).unwrap();
+
+			let actual: T = super::from_slice_with_signature(&buf, &signature, endianness).unwrap();
+			assert_eq!(actual, *expected);
+
+			let two_pass_variant = crate::Variant::deserialize(&mut crate::de::Deserializer::new(&buf, 0, endianness), &signature).unwrap();
+			let two_pass: T = serde::Deserialize::deserialize(two_pass_variant).unwrap();
+			assert_eq!(two_pass, *expected);
+		}
+	}
+
+	#[test]
+	fn test_from_slice_with_signature() {
+		#[derive(Debug, PartialEq, serde_derive::Deserialize)]
+		struct Foo {
+			bar: String,
+			baz: u32,
+		}
+
+		#[derive(Debug, PartialEq, serde_derive::Deserialize)]
+		struct Adapter {
+			#[serde(rename = "Name")]
+			name: String,
+			#[serde(rename = "Powered")]
+			powered: Option<bool>,
+			#[serde(rename = "Class")]
+			class: Option<u32>,
+		}
+
+		type Objects = std::collections::BTreeMap<String, std::collections::BTreeMap<String, Adapter>>;
+
+		test(
+			&crate::Variant::from_map_iter(crate::Signature::String, crate::Signature::U32, [
+				(crate::Variant::String("foo".into()), crate::Variant::U32(3)),
+				(crate::Variant::String("bar".into()), crate::Variant::U32(5)),
+			]).unwrap(),
+			&[("foo", 3), ("bar", 5)].iter().map(|&(k, v)| (k.to_owned(), v)).collect::<std::collections::BTreeMap<_, _>>(),
+		);
+
+		test(
+			&crate::Variant::array("(su)".parse().unwrap(), [
+				crate::Variant::structure([crate::Variant::String("abc".into()), crate::Variant::U32(3)]),
+				crate::Variant::structure([crate::Variant::String("def".into()), crate::Variant::U32(5)]),
+			]).unwrap(),
+			&[Foo { bar: "abc".to_owned(), baz: 3 }, Foo { bar: "def".to_owned(), baz: 5 }],
+		);
+
+		test(&crate::Variant::ArrayU32((&[0x0102_0304, 0x0506_0708][..]).into()), &[0x0102_0304_u32, 0x0506_0708]);
+		test(&crate::Variant::ArrayU8((&[1, 2, 3][..]).into()), &vec![1_u8, 2, 3]);
+		test(&crate::Variant::Bool(true), &true);
+		test(
+			&crate::Variant::DictEntry { key: (&crate::Variant::String("foo".into())).into(), value: (&crate::Variant::U32(3)).into() },
+			&("foo".to_owned(), 3),
+		);
+		test(&crate::Variant::F64(1.5), &1.5);
+		test(&crate::Variant::I64(-5), &-5_i64);
+		test(&crate::Variant::ObjectPath(crate::ObjectPath("/org/freedesktop/DBus".into())), &"/org/freedesktop/DBus".to_owned());
+		test(&crate::Variant::Signature("ay".parse().unwrap()), &"ay".to_owned());
+		test(&crate::Variant::U16(7), &7_u16);
+		test(&crate::Variant::U32(0x0102_0304), &0x0102_0304_u32);
+		test(&crate::Variant::UnixFd(crate::UnixFd(2)), &2_u32);
+		test(&crate::Variant::Variant(Box::new(crate::Variant::String("a".into())).into()), &"a".to_owned());
+		test(&crate::Variant::tuple([crate::Variant::U8(1), crate::Variant::U64(2)]), &(1_u8, 2_u64));
+
+		// The reply of `org.freedesktop.DBus.ObjectManager.GetManagedObjects`, of type `a{oa{sa{sv}}}`
+		let objects = crate::Variant::from_map_iter(crate::Signature::ObjectPath, "a{sa{sv}}".parse().unwrap(), [(
+			crate::Variant::ObjectPath(crate::ObjectPath("/org/bluez/hci0".into())),
+			crate::Variant::from_map_iter(crate::Signature::String, "a{sv}".parse().unwrap(), [(
+				crate::Variant::String("org.bluez.Adapter1".into()),
+				crate::Variant::from_map_iter(crate::Signature::String, crate::Signature::Variant, [
+					(crate::Variant::String("Name".into()), crate::Variant::Variant(Box::new(crate::Variant::String("hci0".into())).into())),
+					(crate::Variant::String("Powered".into()), crate::Variant::Variant(Box::new(crate::Variant::Bool(true)).into())),
+					(crate::Variant::String("UUIDs".into()), crate::Variant::Variant(Box::new(crate::Variant::ArrayString(vec!["a".into()].into())).into())),
+				]).unwrap(),
+			)]).unwrap(),
+		)]).unwrap();
+		test(&objects, &Objects::from([(
+			"/org/bluez/hci0".to_owned(),
+			[("org.bluez.Adapter1".to_owned(), Adapter { name: "hci0".to_owned(), powered: Some(true), class: None })].into(),
+		)]));
+	}
+
+	#[test]
+	fn test_from_slice_with_signature_borrowed() {
+		#[derive(Debug, serde_derive::Deserialize)]
+		struct Borrowed<'a> {
+			#[serde(borrow)]
+			names: Vec<&'a str>,
+			#[serde(borrow, with = "bytes")]
+			data: &'a [u8],
+		}
+
+		mod bytes {
+			pub(super) fn deserialize<'de, D>(deserializer: D) -> Result<&'de [u8], D::Error> where D: serde::Deserializer<'de> {
+				serde::Deserialize::deserialize(deserializer)
+			}
+		}
+
+		let body = crate::Variant::tuple([
+			crate::Variant::ArrayString(vec!["a".into(), "bc".into()].into()),
+			crate::Variant::Variant(Box::new(crate::Variant::ArrayU8((&[1, 2, 3][..]).into())).into()),
+		]);
+		let mut buf = vec![];
+		body.serialize(&mut crate::ser::Serializer::new(&mut buf, crate::Endianness::Little)).unwrap();
+
+		let borrowed: Borrowed<'_> = super::from_slice_with_signature(&buf, &body.signature(), crate::Endianness::Little).unwrap();
+		assert_eq!(borrowed.names, ["a", "bc"]);
+		assert_eq!(borrowed.data, [1, 2, 3]);
+	}
+
+	#[test]
+	fn test_from_slice_with_signature_errors() {
+		let body = crate::Variant::tuple([crate::Variant::String("a".into()), crate::Variant::U32(5)]);
+		let mut buf = vec![];
+		body.serialize(&mut crate::ser::Serializer::new(&mut buf, crate::Endianness::Little)).unwrap();
+
+		// The type doesn't match the signature.
+		let err = super::from_slice_with_signature::<(String, String)>(&buf, &body.signature(), crate::Endianness::Little).unwrap_err();
+		assert_eq!(err.to_string(), "invalid type: integer `5`, expected a string");
+
+		// The type doesn't use all the fields, which are skipped.
+		let (s,): (String,) = super::from_slice_with_signature(&buf, &body.signature(), crate::Endianness::Little).unwrap();
+		assert_eq!(s, "a");
+
+		// The signature doesn't cover all the bytes.
+		let err = super::from_slice_with_signature::<String>(&buf, &crate::Signature::String, crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::TrailingBodyBytes { unread: 6 }), "{err:?}");
+
+		// The bytes don't cover the signature.
+		let err = super::from_slice_with_signature::<(String, u32)>(&buf[..6], &body.signature(), crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, crate::DeserializeError::EndOfInput), "{err:?}");
+	}
+}
@@ -0,0 +1,625 @@
+/// Serialize `value` into `buf` as a value with the given signature in the D-Bus binary format, like the body of a message.
+///
+/// This gives the same result as converting `value` into a [`crate::Variant`] with that signature and serializing the `Variant`,
+/// but writes the bytes directly without building the intermediate `Variant`. The shape of `value` is checked against the signature
+/// as it is written, and a mismatch is reported with [`crate::SerializeError::ValueSignatureMismatch`].
+///
+/// Strings can be written as strings, object paths or signatures, `u32`s as `u32`s or unix fds, `f32`s as doubles,
+/// sequences as arrays, tuples and structs as D-Bus structs or dict entries, and maps as arrays of dict entries.
+/// A struct can also be written as an `a{s...}` dict keyed by its field names. `Option`s and newtype structs are transparent,
+/// but since D-Bus has no null value, `None` is an error. A variant can only hold a basic value, since the signature of
+/// anything else cannot be inferred from its serde representation.
+///
+/// `buf` must end at a position that is 8-byte aligned in the message, like the start of the body. If serialization fails,
+/// `buf` is left as it was.
+pub fn to_writer_with_signature<T>(
+	value: &T,
+	signature: &crate::Signature,
+	buf: &mut Vec<u8>,
+	endianness: crate::Endianness,
+) -> Result<(), crate::SerializeError> where T: serde::Serialize + ?Sized {
+	let start = buf.len();
+
+	let result = serialize_with_signature(value, signature, &mut crate::ser::Serializer::new(buf, endianness));
+	if result.is_err() {
+		buf.truncate(start);
+	}
+
+	result
+}
+
+pub(crate) fn serialize_with_signature<T>(
+	value: &T,
+	signature: &crate::Signature,
+	serializer: &mut crate::ser::Serializer<'_>,
+) -> Result<(), crate::SerializeError> where T: serde::Serialize + ?Sized {
+	signature.validate()?;
+
+	value.serialize(SignatureSerializer {
+		serializer,
+		signature,
+		depth: 0,
+	})
+}
+
+struct SignatureSerializer<'a, 'ser> {
+	serializer: &'a mut crate::ser::Serializer<'ser>,
+	signature: &'a crate::Signature,
+	depth: usize,
+}
+
+impl<'a, 'ser> SignatureSerializer<'a, 'ser> {
+	fn mismatch(&self, actual: impl Into<std::borrow::Cow<'static, str>>) -> crate::SerializeError {
+		crate::SerializeError::ValueSignatureMismatch { expected: self.signature.clone(), actual: actual.into() }
+	}
+
+	/// The depth of the values inside the container that the signature describes.
+	fn container_depth(&self) -> Result<usize, crate::SerializeError> {
+		let depth = self.depth + 1;
+		if depth > crate::Variant::MAX_DEPTH {
+			return Err(crate::SerializeError::ContainerDepthExceeded);
+		}
+
+		Ok(depth)
+	}
+
+	/// Serialize a basic value whose own signature is `signature`, either directly if that is what the signature requires
+	/// or wrapped in a variant.
+	fn serialize_basic(
+		self,
+		signature: &crate::Signature,
+		actual: &'static str,
+		f: impl FnOnce(&mut crate::ser::Serializer<'_>) -> Result<(), crate::SerializeError>,
+	) -> Result<(), crate::SerializeError> {
+		if self.signature == signature {
+			f(self.serializer)
+		}
+		else if let crate::Signature::Variant = self.signature {
+			let _ = self.container_depth()?;
+			signature.serialize(self.serializer)?;
+			f(self.serializer)
+		}
+		else {
+			Err(self.mismatch(actual))
+		}
+	}
+
+	fn serialize_fields(self, len: usize, actual: impl FnOnce() -> String) -> Result<Compound<'a, 'ser>, crate::SerializeError> {
+		let fields: Vec<_> = match self.signature {
+			crate::Signature::DictEntry { key, value } => vec![&**key, &**value],
+			crate::Signature::Struct { fields } => fields.iter().collect(),
+			crate::Signature::Tuple { elements } => elements.iter().collect(),
+			crate::Signature::Array { .. } => return serde::Serializer::serialize_seq(self, Some(len)),
+			_ => return Err(self.mismatch(actual())),
+		};
+		if fields.len() != len {
+			return Err(self.mismatch(actual()));
+		}
+
+		let depth =
+			if let crate::Signature::Tuple { .. } = self.signature {
+				self.depth
+			}
+			else {
+				let depth = self.container_depth()?;
+				self.serializer.pad_to(8);
+				depth
+			};
+
+		Ok(Compound {
+			serializer: self.serializer,
+			kind: CompoundKind::Fields { fields: fields.into_iter() },
+			depth,
+		})
+	}
+}
+
+impl<'a, 'ser> serde::Serializer for SignatureSerializer<'a, 'ser> {
+	type Ok = ();
+	type Error = crate::SerializeError;
+
+	type SerializeSeq = Compound<'a, 'ser>;
+	type SerializeTuple = Compound<'a, 'ser>;
+	type SerializeTupleStruct = Compound<'a, 'ser>;
+	type SerializeTupleVariant = serde::ser::Impossible<(), crate::SerializeError>;
+	type SerializeMap = Compound<'a, 'ser>;
+	type SerializeStruct = Compound<'a, 'ser>;
+	type SerializeStructVariant = serde::ser::Impossible<(), crate::SerializeError>;
+
+	fn is_human_readable(&self) -> bool {
+		false
+	}
+
+	fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+		self.serialize_basic(&crate::Signature::Bool, "a bool", |serializer| { serializer.serialize_bool(v); Ok(()) })
+	}
+
+	fn serialize_i8(self, _: i8) -> Result<Self::Ok, Self::Error> {
+		// D-Bus has no signed byte type.
+		Err(self.mismatch("an i8"))
+	}
+
+	fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+		self.serialize_basic(&crate::Signature::I16, "an i16", |serializer| { serializer.serialize_i16(v); Ok(()) })
+	}
+
+	fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+		self.serialize_basic(&crate::Signature::I32, "an i32", |serializer| { serializer.serialize_i32(v); Ok(()) })
+	}
+
+	fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+		self.serialize_basic(&crate::Signature::I64, "an i64", |serializer| { serializer.serialize_i64(v); Ok(()) })
+	}
+
+	fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+		self.serialize_basic(&crate::Signature::U8, "a u8", |serializer| { serializer.serialize_u8(v); Ok(()) })
+	}
+
+	fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+		self.serialize_basic(&crate::Signature::U16, "a u16", |serializer| { serializer.serialize_u16(v); Ok(()) })
+	}
+
+	fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+		if let crate::Signature::UnixFd = self.signature {
+			crate::UnixFd(v).serialize(self.serializer);
+			return Ok(());
+		}
+
+		self.serialize_basic(&crate::Signature::U32, "a u32", |serializer| { serializer.serialize_u32(v); Ok(()) })
+	}
+
+	fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+		self.serialize_basic(&crate::Signature::U64, "a u64", |serializer| { serializer.serialize_u64(v); Ok(()) })
+	}
+
+	fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+		self.serialize_basic(&crate::Signature::F64, "an f32", |serializer| { serializer.serialize_f64(v.into()); Ok(()) })
+	}
+
+	fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+		self.serialize_basic(&crate::Signature::F64, "an f64", |serializer| { serializer.serialize_f64(v); Ok(()) })
+	}
+
+	fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+		self.serialize_str(v.encode_utf8(&mut [0_u8; 4]))
+	}
+
+	fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+		match self.signature {
+			crate::Signature::ObjectPath => crate::ObjectPath(v.into()).serialize(self.serializer),
+
+			crate::Signature::Signature => {
+				let signature: crate::Signature =
+					v.parse().map_err(|err| crate::SerializeError::InvalidSignature { signature: v.to_owned(), err })?;
+				signature.serialize(self.serializer)
+			},
+
+			_ => self.serialize_basic(&crate::Signature::String, "a string", |serializer| serializer.serialize_string(v)),
+		}
+	}
+
+	fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+		let signature = crate::Signature::Array { element: Box::new(crate::Signature::U8) };
+		if self.signature == &signature {
+			let _ = self.container_depth()?;
+			return self.serializer.serialize_array_u8(v);
+		}
+
+		if let crate::Signature::Variant = self.signature {
+			let depth = self.container_depth()?;
+			if depth + 1 > crate::Variant::MAX_DEPTH {
+				return Err(crate::SerializeError::ContainerDepthExceeded);
+			}
+
+			signature.serialize(self.serializer)?;
+			return self.serializer.serialize_array_u8(v);
+		}
+
+		Err(self.mismatch("a byte array"))
+	}
+
+	fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+		Err(self.mismatch("None"))
+	}
+
+	fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error> where T: serde::Serialize + ?Sized {
+		value.serialize(self)
+	}
+
+	fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+		match self.signature {
+			crate::Signature::Tuple { elements } if elements.is_empty() => Ok(()),
+			_ => Err(self.mismatch("()")),
+		}
+	}
+
+	fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+		match self.signature {
+			crate::Signature::Tuple { elements } if elements.is_empty() => Ok(()),
+			_ => Err(self.mismatch(format!("unit struct {name}"))),
+		}
+	}
+
+	fn serialize_unit_variant(self, name: &'static str, _: u32, variant: &'static str) -> Result<Self::Ok, Self::Error> {
+		Err(self.mismatch(format!("enum variant {name}::{variant}")))
+	}
+
+	fn serialize_newtype_struct<T>(self, _: &'static str, value: &T) -> Result<Self::Ok, Self::Error> where T: serde::Serialize + ?Sized {
+		value.serialize(self)
+	}
+
+	fn serialize_newtype_variant<T>(
+		self,
+		name: &'static str,
+		_: u32,
+		variant: &'static str,
+		_: &T,
+	) -> Result<Self::Ok, Self::Error> where T: serde::Serialize + ?Sized {
+		Err(self.mismatch(format!("enum variant {name}::{variant}")))
+	}
+
+	fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+		let crate::Signature::Array { element } = self.signature else {
+			return Err(self.mismatch("a sequence"));
+		};
+
+		let depth = self.container_depth()?;
+		let start = self.serializer.serialize_array_start(element.alignment());
+
+		Ok(Compound {
+			serializer: self.serializer,
+			kind: CompoundKind::Array { element, start },
+			depth,
+		})
+	}
+
+	fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+		self.serialize_fields(len, || format!("a tuple of {len} elements"))
+	}
+
+	fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, Self::Error> {
+		self.serialize_fields(len, || format!("tuple struct {name} with {len} fields"))
+	}
+
+	fn serialize_tuple_variant(
+		self,
+		name: &'static str,
+		_: u32,
+		variant: &'static str,
+		_: usize,
+	) -> Result<Self::SerializeTupleVariant, Self::Error> {
+		Err(self.mismatch(format!("enum variant {name}::{variant}")))
+	}
+
+	fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+		let crate::Signature::Array { element } = self.signature else {
+			return Err(self.mismatch("a map"));
+		};
+		let crate::Signature::DictEntry { key, value } = &**element else {
+			return Err(self.mismatch("a map"));
+		};
+
+		let depth = self.container_depth()?;
+		let start = self.serializer.serialize_array_start(8);
+
+		Ok(Compound {
+			serializer: self.serializer,
+			kind: CompoundKind::Dict { key, value, start },
+			depth,
+		})
+	}
+
+	fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct, Self::Error> {
+		if let crate::Signature::Array { element } = self.signature {
+			// A struct written as an `a{s...}` dict, like the `a{sv}` property maps of many D-Bus APIs.
+			return match &**element {
+				crate::Signature::DictEntry { key, value } if **key == crate::Signature::String => {
+					let depth = self.container_depth()?;
+					let start = self.serializer.serialize_array_start(8);
+
+					Ok(Compound {
+						serializer: self.serializer,
+						kind: CompoundKind::Dict { key, value, start },
+						depth,
+					})
+				},
+
+				_ => Err(self.mismatch(format!("struct {name}"))),
+			};
+		}
+
+		self.serialize_fields(len, || format!("struct {name} with {len} fields"))
+	}
+
+	fn serialize_struct_variant(
+		self,
+		name: &'static str,
+		_: u32,
+		variant: &'static str,
+		_: usize,
+	) -> Result<Self::SerializeStructVariant, Self::Error> {
+		Err(self.mismatch(format!("enum variant {name}::{variant}")))
+	}
+}
+
+struct Compound<'a, 'ser> {
+	serializer: &'a mut crate::ser::Serializer<'ser>,
+	kind: CompoundKind<'a>,
+	depth: usize,
+}
+
+enum CompoundKind<'a> {
+	Array { element: &'a crate::Signature, start: crate::ser::ArrayStart },
+	Dict { key: &'a crate::Signature, value: &'a crate::Signature, start: crate::ser::ArrayStart },
+	Fields { fields: std::vec::IntoIter<&'a crate::Signature> },
+}
+
+impl Compound<'_, '_> {
+	fn serialize_element<T>(&mut self, value: &T) -> Result<(), crate::SerializeError> where T: serde::Serialize + ?Sized {
+		let signature = match &mut self.kind {
+			CompoundKind::Array { element, start: _ } => *element,
+			CompoundKind::Fields { fields } => fields.next().ok_or_else(|| serde::ser::Error::custom("too many elements"))?,
+			CompoundKind::Dict { .. } => return Err(serde::ser::Error::custom("dict entries must be serialized as map entries")),
+		};
+
+		value.serialize(SignatureSerializer {
+			serializer: self.serializer,
+			signature,
+			depth: self.depth,
+		})
+	}
+
+	fn serialize_entry_part<T>(&mut self, key: bool, value: &T) -> Result<(), crate::SerializeError> where T: serde::Serialize + ?Sized {
+		let CompoundKind::Dict { key: key_signature, value: value_signature, start: _ } = self.kind else {
+			return Err(serde::ser::Error::custom("map entries can only be serialized into a dict"));
+		};
+
+		// The dict entry is itself a container inside the array.
+		let depth = self.depth + 1;
+		if depth > crate::Variant::MAX_DEPTH {
+			return Err(crate::SerializeError::ContainerDepthExceeded);
+		}
+
+		let signature =
+			if key {
+				self.serializer.pad_to(8);
+				key_signature
+			}
+			else {
+				value_signature
+			};
+
+		value.serialize(SignatureSerializer {
+			serializer: self.serializer,
+			signature,
+			depth,
+		})
+	}
+
+	fn end(self) -> Result<(), crate::SerializeError> {
+		match self.kind {
+			CompoundKind::Array { element: _, start } |
+			CompoundKind::Dict { key: _, value: _, start } => self.serializer.serialize_array_end(start),
+
+			CompoundKind::Fields { fields } =>
+				if fields.len() == 0 {
+					Ok(())
+				}
+				else {
+					Err(serde::ser::Error::custom("too few elements"))
+				},
+		}
+	}
+}
+
+impl serde::ser::SerializeSeq for Compound<'_, '_> {
+	type Ok = ();
+	type Error = crate::SerializeError;
+
+	fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error> where T: serde::Serialize + ?Sized {
+		Compound::serialize_element(self, value)
+	}
+
+	fn end(self) -> Result<Self::Ok, Self::Error> {
+		Compound::end(self)
+	}
+}
+
+impl serde::ser::SerializeTuple for Compound<'_, '_> {
+	type Ok = ();
+	type Error = crate::SerializeError;
+
+	fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error> where T: serde::Serialize + ?Sized {
+		Compound::serialize_element(self, value)
+	}
+
+	fn end(self) -> Result<Self::Ok, Self::Error> {
+		Compound::end(self)
+	}
+}
+
+impl serde::ser::SerializeTupleStruct for Compound<'_, '_> {
+	type Ok = ();
+	type Error = crate::SerializeError;
+
+	fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error> where T: serde::Serialize + ?Sized {
+		Compound::serialize_element(self, value)
+	}
+
+	fn end(self) -> Result<Self::Ok, Self::Error> {
+		Compound::end(self)
+	}
+}
+
+impl serde::ser::SerializeMap for Compound<'_, '_> {
+	type Ok = ();
+	type Error = crate::SerializeError;
+
+	fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error> where T: serde::Serialize + ?Sized {
+		self.serialize_entry_part(true, key)
+	}
+
+	fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error> where T: serde::Serialize + ?Sized {
+		self.serialize_entry_part(false, value)
+	}
+
+	fn end(self) -> Result<Self::Ok, Self::Error> {
+		Compound::end(self)
+	}
+}
+
+impl serde::ser::SerializeStruct for Compound<'_, '_> {
+	type Ok = ();
+	type Error = crate::SerializeError;
+
+	fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error> where T: serde::Serialize + ?Sized {
+		if let CompoundKind::Dict { .. } = self.kind {
+			self.serialize_entry_part(true, key)?;
+			self.serialize_entry_part(false, value)
+		}
+		else {
+			Compound::serialize_element(self, value)
+		}
+	}
+
+	fn end(self) -> Result<Self::Ok, Self::Error> {
+		Compound::end(self)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	/// Check that serializing `value` with the signature of the given variant gives the same bytes as serializing the variant,
+	/// in both endiannesses.
+	fn test<T>(value: &T, variant: &crate::Variant<'_>) where T: serde::Serialize + ?Sized {
+		let signature = variant.signature();
+
+		for endianness in [crate::Endianness::Little, crate::Endianness::Big] {
+			let mut expected = vec![];
+			variant.serialize(&mut crate::ser::Serializer::new(&mut expected, endianness)).unwrap();
+
+			let mut actual = vec![];
+			super::to_writer_with_signature(value, &signature, &mut actual, endianness).unwrap();
+			assert_eq!(actual, expected);
+		}
+	}
+
+	fn test_err<T>(value: &T, signature: &str, expected: &str) where T: serde::Serialize + ?Sized {
+		let mut buf = vec![0_u8; 8];
+		let err = super::to_writer_with_signature(value, &signature.parse().unwrap(), &mut buf, crate::Endianness::Little).unwrap_err();
+		assert_eq!(err.to_string(), expected);
+		assert_eq!(buf, [0_u8; 8]);
+	}
+
+	#[derive(serde_derive::Serialize)]
+	struct Foo {
+		bar: String,
+		baz: u32,
+	}
+
+	#[derive(serde_derive::Serialize)]
+	struct Adapter {
+		#[serde(rename = "Name")]
+		name: String,
+		#[serde(rename = "Powered", skip_serializing_if = "Option::is_none")]
+		powered: Option<bool>,
+		#[serde(rename = "Class", skip_serializing_if = "Option::is_none")]
+		class: Option<u32>,
+	}
+
+	#[derive(serde_derive::Serialize)]
+	struct Bytes<'a>(#[serde(with = "bytes")] &'a [u8]);
+
+	mod bytes {
+		pub(super) fn serialize<S>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> where S: serde::Serializer {
+			serializer.serialize_bytes(value)
+		}
+	}
+
+	#[test]
+	fn test_to_writer_with_signature() {
+		test(
+			&std::collections::BTreeMap::from([("bar", 5_u32), ("foo", 3)]),
+			&crate::Variant::from_map_iter(crate::Signature::String, crate::Signature::U32, [
+				(crate::Variant::String("bar".into()), crate::Variant::U32(5)),
+				(crate::Variant::String("foo".into()), crate::Variant::U32(3)),
+			]).unwrap(),
+		);
+
+		test(
+			&[Foo { bar: "abc".to_owned(), baz: 3 }, Foo { bar: "def".to_owned(), baz: 5 }],
+			&crate::Variant::array("(su)".parse().unwrap(), [
+				crate::Variant::structure([crate::Variant::String("abc".into()), crate::Variant::U32(3)]),
+				crate::Variant::structure([crate::Variant::String("def".into()), crate::Variant::U32(5)]),
+			]).unwrap(),
+		);
+
+		test(&[0x0102_0304_u32, 0x0506_0708], &crate::Variant::ArrayU32((&[0x0102_0304, 0x0506_0708][..]).into()));
+		test(&Bytes(&[1, 2, 3]), &crate::Variant::ArrayU8((&[1, 2, 3][..]).into()));
+		test(&true, &crate::Variant::Bool(true));
+		test(
+			&("foo", 3_u32),
+			&crate::Variant::DictEntry { key: (&crate::Variant::String("foo".into())).into(), value: (&crate::Variant::U32(3)).into() },
+		);
+		test(&1.5_f64, &crate::Variant::F64(1.5));
+		test(&1.5_f32, &crate::Variant::F64(1.5));
+		test(&-5_i64, &crate::Variant::I64(-5));
+		test("/org/freedesktop/DBus", &crate::Variant::ObjectPath(crate::ObjectPath("/org/freedesktop/DBus".into())));
+		test("ay", &crate::Variant::Signature("ay".parse().unwrap()));
+		test(&'x', &crate::Variant::String("x".into()));
+		test(&Some(7_u16), &crate::Variant::U16(7));
+		test(&0x0102_0304_u32, &crate::Variant::U32(0x0102_0304));
+		test(&2_u32, &crate::Variant::UnixFd(crate::UnixFd(2)));
+		test("a", &crate::Variant::Variant(Box::new(crate::Variant::String("a".into())).into()));
+		test(&Bytes(&[1, 2]), &crate::Variant::Variant(Box::new(crate::Variant::ArrayU8((&[1, 2][..]).into())).into()));
+		test(&(1_u8, 2_u64), &crate::Variant::tuple([crate::Variant::U8(1), crate::Variant::U64(2)]));
+		test(&(), &crate::Variant::tuple([]));
+
+		// The reply of `org.freedesktop.DBus.ObjectManager.GetManagedObjects`, of type `a{oa{sa{sv}}}`, with the inner `a{sv}` from a struct
+		test(
+			&std::collections::BTreeMap::from([(
+				"/org/bluez/hci0",
+				std::collections::BTreeMap::from([("org.bluez.Adapter1", Adapter { name: "hci0".to_owned(), powered: Some(true), class: None })]),
+			)]),
+			&crate::Variant::from_map_iter(crate::Signature::ObjectPath, "a{sa{sv}}".parse().unwrap(), [(
+				crate::Variant::ObjectPath(crate::ObjectPath("/org/bluez/hci0".into())),
+				crate::Variant::from_map_iter(crate::Signature::String, "a{sv}".parse().unwrap(), [(
+					crate::Variant::String("org.bluez.Adapter1".into()),
+					crate::Variant::from_map_iter(crate::Signature::String, crate::Signature::Variant, [
+						(crate::Variant::String("Name".into()), crate::Variant::Variant(Box::new(crate::Variant::String("hci0".into())).into())),
+						(crate::Variant::String("Powered".into()), crate::Variant::Variant(Box::new(crate::Variant::Bool(true)).into())),
+					]).unwrap(),
+				)]).unwrap(),
+			)]).unwrap(),
+		);
+	}
+
+	#[test]
+	fn test_to_writer_with_signature_errors() {
+		test_err(&5_u32, "s", r#"value is a u32 but the signature is "s""#);
+		test_err(&("a", 5_u32), "ss", r#"value is a u32 but the signature is "s""#);
+		test_err(&("a", 5_u32), "sus", r#"value is a tuple of 2 elements but the signature is "sus""#);
+		test_err(&Foo { bar: "abc".to_owned(), baz: 3 }, "(sus)", r#"value is struct Foo with 2 fields but the signature is "(sus)""#);
+		test_err(&Foo { bar: "abc".to_owned(), baz: 3 }, "a{uv}", r#"value is struct Foo but the signature is "a{uv}""#);
+		test_err(&[1_u32, 2], "a{uu}", r#"value is a u32 but the signature is "{uu}""#);
+		test_err(&std::collections::BTreeMap::from([(1_u32, 2_u32)]), "au", r#"value is a map but the signature is "au""#);
+		test_err(&vec![1_u32, 2], "v", r#"value is a sequence but the signature is "v""#);
+		test_err(&None::<u32>, "u", r#"value is None but the signature is "u""#);
+		test_err(&-1_i8, "y", r#"value is an i8 but the signature is "y""#);
+		test_err("foo", "o", r#""foo" is not a valid object path"#);
+		test_err("a{", "g", r#""a{" is not a valid signature"#);
+		test_err("a\0b", "s", "string contains a nul byte at position 1");
+
+		// Signatures this deep can't be parsed, so build one directly.
+		let mut value = serde_json::json!(1);
+		let mut signature = crate::Signature::U32;
+		for _ in 0..=crate::Variant::MAX_DEPTH {
+			value = serde_json::json!([value]);
+			signature = crate::Signature::Array { element: Box::new(signature) };
+		}
+		let mut buf = vec![];
+		let err = super::to_writer_with_signature(&value, &signature, &mut buf, crate::Endianness::Little).unwrap_err();
+		assert!(matches!(err, crate::SerializeError::ContainerDepthExceeded));
+		assert!(buf.is_empty());
+	}
+}
@@ -10,6 +10,25 @@ pub trait ToVariant {
 
 	/// Convert this value to a variant.
 	fn to_variant(&self) -> crate::Variant<'_>;
+
+	/// Get the D-Bus signature of the elements of an array of values of this type.
+	///
+	/// This is the same as [`ToVariant::signature`], except for 2-tuples, which are converted to dict entries inside arrays.
+	fn array_element_signature() -> crate::Signature where Self: Sized {
+		<Self as ToVariant>::signature()
+	}
+
+	/// Convert a slice of values of this type to an array variant. The impls for `[T]`, `Vec<T>`, etc use this.
+	///
+	/// The default implementation creates a generic `Variant::Array`. The impls for types that have a dedicated `Array*` variant,
+	/// like `u8` and `Variant::ArrayU8`, override it to borrow the slice in that variant instead.
+	/// This avoids allocating a `Variant` for every element, and serializes the array much faster.
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> where Self: Sized {
+		crate::Variant::Array {
+			element_signature: <Self as ToVariant>::array_element_signature(),
+			elements: slice.iter().map(ToVariant::to_variant).collect::<Vec<_>>().into(),
+		}
+	}
 }
 
 impl ToVariant for bool {
@@ -20,6 +39,10 @@ impl ToVariant for bool {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::Bool(*self)
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayBool(slice.into())
+	}
 }
 
 impl ToVariant for f64 {
@@ -30,6 +53,10 @@ impl ToVariant for f64 {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::F64(*self)
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayF64(slice.into())
+	}
 }
 
 impl ToVariant for i16 {
@@ -40,6 +67,10 @@ impl ToVariant for i16 {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::I16(*self)
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayI16(slice.into())
+	}
 }
 
 impl ToVariant for i32 {
@@ -50,6 +81,10 @@ impl ToVariant for i32 {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::I32(*self)
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayI32(slice.into())
+	}
 }
 
 impl ToVariant for i64 {
@@ -60,6 +95,10 @@ impl ToVariant for i64 {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::I64(*self)
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayI64(slice.into())
+	}
 }
 
 impl ToVariant for crate::ObjectPath<'_> {
@@ -70,6 +109,10 @@ impl ToVariant for crate::ObjectPath<'_> {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::ObjectPath(crate::ObjectPath((&*self.0).into()))
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayObjectPath(slice.into())
+	}
 }
 
 impl ToVariant for crate::Signature {
@@ -80,6 +123,10 @@ impl ToVariant for crate::Signature {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::Signature(self.clone())
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArraySignature(slice.into())
+	}
 }
 
 impl ToVariant for str {
@@ -110,6 +157,10 @@ impl ToVariant for std::borrow::Cow<'_, str> {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::String((&**self).into())
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayString(slice.into())
+	}
 }
 
 impl ToVariant for u8 {
@@ -120,6 +171,10 @@ impl ToVariant for u8 {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::U8(*self)
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayU8(slice.into())
+	}
 }
 
 impl ToVariant for u16 {
@@ -130,6 +185,10 @@ impl ToVariant for u16 {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::U16(*self)
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayU16(slice.into())
+	}
 }
 
 impl ToVariant for u32 {
@@ -140,6 +199,10 @@ impl ToVariant for u32 {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::U32(*self)
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayU32(slice.into())
+	}
 }
 
 impl ToVariant for u64 {
@@ -150,6 +213,10 @@ impl ToVariant for u64 {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::U64(*self)
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayU64(slice.into())
+	}
 }
 
 impl ToVariant for crate::UnixFd {
@@ -160,76 +227,297 @@ impl ToVariant for crate::UnixFd {
 	fn to_variant(&self) -> crate::Variant<'_> {
 		crate::Variant::UnixFd(*self)
 	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		crate::Variant::ArrayUnixFd(slice.into())
+	}
 }
 
-// Lack of specialization means we can't impl this different for `[u8]` etc to use the more efficient `Variant::ArrayU8` etc
 impl<T> ToVariant for [T] where T: ToVariant {
 	fn signature() -> crate::Signature {
 		crate::Signature::Array {
-			element: Box::new(<T as ToVariant>::signature()),
+			element: Box::new(<T as ToVariant>::array_element_signature()),
 		}
 	}
 
 	fn to_variant(&self) -> crate::Variant<'_> {
-		crate::Variant::Array {
-			element_signature: <T as ToVariant>::signature(),
-			elements: self.iter().map(ToVariant::to_variant).collect::<Vec<_>>().into(),
-		}
+		<T as ToVariant>::slice_to_variant(self)
 	}
 }
 
-// Lack of specialization means we can't impl this different for `Cow<'_, [u8]>` etc to use the more efficient `Variant::ArrayU8` etc
 impl<T> ToVariant for std::borrow::Cow<'_, [T]> where T: ToVariant, [T]: std::borrow::ToOwned {
 	fn signature() -> crate::Signature {
 		crate::Signature::Array {
-			element: Box::new(<T as ToVariant>::signature()),
+			element: Box::new(<T as ToVariant>::array_element_signature()),
 		}
 	}
 
 	fn to_variant(&self) -> crate::Variant<'_> {
-		crate::Variant::Array {
-			element_signature: <T as ToVariant>::signature(),
-			elements: self.iter().map(ToVariant::to_variant).collect::<Vec<_>>().into(),
-		}
+		<T as ToVariant>::slice_to_variant(self)
 	}
 }
 
-// Lack of specialization means we can't impl this different for `Vec<u8>` etc to use the more efficient `Variant::ArrayU8` etc
 impl<T> ToVariant for Vec<T> where T: ToVariant {
 	fn signature() -> crate::Signature {
 		crate::Signature::Array {
-			element: Box::new(<T as ToVariant>::signature()),
+			element: Box::new(<T as ToVariant>::array_element_signature()),
 		}
 	}
 
 	fn to_variant(&self) -> crate::Variant<'_> {
-		crate::Variant::Array {
-			element_signature: <T as ToVariant>::signature(),
-			elements: self.iter().map(ToVariant::to_variant).collect::<Vec<_>>().into(),
-		}
+		<T as ToVariant>::slice_to_variant(self)
 	}
 }
 
 impl<K, V, S> ToVariant for std::collections::HashMap<K, V, S> where K: ToVariant, V: ToVariant {
 	fn signature() -> crate::Signature {
-		crate::Signature::Array {
-			element: Box::new(crate::Signature::DictEntry {
-				key: Box::new(<K as ToVariant>::signature()),
-				value: Box::new(<V as ToVariant>::signature()),
-			}),
-		}
+		dict_signature::<K, V>()
+	}
+
+	/// The entries are in the map's iteration order, which is not deterministic.
+	/// Use [`SortedDict`] if the entries need to be in a stable order.
+	fn to_variant(&self) -> crate::Variant<'_> {
+		dict_to_variant(self.iter())
+	}
+}
+
+impl<K, V> ToVariant for std::collections::BTreeMap<K, V> where K: ToVariant, V: ToVariant {
+	fn signature() -> crate::Signature {
+		dict_signature::<K, V>()
+	}
+
+	fn to_variant(&self) -> crate::Variant<'_> {
+		dict_to_variant(self.iter())
+	}
+}
+
+/// Converts a `HashMap` to a dict whose entries are sorted by their serialized keys,
+/// so that the serialized dict is the same regardless of the map's iteration order.
+///
+/// ```rust
+/// # use dbus_pure_proto::ToVariant;
+/// let map: std::collections::HashMap<&str, u32> = [("b", 2), ("a", 1)].into_iter().collect();
+/// let sorted = dbus_pure_proto::SortedDict(&map);
+/// let variant = sorted.to_variant();
+/// let keys: Vec<_> = variant.as_dict().unwrap().map(|(key, _)| key.as_string().unwrap()).collect();
+/// assert_eq!(keys, ["a", "b"]);
+/// ```
+#[derive(Debug)]
+pub struct SortedDict<'a, K, V, S = std::collections::hash_map::RandomState>(pub &'a std::collections::HashMap<K, V, S>);
+
+impl<K, V, S> ToVariant for SortedDict<'_, K, V, S> where K: ToVariant, V: ToVariant {
+	fn signature() -> crate::Signature {
+		dict_signature::<K, V>()
 	}
 
 	fn to_variant(&self) -> crate::Variant<'_> {
+		let mut entries: Vec<_> =
+			self.0.iter()
+			.map(|(key, value)| {
+				let key = key.to_variant();
+				let mut serialized_key = vec![];
+				// An invalid key fails to serialize here and will fail again when the dict is serialized, so it just needs to be ordered somewhere.
+				let _ = key.serialize(&mut crate::ser::Serializer::new(&mut serialized_key, crate::Endianness::Little));
+				(serialized_key, key, value)
+			})
+			.collect();
+		entries.sort_by(|(serialized_key1, _, _), (serialized_key2, _, _)| serialized_key1.cmp(serialized_key2));
+
 		crate::Variant::Array {
-			element_signature: crate::Signature::DictEntry {
-				key: Box::new(<K as ToVariant>::signature()),
-				value: Box::new(<V as ToVariant>::signature()),
-			},
-			elements: self.iter().map(|(key, value)| crate::Variant::DictEntry {
-				key: crate::std2::CowRef::Owned(Box::new(key.to_variant())),
+			element_signature: dict_entry_signature::<K, V>(),
+			elements: entries.into_iter().map(|(_, key, value)| crate::Variant::DictEntry {
+				key: crate::std2::CowRef::Owned(Box::new(key)),
 				value: crate::std2::CowRef::Owned(Box::new(value.to_variant())),
 			}).collect::<Vec<_>>().into(),
 		}
 	}
 }
+
+fn dict_entry_signature<K, V>() -> crate::Signature where K: ToVariant, V: ToVariant {
+	crate::Signature::DictEntry {
+		key: Box::new(<K as ToVariant>::signature()),
+		value: Box::new(<V as ToVariant>::signature()),
+	}
+}
+
+fn dict_signature<K, V>() -> crate::Signature where K: ToVariant, V: ToVariant {
+	crate::Signature::Array {
+		element: Box::new(dict_entry_signature::<K, V>()),
+	}
+}
+
+fn dict_to_variant<'a, K, V>(entries: impl Iterator<Item = (&'a K, &'a V)>) -> crate::Variant<'a> where K: ToVariant + 'a, V: ToVariant + 'a {
+	crate::Variant::Array {
+		element_signature: dict_entry_signature::<K, V>(),
+		elements: entries.map(|(key, value)| crate::Variant::DictEntry {
+			key: crate::std2::CowRef::Owned(Box::new(key.to_variant())),
+			value: crate::std2::CowRef::Owned(Box::new(value.to_variant())),
+		}).collect::<Vec<_>>().into(),
+	}
+}
+
+impl<T, const N: usize> ToVariant for [T; N] where T: ToVariant {
+	fn signature() -> crate::Signature {
+		<[T] as ToVariant>::signature()
+	}
+
+	fn to_variant(&self) -> crate::Variant<'_> {
+		<[T] as ToVariant>::to_variant(self)
+	}
+}
+
+impl<T> ToVariant for &'_ T where T: ToVariant + ?Sized {
+	fn signature() -> crate::Signature {
+		<T as ToVariant>::signature()
+	}
+
+	fn to_variant(&self) -> crate::Variant<'_> {
+		<T as ToVariant>::to_variant(self)
+	}
+}
+
+macro_rules! to_variant_tuple {
+	($($ty:ident $index:tt),* $(=> { $($array_fns:tt)* })?) => {
+		/// Tuples are converted to a `Variant::Tuple`, ie the body of a message with multiple arguments.
+		/// Use a struct with `#[derive(dbus_pure_macros::ToVariant)]` for a D-Bus struct instead.
+		impl<$($ty),*> ToVariant for ($($ty,)*) where $($ty: ToVariant),* {
+			fn signature() -> crate::Signature {
+				crate::Signature::Tuple {
+					elements: vec![$(<$ty as ToVariant>::signature() ,)*],
+				}
+			}
+
+			fn to_variant(&self) -> crate::Variant<'_> {
+				crate::Variant::Tuple {
+					elements: vec![$(self.$index.to_variant() ,)*].into(),
+				}
+			}
+
+			$($($array_fns)*)?
+		}
+	};
+}
+
+// The empty tuple is the body of a message without arguments.
+to_variant_tuple!();
+to_variant_tuple!(A 0);
+// Arrays of 2-tuples, like `Vec<(K, V)>`, are converted to dicts with the entries in the same order.
+to_variant_tuple!(A 0, B 1 => {
+	fn array_element_signature() -> crate::Signature {
+		dict_entry_signature::<A, B>()
+	}
+
+	fn slice_to_variant(slice: &[Self]) -> crate::Variant<'_> {
+		dict_to_variant(slice.iter().map(|(key, value)| (key, value)))
+	}
+});
+to_variant_tuple!(A 0, B 1, C 2);
+to_variant_tuple!(A 0, B 1, C 2, D 3);
+to_variant_tuple!(A 0, B 1, C 2, D 3, E 4);
+to_variant_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+to_variant_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+to_variant_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+#[cfg(test)]
+mod tests {
+	use super::ToVariant;
+
+	#[test]
+	fn test_tuple() {
+		fn serialize(body: &crate::Variant<'_>) -> Vec<u8> {
+			let mut buf = vec![];
+			body.serialize(&mut crate::ser::Serializer::new(&mut buf, crate::Endianness::Little)).unwrap();
+			buf
+		}
+
+		let args = ("org.freedesktop.DBus", 5_u32);
+		assert_eq!(<(&str, u32)>::signature().to_string(), "su");
+
+		let body = args.to_variant();
+		let expected = crate::Variant::Tuple { elements: vec![crate::Variant::String("org.freedesktop.DBus".into()), crate::Variant::U32(5)].into() };
+		assert_eq!(body, expected);
+		assert_eq!(serialize(&body), serialize(&expected));
+
+		assert_eq!(<()>::signature().to_string(), "");
+		let body = ().to_variant();
+		assert_eq!(body, crate::Variant::Tuple { elements: vec![].into() });
+		assert_eq!(serialize(&body), b"");
+	}
+
+	#[test]
+	fn test_dict() {
+		fn serialize(body: &crate::Variant<'_>) -> Vec<u8> {
+			let mut buf = vec![];
+			body.serialize(&mut crate::ser::Serializer::new(&mut buf, crate::Endianness::Little)).unwrap();
+			buf
+		}
+
+		let entries = vec![("b", 2_u32), ("a", 1), ("c", 3)];
+		assert_eq!(<Vec<(&str, u32)>>::signature().to_string(), "a{su}");
+		let variant = entries.to_variant();
+		assert_eq!(variant.signature().to_string(), "a{su}");
+		let keys: Vec<_> = variant.as_dict().unwrap().map(|(key, _)| key.as_string().unwrap()).collect();
+		assert_eq!(keys, ["b", "a", "c"]);
+		assert_eq!(serialize(&entries[..].to_variant()), serialize(&variant));
+
+		let expected = serialize(&vec![("a", 1_u32), ("b", 2), ("c", 3)].to_variant());
+
+		let btree_map: std::collections::BTreeMap<_, _> = entries.iter().copied().collect();
+		assert_eq!(<std::collections::BTreeMap<&str, u32>>::signature().to_string(), "a{su}");
+		assert_eq!(serialize(&btree_map.to_variant()), expected);
+
+		// Every `HashMap` has a different random iteration order, so different maps give the same output only if it's sorted.
+		for _ in 0..16 {
+			let hash_map: std::collections::HashMap<_, _> = entries.iter().copied().collect();
+			assert_eq!(serialize(&super::SortedDict(&hash_map).to_variant()), expected);
+		}
+	}
+
+	#[test]
+	fn test_fixed_size_array() {
+		assert_eq!(<[u32; 3]>::signature().to_string(), "au");
+		assert_eq!([1_u32, 2, 3].to_variant(), crate::Variant::ArrayU32(vec![1, 2, 3].into()));
+
+		let paths = [crate::ObjectPath("/a".into())];
+		assert_eq!(paths.to_variant(), crate::Variant::ArrayObjectPath((&paths[..]).into()));
+	}
+
+	#[test]
+	fn test_primitive_slice() {
+		// Start at an odd offset so that the arrays' padding is exercised too.
+		fn serialize(body: &crate::Variant<'_>) -> Vec<u8> {
+			let mut buf = vec![0x01];
+			body.serialize(&mut crate::ser::Serializer::new(&mut buf, crate::Endianness::Little)).unwrap();
+			buf
+		}
+
+		fn generic_array<T>(slice: &[T]) -> crate::Variant<'_> where T: ToVariant {
+			crate::Variant::Array {
+				element_signature: T::signature(),
+				elements: slice.iter().map(ToVariant::to_variant).collect::<Vec<_>>().into(),
+			}
+		}
+
+		let bytes = vec![0_u8; 1024];
+		let variant = bytes.to_variant();
+		let crate::Variant::ArrayU8(std::borrow::Cow::Borrowed(borrowed)) = &variant else { panic!("{variant:?}") };
+		assert_eq!(borrowed.as_ptr(), bytes.as_ptr());
+
+		// The dedicated `Array*` variants serialize to the same bytes as the generic `Array` variant.
+		assert_eq!(serialize(&bytes.to_variant()), serialize(&generic_array(&bytes)));
+		assert_eq!(serialize(&[true, false].to_variant()), serialize(&generic_array(&[true, false])));
+		assert_eq!(serialize(&[1.5_f64, -1.].to_variant()), serialize(&generic_array(&[1.5_f64, -1.])));
+		assert_eq!(serialize(&[1_i16, -1].to_variant()), serialize(&generic_array(&[1_i16, -1])));
+		assert_eq!(serialize(&[1_i32, -1].to_variant()), serialize(&generic_array(&[1_i32, -1])));
+		assert_eq!(serialize(&[1_i64, -1].to_variant()), serialize(&generic_array(&[1_i64, -1])));
+		assert_eq!(serialize(&[1_u16, 2].to_variant()), serialize(&generic_array(&[1_u16, 2])));
+		assert_eq!(serialize(&[1_u32, 2].to_variant()), serialize(&generic_array(&[1_u32, 2])));
+		assert_eq!(serialize(&[1_u64, 2].to_variant()), serialize(&generic_array(&[1_u64, 2])));
+		assert_eq!(serialize(&[crate::UnixFd(3)].to_variant()), serialize(&generic_array(&[crate::UnixFd(3)])));
+		let paths = [crate::ObjectPath("/a".into()), crate::ObjectPath("/b/c".into())];
+		assert_eq!(serialize(&paths.to_variant()), serialize(&generic_array(&paths)));
+		assert_eq!(serialize(&[crate::Signature::U8].to_variant()), serialize(&generic_array(&[crate::Signature::U8])));
+		let strings: [std::borrow::Cow<'_, str>; 2] = ["a".into(), "bc".into()];
+		assert_eq!(serialize(&strings.to_variant()), serialize(&generic_array(&strings)));
+	}
+}
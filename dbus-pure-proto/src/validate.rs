@@ -0,0 +1,213 @@
+//! Functions to check that strings are valid names according to the D-Bus spec, before putting them in a message.
+
+/// The maximum length in bytes of a bus name, interface name, member name or error name.
+pub const MAX_NAME_LEN: usize = 255;
+
+/// The object path reserved by the D-Bus spec for messages generated by the local D-Bus implementation. It must not be used in sent messages.
+pub const LOCAL_PATH: &str = "/org/freedesktop/DBus/Local";
+
+/// The interface reserved by the D-Bus spec for messages generated by the local D-Bus implementation. It must not be used in sent messages.
+pub const LOCAL_INTERFACE: &str = "org.freedesktop.DBus.Local";
+
+/// Check that the given string is a valid bus name.
+///
+/// A bus name is either a unique name like `:1.7`, or a well-known name like `org.freedesktop.DBus`.
+/// Both consist of at least two non-empty elements separated by `.`, with each element containing only the characters `[A-Za-z0-9_-]`.
+/// The elements of well-known names must not start with a digit.
+pub fn validate_bus_name(name: &str) -> Result<(), InvalidName> {
+	match name.strip_prefix(':') {
+		Some(rest) => validate_dotted_name(rest, 1, name.len(), true, true),
+		None => validate_dotted_name(name, 0, name.len(), true, false),
+	}
+}
+
+/// Check that the given string is a valid interface name like `org.freedesktop.DBus`.
+///
+/// An interface name consists of at least two non-empty elements separated by `.`, with each element containing only the characters `[A-Za-z0-9_]`
+/// and not starting with a digit.
+pub fn validate_interface_name(name: &str) -> Result<(), InvalidName> {
+	validate_dotted_name(name, 0, name.len(), false, false)
+}
+
+/// Check that the given string is a valid error name like `org.freedesktop.DBus.Error.Failed`.
+///
+/// Error names follow the same rules as interface names.
+pub fn validate_error_name(name: &str) -> Result<(), InvalidName> {
+	validate_interface_name(name)
+}
+
+/// Check that the given string is a valid member name like `ListNames`.
+///
+/// A member name is non-empty, contains only the characters `[A-Za-z0-9_]`, and does not start with a digit.
+pub fn validate_member_name(name: &str) -> Result<(), InvalidName> {
+	validate_len(name)?;
+
+	for (offset, c) in name.char_indices() {
+		match c {
+			'0'..='9' if offset == 0 => return Err(InvalidName::ElementStartsWithDigit { offset }),
+			'A'..='Z' | 'a'..='z' | '0'..='9' | '_' => (),
+			character => return Err(InvalidName::InvalidCharacter { character, offset }),
+		}
+	}
+
+	Ok(())
+}
+
+/// Check that the given string is a valid object path. See [`crate::ObjectPath::new`] for the rules.
+pub fn validate_object_path(path: &str) -> Result<(), crate::InvalidObjectPath> {
+	let mut chars = path.char_indices();
+
+	if chars.next() != Some((0, '/')) {
+		return Err(crate::InvalidObjectPath::MissingLeadingSlash);
+	}
+
+	if path.len() == 1 {
+		return Ok(());
+	}
+
+	let mut previous = '/';
+	for (offset, c) in chars {
+		match c {
+			'/' if previous == '/' => return Err(crate::InvalidObjectPath::EmptyElement { offset }),
+			'/' | 'A'..='Z' | 'a'..='z' | '0'..='9' | '_' => (),
+			character => return Err(crate::InvalidObjectPath::InvalidCharacter { character, offset }),
+		}
+
+		previous = c;
+	}
+
+	if previous == '/' {
+		return Err(crate::InvalidObjectPath::TrailingSlash { offset: path.len() - 1 });
+	}
+
+	Ok(())
+}
+
+fn validate_len(name: &str) -> Result<(), InvalidName> {
+	if name.is_empty() {
+		return Err(InvalidName::Empty);
+	}
+
+	if name.len() > MAX_NAME_LEN {
+		return Err(InvalidName::TooLong { len: name.len() });
+	}
+
+	Ok(())
+}
+
+/// Validates a name made of `.`-separated elements. `name` starts at byte `start` of the full name, whose length is `full_len`.
+fn validate_dotted_name(name: &str, start: usize, full_len: usize, allow_hyphen: bool, allow_leading_digit: bool) -> Result<(), InvalidName> {
+	if full_len == 0 {
+		return Err(InvalidName::Empty);
+	}
+
+	if full_len > MAX_NAME_LEN {
+		return Err(InvalidName::TooLong { len: full_len });
+	}
+
+	let mut num_elements = 1;
+	let mut element_start = true;
+
+	for (offset, c) in name.char_indices() {
+		let offset = start + offset;
+
+		match c {
+			'.' if element_start => return Err(InvalidName::EmptyElement { offset }),
+			'.' => {
+				num_elements += 1;
+				element_start = true;
+				continue;
+			},
+			'0'..='9' if element_start && !allow_leading_digit => return Err(InvalidName::ElementStartsWithDigit { offset }),
+			'A'..='Z' | 'a'..='z' | '0'..='9' | '_' => (),
+			'-' if allow_hyphen => (),
+			character => return Err(InvalidName::InvalidCharacter { character, offset }),
+		}
+
+		element_start = false;
+	}
+
+	if element_start {
+		return Err(InvalidName::EmptyElement { offset: full_len });
+	}
+
+	if num_elements < 2 {
+		return Err(InvalidName::TooFewElements);
+	}
+
+	Ok(())
+}
+
+/// An error from validating a name.
+///
+/// Offsets are byte offsets into the name.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvalidName {
+	/// The element that starts at the given offset begins with a digit.
+	ElementStartsWithDigit { offset: usize },
+
+	/// The name is empty.
+	Empty,
+
+	/// The element that ends at the given offset is empty.
+	EmptyElement { offset: usize },
+
+	/// The character at the given offset is not allowed in this kind of name.
+	InvalidCharacter { character: char, offset: usize },
+
+	/// The name has only one element, but it must have at least two.
+	TooFewElements,
+
+	/// The name is longer than [`MAX_NAME_LEN`] bytes.
+	TooLong { len: usize },
+}
+
+impl std::fmt::Display for InvalidName {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			InvalidName::ElementStartsWithDigit { offset } => write!(f, "name has an element that starts with a digit at offset {offset}"),
+			InvalidName::Empty => f.write_str("name is empty"),
+			InvalidName::EmptyElement { offset } => write!(f, "name has an empty element at offset {offset}"),
+			InvalidName::InvalidCharacter { character, offset } => write!(f, "name has invalid character {character:?} at offset {offset}"),
+			InvalidName::TooFewElements => f.write_str("name must have at least two elements separated by '.'"),
+			InvalidName::TooLong { len } => write!(f, "name is {len} bytes long which exceeds the maximum of {MAX_NAME_LEN} bytes"),
+		}
+	}
+}
+
+impl std::error::Error for InvalidName {
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_validate_names() {
+		use super::InvalidName;
+
+		let long_name = format!("a.{}", "b".repeat(254));
+
+		for (name, bus_name, interface_name, member_name) in [
+			("org.freedesktop.DBus", Ok(()), Ok(()), Err(InvalidName::InvalidCharacter { character: '.', offset: 3 })),
+			(":1.7", Ok(()), Err(InvalidName::InvalidCharacter { character: ':', offset: 0 }), Err(InvalidName::InvalidCharacter { character: ':', offset: 0 })),
+			(":1", Err(InvalidName::TooFewElements), Err(InvalidName::InvalidCharacter { character: ':', offset: 0 }), Err(InvalidName::InvalidCharacter { character: ':', offset: 0 })),
+			(":", Err(InvalidName::EmptyElement { offset: 1 }), Err(InvalidName::InvalidCharacter { character: ':', offset: 0 }), Err(InvalidName::InvalidCharacter { character: ':', offset: 0 })),
+			("1.org", Err(InvalidName::ElementStartsWithDigit { offset: 0 }), Err(InvalidName::ElementStartsWithDigit { offset: 0 }), Err(InvalidName::ElementStartsWithDigit { offset: 0 })),
+			("org.7zip", Err(InvalidName::ElementStartsWithDigit { offset: 4 }), Err(InvalidName::ElementStartsWithDigit { offset: 4 }), Err(InvalidName::InvalidCharacter { character: '.', offset: 3 })),
+			("org.foo-bar", Ok(()), Err(InvalidName::InvalidCharacter { character: '-', offset: 7 }), Err(InvalidName::InvalidCharacter { character: '.', offset: 3 })),
+			("org.", Err(InvalidName::EmptyElement { offset: 4 }), Err(InvalidName::EmptyElement { offset: 4 }), Err(InvalidName::InvalidCharacter { character: '.', offset: 3 })),
+			(".org.foo", Err(InvalidName::EmptyElement { offset: 0 }), Err(InvalidName::EmptyElement { offset: 0 }), Err(InvalidName::InvalidCharacter { character: '.', offset: 0 })),
+			("org..foo", Err(InvalidName::EmptyElement { offset: 4 }), Err(InvalidName::EmptyElement { offset: 4 }), Err(InvalidName::InvalidCharacter { character: '.', offset: 3 })),
+			("ListNames", Err(InvalidName::TooFewElements), Err(InvalidName::TooFewElements), Ok(())),
+			("_a1", Err(InvalidName::TooFewElements), Err(InvalidName::TooFewElements), Ok(())),
+			("1a", Err(InvalidName::ElementStartsWithDigit { offset: 0 }), Err(InvalidName::ElementStartsWithDigit { offset: 0 }), Err(InvalidName::ElementStartsWithDigit { offset: 0 })),
+			("", Err(InvalidName::Empty), Err(InvalidName::Empty), Err(InvalidName::Empty)),
+			(&long_name, Err(InvalidName::TooLong { len: 256 }), Err(InvalidName::TooLong { len: 256 }), Err(InvalidName::TooLong { len: 256 })),
+			(&long_name[..255], Ok(()), Ok(()), Err(InvalidName::InvalidCharacter { character: '.', offset: 1 })),
+		] {
+			assert_eq!(super::validate_bus_name(name), bus_name, "{name}");
+			assert_eq!(super::validate_interface_name(name), interface_name, "{name}");
+			assert_eq!(super::validate_error_name(name), interface_name, "{name}");
+			assert_eq!(super::validate_member_name(name), member_name, "{name}");
+		}
+	}
+}
@@ -25,6 +25,12 @@ pub enum Variant<'a> {
 	/// Simpler wrapper over an i64 array (`ax`) than the generic `Array` variant.
 	ArrayI64(std::borrow::Cow<'a, [i64]>),
 
+	/// Simpler wrapper over an object path array (`ao`) than the generic `Array` variant.
+	ArrayObjectPath(std::borrow::Cow<'a, [crate::ObjectPath<'a>]>),
+
+	/// Simpler wrapper over a signature array (`ag`) than the generic `Array` variant.
+	ArraySignature(std::borrow::Cow<'a, [crate::Signature]>),
+
 	/// Simpler wrapper over a string array (`as`) than the generic `Array` variant.
 	ArrayString(std::borrow::Cow<'a, [std::borrow::Cow<'a, str>]>),
 
@@ -89,6 +95,129 @@ pub enum Variant<'a> {
 }
 
 impl<'a> Variant<'a> {
+	/// The maximum total nesting depth of containers (arrays, dict entries, structs and variants) in a value.
+	///
+	/// Signatures limit the nesting of arrays and structs, but a variant carries its own signature, so nested variants could otherwise nest indefinitely.
+	/// Deserializing or serializing a value that is nested deeper than this fails.
+	pub const MAX_DEPTH: usize = 64;
+
+	/// Create a `Variant::Array` with the given element signature and elements.
+	///
+	/// Returns an error if any of the elements does not have the given signature.
+	pub fn array(
+		element_signature: crate::Signature,
+		elements: impl IntoIterator<Item = Variant<'a>>,
+	) -> Result<Self, crate::SerializeError> {
+		let elements: Vec<_> = elements.into_iter().collect();
+		for element in &elements {
+			check_signature(&element_signature, element)?;
+		}
+
+		Ok(Variant::Array { element_signature, elements: elements.into() })
+	}
+
+	/// Create a `Variant::DictEntry` with the given key and value.
+	pub fn dict_entry(key: Variant<'a>, value: Variant<'a>) -> Self {
+		Variant::DictEntry { key: Box::new(key).into(), value: Box::new(value).into() }
+	}
+
+	/// Create an array of dict entries, like `a{sv}`, with the given key and value signatures and the given `(key, value)` pairs.
+	///
+	/// Returns an error if the key signature is not a basic type, or if any key or value does not have the given signature.
+	///
+	/// ```rust
+	/// # use dbus_pure_proto::{Signature, Variant};
+	/// let hints = Variant::from_map_iter(Signature::String, Signature::Variant, [
+	///     (Variant::String("urgency".into()), Variant::Variant(Box::new(Variant::U8(2)).into())),
+	/// ]).unwrap();
+	/// assert_eq!(hints.signature().to_string(), "a{sv}");
+	/// ```
+	pub fn from_map_iter(
+		key_signature: crate::Signature,
+		value_signature: crate::Signature,
+		entries: impl IntoIterator<Item = (Variant<'a>, Variant<'a>)>,
+	) -> Result<Self, crate::SerializeError> {
+		if !key_signature.is_basic() {
+			return Err(crate::SerializeError::NonBasicDictEntryKey { key: key_signature });
+		}
+
+		let elements: Result<Vec<_>, _> =
+			entries.into_iter()
+			.map(|(key, value)| {
+				check_signature(&key_signature, &key)?;
+				check_signature(&value_signature, &value)?;
+				Ok(Variant::dict_entry(key, value))
+			})
+			.collect();
+		let elements = elements?;
+
+		Ok(Variant::Array {
+			element_signature: crate::Signature::DictEntry { key: Box::new(key_signature), value: Box::new(value_signature) },
+			elements: elements.into(),
+		})
+	}
+
+	/// Create a `Variant::Tuple` with the given elements. This is the body of a message with multiple arguments.
+	///
+	/// ```rust
+	/// # use dbus_pure_proto::Variant;
+	/// let body = Variant::tuple(["x".into(), 5_u32.into()]);
+	/// assert_eq!(body.signature().to_string(), "su");
+	/// ```
+	pub fn tuple(elements: impl IntoIterator<Item = Variant<'a>>) -> Self {
+		Variant::Tuple { elements: elements.into_iter().collect::<Vec<_>>().into() }
+	}
+
+	/// Create a `Variant::Struct` with the given fields.
+	pub fn structure(fields: impl IntoIterator<Item = Variant<'a>>) -> Self {
+		Variant::Struct { fields: fields.into_iter().collect::<Vec<_>>().into() }
+	}
+
+	/// Returns an iterator over the elements of this `Variant` if it's any kind of array, including the simpler `Array*` variants.
+	///
+	/// Elements of the generic `Array` variant are borrowed. Elements of the other `Array*` variants are wrapped in owned `Variant`s.
+	pub fn iter_array<'b>(&'b self) -> Option<VariantArrayIter<'b, 'a>> {
+		let inner = match self {
+			Variant::Array { element_signature: _, elements } => VariantArrayIterInner::Array(elements.iter()),
+			Variant::ArrayBool(elements) => VariantArrayIterInner::Bool(elements.iter()),
+			Variant::ArrayF64(elements) => VariantArrayIterInner::F64(elements.iter()),
+			Variant::ArrayI16(elements) => VariantArrayIterInner::I16(elements.iter()),
+			Variant::ArrayI32(elements) => VariantArrayIterInner::I32(elements.iter()),
+			Variant::ArrayI64(elements) => VariantArrayIterInner::I64(elements.iter()),
+			Variant::ArrayObjectPath(elements) => VariantArrayIterInner::ObjectPath(elements.iter()),
+			Variant::ArraySignature(elements) => VariantArrayIterInner::Signature(elements.iter()),
+			Variant::ArrayString(elements) => VariantArrayIterInner::String(elements.iter()),
+			Variant::ArrayU8(elements) => VariantArrayIterInner::U8(elements.iter()),
+			Variant::ArrayU16(elements) => VariantArrayIterInner::U16(elements.iter()),
+			Variant::ArrayU32(elements) => VariantArrayIterInner::U32(elements.iter()),
+			Variant::ArrayU64(elements) => VariantArrayIterInner::U64(elements.iter()),
+			Variant::ArrayUnixFd(elements) => VariantArrayIterInner::UnixFd(elements.iter()),
+			_ => return None,
+		};
+		Some(VariantArrayIter(inner))
+	}
+
+	/// Returns the number of elements of this `Variant` if it's any kind of array, including the simpler `Array*` variants.
+	pub fn array_len(&self) -> Option<usize> {
+		match self {
+			Variant::Array { element_signature: _, elements } => Some(elements.len()),
+			Variant::ArrayBool(elements) => Some(elements.len()),
+			Variant::ArrayF64(elements) => Some(elements.len()),
+			Variant::ArrayI16(elements) => Some(elements.len()),
+			Variant::ArrayI32(elements) => Some(elements.len()),
+			Variant::ArrayI64(elements) => Some(elements.len()),
+			Variant::ArrayObjectPath(elements) => Some(elements.len()),
+			Variant::ArraySignature(elements) => Some(elements.len()),
+			Variant::ArrayString(elements) => Some(elements.len()),
+			Variant::ArrayU8(elements) => Some(elements.len()),
+			Variant::ArrayU16(elements) => Some(elements.len()),
+			Variant::ArrayU32(elements) => Some(elements.len()),
+			Variant::ArrayU64(elements) => Some(elements.len()),
+			Variant::ArrayUnixFd(elements) => Some(elements.len()),
+			_ => None,
+		}
+	}
+
 	/// Convenience function to view this `Variant` as a `&[Variant]` if it's an array and its elements have the given signature.
 	pub fn as_array<'b>(&'b self, expected_element_signature: &crate::Signature) -> Option<&'b [Variant<'a>]> {
 		match self {
@@ -97,6 +226,62 @@ impl<'a> Variant<'a> {
 		}
 	}
 
+	/// Convenience function to view this `Variant` as a `&[bool]` if it's an array of bools.
+	pub fn as_array_bool(&self) -> Option<&[bool]> {
+		match self {
+			Variant::ArrayBool(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&[f64]` if it's an array of doubles.
+	pub fn as_array_f64(&self) -> Option<&[f64]> {
+		match self {
+			Variant::ArrayF64(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&[i16]` if it's an array of i16s.
+	pub fn as_array_i16(&self) -> Option<&[i16]> {
+		match self {
+			Variant::ArrayI16(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&[i32]` if it's an array of i32s.
+	pub fn as_array_i32(&self) -> Option<&[i32]> {
+		match self {
+			Variant::ArrayI32(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&[i64]` if it's an array of i64s.
+	pub fn as_array_i64(&self) -> Option<&[i64]> {
+		match self {
+			Variant::ArrayI64(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&[crate::ObjectPath<'_>]` if it's an array of object paths.
+	pub fn as_array_object_path<'b>(&'b self) -> Option<&'b [crate::ObjectPath<'a>]> {
+		match self {
+			Variant::ArrayObjectPath(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&[crate::Signature]` if it's an array of signatures.
+	pub fn as_array_signature(&self) -> Option<&[crate::Signature]> {
+		match self {
+			Variant::ArraySignature(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
 	/// Convenience function to view this `Variant` as a `&[Cow<'_, str>]` if it's an array of strings.
 	pub fn as_array_string<'b>(&'b self) -> Option<&'b [std::borrow::Cow<'a, str>]> {
 		match self {
@@ -105,6 +290,46 @@ impl<'a> Variant<'a> {
 		}
 	}
 
+	/// Convenience function to view this `Variant` as a `&[u8]` if it's an array of bytes.
+	pub fn as_array_u8(&self) -> Option<&[u8]> {
+		match self {
+			Variant::ArrayU8(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&[u16]` if it's an array of u16s.
+	pub fn as_array_u16(&self) -> Option<&[u16]> {
+		match self {
+			Variant::ArrayU16(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&[u32]` if it's an array of u32s.
+	pub fn as_array_u32(&self) -> Option<&[u32]> {
+		match self {
+			Variant::ArrayU32(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&[u64]` if it's an array of u64s.
+	pub fn as_array_u64(&self) -> Option<&[u64]> {
+		match self {
+			Variant::ArrayU64(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&[crate::UnixFd]` if it's an array of unix fds.
+	pub fn as_array_unix_fd(&self) -> Option<&[crate::UnixFd]> {
+		match self {
+			Variant::ArrayUnixFd(elements) => Some(elements),
+			_ => None,
+		}
+	}
+
 	/// Convenience function to view this `Variant` as a `bool` if it is one.
 	pub fn as_bool(&self) -> Option<bool> {
 		match self {
@@ -113,6 +338,90 @@ impl<'a> Variant<'a> {
 		}
 	}
 
+	/// Convenience function to view this `Variant` as an `f64` if it is one.
+	pub fn as_f64(&self) -> Option<f64> {
+		match self {
+			Variant::F64(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as an `i16` if it is one.
+	pub fn as_i16(&self) -> Option<i16> {
+		match self {
+			Variant::I16(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as an `i32` if it is one.
+	pub fn as_i32(&self) -> Option<i32> {
+		match self {
+			Variant::I32(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as an `i64` if it is one.
+	pub fn as_i64(&self) -> Option<i64> {
+		match self {
+			Variant::I64(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as an iterator of `(key, value)` pairs if it's an array of dict entries, like `a{sv}`.
+	pub fn as_dict<'b>(&'b self) -> Option<impl Iterator<Item = (&'b Variant<'a>, &'b Variant<'a>)>> {
+		match self {
+			Variant::Array { element_signature: crate::Signature::DictEntry { .. }, elements } =>
+				Some(elements.iter().filter_map(|element| match element {
+					Variant::DictEntry { key, value } => Some((&**key, &**value)),
+					_ => None,
+				})),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to get the value of the given key if this `Variant` is an array of dict entries with string keys, like `a{sv}`.
+	///
+	/// If the value is a `Variant::Variant`, the inner `Variant` is returned.
+	pub fn get<'b>(&'b self, key: &str) -> Option<&'b Variant<'a>> {
+		let (_, value) = self.as_dict()?.find(|(k, _)| k.as_string() == Some(key))?;
+		Some(value.as_variant().unwrap_or(value))
+	}
+
+	/// Convenience function to convert this `Variant` into a list of `(key, value)` pairs if it's an array of dict entries, like `a{sv}`.
+	pub fn into_map(self) -> Option<Vec<(Variant<'a>, Variant<'a>)>> {
+		match self {
+			Variant::Array { element_signature: crate::Signature::DictEntry { .. }, elements } =>
+				Some(
+					elements.into_owned().into_iter()
+					.filter_map(|element| match element {
+						Variant::DictEntry { key, value } => Some((key.into_owned(), value.into_owned())),
+						_ => None,
+					})
+					.collect(),
+				),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as an `&ObjectPath` if it's an object path.
+	pub fn as_object_path<'b>(&'b self) -> Option<&'b crate::ObjectPath<'a>> {
+		match self {
+			Variant::ObjectPath(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&Signature` if it's a signature.
+	pub fn as_signature(&self) -> Option<&crate::Signature> {
+		match self {
+			Variant::Signature(value) => Some(value),
+			_ => None,
+		}
+	}
+
 	/// Convenience function to view this `Variant` as a `&str` if it's a string.
 	pub fn as_string(&self) -> Option<&str> {
 		match self {
@@ -121,6 +430,38 @@ impl<'a> Variant<'a> {
 		}
 	}
 
+	/// Convenience function to view this `Variant` as a `&[Variant]` of its fields if it's a struct.
+	pub fn as_struct<'b>(&'b self) -> Option<&'b [Variant<'a>]> {
+		match self {
+			Variant::Struct { fields } => Some(fields),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `&[Variant]` of its elements if it's a tuple.
+	pub fn as_tuple<'b>(&'b self) -> Option<&'b [Variant<'a>]> {
+		match self {
+			Variant::Tuple { elements } => Some(elements),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `u8` if it is one.
+	pub fn as_u8(&self) -> Option<u8> {
+		match self {
+			Variant::U8(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `u16` if it is one.
+	pub fn as_u16(&self) -> Option<u16> {
+		match self {
+			Variant::U16(value) => Some(*value),
+			_ => None,
+		}
+	}
+
 	/// Convenience function to view this `Variant` as a `u32` if it is one.
 	pub fn as_u32(&self) -> Option<u32> {
 		match self {
@@ -129,6 +470,46 @@ impl<'a> Variant<'a> {
 		}
 	}
 
+	/// Convenience function to view this `Variant` as a `u64` if it is one.
+	pub fn as_u64(&self) -> Option<u64> {
+		match self {
+			Variant::U64(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to view this `Variant` as a `crate::UnixFd` if it is one.
+	pub fn as_unix_fd(&self) -> Option<crate::UnixFd> {
+		match self {
+			Variant::UnixFd(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	/// Convenience function to take the file descriptor that this `Variant` refers to out of the file descriptors received with its message,
+	/// if it is a `crate::UnixFd` or a variant holding one.
+	///
+	/// Returns `None` if this `Variant` is not a `crate::UnixFd`, if its index is out of range of `fds`, or if the file descriptor was already taken.
+	///
+	/// ```rust
+	/// # use dbus_pure_proto::{UnixFd, Variant};
+	/// let (reader, writer) = std::io::pipe().unwrap();
+	/// let mut fds = vec![Some(reader.into()), Some(writer.into())];
+	///
+	/// assert!(Variant::UnixFd(UnixFd(1)).take_fd(&mut fds).is_some());
+	/// assert!(Variant::UnixFd(UnixFd(1)).take_fd(&mut fds).is_none());
+	/// assert!(Variant::UnixFd(UnixFd(2)).take_fd(&mut fds).is_none());
+	/// assert!(Variant::U32(0).take_fd(&mut fds).is_none());
+	/// ```
+	#[cfg(unix)]
+	pub fn take_fd(&self, fds: &mut [Option<std::os::fd::OwnedFd>]) -> Option<std::os::fd::OwnedFd> {
+		match self {
+			Variant::UnixFd(crate::UnixFd(index)) => fds.get_mut(usize::try_from(*index).ok()?)?.take(),
+			Variant::Variant(value) => value.take_fd(fds),
+			_ => None,
+		}
+	}
+
 	/// Convenience function to view this `Variant` as its inner `Variant` if it has one.
 	pub fn as_variant<'b>(&'b self) -> Option<&'b Variant<'a>> {
 		match self {
@@ -137,7 +518,19 @@ impl<'a> Variant<'a> {
 		}
 	}
 
-	pub(crate) fn inner_signature(&self) -> crate::Signature {
+	/// The signature of this `Variant`'s value.
+	///
+	/// For a `Variant::Tuple`, this is a [`crate::Signature::Tuple`] whose string form is the concatenation of the signatures of its elements.
+	/// For a `Variant::Variant`, this is `v` rather than the signature of the inner value.
+	///
+	/// ```rust
+	/// # use dbus_pure_proto::Variant;
+	/// assert_eq!(Variant::ArrayU8((&[1, 2][..]).into()).signature().to_string(), "ay");
+	///
+	/// let body = Variant::Tuple { elements: vec![Variant::String("a".into()), Variant::U32(1)].into() };
+	/// assert_eq!(body.signature().to_string(), "su");
+	/// ```
+	pub fn signature(&self) -> crate::Signature {
 		match self {
 			Variant::Array { element_signature, elements: _ } =>
 				crate::Signature::Array { element: Box::new(element_signature.clone()) },
@@ -157,6 +550,12 @@ impl<'a> Variant<'a> {
 			Variant::ArrayI64(_) =>
 				crate::Signature::Array { element: Box::new(crate::Signature::I64) },
 
+			Variant::ArrayObjectPath(_) =>
+				crate::Signature::Array { element: Box::new(crate::Signature::ObjectPath) },
+
+			Variant::ArraySignature(_) =>
+				crate::Signature::Array { element: Box::new(crate::Signature::Signature) },
+
 			Variant::ArrayString(_) =>
 				crate::Signature::Array { element: Box::new(crate::Signature::String) },
 
@@ -180,8 +579,8 @@ impl<'a> Variant<'a> {
 
 			Variant::DictEntry { key, value } =>
 				crate::Signature::DictEntry {
-					key: Box::new(key.inner_signature()),
-					value: Box::new(value.inner_signature()),
+					key: Box::new(key.signature()),
+					value: Box::new(value.signature()),
 				},
 
 			Variant::F64(_) =>
@@ -206,10 +605,10 @@ impl<'a> Variant<'a> {
 				crate::Signature::String,
 
 			Variant::Struct { fields } =>
-				crate::Signature::Struct { fields: fields.iter().map(Variant::inner_signature).collect() },
+				crate::Signature::Struct { fields: fields.iter().map(Variant::signature).collect() },
 
 			Variant::Tuple { elements } =>
-				crate::Signature::Tuple { elements: elements.iter().map(Variant::inner_signature).collect() },
+				crate::Signature::Tuple { elements: elements.iter().map(Variant::signature).collect() },
 
 			Variant::U8(_) =>
 				crate::Signature::U8,
@@ -232,8 +631,99 @@ impl<'a> Variant<'a> {
 	}
 }
 
+/// An iterator over the elements of an array `Variant`. See [`Variant::iter_array`]
+#[derive(Clone, Debug)]
+pub struct VariantArrayIter<'b, 'a>(VariantArrayIterInner<'b, 'a>);
+
+#[derive(Clone, Debug)]
+enum VariantArrayIterInner<'b, 'a> {
+	Array(std::slice::Iter<'b, Variant<'a>>),
+	Bool(std::slice::Iter<'b, bool>),
+	F64(std::slice::Iter<'b, f64>),
+	I16(std::slice::Iter<'b, i16>),
+	I32(std::slice::Iter<'b, i32>),
+	I64(std::slice::Iter<'b, i64>),
+	ObjectPath(std::slice::Iter<'b, crate::ObjectPath<'a>>),
+	Signature(std::slice::Iter<'b, crate::Signature>),
+	String(std::slice::Iter<'b, std::borrow::Cow<'a, str>>),
+	U8(std::slice::Iter<'b, u8>),
+	U16(std::slice::Iter<'b, u16>),
+	U32(std::slice::Iter<'b, u32>),
+	U64(std::slice::Iter<'b, u64>),
+	UnixFd(std::slice::Iter<'b, crate::UnixFd>),
+}
+
+impl<'b, 'a> Iterator for VariantArrayIter<'b, 'a> {
+	type Item = std::borrow::Cow<'b, Variant<'a>>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let element = match &mut self.0 {
+			VariantArrayIterInner::Array(iter) => return iter.next().map(std::borrow::Cow::Borrowed),
+			VariantArrayIterInner::Bool(iter) => iter.next().copied().map(Variant::Bool),
+			VariantArrayIterInner::F64(iter) => iter.next().copied().map(Variant::F64),
+			VariantArrayIterInner::I16(iter) => iter.next().copied().map(Variant::I16),
+			VariantArrayIterInner::I32(iter) => iter.next().copied().map(Variant::I32),
+			VariantArrayIterInner::I64(iter) => iter.next().copied().map(Variant::I64),
+			VariantArrayIterInner::ObjectPath(iter) => iter.next().cloned().map(Variant::ObjectPath),
+			VariantArrayIterInner::Signature(iter) => iter.next().cloned().map(Variant::Signature),
+			VariantArrayIterInner::String(iter) => iter.next().cloned().map(Variant::String),
+			VariantArrayIterInner::U8(iter) => iter.next().copied().map(Variant::U8),
+			VariantArrayIterInner::U16(iter) => iter.next().copied().map(Variant::U16),
+			VariantArrayIterInner::U32(iter) => iter.next().copied().map(Variant::U32),
+			VariantArrayIterInner::U64(iter) => iter.next().copied().map(Variant::U64),
+			VariantArrayIterInner::UnixFd(iter) => iter.next().copied().map(Variant::UnixFd),
+		};
+		element.map(std::borrow::Cow::Owned)
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let len = self.len();
+		(len, Some(len))
+	}
+}
+
+impl ExactSizeIterator for VariantArrayIter<'_, '_> {
+	fn len(&self) -> usize {
+		match &self.0 {
+			VariantArrayIterInner::Array(iter) => iter.len(),
+			VariantArrayIterInner::Bool(iter) => iter.len(),
+			VariantArrayIterInner::F64(iter) => iter.len(),
+			VariantArrayIterInner::I16(iter) => iter.len(),
+			VariantArrayIterInner::I32(iter) => iter.len(),
+			VariantArrayIterInner::I64(iter) => iter.len(),
+			VariantArrayIterInner::ObjectPath(iter) => iter.len(),
+			VariantArrayIterInner::Signature(iter) => iter.len(),
+			VariantArrayIterInner::String(iter) => iter.len(),
+			VariantArrayIterInner::U8(iter) => iter.len(),
+			VariantArrayIterInner::U16(iter) => iter.len(),
+			VariantArrayIterInner::U32(iter) => iter.len(),
+			VariantArrayIterInner::U64(iter) => iter.len(),
+			VariantArrayIterInner::UnixFd(iter) => iter.len(),
+		}
+	}
+}
+
+fn check_signature(expected: &crate::Signature, value: &Variant<'_>) -> Result<(), crate::SerializeError> {
+	let actual = value.signature();
+	if actual == *expected {
+		Ok(())
+	}
+	else {
+		Err(crate::SerializeError::SignatureMismatch { expected: expected.clone(), actual })
+	}
+}
+
 impl<'de> Variant<'de> {
 	pub(crate) fn deserialize(deserializer: &mut crate::de::Deserializer<'de>, signature: &crate::Signature) -> Result<Self, crate::DeserializeError> {
+		Self::deserialize_inner(deserializer, signature, 0)
+	}
+
+	fn deserialize_inner(deserializer: &mut crate::de::Deserializer<'de>, signature: &crate::Signature, depth: usize) -> Result<Self, crate::DeserializeError> {
+		let depth = if signature.is_container() { depth + 1 } else { depth };
+		if depth > Variant::MAX_DEPTH {
+			return Err(crate::DeserializeError::ContainerDepthExceeded);
+		}
+
 		match signature {
 			crate::Signature::Array { element } => match &**element {
 				crate::Signature::Bool => {
@@ -261,6 +751,16 @@ impl<'de> Variant<'de> {
 					Ok(Variant::ArrayI64(elements.into()))
 				},
 
+				crate::Signature::ObjectPath => {
+					let elements = deserializer.deserialize_array(element.alignment(), crate::ObjectPath::deserialize)?;
+					Ok(Variant::ArrayObjectPath(elements.into()))
+				},
+
+				crate::Signature::Signature => {
+					let elements = deserializer.deserialize_array(element.alignment(), crate::Signature::deserialize)?;
+					Ok(Variant::ArraySignature(elements.into()))
+				},
+
 				crate::Signature::String => {
 					let elements = deserializer.deserialize_array(element.alignment(), |deserializer| Ok(deserializer.deserialize_string()?.into()))?;
 					Ok(Variant::ArrayString(elements.into()))
@@ -292,7 +792,7 @@ impl<'de> Variant<'de> {
 				},
 
 				element_signature => {
-					let elements = deserializer.deserialize_array(element.alignment(), |deserializer| Self::deserialize(deserializer, element))?;
+					let elements = deserializer.deserialize_array(element.alignment(), |deserializer| Self::deserialize_inner(deserializer, element, depth))?;
 					Ok(Variant::Array { element_signature: element_signature.clone(), elements: elements.into() })
 				},
 			},
@@ -304,8 +804,8 @@ impl<'de> Variant<'de> {
 
 			crate::Signature::DictEntry { key, value } => {
 				let (key, value) = deserializer.deserialize_struct(|deserializer| {
-					let key = Self::deserialize(deserializer, key)?;
-					let value = Self::deserialize(deserializer, value)?;
+					let key = Self::deserialize_inner(deserializer, key, depth)?;
+					let value = Self::deserialize_inner(deserializer, value, depth)?;
 					Ok((key, value))
 				})?;
 				Ok(Variant::DictEntry { key: Box::new(key).into(), value: Box::new(value).into() })
@@ -351,7 +851,7 @@ impl<'de> Variant<'de> {
 					let fields: Result<Vec<_>, _> =
 						fields.iter()
 						.map(|field| {
-							let field = Self::deserialize(deserializer, field)?;
+							let field = Self::deserialize_inner(deserializer, field, depth)?;
 							Ok(field)
 						})
 						.collect();
@@ -361,11 +861,11 @@ impl<'de> Variant<'de> {
 				Ok(Variant::Struct { fields: fields.into() })
 			},
 
-			crate::Signature::Tuple { elements } => {
+			crate::Signature::Tuple { .. } => {
 				let elements: Result<Vec<_>, _> =
-					elements.iter()
+					signature.iter()
 					.map(|element| {
-						let element = Self::deserialize(deserializer, element)?;
+						let element = Self::deserialize_inner(deserializer, element, depth)?;
 						Ok(element)
 					})
 					.collect();
@@ -400,7 +900,7 @@ impl<'de> Variant<'de> {
 
 			crate::Signature::Variant => {
 				let signature = crate::Signature::deserialize(deserializer)?;
-				let value = Self::deserialize(deserializer, &signature)?;
+				let value = Self::deserialize_inner(deserializer, &signature, depth)?;
 				Ok(Variant::Variant(Box::new(value).into()))
 			},
 		}
@@ -428,6 +928,18 @@ impl<'de> Variant<'de> {
 			Variant::ArrayI64(elements) =>
 				Variant::ArrayI64(elements.into_owned().into()),
 
+			Variant::ArrayObjectPath(elements) => Variant::ArrayObjectPath(
+				elements
+				.iter()
+				.cloned()
+				.map(crate::ObjectPath::into_owned)
+				.collect::<Vec<_>>()
+				.into(),
+			),
+
+			Variant::ArraySignature(elements) =>
+				Variant::ArraySignature(elements.into_owned().into()),
+
 			Variant::ArrayString(elements) => Variant::ArrayString(
 				elements
 				.iter()
@@ -523,63 +1035,211 @@ impl<'de> Variant<'de> {
 }
 
 impl Variant<'_> {
-	pub(crate) fn serialize(&self, serializer: &mut crate::ser::Serializer<'_>) -> Result<(), crate::SerializeError> {
+	/// The number of bytes that this variant will be marshalled to, if it is serialized starting at the given offset from the start of the message.
+	///
+	/// The offset is needed because it determines how much padding is inserted to align the values.
+	pub fn serialized_len(&self, starting_offset: usize) -> usize {
+		self.serialized_end(starting_offset) - starting_offset
+	}
+
+	fn serialized_end(&self, pos: usize) -> usize {
 		match self {
-			Variant::Array { element_signature, elements } =>
-				serializer.serialize_array(
-					element_signature.alignment(),
-					elements,
-					Self::serialize,
-				),
+			Variant::Array { element_signature, elements } => {
+				let mut pos = align_to(array_data_start(pos), element_signature.alignment());
+				for element in &**elements {
+					pos = element.serialized_end(pos);
+				}
+				pos
+			},
 
 			Variant::ArrayBool(elements) =>
-				serializer.serialize_array(
-					4,
-					elements,
-					|v, serializer| { serializer.serialize_bool(*v); Ok(()) },
-				),
+				align_to(array_data_start(pos), 4) + 4 * elements.len(),
 
 			Variant::ArrayF64(elements) =>
-				serializer.serialize_array(
-					8,
-					elements,
-					|v, serializer| { serializer.serialize_f64(*v); Ok(()) },
-				),
+				align_to(array_data_start(pos), 8) + 8 * elements.len(),
 
 			Variant::ArrayI16(elements) =>
-				serializer.serialize_array(
-					2,
-					elements,
-					|v, serializer| { serializer.serialize_i16(*v); Ok(()) },
-				),
+				align_to(array_data_start(pos), 2) + 2 * elements.len(),
 
 			Variant::ArrayI32(elements) =>
-				serializer.serialize_array(
-					4,
-					elements,
-					|v, serializer| { serializer.serialize_i32(*v); Ok(()) },
-				),
+				align_to(array_data_start(pos), 4) + 4 * elements.len(),
 
 			Variant::ArrayI64(elements) =>
-				serializer.serialize_array(
-					8,
-					elements,
-					|v, serializer| { serializer.serialize_i64(*v); Ok(()) },
-				),
+				align_to(array_data_start(pos), 8) + 8 * elements.len(),
+
+			Variant::ArrayObjectPath(elements) =>
+				elements.iter().fold(array_data_start(pos), |pos, element| string_end(pos, &element.0)),
+
+			Variant::ArraySignature(elements) =>
+				elements.iter().fold(array_data_start(pos), signature_end),
 
 			Variant::ArrayString(elements) =>
-				serializer.serialize_array(
-					4,
-					elements,
-					|v, serializer| serializer.serialize_string(v),
-				),
+				elements.iter().fold(array_data_start(pos), |pos, element| string_end(pos, element)),
 
 			Variant::ArrayU8(elements) =>
-				serializer.serialize_array_u8(elements),
+				array_data_start(pos) + elements.len(),
 
 			Variant::ArrayU16(elements) =>
-				serializer.serialize_array(
-					2,
+				align_to(array_data_start(pos), 2) + 2 * elements.len(),
+
+			Variant::ArrayU32(elements) =>
+				align_to(array_data_start(pos), 4) + 4 * elements.len(),
+
+			Variant::ArrayU64(elements) =>
+				align_to(array_data_start(pos), 8) + 8 * elements.len(),
+
+			Variant::ArrayUnixFd(elements) =>
+				align_to(array_data_start(pos), 4) + 4 * elements.len(),
+
+			Variant::Bool(_) | Variant::I32(_) | Variant::U32(_) | Variant::UnixFd(_) =>
+				align_to(pos, 4) + 4,
+
+			Variant::DictEntry { key, value } =>
+				value.serialized_end(key.serialized_end(align_to(pos, 8))),
+
+			Variant::F64(_) | Variant::I64(_) | Variant::U64(_) =>
+				align_to(pos, 8) + 8,
+
+			Variant::I16(_) | Variant::U16(_) =>
+				align_to(pos, 2) + 2,
+
+			Variant::ObjectPath(value) =>
+				string_end(pos, &value.0),
+
+			Variant::Signature(value) =>
+				signature_end(pos, value),
+
+			Variant::String(value) =>
+				string_end(pos, value),
+
+			Variant::Struct { fields } =>
+				fields.iter().fold(align_to(pos, 8), |pos, field| field.serialized_end(pos)),
+
+			Variant::Tuple { elements } =>
+				elements.iter().fold(pos, |pos, element| element.serialized_end(pos)),
+
+			Variant::U8(_) =>
+				pos + 1,
+
+			Variant::Variant(value) =>
+				value.serialized_end(signature_end(pos, &value.signature())),
+		}
+	}
+
+	pub(crate) fn serialize(&self, serializer: &mut crate::ser::Serializer<'_>) -> Result<(), crate::SerializeError> {
+		self.serialize_inner(serializer, 0)
+	}
+
+	fn is_container(&self) -> bool {
+		#[allow(clippy::match_same_arms)]
+		match self {
+			Variant::Array { .. } => true,
+			Variant::ArrayBool(_) => true,
+			Variant::ArrayF64(_) => true,
+			Variant::ArrayI16(_) => true,
+			Variant::ArrayI32(_) => true,
+			Variant::ArrayI64(_) => true,
+			Variant::ArrayObjectPath(_) => true,
+			Variant::ArraySignature(_) => true,
+			Variant::ArrayString(_) => true,
+			Variant::ArrayU8(_) => true,
+			Variant::ArrayU16(_) => true,
+			Variant::ArrayU32(_) => true,
+			Variant::ArrayU64(_) => true,
+			Variant::ArrayUnixFd(_) => true,
+			Variant::DictEntry { .. } => true,
+			Variant::Struct { .. } => true,
+			Variant::Tuple { .. } => false,
+			Variant::Variant(_) => true,
+			_ => false,
+		}
+	}
+
+	fn serialize_inner(&self, serializer: &mut crate::ser::Serializer<'_>, depth: usize) -> Result<(), crate::SerializeError> {
+		let depth = if self.is_container() { depth + 1 } else { depth };
+		if depth > Variant::MAX_DEPTH {
+			return Err(crate::SerializeError::ContainerDepthExceeded);
+		}
+
+		match self {
+			Variant::Array { element_signature, elements } => {
+				// The array's signature is written to the message as-is, so an element that does not match it would make the message malformed.
+				for (index, element) in elements.iter().enumerate() {
+					let actual = element.signature();
+					if actual != *element_signature {
+						return Err(crate::SerializeError::ArrayElementSignatureMismatch { expected: element_signature.clone(), actual, index });
+					}
+				}
+
+				serializer.serialize_array(
+					element_signature.alignment(),
+					elements,
+					|element, serializer| element.serialize_inner(serializer, depth),
+				)
+			},
+
+			Variant::ArrayBool(elements) =>
+				serializer.serialize_array(
+					4,
+					elements,
+					|v, serializer| { serializer.serialize_bool(*v); Ok(()) },
+				),
+
+			Variant::ArrayF64(elements) =>
+				serializer.serialize_array(
+					8,
+					elements,
+					|v, serializer| { serializer.serialize_f64(*v); Ok(()) },
+				),
+
+			Variant::ArrayI16(elements) =>
+				serializer.serialize_array(
+					2,
+					elements,
+					|v, serializer| { serializer.serialize_i16(*v); Ok(()) },
+				),
+
+			Variant::ArrayI32(elements) =>
+				serializer.serialize_array(
+					4,
+					elements,
+					|v, serializer| { serializer.serialize_i32(*v); Ok(()) },
+				),
+
+			Variant::ArrayI64(elements) =>
+				serializer.serialize_array(
+					8,
+					elements,
+					|v, serializer| { serializer.serialize_i64(*v); Ok(()) },
+				),
+
+			Variant::ArrayObjectPath(elements) =>
+				serializer.serialize_array(
+					4,
+					elements,
+					|v, serializer| v.serialize(serializer),
+				),
+
+			Variant::ArraySignature(elements) =>
+				serializer.serialize_array(
+					1,
+					elements,
+					|v, serializer| v.serialize(serializer),
+				),
+
+			Variant::ArrayString(elements) =>
+				serializer.serialize_array(
+					4,
+					elements,
+					|v, serializer| serializer.serialize_string(v),
+				),
+
+			Variant::ArrayU8(elements) =>
+				serializer.serialize_array_u8(elements),
+
+			Variant::ArrayU16(elements) =>
+				serializer.serialize_array(
+					2,
 					elements,
 					|v, serializer| { serializer.serialize_u16(*v); Ok(()) },
 				),
@@ -610,12 +1270,18 @@ impl Variant<'_> {
 				Ok(())
 			},
 
-			Variant::DictEntry { key, value } =>
+			Variant::DictEntry { key, value } => {
+				let key_signature = key.signature();
+				if !key_signature.is_basic() {
+					return Err(crate::SerializeError::NonBasicDictEntryKey { key: key_signature });
+				}
+
 				serializer.serialize_struct(|serializer| {
-					key.serialize(serializer)?;
-					value.serialize(serializer)?;
+					key.serialize_inner(serializer, depth)?;
+					value.serialize_inner(serializer, depth)?;
 					Ok(())
-				}),
+				})
+			},
 
 			Variant::F64(value) => {
 				serializer.serialize_f64(*value);
@@ -646,18 +1312,23 @@ impl Variant<'_> {
 			Variant::String(value) =>
 				serializer.serialize_string(value),
 
-			Variant::Struct { fields } =>
+			Variant::Struct { fields } => {
+				if fields.is_empty() {
+					return Err(crate::SerializeError::EmptyStruct);
+				}
+
 				serializer.serialize_struct(|serializer| {
 					for field in &**fields {
-						field.serialize(serializer)?;
+						field.serialize_inner(serializer, depth)?;
 					}
 
 					Ok(())
-				}),
+				})
+			},
 
 			Variant::Tuple { elements } => {
 				for element in &**elements {
-					element.serialize(serializer)?;
+					element.serialize_inner(serializer, depth)?;
 				}
 
 				Ok(())
@@ -689,17 +1360,717 @@ impl Variant<'_> {
 			},
 
 			Variant::Variant(value) => {
-				let signature = value.inner_signature();
+				let signature = value.signature();
 				signature.serialize(serializer)?;
-				value.serialize(serializer)?;
+				value.serialize_inner(serializer, depth)?;
 				Ok(())
 			},
 		}
 	}
 }
 
+/// Formats the variant the way `dbus-monitor` does, like `array [ dict entry( string "Foo" variant uint32 5 ) ]`
+///
+/// The alternate flag (`{:#}`) puts every element of a container on its own line, indented by three spaces per level of nesting.
+impl std::fmt::Display for Variant<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let indent = if f.alternate() { Some(0) } else { None };
+		self.fmt_monitor(f, indent)
+	}
+}
+
+impl Variant<'_> {
+	fn fmt_monitor(&self, f: &mut std::fmt::Formatter<'_>, indent: Option<usize>) -> std::fmt::Result {
+		match self {
+			Variant::Array { element_signature: _, elements } =>
+				fmt_container(f, indent, "array [", "]", elements.iter()),
+
+			Variant::ArrayBool(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().copied().map(Variant::Bool)),
+
+			Variant::ArrayF64(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().copied().map(Variant::F64)),
+
+			Variant::ArrayI16(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().copied().map(Variant::I16)),
+
+			Variant::ArrayI32(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().copied().map(Variant::I32)),
+
+			Variant::ArrayI64(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().copied().map(Variant::I64)),
+
+			Variant::ArrayObjectPath(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().map(|element| Variant::ObjectPath(crate::ObjectPath((&*element.0).into())))),
+
+			Variant::ArraySignature(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().cloned().map(Variant::Signature)),
+
+			Variant::ArrayString(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().map(|element| Variant::String((&**element).into()))),
+
+			Variant::ArrayU8(elements) => {
+				f.write_str("array of bytes [")?;
+				for element in &**elements {
+					write!(f, " {element:02x}")?;
+				}
+				f.write_str(" ]")
+			},
+
+			Variant::ArrayU16(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().copied().map(Variant::U16)),
+
+			Variant::ArrayU32(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().copied().map(Variant::U32)),
+
+			Variant::ArrayU64(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().copied().map(Variant::U64)),
+
+			Variant::ArrayUnixFd(elements) =>
+				fmt_container(f, indent, "array [", "]", elements.iter().copied().map(Variant::UnixFd)),
+
+			Variant::Bool(value) =>
+				write!(f, "boolean {value}"),
+
+			Variant::DictEntry { key, value } =>
+				fmt_container(f, indent, "dict entry(", ")", [&**key, &**value]),
+
+			Variant::F64(value) =>
+				write!(f, "double {value}"),
+
+			Variant::I16(value) =>
+				write!(f, "int16 {value}"),
+
+			Variant::I32(value) =>
+				write!(f, "int32 {value}"),
+
+			Variant::I64(value) =>
+				write!(f, "int64 {value}"),
+
+			Variant::ObjectPath(value) =>
+				write!(f, r#"object path "{value}""#),
+
+			Variant::Signature(value) =>
+				write!(f, r#"signature "{value}""#),
+
+			Variant::String(value) =>
+				write!(f, r#"string "{value}""#),
+
+			Variant::Struct { fields } =>
+				fmt_container(f, indent, "struct {", "}", fields.iter()),
+
+			// A tuple is the top-level body of a message with multiple arguments, so its elements are written one after the other
+			// the way dbus-monitor writes the arguments of a message.
+			Variant::Tuple { elements } => {
+				for (i, element) in elements.iter().enumerate() {
+					if i > 0 {
+						fmt_separator(f, indent)?;
+					}
+					element.fmt_monitor(f, indent)?;
+				}
+				Ok(())
+			},
+
+			Variant::U8(value) =>
+				write!(f, "byte {value}"),
+
+			Variant::U16(value) =>
+				write!(f, "uint16 {value}"),
+
+			Variant::U32(value) =>
+				write!(f, "uint32 {value}"),
+
+			Variant::U64(value) =>
+				write!(f, "uint64 {value}"),
+
+			Variant::UnixFd(crate::UnixFd(value)) =>
+				write!(f, "file descriptor {value}"),
+
+			Variant::Variant(value) => {
+				f.write_str("variant ")?;
+				value.fmt_monitor(f, indent)
+			},
+		}
+	}
+}
+
+fn fmt_container<'a, V>(
+	f: &mut std::fmt::Formatter<'_>,
+	indent: Option<usize>,
+	open: &str,
+	close: &str,
+	elements: impl IntoIterator<Item = V>,
+) -> std::fmt::Result where V: std::borrow::Borrow<Variant<'a>> {
+	f.write_str(open)?;
+
+	let inner_indent = indent.map(|indent| indent + 1);
+	for element in elements {
+		fmt_separator(f, inner_indent)?;
+		element.borrow().fmt_monitor(f, inner_indent)?;
+	}
+
+	fmt_separator(f, indent)?;
+	f.write_str(close)
+}
+
+fn fmt_separator(f: &mut std::fmt::Formatter<'_>, indent: Option<usize>) -> std::fmt::Result {
+	match indent {
+		Some(indent) => write!(f, "\n{:1$}", "", indent * 3),
+		None => f.write_str(" "),
+	}
+}
+
+fn align_to(pos: usize, alignment: usize) -> usize {
+	pos.div_ceil(alignment) * alignment
+}
+
+/// The position after the length prefix of an array that starts at `pos`
+fn array_data_start(pos: usize) -> usize {
+	align_to(pos, 4) + 4
+}
+
+fn string_end(pos: usize, s: &str) -> usize {
+	array_data_start(pos) + s.len() + 1
+}
+
+fn signature_end(pos: usize, signature: &crate::Signature) -> usize {
+	pos + 1 + signature.to_string().len() + 1
+}
+
+/// Serializes the value of the variant in its natural form, for logging or dumping it to formats like JSON. This is not the D-Bus wire format.
+///
+/// Arrays of dict entries are serialized as maps, other arrays as sequences, and structs, tuples and dict entries outside arrays as tuples.
+/// Object paths and signatures are serialized as strings, and nested variants as their inner value.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Variant<'_> {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: serde::Serializer {
+		use serde::ser::{SerializeMap, SerializeTuple};
+
+		#[allow(clippy::match_same_arms)]
+		match self {
+			Variant::Array { element_signature: crate::Signature::DictEntry { .. }, elements } => {
+				let mut map = serializer.serialize_map(Some(elements.len()))?;
+				for element in &**elements {
+					let Variant::DictEntry { key, value } = element else {
+						return Err(serde::ser::Error::custom(format_args!(r#"array element with signature "{}" is not a dict entry"#, element.signature())));
+					};
+					map.serialize_entry(&**key, &**value)?;
+				}
+				map.end()
+			},
+
+			Variant::Array { element_signature: _, elements } => serializer.collect_seq(&**elements),
+
+			Variant::ArrayBool(elements) => serializer.collect_seq(&**elements),
+
+			Variant::ArrayF64(elements) => serializer.collect_seq(&**elements),
+
+			Variant::ArrayI16(elements) => serializer.collect_seq(&**elements),
+
+			Variant::ArrayI32(elements) => serializer.collect_seq(&**elements),
+
+			Variant::ArrayI64(elements) => serializer.collect_seq(&**elements),
+
+			Variant::ArrayObjectPath(elements) => serializer.collect_seq(elements.iter().map(|element| &*element.0)),
+
+			Variant::ArraySignature(elements) => serializer.collect_seq(&**elements),
+
+			Variant::ArrayString(elements) => serializer.collect_seq(&**elements),
+
+			Variant::ArrayU8(elements) => serializer.serialize_bytes(elements),
+
+			Variant::ArrayU16(elements) => serializer.collect_seq(&**elements),
+
+			Variant::ArrayU32(elements) => serializer.collect_seq(&**elements),
+
+			Variant::ArrayU64(elements) => serializer.collect_seq(&**elements),
+
+			Variant::ArrayUnixFd(elements) => serializer.collect_seq(elements.iter().map(|crate::UnixFd(fd)| fd)),
+
+			Variant::Bool(value) => serializer.serialize_bool(*value),
+
+			Variant::DictEntry { key, value } => {
+				let mut tuple = serializer.serialize_tuple(2)?;
+				tuple.serialize_element(&**key)?;
+				tuple.serialize_element(&**value)?;
+				tuple.end()
+			},
+
+			Variant::F64(value) => serializer.serialize_f64(*value),
+
+			Variant::I16(value) => serializer.serialize_i16(*value),
+
+			Variant::I32(value) => serializer.serialize_i32(*value),
+
+			Variant::I64(value) => serializer.serialize_i64(*value),
+
+			Variant::ObjectPath(value) => serializer.serialize_str(&value.0),
+
+			Variant::Signature(value) => serde::Serialize::serialize(value, serializer),
+
+			Variant::String(value) => serializer.serialize_str(value),
+
+			Variant::Struct { fields: elements } | Variant::Tuple { elements } => {
+				let mut tuple = serializer.serialize_tuple(elements.len())?;
+				for element in &**elements {
+					tuple.serialize_element(element)?;
+				}
+				tuple.end()
+			},
+
+			Variant::U8(value) => serializer.serialize_u8(*value),
+
+			Variant::U16(value) => serializer.serialize_u16(*value),
+
+			Variant::U32(value) => serializer.serialize_u32(*value),
+
+			Variant::U64(value) => serializer.serialize_u64(*value),
+
+			Variant::UnixFd(crate::UnixFd(value)) => serializer.serialize_u32(*value),
+
+			Variant::Variant(value) => serde::Serialize::serialize(&**value, serializer),
+		}
+	}
+}
+
+macro_rules! variant_from_copy {
+	($($ty:ty => $variant:ident ,)*) => {
+		$(
+			impl From<$ty> for Variant<'_> {
+				fn from(value: $ty) -> Self {
+					Variant::$variant(value)
+				}
+			}
+		)*
+	};
+}
+
+variant_from_copy! {
+	bool => Bool,
+	f64 => F64,
+	i16 => I16,
+	i32 => I32,
+	i64 => I64,
+	u8 => U8,
+	u16 => U16,
+	u32 => U32,
+	u64 => U64,
+	crate::UnixFd => UnixFd,
+}
+
+impl<'a> From<&'a str> for Variant<'a> {
+	fn from(value: &'a str) -> Self {
+		Variant::String(value.into())
+	}
+}
+
+impl From<String> for Variant<'_> {
+	fn from(value: String) -> Self {
+		Variant::String(value.into())
+	}
+}
+
+impl<'a> From<crate::ObjectPath<'a>> for Variant<'a> {
+	fn from(value: crate::ObjectPath<'a>) -> Self {
+		Variant::ObjectPath(value)
+	}
+}
+
+impl From<crate::Signature> for Variant<'_> {
+	fn from(value: crate::Signature) -> Self {
+		Variant::Signature(value)
+	}
+}
+
+impl<'a> From<&'a [u8]> for Variant<'a> {
+	fn from(value: &'a [u8]) -> Self {
+		Variant::ArrayU8(value.into())
+	}
+}
+
+impl From<Vec<u8>> for Variant<'_> {
+	fn from(value: Vec<u8>) -> Self {
+		Variant::ArrayU8(value.into())
+	}
+}
+
 #[cfg(test)]
 mod tests {
+	#[test]
+	fn test_as() {
+		use super::Variant;
+
+		macro_rules! test {
+			($($as:ident ($value:expr) => $expected:expr ,)*) => {
+				$(
+					assert_eq!(Variant::$as(&$value), Some($expected), stringify!($as));
+					assert_eq!(Variant::$as(&Variant::Bool(true)).is_some(), stringify!($as) == "as_bool", stringify!($as));
+				)*
+			};
+		}
+
+		let path = crate::ObjectPath("/org".into());
+		let signature: crate::Signature = "a{sv}".parse().unwrap();
+		let fields = [Variant::U8(1), Variant::String("a".into())];
+
+		test! {
+			as_array_bool(Variant::ArrayBool((&[true][..]).into())) => &[true][..],
+			as_array_f64(Variant::ArrayF64((&[1.5][..]).into())) => &[1.5][..],
+			as_array_i16(Variant::ArrayI16((&[-1][..]).into())) => &[-1][..],
+			as_array_i32(Variant::ArrayI32((&[-1][..]).into())) => &[-1][..],
+			as_array_i64(Variant::ArrayI64((&[-1][..]).into())) => &[-1][..],
+			as_array_object_path(Variant::ArrayObjectPath((&[path.clone()][..]).into())) => &[path.clone()][..],
+			as_array_signature(Variant::ArraySignature((&[signature.clone()][..]).into())) => &[signature.clone()][..],
+			as_array_string(Variant::ArrayString((&["a".into()][..]).into())) => &[std::borrow::Cow::Borrowed("a")][..],
+			as_array_u8(Variant::ArrayU8((&[1][..]).into())) => &[1][..],
+			as_array_u16(Variant::ArrayU16((&[1][..]).into())) => &[1][..],
+			as_array_u32(Variant::ArrayU32((&[1][..]).into())) => &[1][..],
+			as_array_u64(Variant::ArrayU64((&[1][..]).into())) => &[1][..],
+			as_array_unix_fd(Variant::ArrayUnixFd((&[crate::UnixFd(1)][..]).into())) => &[crate::UnixFd(1)][..],
+			as_bool(Variant::Bool(false)) => false,
+			as_f64(Variant::F64(1.5)) => 1.5,
+			as_i16(Variant::I16(-1)) => -1,
+			as_i32(Variant::I32(-1)) => -1,
+			as_i64(Variant::I64(-1)) => -1,
+			as_object_path(Variant::ObjectPath(path.clone())) => &path,
+			as_signature(Variant::Signature(signature.clone())) => &signature,
+			as_string(Variant::String("a".into())) => "a",
+			as_struct(Variant::Struct { fields: (&fields[..]).into() }) => &fields[..],
+			as_tuple(Variant::Tuple { elements: (&fields[..]).into() }) => &fields[..],
+			as_u8(Variant::U8(1)) => 1,
+			as_u16(Variant::U16(1)) => 1,
+			as_u32(Variant::U32(1)) => 1,
+			as_u64(Variant::U64(1)) => 1,
+			as_unix_fd(Variant::UnixFd(crate::UnixFd(1))) => crate::UnixFd(1),
+			as_variant(Variant::Variant((&Variant::U8(1)).into())) => &Variant::U8(1),
+		}
+
+		// Mismatches between similar variants
+		assert_eq!(Variant::U16(1).as_u32(), None);
+		assert_eq!(Variant::Struct { fields: (&fields[..]).into() }.as_tuple(), None);
+		assert_eq!(Variant::ArrayU8((&[1][..]).into()).as_array_u16(), None);
+	}
+
+	#[test]
+	fn test_build_dict() {
+		use super::Variant;
+
+		// The `hints` parameter of `org.freedesktop.Notifications.Notify`
+		let hints = Variant::from_map_iter(crate::Signature::String, crate::Signature::Variant, [
+			(Variant::String("urgency".into()), Variant::Variant(Box::new(Variant::U8(2)).into())),
+			(Variant::String("category".into()), Variant::Variant(Box::new(Variant::String("im.received".into())).into())),
+		]).unwrap();
+		assert_eq!(hints.signature().to_string(), "a{sv}");
+		assert_eq!(hints.get("urgency"), Some(&Variant::U8(2)));
+
+		let err = Variant::from_map_iter(crate::Signature::String, crate::Signature::Variant, [
+			(Variant::String("urgency".into()), Variant::U8(2)),
+		]).unwrap_err();
+		assert!(matches!(
+			err,
+			crate::SerializeError::SignatureMismatch { expected: crate::Signature::Variant, actual: crate::Signature::U8 },
+		), "{err:?}");
+
+		let err = Variant::from_map_iter(crate::Signature::Variant, crate::Signature::U8, []).unwrap_err();
+		assert!(matches!(err, crate::SerializeError::NonBasicDictEntryKey { key: crate::Signature::Variant }), "{err:?}");
+
+		let array = Variant::array(crate::Signature::U16, [Variant::U16(1), Variant::U16(2)]).unwrap();
+		assert_eq!(array.signature().to_string(), "aq");
+
+		let err = Variant::array(crate::Signature::U16, [Variant::U16(1), Variant::U32(2)]).unwrap_err();
+		assert!(matches!(
+			err,
+			crate::SerializeError::SignatureMismatch { expected: crate::Signature::U16, actual: crate::Signature::U32 },
+		), "{err:?}");
+	}
+
+	#[test]
+	fn test_from() {
+		use super::Variant;
+
+		let body = Variant::tuple(["x".into(), 5_u32.into()]);
+		assert_eq!(body, Variant::Tuple { elements: vec![Variant::String("x".into()), Variant::U32(5)].into() });
+		assert_eq!(body.signature().to_string(), "su");
+
+		let path = crate::ObjectPath("/org".into());
+		let s = Variant::structure([
+			true.into(),
+			1.5.into(),
+			(-1_i16).into(),
+			(-1_i32).into(),
+			(-1_i64).into(),
+			1_u8.into(),
+			1_u16.into(),
+			1_u64.into(),
+			crate::UnixFd(3).into(),
+			String::from("a").into(),
+			path.clone().into(),
+			crate::Signature::Variant.into(),
+			(&[1_u8, 2][..]).into(),
+			vec![1_u8, 2].into(),
+		]);
+		assert_eq!(s, Variant::Struct {
+			fields: vec![
+				Variant::Bool(true),
+				Variant::F64(1.5),
+				Variant::I16(-1),
+				Variant::I32(-1),
+				Variant::I64(-1),
+				Variant::U8(1),
+				Variant::U16(1),
+				Variant::U64(1),
+				Variant::UnixFd(crate::UnixFd(3)),
+				Variant::String("a".into()),
+				Variant::ObjectPath(path),
+				Variant::Signature(crate::Signature::Variant),
+				Variant::ArrayU8((&[1, 2][..]).into()),
+				Variant::ArrayU8((&[1, 2][..]).into()),
+			].into(),
+		});
+		assert_eq!(s.signature().to_string(), "(bdnixyqthsogayay)");
+	}
+
+	#[test]
+	fn test_iter_array() {
+		use super::Variant;
+
+		let bytes = Variant::ArrayU8(vec![1, 2, 3].into());
+		assert_eq!(bytes.array_len(), Some(3));
+		let elements: Vec<_> = bytes.iter_array().unwrap().map(std::borrow::Cow::into_owned).collect();
+		assert_eq!(elements, [Variant::U8(1), Variant::U8(2), Variant::U8(3)]);
+
+		let strings = Variant::ArrayString(vec!["a".into(), "b".into()].into());
+		assert_eq!(strings.array_len(), Some(2));
+		let mut iter = strings.iter_array().unwrap();
+		assert_eq!(iter.len(), 2);
+		assert_eq!(iter.next().as_deref(), Some(&Variant::String("a".into())));
+		assert_eq!(iter.len(), 1);
+		assert_eq!(iter.next().as_deref(), Some(&Variant::String("b".into())));
+		assert!(iter.next().is_none());
+
+		let structs = Variant::array("(us)".parse().unwrap(), [
+			Variant::structure([Variant::U32(1), Variant::String("a".into())]),
+			Variant::structure([Variant::U32(2), Variant::String("b".into())]),
+		]).unwrap();
+		assert_eq!(structs.array_len(), Some(2));
+		let Some([first, second]) = structs.as_array(&"(us)".parse().unwrap()) else { panic!() };
+		for (element, expected) in structs.iter_array().unwrap().zip([first, second]) {
+			assert!(matches!(element, std::borrow::Cow::Borrowed(element) if std::ptr::eq(element, expected)));
+		}
+
+		assert_eq!(Variant::U8(1).array_len(), None);
+		assert!(Variant::U8(1).iter_array().is_none());
+		assert!(Variant::structure([Variant::U8(1)]).iter_array().is_none());
+	}
+
+	#[test]
+	fn test_dict() {
+		use super::Variant;
+
+		// The response of `org.freedesktop.DBus.Properties.GetAll`
+		let properties = Variant::Array {
+			element_signature: "{sv}".parse().unwrap(),
+			elements: vec![
+				Variant::DictEntry {
+					key: Box::new(Variant::String("Version".into())).into(),
+					value: Box::new(Variant::Variant(Box::new(Variant::U32(2)).into())).into(),
+				},
+				Variant::DictEntry {
+					key: Box::new(Variant::String("Features".into())).into(),
+					value: Box::new(Variant::Variant(Box::new(Variant::ArrayString(vec!["a".into()].into())).into())).into(),
+				},
+			].into(),
+		};
+
+		let keys: Vec<_> = properties.as_dict().unwrap().map(|(key, _)| key.as_string().unwrap()).collect();
+		assert_eq!(keys, ["Version", "Features"]);
+
+		assert_eq!(properties.get("Version"), Some(&Variant::U32(2)));
+		assert_eq!(properties.get("Features").and_then(Variant::as_array_string), Some(&["a".into()][..]));
+		assert_eq!(properties.get("Missing"), None);
+
+		let map = properties.into_map().unwrap();
+		assert_eq!(map.len(), 2);
+		assert_eq!(map[0], (Variant::String("Version".into()), Variant::Variant(Box::new(Variant::U32(2)).into())));
+
+		let not_a_dict = Variant::ArrayU32(vec![1].into());
+		assert!(not_a_dict.as_dict().is_none());
+		assert_eq!(not_a_dict.get("Version"), None);
+		assert_eq!(not_a_dict.into_map(), None);
+	}
+
+	#[test]
+	fn test_display() {
+		use super::Variant;
+
+		// The body of a `org.freedesktop.DBus.Properties.PropertiesChanged` signal
+		let body = Variant::Tuple {
+			elements: vec![
+				Variant::String("org.mpris.MediaPlayer2.Player".into()),
+				Variant::Array {
+					element_signature: "{sv}".parse().unwrap(),
+					elements: vec![
+						Variant::dict_entry(
+							Variant::String("Metadata".into()),
+							Variant::Variant(Box::new(Variant::Struct {
+								fields: vec![
+									Variant::ObjectPath(crate::ObjectPath("/track/1".into())),
+									Variant::ArrayU8(vec![0x00, 0x7f, 0xff].into()),
+									Variant::ArrayString(vec!["a".into(), "b".into()].into()),
+								].into(),
+							}).into()),
+						),
+						Variant::dict_entry(
+							Variant::String("Volume".into()),
+							Variant::Variant(Box::new(Variant::F64(0.5)).into()),
+						),
+					].into(),
+				},
+				Variant::ArrayString(vec![].into()),
+			].into(),
+		};
+
+		assert_eq!(
+			body.to_string(),
+			concat!(
+				r#"string "org.mpris.MediaPlayer2.Player" "#,
+				r#"array [ "#,
+				r#"dict entry( string "Metadata" variant struct { object path "/track/1" array of bytes [ 00 7f ff ] array [ string "a" string "b" ] } ) "#,
+				r#"dict entry( string "Volume" variant double 0.5 ) "#,
+				r#"] "#,
+				r#"array [ ]"#,
+			),
+		);
+
+		assert_eq!(
+			format!("{body:#}"),
+			r#"string "org.mpris.MediaPlayer2.Player"
+array [
+   dict entry(
+      string "Metadata"
+      variant struct {
+         object path "/track/1"
+         array of bytes [ 00 7f ff ]
+         array [
+            string "a"
+            string "b"
+         ]
+      }
+   )
+   dict entry(
+      string "Volume"
+      variant double 0.5
+   )
+]
+array [
+]"#,
+		);
+	}
+
+	#[test]
+	fn test_serialized_len() {
+		use super::Variant;
+
+		let path = crate::ObjectPath("/org/freedesktop/DBus".into());
+		let leaves = [
+			Variant::ArrayBool(vec![true, false].into()),
+			Variant::ArrayF64(vec![1.5].into()),
+			Variant::ArrayI16(vec![1, 2, 3].into()),
+			Variant::ArrayI32(vec![].into()),
+			Variant::ArrayI64(vec![1].into()),
+			Variant::ArrayObjectPath(vec![path.clone(), crate::ObjectPath("/".into())].into()),
+			Variant::ArraySignature(vec![crate::Signature::U8, "a{sv}".parse().unwrap()].into()),
+			Variant::ArrayString(vec!["a".into(), "bcdef".into()].into()),
+			Variant::ArrayU8(vec![1, 2, 3].into()),
+			Variant::ArrayU16(vec![1].into()),
+			Variant::ArrayU32(vec![1, 2].into()),
+			Variant::ArrayU64(vec![].into()),
+			Variant::ArrayUnixFd(vec![crate::UnixFd(0)].into()),
+			Variant::Bool(true),
+			Variant::F64(1.5),
+			Variant::I16(1),
+			Variant::I32(1),
+			Variant::I64(1),
+			Variant::ObjectPath(path),
+			Variant::Signature("(yv)".parse().unwrap()),
+			Variant::String("abc".into()),
+			Variant::U8(1),
+			Variant::U16(1),
+			Variant::U32(1),
+			Variant::U64(1),
+			Variant::UnixFd(crate::UnixFd(1)),
+		];
+
+		let mut corpus = leaves.to_vec();
+		for leaf in &leaves {
+			let wrapped = Variant::Variant(Box::new(leaf.clone()).into());
+			corpus.push(Variant::structure([Variant::U8(1), leaf.clone(), wrapped.clone()]));
+			corpus.push(Variant::tuple([leaf.clone(), Variant::U8(1), leaf.clone()]));
+			corpus.push(Variant::from_map_iter(crate::Signature::String, crate::Signature::Variant, [
+				(Variant::String("a".into()), wrapped.clone()),
+				(Variant::String("bc".into()), wrapped.clone()),
+			]).unwrap());
+			corpus.push(Variant::array(leaf.signature(), [leaf.clone(), leaf.clone()]).unwrap());
+			corpus.push(Variant::array(leaf.signature(), []).unwrap());
+			corpus.push(Variant::Variant(Box::new(Variant::structure([wrapped.clone(), Variant::U8(1)])).into()));
+		}
+
+		for value in &corpus {
+			for starting_offset in 0..8 {
+				let mut buf = vec![];
+				let mut serializer = crate::ser::Serializer::new(&mut buf, crate::Endianness::Little);
+				for _ in 0..starting_offset {
+					serializer.serialize_u8(0);
+				}
+				value.serialize(&mut serializer).unwrap();
+
+				assert_eq!(value.serialized_len(starting_offset), buf.len() - starting_offset, "{value} at offset {starting_offset}");
+			}
+		}
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn test_serialize_json() {
+		use super::Variant;
+
+		let wrap = |value: Variant<'static>| Variant::Variant(Box::new(value).into());
+
+		let body = Variant::tuple([
+			Variant::String("org.freedesktop.NetworkManager.Device".into()),
+			Variant::from_map_iter(crate::Signature::String, crate::Signature::Variant, [
+				(Variant::String("Ip4Address".into()), wrap(Variant::U32(0x0100_007f))),
+				(Variant::String("HwAddress".into()), wrap(Variant::ArrayU8(vec![0x00, 0x1b, 0xff].into()))),
+				(Variant::String("Path".into()), wrap(Variant::ObjectPath(crate::ObjectPath("/org/freedesktop/NetworkManager/Devices/1".into())))),
+				(Variant::String("Routes".into()), wrap(Variant::array("(su)".parse().unwrap(), [
+					Variant::structure([Variant::String("10.0.0.0".into()), Variant::U32(8)]),
+				]).unwrap())),
+				(Variant::String("Nested".into()), wrap(Variant::from_map_iter(crate::Signature::U32, crate::Signature::Signature, [
+					(Variant::U32(1), Variant::Signature("a{sv}".parse().unwrap())),
+				]).unwrap())),
+			]).unwrap(),
+			Variant::ArrayF64(vec![1.5, -2.0].into()),
+		]);
+
+		let json = serde_json::to_string(&body).unwrap();
+		assert_eq!(
+			json,
+			concat!(
+				r#"["org.freedesktop.NetworkManager.Device","#,
+				r#"{"Ip4Address":16777343,"HwAddress":[0,27,255],"Path":"/org/freedesktop/NetworkManager/Devices/1","Routes":[["10.0.0.0",8]],"Nested":{"1":"a{sv}"}},"#,
+				r#"[1.5,-2.0]]"#,
+			),
+		);
+
+		// The JSON form of the variant is the same as the JSON form of the equivalent Rust types.
+		let (interface, properties, values): (String, std::collections::BTreeMap<String, serde_json::Value>, Vec<f64>) = serde_json::from_str(&json).unwrap();
+		assert_eq!(interface, "org.freedesktop.NetworkManager.Device");
+		assert_eq!(properties["HwAddress"], serde_json::json!([0, 27, 255]));
+		assert_eq!(properties["Nested"], serde_json::json!({ "1": "a{sv}" }));
+		assert_eq!(values, [1.5, -2.0]);
+		assert_eq!(serde_json::to_value(&body).unwrap(), serde_json::from_str::<serde_json::Value>(&json).unwrap());
+	}
+
 	#[test]
 	fn test_variant_serde() {
 		fn test<'a>(
@@ -870,6 +2241,38 @@ mod tests {
 			},
 		);
 
+		test(
+			"ao",
+			b"\
+				\x13\x00\x00\x00\
+				\x02\x00\x00\x00/a\0\
+				\x00\
+				\x06\x00\x00\x00/org/b\0\
+			",
+			&super::Variant::ArrayObjectPath((&[
+				crate::ObjectPath("/a".into()),
+				crate::ObjectPath("/org/b".into()),
+			][..]).into()),
+		);
+
+		test(
+			"ag",
+			b"\
+				\x0A\x00\x00\x00\
+				\x01s\0\
+				\x05a{sv}\0\
+			",
+			&super::Variant::ArraySignature((&[
+				crate::Signature::String,
+				crate::Signature::Array {
+					element: Box::new(crate::Signature::DictEntry {
+						key: Box::new(crate::Signature::String),
+						value: Box::new(crate::Signature::Variant),
+					}),
+				},
+			][..]).into()),
+		);
+
 		test(
 			"a{qs}",
 			b"\
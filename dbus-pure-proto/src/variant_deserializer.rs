@@ -19,7 +19,7 @@ impl<'de> serde::Deserializer<'de> for crate::Variant<'de> {
 							else {
 								Err(VariantDeserializeError::InvalidValue {
 									expected: format!("array element with signature {element_signature}").into(),
-									actual: format!("array element with signature {}", element.inner_signature()),
+									actual: format!("array element with signature {}", element.signature()),
 								})
 							});
 					visitor.visit_map(MapAccess {
@@ -46,6 +46,12 @@ impl<'de> serde::Deserializer<'de> for crate::Variant<'de> {
 			crate::Variant::ArrayI64(elements) =>
 				visitor.visit_seq(SeqAccess(elements.iter().copied().map(crate::Variant::I64))),
 
+			crate::Variant::ArrayObjectPath(elements) =>
+				visitor.visit_seq(SeqAccess(elements.iter().cloned().map(crate::Variant::ObjectPath))),
+
+			crate::Variant::ArraySignature(elements) =>
+				visitor.visit_seq(SeqAccess(elements.iter().cloned().map(crate::Variant::Signature))),
+
 			crate::Variant::ArrayString(elements) =>
 				visitor.visit_seq(SeqAccess(elements.iter().cloned().map(crate::Variant::String))),
 
@@ -119,6 +125,29 @@ impl<'de> serde::Deserializer<'de> for crate::Variant<'de> {
 		}
 	}
 
+	fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error> where V: serde::de::Visitor<'de> {
+		match self {
+			crate::Variant::ArrayU8(std::borrow::Cow::Borrowed(value)) => visitor.visit_borrowed_bytes(value),
+			crate::Variant::ArrayU8(std::borrow::Cow::Owned(value)) => visitor.visit_byte_buf(value),
+			crate::Variant::Variant(value) => value.into_owned().deserialize_bytes(visitor),
+			variant => variant.deserialize_any(visitor),
+		}
+	}
+
+	fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error> where V: serde::de::Visitor<'de> {
+		self.deserialize_bytes(visitor)
+	}
+
+	/// D-Bus has no null value, so every value deserializes as `Some`.
+	fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error> where V: serde::de::Visitor<'de> {
+		visitor.visit_some(self)
+	}
+
+	/// The value is skipped without visiting its elements.
+	fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error> where V: serde::de::Visitor<'de> {
+		visitor.visit_unit()
+	}
+
 	serde::forward_to_deserialize_any! {
 		bool
 		i8 i16 i32 i64 i128
@@ -126,8 +155,6 @@ impl<'de> serde::Deserializer<'de> for crate::Variant<'de> {
 		f32 f64
 		char
 		str string
-		bytes byte_buf
-		option
 		unit unit_struct
 		newtype_struct
 		seq tuple tuple_struct
@@ -135,7 +162,6 @@ impl<'de> serde::Deserializer<'de> for crate::Variant<'de> {
 		struct
 		enum
 		identifier
-		ignored_any
 	}
 
 	fn is_human_readable(&self) -> bool {
@@ -173,7 +199,8 @@ impl<'de, I> serde::de::MapAccess<'de> for MapAccess<'de, I> where I: Iterator<I
 	}
 
 	fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error> where V: serde::de::DeserializeSeed<'de> {
-		seed.deserialize(self.next_value.take().unwrap())
+		let value = self.next_value.take().ok_or_else(|| serde::de::Error::custom("value requested before its key"))?;
+		seed.deserialize(value)
 	}
 }
 
@@ -315,4 +342,81 @@ mod tests {
 			&0x0102_0304_u32,
 		);
 	}
+
+	#[test]
+	fn test_deserialize_bytes() {
+		struct Bytes<'de>(std::borrow::Cow<'de, [u8]>);
+
+		impl<'de> serde::Deserialize<'de> for Bytes<'de> {
+			fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: serde::Deserializer<'de> {
+				struct Visitor;
+
+				impl<'de> serde::de::Visitor<'de> for Visitor {
+					type Value = Bytes<'de>;
+
+					fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+						f.write_str("bytes")
+					}
+
+					fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E> where E: serde::de::Error {
+						Ok(Bytes(v.into()))
+					}
+
+					fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> where E: serde::de::Error {
+						Ok(Bytes(v.into()))
+					}
+				}
+
+				deserializer.deserialize_bytes(Visitor)
+			}
+		}
+
+		let buf = [1_u8, 2, 3];
+
+		let Bytes(bytes) = serde::Deserialize::deserialize(crate::Variant::ArrayU8((&buf[..]).into())).unwrap();
+		assert!(matches!(bytes, std::borrow::Cow::Borrowed(&[1, 2, 3])), "{bytes:?}");
+
+		let Bytes(bytes) = serde::Deserialize::deserialize(crate::Variant::ArrayU8(buf.to_vec().into())).unwrap();
+		assert!(matches!(bytes, std::borrow::Cow::Owned(ref bytes) if bytes == &buf), "{bytes:?}");
+
+		let Bytes(bytes) = serde::Deserialize::deserialize(crate::Variant::Variant(Box::new(crate::Variant::ArrayU8(buf.to_vec().into())).into())).unwrap();
+		assert_eq!(&*bytes, &buf);
+
+		// Other values are not bytes.
+		let result: Result<Bytes<'_>, _> = serde::Deserialize::deserialize(crate::Variant::U32(1));
+		let err = result.err().unwrap();
+		assert_eq!(err.to_string(), "invalid type: integer `1`, expected bytes");
+
+		// `Vec<u8>` still deserializes from `ay` as a sequence.
+		let bytes: Vec<u8> = serde::Deserialize::deserialize(crate::Variant::ArrayU8((&buf[..]).into())).unwrap();
+		assert_eq!(bytes, buf);
+	}
+
+	#[test]
+	fn test_deserialize_option_and_ignored_any() {
+		#[derive(Debug, PartialEq, serde_derive::Deserialize)]
+		struct Properties {
+			#[serde(rename = "Name")]
+			name: Option<String>,
+			#[serde(rename = "Powered")]
+			powered: Option<bool>,
+		}
+
+		// `Option` fields that are present are `Some`, fields that are missing are `None`,
+		// and the values of unknown properties are skipped.
+		let properties = crate::Variant::from_map_iter(crate::Signature::String, crate::Signature::Variant, [
+			(crate::Variant::String("Name".into()), crate::Variant::Variant(Box::new(crate::Variant::String("hci0".into())).into())),
+			(
+				crate::Variant::String("UUIDs".into()),
+				crate::Variant::Variant(Box::new(crate::Variant::Array { element_signature: crate::Signature::String, elements: vec![].into() }).into()),
+			),
+		]).unwrap();
+		let properties: Properties = serde::Deserialize::deserialize(properties).unwrap();
+		assert_eq!(properties, Properties { name: Some("hci0".to_owned()), powered: None });
+
+		let value: Option<u32> = serde::Deserialize::deserialize(crate::Variant::U32(5)).unwrap();
+		assert_eq!(value, Some(5));
+
+		let serde::de::IgnoredAny = serde::Deserialize::deserialize(crate::Variant::ArrayU32(vec![1, 2].into())).unwrap();
+	}
 }
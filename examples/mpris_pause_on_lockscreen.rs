@@ -4,6 +4,7 @@
 	clippy::default_trait_access,
 	clippy::let_and_return,
 	clippy::let_unit_value,
+	clippy::unnecessary_debug_formatting,
 )]
 
 // Connects to the session bus and subscribes to screen lock / unlock events.
@@ -32,39 +33,30 @@ fn main() -> Result<(), Error> {
 
 	let mut client = dbus_pure::Client::new(connection)?;
 
-	// Add a match for all screen lock and unlock events. These events manifest as the `org.freedesktop.ScreenSaver.ActiveChanged` signal
+	// Receive all screen lock and unlock events. These events manifest as the `org.freedesktop.ScreenSaver.ActiveChanged` signal
 	// from the `/org/freedesktop/ScreenSaver` object.
 	//
-	// Adding a match is done by calling the `org.freedesktop.DBus.AddMatch` method on the `/org/freedesktop/DBus` object
-	// at the destination `org.freedesktop.DBus`. The method takes a single string parameter for the match rule.
-	{
-		let obj = OrgFreeDesktopDbusObject {
-			name: "org.freedesktop.DBus".into(),
-			path: dbus_pure::proto::ObjectPath("/org/freedesktop/DBus".into()),
-		};
-		let () =
-			obj.add_match(
-				&mut client,
-				"type='signal',path='/org/freedesktop/ScreenSaver',interface='org.freedesktop.ScreenSaver',member='ActiveChanged'",
-			)?;
-	}
+	// This adds a match rule for the signal by calling the `org.freedesktop.DBus.AddMatch` method on the `/org/freedesktop/DBus` object
+	// at the destination `org.freedesktop.DBus`, and picks out the signals that match it from the messages that the client receives.
+	let mut signals = client.signals(
+		dbus_pure::MatchRule::new()
+		.path(dbus_pure::proto::ObjectPath("/org/freedesktop/ScreenSaver".into()))
+		.interface("org.freedesktop.ScreenSaver")
+		.member("ActiveChanged"),
+	)?;
 
 	let mut players_to_resume: std::collections::BTreeSet<_> = Default::default();
 
-	loop {
+	while let Some(signal) = signals.next() {
 		let locked = {
-			let (header, body) = client.recv()?;
-			match header.r#type {
-				dbus_pure::proto::MessageType::Signal { interface, member, path: _ }
-					if interface == "org.freedesktop.ScreenSaver" && member == "ActiveChanged" => (),
-				_ => continue,
-			}
-
-			let body = body.ok_or("ActiveChanged signal does not have a body")?;
+			let body = signal?.body.ok_or("ActiveChanged signal does not have a body")?;
 			let body: bool = serde::Deserialize::deserialize(body)?;
 			body
 		};
 
+		// The stream holds on to the client, but lends it out to call methods.
+		let client = signals.client();
+
 		println!("Screen is {}", if locked { "locked" } else { "unlocked" });
 
 		if locked {
@@ -75,7 +67,7 @@ fn main() -> Result<(), Error> {
 					name: "org.freedesktop.DBus".into(),
 					path: dbus_pure::proto::ObjectPath("/org/freedesktop/DBus".into()),
 				};
-				let names = obj.list_names(&mut client)?;
+				let names = obj.list_names(client)?;
 				names
 			};
 
@@ -98,7 +90,7 @@ fn main() -> Result<(), Error> {
 				// Properties in general are accessed by calling the `org.freedesktop.DBus.Properties.Get` method
 				// with two parameters - the interface name and the property name.
 				let playback_status = {
-					let playback_status = obj.get(&mut client, "org.mpris.MediaPlayer2.Player", "PlaybackStatus")?;
+					let playback_status = obj.get(client, "org.mpris.MediaPlayer2.Player", "PlaybackStatus")?;
 					let playback_status: String = serde::Deserialize::deserialize(playback_status)?;
 					playback_status
 				};
@@ -107,7 +99,7 @@ fn main() -> Result<(), Error> {
 					println!("Pausing {media_player_name} ...");
 
 					// Pause the player by invoking its `org.mpris.MediaPlayer2.Player.Pause` method.
-					let () = obj.pause(&mut client)?;
+					let () = obj.pause(client)?;
 
 					println!("{media_player_name} is paused");
 
@@ -126,13 +118,15 @@ fn main() -> Result<(), Error> {
 
 				// Unpause the player by invoking its `org.mpris.MediaPlayer2.Player.Play` method.
 				// Swallow any errors in case the player refuses to play or no longer exists.
-				let result = obj.play(&mut client);
+				let result = obj.play(client);
 				if result.is_ok() {
 					println!("{media_player_name} is unpaused");
 				}
 			}
 		}
 	}
+
+	Ok(())
 }
 
 struct Error(Box<dyn std::error::Error>);
@@ -159,9 +153,6 @@ impl std::fmt::Debug for Error {
 
 #[dbus_pure_macros::interface("org.freedesktop.DBus")]
 trait OrgFreeDesktopDbusInterface {
-	#[name = "AddMatch"]
-	fn add_match(rule: &str);
-
 	#[name = "ListNames"]
 	fn list_names() -> Vec<String>;
 }
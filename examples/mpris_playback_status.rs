@@ -2,6 +2,7 @@
 #![deny(clippy::all, clippy::pedantic)]
 #![allow(
 	clippy::let_and_return,
+	clippy::unnecessary_debug_formatting,
 )]
 
 // Connects to the session bus, enumerates all media players that implement MPRIS, and prints their playback status.
@@ -51,16 +52,15 @@ fn main() -> Result<(), Error> {
 		// on the `org.mpris.MediaPlayer2.Player` interface.
 		//
 		// Properties in general are accessed by calling the `org.freedesktop.DBus.Properties.Get` method
-		// with two parameters - the interface name and the property name.
-		let playback_status = {
-			let obj = OrgMprisMediaPlayer2Object {
-				name: (&**media_player_name).into(),
-				path: dbus_pure::proto::ObjectPath("/org/mpris/MediaPlayer2".into()),
-			};
-			let playback_status = obj.get(&mut client, "org.mpris.MediaPlayer2.Player", "PlaybackStatus")?;
-			let playback_status: String = serde::Deserialize::deserialize(playback_status)?;
-			playback_status
-		};
+		// with two parameters - the interface name and the property name. `Client::get_property` does this
+		// and deserializes the value.
+		let playback_status: String =
+			client.get_property(
+				media_player_name,
+				dbus_pure::proto::ObjectPath("/org/mpris/MediaPlayer2".into()),
+				"org.mpris.MediaPlayer2.Player",
+				"PlaybackStatus",
+			)?;
 
 		println!("{media_player_name} is {playback_status}");
 	}
@@ -98,12 +98,3 @@ trait OrgFreeDesktopDbusInterface {
 
 #[dbus_pure_macros::object(OrgFreeDesktopDbusInterface)]
 struct OrgFreeDesktopDbusObject;
-
-#[dbus_pure_macros::interface("org.freedesktop.DBus.Properties")]
-trait OrgFreeDesktopDbusPropertiesInterface {
-	#[name = "Get"]
-	fn get(interface_name: &str, property_name: &str) -> dbus_pure::proto::Variant<'static>;
-}
-
-#[dbus_pure_macros::object(OrgFreeDesktopDbusPropertiesInterface)]
-struct OrgMprisMediaPlayer2Object;
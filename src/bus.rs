@@ -0,0 +1,270 @@
+/// The methods of the `org.freedesktop.DBus` interface of the message bus, called through a [`crate::Client`]. See [`crate::Client::bus`]
+///
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// #
+/// let connection = dbus_pure::Connection::new(dbus_pure::BusPath::Session, dbus_pure::SaslAuthType::Uid)?;
+/// let mut client = dbus_pure::Client::new(connection)?;
+///
+/// let names = client.bus().list_names()?;
+/// assert!(names.iter().any(|name| name == "org.freedesktop.DBus"));
+/// assert!(client.bus().name_has_owner("org.freedesktop.DBus")?);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct BusProxy<'a> {
+	client: &'a mut crate::Client,
+}
+
+impl<'a> BusProxy<'a> {
+	pub(crate) fn new(client: &'a mut crate::Client) -> Self {
+		BusProxy { client }
+	}
+
+	/// Ask the bus to assign the given well-known name to this client, with `org.freedesktop.DBus.RequestName`
+	pub fn request_name(&mut self, name: &str, flags: RequestNameFlags) -> Result<RequestNameReply, crate::MethodCallError> {
+		let reply: u32 = self.call("RequestName", &(name, flags.0))?;
+		match reply {
+			1 => Ok(RequestNameReply::PrimaryOwner),
+			2 => Ok(RequestNameReply::InQueue),
+			3 => Ok(RequestNameReply::Exists),
+			4 => Ok(RequestNameReply::AlreadyOwner),
+			reply => Err(unexpected_reply(reply, "a RequestName reply code")),
+		}
+	}
+
+	/// Ask the bus to unassign the given well-known name from this client, with `org.freedesktop.DBus.ReleaseName`
+	pub fn release_name(&mut self, name: &str) -> Result<ReleaseNameReply, crate::MethodCallError> {
+		let reply: u32 = self.call("ReleaseName", name)?;
+		match reply {
+			1 => Ok(ReleaseNameReply::Released),
+			2 => Ok(ReleaseNameReply::NonExistent),
+			3 => Ok(ReleaseNameReply::NotOwner),
+			reply => Err(unexpected_reply(reply, "a ReleaseName reply code")),
+		}
+	}
+
+	/// Whether the given name has an owner, with `org.freedesktop.DBus.NameHasOwner`
+	pub fn name_has_owner(&mut self, name: &str) -> Result<bool, crate::MethodCallError> {
+		self.call("NameHasOwner", name)
+	}
+
+	/// The unique name of the owner of the given name, with `org.freedesktop.DBus.GetNameOwner`
+	///
+	/// Fails with [`crate::MethodCallError::Error`] with `org.freedesktop.DBus.Error.NameHasNoOwner` if the name has no owner.
+	pub fn get_name_owner(&mut self, name: &str) -> Result<String, crate::MethodCallError> {
+		self.call("GetNameOwner", name)
+	}
+
+	/// The names that currently have an owner, with `org.freedesktop.DBus.ListNames`
+	pub fn list_names(&mut self) -> Result<Vec<String>, crate::MethodCallError> {
+		self.call("ListNames", &())
+	}
+
+	/// The names that the bus can start a service for, with `org.freedesktop.DBus.ListActivatableNames`
+	pub fn list_activatable_names(&mut self) -> Result<Vec<String>, crate::MethodCallError> {
+		self.call("ListActivatableNames", &())
+	}
+
+	/// Ask the bus to start the service for the given name, with `org.freedesktop.DBus.StartServiceByName`
+	pub fn start_service_by_name(&mut self, name: &str) -> Result<StartServiceReply, crate::MethodCallError> {
+		// The flags parameter is currently unused by the specification.
+		let reply: u32 = self.call("StartServiceByName", &(name, 0_u32))?;
+		match reply {
+			1 => Ok(StartServiceReply::Success),
+			2 => Ok(StartServiceReply::AlreadyRunning),
+			reply => Err(unexpected_reply(reply, "a StartServiceByName reply code")),
+		}
+	}
+
+	/// The uid of the process that owns the given name, with `org.freedesktop.DBus.GetConnectionUnixUser`
+	pub fn get_connection_unix_user(&mut self, name: &str) -> Result<u32, crate::MethodCallError> {
+		self.call("GetConnectionUnixUser", name)
+	}
+
+	/// The pid of the process that owns the given name, with `org.freedesktop.DBus.GetConnectionUnixProcessID`
+	pub fn get_connection_unix_process_id(&mut self, name: &str) -> Result<u32, crate::MethodCallError> {
+		self.call("GetConnectionUnixProcessID", name)
+	}
+
+	fn call<P, R>(&mut self, member: &str, parameters: &P) -> Result<R, crate::MethodCallError>
+	where
+		P: crate::proto::ToVariant + ?Sized,
+		R: serde::de::DeserializeOwned + crate::proto::ExpectedSignature,
+	{
+		self.client.call_checked(
+			"org.freedesktop.DBus",
+			crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+			"org.freedesktop.DBus",
+			member,
+			parameters,
+		)
+	}
+}
+
+/// The flags of [`BusProxy::request_name`]
+///
+/// Flags can be combined with `|`
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RequestNameFlags(u32);
+
+impl RequestNameFlags {
+	/// No flags.
+	pub const NONE: Self = RequestNameFlags(0x0);
+
+	/// Let another client that requests the name with [`RequestNameFlags::REPLACE_EXISTING`] take it over from this client.
+	pub const ALLOW_REPLACEMENT: Self = RequestNameFlags(0x1);
+
+	/// Take the name over from its current owner, if the owner allows it.
+	pub const REPLACE_EXISTING: Self = RequestNameFlags(0x2);
+
+	/// Fail instead of queueing for the name if it already has an owner.
+	pub const DO_NOT_QUEUE: Self = RequestNameFlags(0x4);
+
+	/// The raw representation of these flags.
+	pub fn bits(self) -> u32 {
+		self.0
+	}
+}
+
+impl std::ops::BitOr for RequestNameFlags {
+	type Output = Self;
+
+	fn bitor(self, rhs: Self) -> Self {
+		RequestNameFlags(self.0 | rhs.0)
+	}
+}
+
+/// The result of [`BusProxy::request_name`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RequestNameReply {
+	/// This client is now the owner of the name.
+	PrimaryOwner,
+
+	/// The name already has an owner, and this client is queued to own it after the owner releases it.
+	InQueue,
+
+	/// The name already has an owner, and [`RequestNameFlags::DO_NOT_QUEUE`] was specified.
+	Exists,
+
+	/// This client already owns the name.
+	AlreadyOwner,
+}
+
+/// The result of [`BusProxy::release_name`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ReleaseNameReply {
+	/// This client has released the name.
+	Released,
+
+	/// The name has no owner.
+	NonExistent,
+
+	/// This client does not own the name and is not queued for it.
+	NotOwner,
+}
+
+/// The result of [`BusProxy::start_service_by_name`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StartServiceReply {
+	/// The service was started.
+	Success,
+
+	/// The name already has an owner.
+	AlreadyRunning,
+}
+
+fn unexpected_reply(reply: u32, expected: &str) -> crate::MethodCallError {
+	let err = serde::de::Error::invalid_value(serde::de::Unexpected::Unsigned(reply.into()), &expected);
+	crate::MethodCallError::UnexpectedResponse(Some(err))
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_request_name() {
+		let mut client = crate::fake_bus::client(|header, body| {
+			let reply = match (header.member().unwrap(), body) {
+				("RequestName", Some(crate::proto::Variant::Tuple { elements })) => match &elements[..] {
+					[crate::proto::Variant::String(name), crate::proto::Variant::U32(flags)] => match &**name {
+						"org.example.Primary" => { assert_eq!(*flags, 0x0); 1 },
+						"org.example.InQueue" => { assert_eq!(*flags, 0x1); 2 },
+						"org.example.Exists" => { assert_eq!(*flags, 0x6); 3 },
+						"org.example.AlreadyOwner" => 4,
+						_ => 5,
+					},
+					_ => panic!("unexpected parameters {elements:?}"),
+				},
+				("StartServiceByName", Some(crate::proto::Variant::Tuple { elements })) => match &elements[..] {
+					[crate::proto::Variant::String(name), crate::proto::Variant::U32(0)] => if name == "org.example.Running" { 2 } else { 1 },
+					_ => panic!("unexpected parameters {elements:?}"),
+				},
+				("ReleaseName", Some(crate::proto::Variant::String(name))) => match &**name {
+					"org.example.Released" => 1,
+					"org.example.NonExistent" => 2,
+					"org.example.NotOwner" => 3,
+					_ => 0,
+				},
+				(member, body) => panic!("unexpected method call {member} {body:?}"),
+			};
+			vec![crate::fake_bus::method_return(header, Some(crate::proto::Variant::U32(reply)))]
+		});
+		let mut bus = client.bus();
+
+		assert_eq!(bus.request_name("org.example.Primary", super::RequestNameFlags::NONE).unwrap(), super::RequestNameReply::PrimaryOwner);
+		assert_eq!(
+			bus.request_name("org.example.InQueue", super::RequestNameFlags::ALLOW_REPLACEMENT).unwrap(),
+			super::RequestNameReply::InQueue,
+		);
+		assert_eq!(
+			bus.request_name("org.example.Exists", super::RequestNameFlags::REPLACE_EXISTING | super::RequestNameFlags::DO_NOT_QUEUE).unwrap(),
+			super::RequestNameReply::Exists,
+		);
+		assert_eq!(
+			bus.request_name("org.example.AlreadyOwner", super::RequestNameFlags::NONE).unwrap(),
+			super::RequestNameReply::AlreadyOwner,
+		);
+		let err = bus.request_name("org.example.Unknown", super::RequestNameFlags::NONE).unwrap_err();
+		assert!(matches!(err, crate::MethodCallError::UnexpectedResponse(Some(_))), "{err:?}");
+
+		assert_eq!(bus.release_name("org.example.Released").unwrap(), super::ReleaseNameReply::Released);
+		assert_eq!(bus.release_name("org.example.NonExistent").unwrap(), super::ReleaseNameReply::NonExistent);
+		assert_eq!(bus.release_name("org.example.NotOwner").unwrap(), super::ReleaseNameReply::NotOwner);
+		let err = bus.release_name("org.example.Unknown").unwrap_err();
+		assert!(matches!(err, crate::MethodCallError::UnexpectedResponse(Some(_))), "{err:?}");
+
+		assert_eq!(bus.start_service_by_name("org.example.Started").unwrap(), super::StartServiceReply::Success);
+		assert_eq!(bus.start_service_by_name("org.example.Running").unwrap(), super::StartServiceReply::AlreadyRunning);
+	}
+
+	#[test]
+	fn test_queries() {
+		let mut client = crate::fake_bus::client(|header, body| {
+			let name = match body {
+				Some(crate::proto::Variant::String(name)) => Some(&**name),
+				_ => None,
+			};
+			let response = match (header.member().unwrap(), name) {
+				("NameHasOwner", Some(name)) => crate::proto::Variant::Bool(name == "org.example"),
+				("GetNameOwner", Some("org.example")) => crate::proto::Variant::String(":1.5".into()),
+				("ListNames" | "ListActivatableNames", None) =>
+					crate::proto::Variant::ArrayString(vec!["org.freedesktop.DBus".into(), header.member().unwrap().to_owned().into()].into()),
+				("GetConnectionUnixUser", Some("org.example")) => crate::proto::Variant::U32(1000),
+				("GetConnectionUnixProcessID", Some("org.example")) => crate::proto::Variant::U32(1234),
+				(member, _) => panic!("unexpected method call {member} {body:?}"),
+			};
+			vec![crate::fake_bus::method_return(header, Some(response))]
+		});
+		let mut bus = client.bus();
+
+		assert!(bus.name_has_owner("org.example").unwrap());
+		assert!(!bus.name_has_owner("org.example.Other").unwrap());
+		assert_eq!(bus.get_name_owner("org.example").unwrap(), ":1.5");
+		assert_eq!(bus.list_names().unwrap(), ["org.freedesktop.DBus", "ListNames"]);
+		assert_eq!(bus.list_activatable_names().unwrap(), ["org.freedesktop.DBus", "ListActivatableNames"]);
+		assert_eq!(bus.get_connection_unix_user("org.example").unwrap(), 1000);
+		assert_eq!(bus.get_connection_unix_process_id("org.example").unwrap(), 1234);
+	}
+}
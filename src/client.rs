@@ -1,45 +1,333 @@
 /// A D-Bus client.
 pub struct Client {
-	connection: crate::conn::Connection,
-	last_serial: u32,
-	name: Option<String>,
-	received_messages: std::collections::VecDeque<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>)>,
+	send_half: ClientSendHalf,
+	recv_half: ClientRecvHalf,
+	auto_reconnect: Option<ReconnectPolicy>,
+
+	/// The name returned by the `org.freedesktop.DBus.Hello` handshake.
+	unique_name: Option<String>,
+
+	/// Whether `org.freedesktop.DBus.Peer.Ping` calls are answered by the `recv_matching` functions. See [`Client::set_answer_peer_pings`]
+	answer_peer_pings: bool,
 }
 
+/// A received message, along with the file descriptors that were sent with it.
+type ReceivedMessage = (crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>, Vec<std::os::fd::OwnedFd>);
+
 impl Client {
 	/// Create a client that uses the given connection to a message bus.
 	///
 	/// This function will complete the `org.freedesktop.DBus.Hello` handshake and obtain its name before returning.
 	pub fn new(connection: crate::conn::Connection) -> Result<Self, CreateClientError> {
-		let mut client = Client {
-			connection,
+		let mut client = Client::from_connection(connection, false);
+		let () = client.hello().map_err(CreateClientError::Hello)?;
+		Ok(client)
+	}
+
+	/// Create a client that uses the given peer-to-peer connection, such as one accepted by a [`crate::Listener`]
+	///
+	/// There is no message bus to call `org.freedesktop.DBus.Hello` on, so the client has no name and sends messages without
+	/// a `MessageHeaderField::Sender` field unless one is set with [`Client::set_name`]. The destination of method calls is ignored
+	/// by the peer, but it must still be a valid bus name.
+	pub fn new_peer(connection: crate::conn::Connection) -> Self {
+		Client::from_connection(connection, true)
+	}
+
+	fn from_connection(connection: crate::conn::Connection, peer: bool) -> Self {
+		let (send_connection, recv_connection) = connection.split();
+
+		let state = std::sync::Arc::new(std::sync::Mutex::new(ClientState {
 			last_serial: 0,
 			name: None,
-			received_messages: Default::default(),
+			request_endianness: None,
+			pending_method_calls: Some(Default::default()),
+			match_rules: vec![],
+			removed_match_rules: vec![],
+			peer,
+		}));
+
+		Client {
+			send_half: ClientSendHalf {
+				connection: std::sync::Arc::new(std::sync::Mutex::new(send_connection)),
+				method_call_timeout: None,
+				response_sender: None,
+				state: state.clone(),
+			},
+			recv_half: ClientRecvHalf {
+				connection: recv_connection,
+				received_messages: Default::default(),
+				state,
+				monitor: false,
+			},
+			auto_reconnect: None,
+			unique_name: None,
+			answer_peer_pings: true,
+		}
+	}
+
+	/// Complete the `org.freedesktop.DBus.Hello` handshake and use the name it returns as the name of this client.
+	fn hello(&mut self) -> Result<(), MethodCallError> {
+		let obj = OrgFreeDesktopDbusObject {
+			name: "org.freedesktop.DBus".into(),
+			path: crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
 		};
 
-		client.name = Some({
-			let obj = OrgFreeDesktopDbusObject {
-				name: "org.freedesktop.DBus".into(),
-				path: crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
-			};
+		let name = obj.hello(self)?;
+		if !name.starts_with(':') || crate::proto::validate::validate_bus_name(&name).is_err() {
+			let err = serde::de::Error::invalid_value(serde::de::Unexpected::Str(&name), &"a unique bus name");
+			return Err(MethodCallError::UnexpectedResponse(Some(err)));
+		}
 
-			let name = obj.hello(&mut client).map_err(CreateClientError::Hello)?;
-			name
-		});
+		let _ = self.set_name(name.clone());
+		self.unique_name = Some(name);
+		Ok(())
+	}
 
-		Ok(client)
+	/// The unique name that the bus assigned to this client in the `org.freedesktop.DBus.Hello` handshake.
+	///
+	/// This is `None` for a client of a peer-to-peer connection. It's not affected by [`Client::set_name`]
+	pub fn unique_name(&self) -> Option<&str> {
+		self.unique_name.as_deref()
+	}
+
+	/// Replace the connection of this client with a new connection to the same bus, such as after the bus restarted.
+	///
+	/// The new connection is opened with [`crate::Connection::reconnect`], so the connection of this client must have been opened
+	/// with [`crate::Connection::new`]. The client completes the `org.freedesktop.DBus.Hello` handshake again and uses the new name
+	/// that the bus returns, then adds the match rules that were added with [`Client::add_match`] again.
+	///
+	/// Messages that were received from the old connection but not yet returned by one of the `recv` functions are kept.
+	/// Settings of the connection, like its timeouts and interner, are not carried over to the new connection.
+	pub fn reconnect(&mut self) -> Result<(), ReconnectError> {
+		let connection = lock_connection(&self.send_half.connection).reconnect().map_err(ReconnectError::Connect)?;
+		let (send_connection, recv_connection) = connection.split();
+		*lock_connection(&self.send_half.connection) = send_connection;
+		self.recv_half.connection = recv_connection;
+
+		let (peer, match_rules) = {
+			let mut state = lock_state(&self.send_half.state);
+			state.name = None;
+			self.unique_name = None;
+			if let Some(request_endianness) = &mut state.request_endianness {
+				request_endianness.clear();
+			}
+			state.pending_method_calls = Some(Default::default());
+			// The rules that are still to be removed are not added to the new connection in the first place.
+			state.removed_match_rules.clear();
+			(state.peer, state.match_rules.clone())
+		};
+
+		// The calls made while reconnecting must not reconnect again if they fail.
+		let auto_reconnect = self.auto_reconnect.take();
+		let result = self.restore_session(peer, &match_rules);
+		self.auto_reconnect = auto_reconnect;
+		result
+	}
+
+	/// Complete the `org.freedesktop.DBus.Hello` handshake and add the given match rules on a new connection.
+	fn restore_session(&mut self, peer: bool, match_rules: &[String]) -> Result<(), ReconnectError> {
+		if !peer {
+			self.hello().map_err(ReconnectError::Hello)?;
+		}
+
+		for rule in match_rules {
+			let _ = self.method_call(
+				"org.freedesktop.DBus",
+				crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+				"org.freedesktop.DBus",
+				"AddMatch",
+				Some(&crate::proto::Variant::String(rule.into())),
+			).map_err(ReconnectError::AddMatch)?;
+		}
+
+		Ok(())
+	}
+
+	/// Set whether the client reconnects by itself when its connection breaks, and how.
+	///
+	/// If enabled, a send, receive or method call that fails with an I/O error makes the client call [`Client::reconnect`] until it succeeds,
+	/// up to the number of attempts of the policy. The operation still returns its error, since its message might have been lost,
+	/// but the client can be used again if it reconnected.
+	///
+	/// Auto-reconnect does not apply to the halves of a split client.
+	///
+	/// Defaults to `None`.
+	pub fn set_auto_reconnect(&mut self, policy: Option<ReconnectPolicy>) {
+		self.auto_reconnect = policy;
+	}
+
+	/// Set whether `org.freedesktop.DBus.Peer.Ping` calls received by [`Client::recv_matching`] and the functions like it are answered.
+	///
+	/// Other implementations consider a connection that doesn't answer pings to be dead. If enabled, a ping that is received
+	/// while waiting for a matching message is answered and discarded, unless it matches the predicate itself.
+	/// Pings that are returned by the other `recv` functions are left for the caller to answer.
+	///
+	/// Pings are not answered by the halves of a split client.
+	///
+	/// Defaults to `true`.
+	pub fn set_answer_peer_pings(&mut self, answer_peer_pings: bool) {
+		self.answer_peer_pings = answer_peer_pings;
+	}
+
+	/// Answers the given `org.freedesktop.DBus.Peer.Ping` call.
+	fn answer_peer_ping(&mut self, ping: &crate::proto::MessageHeader<'_>) {
+		if ping.flags.contains(crate::proto::message_flags::NO_REPLY_EXPECTED) {
+			return;
+		}
+
+		let reply = crate::proto::MessageHeader {
+			r#type: crate::proto::MessageType::MethodReturn { reply_serial: ping.serial },
+			flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+			body_len: 0,
+			serial: 0,
+			fields: ping.sender().map(|sender| crate::proto::MessageHeaderField::Destination(sender.into())).into_iter().collect::<Vec<_>>().into(),
+		};
+
+		// Failing to answer a ping is not an error of the receive it happened in.
+		// If the connection broke, the next receive fails anyway.
+		let _ = self.send_half.send(&reply, None);
+	}
+
+	/// Reconnects if auto-reconnect is enabled and `is_disconnected` says that the error of the given result means that the connection broke.
+	fn auto_reconnect<T, E>(&mut self, result: Result<T, E>, is_disconnected: impl FnOnce(&E) -> bool) -> Result<T, E> {
+		if let (Err(err), Some(policy)) = (&result, self.auto_reconnect) {
+			if is_disconnected(err) {
+				for attempt in 0..policy.max_attempts {
+					if attempt > 0 {
+						std::thread::sleep(policy.delay);
+					}
+
+					if self.reconnect().is_ok() {
+						break;
+					}
+				}
+			}
+		}
+
+		result
+	}
+
+	/// Add a match rule with `org.freedesktop.DBus.AddMatch`, so that the bus sends this client the messages that match it.
+	///
+	/// The rule stays added as long as the returned [`MatchGuard`] is alive, and [`Client::reconnect`] adds it again meanwhile.
+	/// Dropping the guard removes the rule with `org.freedesktop.DBus.RemoveMatch` without waiting for the response. If that can't be sent
+	/// right away, it's sent along with the next message that the client sends instead. Use [`Client::remove_match`] instead to remove it immediately and find out whether that succeeded.
+	pub fn add_match(&mut self, rule: &crate::MatchRule<'_>) -> Result<MatchGuard, MethodCallError> {
+		let rule = rule.to_string();
+		let _ = self.method_call(
+			"org.freedesktop.DBus",
+			crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+			"org.freedesktop.DBus",
+			"AddMatch",
+			Some(&crate::proto::Variant::String((&*rule).into())),
+		)?;
+		Ok(MatchGuard::new(&self.send_half, rule))
+	}
+
+	/// Remove a match rule that was added with [`Client::add_match`], with `org.freedesktop.DBus.RemoveMatch`
+	///
+	/// The rule is not added again when reconnecting even if this fails.
+	pub fn remove_match(&mut self, guard: MatchGuard) -> Result<(), MethodCallError> {
+		let rule = guard.forget();
+		let _ = self.method_call(
+			"org.freedesktop.DBus",
+			crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+			"org.freedesktop.DBus",
+			"RemoveMatch",
+			Some(&crate::proto::Variant::String(rule.into())),
+		)?;
+		Ok(())
 	}
 
 	/// Override the name of this client. The given name will be used as the `MessageHeaderField::Sender` value
 	/// instead of the name returned by the `org.freedesktop.DBus.Hello` handshake.
-	pub fn set_name(&mut self, name: String) {
-		self.name = Some(name);
+	///
+	/// Returns the name that was used until now.
+	pub fn set_name(&mut self, name: String) -> Option<String> {
+		self.send_half.set_name(name)
+	}
+
+	/// Set whether replies to method calls are sent with the same endianness as the method call, as the D-Bus specification recommends.
+	///
+	/// If enabled, the endianness of every received method call that expects a reply is remembered until a `METHOD_RETURN` or `ERROR`
+	/// with its serial is sent to its sender using [`Client::send`]. Other messages are sent with the endianness of the connection.
+	///
+	/// Disabled by default.
+	pub fn set_reply_in_kind(&mut self, reply_in_kind: bool) {
+		self.send_half.set_reply_in_kind(reply_in_kind);
+	}
+
+	/// Set how long method calls wait for their response, or `None` to wait until the response is received.
+	///
+	/// When the timeout elapses, the method call fails with [`MethodCallError::RecvResponse`] with [`crate::conn::RecvError::TimedOut`].
+	/// The client remains usable. A response that is received later is returned from [`Client::recv`] like any other message.
+	///
+	/// See [`Client::recv_timeout`] for the requirements on the connection.
+	///
+	/// Defaults to `None`.
+	pub fn set_method_call_timeout(&mut self, timeout: Option<std::time::Duration>) {
+		self.send_half.set_method_call_timeout(timeout);
+	}
+
+	/// Opt in to sharing the names and paths of received message headers using the given interner.
+	///
+	/// See [`crate::proto::Interner`] for details.
+	pub fn set_interner(&mut self, interner: Option<&'static crate::proto::Interner>) {
+		self.recv_half.set_interner(interner);
+	}
+
+	/// Set whether the connection is in non-blocking mode.
+	///
+	/// In non-blocking mode, [`Client::try_recv`] and [`Client::try_recv_matching`] return immediately,
+	/// and [`Client::recv`] and method calls fail with [`crate::conn::RecvError::TimedOut`] if a response has not been received yet.
+	///
+	/// See [`crate::Connection::set_nonblocking`] for details.
+	pub fn set_nonblocking(&mut self, nonblocking: bool) -> std::io::Result<()> {
+		self.recv_half.connection.set_nonblocking(nonblocking)
+	}
+
+	/// Write the rest of a message that was only partially sent because the connection is in non-blocking mode.
+	///
+	/// See [`crate::Connection::flush`] for details.
+	pub fn flush(&mut self) -> std::io::Result<()> {
+		self.send_half.flush()
+	}
+
+	/// Write the rest of a message that was only partially sent, failing with [`crate::conn::SendError::WouldBlock`] if it still could not be written.
+	///
+	/// See [`crate::Connection::flush_pending`] for details.
+	pub fn flush_pending(&mut self) -> Result<(), crate::conn::SendError> {
+		self.send_half.flush_pending()
+	}
+
+	/// The credentials of the process on the other end of the connection.
+	///
+	/// See [`crate::Connection::peer_credentials`] for details.
+	pub fn peer_credentials(&self) -> std::io::Result<crate::conn::PeerCredentials> {
+		self.recv_half.connection.peer_credentials()
+	}
+
+	/// The statistics of the messages sent and received by the client.
+	pub fn stats(&self) -> ClientStats {
+		ClientStats {
+			connection: crate::conn::ConnectionStats::combine(lock_connection(&self.send_half.connection).stats(), self.recv_half.connection.stats()),
+			queued_messages: self.recv_half.received_messages.len(),
+		}
+	}
+
+	/// Reset the statistics of the messages sent and received by the client to zero.
+	///
+	/// Messages that have been received but not yet returned by one of the `recv` functions remain counted in [`ClientStats::queued_messages`].
+	pub fn reset_stats(&mut self) {
+		lock_connection(&self.send_half.connection).reset_stats();
+		self.recv_half.connection.reset_stats();
 	}
 
 	/// Send a message with the given header and body.
 	///
-	/// - The header serial will be overwritten to a unique serial number, and does not need to be set to any specific value by the caller.
+	/// - The header serial is ignored. The message is sent with a unique serial number instead, which is returned.
+	///
+	/// - The header is not modified, so the same header can be sent again.
 	///
 	/// - Header fields corresponding to the required properties of the message type will be automatically inserted, and *must not* be inserted by the caller.
 	///   For example, if `header.type` is `MethodCall { member, path }`, the `MessageHeaderField::Member` and `MessageHeaderField::Path` fields
@@ -50,19 +338,61 @@ impl Client {
 	/// - The `MessageHeaderField::Signature` field will be automatically inserted if a body is specified, and must not be inserted by the caller.
 	///
 	/// Returns the serial of the message.
-	pub fn send(&mut self, header: &mut crate::proto::MessageHeader<'_>, body: Option<&crate::proto::Variant<'_>>) -> Result<u32, crate::conn::SendError> {
-		// Serial is in the range 1..=u32::MAX , ie it rolls over to 1 rather than 0
-		self.last_serial = self.last_serial % u32::MAX + 1;
-		header.serial = self.last_serial;
+	///
+	/// Returns [`crate::conn::SendError::InvalidSender`] if the name of this client set via [`Client::set_name`] is not a valid bus name.
+	pub fn send(&mut self, header: &crate::proto::MessageHeader<'_>, body: Option<&crate::proto::Variant<'_>>) -> Result<u32, crate::conn::SendError> {
+		let result = self.send_half.send(header, body);
+		self.auto_reconnect(result, is_send_disconnected)
+	}
 
-		if let Some(name) = &self.name {
-			// name is cloned because the lifetime of self.name needs to be independent of the lifetime of header
-			header.fields.to_mut().push(crate::proto::MessageHeaderField::Sender(name.clone().into()));
-		}
+	/// Like [`Client::send`], but the body is serialized directly from `body` with the given signature,
+	/// without converting it to a [`crate::proto::Variant`] first.
+	///
+	/// Returns [`crate::conn::SendError::Serialize`] if `body` does not have the shape that `body_signature` requires.
+	/// See [`crate::proto::to_writer_with_signature`] for details.
+	pub fn send_serialized<T>(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: &T,
+		body_signature: &crate::proto::Signature,
+	) -> Result<u32, crate::conn::SendError> where T: serde::Serialize + ?Sized {
+		let result = self.send_half.send_serialized(header, body, body_signature);
+		self.auto_reconnect(result, is_send_disconnected)
+	}
 
-		let () = self.connection.send(header, body)?;
+	/// Like [`Client::send`], but also sends the given file descriptors along with the message.
+	///
+	/// See [`crate::Connection::send_with_fds`] for details.
+	pub fn send_with_fds(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: Option<&crate::proto::Variant<'_>>,
+		fds: &[std::os::fd::BorrowedFd<'_>],
+	) -> Result<u32, crate::conn::SendError> {
+		let result = self.send_half.send_with_fds(header, body, fds);
+		self.auto_reconnect(result, is_send_disconnected)
+	}
 
-		Ok(self.last_serial)
+	/// Send a message built by one of the builders in [`crate::proto`], like [`crate::proto::MethodCallBuilder`]
+	///
+	/// Returns the serial of the message. See [`Client::send`] for details.
+	///
+	/// ```rust,no_run
+	/// # fn foo(client: &mut dbus_pure::Client) -> Result<(), Box<dyn std::error::Error>> {
+	/// let message =
+	///     dbus_pure::proto::SignalBuilder::new(dbus_pure::proto::ObjectPath("/org/example/Player".into()), "org.example.Player", "Seeked")
+	///     .body(dbus_pure::proto::Variant::I64(5_000_000))
+	///     .build()?;
+	/// let _ = client.send_message(message)?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn send_message(
+		&mut self,
+		message: (crate::proto::MessageHeader<'_>, Option<crate::proto::Variant<'_>>),
+	) -> Result<u32, crate::conn::SendError> {
+		let result = self.send_half.send_message(message);
+		self.auto_reconnect(result, is_send_disconnected)
 	}
 
 	/// A convenience wrapper around sending a `METHOD_CALL` message and receiving the corresponding `METHOD_RETURN` or `ERROR` response.
@@ -72,6 +402,9 @@ impl Client {
 	/// - If the method has more than one parameter, set `parameters` to `Some(&Variant::Tuple { ... })`.
 	///   For example, if the method takes two parameters of type string and byte, `parameters` should be
 	///   `Some(&Variant::Tuple { elements: (&[Variant::String(...), Variant::U8(...)][..]).into() })`
+	///
+	/// The destination, interface and member are checked to be valid names before the request is sent.
+	/// If they are not, this returns [`MethodCallError::InvalidArgument`].
 	pub fn method_call(
 		&mut self,
 		destination: &str,
@@ -80,150 +413,1748 @@ impl Client {
 		member: &str,
 		parameters: Option<&crate::proto::Variant<'_>>,
 	) -> Result<Option<crate::proto::Variant<'static>>, MethodCallError> {
-		let request_header_fields = &[
-			crate::proto::MessageHeaderField::Destination(destination.into()),
-			crate::proto::MessageHeaderField::Interface(interface.into()),
-		][..];
-		let mut request_header = crate::proto::MessageHeader {
-			r#type: crate::proto::MessageType::MethodCall {
-				member: member.into(),
-				path,
-			},
-			flags: crate::proto::message_flags::NONE,
-			body_len: 0,
-			serial: 0,
-			fields: request_header_fields.into(),
-		};
-
-		self.send(&mut request_header, parameters).map_err(MethodCallError::SendRequest)?;
-
-		let response = self.recv_matching(|header, _| {
-			match header.r#type {
-				crate::proto::MessageType::Error { reply_serial, .. } if reply_serial == request_header.serial => true,
-				crate::proto::MessageType::MethodReturn { reply_serial, .. } if reply_serial == request_header.serial => true,
-				_ => false,
-			}
-		}).map_err(MethodCallError::RecvResponse)?;
-
-		match response.0.r#type {
-			crate::proto::MessageType::Error { name, reply_serial: _ } =>
-				Err(MethodCallError::Error(name.into_owned(), response.1)),
-
-			crate::proto::MessageType::MethodReturn { reply_serial: _ } =>
-				Ok(response.1),
-
-			_ => unreachable!(),
-		}
+		let (_, body, _) = self.method_call_inner(destination, path, interface, member, |client, header| client.send(header, parameters))?;
+		Ok(body)
 	}
 
-	/// Receive a message from the message bus.
+	/// Like [`Client::method_call`], but the method call is sent with the given flags.
 	///
-	/// Blocks until a message is received.
-	pub fn recv(&mut self) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
-		if let Some(message) = self.received_messages.pop_front() {
-			return Ok(message);
+	/// - `NO_REPLY_EXPECTED`: The method call is only sent, and this returns `Ok(None)` without waiting for a response.
+	///   The service does not send one, and a response that it sends anyway is returned from [`Client::recv`] like any other message.
+	///
+	/// - `NO_AUTO_START`: The bus does not start the service if it is not running. The method call fails with
+	///   `org.freedesktop.DBus.Error.ServiceUnknown` instead.
+	///
+	/// - `ALLOW_INTERACTIVE_AUTHORIZATION`: The service may ask the user to authorize the method call, such as with a polkit prompt,
+	///   which can take a long time. Consider a longer [`Client::set_method_call_timeout`] for such calls.
+	pub fn method_call_with_flags(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: Option<&crate::proto::Variant<'_>>,
+		flags: crate::proto::MessageFlags,
+	) -> Result<Option<crate::proto::Variant<'static>>, MethodCallError> {
+		if flags.contains(crate::proto::message_flags::NO_REPLY_EXPECTED) {
+			let mut request_header = method_call_header(destination, path, interface, member)?;
+			request_header.flags = flags;
+			let _ = self.send(&request_header, parameters).map_err(MethodCallError::SendRequest)?;
+			return Ok(None);
 		}
 
-		self.recv_new()
+		let (_, body, _) = self.method_call_inner(destination, path, interface, member, |client, header| {
+			let mut header = header.clone();
+			header.flags = flags;
+			client.send(&header, parameters)
+		})?;
+		Ok(body)
 	}
 
-	/// Receive a message from the message bus that satisfies the given predicate.
+	/// Like [`Client::method_call`], but also sends the given file descriptors along with the method call,
+	/// and returns the file descriptors that were sent along with the response.
 	///
-	/// Messages that do not match the predicate will not be discarded. Instead they will be returned
-	/// from subsequent calls to [`Client::recv`] or `recv_matching`.
-	#[allow(clippy::missing_panics_doc)] // `self.received_messages.remove(i).unwrap()` cannot fail
-	pub fn recv_matching(
+	/// `Variant::UnixFd` values in `parameters` are indices into `fds`, so the file descriptors must be in the same order
+	/// as the parameters that refer to them. Use [`crate::proto::Variant::take_fd`] to resolve `Variant::UnixFd` values in the response.
+	///
+	/// ```rust,no_run
+	/// # fn foo(client: &mut dbus_pure::Client) -> Result<(), Box<dyn std::error::Error>> {
+	/// let (body, fds) = client.method_call_with_fds(
+	///     "org.freedesktop.login1",
+	///     dbus_pure::proto::ObjectPath("/org/freedesktop/login1".into()),
+	///     "org.freedesktop.login1.Manager",
+	///     "Inhibit",
+	///     Some(&dbus_pure::proto::Variant::tuple([
+	///         dbus_pure::proto::Variant::String("sleep".into()),
+	///         dbus_pure::proto::Variant::String("example".into()),
+	///         dbus_pure::proto::Variant::String("Saving state".into()),
+	///         dbus_pure::proto::Variant::String("delay".into()),
+	///     ])),
+	///     vec![],
+	/// )?;
+	/// let mut fds: Vec<_> = fds.into_iter().map(Some).collect();
+	/// let inhibitor_lock = body.and_then(|body| body.take_fd(&mut fds)).ok_or("Inhibit did not return a file descriptor")?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn method_call_with_fds(
 		&mut self,
-		mut predicate: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> bool,
-	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
-		for (i, already_received_message) in self.received_messages.iter().enumerate() {
-			if predicate(&already_received_message.0, already_received_message.1.as_ref()) {
-				let result = self.received_messages.remove(i).unwrap();
-				return Ok(result);
-			}
-		}
-
-		loop {
-			let (header, body) = self.recv_new()?;
-			if predicate(&header, body.as_ref()) {
-				return Ok((header, body));
-			}
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: Option<&crate::proto::Variant<'_>>,
+		fds: Vec<std::os::fd::OwnedFd>,
+	) -> Result<(Option<crate::proto::Variant<'static>>, Vec<std::os::fd::OwnedFd>), MethodCallError> {
+		self.method_call_with_fds_and_expected_signature(destination, path, interface, member, parameters, fds, None)
+	}
 
-			self.received_messages.push_back((header, body));
+	/// Like [`Client::method_call_with_fds`], but also checks that the response body has the expected signature if one is given,
+	/// like [`Client::method_call_with_signature`] does.
+	#[allow(clippy::too_many_arguments)]
+	pub(crate) fn method_call_with_fds_and_expected_signature(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: Option<&crate::proto::Variant<'_>>,
+		fds: Vec<std::os::fd::OwnedFd>,
+		expected_signature: Option<&crate::proto::Signature>,
+	) -> Result<(Option<crate::proto::Variant<'static>>, Vec<std::os::fd::OwnedFd>), MethodCallError> {
+		let (header, body, response_fds) = self.method_call_inner(destination, path, interface, member, |client, header| {
+			let borrowed_fds: Vec<_> = fds.iter().map(std::os::fd::AsFd::as_fd).collect();
+			let result = client.send_with_fds(header, parameters, &borrowed_fds);
+			// The file descriptors have been sent, so this process's copies can be closed.
+			drop(fds);
+			result
+		})?;
+		if let Some(expected_signature) = expected_signature {
+			let () = check_signature(&header, expected_signature, member)?;
 		}
+		Ok((body, response_fds))
 	}
 
-	fn recv_new(&mut self) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
-		self.connection.recv()
+	/// Like [`Client::method_call`], but the parameters are serialized directly from `parameters` with the given signature,
+	/// without converting them to a [`crate::proto::Variant`] first.
+	///
+	/// For example, if the method takes two parameters of type string and byte, `parameters` can be `&("foo", 5_u8)`
+	/// with a `parameters_signature` of `"sy"`.
+	///
+	/// If `parameters` does not have the shape that `parameters_signature` requires, this returns [`MethodCallError::SendRequest`]
+	/// without sending the request. See [`crate::proto::to_writer_with_signature`] for details.
+	pub fn method_call_serialized<T>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: &T,
+		parameters_signature: &crate::proto::Signature,
+	) -> Result<Option<crate::proto::Variant<'static>>, MethodCallError> where T: serde::Serialize + ?Sized {
+		let (_, body, _) =
+			self.method_call_inner(
+				destination,
+				path,
+				interface,
+				member,
+				|client, header| client.send_serialized(header, parameters, parameters_signature),
+			)?;
+		Ok(body)
 	}
-}
 
-impl std::fmt::Debug for Client {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		f.debug_struct("Client")
-			.field("connection", &())
-			.field("last_serial", &self.last_serial)
-			.field("name", &self.name)
-			.finish_non_exhaustive()
+	/// Like [`Client::method_call`], but also checks that the response body has the given signature.
+	///
+	/// If the `SIGNATURE` header field of the response does not match `expected_signature`, this returns [`MethodCallError::SignatureMismatch`]
+	/// instead of the response body. This is useful to detect a service that returns a different type than expected before trying to deserialize it.
+	///
+	/// The check is lenient for differences that do not matter when deserializing the body with serde. For example, an expected string
+	/// matches an object path or signature, and an expected struct matches a sequence of the same types.
+	pub fn method_call_with_signature(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: Option<&crate::proto::Variant<'_>>,
+		expected_signature: &crate::proto::Signature,
+	) -> Result<Option<crate::proto::Variant<'static>>, MethodCallError> {
+		self.method_call_with_expected_signature(destination, path, interface, member, parameters, Some(expected_signature))
 	}
-}
-
-/// An error from creating a [`Client`].
-#[derive(Debug)]
-pub enum CreateClientError {
-	Hello(MethodCallError),
-}
 
-impl std::fmt::Display for CreateClientError {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		match self {
-			CreateClientError::Hello(_) => f.write_str("could not complete hello"),
+	/// Like [`Client::method_call_with_signature`], except the signature is only checked if one is given.
+	pub(crate) fn method_call_with_expected_signature(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: Option<&crate::proto::Variant<'_>>,
+		expected_signature: Option<&crate::proto::Signature>,
+	) -> Result<Option<crate::proto::Variant<'static>>, MethodCallError> {
+		let (header, body, _) = self.method_call_inner(destination, path, interface, member, |client, header| client.send(header, parameters))?;
+		if let Some(expected_signature) = expected_signature {
+			let () = check_signature(&header, expected_signature, member)?;
 		}
+		Ok(body)
 	}
-}
 
-impl std::error::Error for CreateClientError {
-	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-		match self {
-			CreateClientError::Hello(err) => Some(err),
-		}
+	/// A typed convenience wrapper around [`Client::method_call`] that converts the parameters to a body, and deserializes the response body.
+	///
+	/// - `parameters` is converted with [`crate::proto::ToVariant`]. Use a tuple for a method with multiple parameters, like `&("sleep", 5_u32)`,
+	///   and `&()` for a method without parameters. See also [`Client::call0`]
+	///
+	/// - The response body is deserialized into `R` with serde. `()` and `Option<T>` accept a response without a body.
+	///   If the body can't be deserialized into `R`, this returns [`MethodCallError::UnexpectedResponse`].
+	///
+	/// The signature of the response body is not checked. See [`Client::call_checked`] for a variant that checks it.
+	///
+	/// ```rust,no_run
+	/// # fn foo(client: &mut dbus_pure::Client) -> Result<(), Box<dyn std::error::Error>> {
+	/// let names: Vec<String> = client.call0(
+	///     "org.freedesktop.DBus",
+	///     dbus_pure::proto::ObjectPath("/org/freedesktop/DBus".into()),
+	///     "org.freedesktop.DBus",
+	///     "ListNames",
+	/// )?;
+	/// let owner: String = client.call(
+	///     "org.freedesktop.DBus",
+	///     dbus_pure::proto::ObjectPath("/org/freedesktop/DBus".into()),
+	///     "org.freedesktop.DBus",
+	///     "GetNameOwner",
+	///     &"org.freedesktop.Notifications",
+	/// )?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn call<P, R>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: &P,
+	) -> Result<R, MethodCallError> where P: crate::proto::ToVariant + ?Sized, R: serde::de::DeserializeOwned {
+		let parameters = parameters.to_variant();
+		let body = self.method_call(destination, path, interface, member, parameters_body(&parameters))?;
+		deserialize_response(body)
 	}
-}
-
-/// An error from calling a method using a [`Client`].
-#[derive(Debug)]
-pub enum MethodCallError {
-	Error(String, Option<crate::proto::Variant<'static>>),
-	RecvResponse(crate::conn::RecvError),
-	SendRequest(crate::conn::SendError),
-	UnexpectedResponse(Option<crate::proto::VariantDeserializeError>),
-}
 
-impl std::fmt::Display for MethodCallError {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		match self {
-			MethodCallError::Error(error_name, body) => write!(f, "method call failed with an error: {error_name} {body:?}"),
-			MethodCallError::RecvResponse(_) => f.write_str("could not receive response"),
-			MethodCallError::SendRequest(_) => f.write_str("could not send request"),
-			MethodCallError::UnexpectedResponse(Some(_)) => f.write_str("could not deserialize response body"),
-			MethodCallError::UnexpectedResponse(None) => f.write_str("could not deserialize response body: response has empty body"),
-		}
+	/// Like [`Client::call`], for a method without parameters.
+	pub fn call0<R>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+	) -> Result<R, MethodCallError> where R: serde::de::DeserializeOwned {
+		let body = self.method_call(destination, path, interface, member, None)?;
+		deserialize_response(body)
 	}
-}
 
-impl std::error::Error for MethodCallError {
+	/// Like [`Client::call`], but checks the signature of the response body against [`crate::proto::ExpectedSignature`] of `R` first,
+	/// like [`Client::method_call_with_signature`] does. So a response of the wrong type returns [`MethodCallError::SignatureMismatch`]
+	/// instead of whatever error serde happens to hit.
+	///
+	/// `ExpectedSignature` is implemented for every type that implements [`crate::proto::ToVariant`].
+	pub fn call_checked<P, R>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: &P,
+	) -> Result<R, MethodCallError> where P: crate::proto::ToVariant + ?Sized, R: serde::de::DeserializeOwned + crate::proto::ExpectedSignature {
+		let parameters = parameters.to_variant();
+		let body = self.method_call_with_expected_signature(
+			destination,
+			path,
+			interface,
+			member,
+			parameters_body(&parameters),
+			R::expected_signature().as_ref(),
+		)?;
+		deserialize_response(body)
+	}
+
+	/// Like [`Client::call_checked`], for a method without parameters.
+	pub fn call0_checked<R>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+	) -> Result<R, MethodCallError> where R: serde::de::DeserializeOwned + crate::proto::ExpectedSignature {
+		let body = self.method_call_with_expected_signature(destination, path, interface, member, None, R::expected_signature().as_ref())?;
+		deserialize_response(body)
+	}
+
+	fn method_call_inner(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		send: impl FnOnce(&mut ClientSendHalf, &crate::proto::MessageHeader<'_>) -> Result<u32, crate::conn::SendError>,
+	) -> Result<ReceivedMessage, MethodCallError> {
+		let request_header = method_call_header(destination, path, interface, member)?;
+
+		let request_serial = send(&mut self.send_half, &request_header).map_err(MethodCallError::SendRequest);
+		let request_serial = self.auto_reconnect(request_serial, is_method_call_disconnected)?;
+
+		let deadline = self.send_half.method_call_timeout.map(|timeout| std::time::Instant::now() + timeout);
+
+		let result =
+			self.recv_half.recv_matching_with_fds(deadline, |header, _| is_response(header, request_serial))
+			.map_err(MethodCallError::RecvResponse)
+			.and_then(method_call_result);
+		crate::trace::method_call(request_serial, &request_header, &result);
+		self.auto_reconnect(result, is_method_call_disconnected)
+	}
+
+	/// Send a `METHOD_CALL` message without waiting for its response, so that more method calls can be sent before it arrives.
+	///
+	/// The response is received with [`PendingReply::wait`] or [`Client::poll_reply`]. Responses are handed to their [`PendingReply`]
+	/// in whatever order they arrive, and are never returned from [`Client::recv`]. If the [`PendingReply`] is dropped,
+	/// its response is discarded when it arrives.
+	///
+	/// The header must be of a method call that expects a reply. See [`Client::send`] for details about the header.
+	pub fn send_with_reply(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: Option<&crate::proto::Variant<'_>>,
+	) -> Result<PendingReply, crate::conn::SendError> {
+		let (response_sender, response_receiver) = std::sync::mpsc::channel();
+		self.send_half.response_sender = Some(response_sender);
+		let serial = self.send_half.send(header, body);
+		// The response sender is still here if sending failed before the method call was registered.
+		self.send_half.response_sender = None;
+		let serial = self.auto_reconnect(serial, is_send_disconnected)?;
+
+		Ok(PendingReply {
+			serial,
+			response_receiver,
+		})
+	}
+
+	/// Like [`PendingReply::wait`], but returns `Ok(None)` if the response has not been received yet instead of blocking,
+	/// when the connection is in non-blocking mode.
+	///
+	/// Once this has returned the response, the [`PendingReply`] is finished, and waiting for it again fails with [`MethodCallError::Disconnected`].
+	///
+	/// See [`crate::Connection::set_nonblocking`] for details.
+	pub fn poll_reply(
+		&mut self,
+		pending_reply: &PendingReply,
+	) -> Result<Option<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>)>, MethodCallError> {
+		let result = self.recv_reply(pending_reply, None, false);
+		let result = self.auto_reconnect(result, is_method_call_disconnected)?;
+		result.map(|response| method_call_result(response).map(|(header, body, _)| (header, body))).transpose()
+	}
+
+	/// Receive the response of the given pending reply. Other messages that are received meanwhile are queued.
+	///
+	/// If `block` is false, this returns `Ok(None)` instead of blocking when the connection is in non-blocking mode.
+	fn recv_reply(
+		&mut self,
+		pending_reply: &PendingReply,
+		deadline: Option<std::time::Instant>,
+		block: bool,
+	) -> Result<Option<ReceivedMessage>, MethodCallError> {
+		loop {
+			match pending_reply.response_receiver.try_recv() {
+				Ok(response) => return Ok(Some(response)),
+				Err(std::sync::mpsc::TryRecvError::Disconnected) => return Err(MethodCallError::Disconnected),
+				Err(std::sync::mpsc::TryRecvError::Empty) => (),
+			}
+
+			// The response is handed over to the receiver instead of being returned, so any message that is returned is some other message.
+			let message = match self.recv_half.recv_next(deadline) {
+				Ok(message) => message,
+				Err(crate::conn::RecvError::TimedOut) if !block => return Ok(None),
+				Err(err) => return Err(MethodCallError::RecvResponse(err)),
+			};
+			self.recv_half.received_messages.extend(message);
+		}
+	}
+
+	/// Receive a message from the message bus.
+	///
+	/// Blocks until a message is received.
+	///
+	/// Any file descriptors sent along with the message are closed.
+	pub fn recv(&mut self) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
+		let result = self.recv_half.recv();
+		self.auto_reconnect(result, is_recv_disconnected)
+	}
+
+	/// Like [`Client::recv`], but returns `Ok(None)` if no message has been received yet instead of blocking,
+	/// when the connection is in non-blocking mode.
+	///
+	/// See [`crate::Connection::set_nonblocking`] for details.
+	pub fn try_recv(
+		&mut self,
+	) -> Result<Option<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>)>, crate::conn::RecvError> {
+		let result = self.recv_half.try_recv();
+		self.auto_reconnect(result, is_recv_disconnected)
+	}
+
+	/// Like [`Client::recv_matching`], but returns `Ok(None)` if no matching message has been received yet instead of blocking,
+	/// when the connection is in non-blocking mode.
+	///
+	/// See [`crate::Connection::set_nonblocking`] for details.
+	pub fn try_recv_matching(
+		&mut self,
+		mut predicate: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> bool,
+	) -> Result<Option<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>)>, crate::conn::RecvError> {
+		loop {
+			let mut is_ping = false;
+			let result = self.recv_half.try_recv_matching(or_peer_ping(self.answer_peer_pings, &mut predicate, &mut is_ping));
+			let message = self.auto_reconnect(result, is_recv_disconnected)?;
+			match message {
+				Some((header, _)) if is_ping => self.answer_peer_ping(&header),
+				message => return Ok(message),
+			}
+		}
+	}
+
+	/// Like [`Client::recv`], but fails with [`crate::conn::RecvError::TimedOut`] if no message is received within the given timeout.
+	///
+	/// The client remains usable after the timeout elapses.
+	///
+	/// The timeout is implemented with [`crate::Connection::set_read_timeout`], so this fails with [`crate::conn::RecvError::Io`]
+	/// if the connection was opened with [`crate::Connection::from_stream`].
+	pub fn recv_timeout(
+		&mut self,
+		timeout: std::time::Duration,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
+		let result = self.recv_half.recv_timeout(timeout);
+		self.auto_reconnect(result, is_recv_disconnected)
+	}
+
+	/// Receive a message from the message bus that satisfies the given predicate.
+	///
+	/// Messages that do not match the predicate will not be discarded. Instead they will be returned
+	/// from subsequent calls to [`Client::recv`] or `recv_matching`.
+	///
+	/// Any file descriptors sent along with the returned message are closed.
+	///
+	/// `org.freedesktop.DBus.Peer.Ping` calls that are received in the meantime are answered. See [`Client::set_answer_peer_pings`]
+	pub fn recv_matching(
+		&mut self,
+		mut predicate: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> bool,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
+		loop {
+			let mut is_ping = false;
+			let result = self.recv_half.recv_matching(or_peer_ping(self.answer_peer_pings, &mut predicate, &mut is_ping));
+			let (header, body) = self.auto_reconnect(result, is_recv_disconnected)?;
+			if !is_ping {
+				return Ok((header, body));
+			}
+
+			self.answer_peer_ping(&header);
+		}
+	}
+
+	/// Like [`Client::recv_matching`], but fails with [`crate::conn::RecvError::TimedOut`] if no matching message is received
+	/// within the given timeout.
+	///
+	/// See [`Client::recv_timeout`] for details.
+	pub fn recv_matching_timeout(
+		&mut self,
+		timeout: std::time::Duration,
+		mut predicate: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> bool,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
+		let deadline = std::time::Instant::now() + timeout;
+		let mut timeout = timeout;
+		loop {
+			let mut is_ping = false;
+			let result = self.recv_half.recv_matching_timeout(timeout, or_peer_ping(self.answer_peer_pings, &mut predicate, &mut is_ping));
+			let (header, body) = self.auto_reconnect(result, is_recv_disconnected)?;
+			if !is_ping {
+				return Ok((header, body));
+			}
+
+			self.answer_peer_ping(&header);
+			timeout = deadline.saturating_duration_since(std::time::Instant::now());
+		}
+	}
+
+	/// Call the methods of the `org.freedesktop.DBus` interface of the message bus.
+	pub fn bus(&mut self) -> crate::BusProxy<'_> {
+		crate::BusProxy::new(self)
+	}
+
+	/// Add the given match rule and receive the signals that match it.
+	///
+	/// The rule only matches signals, whatever message type it was built with. It's removed when the returned stream is dropped.
+	/// See [`SignalStream`] for details.
+	pub fn signals<'a>(&'a mut self, rule: crate::MatchRule<'a>) -> Result<crate::SignalStream<'a>, MethodCallError> {
+		crate::SignalStream::new(self, rule)
+	}
+
+	/// Call `org.freedesktop.DBus.Peer.Ping` on the given destination, and return how long it took to answer.
+	///
+	/// This checks that the connection that owns the destination name is alive and handling its messages.
+	pub fn ping(&mut self, destination: &str) -> Result<std::time::Duration, MethodCallError> {
+		let start = std::time::Instant::now();
+		let body = self.method_call(destination, crate::proto::ObjectPath("/".into()), "org.freedesktop.DBus.Peer", "Ping", None)?;
+		let elapsed = start.elapsed();
+
+		if let Some(body) = body {
+			let err = serde::de::Error::invalid_type(serde::de::Unexpected::Other(&format!("{body:?}")), &"an empty body");
+			return Err(MethodCallError::UnexpectedResponse(Some(err)));
+		}
+
+		Ok(elapsed)
+	}
+
+	/// Call `org.freedesktop.DBus.Peer.GetMachineId` on the given destination, and return the ID of the machine it runs on.
+	///
+	/// The ID is 32 lowercase hexadecimal characters, as in `/etc/machine-id`
+	pub fn get_machine_id(&mut self, destination: &str) -> Result<String, MethodCallError> {
+		let machine_id: String = self.call0(destination, crate::proto::ObjectPath("/".into()), "org.freedesktop.DBus.Peer", "GetMachineId")?;
+
+		if machine_id.len() != 32 || !machine_id.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+			let err = serde::de::Error::invalid_value(serde::de::Unexpected::Str(&machine_id), &"a machine ID");
+			return Err(MethodCallError::UnexpectedResponse(Some(err)));
+		}
+
+		Ok(machine_id)
+	}
+
+	/// Get the value of a property with `org.freedesktop.DBus.Properties.Get`, and deserialize it into `T` with serde.
+	///
+	/// If the value can't be deserialized into `T`, this returns [`MethodCallError::PropertyTypeMismatch`].
+	pub fn get_property<T>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		name: &str,
+	) -> Result<T, MethodCallError> where T: serde::de::DeserializeOwned {
+		let body = self.method_call_with_signature(
+			destination,
+			path,
+			"org.freedesktop.DBus.Properties",
+			"Get",
+			Some(&crate::proto::Variant::Tuple { elements: vec![
+				crate::proto::Variant::String(interface.into()),
+				crate::proto::Variant::String(name.into()),
+			].into() }),
+			&crate::proto::Signature::Variant,
+		)?;
+		let Some(crate::proto::Variant::Variant(value)) = body else {
+			return Err(MethodCallError::UnexpectedResponse(None));
+		};
+
+		T::deserialize(value.into_owned()).map_err(|err| MethodCallError::PropertyTypeMismatch {
+			interface: interface.to_owned(),
+			name: name.to_owned(),
+			err,
+		})
+	}
+
+	/// Set the value of a property with `org.freedesktop.DBus.Properties.Set`
+	///
+	/// The value is converted with [`crate::proto::ToVariant`] and wrapped in a `Variant::Variant`, as the method requires.
+	pub fn set_property<T>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		name: &str,
+		value: &T,
+	) -> Result<(), MethodCallError> where T: crate::proto::ToVariant + ?Sized {
+		let _ = self.method_call(
+			destination,
+			path,
+			"org.freedesktop.DBus.Properties",
+			"Set",
+			Some(&crate::proto::Variant::Tuple { elements: vec![
+				crate::proto::Variant::String(interface.into()),
+				crate::proto::Variant::String(name.into()),
+				crate::proto::Variant::Variant(Box::new(value.to_variant()).into()),
+			].into() }),
+		)?;
+		Ok(())
+	}
+
+	/// Get the values of all the properties of an interface with `org.freedesktop.DBus.Properties.GetAll`, keyed by their names.
+	///
+	/// The values are unwrapped from the `Variant::Variant` that they're sent in.
+	pub fn get_all_properties(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+	) -> Result<std::collections::HashMap<String, crate::proto::Variant<'static>>, MethodCallError> {
+		let body = self.method_call_with_signature(
+			destination,
+			path,
+			"org.freedesktop.DBus.Properties",
+			"GetAll",
+			Some(&crate::proto::Variant::String(interface.into())),
+			&crate::proto::Signature::Array {
+				element: Box::new(crate::proto::Signature::DictEntry {
+					key: Box::new(crate::proto::Signature::String),
+					value: Box::new(crate::proto::Signature::Variant),
+				}),
+			},
+		)?;
+		let properties = body.and_then(crate::proto::Variant::into_map).ok_or(MethodCallError::UnexpectedResponse(None))?;
+
+		let properties =
+			properties.into_iter()
+			.filter_map(|(name, value)| {
+				let crate::proto::Variant::String(name) = name else {
+					return None;
+				};
+				let value = match value {
+					crate::proto::Variant::Variant(value) => value.into_owned(),
+					value => value,
+				};
+				Some((name.into_owned(), value))
+			})
+			.collect();
+		Ok(properties)
+	}
+
+	/// Receive the `org.freedesktop.DBus.Properties.PropertiesChanged` signals of the given object, optionally only those of the given interface.
+	///
+	/// See [`SignalStream`] for details, including how a well-known `destination` is handled.
+	pub fn subscribe_properties_changed<'a>(
+		&'a mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'a>,
+		interface: Option<&'a str>,
+	) -> Result<crate::SignalStream<'a, crate::PropertiesChanged<'static>>, MethodCallError> {
+		let mut rule =
+			crate::MatchRule::new()
+			.sender(destination.to_owned())
+			.path(path)
+			.interface("org.freedesktop.DBus.Properties")
+			.member("PropertiesChanged");
+		if let Some(interface) = interface {
+			rule = rule.arg(0, interface);
+		}
+
+		crate::SignalStream::new(self, rule)
+	}
+
+	/// Turn the client into a monitor that receives every message matching the given rules, with `org.freedesktop.DBus.Monitoring.BecomeMonitor`.
+	///
+	/// If no rules are given, the monitor receives every message on the bus. See [`Monitor`] for details.
+	///
+	/// A monitor can't send messages, so the client is consumed. Responses to [`PendingReply`]s that haven't been received yet
+	/// are returned by the monitor like any other message, and automatic reconnection is disabled.
+	pub fn become_monitor(mut self, rules: &[crate::MatchRule<'_>]) -> Result<crate::Monitor, MethodCallError> {
+		let rules: Vec<String> = rules.iter().map(ToString::to_string).collect();
+		let () = self.call(
+			"org.freedesktop.DBus",
+			crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+			"org.freedesktop.DBus.Monitoring",
+			"BecomeMonitor",
+			&(rules, 0_u32),
+		)?;
+
+		{
+			let mut state = lock_state(&self.send_half.state);
+			state.pending_method_calls = None;
+			state.request_endianness = None;
+		}
+		self.recv_half.monitor = true;
+		self.auto_reconnect = None;
+
+		Ok(crate::Monitor::new(self))
+	}
+
+	/// Split the client into a half that sends messages and calls methods, and a half that receives messages,
+	/// so that one thread can call methods while another thread is blocked receiving signals.
+	///
+	/// The halves share the serial counter and the name of the client, so every message sent with the [`ClientSendHalf`]
+	/// still gets a unique serial.
+	///
+	/// The response to a method call made with the send half is received by the [`ClientRecvHalf`], which hands it over to the method call
+	/// instead of returning it. So method calls only complete while another thread is receiving messages with the receive half.
+	/// If the receive half is dropped or its connection breaks, method calls that are waiting for their response fail with
+	/// [`MethodCallError::Disconnected`].
+	///
+	/// Messages are sent in the order they are sent with the send half, and the receive half returns the other messages
+	/// in the order they were received. See [`crate::Connection::split`] for details.
+	pub fn split(self) -> (ClientSendHalf, ClientRecvHalf) {
+		(self.send_half, self.recv_half)
+	}
+}
+
+impl std::fmt::Debug for Client {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let state = lock_state(&self.send_half.state);
+		f.debug_struct("Client")
+			.field("connection", &())
+			.field("last_serial", &state.last_serial)
+			.field("name", &state.name)
+			.field("reply_in_kind", &state.request_endianness.is_some())
+			.finish_non_exhaustive()
+	}
+}
+
+/// Statistics about the messages sent and received by a [`Client`]. See [`Client::stats`]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ClientStats {
+	/// The statistics of the underlying connection.
+	pub connection: crate::conn::ConnectionStats,
+
+	/// The number of messages that have been received but not yet returned by one of the `recv` functions,
+	/// because they were received while waiting for the response to a method call.
+	pub queued_messages: usize,
+}
+
+/// A match rule that was added with [`Client::add_match`]
+///
+/// Dropping this removes the rule. See [`Client::add_match`] for details.
+#[derive(Debug)]
+pub struct MatchGuard {
+	rule: String,
+
+	/// The state of the client that added the rule. Empty once the rule has been forgotten.
+	state: std::sync::Weak<std::sync::Mutex<ClientState>>,
+
+	/// The connection that the rule is removed on when the guard is dropped.
+	connection: std::sync::Weak<std::sync::Mutex<crate::conn::SendHalf>>,
+}
+
+impl MatchGuard {
+	fn new(send_half: &ClientSendHalf, rule: String) -> Self {
+		lock_state(&send_half.state).match_rules.push(rule.clone());
+		MatchGuard {
+			rule,
+			state: std::sync::Arc::downgrade(&send_half.state),
+			connection: std::sync::Arc::downgrade(&send_half.connection),
+		}
+	}
+
+	/// The rule, as it was sent to the bus.
+	pub fn rule(&self) -> &str {
+		&self.rule
+	}
+
+	/// Forget the rule without removing it from the bus, and return it.
+	fn forget(mut self) -> String {
+		if let Some(state) = std::mem::take(&mut self.state).upgrade() {
+			remove_match_rule(&mut lock_state(&state), &self.rule);
+		}
+		std::mem::take(&mut self.rule)
+	}
+}
+
+impl Drop for MatchGuard {
+	fn drop(&mut self) {
+		let (Some(state), Some(connection)) = (self.state.upgrade(), self.connection.upgrade()) else {
+			return;
+		};
+
+		let rule = std::mem::take(&mut self.rule);
+		{
+			let mut state = lock_state(&state);
+			remove_match_rule(&mut state, &rule);
+			// A peer has no match rules to remove.
+			if state.peer {
+				return;
+			}
+		}
+
+		// The rule is removed without waiting for the response. If it can't be sent now, eg because the connection is non-blocking
+		// and its socket is full, it's removed along with the next message that is sent instead.
+		if send_remove_match(&state, &connection, &rule).is_err() {
+			lock_state(&state).removed_match_rules.push(rule);
+		}
+	}
+}
+
+/// The response to a method call that was sent with [`Client::send_with_reply`]
+///
+/// Dropping this discards the response when it arrives.
+#[derive(Debug)]
+pub struct PendingReply {
+	serial: u32,
+	response_receiver: std::sync::mpsc::Receiver<ReceivedMessage>,
+}
+
+impl PendingReply {
+	/// The serial of the method call.
+	pub fn serial(&self) -> u32 {
+		self.serial
+	}
+
+	/// Wait for the response to the method call, using the given client to receive messages until it arrives.
+	///
+	/// The client must be the one that sent the method call. Messages other than the response that are received meanwhile
+	/// are returned from subsequent calls to [`Client::recv`]. The timeout of [`Client::set_method_call_timeout`] applies.
+	///
+	/// If the response is an `ERROR`, this returns [`MethodCallError::Error`]
+	pub fn wait(
+		self,
+		client: &mut Client,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), MethodCallError> {
+		let deadline = client.send_half.method_call_timeout.map(|timeout| std::time::Instant::now() + timeout);
+		let result = client.recv_reply(&self, deadline, true);
+		let response = client.auto_reconnect(result, is_method_call_disconnected)?;
+		let Some(response) = response else {
+			unreachable!("blocking receive returned no message");
+		};
+		let (header, body, _) = method_call_result(response)?;
+		Ok((header, body))
+	}
+}
+
+/// How a [`Client`] reconnects by itself when its connection breaks. See [`Client::set_auto_reconnect`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ReconnectPolicy {
+	/// How many times to try to reconnect before giving up. The operation that failed returns its error either way.
+	pub max_attempts: u32,
+
+	/// How long to wait between attempts. The first attempt is made immediately.
+	pub delay: std::time::Duration,
+}
+
+/// The half of a [`Client`] that sends messages and calls methods. See [`Client::split`]
+pub struct ClientSendHalf {
+	/// Shared with the [`MatchGuard`]s of the client, so that they can remove their rules when they're dropped.
+	connection: std::sync::Arc<std::sync::Mutex<crate::conn::SendHalf>>,
+	method_call_timeout: Option<std::time::Duration>,
+
+	/// The channel that the response to the method call that is being sent is handed over on.
+	/// It is registered along with the serial of the method call, so that the response can't be received before it's registered.
+	response_sender: Option<std::sync::mpsc::Sender<ReceivedMessage>>,
+
+	state: std::sync::Arc<std::sync::Mutex<ClientState>>,
+}
+
+impl ClientSendHalf {
+	/// See [`Client::set_name`]
+	pub fn set_name(&mut self, name: String) -> Option<String> {
+		lock_state(&self.state).name.replace(name)
+	}
+
+	/// See [`Client::set_reply_in_kind`]
+	pub fn set_reply_in_kind(&mut self, reply_in_kind: bool) {
+		lock_state(&self.state).request_endianness = reply_in_kind.then(Default::default);
+	}
+
+	/// Set how long method calls wait for their response, or `None` to wait until the response is received.
+	///
+	/// Unlike [`Client::set_method_call_timeout`], this does not use the read timeout of the connection.
+	pub fn set_method_call_timeout(&mut self, timeout: Option<std::time::Duration>) {
+		self.method_call_timeout = timeout;
+	}
+
+	/// See [`Client::flush`]
+	pub fn flush(&mut self) -> std::io::Result<()> {
+		lock_connection(&self.connection).flush()
+	}
+
+	/// See [`Client::flush_pending`]
+	pub fn flush_pending(&mut self) -> Result<(), crate::conn::SendError> {
+		lock_connection(&self.connection).flush_pending()
+	}
+
+	/// See [`Client::send`]
+	pub fn send(&mut self, header: &crate::proto::MessageHeader<'_>, body: Option<&crate::proto::Variant<'_>>) -> Result<u32, crate::conn::SendError> {
+		self.send_inner(header, |connection, header, endianness| match endianness {
+			Some(endianness) => connection.send_with_endianness(header, body, endianness),
+			None => connection.send(header, body),
+		})
+	}
+
+	/// See [`Client::send_serialized`]
+	pub fn send_serialized<T>(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: &T,
+		body_signature: &crate::proto::Signature,
+	) -> Result<u32, crate::conn::SendError> where T: serde::Serialize + ?Sized {
+		self.send_inner(header, |connection, header, endianness| match endianness {
+			Some(endianness) => connection.send_serialized_with_endianness(header, body, body_signature, endianness),
+			None => connection.send_serialized(header, body, body_signature),
+		})
+	}
+
+	/// See [`Client::send_with_fds`]
+	pub fn send_with_fds(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: Option<&crate::proto::Variant<'_>>,
+		fds: &[std::os::fd::BorrowedFd<'_>],
+	) -> Result<u32, crate::conn::SendError> {
+		self.send_inner(header, |connection, header, endianness| match endianness {
+			Some(endianness) => connection.send_with_fds_and_endianness(header, body, fds, endianness),
+			None => connection.send_with_fds(header, body, fds),
+		})
+	}
+
+	/// See [`Client::send_message`]
+	pub fn send_message(
+		&mut self,
+		(header, body): (crate::proto::MessageHeader<'_>, Option<crate::proto::Variant<'_>>),
+	) -> Result<u32, crate::conn::SendError> {
+		self.send(&header, body.as_ref())
+	}
+
+	/// Send the message with [`send_one`], after removing the match rules whose [`MatchGuard`] could not remove them when it was dropped.
+	fn send_inner(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		send: impl FnOnce(&mut crate::conn::SendHalf, &crate::proto::MessageHeader<'_>, Option<crate::proto::Endianness>) -> Result<(), crate::conn::SendError>,
+	) -> Result<u32, crate::conn::SendError> {
+		// Removing the rules is best-effort, so the rules that still can't be removed are kept for the next message,
+		// and don't fail the message itself.
+		let removed_match_rules = std::mem::take(&mut lock_state(&self.state).removed_match_rules);
+		let mut removed_match_rules = removed_match_rules.into_iter();
+		while let Some(rule) = removed_match_rules.next() {
+			if send_remove_match(&self.state, &self.connection, &rule).is_err() {
+				lock_state(&self.state).removed_match_rules.extend(std::iter::once(rule).chain(removed_match_rules));
+				break;
+			}
+		}
+
+		let response_sender = self.response_sender.take();
+		send_one(&self.state, &self.connection, header, response_sender, send)
+	}
+
+	/// See [`Client::method_call`]
+	pub fn method_call(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: Option<&crate::proto::Variant<'_>>,
+	) -> Result<Option<crate::proto::Variant<'static>>, MethodCallError> {
+		let (_, body, _) = self.method_call_inner(destination, path, interface, member, |client, header| client.send(header, parameters))?;
+		Ok(body)
+	}
+
+	/// See [`Client::method_call_with_flags`]
+	pub fn method_call_with_flags(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: Option<&crate::proto::Variant<'_>>,
+		flags: crate::proto::MessageFlags,
+	) -> Result<Option<crate::proto::Variant<'static>>, MethodCallError> {
+		// The method call is not registered to wait for a response, since none is sent.
+		if flags.contains(crate::proto::message_flags::NO_REPLY_EXPECTED) {
+			let mut request_header = method_call_header(destination, path, interface, member)?;
+			request_header.flags = flags;
+			let _ = self.send(&request_header, parameters).map_err(MethodCallError::SendRequest)?;
+			return Ok(None);
+		}
+
+		let (_, body, _) = self.method_call_inner(destination, path, interface, member, |client, header| {
+			let mut header = header.clone();
+			header.flags = flags;
+			client.send(&header, parameters)
+		})?;
+		Ok(body)
+	}
+
+	/// See [`Client::method_call_with_fds`]
+	pub fn method_call_with_fds(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: Option<&crate::proto::Variant<'_>>,
+		fds: Vec<std::os::fd::OwnedFd>,
+	) -> Result<(Option<crate::proto::Variant<'static>>, Vec<std::os::fd::OwnedFd>), MethodCallError> {
+		let (_, body, response_fds) = self.method_call_inner(destination, path, interface, member, |client, header| {
+			let borrowed_fds: Vec<_> = fds.iter().map(std::os::fd::AsFd::as_fd).collect();
+			let result = client.send_with_fds(header, parameters, &borrowed_fds);
+			// The file descriptors have been sent, so this process's copies can be closed.
+			drop(fds);
+			result
+		})?;
+		Ok((body, response_fds))
+	}
+
+	/// See [`Client::method_call_serialized`]
+	pub fn method_call_serialized<T>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: &T,
+		parameters_signature: &crate::proto::Signature,
+	) -> Result<Option<crate::proto::Variant<'static>>, MethodCallError> where T: serde::Serialize + ?Sized {
+		let (_, body, _) =
+			self.method_call_inner(
+				destination,
+				path,
+				interface,
+				member,
+				|client, header| client.send_serialized(header, parameters, parameters_signature),
+			)?;
+		Ok(body)
+	}
+
+	/// See [`Client::method_call_with_signature`]
+	pub fn method_call_with_signature(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: Option<&crate::proto::Variant<'_>>,
+		expected_signature: &crate::proto::Signature,
+	) -> Result<Option<crate::proto::Variant<'static>>, MethodCallError> {
+		self.method_call_with_expected_signature(destination, path, interface, member, parameters, Some(expected_signature))
+	}
+
+	/// Like [`Client::method_call_with_signature`], except the signature is only checked if one is given.
+	pub(crate) fn method_call_with_expected_signature(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: Option<&crate::proto::Variant<'_>>,
+		expected_signature: Option<&crate::proto::Signature>,
+	) -> Result<Option<crate::proto::Variant<'static>>, MethodCallError> {
+		let (header, body, _) = self.method_call_inner(destination, path, interface, member, |client, header| client.send(header, parameters))?;
+		if let Some(expected_signature) = expected_signature {
+			let () = check_signature(&header, expected_signature, member)?;
+		}
+		Ok(body)
+	}
+
+	/// See [`Client::call`]
+	pub fn call<P, R>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: &P,
+	) -> Result<R, MethodCallError> where P: crate::proto::ToVariant + ?Sized, R: serde::de::DeserializeOwned {
+		let parameters = parameters.to_variant();
+		let body = self.method_call(destination, path, interface, member, parameters_body(&parameters))?;
+		deserialize_response(body)
+	}
+
+	/// See [`Client::call0`]
+	pub fn call0<R>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+	) -> Result<R, MethodCallError> where R: serde::de::DeserializeOwned {
+		let body = self.method_call(destination, path, interface, member, None)?;
+		deserialize_response(body)
+	}
+
+	/// See [`Client::call_checked`]
+	pub fn call_checked<P, R>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		parameters: &P,
+	) -> Result<R, MethodCallError> where P: crate::proto::ToVariant + ?Sized, R: serde::de::DeserializeOwned + crate::proto::ExpectedSignature {
+		let parameters = parameters.to_variant();
+		let body = self.method_call_with_expected_signature(
+			destination,
+			path,
+			interface,
+			member,
+			parameters_body(&parameters),
+			R::expected_signature().as_ref(),
+		)?;
+		deserialize_response(body)
+	}
+
+	/// See [`Client::call0_checked`]
+	pub fn call0_checked<R>(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+	) -> Result<R, MethodCallError> where R: serde::de::DeserializeOwned + crate::proto::ExpectedSignature {
+		let body = self.method_call_with_expected_signature(destination, path, interface, member, None, R::expected_signature().as_ref())?;
+		deserialize_response(body)
+	}
+
+	/// See [`Client::add_match`]
+	pub fn add_match(&mut self, rule: &crate::MatchRule<'_>) -> Result<MatchGuard, MethodCallError> {
+		let rule = rule.to_string();
+		let _ = self.method_call(
+			"org.freedesktop.DBus",
+			crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+			"org.freedesktop.DBus",
+			"AddMatch",
+			Some(&crate::proto::Variant::String((&*rule).into())),
+		)?;
+		Ok(MatchGuard::new(self, rule))
+	}
+
+	/// See [`Client::remove_match`]
+	pub fn remove_match(&mut self, guard: MatchGuard) -> Result<(), MethodCallError> {
+		let rule = guard.forget();
+		let _ = self.method_call(
+			"org.freedesktop.DBus",
+			crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+			"org.freedesktop.DBus",
+			"RemoveMatch",
+			Some(&crate::proto::Variant::String(rule.into())),
+		)?;
+		Ok(())
+	}
+
+	fn method_call_inner(
+		&mut self,
+		destination: &str,
+		path: crate::proto::ObjectPath<'_>,
+		interface: &str,
+		member: &str,
+		send: impl FnOnce(&mut Self, &crate::proto::MessageHeader<'_>) -> Result<u32, crate::conn::SendError>,
+	) -> Result<ReceivedMessage, MethodCallError> {
+		let request_header = method_call_header(destination, path, interface, member)?;
+
+		let (response_sender, response_receiver) = std::sync::mpsc::channel();
+		self.response_sender = Some(response_sender);
+		let request_serial = send(self, &request_header);
+		// The response sender is still here if sending failed before the method call was registered.
+		self.response_sender = None;
+		let request_serial = request_serial.map_err(MethodCallError::SendRequest)?;
+
+		// The response sender is dropped if the receive half is dropped or its connection breaks.
+		let response = match self.method_call_timeout {
+			Some(timeout) => response_receiver.recv_timeout(timeout).map_err(|err| match err {
+				std::sync::mpsc::RecvTimeoutError::Timeout => MethodCallError::RecvResponse(crate::conn::RecvError::TimedOut),
+				std::sync::mpsc::RecvTimeoutError::Disconnected => MethodCallError::Disconnected,
+			}),
+			None => response_receiver.recv().map_err(|std::sync::mpsc::RecvError| MethodCallError::Disconnected),
+		};
+
+		let response = match response {
+			Ok(response) => Ok(response),
+
+			Err(err) => {
+				// Unregister the method call, so that a response that is received later is returned from `ClientRecvHalf::recv`
+				// like any other message.
+				if let Some(pending_method_calls) = &mut lock_state(&self.state).pending_method_calls {
+					let _ = pending_method_calls.remove(&request_serial);
+				}
+
+				// The response might have been handed over after the timeout elapsed but before the method call was unregistered.
+				response_receiver.try_recv().map_err(|_| err)
+			},
+		};
+
+		let result = response.and_then(method_call_result);
+		crate::trace::method_call(request_serial, &request_header, &result);
+		result
+	}
+}
+
+/// The half of a [`Client`] that receives messages. See [`Client::split`]
+pub struct ClientRecvHalf {
+	connection: crate::conn::RecvHalf,
+	received_messages: std::collections::VecDeque<ReceivedMessage>,
+	state: std::sync::Arc<std::sync::Mutex<ClientState>>,
+
+	/// Whether the client has become a monitor, so messages of unknown types are returned instead of being ignored.
+	monitor: bool,
+}
+
+impl ClientRecvHalf {
+	/// See [`Client::set_interner`]
+	pub fn set_interner(&mut self, interner: Option<&'static crate::proto::Interner>) {
+		self.connection.set_interner(interner);
+	}
+
+	/// See [`Client::recv`]
+	pub fn recv(&mut self) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
+		let (header, body, _) = match self.received_messages.pop_front() {
+			Some(message) => message,
+			None => self.recv_new(None)?,
+		};
+		Ok((header, body))
+	}
+
+	/// See [`Client::try_recv`]
+	pub fn try_recv(
+		&mut self,
+	) -> Result<Option<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>)>, crate::conn::RecvError> {
+		let message = match self.received_messages.pop_front() {
+			Some(message) => Some(message),
+			None => self.try_recv_new()?,
+		};
+		Ok(message.map(|(header, body, _)| (header, body)))
+	}
+
+	/// See [`Client::try_recv_matching`]
+	#[allow(clippy::missing_panics_doc)] // `self.received_messages.remove(i).unwrap()` cannot fail
+	pub fn try_recv_matching(
+		&mut self,
+		mut predicate: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> bool,
+	) -> Result<Option<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>)>, crate::conn::RecvError> {
+		for (i, already_received_message) in self.received_messages.iter().enumerate() {
+			if predicate(&already_received_message.0, already_received_message.1.as_ref()) {
+				let (header, body, _) = self.received_messages.remove(i).unwrap();
+				return Ok(Some((header, body)));
+			}
+		}
+
+		while let Some(message) = self.try_recv_new()? {
+			if predicate(&message.0, message.1.as_ref()) {
+				let (header, body, _) = message;
+				return Ok(Some((header, body)));
+			}
+
+			self.received_messages.push_back(message);
+		}
+
+		Ok(None)
+	}
+
+	/// See [`Client::recv_timeout`]
+	pub fn recv_timeout(
+		&mut self,
+		timeout: std::time::Duration,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
+		let (header, body, _) = match self.received_messages.pop_front() {
+			Some(message) => message,
+			None => self.recv_new(Some(std::time::Instant::now() + timeout))?,
+		};
+		Ok((header, body))
+	}
+
+	/// See [`Client::recv_matching`]
+	pub fn recv_matching(
+		&mut self,
+		predicate: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> bool,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
+		let (header, body, _) = self.recv_matching_with_fds(None, predicate)?;
+		Ok((header, body))
+	}
+
+	/// See [`Client::recv_matching_timeout`]
+	pub fn recv_matching_timeout(
+		&mut self,
+		timeout: std::time::Duration,
+		predicate: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> bool,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
+		let (header, body, _) = self.recv_matching_with_fds(Some(std::time::Instant::now() + timeout), predicate)?;
+		Ok((header, body))
+	}
+
+	#[allow(clippy::missing_panics_doc)] // `self.received_messages.remove(i).unwrap()` cannot fail
+	fn recv_matching_with_fds(
+		&mut self,
+		deadline: Option<std::time::Instant>,
+		mut predicate: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> bool,
+	) -> Result<ReceivedMessage, crate::conn::RecvError> {
+		for (i, already_received_message) in self.received_messages.iter().enumerate() {
+			if predicate(&already_received_message.0, already_received_message.1.as_ref()) {
+				let result = self.received_messages.remove(i).unwrap();
+				return Ok(result);
+			}
+		}
+
+		loop {
+			let message = self.recv_new(deadline)?;
+			if predicate(&message.0, message.1.as_ref()) {
+				return Ok(message);
+			}
+
+			self.received_messages.push_back(message);
+		}
+	}
+
+	/// Receive a message from the connection, failing with [`crate::conn::RecvError::TimedOut`] if the deadline passes first.
+	fn recv_new(&mut self, deadline: Option<std::time::Instant>) -> Result<ReceivedMessage, crate::conn::RecvError> {
+		loop {
+			if let Some(message) = self.recv_next(deadline)? {
+				return Ok(message);
+			}
+		}
+	}
+
+	fn try_recv_new(&mut self) -> Result<Option<ReceivedMessage>, crate::conn::RecvError> {
+		loop {
+			match self.recv_next(None) {
+				Ok(Some(message)) => return Ok(Some(message)),
+				Ok(None) => (),
+				Err(crate::conn::RecvError::TimedOut) => return Ok(None),
+				Err(err) => return Err(err),
+			}
+		}
+	}
+
+	/// Receive the next message from the connection, failing with [`crate::conn::RecvError::TimedOut`] if the deadline passes first.
+	///
+	/// Responses to method calls that are waiting for them, such as those of the send half of a split client
+	/// or those of a [`PendingReply`], are handed over to the method call instead of being returned, in which case this returns `Ok(None)`.
+	/// Messages of unknown types are ignored unless the client is a monitor, in which case this also returns `Ok(None)`.
+	fn recv_next(&mut self, deadline: Option<std::time::Instant>) -> Result<Option<ReceivedMessage>, crate::conn::RecvError> {
+		let Some(deadline) = deadline else {
+			return self.recv_next_inner();
+		};
+
+		let timeout = deadline.saturating_duration_since(std::time::Instant::now());
+		if timeout.is_zero() {
+			return Err(crate::conn::RecvError::TimedOut);
+		}
+
+		let previous_timeout = self.connection.read_timeout();
+		self.connection.set_read_timeout(Some(timeout)).map_err(crate::conn::RecvError::Io)?;
+		let message = self.recv_next_inner();
+		let restored = self.connection.set_read_timeout(previous_timeout);
+
+		match (message, restored) {
+			(Ok(message), Ok(())) => Ok(message),
+			(Err(err), _) => Err(err),
+			(Ok(message), Err(err)) => {
+				// Keep the message for a later recv rather than losing it.
+				self.received_messages.extend(message);
+				Err(crate::conn::RecvError::Io(err))
+			},
+		}
+	}
+
+	fn recv_next_inner(&mut self) -> Result<Option<ReceivedMessage>, crate::conn::RecvError> {
+		let (header, body, endianness, fds) = match self.connection.recv_with_endianness_and_fds() {
+			Ok(message) => message,
+
+			Err(err) => {
+				if matches!(err, crate::conn::RecvError::Io(_)) {
+					// The connection is broken, so wake up the method calls that are waiting for their response,
+					// since it can't be received anymore.
+					lock_state(&self.state).pending_method_calls = None;
+				}
+
+				return Err(err);
+			},
+		};
+
+		if matches!(header.r#type, crate::proto::MessageType::Unknown { .. }) && !self.monitor {
+			// The specification requires messages of unknown types to be ignored.
+			return Ok(None);
+		}
+
+		let response_sender = {
+			let mut state = lock_state(&self.state);
+
+			if let Some(request_endianness) = &mut state.request_endianness {
+				if
+					matches!(header.r#type, crate::proto::MessageType::MethodCall { .. }) &&
+					!header.flags.contains(crate::proto::message_flags::NO_REPLY_EXPECTED)
+				{
+					let _ = request_endianness.insert((header.sender().map(ToOwned::to_owned), header.serial), endianness);
+				}
+			}
+
+			match (&mut state.pending_method_calls, &header.r#type) {
+				(
+					Some(pending_method_calls),
+					crate::proto::MessageType::Error { reply_serial, .. } | crate::proto::MessageType::MethodReturn { reply_serial },
+				) => pending_method_calls.remove(reply_serial),
+				_ => None,
+			}
+		};
+
+		let message = (header, body, fds);
+
+		match response_sender {
+			Some(response_sender) => {
+				// If the receiver has been dropped, nothing is waiting for the response anymore, such as a dropped `PendingReply`,
+				// so the response is discarded.
+				let _ = response_sender.send(message);
+				Ok(None)
+			},
+
+			None => Ok(Some(message)),
+		}
+	}
+}
+
+impl Drop for ClientRecvHalf {
+	fn drop(&mut self) {
+		// Wake up the method calls that are waiting for their response, since it can't be received anymore.
+		lock_state(&self.state).pending_method_calls = None;
+	}
+}
+
+/// The state of a [`Client`] that is shared by its halves.
+struct ClientState {
+	last_serial: u32,
+	name: Option<String>,
+
+	/// The endianness of received method calls that have not been replied to yet, keyed by their sender and serial.
+	/// `None` if replies are not sent in kind.
+	request_endianness: Option<std::collections::HashMap<(Option<String>, u32), crate::proto::Endianness>>,
+
+	/// The method calls of the send half that are waiting for their response, keyed by their serial.
+	/// `None` once the receive half has been dropped or its connection has broken.
+	pending_method_calls: Option<std::collections::HashMap<u32, std::sync::mpsc::Sender<ReceivedMessage>>>,
+
+	/// The match rules that were added with [`Client::add_match`], to add again when reconnecting.
+	match_rules: Vec<String>,
+
+	/// The match rules whose [`MatchGuard`] was dropped but could not remove them, to remove with the next message that is sent.
+	removed_match_rules: Vec<String>,
+
+	/// Whether the client is connected to a peer rather than a message bus, so it doesn't call `org.freedesktop.DBus.Hello` when reconnecting.
+	peer: bool,
+}
+
+/// Locks the state of a client. The state is only modified in ways that can't panic, so a poisoned lock is ignored.
+fn lock_state(state: &std::sync::Mutex<ClientState>) -> std::sync::MutexGuard<'_, ClientState> {
+	state.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Locks the send half of the connection of a client. A panic while sending leaves the connection in a state that the next send can
+/// still handle, so a poisoned lock is ignored.
+fn lock_connection(connection: &std::sync::Mutex<crate::conn::SendHalf>) -> std::sync::MutexGuard<'_, crate::conn::SendHalf> {
+	connection.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Prepare the header to be sent, and send it with `send` along with the endianness to reply in, if any.
+///
+/// If a response sender is given, it's registered to receive the response to the method call.
+fn send_one(
+	state: &std::sync::Mutex<ClientState>,
+	connection: &std::sync::Mutex<crate::conn::SendHalf>,
+	header: &crate::proto::MessageHeader<'_>,
+	response_sender: Option<std::sync::mpsc::Sender<ReceivedMessage>>,
+	send: impl FnOnce(&mut crate::conn::SendHalf, &crate::proto::MessageHeader<'_>, Option<crate::proto::Endianness>) -> Result<(), crate::conn::SendError>,
+) -> Result<u32, crate::conn::SendError> {
+	let (header, endianness) = {
+		let mut state = lock_state(state);
+
+		if let Some(name) = &state.name {
+			crate::proto::validate::validate_bus_name(name).map_err(crate::conn::SendError::InvalidSender)?;
+		}
+
+		// Serial is in the range 1..=u32::MAX , ie it rolls over to 1 rather than 0
+		state.last_serial = state.last_serial % u32::MAX + 1;
+
+		// The caller's header is left as-is, so that it can be sent again.
+		let mut header = header.clone();
+		header.serial = state.last_serial;
+
+		if let Some(name) = &state.name {
+			// name is cloned because the lifetime of state.name needs to be independent of the lifetime of header
+			header.fields.to_mut().push(crate::proto::MessageHeaderField::Sender(name.clone().into()));
+		}
+
+		let endianness = match (&mut state.request_endianness, &header.r#type) {
+			(
+				Some(request_endianness),
+				crate::proto::MessageType::Error { reply_serial, .. } | crate::proto::MessageType::MethodReturn { reply_serial },
+			) => request_endianness.remove(&(header.destination().map(ToOwned::to_owned), *reply_serial)),
+			_ => None,
+		};
+
+		// If the receive half has been dropped, the response sender is dropped too, so the method call fails when it waits for the response.
+		if let (Some(pending_method_calls), Some(response_sender)) = (&mut state.pending_method_calls, response_sender) {
+			let _ = pending_method_calls.insert(header.serial, response_sender);
+		}
+
+		(header, endianness)
+	};
+
+	let result = send(&mut lock_connection(connection), &header, endianness);
+	if result.is_err() {
+		if let Some(pending_method_calls) = &mut lock_state(state).pending_method_calls {
+			let _ = pending_method_calls.remove(&header.serial);
+		}
+	}
+	let () = result?;
+
+	Ok(header.serial)
+}
+
+/// Send `org.freedesktop.DBus.RemoveMatch` for the given rule without waiting for the response.
+fn send_remove_match(
+	state: &std::sync::Mutex<ClientState>,
+	connection: &std::sync::Mutex<crate::conn::SendHalf>,
+	rule: &str,
+) -> Result<u32, crate::conn::SendError> {
+	let header = crate::proto::MessageHeader {
+		r#type: crate::proto::MessageType::MethodCall {
+			member: "RemoveMatch".into(),
+			path: crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+		},
+		flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+		body_len: 0,
+		serial: 0,
+		fields: vec![
+			crate::proto::MessageHeaderField::Destination("org.freedesktop.DBus".into()),
+			crate::proto::MessageHeaderField::Interface("org.freedesktop.DBus".into()),
+		].into(),
+	};
+	let body = crate::proto::Variant::String(rule.into());
+	send_one(state, connection, &header, None, |connection, header, _| connection.send(header, Some(&body)))
+}
+
+/// Forgets one occurrence of a match rule, so that it's not added again when reconnecting.
+fn remove_match_rule(state: &mut ClientState, rule: &str) {
+	if let Some(i) = state.match_rules.iter().position(|match_rule| match_rule == rule) {
+		let _ = state.match_rules.remove(i);
+	}
+}
+
+fn is_send_disconnected(err: &crate::conn::SendError) -> bool {
+	matches!(err, crate::conn::SendError::Io(_))
+}
+
+fn is_recv_disconnected(err: &crate::conn::RecvError) -> bool {
+	matches!(err, crate::conn::RecvError::Io(_))
+}
+
+fn is_method_call_disconnected(err: &MethodCallError) -> bool {
+	match err {
+		MethodCallError::SendRequest(err) => is_send_disconnected(err),
+		MethodCallError::RecvResponse(err) => is_recv_disconnected(err),
+		_ => false,
+	}
+}
+
+/// The header of a method call, after checking that the destination, interface and member are valid names.
+pub(crate) fn method_call_header<'a>(
+	destination: &'a str,
+	path: crate::proto::ObjectPath<'a>,
+	interface: &'a str,
+	member: &'a str,
+) -> Result<crate::proto::MessageHeader<'a>, MethodCallError> {
+	crate::proto::validate::validate_bus_name(destination)
+		.map_err(|reason| MethodCallError::InvalidArgument { field: "destination", reason })?;
+	crate::proto::validate::validate_interface_name(interface)
+		.map_err(|reason| MethodCallError::InvalidArgument { field: "interface", reason })?;
+	crate::proto::validate::validate_member_name(member)
+		.map_err(|reason| MethodCallError::InvalidArgument { field: "member", reason })?;
+
+	Ok(crate::proto::MessageHeader {
+		r#type: crate::proto::MessageType::MethodCall {
+			member: member.into(),
+			path,
+		},
+		flags: crate::proto::message_flags::NONE,
+		body_len: 0,
+		serial: 0,
+		fields: vec![
+			crate::proto::MessageHeaderField::Destination(destination.into()),
+			crate::proto::MessageHeaderField::Interface(interface.into()),
+		].into(),
+	})
+}
+
+/// Wraps the predicate of a `recv_matching` function so that it also matches `org.freedesktop.DBus.Peer.Ping` calls
+/// if they're answered, and sets `is_ping` if the matching message is a ping that didn't match the predicate itself.
+fn or_peer_ping<'a>(
+	answer_peer_pings: bool,
+	predicate: &'a mut impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> bool,
+	is_ping: &'a mut bool,
+) -> impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> bool + 'a {
+	move |header, body| {
+		if predicate(header, body) {
+			return true;
+		}
+
+		*is_ping = answer_peer_pings && is_peer_ping(header);
+		*is_ping
+	}
+}
+
+/// Whether the given message is an `org.freedesktop.DBus.Peer.Ping` call.
+fn is_peer_ping(header: &crate::proto::MessageHeader<'_>) -> bool {
+	matches!(header.r#type, crate::proto::MessageType::MethodCall { ref member, .. } if member == "Ping") &&
+		header.interface() == Some("org.freedesktop.DBus.Peer")
+}
+
+/// Whether the given message is the response to the method call with the given serial.
+fn is_response(header: &crate::proto::MessageHeader<'_>, request_serial: u32) -> bool {
+	match header.r#type {
+		crate::proto::MessageType::Error { reply_serial, .. } if reply_serial == request_serial => true,
+		crate::proto::MessageType::MethodReturn { reply_serial, .. } if reply_serial == request_serial => true,
+		_ => false,
+	}
+}
+
+/// Converts the response to a method call into an error if it's an `ERROR`.
+fn method_call_result(response: ReceivedMessage) -> Result<ReceivedMessage, MethodCallError> {
+	match response.0.r#type {
+		crate::proto::MessageType::Error { name, reply_serial: _ } =>
+			Err(MethodCallError::Error(name.into_owned(), response.1)),
+
+		crate::proto::MessageType::MethodReturn { reply_serial: _ } =>
+			Ok(response),
+
+		_ => unreachable!(),
+	}
+}
+
+/// The body of a method call with the given parameters. An empty tuple, ie `()`, means that the method call has no body.
+fn parameters_body<'a>(parameters: &'a crate::proto::Variant<'a>) -> Option<&'a crate::proto::Variant<'a>> {
+	match parameters {
+		crate::proto::Variant::Tuple { elements } if elements.is_empty() => None,
+		parameters => Some(parameters),
+	}
+}
+
+/// Deserializes the body of the response to a method call. See [`Client::call`]
+fn deserialize_response<R>(body: Option<crate::proto::Variant<'static>>) -> Result<R, MethodCallError> where R: serde::de::DeserializeOwned {
+	match body {
+		Some(body) => R::deserialize(body).map_err(|err| MethodCallError::UnexpectedResponse(Some(err))),
+
+		// A unit deserializes into `()`, and into `None` for `Option<T>`
+		None =>
+			R::deserialize(serde::de::value::UnitDeserializer::<crate::proto::VariantDeserializeError>::new())
+			.map_err(|_| MethodCallError::UnexpectedResponse(None)),
+	}
+}
+
+/// Checks that the body of the response to a method call has the expected signature. See [`Client::method_call_with_signature`]
+fn check_signature(
+	header: &crate::proto::MessageHeader<'_>,
+	expected_signature: &crate::proto::Signature,
+	member: &str,
+) -> Result<(), MethodCallError> {
+	if let Some(actual_signature) = header.signature() {
+		if !signature_matches(expected_signature, actual_signature) {
+			return Err(MethodCallError::SignatureMismatch {
+				expected: expected_signature.clone(),
+				actual: actual_signature.clone(),
+				member: member.to_owned(),
+			});
+		}
+	}
+
+	Ok(())
+}
+
+/// An error from creating a [`Client`].
+#[derive(Debug)]
+pub enum CreateClientError {
+	Hello(MethodCallError),
+}
+
+impl std::fmt::Display for CreateClientError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			CreateClientError::Hello(_) => f.write_str("could not complete hello"),
+		}
+	}
+}
+
+impl std::error::Error for CreateClientError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			CreateClientError::Hello(err) => Some(err),
+		}
+	}
+}
+
+/// An error from reconnecting a [`Client`] with [`Client::reconnect`]
+#[derive(Debug)]
+pub enum ReconnectError {
+	AddMatch(MethodCallError),
+	Connect(crate::conn::ConnectError),
+	Hello(MethodCallError),
+}
+
+impl std::fmt::Display for ReconnectError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ReconnectError::AddMatch(_) => f.write_str("could not add match rule again"),
+			ReconnectError::Connect(_) => f.write_str("could not reconnect to bus"),
+			ReconnectError::Hello(_) => f.write_str("could not complete hello"),
+		}
+	}
+}
+
+impl std::error::Error for ReconnectError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		#[allow(clippy::match_same_arms)]
+		match self {
+			ReconnectError::AddMatch(err) => Some(err),
+			ReconnectError::Connect(err) => Some(err),
+			ReconnectError::Hello(err) => Some(err),
+		}
+	}
+}
+
+/// An error from calling a method using a [`Client`].
+#[derive(Debug)]
+pub enum MethodCallError {
+	/// The method call of the send half of a split client was waiting for its response when the receive half was dropped
+	/// or its connection broke, so the response can't be received.
+	Disconnected,
+
+	Error(String, Option<crate::proto::Variant<'static>>),
+	InvalidArgument { field: &'static str, reason: crate::proto::validate::InvalidName },
+	PropertyTypeMismatch { interface: String, name: String, err: crate::proto::VariantDeserializeError },
+	RecvResponse(crate::conn::RecvError),
+	SendRequest(crate::conn::SendError),
+	SignatureMismatch { expected: crate::proto::Signature, actual: crate::proto::Signature, member: String },
+	UnexpectedResponse(Option<crate::proto::VariantDeserializeError>),
+}
+
+impl std::fmt::Display for MethodCallError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			MethodCallError::Disconnected => f.write_str("the connection was closed before the response was received"),
+			MethodCallError::Error(error_name, body) => write!(f, "method call failed with an error: {error_name} {body:?}"),
+			MethodCallError::InvalidArgument { field, reason: _ } => write!(f, "{field} is not a valid name"),
+			MethodCallError::PropertyTypeMismatch { interface, name, err: _ } =>
+				write!(f, "property {name} of interface {interface} does not have the expected type"),
+			MethodCallError::RecvResponse(_) => f.write_str("could not receive response"),
+			MethodCallError::SendRequest(_) => f.write_str("could not send request"),
+			MethodCallError::SignatureMismatch { expected, actual, member } =>
+				write!(f, r#"response of {member} has signature "{actual}" but expected "{expected}""#),
+			MethodCallError::UnexpectedResponse(Some(_)) => f.write_str("could not deserialize response body"),
+			MethodCallError::UnexpectedResponse(None) => f.write_str("could not deserialize response body: response has empty body"),
+		}
+	}
+}
+
+impl std::error::Error for MethodCallError {
 	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
 		#[allow(clippy::match_same_arms)]
 		match self {
+			MethodCallError::Disconnected => None,
 			MethodCallError::Error(_, _) => None,
+			MethodCallError::InvalidArgument { field: _, reason } => Some(reason),
+			MethodCallError::PropertyTypeMismatch { interface: _, name: _, err } => Some(err),
 			MethodCallError::RecvResponse(err) => Some(err),
 			MethodCallError::SendRequest(err) => Some(err),
+			MethodCallError::SignatureMismatch { expected: _, actual: _, member: _ } => None,
 			MethodCallError::UnexpectedResponse(Some(err)) => Some(err),
 			MethodCallError::UnexpectedResponse(None) => None,
 		}
 	}
 }
 
+/// Whether a response body with the `actual` signature can be deserialized into a type with the `expected` signature.
+fn signature_matches(expected: &crate::proto::Signature, actual: &crate::proto::Signature) -> bool {
+	#[allow(clippy::match_same_arms)]
+	match (expected, actual) {
+		(crate::proto::Signature::Array { element: expected }, crate::proto::Signature::Array { element: actual }) =>
+			signature_matches(expected, actual),
+
+		(
+			crate::proto::Signature::DictEntry { key: expected_key, value: expected_value },
+			crate::proto::Signature::DictEntry { key: actual_key, value: actual_value },
+		) =>
+			signature_matches(expected_key, actual_key) && signature_matches(expected_value, actual_value),
+
+		// Structs and tuples are both deserialized as sequences, so a struct type can be used for the response of a method with multiple out args.
+		(
+			crate::proto::Signature::Struct { fields: expected } | crate::proto::Signature::Tuple { elements: expected },
+			crate::proto::Signature::Struct { fields: actual } | crate::proto::Signature::Tuple { elements: actual },
+		) =>
+			expected.len() == actual.len() &&
+			expected.iter().zip(actual).all(|(expected, actual)| signature_matches(expected, actual)),
+
+		// Object paths and signatures are deserialized as strings.
+		(crate::proto::Signature::String, crate::proto::Signature::ObjectPath | crate::proto::Signature::Signature) => true,
+
+		// Unix fds are deserialized as their u32 index.
+		(crate::proto::Signature::U32, crate::proto::Signature::UnixFd) => true,
+
+		(expected, actual) => expected == actual,
+	}
+}
+
 use crate as dbus_pure;
 
 #[dbus_pure_macros::interface("org.freedesktop.DBus")]
@@ -234,3 +2165,1164 @@ trait OrgFreeDesktopDbusInterface {
 
 #[dbus_pure_macros::object(OrgFreeDesktopDbusInterface)]
 struct OrgFreeDesktopDbusObject;
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_signature_matches() {
+		use crate::proto::Signature;
+
+		for (expected, actual, matches) in [
+			(Signature::String, Signature::String, true),
+			(Signature::String, Signature::ObjectPath, true),
+			(Signature::String, Signature::Signature, true),
+			(Signature::ObjectPath, Signature::String, false),
+			(Signature::String, Signature::U32, false),
+			(Signature::U32, Signature::UnixFd, true),
+			(
+				Signature::Array { element: Box::new(Signature::String) },
+				Signature::Array { element: Box::new(Signature::ObjectPath) },
+				true,
+			),
+			(
+				Signature::Array { element: Box::new(Signature::String) },
+				Signature::Array { element: Box::new(Signature::U8) },
+				false,
+			),
+			(
+				Signature::Struct { fields: vec![Signature::String, Signature::U32] },
+				Signature::Tuple { elements: vec![Signature::String, Signature::U32] },
+				true,
+			),
+			(
+				Signature::Struct { fields: vec![Signature::String, Signature::U32] },
+				Signature::Tuple { elements: vec![Signature::String] },
+				false,
+			),
+		] {
+			assert_eq!(super::signature_matches(&expected, &actual), matches, "{expected} {actual}");
+		}
+	}
+
+	#[test]
+	fn test_method_call_invalid_argument() {
+		let mut client = crate::fake_bus::client(|_, _| panic!("request should not have been sent"));
+
+		let err = client.method_call(
+			"org.freedesktop.DBus",
+			crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+			"org.freedesktop.DBus",
+			"",
+			None,
+		).unwrap_err();
+		assert!(matches!(
+			err,
+			super::MethodCallError::InvalidArgument { field: "member", reason: crate::proto::validate::InvalidName::Empty },
+		), "{err:?}");
+
+		let destination = format!("org.{}", "a".repeat(252));
+		let err = client.method_call(
+			&destination,
+			crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+			"org.freedesktop.DBus",
+			"GetId",
+			None,
+		).unwrap_err();
+		assert!(matches!(
+			err,
+			super::MethodCallError::InvalidArgument { field: "destination", reason: crate::proto::validate::InvalidName::TooLong { len: 256 } },
+		), "{err:?}");
+
+		assert_eq!(client.set_name("not a name".to_owned()).as_deref(), Some(crate::fake_bus::UNIQUE_NAME));
+		let err = client.method_call(
+			"org.freedesktop.DBus",
+			crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+			"org.freedesktop.DBus",
+			"GetId",
+			None,
+		).unwrap_err();
+		assert!(matches!(
+			err,
+			super::MethodCallError::SendRequest(crate::SendError::InvalidSender(crate::proto::validate::InvalidName::InvalidCharacter { character: ' ', offset: 3 })),
+		), "{err:?}");
+	}
+
+	#[test]
+	fn test_unique_name() {
+		let mut client = crate::fake_bus::client(|_, _| unreachable!());
+		assert_eq!(client.unique_name(), Some(crate::fake_bus::UNIQUE_NAME));
+
+		// Overriding the name doesn't change the unique name.
+		assert_eq!(client.set_name("org.example".to_owned()).as_deref(), Some(crate::fake_bus::UNIQUE_NAME));
+		assert_eq!(client.set_name(":1.2".to_owned()).as_deref(), Some("org.example"));
+		assert_eq!(client.unique_name(), Some(crate::fake_bus::UNIQUE_NAME));
+	}
+
+	#[test]
+	fn test_hello_not_unique_name() {
+		let name = format!("dbus-pure-test-hello-{}", std::process::id());
+		let listener = crate::Listener::bind_abstract(name.as_bytes()).unwrap();
+
+		// A bus that responds to `Hello` with a well-known name.
+		let server = std::thread::spawn(move || {
+			let mut server = super::Client::new_peer(listener.accept().unwrap());
+			let (request, _) = server.recv().unwrap();
+			assert_eq!(request.member(), Some("Hello"));
+			let (header, body) = crate::fake_bus::method_return(&request, Some(crate::proto::Variant::String("org.example".into())));
+			let _ = server.send(&header, body.as_ref()).unwrap();
+		});
+
+		let stream = std::os::unix::net::UnixStream::connect_addr(&{
+			use std::os::linux::net::SocketAddrExt;
+			std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes()).unwrap()
+		}).unwrap();
+		let connection = crate::Connection::from_unix_stream(stream, crate::SaslAuthType::Uid).unwrap();
+		let err = super::Client::new(connection).unwrap_err();
+		assert!(matches!(err, super::CreateClientError::Hello(super::MethodCallError::UnexpectedResponse(Some(_)))), "{err:?}");
+
+		server.join().unwrap();
+	}
+
+	#[test]
+	fn test_method_call_signature_mismatch() {
+		let mut client = crate::fake_bus::client(|request, _| vec![
+			crate::fake_bus::method_return(request, Some(crate::proto::Variant::U32(5))),
+		]);
+
+		let err = client.method_call_with_signature(
+			"org.freedesktop.DBus",
+			crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+			"org.freedesktop.DBus",
+			"GetId",
+			None,
+			&crate::proto::Signature::String,
+		).unwrap_err();
+		assert!(matches!(
+			err,
+			super::MethodCallError::SignatureMismatch {
+				expected: crate::proto::Signature::String,
+				actual: crate::proto::Signature::U32,
+				ref member,
+			} if member == "GetId",
+		), "{err:?}");
+
+		let body = client.method_call_with_signature(
+			"org.freedesktop.DBus",
+			crate::proto::ObjectPath("/org/freedesktop/DBus".into()),
+			"org.freedesktop.DBus",
+			"GetId",
+			None,
+			&crate::proto::Signature::U32,
+		).unwrap();
+		assert!(matches!(body, Some(crate::proto::Variant::U32(5))));
+	}
+
+	#[test]
+	fn test_interface_return_types() {
+		use crate as dbus_pure;
+		use crate::proto::Variant;
+
+		#[derive(Debug, PartialEq)]
+		struct Number(u32);
+		impl<'de> serde::Deserialize<'de> for Number {
+			fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: serde::Deserializer<'de> {
+				u32::deserialize(deserializer).map(Number)
+			}
+		}
+
+		type Names = Vec<String>;
+
+		#[dbus_pure_macros::interface("org.example")]
+		trait Interface {
+			#[name = "Names"]
+			fn names() -> Names;
+
+			#[name = "Number"]
+			fn number() -> Number;
+
+			#[name = "Variant"]
+			fn variant() -> Variant<'static>;
+
+			#[name = "Variant"]
+			fn variant_as_names() -> Names;
+		}
+
+		#[dbus_pure_macros::object(Interface)]
+		struct ExampleObject;
+
+		let mut client = crate::fake_bus::client(|request, _| {
+			let body = match request.member().unwrap() {
+				"Names" => crate::proto::Variant::ArrayString(vec!["a".into(), "b".into()].into()),
+				"Number" | "Variant" => crate::proto::Variant::U32(5),
+				member => panic!("unexpected method call {member}"),
+			};
+			vec![crate::fake_bus::method_return(request, Some(body))]
+		});
+		let object = ExampleObject { name: "org.example".into(), path: crate::proto::ObjectPath("/".into()) };
+
+		assert_eq!(object.names(&mut client).unwrap(), ["a", "b"]);
+		assert_eq!(object.number(&mut client).unwrap(), Number(5));
+		assert_eq!(object.variant(&mut client).unwrap(), crate::proto::Variant::U32(5));
+
+		let err = object.variant_as_names(&mut client).unwrap_err();
+		assert!(matches!(err, super::MethodCallError::SignatureMismatch { actual: crate::proto::Signature::U32, .. }), "{err:?}");
+
+		// Return types without an expected signature are described as variants.
+		let signatures: Vec<_> =
+			<ExampleObject<'_> as Interface>::DESCRIPTOR.methods.iter()
+			.map(|method| (method.args[0].signature)().to_string())
+			.collect();
+		assert_eq!(signatures, ["as", "v", "v", "as"]);
+	}
+
+	#[test]
+	fn test_method_call_serialized() {
+		// Echo the parameters back.
+		let mut client = crate::fake_bus::client(|request, body| vec![
+			crate::fake_bus::method_return(request, body.cloned()),
+		]);
+
+		let body = client.method_call_serialized(
+			"org.example",
+			crate::proto::ObjectPath("/".into()),
+			"org.example",
+			"Echo",
+			&("foo", 5_u8),
+			&"sy".parse().unwrap(),
+		).unwrap();
+		assert_eq!(body.unwrap(), crate::proto::Variant::tuple([crate::proto::Variant::String("foo".into()), crate::proto::Variant::U8(5)]));
+
+		let err = client.method_call_serialized(
+			"org.example",
+			crate::proto::ObjectPath("/".into()),
+			"org.example",
+			"Echo",
+			&("foo", "bar"),
+			&"sy".parse().unwrap(),
+		).unwrap_err();
+		let super::MethodCallError::SendRequest(crate::SendError::Serialize(err)) = err else { panic!("{err:?}") };
+		assert_eq!(err.to_string(), r#"value is a string but the signature is "y""#);
+
+		// Nothing was written for the failed call, so the connection is still usable.
+		let body = client.method_call_serialized(
+			"org.example",
+			crate::proto::ObjectPath("/".into()),
+			"org.example",
+			"Echo",
+			&[1_u32, 2],
+			&"au".parse().unwrap(),
+		).unwrap();
+		assert_eq!(body.unwrap(), crate::proto::Variant::ArrayU32((&[1, 2][..]).into()));
+	}
+
+	#[test]
+	fn test_method_call_with_fds() {
+		use std::io::{Read, Write};
+
+		// Write to the pipe that the request passes, and return the read end of a new pipe with something already written to it.
+		let mut client = crate::fake_bus::client_with_fds(|request, body, mut fds| {
+			let mut fds: Vec<_> = fds.drain(..).map(Some).collect();
+			let mut request_pipe_writer = std::fs::File::from(body.unwrap().take_fd(&mut fds).unwrap());
+			request_pipe_writer.write_all(b"ping").unwrap();
+
+			let (response_pipe_reader, mut response_pipe_writer) = std::io::pipe().unwrap();
+			response_pipe_writer.write_all(b"pong").unwrap();
+
+			vec![(
+				crate::fake_bus::method_return(request, Some(crate::proto::Variant::UnixFd(crate::proto::UnixFd(0)))),
+				vec![response_pipe_reader.into()],
+			)]
+		});
+
+		let (mut pipe_reader, pipe_writer) = std::io::pipe().unwrap();
+
+		let (body, fds) = client.method_call_with_fds(
+			"org.example",
+			crate::proto::ObjectPath("/".into()),
+			"org.example",
+			"Pipe",
+			Some(&crate::proto::Variant::UnixFd(crate::proto::UnixFd(0))),
+			vec![pipe_writer.into()],
+		).unwrap();
+
+		let mut buf = [0_u8; 4];
+		pipe_reader.read_exact(&mut buf).unwrap();
+		assert_eq!(&buf, b"ping");
+
+		let mut fds: Vec<_> = fds.into_iter().map(Some).collect();
+		let mut response_pipe_reader = std::fs::File::from(body.unwrap().take_fd(&mut fds).unwrap());
+		response_pipe_reader.read_exact(&mut buf).unwrap();
+		assert_eq!(&buf, b"pong");
+	}
+
+	#[test]
+	fn test_interface_fd_signature_mismatch() {
+		use crate as dbus_pure;
+
+		#[dbus_pure_macros::interface("org.example")]
+		trait Interface {
+			#[name = "Open"]
+			fn open() -> std::os::fd::OwnedFd;
+
+			#[name = "Pass"]
+			fn pass(fd: std::os::fd::BorrowedFd<'_>) -> u32;
+		}
+
+		#[dbus_pure_macros::object(Interface)]
+		struct ExampleObject;
+
+		// Neither method gets a response of the type it returns.
+		let mut client = crate::fake_bus::client_with_fds(|request, _, _| {
+			let body = match request.member().unwrap() {
+				"Open" => crate::proto::Variant::U32(5),
+				"Pass" => crate::proto::Variant::String("5".into()),
+				member => panic!("unexpected method call {member}"),
+			};
+			vec![(crate::fake_bus::method_return(request, Some(body)), vec![])]
+		});
+		let object = ExampleObject { name: "org.example".into(), path: crate::proto::ObjectPath("/".into()) };
+
+		let err = object.open(&mut client).unwrap_err();
+		assert!(matches!(
+			err,
+			super::MethodCallError::SignatureMismatch { expected: crate::proto::Signature::UnixFd, actual: crate::proto::Signature::U32, .. },
+		), "{err:?}");
+
+		let (_pipe_reader, pipe_writer) = std::io::pipe().unwrap();
+		let err = object.pass(&mut client, std::os::fd::AsFd::as_fd(&pipe_writer)).unwrap_err();
+		assert!(matches!(
+			err,
+			super::MethodCallError::SignatureMismatch { expected: crate::proto::Signature::U32, actual: crate::proto::Signature::String, .. },
+		), "{err:?}");
+	}
+
+	#[test]
+	fn test_timeout() {
+		let mut client = crate::fake_bus::client(|request, body| {
+			match request.member() {
+				Some("Echo") => vec![crate::fake_bus::method_return(request, body.cloned())],
+
+				Some("Late") => {
+					std::thread::sleep(std::time::Duration::from_millis(300));
+					vec![crate::fake_bus::method_return(request, body.cloned())]
+				},
+
+				_ => vec![],
+			}
+		});
+
+		let start = std::time::Instant::now();
+		let err = client.recv_timeout(std::time::Duration::from_millis(50)).unwrap_err();
+		assert!(matches!(err, crate::conn::RecvError::TimedOut), "{err:?}");
+		assert!(start.elapsed() >= std::time::Duration::from_millis(50));
+
+		client.set_method_call_timeout(Some(std::time::Duration::from_millis(100)));
+
+		let err = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Silent", None).unwrap_err();
+		assert!(matches!(err, super::MethodCallError::RecvResponse(crate::conn::RecvError::TimedOut)), "{err:?}");
+
+		let body = crate::proto::Variant::String("late".into());
+		let err = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Late", Some(&body)).unwrap_err();
+		assert!(matches!(err, super::MethodCallError::RecvResponse(crate::conn::RecvError::TimedOut)), "{err:?}");
+
+		// The client is still usable after the timeouts.
+		client.set_method_call_timeout(None);
+		let body = crate::proto::Variant::String("hello".into());
+		let response = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Echo", Some(&body)).unwrap();
+		assert_eq!(response, Some(body));
+
+		// The response that arrived after its method call timed out is received like any other message.
+		let (header, body) = client.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+		assert!(matches!(header.r#type, crate::proto::MessageType::MethodReturn { .. }));
+		assert_eq!(body, Some(crate::proto::Variant::String("late".into())));
+	}
+
+	#[test]
+	fn test_try_recv() {
+		let mut client = crate::fake_bus::client(|request, _| {
+			let signal = |member: &'static str| (
+				crate::proto::MessageHeader {
+					r#type: crate::proto::MessageType::Signal {
+						interface: "org.example".into(),
+						member: member.into(),
+						path: crate::proto::ObjectPath("/".into()),
+					},
+					flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+					body_len: 0,
+					serial: 0,
+					fields: vec![].into(),
+				},
+				None,
+			);
+			vec![signal("First"), signal("Second"), crate::fake_bus::method_return(request, None)]
+		});
+		client.set_nonblocking(true).unwrap();
+
+		assert!(client.try_recv().unwrap().is_none());
+		assert!(client.try_recv_matching(|_, _| true).unwrap().is_none());
+
+		let header = crate::proto::MessageHeader {
+			r#type: crate::proto::MessageType::MethodCall {
+				member: "Trigger".into(),
+				path: crate::proto::ObjectPath("/".into()),
+			},
+			flags: crate::proto::message_flags::NONE,
+			body_len: 0,
+			serial: 0,
+			fields: vec![
+				crate::proto::MessageHeaderField::Destination("org.example".into()),
+				crate::proto::MessageHeaderField::Interface("org.example".into()),
+			].into(),
+		};
+		let serial = client.send(&header, None).unwrap();
+		client.flush().unwrap();
+
+		// Poll for the response, which comes after both signals.
+		let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+		let response = loop {
+			let response = client.try_recv_matching(|header, _| matches!(header.r#type, crate::proto::MessageType::MethodReturn { reply_serial } if reply_serial == serial)).unwrap();
+			if let Some(response) = response {
+				break response;
+			}
+			assert!(std::time::Instant::now() < deadline, "timed out waiting for response");
+			std::thread::sleep(std::time::Duration::from_millis(1));
+		};
+		assert!(response.1.is_none());
+
+		// The signals that were received while polling are kept in order.
+		let (header, _) = client.try_recv().unwrap().unwrap();
+		assert_eq!(header.member(), Some("First"));
+		let (header, _) = client.try_recv_matching(|_, _| true).unwrap().unwrap();
+		assert_eq!(header.member(), Some("Second"));
+		assert!(client.try_recv().unwrap().is_none());
+	}
+
+	#[test]
+	fn test_stats() {
+		let mut client = crate::fake_bus::client(|request, _| vec![
+			(
+				crate::proto::MessageHeader {
+					r#type: crate::proto::MessageType::Signal {
+						interface: "org.example".into(),
+						member: "Called".into(),
+						path: crate::proto::ObjectPath("/".into()),
+					},
+					flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+					body_len: 0,
+					serial: 0,
+					fields: vec![].into(),
+				},
+				None,
+			),
+			crate::fake_bus::method_return(request, None),
+		]);
+
+		// `Hello` has already been called.
+		let stats = client.stats();
+		assert_eq!(stats.connection.sent.method_calls, 1);
+		assert_eq!(stats.connection.received.method_returns, 1);
+		assert_eq!(stats.queued_messages, 0);
+
+		let response = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Call", None).unwrap();
+		assert!(response.is_none());
+
+		// The signal was received while waiting for the response, so it's queued.
+		let stats = client.stats();
+		assert_eq!(stats.connection.sent, crate::conn::MessageCounts { method_calls: 2, ..Default::default() });
+		assert_eq!(stats.connection.received, crate::conn::MessageCounts { method_returns: 2, signals: 1, ..Default::default() });
+		assert!(stats.connection.bytes_sent > 0);
+		assert!(stats.connection.bytes_received > 0);
+		assert_eq!(stats.queued_messages, 1);
+
+		client.reset_stats();
+		let stats = client.stats();
+		assert_eq!(stats.connection.sent.total(), 0);
+		assert_eq!(stats.connection.received.total(), 0);
+		assert_eq!(stats.connection.bytes_sent, 0);
+		assert_eq!(stats.connection.bytes_received, 0);
+		assert_eq!(stats.queued_messages, 1);
+
+		let (header, _) = client.recv().unwrap();
+		assert_eq!(header.member(), Some("Called"));
+		assert_eq!(client.stats().queued_messages, 0);
+	}
+
+	#[test]
+	fn test_reply_in_kind() {
+		let mut connection = crate::fake_bus::connection_with_endianness(crate::proto::Endianness::Big, |request, _, request_endianness| {
+			match &request.r#type {
+				crate::proto::MessageType::MethodCall { member, .. } if member == "Trigger" => {
+					let ping = crate::proto::MessageHeader {
+						r#type: crate::proto::MessageType::MethodCall { member: "Ping".into(), path: crate::proto::ObjectPath("/".into()) },
+						flags: crate::proto::message_flags::NONE,
+						body_len: 0,
+						serial: 0,
+						fields: vec![crate::proto::MessageHeaderField::Destination(crate::fake_bus::UNIQUE_NAME.into())].into(),
+					};
+					vec![crate::fake_bus::method_return(request, None), (ping, None)]
+				},
+
+				// Tell the client what endianness its reply to `Ping` used.
+				crate::proto::MessageType::MethodReturn { .. } => {
+					let echo = crate::proto::MessageHeader {
+						r#type: crate::proto::MessageType::Signal {
+							interface: "org.example".into(),
+							member: "Echo".into(),
+							path: crate::proto::ObjectPath("/".into()),
+						},
+						flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+						body_len: 0,
+						serial: 0,
+						fields: vec![].into(),
+					};
+					vec![(echo, Some(crate::proto::Variant::Bool(request_endianness == crate::proto::Endianness::Big)))]
+				},
+
+				_ => vec![],
+			}
+		});
+		connection.set_write_endianness(crate::proto::Endianness::Little);
+		let mut client = super::Client::new(connection).unwrap();
+
+		for reply_in_kind in [true, false] {
+			client.set_reply_in_kind(reply_in_kind);
+
+			let _ = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Trigger", None).unwrap();
+
+			let (ping, _) = client.recv().unwrap();
+			assert_eq!(ping.member(), Some("Ping"));
+
+			let reply = crate::proto::MessageHeader {
+				r#type: crate::proto::MessageType::MethodReturn { reply_serial: ping.serial },
+				flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+				body_len: 0,
+				serial: 0,
+				fields: vec![].into(),
+			};
+			let _ = client.send(&reply, None).unwrap();
+
+			let (echo, body) = client.recv().unwrap();
+			assert_eq!(echo.member(), Some("Echo"));
+			assert_eq!(body, Some(crate::proto::Variant::Bool(reply_in_kind)));
+		}
+	}
+
+	#[test]
+	fn test_split() {
+		let signal = |member: &'static str| (
+			crate::proto::MessageHeader {
+				r#type: crate::proto::MessageType::Signal {
+					interface: "org.example".into(),
+					member: member.into(),
+					path: crate::proto::ObjectPath("/".into()),
+				},
+				flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+				body_len: 0,
+				serial: 0,
+				fields: vec![].into(),
+			},
+			None,
+		);
+
+		// Echo the parameters back, after a signal. `Stop` only emits a signal.
+		let client = crate::fake_bus::client(move |request, body| match request.member() {
+			Some("Echo") => vec![signal("Echoed"), crate::fake_bus::method_return(request, body.cloned())],
+			Some("Stop") => vec![signal("Stopped")],
+			_ => vec![],
+		});
+		let (mut send_half, mut recv_half) = client.split();
+
+		// The receive half gets the signals, and hands the responses to the method calls of the send half.
+		let receiver = std::thread::spawn(move || {
+			let mut num_echoed = 0;
+			loop {
+				let (header, _) = recv_half.recv().unwrap();
+				match header.member() {
+					Some("Echoed") => num_echoed += 1,
+					Some("Stopped") => break,
+					_ => panic!("unexpected message {header:?}"),
+				}
+			}
+			(recv_half, num_echoed)
+		});
+
+		for i in 0..100_u32 {
+			let body = crate::proto::Variant::U32(i);
+			let response = send_half.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Echo", Some(&body)).unwrap();
+			assert_eq!(response, Some(body));
+		}
+
+		let stop =
+			crate::proto::MethodCallBuilder::new(crate::proto::ObjectPath("/".into()), "Stop")
+			.destination("org.example")
+			.interface("org.example")
+			.build()
+			.unwrap();
+		let _ = send_half.send_message(stop).unwrap();
+
+		let (recv_half, num_echoed) = receiver.join().unwrap();
+		assert_eq!(num_echoed, 100);
+
+		// Method calls fail instead of waiting forever once the receive half is gone.
+		drop(recv_half);
+		let err = send_half.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Echo", None).unwrap_err();
+		assert!(matches!(err, super::MethodCallError::Disconnected), "{err:?}");
+	}
+
+	#[test]
+	fn test_method_call_with_flags() {
+		let flags = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+		let mut client = crate::fake_bus::client({
+			let flags = flags.clone();
+			move |header, _| {
+				flags.lock().unwrap().push((header.member().unwrap().to_owned(), header.flags));
+				if header.flags.contains(crate::proto::message_flags::NO_REPLY_EXPECTED) {
+					vec![]
+				}
+				else {
+					vec![crate::fake_bus::method_return(header, Some(crate::proto::Variant::U32(1)))]
+				}
+			}
+		});
+
+		let mut call = |member, flags| client.method_call_with_flags("org.example", crate::proto::ObjectPath("/".into()), "org.example", member, None, flags);
+
+		// The call returns without waiting for a response, which the fake bus would never send.
+		assert_eq!(call("FireAndForget", crate::proto::message_flags::NO_REPLY_EXPECTED | crate::proto::message_flags::NO_AUTO_START).unwrap(), None);
+		assert_eq!(call("NoAutoStart", crate::proto::message_flags::NO_AUTO_START).unwrap(), Some(crate::proto::Variant::U32(1)));
+		assert_eq!(
+			call("Interactive", crate::proto::message_flags::ALLOW_INTERACTIVE_AUTHORIZATION).unwrap(),
+			Some(crate::proto::Variant::U32(1)),
+		);
+
+		let flags: Vec<_> = flags.lock().unwrap().iter().map(|(member, flags)| (member.clone(), flags.bits())).collect();
+		assert_eq!(flags, [
+			("FireAndForget".to_owned(), 0x03),
+			("NoAutoStart".to_owned(), 0x02),
+			("Interactive".to_owned(), 0x04),
+		]);
+
+		// The send half of a split client does not wait for a response either, so it doesn't need the receive half to receive one.
+		let (mut send_half, recv_half) = client.split();
+		drop(recv_half);
+		let response = send_half.method_call_with_flags(
+			"org.example",
+			crate::proto::ObjectPath("/".into()),
+			"org.example",
+			"FireAndForget",
+			None,
+			crate::proto::message_flags::NO_REPLY_EXPECTED,
+		).unwrap();
+		assert_eq!(response, None);
+	}
+
+	#[test]
+	fn test_call() {
+		#[derive(Debug, PartialEq)]
+		struct Number(u32);
+		impl<'de> serde::Deserialize<'de> for Number {
+			fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: serde::Deserializer<'de> {
+				u32::deserialize(deserializer).map(Number)
+			}
+		}
+
+		let mut client = crate::fake_bus::client(|header, body| {
+			let response = match (header.member().unwrap(), body) {
+				("Empty", None) => None,
+				("Echo", Some(body)) => Some(body.clone()),
+				("Add", Some(crate::proto::Variant::Tuple { elements })) => match &elements[..] {
+					[crate::proto::Variant::U32(a), crate::proto::Variant::U32(b)] => Some(crate::proto::Variant::U32(a + b)),
+					_ => panic!("unexpected parameters {elements:?}"),
+				},
+				(member, body) => panic!("unexpected method call {member} {body:?}"),
+			};
+			vec![crate::fake_bus::method_return(header, response)]
+		});
+
+		let path = || crate::proto::ObjectPath("/".into());
+
+		// `()` is no body in either direction.
+		let () = client.call("org.example", path(), "org.example", "Empty", &()).unwrap();
+		let () = client.call0("org.example", path(), "org.example", "Empty").unwrap();
+		let response: Option<u32> = client.call0("org.example", path(), "org.example", "Empty").unwrap();
+		assert_eq!(response, None);
+
+		let response: String = client.call("org.example", path(), "org.example", "Echo", "foo").unwrap();
+		assert_eq!(response, "foo");
+		let response: Option<u32> = client.call("org.example", path(), "org.example", "Echo", &5_u32).unwrap();
+		assert_eq!(response, Some(5));
+		let response: u32 = client.call("org.example", path(), "org.example", "Add", &(2_u32, 3_u32)).unwrap();
+		assert_eq!(response, 5);
+
+		let err = client.call0::<u32>("org.example", path(), "org.example", "Empty").unwrap_err();
+		assert!(matches!(err, super::MethodCallError::UnexpectedResponse(None)), "{err:?}");
+		let err = client.call::<_, u32>("org.example", path(), "org.example", "Echo", "foo").unwrap_err();
+		assert!(matches!(err, super::MethodCallError::UnexpectedResponse(Some(_))), "{err:?}");
+
+		// A type that only implements `Deserialize` works too.
+		let response: Number = client.call("org.example", path(), "org.example", "Echo", &5_u32).unwrap();
+		assert_eq!(response, Number(5));
+		let err = client.call::<_, Number>("org.example", path(), "org.example", "Echo", "foo").unwrap_err();
+		assert!(matches!(err, super::MethodCallError::UnexpectedResponse(Some(_))), "{err:?}");
+
+		// The checked variants check the signature of the response before deserializing it.
+		let response: u32 = client.call_checked("org.example", path(), "org.example", "Add", &(2_u32, 3_u32)).unwrap();
+		assert_eq!(response, 5);
+		let response: Option<u32> = client.call0_checked("org.example", path(), "org.example", "Empty").unwrap();
+		assert_eq!(response, None);
+		let err = client.call_checked::<_, u32>("org.example", path(), "org.example", "Echo", "foo").unwrap_err();
+		assert!(matches!(
+			err,
+			super::MethodCallError::SignatureMismatch { expected: crate::proto::Signature::U32, actual: crate::proto::Signature::String, .. },
+		), "{err:?}");
+		let err = client.call_checked::<_, ()>("org.example", path(), "org.example", "Echo", &5_u32).unwrap_err();
+		assert!(matches!(err, super::MethodCallError::SignatureMismatch { actual: crate::proto::Signature::U32, .. }), "{err:?}");
+	}
+
+	#[test]
+	fn test_send_with_reply() {
+		// `Deferred` calls are answered in reverse order when a `Flush` call is received, followed by a signal and then the answer to `Flush`.
+		let mut deferred = vec![];
+		let mut client = crate::fake_bus::client(move |header, body| match header.member().unwrap() {
+			"Deferred" => {
+				deferred.push(crate::fake_bus::method_return(header, body.cloned()));
+				vec![]
+			},
+
+			"Flush" => {
+				let mut messages: Vec<_> = deferred.drain(..).rev().collect();
+				messages.push((
+					crate::proto::MessageHeader {
+						r#type: crate::proto::MessageType::Signal {
+							interface: "org.example".into(),
+							member: "Flushed".into(),
+							path: crate::proto::ObjectPath("/".into()),
+						},
+						flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+						body_len: 0,
+						serial: 0,
+						fields: vec![].into(),
+					},
+					None,
+				));
+				messages.push(crate::fake_bus::method_return(header, body.cloned()));
+				messages
+			},
+
+			member => panic!("unexpected method call {member}"),
+		});
+
+		let header = |member| super::method_call_header("org.example", crate::proto::ObjectPath("/".into()), "org.example", member).unwrap();
+
+		let first = client.send_with_reply(&header("Deferred"), Some(&crate::proto::Variant::U32(1))).unwrap();
+		let second = client.send_with_reply(&header("Deferred"), Some(&crate::proto::Variant::U32(2))).unwrap();
+		let third = client.send_with_reply(&header("Flush"), Some(&crate::proto::Variant::U32(3))).unwrap();
+		assert!(first.serial() < second.serial() && second.serial() < third.serial());
+
+		// The responses arrive in the order 2, 1, 3, and each one is handed to its own pending reply regardless.
+		let (_, body) = first.wait(&mut client).unwrap();
+		assert_eq!(body, Some(crate::proto::Variant::U32(1)));
+		let (_, body) = third.wait(&mut client).unwrap();
+		assert_eq!(body, Some(crate::proto::Variant::U32(3)));
+		let (_, body) = second.wait(&mut client).unwrap();
+		assert_eq!(body, Some(crate::proto::Variant::U32(2)));
+
+		// Only the signal is queued.
+		assert_eq!(client.stats().queued_messages, 1);
+		let (header_, _) = client.recv().unwrap();
+		assert_eq!(header_.member(), Some("Flushed"));
+
+		// The response of a dropped pending reply is discarded.
+		drop(client.send_with_reply(&header("Deferred"), Some(&crate::proto::Variant::U32(4))).unwrap());
+		let response = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Flush", None).unwrap();
+		assert_eq!(response, None);
+		assert_eq!(client.stats().queued_messages, 1);
+		let (header_, _) = client.recv().unwrap();
+		assert_eq!(header_.member(), Some("Flushed"));
+
+		// Polling does not block in non-blocking mode.
+		let fifth = client.send_with_reply(&header("Deferred"), Some(&crate::proto::Variant::U32(5))).unwrap();
+		client.set_nonblocking(true).unwrap();
+		assert_eq!(client.poll_reply(&fifth).unwrap(), None);
+		let sixth = client.send_with_reply(&header("Flush"), None).unwrap();
+		let (_, body) = loop {
+			if let Some(response) = client.poll_reply(&fifth).unwrap() {
+				break response;
+			}
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		};
+		assert_eq!(body, Some(crate::proto::Variant::U32(5)));
+		// The response to the sixth call is sent after the fifth one, so it may not have been read yet either.
+		let (_, body) = loop {
+			if let Some(response) = client.poll_reply(&sixth).unwrap() {
+				break response;
+			}
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		};
+		assert_eq!(body, None);
+
+		let err = client.poll_reply(&fifth).unwrap_err();
+		assert!(matches!(err, super::MethodCallError::Disconnected), "{err:?}");
+	}
+
+	#[test]
+	fn test_peer() {
+		let mut client = crate::fake_bus::client(|header, _| {
+			let body = match (header.destination().unwrap(), header.member().unwrap()) {
+				("org.example.Good", "Ping") => None,
+				("org.example.Good", "GetMachineId") => Some(crate::proto::Variant::String("0123456789abcdef0123456789abcdef".into())),
+				("org.example.Bad", "Ping") => Some(crate::proto::Variant::U32(1)),
+				("org.example.Bad", "GetMachineId") => Some(crate::proto::Variant::String("not a machine ID".into())),
+				(destination, member) => panic!("unexpected method call {destination} {member}"),
+			};
+			assert_eq!(header.interface(), Some("org.freedesktop.DBus.Peer"));
+			vec![crate::fake_bus::method_return(header, body)]
+		});
+
+		let _ = client.ping("org.example.Good").unwrap();
+		let machine_id = client.get_machine_id("org.example.Good").unwrap();
+		assert_eq!(machine_id, "0123456789abcdef0123456789abcdef");
+
+		let err = client.ping("org.example.Bad").unwrap_err();
+		assert!(matches!(err, super::MethodCallError::UnexpectedResponse(Some(_))), "{err:?}");
+		let err = client.get_machine_id("org.example.Bad").unwrap_err();
+		assert!(matches!(err, super::MethodCallError::UnexpectedResponse(Some(_))), "{err:?}");
+	}
+
+	#[test]
+	fn test_properties() {
+		let mut properties = std::collections::BTreeMap::new();
+		let _ = properties.insert("Volume".to_owned(), crate::proto::Variant::F64(0.5));
+		let _ = properties.insert("Identity".to_owned(), crate::proto::Variant::String("Example".into()));
+
+		let mut client = crate::fake_bus::client(move |header, body| {
+			assert_eq!(header.interface(), Some("org.freedesktop.DBus.Properties"));
+			let body = body.unwrap();
+			let body = match header.member().unwrap() {
+				"Get" => {
+					let (interface, name): (String, String) = serde::Deserialize::deserialize(body.clone()).unwrap();
+					assert_eq!(interface, "org.example");
+					Some(crate::proto::Variant::Variant(Box::new(properties[&name].clone()).into()))
+				},
+
+				"Set" => {
+					let [interface, name, value] = body.as_tuple().unwrap() else { panic!("{body:?}"); };
+					assert_eq!(interface.as_string(), Some("org.example"));
+					let _ = properties.insert(name.as_string().unwrap().to_owned(), value.as_variant().unwrap().clone());
+					None
+				},
+
+				"GetAll" => {
+					assert_eq!(body.as_string(), Some("org.example"));
+					Some(crate::proto::Variant::from_map_iter(
+						crate::proto::Signature::String,
+						crate::proto::Signature::Variant,
+						properties.iter().map(|(name, value)| (
+							crate::proto::Variant::String(name.clone().into()),
+							crate::proto::Variant::Variant(Box::new(value.clone()).into()),
+						)),
+					).unwrap())
+				},
+
+				member => panic!("unexpected method call {member}"),
+			};
+			vec![crate::fake_bus::method_return(header, body)]
+		});
+
+		let path = || crate::proto::ObjectPath("/org/example".into());
+
+		let volume: f64 = client.get_property("org.example", path(), "org.example", "Volume").unwrap();
+		assert!((volume - 0.5).abs() < f64::EPSILON);
+
+		client.set_property("org.example", path(), "org.example", "Volume", &1.0_f64).unwrap();
+		let volume: f64 = client.get_property("org.example", path(), "org.example", "Volume").unwrap();
+		assert!((volume - 1.0).abs() < f64::EPSILON);
+
+		let err = client.get_property::<u32>("org.example", path(), "org.example", "Identity").unwrap_err();
+		let super::MethodCallError::PropertyTypeMismatch { interface, name, err: _ } = &err else { panic!("{err:?}"); };
+		assert_eq!((&**interface, &**name), ("org.example", "Identity"));
+		assert_eq!(err.to_string(), "property Identity of interface org.example does not have the expected type");
+
+		let properties = client.get_all_properties("org.example", path(), "org.example").unwrap();
+		assert_eq!(properties, [
+			("Identity".to_owned(), crate::proto::Variant::String("Example".into())),
+			("Volume".to_owned(), crate::proto::Variant::F64(1.0)),
+		].into_iter().collect());
+	}
+
+	#[test]
+	fn test_answer_peer_pings() {
+		// `Trigger` is answered and followed by a ping from another connection. The answer to the ping is reported with an `Answered` signal.
+		let mut client = crate::fake_bus::client(|header, _| match &header.r#type {
+			crate::proto::MessageType::MethodCall { member, .. } if member == "Trigger" => {
+				let ping = crate::proto::MessageHeader {
+					r#type: crate::proto::MessageType::MethodCall { member: "Ping".into(), path: crate::proto::ObjectPath("/".into()) },
+					flags: crate::proto::message_flags::NONE,
+					body_len: 0,
+					serial: 0,
+					fields: vec![
+						crate::proto::MessageHeaderField::Destination(crate::fake_bus::UNIQUE_NAME.into()),
+						crate::proto::MessageHeaderField::Interface("org.freedesktop.DBus.Peer".into()),
+						crate::proto::MessageHeaderField::Sender(":1.5".into()),
+					].into(),
+				};
+				vec![crate::fake_bus::method_return(header, None), (ping, None)]
+			},
+
+			crate::proto::MessageType::MethodReturn { reply_serial } => {
+				let answered = crate::proto::MessageHeader {
+					r#type: crate::proto::MessageType::Signal {
+						interface: "org.example".into(),
+						member: "Answered".into(),
+						path: crate::proto::ObjectPath("/".into()),
+					},
+					flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+					body_len: 0,
+					serial: 0,
+					fields: vec![].into(),
+				};
+				let destination = header.destination().unwrap().to_owned();
+				vec![(answered, Some(crate::proto::Variant::Tuple { elements: vec![
+					crate::proto::Variant::U32(*reply_serial),
+					crate::proto::Variant::String(destination.into()),
+				].into() }))]
+			},
+
+			r#type => panic!("unexpected message {type:?}"),
+		});
+
+		let is_answered = |header: &crate::proto::MessageHeader<'_>, _: Option<&_>| header.member() == Some("Answered");
+
+		let _ = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Trigger", None).unwrap();
+		let (_, body) = client.recv_matching(is_answered).unwrap();
+		let (_, destination): (u32, String) = serde::Deserialize::deserialize(body.unwrap()).unwrap();
+		assert_eq!(destination, ":1.5");
+		assert_eq!(client.stats().queued_messages, 0);
+
+		// A ping that matches the predicate is returned instead of being answered.
+		let _ = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Trigger", None).unwrap();
+		let (header, _) = client.recv_matching(|header, _| header.member() == Some("Ping")).unwrap();
+		assert_eq!(header.sender(), Some(":1.5"));
+
+		client.set_answer_peer_pings(false);
+		let _ = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Trigger", None).unwrap();
+		let err = client.recv_matching_timeout(std::time::Duration::from_millis(100), is_answered).unwrap_err();
+		assert!(matches!(err, crate::conn::RecvError::TimedOut), "{err:?}");
+		let (header, _) = client.recv().unwrap();
+		assert_eq!(header.member(), Some("Ping"));
+	}
+
+	#[test]
+	fn test_unknown_message_type() {
+		let mut client = crate::fake_bus::client(|header, _| vec![
+			(
+				crate::proto::MessageHeader {
+					r#type: crate::proto::MessageType::Unknown { r#type: 5 },
+					flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+					body_len: 0,
+					serial: 0,
+					fields: vec![].into(),
+				},
+				Some(crate::proto::Variant::U32(1)),
+			),
+			crate::fake_bus::method_return(header, None),
+		]);
+
+		// The message of an unknown type is ignored rather than queued.
+		let response = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Ping", None).unwrap();
+		assert_eq!(response, None);
+		assert_eq!(client.stats().queued_messages, 0);
+		assert_eq!(client.stats().connection.received.unknown, 1);
+
+		let err = client.recv_timeout(std::time::Duration::from_millis(100)).unwrap_err();
+		assert!(matches!(err, crate::conn::RecvError::TimedOut), "{err:?}");
+	}
+
+	#[test]
+	fn test_match_guard() {
+		let calls = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+		let mut client = crate::fake_bus::client({
+			let calls = calls.clone();
+			move |header, body| {
+				let body = match body {
+					Some(crate::proto::Variant::String(body)) => Some(body.clone().into_owned()),
+					_ => None,
+				};
+				calls.lock().unwrap().push((header.member().unwrap().to_owned(), body, header.flags));
+				if header.flags.contains(crate::proto::message_flags::NO_REPLY_EXPECTED) {
+					vec![]
+				}
+				else {
+					vec![crate::fake_bus::method_return(header, None)]
+				}
+			}
+		});
+
+		let rule = crate::MatchRule::new().r#type(crate::MatchMessageType::Signal).arg(0, "it's");
+		let guard = client.add_match(&rule).unwrap();
+		assert_eq!(guard.rule(), r"type='signal',arg0='it'\''s'");
+
+		// The rule is removed when the guard is dropped, without waiting for a response or for another message to be sent.
+		drop(guard);
+		let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+		while calls.lock().unwrap().len() < 2 {
+			assert!(std::time::Instant::now() < deadline, "RemoveMatch was not received");
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+		let call = |client: &mut super::Client| client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Call", None);
+		let _ = call(&mut client).unwrap();
+
+		// A rule that is removed explicitly is not removed again when its guard is dropped.
+		let guard = client.add_match(&rule).unwrap();
+		client.remove_match(guard).unwrap();
+		let _ = call(&mut client).unwrap();
+
+		// A guard that outlives its client does nothing when dropped.
+		let guard = client.add_match(&rule).unwrap();
+		drop(client);
+		drop(guard);
+
+		let rule = Some(r"type='signal',arg0='it'\''s'".to_owned());
+		assert_eq!(std::mem::take(&mut *calls.lock().unwrap()), [
+			("AddMatch".to_owned(), rule.clone(), crate::proto::message_flags::NONE),
+			("RemoveMatch".to_owned(), rule.clone(), crate::proto::message_flags::NO_REPLY_EXPECTED),
+			("Call".to_owned(), None, crate::proto::message_flags::NONE),
+			("AddMatch".to_owned(), rule.clone(), crate::proto::message_flags::NONE),
+			("RemoveMatch".to_owned(), rule.clone(), crate::proto::message_flags::NONE),
+			("Call".to_owned(), None, crate::proto::message_flags::NONE),
+			("AddMatch".to_owned(), rule, crate::proto::message_flags::NONE),
+		]);
+
+		// A client that is connected to a peer doesn't remove the rule, since the peer isn't a message bus.
+		let mut client = super::Client::new_peer(crate::fake_bus::connection({
+			let calls = calls.clone();
+			move |header, _| {
+				calls.lock().unwrap().push((header.member().unwrap().to_owned(), None, header.flags));
+				vec![crate::fake_bus::method_return(header, None)]
+			}
+		}));
+		drop(client.add_match(&crate::MatchRule::new()).unwrap());
+		let _ = call(&mut client).unwrap();
+		assert_eq!(std::mem::take(&mut *calls.lock().unwrap()), [
+			("AddMatch".to_owned(), None, crate::proto::message_flags::NONE),
+			("Call".to_owned(), None, crate::proto::message_flags::NONE),
+		]);
+	}
+
+	/// Serve every connection accepted by the given listener with a fake bus that answers every method call with an empty `METHOD_RETURN`,
+	/// except `Hang` which is never answered. The members of the method calls are recorded along with the index of the connection
+	/// they were received on.
+	///
+	/// Returns the server ends of the accepted connections, so that the test can break them.
+	fn serve_restartable_bus(
+		listener: std::os::unix::net::UnixListener,
+		calls: std::sync::Arc<std::sync::Mutex<Vec<(usize, String)>>>,
+	) -> std::sync::mpsc::Receiver<std::os::unix::net::UnixStream> {
+		let (streams_sender, streams_receiver) = std::sync::mpsc::channel();
+		let _ = std::thread::spawn(move || {
+			for (i, stream) in listener.incoming().enumerate() {
+				let stream = stream.unwrap();
+				if streams_sender.send(stream.try_clone().unwrap()).is_err() {
+					break;
+				}
+
+				let calls = calls.clone();
+				crate::fake_bus::spawn(stream, move |header, _| {
+					let member = header.member().unwrap().to_owned();
+					let hang = member == "Hang";
+					calls.lock().unwrap().push((i, member));
+					if hang { vec![] } else { vec![crate::fake_bus::method_return(header, None)] }
+				});
+			}
+		});
+		streams_receiver
+	}
+
+	#[test]
+	fn test_reconnect() {
+		let dir = std::env::temp_dir().join(format!("dbus-pure-test-reconnect-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir(&dir).unwrap();
+		let path = dir.join("bus");
+
+		let calls = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+		let streams = serve_restartable_bus(std::os::unix::net::UnixListener::bind(&path).unwrap(), calls.clone());
+
+		let connection = crate::Connection::new(crate::BusPath::UnixSocketFile(&path), crate::SaslAuthType::Uid).unwrap();
+		let mut client = super::Client::new(connection).unwrap();
+		let _guard = client.add_match(&crate::MatchRule::new().r#type(crate::MatchMessageType::Signal).interface("org.example")).unwrap();
+		let guard = client.add_match(&crate::MatchRule::new().r#type(crate::MatchMessageType::Signal).member("Removed")).unwrap();
+		client.remove_match(guard).unwrap();
+
+		let call = |client: &mut super::Client| client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Call", None);
+		let _ = call(&mut client).unwrap();
+
+		// Break the connection. The client reconnects by itself after the method call fails.
+		client.set_auto_reconnect(Some(super::ReconnectPolicy { max_attempts: 3, delay: std::time::Duration::from_millis(10) }));
+		streams.recv().unwrap().shutdown(std::net::Shutdown::Both).unwrap();
+		let err = call(&mut client).unwrap_err();
+		assert!(matches!(err, super::MethodCallError::SendRequest(crate::SendError::Io(_)) | super::MethodCallError::RecvResponse(crate::RecvError::Io(_))), "{err:?}");
+		let _ = call(&mut client).unwrap();
+
+		// Reconnect explicitly.
+		client.set_auto_reconnect(None);
+		streams.recv().unwrap().shutdown(std::net::Shutdown::Both).unwrap();
+		let _ = call(&mut client).unwrap_err();
+		let _ = call(&mut client).unwrap_err();
+		client.reconnect().unwrap();
+		let _ = call(&mut client).unwrap();
+
+		// The match rule that is still added is added again on every new connection.
+		let calls = std::mem::take(&mut *calls.lock().unwrap());
+		assert_eq!(calls, [
+			(0, "AddMatch".to_owned()),
+			(0, "AddMatch".to_owned()),
+			(0, "RemoveMatch".to_owned()),
+			(0, "Call".to_owned()),
+			(1, "AddMatch".to_owned()),
+			(1, "Call".to_owned()),
+			(2, "AddMatch".to_owned()),
+			(2, "Call".to_owned()),
+		]);
+
+		// A connection that was not opened from a bus path can't be reopened.
+		let mut client = crate::fake_bus::client(|_, _| vec![]);
+		let err = client.reconnect().unwrap_err();
+		assert!(matches!(err, super::ReconnectError::Connect(crate::ConnectError::NotReconnectable)), "{err:?}");
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_split_disconnected() {
+		let dir = std::env::temp_dir().join(format!("dbus-pure-test-split-disconnected-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir(&dir).unwrap();
+		let path = dir.join("bus");
+
+		let calls = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+		let streams = serve_restartable_bus(std::os::unix::net::UnixListener::bind(&path).unwrap(), calls.clone());
+
+		let connection = crate::Connection::new(crate::BusPath::UnixSocketFile(&path), crate::SaslAuthType::Uid).unwrap();
+		let (mut send_half, mut recv_half) = super::Client::new(connection).unwrap().split();
+		let stream = streams.recv().unwrap();
+
+		let receiver = std::thread::spawn(move || recv_half.recv().unwrap_err());
+
+		// Break the connection once the bus has received the method call that it never answers.
+		let _ = std::thread::spawn(move || {
+			while !calls.lock().unwrap().iter().any(|(_, member)| member == "Hang") {
+				std::thread::sleep(std::time::Duration::from_millis(1));
+			}
+			stream.shutdown(std::net::Shutdown::Both).unwrap();
+		});
+
+		let err = send_half.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Hang", None).unwrap_err();
+		assert!(matches!(err, super::MethodCallError::Disconnected), "{err:?}");
+
+		let err = receiver.join().unwrap();
+		assert!(matches!(err, crate::RecvError::Io(_)), "{err:?}");
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -1,12 +1,30 @@
 /// A connection to a message bus.
 pub struct Connection {
-	reader: std::io::BufReader<std::os::unix::net::UnixStream>,
-	read_buf: Vec<u8>,
-	read_end: usize,
-	writer: std::os::unix::net::UnixStream,
-	write_buf: Vec<u8>,
-	write_endianness: crate::proto::Endianness,
-	server_guid: Vec<u8>,
+	send_half: SendHalf,
+	recv_half: RecvHalf,
+	server_guid: ServerGuid,
+}
+
+/// The file descriptor of the socket of the connection, to wait for it to become readable or writable with `poll(2)` or an event loop.
+///
+/// The connection buffers what it reads from the socket, so a message can be ready to be received even though the socket is not readable.
+/// Check [`Connection::has_buffered_data`] and drain the connection with [`Connection::try_recv`] before waiting for the socket
+/// to become readable. Similarly, wait for it to become writable only if [`Connection::has_pending_write`].
+///
+/// # Panics
+///
+/// Panics if the connection was opened with [`Connection::from_stream`], since the stream might not have a file descriptor.
+impl std::os::fd::AsFd for Connection {
+	fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+		self.recv_half.as_fd()
+	}
+}
+
+/// See the [`std::os::fd::AsFd`] impl for details.
+impl std::os::fd::AsRawFd for Connection {
+	fn as_raw_fd(&self) -> std::os::fd::RawFd {
+		std::os::fd::AsRawFd::as_raw_fd(&std::os::fd::AsFd::as_fd(self))
+	}
 }
 
 /// The path of a message bus.
@@ -23,27 +41,254 @@ pub enum BusPath<'a> {
 	UnixSocketFile(&'a std::path::Path),
 }
 
-/// The string to send for SASL EXTERNAL authentication with the message bus.
+/// How to authenticate with the message bus.
 ///
 /// `Uid` is usually the type to use for local message buses.
 #[derive(Clone, Copy, Debug)]
 pub enum SaslAuthType<'a> {
-	/// The user ID of the current thread will be used.
+	/// The SASL EXTERNAL mechanism with the user ID of the current thread.
 	Uid,
 
-	/// The specified string will be used.
+	/// The SASL EXTERNAL mechanism with the specified string as the hex-encoded authorization identity.
 	Other(&'a str),
+
+	/// The SASL ANONYMOUS mechanism. Message buses only accept this if they are configured to allow anonymous clients.
+	Anonymous,
+
+	/// The SASL EXTERNAL mechanism with the user ID of the current thread, and then the ANONYMOUS mechanism if the bus rejects that
+	/// and offers ANONYMOUS instead.
+	Auto,
+}
+
+impl<'a> SaslAuthType<'a> {
+	/// The mechanisms to try, in order. The first one is tried first, and the rest are tried in the order that the bus prefers them
+	/// if it rejects the previous one.
+	fn mechanisms(self) -> Vec<SaslMechanism<'a>> {
+		match self {
+			SaslAuthType::Uid => vec![SaslMechanism::External(uid_auth_id().into())],
+			SaslAuthType::Other(sasl_auth_id) => vec![SaslMechanism::External(sasl_auth_id.into())],
+			SaslAuthType::Anonymous => vec![SaslMechanism::Anonymous],
+			SaslAuthType::Auto => vec![SaslMechanism::External(uid_auth_id().into()), SaslMechanism::Anonymous],
+		}
+	}
+}
+
+/// An owned copy of the arguments of [`Connection::new`], so that [`Connection::reconnect`] can open a connection to the same bus again.
+#[derive(Debug)]
+struct ConnectArgs {
+	bus_path: ConnectArgsBusPath,
+	sasl_auth_type: ConnectArgsSaslAuthType,
+}
+
+/// An owned [`BusPath`]
+#[derive(Debug)]
+enum ConnectArgsBusPath {
+	Session,
+	System,
+	UnixSocketFile(std::path::PathBuf),
+}
+
+/// An owned [`SaslAuthType`]
+#[derive(Debug)]
+enum ConnectArgsSaslAuthType {
+	Uid,
+	Other(String),
+	Anonymous,
+	Auto,
+}
+
+impl ConnectArgs {
+	fn new(bus_path: BusPath<'_>, sasl_auth_type: SaslAuthType<'_>) -> Self {
+		let bus_path = match bus_path {
+			BusPath::Session => ConnectArgsBusPath::Session,
+			BusPath::System => ConnectArgsBusPath::System,
+			BusPath::UnixSocketFile(path) => ConnectArgsBusPath::UnixSocketFile(path.to_owned()),
+		};
+		let sasl_auth_type = match sasl_auth_type {
+			SaslAuthType::Uid => ConnectArgsSaslAuthType::Uid,
+			SaslAuthType::Other(sasl_auth_id) => ConnectArgsSaslAuthType::Other(sasl_auth_id.to_owned()),
+			SaslAuthType::Anonymous => ConnectArgsSaslAuthType::Anonymous,
+			SaslAuthType::Auto => ConnectArgsSaslAuthType::Auto,
+		};
+		ConnectArgs { bus_path, sasl_auth_type }
+	}
+
+	fn connect(&self) -> Result<Connection, ConnectError> {
+		let bus_path = match &self.bus_path {
+			ConnectArgsBusPath::Session => BusPath::Session,
+			ConnectArgsBusPath::System => BusPath::System,
+			ConnectArgsBusPath::UnixSocketFile(path) => BusPath::UnixSocketFile(path),
+		};
+		let sasl_auth_type = match &self.sasl_auth_type {
+			ConnectArgsSaslAuthType::Uid => SaslAuthType::Uid,
+			ConnectArgsSaslAuthType::Other(sasl_auth_id) => SaslAuthType::Other(sasl_auth_id),
+			ConnectArgsSaslAuthType::Anonymous => SaslAuthType::Anonymous,
+			ConnectArgsSaslAuthType::Auto => SaslAuthType::Auto,
+		};
+		Connection::new(bus_path, sasl_auth_type)
+	}
+}
+
+/// A SASL mechanism, along with what to send as its initial response.
+#[derive(Debug)]
+enum SaslMechanism<'a> {
+	Anonymous,
+
+	/// The hex-encoded authorization identity.
+	External(std::borrow::Cow<'a, str>),
+}
+
+impl SaslMechanism<'_> {
+	fn name(&self) -> &'static str {
+		match self {
+			SaslMechanism::Anonymous => "ANONYMOUS",
+			SaslMechanism::External(_) => "EXTERNAL",
+		}
+	}
+
+	/// The hex-encoded initial response.
+	fn initial_response(&self) -> std::borrow::Cow<'_, str> {
+		match self {
+			// The initial response of ANONYMOUS is trace information that the server may log.
+			SaslMechanism::Anonymous => hex_encode(b"dbus-pure").into(),
+			SaslMechanism::External(sasl_auth_id) => (**sasl_auth_id).into(),
+		}
+	}
+}
+
+/// The authorization identity of the user ID of the current thread, as the SASL EXTERNAL mechanism expects it.
+fn uid_auth_id() -> String {
+	let uid = (unsafe { libc::getuid() }).to_string();
+	hex_encode(uid.as_bytes())
+}
+
+fn hex_encode(s: &[u8]) -> String {
+	let mut result = String::with_capacity(s.len() * 2);
+	for b in s {
+		use std::fmt::Write;
+		write!(result, "{b:02x}").expect("cannot fail");
+	}
+	result
+}
+
+/// Statistics about the messages sent and received over a connection. See [`Connection::stats`]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ConnectionStats {
+	pub sent: MessageCounts,
+	pub received: MessageCounts,
+
+	/// The number of bytes written to the connection, including the parts of messages that are still pending in non-blocking mode.
+	pub bytes_sent: u64,
+
+	/// The number of bytes read from the connection, including the parts of messages that have not been received yet.
+	pub bytes_received: u64,
+
+	/// The current capacity of the buffer for received messages. See [`Connection::set_max_read_buffer`]
+	pub read_buffer_capacity: usize,
+}
+
+impl ConnectionStats {
+	/// Combines the statistics of the send half and the receive half of a connection.
+	pub(crate) fn combine(send_half: Self, recv_half: Self) -> Self {
+		ConnectionStats {
+			sent: send_half.sent,
+			bytes_sent: send_half.bytes_sent,
+			..recv_half
+		}
+	}
+}
+
+/// The number of messages of each type.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MessageCounts {
+	pub method_calls: u64,
+	pub method_returns: u64,
+	pub errors: u64,
+	pub signals: u64,
+
+	/// Messages of types that are not defined by the D-Bus specification. See [`crate::proto::MessageType::Unknown`]
+	pub unknown: u64,
+}
+
+impl MessageCounts {
+	/// The number of messages of all types.
+	pub fn total(&self) -> u64 {
+		self.method_calls + self.method_returns + self.errors + self.signals + self.unknown
+	}
+
+	fn add(&mut self, r#type: &crate::proto::MessageType<'_>) {
+		let count = match r#type {
+			crate::proto::MessageType::Error { .. } => &mut self.errors,
+			crate::proto::MessageType::MethodCall { .. } => &mut self.method_calls,
+			crate::proto::MessageType::MethodReturn { .. } => &mut self.method_returns,
+			crate::proto::MessageType::Signal { .. } => &mut self.signals,
+			crate::proto::MessageType::Unknown { .. } => &mut self.unknown,
+		};
+		*count += 1;
+	}
+}
+
+/// The credentials of the process on the other end of a connection. See [`Connection::peer_credentials`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PeerCredentials {
+	pub uid: u32,
+	pub gid: u32,
+
+	/// `None` if the platform does not report it, or if the process is in a different PID namespace.
+	pub pid: Option<u32>,
+}
+
+/// The GUID of a message bus, which identifies the address that it listens on.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ServerGuid(pub [u8; 16]);
+
+impl ServerGuid {
+	/// Parses a GUID from its 32 hex digits, as it appears in bus addresses and the SASL handshake.
+	fn parse(hex: &[u8]) -> Option<Self> {
+		if hex.len() != 32 {
+			return None;
+		}
+
+		let mut result = [0_u8; 16];
+		for (b, hex) in result.iter_mut().zip(hex.chunks_exact(2)) {
+			let hex = std::str::from_utf8(hex).ok()?;
+			if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+				return None;
+			}
+			*b = u8::from_str_radix(hex, 16).ok()?;
+		}
+		Some(ServerGuid(result))
+	}
+}
+
+/// Formats the GUID as 32 lowercase hex digits.
+impl std::fmt::Display for ServerGuid {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for b in self.0 {
+			write!(f, "{b:02x}")?;
+		}
+		Ok(())
+	}
 }
 
 impl Connection {
 	/// Opens a connection to the bus at the given path with the given authentication type.
+	///
+	/// The connection remembers the path and authentication type, so that it can be opened again with [`Connection::reconnect`]
 	pub fn new(
 		bus_path: BusPath<'_>,
 		sasl_auth_type: SaslAuthType<'_>,
 	) -> Result<Self, ConnectError> {
-		use std::io::{BufRead, Write};
+		let mut connection = Connection::open(bus_path, sasl_auth_type)?;
+		connection.send_half.connect_args = Some(std::sync::Arc::new(ConnectArgs::new(bus_path, sasl_auth_type)));
+		Ok(connection)
+	}
 
-		let stream = match bus_path {
+	fn open(
+		bus_path: BusPath<'_>,
+		sasl_auth_type: SaslAuthType<'_>,
+	) -> Result<Self, ConnectError> {
+		let (stream, nonce, expected_server_guid, process) = match bus_path {
 			BusPath::Session => {
 				let bus_address = std::env::var_os("DBUS_SESSION_BUS_ADDRESS").ok_or(ConnectError::MissingSessionBusEnvVar)?;
 				connect(&bus_address)?
@@ -60,195 +305,1033 @@ impl Connection {
 				let stream =
 					std::os::unix::net::UnixStream::connect(bus_path)
 					.map_err(|err| ConnectError::Connect(vec![(bus_path.to_owned(), err)]))?;
-				stream
+				return Connection::from_unix_stream(stream, sasl_auth_type);
 			},
 		};
 
-		let sasl_auth_id = match sasl_auth_type {
-			SaslAuthType::Uid => &{
-				let uid = (unsafe { libc::getuid() }).to_string();
-				let mut sasl_auth_id = String::with_capacity(uid.len() * 2);
-				for c in uid.chars() {
-					use std::fmt::Write;
-					write!(sasl_auth_id, "{:02x}", c as u32).expect("cannot fail");
-				}
-				sasl_auth_id
-			},
+		let mut connection = Connection::authenticate(stream, nonce, expected_server_guid, sasl_auth_type)?;
+		let process = process.map(std::sync::Arc::new);
+		connection.send_half.process.clone_from(&process);
+		connection.recv_half.process = process;
+		Ok(connection)
+	}
 
-			SaslAuthType::Other(sasl_auth_id) => sasl_auth_id,
-		};
+	/// Opens a new connection to the same bus with the same authentication type as this connection, such as after the bus restarted.
+	///
+	/// Session and system bus addresses are looked up from the environment again. The settings of this connection,
+	/// like its timeouts and limits, are not copied to the new connection.
+	///
+	/// Fails with [`ConnectError::NotReconnectable`] if this connection was not opened with [`Connection::new`]
+	pub fn reconnect(&self) -> Result<Self, ConnectError> {
+		self.send_half.reconnect()
+	}
+
+	/// Opens a connection to the bus on the other end of the given stream with the given authentication type.
+	///
+	/// Unix file descriptors cannot be passed over the connection. Use [`Connection::from_unix_stream`] for a unix socket
+	/// to be able to pass them.
+	pub fn from_stream<S>(
+		stream: S,
+		sasl_auth_type: SaslAuthType<'_>,
+	) -> Result<Self, ConnectError> where S: std::io::Read + std::io::Write + Send + 'static {
+		let stream = std::sync::Arc::new(std::sync::Mutex::new(stream));
+		Connection::authenticate(crate::fd::Stream::Other(stream), None, None, sasl_auth_type)
+	}
+
+	/// Opens a connection to the bus on the other end of the given unix socket with the given authentication type.
+	///
+	/// Unix file descriptors can be passed over the connection if the bus agrees to it. See [`Connection::supports_unix_fds`]
+	pub fn from_unix_stream(
+		stream: std::os::unix::net::UnixStream,
+		sasl_auth_type: SaslAuthType<'_>,
+	) -> Result<Self, ConnectError> {
+		Connection::authenticate(crate::fd::Stream::Unix(stream), None, None, sasl_auth_type)
+	}
+
+	/// Opens a connection over the socket that systemd passed to this process with socket activation, with the given authentication type.
+	///
+	/// The socket must be a connected unix socket, like the ones that a socket unit with `Accept=yes` passes to the instances of its service.
+	/// The `LISTEN_PID` and `LISTEN_FDS` environment variables must say that exactly one file descriptor was passed to this process.
+	/// They are removed from the environment once the file descriptor has been taken, so this can only succeed once.
+	///
+	/// For a socket unit with `Accept=no`, see [`Connection::activation_listener`]
+	pub fn from_activation(sasl_auth_type: SaslAuthType<'_>) -> Result<Self, ConnectError> {
+		let fd = take_activation_fd().map_err(ConnectError::Activation)?;
+		let fd = activation_socket(fd, crate::fd::SocketKind::Connected).map_err(ConnectError::Activation)?;
+		Connection::from_unix_stream(fd.into(), sasl_auth_type)
+	}
+
+	/// Takes the listening socket that systemd passed to this process with socket activation, like a socket unit with `Accept=no` does.
+	///
+	/// The requirements on the environment are the same as for [`Connection::from_activation`]
+	pub fn activation_listener() -> Result<std::os::unix::net::UnixListener, ActivationError> {
+		let fd = take_activation_fd()?;
+		let fd = activation_socket(fd, crate::fd::SocketKind::Listening)?;
+		Ok(fd.into())
+	}
+
+	/// Authenticate with the message bus on the other end of the given stream.
+	///
+	/// If a nonce is given, it is sent before the SASL handshake, as `nonce-tcp:` addresses require.
+	///
+	/// If an expected GUID is given, authentication fails if the server has a different one.
+	///
+	/// Unix file descriptors are only negotiated if the stream is a unix socket.
+	pub(crate) fn authenticate(
+		stream: crate::fd::Stream,
+		nonce: Option<Nonce>,
+		expected_server_guid: Option<ServerGuid>,
+		sasl_auth_type: SaslAuthType<'_>,
+	) -> Result<Self, ConnectError> {
+		use std::io::{BufRead, Write};
+
+		let mut mechanisms = sasl_auth_type.mechanisms();
+		let mut mechanism = mechanisms.remove(0);
 
 		let reader = stream.try_clone().map_err(ConnectError::Authenticate)?;
-		let mut reader = std::io::BufReader::new(reader);
+		let mut reader = std::io::BufReader::new(crate::fd::FdReader::new(reader));
 		let mut read_buf = vec![];
 
-		let mut writer = stream;
-		let write_buf = vec![];
+		let can_pass_unix_fds = matches!(stream, crate::fd::Stream::Unix(_));
+		let mut writer = crate::fd::FdWriter::new(stream);
 
-		#[allow(clippy::write_with_newline)]
-		write!(writer, "\0AUTH EXTERNAL {sasl_auth_id}\r\n").map_err(ConnectError::Authenticate)?;
-		writer.flush().map_err(ConnectError::Authenticate)?;
+		// A server that rejects the nonce closes the connection without responding to the SASL handshake.
+		let map_first_err = |err: std::io::Error|
+			if nonce.is_some() && matches!(err.kind(), std::io::ErrorKind::BrokenPipe | std::io::ErrorKind::ConnectionReset) {
+				ConnectError::NonceRejected
+			}
+			else {
+				ConnectError::Authenticate(err)
+			};
 
-		let _ = reader.read_until(b'\n', &mut read_buf).map_err(ConnectError::Authenticate)?;
-		if read_buf.iter().rev().nth(1).copied() != Some(b'\r') {
-			return Err(ConnectError::Authenticate(std::io::Error::new(std::io::ErrorKind::Other, "malformed response")));
+		if let Some(nonce) = &nonce {
+			writer.write_all(nonce).map_err(map_first_err)?;
 		}
 
-		let server_guid =
-			if read_buf.starts_with(b"OK ") {
-				&read_buf[b"OK ".len()..][..32]
+		writer.write_all(b"\0").map_err(map_first_err)?;
+
+		let mut is_first_attempt = true;
+
+		let server_guid = loop {
+			let map_err = |err| if is_first_attempt { map_first_err(err) } else { ConnectError::Authenticate(err) };
+
+			let auth_line = format!("AUTH {} {}\r\n", mechanism.name(), mechanism.initial_response());
+			crate::trace::sasl_line("sent", auth_line.as_bytes());
+			writer.write_all(auth_line.as_bytes()).map_err(map_err)?;
+			writer.flush().map_err(map_err)?;
+
+			read_buf.clear();
+			let read = reader.read_until(b'\n', &mut read_buf).map_err(map_err)?;
+			crate::trace::sasl_line("received", &read_buf);
+			if read == 0 && nonce.is_some() && is_first_attempt {
+				return Err(ConnectError::NonceRejected);
 			}
-			else {
-				return Err(ConnectError::Authenticate(std::io::Error::new(std::io::ErrorKind::Other, "malformed response")));
+			let line = read_buf.strip_suffix(b"\r\n").ok_or_else(malformed_response)?;
+
+			if let Some(server_guid) = line.strip_prefix(b"OK ") {
+				break ServerGuid::parse(server_guid).ok_or_else(malformed_response)?;
+			}
+
+			let offered =
+				if let Some(offered) = parse_rejected(line) {
+					offered
+				}
+				else if line.starts_with(b"ERROR") || line.starts_with(b"DATA") {
+					// The mechanisms only send an initial response, so there is nothing else to send for a challenge,
+					// and an error means that the server did not understand the initial response. Cancelling the attempt
+					// makes the server reject it with the mechanisms it supports.
+					crate::trace::sasl_line("sent", b"CANCEL\r\n");
+					writer.write_all(b"CANCEL\r\n").map_err(ConnectError::Authenticate)?;
+					writer.flush().map_err(ConnectError::Authenticate)?;
+
+					read_buf.clear();
+					let _ = reader.read_until(b'\n', &mut read_buf).map_err(ConnectError::Authenticate)?;
+					crate::trace::sasl_line("received", &read_buf);
+					let line = read_buf.strip_suffix(b"\r\n").ok_or_else(malformed_response)?;
+					parse_rejected(line).ok_or_else(malformed_response)?
+				}
+				else {
+					return Err(malformed_response());
+				};
+
+			// Try the remaining mechanism that the server prefers the most.
+			let next_mechanism = offered.iter().find_map(|offered| mechanisms.iter().position(|mechanism| mechanism.name() == offered));
+			let Some(next_mechanism) = next_mechanism else {
+				return Err(ConnectError::AuthRejected { offered });
 			};
-		let server_guid = server_guid.to_owned();
+			mechanism = mechanisms.remove(next_mechanism);
+			is_first_attempt = false;
+		};
+
+		// The GUID in the bus address identifies the server, so a different one means that the address led to the wrong server.
+		if let Some(expected_server_guid) = expected_server_guid {
+			if server_guid != expected_server_guid {
+				return Err(ConnectError::ServerGuidMismatch { expected: expected_server_guid, actual: server_guid });
+			}
+		}
+
+		let unix_fds =
+			if can_pass_unix_fds {
+				crate::trace::sasl_line("sent", b"NEGOTIATE_UNIX_FD\r\n");
+				writer.write_all(b"NEGOTIATE_UNIX_FD\r\n").map_err(ConnectError::Authenticate)?;
+				writer.flush().map_err(ConnectError::Authenticate)?;
 
-		read_buf.clear();
-		read_buf.resize(1, 0);
+				read_buf.clear();
+				let _ = reader.read_until(b'\n', &mut read_buf).map_err(ConnectError::Authenticate)?;
+				crate::trace::sasl_line("received", &read_buf);
+				if read_buf == b"AGREE_UNIX_FD\r\n" {
+					true
+				}
+				else if read_buf.starts_with(b"ERROR") && read_buf.ends_with(b"\r\n") {
+					false
+				}
+				else {
+					return Err(malformed_response());
+				}
+			}
+			else {
+				false
+			};
 
+		crate::trace::sasl_line("sent", b"BEGIN\r\n");
 		writer.write_all(b"BEGIN\r\n").map_err(ConnectError::Authenticate)?;
 		writer.flush().map_err(ConnectError::Authenticate)?;
 
+		Ok(Connection::from_authenticated(reader, writer, unix_fds, server_guid))
+	}
+
+	/// Creates a connection from the two ends of a stream that has completed the SASL handshake.
+	pub(crate) fn from_authenticated(
+		mut reader: std::io::BufReader<crate::fd::FdReader>,
+		mut writer: crate::fd::FdWriter,
+		unix_fds: bool,
+		server_guid: ServerGuid,
+	) -> Self {
+		// The statistics of the connection only count messages.
+		writer.reset_bytes_written();
+		reader.get_mut().reset_bytes_read();
+
 		// Default to target endianness
 		let write_endianness = if cfg!(target_endian = "big") { crate::proto::Endianness::Big } else { crate::proto::Endianness::Little };
 
-		Ok(Connection {
-			reader,
-			read_buf,
-			read_end: 0,
-			writer,
-			write_buf,
-			write_endianness,
+		Connection {
+			send_half: SendHalf {
+				writer: crate::proto::MessageWriter::new(writer),
+				write_endianness,
+				unix_fds,
+				write_timeout: None,
+				sent: Default::default(),
+				process: None,
+				connect_args: None,
+			},
+			recv_half: RecvHalf {
+				reader: crate::proto::MessageReader::new(reader),
+				unclaimed_fds: 0,
+				read_timeout: None,
+				received: Default::default(),
+				process: None,
+			},
 			server_guid,
-		})
+		}
 	}
 
 	/// The GUID of the server.
-	pub fn server_guid(&self) -> &[u8] {
-		&self.server_guid
+	pub fn server_guid(&self) -> ServerGuid {
+		self.server_guid
+	}
+
+	/// The credentials of the process on the other end of the connection, as the kernel recorded them when the connection was established.
+	///
+	/// Fails if the connection is not over a unix socket, or if the platform does not support getting them.
+	pub fn peer_credentials(&self) -> std::io::Result<PeerCredentials> {
+		self.recv_half.peer_credentials()
+	}
+
+	/// Whether the message bus agreed to pass unix file descriptors over this connection.
+	///
+	/// This is always `false` for connections that are not over unix sockets.
+	///
+	/// If it did not, [`Connection::send_with_fds`] fails with [`SendError::UnixFdsNotSupported`].
+	pub fn supports_unix_fds(&self) -> bool {
+		self.send_half.supports_unix_fds()
 	}
 
 	/// Send a message with the given header and body to the message bus.
 	///
+	/// - The header serial must be set by the caller, and must not be 0. [`crate::Client::send`] takes care of this automatically.
+	///
 	/// - Header fields corresponding to the required properties of the message type will be automatically inserted, and *must not* be inserted by the caller.
 	///   For example, if `header.type` is `MethodCall { member, path }`, the `MessageHeaderField::Member` and `MessageHeaderField::Path` fields
 	///   will be inserted automatically.
 	///
 	/// - The `MessageHeaderField::Signature` field will be automatically inserted if a body is specified, and must not be inserted by the caller.
-	pub fn send(&mut self, header: &mut crate::proto::MessageHeader<'_>, body: Option<&crate::proto::Variant<'_>>) -> Result<(), SendError> {
-		use std::io::Write;
+	pub fn send(&mut self, header: &crate::proto::MessageHeader<'_>, body: Option<&crate::proto::Variant<'_>>) -> Result<(), SendError> {
+		self.send_half.send(header, body)
+	}
+
+	/// Like [`Connection::send`], but sends the message with the given endianness instead of the one set by [`Connection::set_write_endianness`].
+	pub fn send_with_endianness(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: Option<&crate::proto::Variant<'_>>,
+		endianness: crate::proto::Endianness,
+	) -> Result<(), SendError> {
+		self.send_half.send_with_endianness(header, body, endianness)
+	}
+
+	/// Like [`Connection::send`], but also sends the given file descriptors along with the message.
+	///
+	/// The `MessageHeaderField::UnixFds` field will be automatically inserted with the number of file descriptors,
+	/// and must not be inserted by the caller. `Variant::UnixFd` values in the body are indices into `fds`.
+	///
+	/// Returns [`SendError::UnixFdsNotSupported`] if `fds` is not empty but the message bus did not agree to pass file descriptors.
+	/// See [`Connection::supports_unix_fds`]
+	pub fn send_with_fds(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: Option<&crate::proto::Variant<'_>>,
+		fds: &[std::os::fd::BorrowedFd<'_>],
+	) -> Result<(), SendError> {
+		self.send_half.send_with_fds(header, body, fds)
+	}
 
-		let () = crate::proto::serialize_message(header, body, &mut self.write_buf, self.write_endianness).map_err(SendError::Serialize)?;
+	/// Like [`Connection::send_with_fds`], but sends the message with the given endianness instead of the one set by [`Connection::set_write_endianness`].
+	pub fn send_with_fds_and_endianness(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: Option<&crate::proto::Variant<'_>>,
+		fds: &[std::os::fd::BorrowedFd<'_>],
+		endianness: crate::proto::Endianness,
+	) -> Result<(), SendError> {
+		self.send_half.send_with_fds_and_endianness(header, body, fds, endianness)
+	}
 
-		let () = self.writer.write_all(&self.write_buf).map_err(SendError::Io)?;
-		self.write_buf.clear();
+	/// Like [`Connection::send`], but the body is serialized directly from `body` with the given signature,
+	/// without converting it to a [`crate::proto::Variant`] first.
+	///
+	/// Returns [`SendError::Serialize`] with [`crate::proto::SerializeError::ValueSignatureMismatch`] if `body` does not have the shape
+	/// that `body_signature` requires. See [`crate::proto::to_writer_with_signature`] for details.
+	pub fn send_serialized<T>(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: &T,
+		body_signature: &crate::proto::Signature,
+	) -> Result<(), SendError> where T: serde::Serialize + ?Sized {
+		self.send_half.send_serialized(header, body, body_signature)
+	}
 
-		let () = self.writer.flush().map_err(SendError::Io)?;
+	/// Like [`Connection::send_serialized`], but sends the message with the given endianness instead of the one set by [`Connection::set_write_endianness`].
+	pub fn send_serialized_with_endianness<T>(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: &T,
+		body_signature: &crate::proto::Signature,
+		endianness: crate::proto::Endianness,
+	) -> Result<(), SendError> where T: serde::Serialize + ?Sized {
+		self.send_half.send_serialized_with_endianness(header, body, body_signature, endianness)
+	}
 
-		Ok(())
+	/// Whether data has already been read from the socket that has not been received as a message yet.
+	///
+	/// If so, the next receive might not need to read from the socket at all, so the connection should be drained with
+	/// [`Connection::try_recv`] before waiting for its file descriptor to become readable. See the [`std::os::fd::AsFd`] impl for details.
+	pub fn has_buffered_data(&self) -> bool {
+		self.recv_half.has_buffered_data()
+	}
+
+	/// Write the rest of a message that was only partially sent because the connection is in non-blocking mode.
+	///
+	/// Fails with an error of kind [`std::io::ErrorKind::WouldBlock`] if the rest of the message still could not be written,
+	/// in which case this should be called again once the socket is writable. See [`Connection::set_nonblocking`]
+	pub fn flush(&mut self) -> std::io::Result<()> {
+		self.send_half.flush()
+	}
+
+	/// Like [`Connection::flush`], but fails with [`SendError::WouldBlock`] if the rest of the message still could not be written.
+	///
+	/// This is how a [`SendError::WouldBlock`] from sending a message is resolved. Once this succeeds, the message can be sent again.
+	pub fn flush_pending(&mut self) -> Result<(), SendError> {
+		self.send_half.flush_pending()
+	}
+
+	/// Whether a message was only partially sent, and the rest of it needs to be written by [`Connection::flush`]
+	pub fn has_pending_write(&self) -> bool {
+		self.send_half.has_pending_write()
 	}
 
 	/// Receive a message from the message bus.
 	pub fn recv(&mut self) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), RecvError> {
-		use std::io::Read;
-
-		loop {
-			match crate::proto::deserialize_message(&self.read_buf[..self.read_end]) {
-				Ok((message_header, message_body, read)) => {
-					let message_header = message_header.into_owned();
-					let message_body = message_body.map(crate::proto::Variant::into_owned);
-					self.read_buf.copy_within(read..self.read_end, 0);
-					self.read_end -= read;
-					return Ok((message_header, message_body));
-				},
+		self.recv_half.recv()
+	}
 
-				Err(crate::proto::DeserializeError::EndOfInput) => {
-					if self.read_end == self.read_buf.len() {
-						self.read_buf.resize(self.read_buf.len() * 2, 0);
-					}
+	/// Like [`Connection::recv`], but returns `Ok(None)` instead of failing with [`RecvError::TimedOut`]
+	/// if a complete message has not been received yet.
+	///
+	/// This is meant for connections in non-blocking mode, where it returns immediately. See [`Connection::set_nonblocking`]
+	pub fn try_recv(&mut self) -> Result<Option<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>)>, RecvError> {
+		self.recv_half.try_recv()
+	}
 
-					let read = self.reader.read(&mut self.read_buf[self.read_end..]).map_err(RecvError::Io)?;
-					if read == 0 {
-						return Err(RecvError::Io(std::io::ErrorKind::UnexpectedEof.into()));
-					}
+	/// Like [`Connection::recv`], but also returns the endianness that the sender used for the message.
+	///
+	/// Any file descriptors sent along with the message are closed. Use [`Connection::recv_with_fds`] to receive them.
+	pub fn recv_with_endianness(
+		&mut self,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>, crate::proto::Endianness), RecvError> {
+		self.recv_half.recv_with_endianness()
+	}
 
-					self.read_end += read;
-				},
+	/// Like [`Connection::recv`], but also returns the file descriptors that were sent along with the message.
+	///
+	/// `Variant::UnixFd` values in the body are indices into the returned file descriptors.
+	///
+	/// Returns [`RecvError::MissingUnixFds`] if fewer file descriptors were received than the `MessageHeaderField::UnixFds` field of the message says.
+	pub fn recv_with_fds(
+		&mut self,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>, Vec<std::os::fd::OwnedFd>), RecvError> {
+		self.recv_half.recv_with_fds()
+	}
 
-				Err(err) => return Err(RecvError::Deserialize(err)),
-			}
-		}
+	/// Like [`Connection::recv`], but the strings and arrays of the received message borrow from the connection's read buffer
+	/// instead of being copied out of it.
+	///
+	/// See [`crate::proto::MessageReader::read_message_ref`] for details.
+	///
+	/// Any file descriptors sent along with the message are closed.
+	pub fn recv_ref(&mut self) -> Result<crate::proto::MessageRef<'_>, RecvError> {
+		self.recv_half.recv_ref()
 	}
 
 	/// Set the endianness used for sending messages.
 	///
 	/// By default, the connection uses the target endianness. Use this method to override that.
 	pub fn set_write_endianness(&mut self, endianness: crate::proto::Endianness) {
-		self.write_endianness = endianness;
+		self.send_half.set_write_endianness(endianness);
 	}
-}
-
-/// An error from connecting to a message bus.
-#[derive(Debug)]
-pub enum ConnectError {
-	Authenticate(std::io::Error),
-
-	Connect(Vec<(std::path::PathBuf, std::io::Error)>),
 
-	MissingSessionBusEnvVar,
+	/// Set the interner used to share the names and paths of received message headers.
+	///
+	/// By default, the connection does not intern anything and every received header gets freshly allocated strings.
+	pub fn set_interner(&mut self, interner: Option<&'static crate::proto::Interner>) {
+		self.recv_half.set_interner(interner);
+	}
 
-	UnsupportedTransport(std::ffi::OsString),
-}
+	/// Set the capacity of the buffer for received messages that is kept between messages.
+	///
+	/// Receiving a message larger than this grows the buffer to fit it, and the buffer is shrunk back to this capacity afterwards.
+	/// To bound the size of received messages themselves, use [`Connection::set_limits`].
+	///
+	/// Defaults to 64 KiB.
+	pub fn set_max_read_buffer(&mut self, max_read_buffer: usize) {
+		self.recv_half.set_max_read_buffer(max_read_buffer);
+	}
 
-impl std::fmt::Display for ConnectError {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		match self {
-			ConnectError::Authenticate(_) => f.write_str("could not authenticate with bus"),
+	/// Set the limits on the sizes of sent and received messages.
+	///
+	/// By default, the connection uses [`crate::proto::MessageLimits::SPEC`], which are the limits that message buses enforce.
+	/// Use this method to override that for a connection to a peer that is not a message bus.
+	///
+	/// Receiving a message that exceeds the limits fails with [`crate::proto::DeserializeError::MessageTooLong`]
+	/// or [`crate::proto::DeserializeError::ArrayTooLong`], without buffering the whole message first.
+	/// The latter is wrapped in [`crate::proto::DeserializeError::InvalidBody`] if the array is in the message body.
+	pub fn set_limits(&mut self, limits: crate::proto::MessageLimits) {
+		self.send_half.set_limits(limits);
+		self.recv_half.set_limits(limits);
+	}
 
-			ConnectError::Connect(inner) => {
-				f.write_str("could not connect to any bus paths: [")?;
-				for (i, (bus_path, err)) in inner.iter().enumerate() {
-					if i > 0 {
-						f.write_str(", ")?;
-					}
+	/// Set whether the connection is in non-blocking mode.
+	///
+	/// In non-blocking mode, receiving fails with [`RecvError::TimedOut`] immediately if a complete message has not been received yet.
+	/// [`Connection::try_recv`] returns `Ok(None)` instead. The bytes of a partially received message are kept, and the next receive
+	/// continues with the rest of it.
+	///
+	/// Sending a message fails with [`SendError::WouldBlock`] if none of it could be written, or if the rest of a previous message
+	/// has not been written yet. If only some of it could be written, sending succeeds and the rest of it is written by [`Connection::flush_pending`].
+	/// See [`crate::proto::MessageWriter`] for details.
+	///
+	/// Fails if the connection was opened with [`Connection::from_stream`] since the stream might not be a socket.
+	pub fn set_nonblocking(&mut self, nonblocking: bool) -> std::io::Result<()> {
+		self.recv_half.set_nonblocking(nonblocking)
+	}
 
-					write!(f, "{bus_path:?}: {:?}", err.to_string())?;
-				}
-				f.write_str("]")?;
-				Ok(())
-			},
+	/// The timeout set by [`Connection::set_read_timeout`]
+	pub fn read_timeout(&self) -> Option<std::time::Duration> {
+		self.recv_half.read_timeout()
+	}
 
-			ConnectError::MissingSessionBusEnvVar => f.write_str("the DBUS_SESSION_BUS_ADDRESS env var is not set"),
+	/// Set the timeout for receiving a message, or `None` to block until a message is received.
+	///
+	/// When the timeout elapses, receiving fails with [`RecvError::TimedOut`]. The connection remains usable,
+	/// and the next receive continues with the rest of the message that was being received, if any.
+	///
+	/// The timeout applies to each read from the underlying socket, so a message that arrives in pieces can take longer than the timeout
+	/// to be received. [`crate::Client::recv_timeout`] bounds the total time instead.
+	///
+	/// Fails if the timeout is zero, or if the connection was opened with [`Connection::from_stream`] since the stream might not be a socket.
+	pub fn set_read_timeout(&mut self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+		self.recv_half.set_read_timeout(timeout)
+	}
 
-			ConnectError::UnsupportedTransport(value) => write!(f, "the bus path {value:?} has an unsupported transport"),
-		}
+	/// The timeout set by [`Connection::set_write_timeout`]
+	pub fn write_timeout(&self) -> Option<std::time::Duration> {
+		self.send_half.write_timeout()
 	}
-}
+
+	/// Set the timeout for sending a message, or `None` to block until the message is sent.
+	///
+	/// When the timeout elapses, sending fails with [`SendError::WouldBlock`] if none of the message was written. If some of it was written,
+	/// the rest is kept and written by [`Connection::flush_pending`], as in non-blocking mode. See [`Connection::set_nonblocking`]
+	///
+	/// Fails if the timeout is zero, or if the connection was opened with [`Connection::from_stream`] since the stream might not be a socket.
+	pub fn set_write_timeout(&mut self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+		self.send_half.set_write_timeout(timeout)
+	}
+
+	/// The statistics of the messages sent and received over the connection.
+	///
+	/// The bytes of the SASL handshake are not counted.
+	pub fn stats(&self) -> ConnectionStats {
+		ConnectionStats::combine(self.send_half.stats(), self.recv_half.stats())
+	}
+
+	/// Reset the statistics of the messages sent and received over the connection to zero.
+	pub fn reset_stats(&mut self) {
+		self.send_half.reset_stats();
+		self.recv_half.reset_stats();
+	}
+
+	/// Split the connection into a half that sends messages and a half that receives them, so that one thread can send messages
+	/// while another thread is blocked receiving them.
+	///
+	/// Each half has its own clone of the connection's socket and its own buffer. Messages sent with the [`SendHalf`] are written
+	/// in the order they are sent, and the [`RecvHalf`] returns messages in the order they were received, as with the connection itself.
+	/// There is no ordering between the two halves. For example, the response to a method call can be received
+	/// before the send of the method call has returned.
+	///
+	/// The clones of the socket share its non-blocking mode, so [`Connection::set_nonblocking`] should be called before splitting
+	/// the connection, and applies to both halves. Read and write timeouts are independent.
+	///
+	/// If the connection was opened with [`Connection::from_stream`], the halves share the stream behind a lock,
+	/// so sending waits for a receive that is blocked reading from the stream.
+	pub fn split(self) -> (SendHalf, RecvHalf) {
+		(self.send_half, self.recv_half)
+	}
+}
+
+/// The half of a [`Connection`] that sends messages. See [`Connection::split`]
+pub struct SendHalf {
+	writer: crate::proto::MessageWriter<crate::fd::FdWriter>,
+	write_endianness: crate::proto::Endianness,
+	unix_fds: bool,
+	write_timeout: Option<std::time::Duration>,
+	sent: MessageCounts,
+
+	/// The process spawned for a `unixexec:` address, shared with the [`RecvHalf`]. This is declared after the writer
+	/// so that the process's end of the connection is closed by the time the process is killed.
+	process: Option<std::sync::Arc<ChildProcess>>,
+
+	/// The arguments that the connection was opened with, if it was opened with [`Connection::new`]
+	connect_args: Option<std::sync::Arc<ConnectArgs>>,
+}
+
+impl SendHalf {
+	/// See [`Connection::supports_unix_fds`]
+	pub fn supports_unix_fds(&self) -> bool {
+		self.unix_fds
+	}
+
+	/// See [`Connection::send`]
+	pub fn send(&mut self, header: &crate::proto::MessageHeader<'_>, body: Option<&crate::proto::Variant<'_>>) -> Result<(), SendError> {
+		self.send_with_endianness(header, body, self.write_endianness)
+	}
+
+	/// See [`Connection::send_with_endianness`]
+	pub fn send_with_endianness(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: Option<&crate::proto::Variant<'_>>,
+		endianness: crate::proto::Endianness,
+	) -> Result<(), SendError> {
+		self.writer.write_message(header, body, endianness).map_err(SendError::from_write_message_error)?;
+		self.sent.add(&header.r#type);
+		crate::trace::message("sent", header, || body.map(crate::proto::Variant::signature), self.writer.last_message_len());
+		Ok(())
+	}
+
+	/// See [`Connection::send_with_fds`]
+	pub fn send_with_fds(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: Option<&crate::proto::Variant<'_>>,
+		fds: &[std::os::fd::BorrowedFd<'_>],
+	) -> Result<(), SendError> {
+		self.send_with_fds_and_endianness(header, body, fds, self.write_endianness)
+	}
+
+	/// See [`Connection::send_with_fds_and_endianness`]
+	pub fn send_with_fds_and_endianness(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: Option<&crate::proto::Variant<'_>>,
+		fds: &[std::os::fd::BorrowedFd<'_>],
+		endianness: crate::proto::Endianness,
+	) -> Result<(), SendError> {
+		if fds.is_empty() {
+			return self.send_with_endianness(header, body, endianness);
+		}
+
+		if !self.unix_fds {
+			return Err(SendError::UnixFdsNotSupported);
+		}
+
+		let num_fds: u32 = fds.len().try_into().map_err(|err| SendError::Serialize(crate::proto::SerializeError::ExceedsNumericLimits(err)))?;
+
+		let mut header = header.clone();
+		header.fields.to_mut().push(crate::proto::MessageHeaderField::UnixFds(num_fds));
+
+		self.writer.get_mut().set_fds(fds);
+		let result = self.send_with_endianness(&header, body, endianness);
+		// The file descriptors are only borrowed for the duration of this call, so they must not be sent with a later message
+		// if this one was not written.
+		self.writer.get_mut().clear_fds();
+		result
+	}
+
+	/// See [`Connection::send_serialized`]
+	pub fn send_serialized<T>(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: &T,
+		body_signature: &crate::proto::Signature,
+	) -> Result<(), SendError> where T: serde::Serialize + ?Sized {
+		self.send_serialized_with_endianness(header, body, body_signature, self.write_endianness)
+	}
+
+	/// See [`Connection::send_serialized_with_endianness`]
+	pub fn send_serialized_with_endianness<T>(
+		&mut self,
+		header: &crate::proto::MessageHeader<'_>,
+		body: &T,
+		body_signature: &crate::proto::Signature,
+		endianness: crate::proto::Endianness,
+	) -> Result<(), SendError> where T: serde::Serialize + ?Sized {
+		self.writer.write_message_serialized(header, body, body_signature, endianness).map_err(SendError::from_write_message_error)?;
+		self.sent.add(&header.r#type);
+		crate::trace::message("sent", header, || Some(body_signature.clone()), self.writer.last_message_len());
+		Ok(())
+	}
+
+	/// See [`Connection::flush`]
+	pub fn flush(&mut self) -> std::io::Result<()> {
+		self.writer.flush()
+	}
+
+	/// See [`Connection::flush_pending`]
+	pub fn flush_pending(&mut self) -> Result<(), SendError> {
+		self.flush().map_err(|err| SendError::from_write_message_error(crate::proto::WriteMessageError::Io(err)))
+	}
+
+	/// See [`Connection::has_pending_write`]
+	pub fn has_pending_write(&self) -> bool {
+		self.writer.has_pending_write()
+	}
+
+	/// The statistics of the messages sent over the connection. The fields for received messages are zero.
+	pub fn stats(&self) -> ConnectionStats {
+		ConnectionStats {
+			sent: self.sent,
+			bytes_sent: self.writer.get_ref().bytes_written(),
+			..Default::default()
+		}
+	}
+
+	/// Reset the statistics of the messages sent over the connection.
+	pub fn reset_stats(&mut self) {
+		self.sent = Default::default();
+		self.writer.get_mut().reset_bytes_written();
+	}
+
+	/// See [`Connection::set_write_endianness`]
+	pub fn set_write_endianness(&mut self, endianness: crate::proto::Endianness) {
+		self.write_endianness = endianness;
+	}
+
+	/// See [`Connection::reconnect`]
+	pub(crate) fn reconnect(&self) -> Result<Connection, ConnectError> {
+		let connect_args = self.connect_args.as_ref().ok_or(ConnectError::NotReconnectable)?;
+		connect_args.connect()
+	}
+
+	/// Set the limits on the sizes of sent messages. See [`Connection::set_limits`]
+	pub fn set_limits(&mut self, limits: crate::proto::MessageLimits) {
+		self.writer.set_limits(limits);
+	}
+
+	/// See [`Connection::write_timeout`]
+	pub fn write_timeout(&self) -> Option<std::time::Duration> {
+		self.write_timeout
+	}
+
+	/// See [`Connection::set_write_timeout`]
+	pub fn set_write_timeout(&mut self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+		self.writer.get_mut().stream().set_write_timeout(timeout)?;
+		self.write_timeout = timeout;
+		Ok(())
+	}
+}
+
+/// The file descriptor of the socket of the send half, to wait for it to become writable. See the impl for [`Connection`] for details.
+impl std::os::fd::AsFd for SendHalf {
+	fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+		self.writer.get_ref().stream().as_fd()
+	}
+}
+
+/// The half of a [`Connection`] that receives messages. See [`Connection::split`]
+pub struct RecvHalf {
+	reader: crate::proto::MessageReader<std::io::BufReader<crate::fd::FdReader>>,
+
+	/// The number of received file descriptors that belong to messages returned by [`RecvHalf::recv_ref`],
+	/// and are discarded before the next message is received.
+	unclaimed_fds: usize,
+
+	read_timeout: Option<std::time::Duration>,
+	received: MessageCounts,
+
+	/// The process spawned for a `unixexec:` address, shared with the [`SendHalf`]. This is declared after the reader
+	/// so that the process's end of the connection is closed by the time the process is killed.
+	process: Option<std::sync::Arc<ChildProcess>>,
+}
+
+impl RecvHalf {
+	/// See [`Connection::peer_credentials`]
+	pub fn peer_credentials(&self) -> std::io::Result<PeerCredentials> {
+		self.reader.get_ref().get_ref().stream().peer_credentials()
+	}
+
+	/// See [`Connection::has_buffered_data`]
+	pub fn has_buffered_data(&self) -> bool {
+		!self.reader.get_ref().buffer().is_empty()
+	}
+
+	/// See [`Connection::recv`]
+	pub fn recv(&mut self) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), RecvError> {
+		let (message_header, message_body, _) = self.recv_with_endianness()?;
+		Ok((message_header, message_body))
+	}
+
+	/// See [`Connection::try_recv`]
+	pub fn try_recv(&mut self) -> Result<Option<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>)>, RecvError> {
+		match self.recv() {
+			Ok(message) => Ok(Some(message)),
+			Err(RecvError::TimedOut) => Ok(None),
+			Err(err) => Err(err),
+		}
+	}
+
+	/// See [`Connection::recv_with_endianness`]
+	pub fn recv_with_endianness(
+		&mut self,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>, crate::proto::Endianness), RecvError> {
+		let (header, body, endianness) = self.recv_inner()?;
+		let num_fds = header.unix_fds().unwrap_or_default();
+		drop(self.reader.get_mut().get_mut().take_fds(num_fds as usize));
+		Ok((header, body, endianness))
+	}
+
+	/// See [`Connection::recv_with_fds`]
+	pub fn recv_with_fds(
+		&mut self,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>, Vec<std::os::fd::OwnedFd>), RecvError> {
+		let (header, body, _, fds) = self.recv_with_endianness_and_fds()?;
+		Ok((header, body, fds))
+	}
+
+	/// Like [`RecvHalf::recv_with_fds`], but also returns the endianness that the sender used for the message.
+	pub(crate) fn recv_with_endianness_and_fds(
+		&mut self,
+	) -> Result<
+		(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>, crate::proto::Endianness, Vec<std::os::fd::OwnedFd>),
+		RecvError,
+	> {
+		let (header, body, endianness) = self.recv_inner()?;
+		let num_fds = header.unix_fds().unwrap_or_default();
+		let fds = self.reader.get_mut().get_mut().take_fds(num_fds as usize);
+		if fds.len() != num_fds as usize {
+			return Err(RecvError::MissingUnixFds { expected: num_fds, received: fds.len() });
+		}
+		Ok((header, body, endianness, fds))
+	}
+
+	fn recv_inner(
+		&mut self,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>, crate::proto::Endianness), RecvError> {
+		self.discard_unclaimed_fds();
+
+		let message = self.reader.read_message().map_err(RecvError::from_read_message_error)?;
+		self.received.add(&message.0.r#type);
+		crate::trace::message("received", &message.0, || message.0.signature().cloned(), self.reader.last_message_len());
+		Ok(message)
+	}
+
+	fn discard_unclaimed_fds(&mut self) {
+		drop(self.reader.get_mut().get_mut().take_fds(self.unclaimed_fds));
+		self.unclaimed_fds = 0;
+	}
+
+	/// See [`Connection::recv_ref`]
+	pub fn recv_ref(&mut self) -> Result<crate::proto::MessageRef<'_>, RecvError> {
+		self.discard_unclaimed_fds();
+
+		let message = self.reader.read_message_ref().map_err(RecvError::from_read_message_error)?;
+		self.received.add(&message.header.r#type);
+		crate::trace::message("received", &message.header, || message.header.signature().cloned(), message.len);
+
+		// The message borrows the reader, so its file descriptors are discarded when the next message is received.
+		self.unclaimed_fds = message.header.unix_fds().unwrap_or_default() as usize;
+
+		Ok(message)
+	}
+
+	/// The statistics of the messages received over the connection. The fields for sent messages are zero.
+	pub fn stats(&self) -> ConnectionStats {
+		ConnectionStats {
+			received: self.received,
+			bytes_received: self.reader.get_ref().get_ref().bytes_read(),
+			read_buffer_capacity: self.reader.buffer_capacity(),
+			..Default::default()
+		}
+	}
+
+	/// Reset the statistics of the messages received over the connection.
+	pub fn reset_stats(&mut self) {
+		self.received = Default::default();
+		self.reader.get_mut().get_mut().reset_bytes_read();
+	}
+
+	/// See [`Connection::set_interner`]
+	pub fn set_interner(&mut self, interner: Option<&'static crate::proto::Interner>) {
+		self.reader.set_interner(interner);
+	}
+
+	/// See [`Connection::set_max_read_buffer`]
+	pub fn set_max_read_buffer(&mut self, max_read_buffer: usize) {
+		self.reader.set_max_read_buffer(max_read_buffer);
+	}
+
+	/// Set the limits on the sizes of received messages. See [`Connection::set_limits`]
+	pub fn set_limits(&mut self, limits: crate::proto::MessageLimits) {
+		self.reader.set_limits(limits);
+	}
+
+	/// See [`Connection::set_nonblocking`]
+	///
+	/// This is not public, since the clones of the socket share its non-blocking mode, so it would also affect the [`SendHalf`].
+	pub(crate) fn set_nonblocking(&mut self, nonblocking: bool) -> std::io::Result<()> {
+		self.reader.get_mut().get_mut().stream().set_nonblocking(nonblocking)
+	}
+
+	/// See [`Connection::read_timeout`]
+	pub fn read_timeout(&self) -> Option<std::time::Duration> {
+		self.read_timeout
+	}
+
+	/// See [`Connection::set_read_timeout`]
+	pub fn set_read_timeout(&mut self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+		self.reader.get_mut().get_mut().stream().set_read_timeout(timeout)?;
+		self.read_timeout = timeout;
+		Ok(())
+	}
+}
+
+/// The file descriptor of the socket of the receive half, to wait for it to become readable. See the impl for [`Connection`] for details.
+impl std::os::fd::AsFd for RecvHalf {
+	fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+		self.reader.get_ref().get_ref().stream().as_fd()
+	}
+}
+
+/// An error from connecting to a message bus.
+#[derive(Debug)]
+pub enum ConnectError {
+	/// The socket passed by systemd socket activation could not be taken. See [`Connection::from_activation`]
+	Activation(ActivationError),
+
+	Authenticate(std::io::Error),
+
+	/// The bus rejected every authentication mechanism that was tried. `offered` are the mechanisms that it supports.
+	AuthRejected { offered: Vec<String> },
+
+	Connect(Vec<(std::path::PathBuf, std::io::Error)>),
+
+	/// The nonce file of a `nonce-tcp:` address does not contain a 16-byte nonce.
+	///
+	/// This is the inner error of the `std::io::Error` that [`ConnectError::Connect`] records for the nonce file,
+	/// so that the remaining addresses are still tried.
+	InvalidNonceFile { path: std::path::PathBuf, len: usize },
+
+	MissingSessionBusEnvVar,
+
+	/// The server of a `nonce-tcp:` address closed the connection after the nonce was sent.
+	NonceRejected,
+
+	/// The connection was not opened with [`Connection::new`], so [`Connection::reconnect`] can't open it again.
+	NotReconnectable,
+
+	/// The nonce file of a `nonce-tcp:` address could not be read.
+	///
+	/// This is the inner error of the `std::io::Error` that [`ConnectError::Connect`] records for the nonce file,
+	/// so that the remaining addresses are still tried.
+	ReadNonceFile(std::path::PathBuf, std::io::Error),
+
+	/// The server has a different GUID than the `guid` of the bus address.
+	ServerGuidMismatch { expected: ServerGuid, actual: ServerGuid },
+
+	UnsupportedTransport(std::ffi::OsString),
+}
+
+impl std::fmt::Display for ConnectError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ConnectError::Activation(_) => f.write_str("could not take the socket passed by socket activation"),
+
+			ConnectError::Authenticate(_) => f.write_str("could not authenticate with bus"),
+
+			ConnectError::AuthRejected { offered } => write!(f, "the bus rejected authentication, and offered [{}]", offered.join(", ")),
+
+			ConnectError::Connect(inner) => {
+				f.write_str("could not connect to any bus paths: [")?;
+				for (i, (bus_path, err)) in inner.iter().enumerate() {
+					if i > 0 {
+						f.write_str(", ")?;
+					}
+
+					write!(f, "{bus_path:?}: {:?}", err.to_string())?;
+				}
+				f.write_str("]")?;
+				Ok(())
+			},
+
+			ConnectError::InvalidNonceFile { path, len } =>
+				write!(f, "the nonce file {} has {len} bytes instead of {}", path.display(), std::mem::size_of::<Nonce>()),
+
+			ConnectError::MissingSessionBusEnvVar => f.write_str("the DBUS_SESSION_BUS_ADDRESS env var is not set"),
+
+			ConnectError::NonceRejected => f.write_str("the bus closed the connection after the nonce was sent"),
+
+			ConnectError::NotReconnectable => f.write_str("the connection was not opened with a bus path, so it can't be reopened"),
+
+			ConnectError::ReadNonceFile(path, _) => write!(f, "could not read the nonce file {}", path.display()),
+
+			ConnectError::ServerGuidMismatch { expected, actual } =>
+				write!(f, "the bus has the GUID {actual} but the bus address has the GUID {expected}"),
+
+			ConnectError::UnsupportedTransport(value) => write!(f, "the bus path {value:?} has an unsupported transport"),
+		}
+	}
+}
 
 impl std::error::Error for ConnectError {
 	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
 		#[allow(clippy::match_same_arms)]
 		match self {
+			ConnectError::Activation(err) => Some(err),
 			ConnectError::Authenticate(err) => Some(err),
+			ConnectError::AuthRejected { offered: _ } => None,
 			ConnectError::Connect(_) => None,
+			ConnectError::InvalidNonceFile { path: _, len: _ } => None,
 			ConnectError::MissingSessionBusEnvVar => None,
+			ConnectError::NonceRejected => None,
+			ConnectError::NotReconnectable => None,
+			ConnectError::ReadNonceFile(_, err) => Some(err),
+			ConnectError::ServerGuidMismatch { expected: _, actual: _ } => None,
 			ConnectError::UnsupportedTransport(_) => None,
 		}
 	}
 }
 
+/// An error from taking the socket that systemd passed to this process with socket activation.
+/// See [`Connection::from_activation`] and [`Connection::activation_listener`]
+#[derive(Debug)]
+pub enum ActivationError {
+	/// An environment variable is not a valid number.
+	InvalidEnvVar { name: &'static str, value: std::ffi::OsString },
+
+	Io(std::io::Error),
+
+	/// An environment variable is not set, so this process was not socket-activated.
+	MissingEnvVar { name: &'static str },
+
+	/// The file descriptors were passed to a different process, from which this process inherited the environment variables.
+	PidMismatch { listen_pid: u32, pid: u32 },
+
+	/// A number of file descriptors other than one was passed.
+	UnexpectedNumberOfFds(u32),
+
+	/// The file descriptor that was passed is not a unix socket of the expected kind.
+	UnexpectedSocket,
+}
+
+impl std::fmt::Display for ActivationError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ActivationError::InvalidEnvVar { name, value } => write!(f, "the {name} env var has the invalid value {:?}", value.display()),
+			ActivationError::Io(_) => f.write_str("could not take the passed file descriptor"),
+			ActivationError::MissingEnvVar { name } => write!(f, "the {name} env var is not set"),
+			ActivationError::PidMismatch { listen_pid, pid } =>
+				write!(f, "the file descriptors were passed to process {listen_pid} instead of this process {pid}"),
+			ActivationError::UnexpectedNumberOfFds(num_fds) => write!(f, "expected one file descriptor to be passed but {num_fds} were passed"),
+			ActivationError::UnexpectedSocket => f.write_str("the passed file descriptor is not a unix socket of the expected kind"),
+		}
+	}
+}
+
+impl std::error::Error for ActivationError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		#[allow(clippy::match_same_arms)]
+		match self {
+			ActivationError::InvalidEnvVar { name: _, value: _ } => None,
+			ActivationError::Io(err) => Some(err),
+			ActivationError::MissingEnvVar { name: _ } => None,
+			ActivationError::PidMismatch { listen_pid: _, pid: _ } => None,
+			ActivationError::UnexpectedNumberOfFds(_) => None,
+			ActivationError::UnexpectedSocket => None,
+		}
+	}
+}
+
 /// An error from sending a message using a [`Connection::send`].
 #[derive(Debug)]
 pub enum SendError {
+	InvalidSender(crate::proto::validate::InvalidName),
 	Io(std::io::Error),
 	Serialize(crate::proto::SerializeError),
+	UnixFdsNotSupported,
+
+	/// The message could not be sent without blocking, because the connection is in non-blocking mode or its write timeout elapsed.
+	///
+	/// None of the message was sent. Call [`Connection::flush_pending`] until it succeeds, then send the message again.
+	WouldBlock,
+}
+
+impl SendError {
+	fn from_write_message_error(err: crate::proto::WriteMessageError) -> Self {
+		match err {
+			crate::proto::WriteMessageError::Io(err) if err.kind() == std::io::ErrorKind::WouldBlock => SendError::WouldBlock,
+			crate::proto::WriteMessageError::Io(err) => SendError::Io(err),
+			crate::proto::WriteMessageError::Serialize(err) => SendError::Serialize(err),
+		}
+	}
 }
 
 impl std::fmt::Display for SendError {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match self {
+			SendError::InvalidSender(_) => f.write_str("sender is not a valid bus name"),
 			SendError::Io(_) => f.write_str("could not send message"),
 			SendError::Serialize(_) => f.write_str("could not serialize message"),
+			SendError::UnixFdsNotSupported => f.write_str("the message bus does not support passing unix file descriptors"),
+			SendError::WouldBlock => f.write_str("could not send message without blocking"),
 		}
 	}
 }
@@ -256,8 +1339,11 @@ impl std::fmt::Display for SendError {
 impl std::error::Error for SendError {
 	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
 		match self {
+			SendError::InvalidSender(err) => Some(err),
 			SendError::Io(err) => Some(err),
 			SendError::Serialize(err) => Some(err),
+			SendError::UnixFdsNotSupported |
+			SendError::WouldBlock => None,
 		}
 	}
 }
@@ -267,6 +1353,19 @@ impl std::error::Error for SendError {
 pub enum RecvError {
 	Deserialize(crate::proto::DeserializeError),
 	Io(std::io::Error),
+	MissingUnixFds { expected: u32, received: usize },
+	TimedOut,
+}
+
+impl RecvError {
+	fn from_read_message_error(err: crate::proto::ReadMessageError) -> Self {
+		match err {
+			crate::proto::ReadMessageError::Deserialize(err) => RecvError::Deserialize(err),
+			crate::proto::ReadMessageError::Io(err) if matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) =>
+				RecvError::TimedOut,
+			crate::proto::ReadMessageError::Io(err) => RecvError::Io(err),
+		}
+	}
 }
 
 impl std::fmt::Display for RecvError {
@@ -274,65 +1373,1418 @@ impl std::fmt::Display for RecvError {
 		match self {
 			RecvError::Deserialize(_) => f.write_str("could not deserialize message"),
 			RecvError::Io(_) => f.write_str("could not receive message"),
+			RecvError::MissingUnixFds { expected, received } =>
+				write!(f, "message has {expected} unix file descriptors but only {received} were received"),
+			RecvError::TimedOut => f.write_str("timed out receiving message"),
 		}
 	}
 }
 
 impl std::error::Error for RecvError {
 	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		#[allow(clippy::match_same_arms)]
 		match self {
 			RecvError::Deserialize(err) => Some(err),
 			RecvError::Io(err) => Some(err),
+			RecvError::MissingUnixFds { expected: _, received: _ } => None,
+			RecvError::TimedOut => None,
 		}
 	}
 }
 
-fn connect(bus_address: &std::ffi::OsStr) -> Result<std::os::unix::net::UnixStream, ConnectError> {
+/// The nonce that a client must send before the SASL handshake when connecting to a `nonce-tcp:` address.
+pub(crate) type Nonce = [u8; 16];
+
+fn malformed_response() -> ConnectError {
+	ConnectError::Authenticate(std::io::Error::other("malformed response"))
+}
+
+/// Parses a `REJECTED` line of the SASL handshake into the mechanisms that the server supports.
+fn parse_rejected(line: &[u8]) -> Option<Vec<String>> {
+	let offered = line.strip_prefix(b"REJECTED")?;
+	if !offered.is_empty() && !offered.starts_with(b" ") {
+		return None;
+	}
+
+	Some(
+		offered.split(|&b| b == b' ')
+		.filter(|mechanism| !mechanism.is_empty())
+		.map(|mechanism| String::from_utf8_lossy(mechanism).into_owned())
+		.collect(),
+	)
+}
+
+/// A process spawned for a `unixexec:` address, which is killed and reaped when dropped.
+#[derive(Debug)]
+struct ChildProcess(std::process::Child);
+
+impl Drop for ChildProcess {
+	fn drop(&mut self) {
+		let _ = self.0.kill();
+		let _ = self.0.wait();
+	}
+}
+
+/// A stream connected to a bus address, along with the nonce to send and the GUID to expect when authenticating over it,
+/// and the process that was spawned for a `unixexec:` address.
+type Connected = (crate::fd::Stream, Option<Nonce>, Option<ServerGuid>, Option<ChildProcess>);
+
+fn connect(bus_address: &std::ffi::OsStr) -> Result<Connected, ConnectError> {
 	let bus_address_bytes = std::os::unix::ffi::OsStrExt::as_bytes(bus_address);
 
 	let mut connect_errs = vec![];
 
 	for bus_address_bytes in bus_address_bytes.split(|&b| b == b';') {
-		if !bus_address_bytes.starts_with(b"unix:") {
+		if bus_address_bytes.is_empty() {
 			continue;
 		}
-		let bus_address_bytes = &bus_address_bytes[b"unix:".len()..];
-
-		let path =
-			bus_address_bytes.split(|&b| b == b',')
-			.find_map(|pair| {
-				let mut pair_parts = pair.splitn(2, |&b| b == b'=');
-
-				let key = pair_parts.next().expect("split returns at least one subslice");
-				if let Ok(key) = percent_encoding::percent_decode(key).decode_utf8() {
-					if key == "path" {
-						// We want to stop at the first `path` component even if it has no value,
-						// so return `Some(None)` in that case rather than `None`.
-						let value =
-							pair_parts.next()
-							.map(|value| {
-								let value: Vec<u8> = percent_encoding::percent_decode(value).collect();
-								let value: &std::ffi::OsStr = std::os::unix::ffi::OsStrExt::from_bytes(&value);
-								let value: std::path::PathBuf = value.into();
-								value
-							});
-						return Some(value);
-					}
-				}
 
-				None
-			});
-		if let Some(Some(path)) = path {
-			let stream = std::os::unix::net::UnixStream::connect(&path);
-			match stream {
-				Ok(stream) => return Ok(stream),
-				Err(err) => {
-					connect_errs.push((path, err));
+		// There is no filesystem path for most kinds of addresses, so the whole address is used to identify them instead.
+		let address: &std::ffi::OsStr = std::os::unix::ffi::OsStrExt::from_bytes(bus_address_bytes);
+		let address: std::path::PathBuf = address.into();
+
+		let (transport, pairs) = match parse_address(bus_address_bytes) {
+			Ok(parsed) => parsed,
+			Err(err) => {
+				connect_errs.push((address, err));
+				continue;
+			},
+		};
+
+		let guid = match pairs.get("guid").map(|guid| ServerGuid::parse(guid)) {
+			Some(Some(guid)) => Some(guid),
+			Some(None) => {
+				connect_errs.push((address, std::io::Error::new(std::io::ErrorKind::InvalidInput, "guid is not 32 hex digits")));
+				continue;
+			},
+			None => None,
+		};
+
+		match transport {
+			b"unix" => match unix_address(&pairs) {
+				Ok(UnixAddress::Path(path)) => match std::os::unix::net::UnixStream::connect(&path) {
+					Ok(stream) => return Ok((crate::fd::Stream::Unix(stream), None, guid, None)),
+					Err(err) => connect_errs.push((path, err)),
+				},
+
+				Ok(UnixAddress::Abstract(name)) => match connect_abstract(&name) {
+					Ok(stream) => return Ok((crate::fd::Stream::Unix(stream), None, guid, None)),
+					Err(err) => connect_errs.push((address, err)),
+				},
+
+				Err(err) => connect_errs.push((address, err)),
+			},
+
+			b"tcp" => match connect_tcp(&pairs) {
+				Ok(stream) => return Ok((crate::fd::Stream::Tcp(stream), None, guid, None)),
+				Err(err) => connect_errs.push((address, err)),
+			},
+
+			b"nonce-tcp" => {
+				let Some(nonce_file) = pairs.get("noncefile") else {
+					connect_errs.push((address, std::io::Error::new(std::io::ErrorKind::InvalidInput, "missing noncefile")));
 					continue;
+				};
+				let nonce_file: &std::ffi::OsStr = std::os::unix::ffi::OsStrExt::from_bytes(nonce_file);
+				let nonce = match read_nonce_file(nonce_file.as_ref()) {
+					Ok(nonce) => nonce,
+					Err(err) => {
+						connect_errs.push((nonce_file.into(), err));
+						continue;
+					},
+				};
+
+				match connect_tcp(&pairs) {
+					Ok(stream) => return Ok((crate::fd::Stream::Tcp(stream), Some(nonce), guid, None)),
+					Err(err) => connect_errs.push((address, err)),
+				}
+			},
+
+			b"unixexec" => match spawn_unixexec(&pairs) {
+				Ok((stream, process)) => return Ok((crate::fd::Stream::Unix(stream), None, guid, Some(process))),
+				Err(err) => connect_errs.push((address, err)),
+			},
+
+			#[cfg(target_os = "macos")]
+			b"launchd" => match launchd_socket_path(&pairs, launchctl_getenv) {
+				Ok(path) => match std::os::unix::net::UnixStream::connect(&path) {
+					Ok(stream) => return Ok((crate::fd::Stream::Unix(stream), None, guid, None)),
+					Err(err) => connect_errs.push((path, err)),
 				},
-			}
+				Err(err) => connect_errs.push((address, err)),
+			},
+
+			// Clients skip addresses with transports they don't support, and try the next one.
+			_ => (),
 		}
 	}
 
+	if connect_errs.is_empty() {
+		return Err(ConnectError::UnsupportedTransport(bus_address.to_owned()));
+	}
+
 	Err(ConnectError::Connect(connect_errs))
 }
+
+/// The percent-decoded key-value pairs of a bus address.
+type AddressPairs = std::collections::BTreeMap<String, Vec<u8>>;
+
+/// Splits a bus address into its transport and its key-value pairs.
+///
+/// Fails if the address does not have a transport, or if a pair is malformed or has the same key as an earlier pair.
+fn parse_address(address: &[u8]) -> std::io::Result<(&[u8], AddressPairs)> {
+	let invalid_input = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+
+	let (transport, pairs_bytes) =
+		address.iter().position(|&b| b == b':')
+		.map(|pos| (&address[..pos], &address[(pos + 1)..]))
+		.ok_or_else(|| invalid_input("address does not have a transport"))?;
+	if transport.is_empty() {
+		return Err(invalid_input("address does not have a transport"));
+	}
+
+	let mut pairs = AddressPairs::new();
+
+	if pairs_bytes.is_empty() {
+		return Ok((transport, pairs));
+	}
+
+	for pair in pairs_bytes.split(|&b| b == b',') {
+		let mut pair_parts = pair.splitn(2, |&b| b == b'=');
+
+		let key = pair_parts.next().expect("split returns at least one subslice");
+		let value = pair_parts.next().ok_or_else(|| invalid_input("address has a key without a value"))?;
+
+		let key = percent_encoding::percent_decode(key).decode_utf8().map_err(|_| invalid_input("address has a key that is not valid UTF-8"))?;
+		if key.is_empty() {
+			return Err(invalid_input("address has an empty key"));
+		}
+
+		let value = percent_encoding::percent_decode(value).collect();
+		if pairs.insert(key.into_owned(), value).is_some() {
+			return Err(invalid_input("address has a duplicate key"));
+		}
+	}
+
+	Ok((transport, pairs))
+}
+
+/// The socket that a `unix:` address refers to.
+#[derive(Debug, PartialEq)]
+enum UnixAddress {
+	/// A unix socket file at the specified filesystem path
+	Path(std::path::PathBuf),
+
+	/// A unix socket with the specified name in the abstract namespace
+	Abstract(Vec<u8>),
+}
+
+/// Resolves the socket that the key-value pairs of a `unix:` address refer to.
+///
+/// Exactly one of the `path`, `abstract`, `runtime`, `tmpdir` and `dir` keys must be present.
+/// `runtime=yes` refers to the `bus` socket in `$XDG_RUNTIME_DIR`. `tmpdir` and `dir` are only meaningful for servers,
+/// so they are rejected.
+fn unix_address(pairs: &AddressPairs) -> std::io::Result<UnixAddress> {
+	let invalid_input = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+
+	let mut keys = ["path", "abstract", "runtime", "tmpdir", "dir"].into_iter().filter_map(|key| pairs.get_key_value(key));
+	let (key, value) = keys.next().ok_or_else(|| invalid_input("unix address has none of path, abstract or runtime"))?;
+	if keys.next().is_some() {
+		return Err(invalid_input("unix address has more than one of path, abstract, runtime, tmpdir and dir"));
+	}
+
+	match &**key {
+		"path" => {
+			let path: &std::ffi::OsStr = std::os::unix::ffi::OsStrExt::from_bytes(value);
+			Ok(UnixAddress::Path(path.into()))
+		},
+
+		"abstract" => Ok(UnixAddress::Abstract(value.clone())),
+
+		"runtime" => {
+			if value != b"yes" {
+				return Err(invalid_input("unix address has a runtime value other than yes"));
+			}
+
+			let runtime_dir =
+				std::env::var_os("XDG_RUNTIME_DIR")
+				.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "the XDG_RUNTIME_DIR env var is not set"))?;
+			Ok(UnixAddress::Path(std::path::Path::new(&runtime_dir).join("bus")))
+		},
+
+		_ => Err(invalid_input("unix addresses with tmpdir or dir can only be listened on")),
+	}
+}
+
+/// Connects to the unix socket with the given name in the abstract namespace.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn connect_abstract(name: &[u8]) -> std::io::Result<std::os::unix::net::UnixStream> {
+	#[cfg(target_os = "android")]
+	use std::os::android::net::SocketAddrExt;
+	#[cfg(target_os = "linux")]
+	use std::os::linux::net::SocketAddrExt;
+
+	let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+	std::os::unix::net::UnixStream::connect_addr(&addr)
+}
+
+/// Connects to the unix socket with the given name in the abstract namespace.
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn connect_abstract(_name: &[u8]) -> std::io::Result<std::os::unix::net::UnixStream> {
+	Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "abstract unix sockets are not supported on this platform"))
+}
+
+/// Resolves the socket file that the key-value pairs of a `launchd:` address refer to.
+///
+/// The path of the socket is the value of the launchd environment variable named by the `env` key, which is looked up with `getenv`.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn launchd_socket_path(
+	pairs: &AddressPairs,
+	getenv: impl FnOnce(&std::ffi::OsStr) -> std::io::Result<Option<std::ffi::OsString>>,
+) -> std::io::Result<std::path::PathBuf> {
+	let name = pairs.get("env").ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "launchd address has no env"))?;
+	let name: &std::ffi::OsStr = std::os::unix::ffi::OsStrExt::from_bytes(name);
+
+	match getenv(name)? {
+		Some(path) if !path.is_empty() => Ok(path.into()),
+		_ => Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("the launchd env var {} is not set", name.display()))),
+	}
+}
+
+/// Gets the value of the given environment variable of launchd, or `None` if it is not set.
+#[cfg(target_os = "macos")]
+fn launchctl_getenv(name: &std::ffi::OsStr) -> std::io::Result<Option<std::ffi::OsString>> {
+	let output =
+		std::process::Command::new("launchctl")
+		.arg("getenv")
+		.arg(name)
+		.stdin(std::process::Stdio::null())
+		.stderr(std::process::Stdio::null())
+		.output()?;
+	if !output.status.success() {
+		return Err(std::io::Error::other(format!("launchctl getenv exited with {}", output.status)));
+	}
+
+	// launchctl prints the value followed by a newline, or nothing if the variable is not set.
+	let mut value = output.stdout;
+	if value.last() == Some(&b'\n') {
+		let _ = value.pop();
+	}
+	Ok((!value.is_empty()).then(|| std::os::unix::ffi::OsStringExt::from_vec(value)))
+}
+
+/// Spawns the process of the key-value pairs of a `unixexec:` address, with its stdin and stdout connected to the returned socket.
+///
+/// The process is run from `path` with the `argv0`, `argv1`, ... arguments. `argv0` defaults to `path`.
+fn spawn_unixexec(pairs: &AddressPairs) -> std::io::Result<(std::os::unix::net::UnixStream, ChildProcess)> {
+	let path = pairs.get("path").ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "missing path"))?;
+	let path: &std::ffi::OsStr = std::os::unix::ffi::OsStrExt::from_bytes(path);
+
+	let mut command = std::process::Command::new(path);
+
+	if let Some(argv0) = pairs.get("argv0") {
+		let argv0: &std::ffi::OsStr = std::os::unix::ffi::OsStrExt::from_bytes(argv0);
+		let _ = std::os::unix::process::CommandExt::arg0(&mut command, argv0);
+	}
+
+	for i in 1.. {
+		let Some(arg) = pairs.get(&format!("argv{i}")) else { break; };
+		let arg: &std::ffi::OsStr = std::os::unix::ffi::OsStrExt::from_bytes(arg);
+		let _ = command.arg(arg);
+	}
+
+	let (stream, process_stream) = std::os::unix::net::UnixStream::pair()?;
+	let process_stdin = std::os::fd::OwnedFd::from(process_stream.try_clone()?);
+	let process_stdout = std::os::fd::OwnedFd::from(process_stream);
+	let process = command.stdin(process_stdin).stdout(process_stdout).spawn()?;
+
+	// The command holds the process's ends of the socket, which must be closed so that the process sees EOF
+	// when the connection is closed.
+	drop(command);
+
+	Ok((stream, ChildProcess(process)))
+}
+
+/// The first file descriptor that systemd passes with socket activation, `SD_LISTEN_FDS_START`
+const SD_LISTEN_FDS_START: std::os::fd::RawFd = 3;
+
+/// Takes the file descriptor that systemd passed to this process with socket activation, as `sd_listen_fds` does.
+///
+/// The environment variables are removed, so that the file descriptor is not taken again.
+fn take_activation_fd() -> Result<std::os::fd::OwnedFd, ActivationError> {
+	let num_fds = activation_num_fds(std::env::var_os("LISTEN_PID"), std::env::var_os("LISTEN_FDS"), std::process::id())?;
+	if num_fds != 1 {
+		return Err(ActivationError::UnexpectedNumberOfFds(num_fds));
+	}
+
+	for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+		std::env::remove_var(name);
+	}
+
+	// The file descriptor is owned from here on, so it must not leak into processes that this process spawns.
+	crate::fd::set_cloexec(SD_LISTEN_FDS_START).map_err(ActivationError::Io)?;
+
+	// SAFETY: The file descriptor is open, since setting its flags succeeded. It was passed to this process for it to take,
+	// and the environment variables that said so were removed, so nothing else in this process takes ownership of it.
+	Ok(unsafe { std::os::fd::FromRawFd::from_raw_fd(SD_LISTEN_FDS_START) })
+}
+
+/// Gets the number of file descriptors that were passed to the process with the given pid,
+/// from the values of the `LISTEN_PID` and `LISTEN_FDS` environment variables.
+fn activation_num_fds(
+	listen_pid: Option<std::ffi::OsString>,
+	listen_fds: Option<std::ffi::OsString>,
+	pid: u32,
+) -> Result<u32, ActivationError> {
+	let parse = |name, value: Option<std::ffi::OsString>| {
+		let value = value.ok_or(ActivationError::MissingEnvVar { name })?;
+		match value.to_str().and_then(|value| value.parse().ok()) {
+			Some(value) => Ok(value),
+			None => Err(ActivationError::InvalidEnvVar { name, value }),
+		}
+	};
+
+	let listen_pid = parse("LISTEN_PID", listen_pid)?;
+	if listen_pid != pid {
+		return Err(ActivationError::PidMismatch { listen_pid, pid });
+	}
+
+	parse("LISTEN_FDS", listen_fds)
+}
+
+/// Checks that a file descriptor that was passed with socket activation is a unix socket of the expected kind.
+fn activation_socket(fd: std::os::fd::OwnedFd, expected: crate::fd::SocketKind) -> Result<std::os::fd::OwnedFd, ActivationError> {
+	let kind = crate::fd::socket_kind(std::os::fd::AsFd::as_fd(&fd)).map_err(ActivationError::Io)?;
+	if kind != expected {
+		return Err(ActivationError::UnexpectedSocket);
+	}
+
+	Ok(fd)
+}
+
+/// Reads the nonce from the nonce file of a `nonce-tcp:` address.
+///
+/// The error is a [`ConnectError::ReadNonceFile`] or [`ConnectError::InvalidNonceFile`] wrapped in a `std::io::Error`,
+/// so that it can be recorded in [`ConnectError::Connect`] like the errors of other addresses.
+fn read_nonce_file(path: &std::path::Path) -> std::io::Result<Nonce> {
+	let nonce =
+		std::fs::read(path)
+		.map_err(|err| std::io::Error::new(err.kind(), ConnectError::ReadNonceFile(path.to_owned(), err)))?;
+	let len = nonce.len();
+	nonce.try_into().map_err(|_| std::io::Error::new(
+		std::io::ErrorKind::InvalidData,
+		ConnectError::InvalidNonceFile { path: path.to_owned(), len },
+	))
+}
+
+/// Connects to the host and port of the key-value pairs of a `tcp:` or `nonce-tcp:` address,
+/// restricted to the address family if there is one.
+///
+/// The host defaults to `localhost` if it is not specified.
+fn connect_tcp(pairs: &AddressPairs) -> std::io::Result<std::net::TcpStream> {
+	let host =
+		pairs.get("host").map_or(Ok("localhost"), |host| std::str::from_utf8(host))
+		.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid host"))?;
+
+	let port: u16 =
+		pairs.get("port")
+		.and_then(|port| std::str::from_utf8(port).ok())
+		.and_then(|port| port.parse().ok())
+		.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "missing or invalid port"))?;
+
+	let family_filter: fn(&std::net::SocketAddr) -> bool = match pairs.get("family").map(Vec::as_slice) {
+		None => |_| true,
+		Some(b"ipv4") => std::net::SocketAddr::is_ipv4,
+		Some(b"ipv6") => std::net::SocketAddr::is_ipv6,
+		Some(_) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid family")),
+	};
+
+	let addrs: Vec<_> = std::net::ToSocketAddrs::to_socket_addrs(&(host, port))?.filter(family_filter).collect();
+	if addrs.is_empty() {
+		return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "host has no addresses of the requested family"));
+	}
+
+	std::net::TcpStream::connect(&*addrs)
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_unix_fds() {
+		use std::io::{BufRead, Read, Write};
+
+		let (client_stream, server_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+
+		// A peer that authenticates the client and then echoes everything back to it, along with the file descriptors received with it.
+		let _ = std::thread::spawn(move || {
+			let mut reader = std::io::BufReader::new(crate::fd::FdReader::new(crate::fd::Stream::Unix(server_stream.try_clone().unwrap())));
+			let mut writer = crate::fd::FdWriter::new(crate::fd::Stream::Unix(server_stream));
+
+			let mut line = vec![];
+			for response in [&b"OK 0123456789abcdef0123456789abcdef\r\n"[..], b"AGREE_UNIX_FD\r\n"] {
+				line.clear();
+				let _ = reader.read_until(b'\n', &mut line).unwrap();
+				writer.write_all(response).unwrap();
+			}
+			line.clear();
+			let _ = reader.read_until(b'\n', &mut line).unwrap();
+			assert_eq!(line, b"BEGIN\r\n");
+
+			loop {
+				let buf = reader.fill_buf().unwrap().to_owned();
+				if buf.is_empty() {
+					break;
+				}
+				reader.consume(buf.len());
+
+				let fds = reader.get_mut().take_fds(usize::MAX);
+				writer.set_fds(&fds.iter().map(std::os::fd::AsFd::as_fd).collect::<Vec<_>>());
+				writer.write_all(&buf).unwrap();
+			}
+		});
+
+		let mut connection = super::Connection::from_unix_stream(client_stream, super::SaslAuthType::Uid).unwrap();
+		assert!(connection.supports_unix_fds());
+
+		let header = crate::proto::MessageHeader {
+			r#type: crate::proto::MessageType::Signal {
+				interface: "org.example".into(),
+				member: "Pipe".into(),
+				path: crate::proto::ObjectPath("/".into()),
+			},
+			flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+			body_len: 0,
+			serial: 1,
+			fields: vec![].into(),
+		};
+		let body = crate::proto::Variant::UnixFd(crate::proto::UnixFd(0));
+
+		let (mut pipe_reader, pipe_writer) = std::io::pipe().unwrap();
+
+		connection.send_with_fds(&header, Some(&body), &[std::os::fd::AsFd::as_fd(&pipe_writer)]).unwrap();
+		let (received_header, received_body, mut fds) = connection.recv_with_fds().unwrap();
+		assert_eq!(received_header.unix_fds(), Some(1));
+		assert_eq!(received_body, Some(body.clone()));
+		assert_eq!(fds.len(), 1);
+
+		// The received file descriptor is the write end of the pipe.
+		let mut received_pipe_writer = std::fs::File::from(fds.pop().unwrap());
+		received_pipe_writer.write_all(b"hello").unwrap();
+		drop(received_pipe_writer);
+		let mut buf = [0_u8; 5];
+		pipe_reader.read_exact(&mut buf).unwrap();
+		assert_eq!(&buf, b"hello");
+
+		// A message received without asking for its file descriptors closes them,
+		// so after the original is closed too, the pipe has no write ends left.
+		connection.send_with_fds(&header, Some(&body), &[std::os::fd::AsFd::as_fd(&pipe_writer)]).unwrap();
+		drop(pipe_writer);
+		let (received_header, _) = connection.recv().unwrap();
+		assert_eq!(received_header.unix_fds(), Some(1));
+		assert_eq!(pipe_reader.read(&mut buf).unwrap(), 0);
+
+		// Messages without file descriptors are unaffected.
+		connection.send(&header, None).unwrap();
+		let (_, _, fds) = connection.recv_with_fds().unwrap();
+		assert!(fds.is_empty());
+	}
+
+	#[test]
+	fn test_tcp() {
+		use std::io::{BufRead, Write};
+
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let port = listener.local_addr().unwrap().port();
+
+		// A peer that authenticates the client and then echoes everything back to it.
+		let _ = std::thread::spawn(move || {
+			let (stream, _) = listener.accept().unwrap();
+			let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+			let mut writer = stream;
+
+			let mut line = vec![];
+			let _ = reader.read_until(b'\n', &mut line).unwrap();
+			assert!(line.starts_with(b"\0AUTH EXTERNAL "));
+			writer.write_all(b"OK 0123456789abcdef0123456789abcdef\r\n").unwrap();
+
+			// File descriptors cannot be passed over TCP, so the client must not try to negotiate them.
+			line.clear();
+			let _ = reader.read_until(b'\n', &mut line).unwrap();
+			assert_eq!(line, b"BEGIN\r\n");
+
+			let _ = std::io::copy(&mut reader, &mut writer).unwrap();
+		});
+
+		// The unix socket does not exist, so the tcp address after it is used.
+		let bus_address = format!("unix:path=/nonexistent/dbus-pure-test;tcp:host=127.0.0.1,port={port},family=ipv4");
+		let (stream, nonce, _, _) = super::connect(bus_address.as_ref()).unwrap();
+		assert!(matches!(stream, crate::fd::Stream::Tcp(_)));
+		assert!(nonce.is_none());
+
+		let mut connection = super::Connection::authenticate(stream, nonce, None, super::SaslAuthType::Uid).unwrap();
+		assert_eq!(connection.server_guid().to_string(), "0123456789abcdef0123456789abcdef");
+		assert!(!connection.supports_unix_fds());
+
+		let header = crate::proto::MessageHeader {
+			r#type: crate::proto::MessageType::Signal {
+				interface: "org.example".into(),
+				member: "Echo".into(),
+				path: crate::proto::ObjectPath("/".into()),
+			},
+			flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+			body_len: 0,
+			serial: 1,
+			fields: vec![].into(),
+		};
+		let body = crate::proto::Variant::String("hello".into());
+
+		connection.send(&header, Some(&body)).unwrap();
+		let (received_header, received_body) = connection.recv().unwrap();
+		assert_eq!(received_header.serial, 1);
+		assert_eq!(received_body, Some(body));
+
+		let (_, pipe_writer) = std::io::pipe().unwrap();
+		let err = connection.send_with_fds(&header, None, &[std::os::fd::AsFd::as_fd(&pipe_writer)]).unwrap_err();
+		assert!(matches!(err, super::SendError::UnixFdsNotSupported), "{err:?}");
+	}
+
+	#[test]
+	fn test_connect_errors() {
+		let err = super::connect("autolaunch:".as_ref()).unwrap_err();
+		assert!(matches!(err, super::ConnectError::UnsupportedTransport(_)), "{err:?}");
+
+		for bus_address in [
+			"tcp:host=127.0.0.1",
+			"tcp:host=127.0.0.1,port=foo",
+			"tcp:host=127.0.0.1,port=1,family=ipx",
+			"tcp:host=127.0.0.1,port=1,family=ipv6",
+		] {
+			let err = super::connect(bus_address.as_ref()).unwrap_err();
+			let super::ConnectError::Connect(errs) = err else { panic!("{err:?}") };
+			assert_eq!(errs.len(), 1);
+			assert_eq!(errs[0].0, std::path::Path::new(bus_address));
+		}
+	}
+
+	#[test]
+	fn test_nonce_tcp() {
+		use std::io::{BufRead, Read, Write};
+
+		const NONCE: &super::Nonce = b"0123456789abcdef";
+
+		// A server that checks the nonce before authenticating the client, and then echoes everything back to it.
+		// It closes the connection without responding if the nonce is wrong.
+		fn start_server() -> u16 {
+			let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+			let port = listener.local_addr().unwrap().port();
+
+			let _ = std::thread::spawn(move || {
+				let (stream, _) = listener.accept().unwrap();
+				let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+				let mut writer = stream;
+
+				let mut nonce = [0_u8; 16];
+				reader.read_exact(&mut nonce).unwrap();
+				if nonce != *NONCE {
+					return;
+				}
+
+				let mut line = vec![];
+				let _ = reader.read_until(b'\n', &mut line).unwrap();
+				assert!(line.starts_with(b"\0AUTH EXTERNAL "));
+				writer.write_all(b"OK 0123456789abcdef0123456789abcdef\r\n").unwrap();
+
+				line.clear();
+				let _ = reader.read_until(b'\n', &mut line).unwrap();
+				assert_eq!(line, b"BEGIN\r\n");
+
+				let _ = std::io::copy(&mut reader, &mut writer).unwrap();
+			});
+
+			port
+		}
+
+		let nonce_file_path = |name: &str| std::env::temp_dir().join(format!("dbus-pure-test-{}-{name}", std::process::id()));
+
+		let good_nonce_file = nonce_file_path("good-nonce");
+		std::fs::write(&good_nonce_file, NONCE).unwrap();
+		let bad_nonce_file = nonce_file_path("bad-nonce");
+		std::fs::write(&bad_nonce_file, b"fedcba9876543210").unwrap();
+		let short_nonce_file = nonce_file_path("short-nonce");
+		std::fs::write(&short_nonce_file, b"0123").unwrap();
+		let missing_nonce_file = nonce_file_path("missing-nonce");
+
+		let bus_address = |port: u16, nonce_file: &std::path::Path| {
+			// Percent-encode the path to test that it is decoded.
+			let nonce_file = nonce_file.to_str().unwrap().replace('-', "%2d");
+			format!("nonce-tcp:host=127.0.0.1,port={port},noncefile={nonce_file}")
+		};
+
+		{
+			let (stream, nonce, _, _) = super::connect(bus_address(start_server(), &good_nonce_file).as_ref()).unwrap();
+			assert!(matches!(stream, crate::fd::Stream::Tcp(_)));
+			assert_eq!(nonce.as_ref(), Some(NONCE));
+
+			let mut connection = super::Connection::authenticate(stream, nonce, None, super::SaslAuthType::Uid).unwrap();
+			let header = crate::proto::MessageHeader {
+				r#type: crate::proto::MessageType::Signal {
+					interface: "org.example".into(),
+					member: "Echo".into(),
+					path: crate::proto::ObjectPath("/".into()),
+				},
+				flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+				body_len: 0,
+				serial: 1,
+				fields: vec![].into(),
+			};
+			connection.send(&header, None).unwrap();
+			let (received_header, _) = connection.recv().unwrap();
+			assert_eq!(received_header.serial, 1);
+		}
+
+		{
+			let (stream, nonce, _, _) = super::connect(bus_address(start_server(), &bad_nonce_file).as_ref()).unwrap();
+			let err = super::Connection::authenticate(stream, nonce, None, super::SaslAuthType::Uid).err().unwrap();
+			assert!(matches!(err, super::ConnectError::NonceRejected), "{err:?}");
+		}
+
+		{
+			let err = super::connect(bus_address(1, &short_nonce_file).as_ref()).unwrap_err();
+			let super::ConnectError::Connect(errs) = err else { panic!("{err:?}") };
+			assert_eq!(errs.len(), 1);
+			assert_eq!(errs[0].0, short_nonce_file);
+			assert_eq!(errs[0].1.kind(), std::io::ErrorKind::InvalidData);
+			let err = errs[0].1.get_ref().and_then(|err| err.downcast_ref::<super::ConnectError>());
+			let Some(super::ConnectError::InvalidNonceFile { path, len }) = err else { panic!("{err:?}") };
+			assert_eq!(*path, short_nonce_file);
+			assert_eq!(*len, 4);
+		}
+
+		{
+			let err = super::connect(bus_address(1, &missing_nonce_file).as_ref()).unwrap_err();
+			let super::ConnectError::Connect(errs) = err else { panic!("{err:?}") };
+			assert_eq!(errs.len(), 1);
+			assert_eq!(errs[0].0, missing_nonce_file);
+			assert_eq!(errs[0].1.kind(), std::io::ErrorKind::NotFound);
+			let err = errs[0].1.get_ref().and_then(|err| err.downcast_ref::<super::ConnectError>());
+			let Some(super::ConnectError::ReadNonceFile(path, err)) = err else { panic!("{err:?}") };
+			assert_eq!(*path, missing_nonce_file);
+			assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+		}
+
+		{
+			// An address whose nonce file can't be read is skipped in favor of the next one.
+			let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+			let port = listener.local_addr().unwrap().port();
+			let bus_address = format!("{};tcp:host=127.0.0.1,port={port}", bus_address(1, &missing_nonce_file));
+			let (stream, nonce, _, _) = super::connect(bus_address.as_ref()).unwrap();
+			assert!(matches!(stream, crate::fd::Stream::Tcp(_)));
+			assert_eq!(nonce, None);
+		}
+
+		{
+			let bus_address = "nonce-tcp:host=127.0.0.1,port=1";
+			let err = super::connect(bus_address.as_ref()).unwrap_err();
+			let super::ConnectError::Connect(errs) = err else { panic!("{err:?}") };
+			assert_eq!(errs.len(), 1);
+			assert_eq!(errs[0].0, std::path::Path::new(bus_address));
+			assert_eq!(errs[0].1.kind(), std::io::ErrorKind::InvalidInput);
+		}
+
+		for path in [good_nonce_file, bad_nonce_file, short_nonce_file] {
+			std::fs::remove_file(path).unwrap();
+		}
+	}
+
+	#[test]
+	fn test_parse_address() {
+		for (address, expected) in [
+			// Examples from the spec
+			("unix:path=/tmp/dbus-test", Some(("unix", &[("path", &b"/tmp/dbus-test"[..])][..]))),
+			("unix:abstract=/tmp/dbus-XXXXXXXXXX,guid=ae3f6d5b4b0fc8d8a5f5a1c1e93db6b5", Some(("unix", &[
+				("abstract", &b"/tmp/dbus-XXXXXXXXXX"[..]),
+				("guid", b"ae3f6d5b4b0fc8d8a5f5a1c1e93db6b5"),
+			][..]))),
+			("unix:runtime=yes", Some(("unix", &[("runtime", &b"yes"[..])][..]))),
+			("unix:tmpdir=/tmp", Some(("unix", &[("tmpdir", &b"/tmp"[..])][..]))),
+			("unix:dir=/tmp", Some(("unix", &[("dir", &b"/tmp"[..])][..]))),
+			("tcp:host=127.0.0.1,port=4242,family=ipv4", Some(("tcp", &[
+				("family", &b"ipv4"[..]),
+				("host", b"127.0.0.1"),
+				("port", b"4242"),
+			][..]))),
+			("nonce-tcp:host=localhost,port=4242,noncefile=/tmp/dbus-nonce", Some(("nonce-tcp", &[
+				("host", &b"localhost"[..]),
+				("noncefile", b"/tmp/dbus-nonce"),
+				("port", b"4242"),
+			][..]))),
+			("unixexec:path=/bin/sh,argv1=-c,argv2=exit", Some(("unixexec", &[
+				("argv1", &b"-c"[..]),
+				("argv2", b"exit"),
+				("path", b"/bin/sh"),
+			][..]))),
+			("autolaunch:", Some(("autolaunch", &[][..]))),
+
+			// Keys and values are percent-decoded, and values may be empty.
+			("unix:path=/tmp/dbus%20test,guid=", Some(("unix", &[("guid", &b""[..]), ("path", b"/tmp/dbus test")][..]))),
+			("unix:p%61th=/tmp/%ff", Some(("unix", &[("path", &b"/tmp/\xff"[..])][..]))),
+
+			// Malformed addresses
+			("unix", None),
+			(":path=/tmp/dbus-test", None),
+			("unix:path", None),
+			("unix:path=/tmp/dbus-test,", None),
+			("unix:=/tmp/dbus-test", None),
+			("unix:path=/tmp/a,path=/tmp/b", None),
+			("unix:%ff=/tmp/dbus-test", None),
+		] {
+			let actual = super::parse_address(address.as_bytes());
+			match (actual, expected) {
+				(Ok((actual_transport, actual_pairs)), Some((expected_transport, expected_pairs))) => {
+					assert_eq!(actual_transport, expected_transport.as_bytes(), "{address}");
+					let actual_pairs: Vec<_> = actual_pairs.iter().map(|(key, value)| (&**key, &**value)).collect();
+					assert_eq!(actual_pairs, expected_pairs, "{address}");
+				},
+
+				(Err(err), None) => assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput, "{address}"),
+
+				(actual, expected) => panic!("{address}: expected {expected:?} but got {actual:?}"),
+			}
+		}
+
+		let runtime_bus_path = std::env::var_os("XDG_RUNTIME_DIR").map(|runtime_dir| std::path::Path::new(&runtime_dir).join("bus"));
+
+		for (address, expected) in [
+			("unix:path=/tmp/dbus-test", Some(super::UnixAddress::Path("/tmp/dbus-test".into()))),
+			("unix:guid=ae3f6d5b4b0fc8d8a5f5a1c1e93db6b5,path=/tmp/dbus-test", Some(super::UnixAddress::Path("/tmp/dbus-test".into()))),
+			("unix:abstract=/tmp/dbus-test", Some(super::UnixAddress::Abstract(b"/tmp/dbus-test".to_vec()))),
+			("unix:runtime=yes", runtime_bus_path.map(super::UnixAddress::Path)),
+			("unix:runtime=no", None),
+			("unix:tmpdir=/tmp", None),
+			("unix:dir=/tmp", None),
+			("unix:guid=ae3f6d5b4b0fc8d8a5f5a1c1e93db6b5", None),
+			("unix:path=/tmp/dbus-test,abstract=/tmp/dbus-test", None),
+			("unix:runtime=yes,path=/tmp/dbus-test", None),
+		] {
+			let (_, pairs) = super::parse_address(address.as_bytes()).unwrap();
+			let actual = super::unix_address(&pairs);
+			match (actual, expected) {
+				(Ok(actual), Some(expected)) => assert_eq!(actual, expected, "{address}"),
+				(Err(_), None) => (),
+				(actual, expected) => panic!("{address}: expected {expected:?} but got {actual:?}"),
+			}
+		}
+
+		// Addresses with unsupported transports are skipped.
+		let err = super::connect("autolaunch:;autolaunch:scope=*user".as_ref()).unwrap_err();
+		assert!(matches!(err, super::ConnectError::UnsupportedTransport(_)), "{err:?}");
+		let err = super::connect("autolaunch:;unix:path=/nonexistent/dbus-pure-test".as_ref()).unwrap_err();
+		let super::ConnectError::Connect(errs) = err else { panic!("{err:?}") };
+		assert_eq!(errs.len(), 1);
+		assert_eq!(errs[0].0, std::path::Path::new("/nonexistent/dbus-pure-test"));
+	}
+
+	#[test]
+	fn test_unix_address() {
+		// A unix address without a path or abstract name is reported instead of being skipped silently.
+		let bus_address = "unix:guid=0123456789abcdef0123456789abcdef";
+		let err = super::connect(bus_address.as_ref()).unwrap_err();
+		let super::ConnectError::Connect(errs) = err else { panic!("{err:?}") };
+		assert_eq!(errs.len(), 1);
+		assert_eq!(errs[0].0, std::path::Path::new(bus_address));
+		assert_eq!(errs[0].1.kind(), std::io::ErrorKind::InvalidInput);
+
+		let bus_address = "unix:path=/nonexistent/dbus-pure-test;unix:tmpdir=/tmp";
+		let err = super::connect(bus_address.as_ref()).unwrap_err();
+		let super::ConnectError::Connect(errs) = err else { panic!("{err:?}") };
+		assert_eq!(errs.len(), 2);
+		assert_eq!(errs[0].0, std::path::Path::new("/nonexistent/dbus-pure-test"));
+		assert_eq!(errs[0].1.kind(), std::io::ErrorKind::NotFound);
+		assert_eq!(errs[1].0, std::path::Path::new("unix:tmpdir=/tmp"));
+		assert_eq!(errs[1].1.kind(), std::io::ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn test_activation() {
+		use super::ActivationError;
+
+		let num_fds = |listen_pid: Option<&str>, listen_fds: Option<&str>| super::activation_num_fds(listen_pid.map(Into::into), listen_fds.map(Into::into), 1234);
+		assert_eq!(num_fds(Some("1234"), Some("1")).unwrap(), 1);
+		assert_eq!(num_fds(Some("1234"), Some("2")).unwrap(), 2);
+		assert!(matches!(num_fds(None, Some("1")), Err(ActivationError::MissingEnvVar { name: "LISTEN_PID" })));
+		assert!(matches!(num_fds(Some("1234"), None), Err(ActivationError::MissingEnvVar { name: "LISTEN_FDS" })));
+		assert!(matches!(num_fds(Some("1235"), Some("1")), Err(ActivationError::PidMismatch { listen_pid: 1235, pid: 1234 })));
+		assert!(matches!(num_fds(Some("12a"), Some("1")), Err(ActivationError::InvalidEnvVar { name: "LISTEN_PID", value: _ })));
+		assert!(matches!(num_fds(Some("1234"), Some("-1")), Err(ActivationError::InvalidEnvVar { name: "LISTEN_FDS", value: _ })));
+
+		// The test process was not socket-activated.
+		if std::env::var_os("LISTEN_PID").is_none() {
+			let err = super::Connection::from_activation(super::SaslAuthType::Uid).err().unwrap();
+			assert!(matches!(err, super::ConnectError::Activation(ActivationError::MissingEnvVar { name: "LISTEN_PID" })), "{err:?}");
+			let err = super::Connection::activation_listener().unwrap_err();
+			assert!(matches!(err, ActivationError::MissingEnvVar { name: "LISTEN_PID" }), "{err:?}");
+		}
+
+		// Only unix stream sockets of the expected kind are accepted.
+		let dir = std::env::temp_dir().join(format!("dbus-pure-test-activation-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir(&dir).unwrap();
+		let listener = std::os::unix::net::UnixListener::bind(dir.join("socket")).unwrap();
+		let (stream, _) = std::os::unix::net::UnixStream::pair().unwrap();
+		let (datagram, _) = std::os::unix::net::UnixDatagram::pair().unwrap();
+		let tcp_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let (pipe_reader, _) = std::io::pipe().unwrap();
+
+		let listener = super::activation_socket(listener.into(), crate::fd::SocketKind::Listening).unwrap();
+		let stream = super::activation_socket(stream.into(), crate::fd::SocketKind::Connected).unwrap();
+		for (fd, expected) in [
+			(listener, crate::fd::SocketKind::Connected),
+			(stream, crate::fd::SocketKind::Listening),
+			(datagram.into(), crate::fd::SocketKind::Connected),
+			(tcp_listener.into(), crate::fd::SocketKind::Listening),
+			(pipe_reader.into(), crate::fd::SocketKind::Connected),
+		] {
+			let err = super::activation_socket(fd, expected).unwrap_err();
+			assert!(matches!(err, ActivationError::UnexpectedSocket), "{err:?}");
+		}
+
+		std::fs::remove_dir_all(&dir).unwrap();
+
+		// The file descriptor is not inherited by spawned processes once it has been taken.
+		let (pipe_reader, _) = std::io::pipe().unwrap();
+		let fd = unsafe { libc::dup(std::os::fd::AsRawFd::as_raw_fd(&pipe_reader)) };
+		assert!(fd >= 0);
+		let fd: std::os::fd::OwnedFd = unsafe { std::os::fd::FromRawFd::from_raw_fd(fd) };
+		let flags = || unsafe { libc::fcntl(std::os::fd::AsRawFd::as_raw_fd(&fd), libc::F_GETFD) };
+		assert_eq!(flags() & libc::FD_CLOEXEC, 0);
+		crate::fd::set_cloexec(std::os::fd::AsRawFd::as_raw_fd(&fd)).unwrap();
+		assert_eq!(flags() & libc::FD_CLOEXEC, libc::FD_CLOEXEC);
+	}
+
+	#[test]
+	fn test_launchd_address() {
+		let (transport, pairs) = super::parse_address(b"launchd:env=DBUS_LAUNCHD_SESSION_BUS_SOCKET").unwrap();
+		assert_eq!(transport, b"launchd");
+
+		let path = super::launchd_socket_path(&pairs, |name| {
+			assert_eq!(name, "DBUS_LAUNCHD_SESSION_BUS_SOCKET");
+			Ok(Some("/private/tmp/com.apple.launchd.abc/unix_domain_listener".into()))
+		}).unwrap();
+		assert_eq!(path, std::path::Path::new("/private/tmp/com.apple.launchd.abc/unix_domain_listener"));
+
+		// An unset variable is reported with its name.
+		for value in [None, Some("".into())] {
+			let err = super::launchd_socket_path(&pairs, |_| Ok(value)).unwrap_err();
+			assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+			assert_eq!(err.to_string(), "the launchd env var DBUS_LAUNCHD_SESSION_BUS_SOCKET is not set");
+		}
+
+		let err = super::launchd_socket_path(&pairs, |_| Err(std::io::ErrorKind::PermissionDenied.into())).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+
+		let (_, pairs) = super::parse_address(b"launchd:").unwrap();
+		let err = super::launchd_socket_path(&pairs, |_| unreachable!()).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+		// launchd is only supported on macOS, so the address is skipped like any other unsupported transport elsewhere.
+		#[cfg(not(target_os = "macos"))]
+		{
+			let err = super::connect("launchd:env=DBUS_LAUNCHD_SESSION_BUS_SOCKET".as_ref()).unwrap_err();
+			assert!(matches!(err, super::ConnectError::UnsupportedTransport(_)), "{err:?}");
+		}
+	}
+
+	#[cfg(target_os = "linux")]
+	#[test]
+	fn test_unix_abstract() {
+		use std::os::linux::net::SocketAddrExt;
+
+		let name = format!("/tmp/dbus-pure-test-{}", std::process::id());
+		let addr = std::os::unix::net::SocketAddr::from_abstract_name(&name).unwrap();
+		let listener = std::os::unix::net::UnixListener::bind_addr(&addr).unwrap();
+
+		// Percent-encode the name to test that it is decoded.
+		let bus_address = format!("unix:abstract={},guid=0123456789abcdef0123456789abcdef", name.replace('-', "%2d"));
+		let (stream, nonce, _, _) = super::connect(bus_address.as_ref()).unwrap();
+		let crate::fd::Stream::Unix(stream) = stream else { panic!("{stream:?}") };
+		assert!(nonce.is_none());
+
+		let (server_stream, _) = listener.accept().unwrap();
+		let server_addr = stream.peer_addr().unwrap();
+		assert_eq!(server_addr.as_abstract_name(), Some(name.as_bytes()));
+		drop(server_stream);
+
+		// The abstract name no longer exists after the listener is closed.
+		drop(listener);
+		let err = super::connect(bus_address.as_ref()).unwrap_err();
+		let super::ConnectError::Connect(errs) = err else { panic!("{err:?}") };
+		assert_eq!(errs.len(), 1);
+		assert_eq!(errs[0].0, std::path::Path::new(&bus_address));
+		assert_eq!(errs[0].1.kind(), std::io::ErrorKind::ConnectionRefused);
+	}
+
+	#[test]
+	fn test_unixexec() {
+		use std::io::{BufRead, Write};
+
+		// cat echoes the SASL handshake back, which is enough to test that the connection is wired to its stdin and stdout.
+		let (stream, nonce, _, process) = super::connect("unixexec:path=cat".as_ref()).unwrap();
+		let crate::fd::Stream::Unix(stream) = stream else { panic!("{stream:?}") };
+		assert!(nonce.is_none());
+		let process = process.unwrap();
+		let pid: libc::pid_t = process.0.id().try_into().unwrap();
+
+		let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+		let mut writer = stream;
+		writer.write_all(b"\0AUTH EXTERNAL 31303030\r\n").unwrap();
+		let mut line = vec![];
+		let _ = reader.read_until(b'\n', &mut line).unwrap();
+		assert_eq!(line, b"\0AUTH EXTERNAL 31303030\r\n");
+
+		// The process is reaped when it's dropped.
+		drop((reader, writer));
+		drop(process);
+		assert_eq!(unsafe { libc::kill(pid, 0) }, -1);
+		assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ESRCH));
+
+		// Arguments are percent-decoded and passed in order.
+		let (stream, _, _, _process) = super::connect("unixexec:path=sh,argv0=dbus-pure-test,argv2=echo%20%240%20%241,argv1=-c,argv3=a,argv4=b%2cc".as_ref()).unwrap();
+		let crate::fd::Stream::Unix(stream) = stream else { panic!("{stream:?}") };
+		let mut line = vec![];
+		let _ = std::io::BufReader::new(stream).read_until(b'\n', &mut line).unwrap();
+		assert_eq!(line, b"a b,c\n");
+
+		let err = super::connect("unixexec:path=/nonexistent/dbus-pure-test".as_ref()).unwrap_err();
+		let super::ConnectError::Connect(errs) = err else { panic!("{err:?}") };
+		assert_eq!(errs.len(), 1);
+		assert_eq!(errs[0].1.kind(), std::io::ErrorKind::NotFound);
+
+		let err = super::connect("unixexec:argv1=foo".as_ref()).unwrap_err();
+		let super::ConnectError::Connect(errs) = err else { panic!("{err:?}") };
+		assert_eq!(errs[0].1.kind(), std::io::ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn test_from_stream() {
+		// A stream that the connection does not know is a unix socket
+		struct Stream(std::os::unix::net::UnixStream);
+
+		impl std::io::Read for Stream {
+			fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+				self.0.read(buf)
+			}
+		}
+
+		impl std::io::Write for Stream {
+			fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+				self.0.write(buf)
+			}
+
+			fn flush(&mut self) -> std::io::Result<()> {
+				self.0.flush()
+			}
+		}
+
+		let (client_stream, server_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+		crate::fake_bus::spawn(server_stream, |header, body| {
+			let crate::proto::MessageType::MethodCall { member, .. } = &header.r#type else { return vec![]; };
+			assert_eq!(member, "Echo");
+			vec![crate::fake_bus::method_return(header, body.cloned())]
+		});
+
+		let mut connection = super::Connection::from_stream(Stream(client_stream), super::SaslAuthType::Uid).unwrap();
+		assert_eq!(connection.server_guid().to_string(), "0123456789abcdef0123456789abcdef");
+		assert!(!connection.supports_unix_fds());
+
+		// Peer credentials and timeouts need a socket.
+		let err = connection.peer_credentials().unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+
+		let err = connection.set_read_timeout(Some(std::time::Duration::from_secs(1))).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+		assert_eq!(connection.read_timeout(), None);
+
+		let mut client = crate::Client::new(connection).unwrap();
+
+		let body = crate::proto::Variant::String("hello".into());
+		let response = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Echo", Some(&body)).unwrap();
+		assert_eq!(response, Some(body));
+
+		let (_, pipe_writer) = std::io::pipe().unwrap();
+		let err = client.send_with_fds(
+			&crate::proto::MessageHeader {
+				r#type: crate::proto::MessageType::Signal {
+					interface: "org.example".into(),
+					member: "Pipe".into(),
+					path: crate::proto::ObjectPath("/".into()),
+				},
+				flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+				body_len: 0,
+				serial: 1,
+				fields: vec![].into(),
+			},
+			None,
+			&[std::os::fd::AsFd::as_fd(&pipe_writer)],
+		).unwrap_err();
+		assert!(matches!(err, super::SendError::UnixFdsNotSupported), "{err:?}");
+	}
+
+	#[test]
+	fn test_sasl_rejected() {
+		let err = authenticate_scripted(None, super::SaslAuthType::Other("31303030"), &[
+			(b"\0AUTH EXTERNAL 31303030\r\n", b"REJECTED KERBEROS_V4 DBUS_COOKIE_SHA1 ANONYMOUS\r\n"),
+		]).err().unwrap();
+		assert!(matches!(
+			err,
+			super::ConnectError::AuthRejected { ref offered } if offered == &["KERBEROS_V4", "DBUS_COOKIE_SHA1", "ANONYMOUS"],
+		), "{err:?}");
+
+		// The next mechanism is tried if the bus offers it.
+		let connection = authenticate_scripted(None, super::SaslAuthType::Auto, &[
+			(b"\0AUTH EXTERNAL ", b"REJECTED DBUS_COOKIE_SHA1 ANONYMOUS\r\n"),
+			(b"AUTH ANONYMOUS 646275732d70757265\r\n", b"OK 0123456789abcdef0123456789abcdef\r\n"),
+			(b"NEGOTIATE_UNIX_FD\r\n", b"AGREE_UNIX_FD\r\n"),
+			(b"BEGIN\r\n", b""),
+		]).unwrap();
+		assert!(connection.supports_unix_fds());
+
+		// Errors and challenges cancel the attempt. A mechanism is not tried again.
+		let err = authenticate_scripted(None, super::SaslAuthType::Auto, &[
+			(b"\0AUTH EXTERNAL ", b"ERROR\r\n"),
+			(b"CANCEL\r\n", b"REJECTED ANONYMOUS EXTERNAL\r\n"),
+			(b"AUTH ANONYMOUS ", b"DATA\r\n"),
+			(b"CANCEL\r\n", b"REJECTED EXTERNAL\r\n"),
+		]).err().unwrap();
+		assert!(matches!(err, super::ConnectError::AuthRejected { ref offered } if offered == &["EXTERNAL"]), "{err:?}");
+
+		let err = authenticate_scripted(None, super::SaslAuthType::Uid, &[
+			(b"\0AUTH EXTERNAL ", b"REJECTEDEXTERNAL\r\n"),
+		]).err().unwrap();
+		assert!(matches!(err, super::ConnectError::Authenticate(_)), "{err:?}");
+	}
+
+	#[test]
+	fn test_server_guid() {
+		const MALFORMED_GUID_SCRIPTS: &[&[(&[u8], &[u8])]] = &[
+			&[(b"\0AUTH EXTERNAL ", b"OK 0123456789abcdef\r\n")],
+			&[(b"\0AUTH EXTERNAL ", b"OK 0123456789abcdef0123456789abcdefgh\r\n")],
+			&[(b"\0AUTH EXTERNAL ", b"OK\r\n")],
+		];
+
+		let guid = super::ServerGuid::parse(b"0123456789abcdef0123456789ABCDEF").unwrap();
+		assert_eq!(guid.0, [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]);
+		assert_eq!(guid.to_string(), "0123456789abcdef0123456789abcdef");
+		for malformed in [&b""[..], b"0123456789abcdef", b"0123456789abcdef0123456789abcdeg", b"+123456789abcdef0123456789abcdef", b"0123456789abcdef0123456789abcdef0"] {
+			assert_eq!(super::ServerGuid::parse(malformed), None, "{}", malformed.escape_ascii());
+		}
+
+		let connection = authenticate_scripted(Some(guid), super::SaslAuthType::Uid, &[
+			(b"\0AUTH EXTERNAL ", b"OK 0123456789abcdef0123456789abcdef\r\n"),
+			(b"NEGOTIATE_UNIX_FD\r\n", b"AGREE_UNIX_FD\r\n"),
+			(b"BEGIN\r\n", b""),
+		]).unwrap();
+		assert_eq!(connection.server_guid(), guid);
+
+		let err = authenticate_scripted(Some(guid), super::SaslAuthType::Uid, &[
+			(b"\0AUTH EXTERNAL ", b"OK fedcba9876543210fedcba9876543210\r\n"),
+		]).err().unwrap();
+		assert!(matches!(
+			err,
+			super::ConnectError::ServerGuidMismatch { expected, actual } if expected == guid && actual.to_string() == "fedcba9876543210fedcba9876543210",
+		), "{err:?}");
+
+		for script in MALFORMED_GUID_SCRIPTS {
+			let err = authenticate_scripted(None, super::SaslAuthType::Uid, script).err().unwrap();
+			assert!(matches!(err, super::ConnectError::Authenticate(_)), "{err:?}");
+		}
+
+		// A malformed GUID in the bus address fails that address.
+		let err = super::connect("unix:path=/nonexistent/dbus-pure-test,guid=0123".as_ref()).unwrap_err();
+		let super::ConnectError::Connect(errs) = err else { panic!("{err:?}") };
+		assert_eq!(errs.len(), 1);
+		assert_eq!(errs[0].1.kind(), std::io::ErrorKind::InvalidInput);
+	}
+
+	/// Authenticate with a server that expects lines that start with the given prefixes, and responds to each of them with the given response.
+	fn authenticate_scripted(
+		expected_server_guid: Option<super::ServerGuid>,
+		sasl_auth_type: super::SaslAuthType<'_>,
+		script: &'static [(&'static [u8], &'static [u8])],
+	) -> Result<super::Connection, super::ConnectError> {
+		use std::io::{BufRead, Write};
+
+		let (client_stream, server_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+
+		let server = std::thread::spawn(move || {
+			let mut reader = std::io::BufReader::new(server_stream.try_clone().unwrap());
+			let mut writer = server_stream;
+
+			let mut line = vec![];
+			for &(expected, response) in script {
+				line.clear();
+				let _ = reader.read_until(b'\n', &mut line).unwrap();
+				assert!(line.starts_with(expected), "expected {:?} but got {:?}", expected.escape_ascii().to_string(), line.escape_ascii().to_string());
+				writer.write_all(response).unwrap();
+			}
+		});
+
+		let result = super::Connection::authenticate(crate::fd::Stream::Unix(client_stream), None, expected_server_guid, sasl_auth_type);
+		server.join().unwrap();
+		result
+	}
+
+	#[test]
+	fn test_peer_credentials() {
+		let (connection, _server_stream) = connect_pair();
+
+		let credentials = connection.peer_credentials().unwrap();
+		assert_eq!(credentials.uid, unsafe { libc::getuid() });
+		assert_eq!(credentials.gid, unsafe { libc::getgid() });
+		#[cfg(any(target_os = "android", target_os = "linux"))]
+		assert_eq!(credentials.pid, Some(std::process::id()));
+
+		let client = crate::fake_bus::client(|_, _| vec![]);
+		assert_eq!(client.peer_credentials().unwrap().uid, unsafe { libc::getuid() });
+	}
+
+	#[test]
+	fn test_stats() {
+		use std::io::Write;
+
+		let (mut connection, mut server_stream) = connect_pair();
+
+		// The SASL handshake is not counted.
+		assert_eq!(connection.stats(), super::ConnectionStats {
+			read_buffer_capacity: connection.stats().read_buffer_capacity,
+			..Default::default()
+		});
+
+		let (mut method_call, _) =
+			crate::proto::MethodCallBuilder::new(crate::proto::ObjectPath("/".into()), "Call")
+			.destination("org.example")
+			.build()
+			.unwrap();
+		method_call.serial = 1;
+		let (mut signal, _) =
+			crate::proto::SignalBuilder::new(crate::proto::ObjectPath("/".into()), "org.example", "Signaled")
+			.build()
+			.unwrap();
+		signal.serial = 2;
+
+		let mut expected = vec![];
+		for header in [&method_call, &method_call, &signal] {
+			connection.send(header, None).unwrap();
+			crate::proto::serialize_message(header, None, &mut expected, crate::proto::Endianness::Little).unwrap();
+		}
+
+		let mut reply = vec![];
+		crate::proto::serialize_message(&signal, None, &mut reply, crate::proto::Endianness::Little).unwrap();
+		server_stream.write_all(&reply).unwrap();
+		let (header, _) = connection.recv().unwrap();
+		assert_eq!(header.member(), Some("Signaled"));
+
+		let stats = connection.stats();
+		assert_eq!(stats.sent, super::MessageCounts { method_calls: 2, signals: 1, ..Default::default() });
+		assert_eq!(stats.sent.total(), 3);
+		assert_eq!(stats.received, super::MessageCounts { signals: 1, ..Default::default() });
+		assert_eq!(stats.bytes_sent, u64::try_from(expected.len()).unwrap());
+		assert_eq!(stats.bytes_received, u64::try_from(reply.len()).unwrap());
+		assert!(stats.read_buffer_capacity >= reply.len());
+
+		connection.reset_stats();
+		let stats = connection.stats();
+		assert_eq!(stats.sent.total(), 0);
+		assert_eq!(stats.received.total(), 0);
+		assert_eq!(stats.bytes_sent, 0);
+		assert_eq!(stats.bytes_received, 0);
+	}
+
+	/// Open a connection over a socket pair, and return it along with the peer's end of the socket after the handshake.
+	fn connect_pair() -> (super::Connection, std::os::unix::net::UnixStream) {
+		use std::io::{BufRead, Write};
+
+		let (client_stream, server_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+
+		let handshake = std::thread::spawn(move || {
+			let mut reader = std::io::BufReader::new(server_stream.try_clone().unwrap());
+			let mut writer = server_stream;
+
+			let mut line = vec![];
+			for response in [&b"OK 0123456789abcdef0123456789abcdef\r\n"[..], b"AGREE_UNIX_FD\r\n"] {
+				line.clear();
+				let _ = reader.read_until(b'\n', &mut line).unwrap();
+				writer.write_all(response).unwrap();
+			}
+			line.clear();
+			let _ = reader.read_until(b'\n', &mut line).unwrap();
+			assert_eq!(line, b"BEGIN\r\n");
+			assert!(reader.buffer().is_empty());
+
+			writer
+		});
+		let connection = super::Connection::from_unix_stream(client_stream, super::SaslAuthType::Uid).unwrap();
+		let server_stream = handshake.join().unwrap();
+		(connection, server_stream)
+	}
+
+	#[test]
+	fn test_nonblocking() {
+		use std::io::Write;
+
+		let (mut connection, mut server_stream) = connect_pair();
+
+		connection.set_nonblocking(true).unwrap();
+
+		let header = |serial| crate::proto::MessageHeader {
+			r#type: crate::proto::MessageType::Signal {
+				interface: "org.example".into(),
+				member: "Changed".into(),
+				path: crate::proto::ObjectPath("/".into()),
+			},
+			flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+			body_len: 0,
+			serial,
+			fields: vec![].into(),
+		};
+
+		// Partial reads
+		assert!(connection.try_recv().unwrap().is_none());
+		let err = connection.recv().unwrap_err();
+		assert!(matches!(err, super::RecvError::TimedOut), "{err:?}");
+
+		let mut message = vec![];
+		crate::proto::serialize_message(&header(1), Some(&crate::proto::Variant::String("hello".into())), &mut message, crate::proto::Endianness::Little).unwrap();
+		for chunk in [&message[..10], &message[10..20]] {
+			server_stream.write_all(chunk).unwrap();
+			assert!(connection.try_recv().unwrap().is_none());
+		}
+		server_stream.write_all(&message[20..]).unwrap();
+		let (received_header, received_body) = connection.try_recv().unwrap().unwrap();
+		assert_eq!(received_header.serial, 1);
+		assert_eq!(received_body, Some(crate::proto::Variant::String("hello".into())));
+		assert!(connection.try_recv().unwrap().is_none());
+
+		// Partial writes. The message is larger than the socket's send buffer, so only the start of it can be written until the peer reads it.
+		let body = crate::proto::Variant::ArrayU8(vec![5_u8; 4 * 1024 * 1024].into());
+		connection.send(&header(2), Some(&body)).unwrap();
+		assert!(connection.has_pending_write());
+		let err = connection.flush().unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+
+		// Other messages can't be sent until the rest of it is written.
+		let err = connection.send(&header(3), None).unwrap_err();
+		assert!(matches!(err, super::SendError::WouldBlock), "{err:?}");
+		let err = connection.flush_pending().unwrap_err();
+		assert!(matches!(err, super::SendError::WouldBlock), "{err:?}");
+
+		let peer = std::thread::spawn(move || {
+			let mut reader = crate::proto::MessageReader::new(server_stream);
+			let messages: Vec<_> = (0..2).map(|_| reader.read_message().unwrap()).collect();
+			messages
+		});
+
+		let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+		while let Err(err) = connection.flush_pending() {
+			assert!(matches!(err, super::SendError::WouldBlock), "{err:?}");
+			assert!(std::time::Instant::now() < deadline, "timed out writing message");
+			std::thread::sleep(std::time::Duration::from_millis(1));
+		}
+		assert!(!connection.has_pending_write());
+		connection.send(&header(3), None).unwrap();
+
+		let messages = peer.join().unwrap();
+		assert_eq!(messages[0].0.serial, 2);
+		assert_eq!(messages[0].1.as_ref(), Some(&body));
+		assert_eq!(messages[1].0.serial, 3);
+		assert_eq!(messages[1].1, None);
+	}
+
+	#[test]
+	fn test_poll() {
+		use std::io::Write;
+
+		fn poll(connection: &super::Connection) -> bool {
+			let mut pollfd = libc::pollfd { fd: std::os::fd::AsRawFd::as_raw_fd(connection), events: libc::POLLIN, revents: 0 };
+			let ready = unsafe { libc::poll(&raw mut pollfd, 1, 5000) };
+			assert!(ready >= 0, "{}", std::io::Error::last_os_error());
+			pollfd.revents & libc::POLLIN != 0
+		}
+
+		let (mut connection, mut server_stream) = connect_pair();
+		connection.set_nonblocking(true).unwrap();
+
+		let mut messages = vec![];
+		for serial in 1..=2 {
+			let header = crate::proto::MessageHeader {
+				r#type: crate::proto::MessageType::Signal {
+					interface: "org.example".into(),
+					member: "Changed".into(),
+					path: crate::proto::ObjectPath("/".into()),
+				},
+				flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+				body_len: 0,
+				serial,
+				fields: vec![].into(),
+			};
+			crate::proto::serialize_message(&header, None, &mut messages, crate::proto::Endianness::Little).unwrap();
+		}
+
+		let writer = std::thread::spawn(move || {
+			std::thread::sleep(std::time::Duration::from_millis(50));
+			server_stream.write_all(&messages).unwrap();
+			server_stream
+		});
+
+		assert!(!connection.has_buffered_data());
+		assert!(poll(&connection));
+
+		// Both messages are read from the socket at once, so the second one is received without the socket being readable again.
+		let (received_header, _) = connection.try_recv().unwrap().unwrap();
+		assert_eq!(received_header.serial, 1);
+		assert!(connection.has_buffered_data());
+		let (received_header, _) = connection.try_recv().unwrap().unwrap();
+		assert_eq!(received_header.serial, 2);
+		assert!(!connection.has_buffered_data());
+		assert!(connection.try_recv().unwrap().is_none());
+
+		// The socket becomes readable again when the peer closes it.
+		drop(writer.join().unwrap());
+		assert!(poll(&connection));
+	}
+
+	#[test]
+	fn test_split() {
+		// Echo the body of every method call back.
+		let connection = crate::fake_bus::connection(|request, body| vec![crate::fake_bus::method_return(request, body.cloned())]);
+		let (mut send_half, mut recv_half) = connection.split();
+
+		let sender = std::thread::spawn(move || {
+			for serial in 1..=100 {
+				let header = crate::proto::MessageHeader {
+					r#type: crate::proto::MessageType::MethodCall {
+						member: "Echo".into(),
+						path: crate::proto::ObjectPath("/".into()),
+					},
+					flags: crate::proto::message_flags::NONE,
+					body_len: 0,
+					serial,
+					fields: vec![
+						crate::proto::MessageHeaderField::Destination("org.example".into()),
+						crate::proto::MessageHeaderField::Interface("org.example".into()),
+					].into(),
+				};
+				send_half.send(&header, Some(&crate::proto::Variant::U32(serial))).unwrap();
+			}
+			send_half
+		});
+
+		// The responses are received while the requests are still being sent, in the order the requests were sent.
+		for serial in 1..=100 {
+			let (header, body) = recv_half.recv().unwrap();
+			assert!(matches!(header.r#type, crate::proto::MessageType::MethodReturn { reply_serial } if reply_serial == serial), "{header:?}");
+			assert_eq!(body, Some(crate::proto::Variant::U32(serial)));
+		}
+
+		let _send_half = sender.join().unwrap();
+	}
+}
@@ -0,0 +1,177 @@
+//! A scripted message bus for tests.
+//!
+//! It serves one end of a unix socket pair, performs the server side of the SASL handshake, answers `org.freedesktop.DBus.Hello`,
+//! and hands every other message to a handler that returns the messages to send back.
+
+pub(crate) type Message = (crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>);
+
+type Handler<'a> =
+	dyn FnMut(
+		&crate::proto::MessageHeader<'static>,
+		Option<&crate::proto::Variant<'static>>,
+		crate::proto::Endianness,
+		Vec<std::os::fd::OwnedFd>,
+	) -> Vec<(Message, Vec<std::os::fd::OwnedFd>)> + 'a;
+
+pub(crate) const UNIQUE_NAME: &str = ":1.1";
+
+/// Start a fake bus that uses the given handler, and return a client connected to it.
+pub(crate) fn client(
+	handler: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> Vec<Message> + Send + 'static,
+) -> crate::Client {
+	let connection = connection(handler);
+	crate::Client::new(connection).unwrap()
+}
+
+/// Start a fake bus that uses the given handler, and return a client connected to it.
+///
+/// The handler is also given the file descriptors sent along with each message it handles,
+/// and returns the file descriptors to send along with each of its messages.
+pub(crate) fn client_with_fds(
+	mut handler: impl FnMut(
+		&crate::proto::MessageHeader<'static>,
+		Option<&crate::proto::Variant<'static>>,
+		Vec<std::os::fd::OwnedFd>,
+	) -> Vec<(Message, Vec<std::os::fd::OwnedFd>)> + Send + 'static,
+) -> crate::Client {
+	let connection = start(crate::proto::Endianness::Little, move |header, body, _, fds| handler(header, body, fds));
+	crate::Client::new(connection).unwrap()
+}
+
+/// Start a fake bus that uses the given handler, and return a connection to it.
+pub(crate) fn connection(
+	mut handler: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> Vec<Message> + Send + 'static,
+) -> crate::Connection {
+	connection_with_endianness(crate::proto::Endianness::Little, move |header, body, _| handler(header, body))
+}
+
+/// Start a fake bus that sends messages with the given endianness and uses the given handler, and return a connection to it.
+///
+/// The handler is also given the endianness of each message it handles.
+pub(crate) fn connection_with_endianness(
+	endianness: crate::proto::Endianness,
+	mut handler: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>, crate::proto::Endianness) -> Vec<Message> + Send + 'static,
+) -> crate::Connection {
+	start(endianness, move |header, body, request_endianness, _| {
+		handler(header, body, request_endianness).into_iter().map(|message| (message, vec![])).collect()
+	})
+}
+
+fn start(
+	endianness: crate::proto::Endianness,
+	mut handler: impl FnMut(
+		&crate::proto::MessageHeader<'static>,
+		Option<&crate::proto::Variant<'static>>,
+		crate::proto::Endianness,
+		Vec<std::os::fd::OwnedFd>,
+	) -> Vec<(Message, Vec<std::os::fd::OwnedFd>)> + Send + 'static,
+) -> crate::Connection {
+	let (client_stream, server_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+
+	let _ = std::thread::spawn(move || serve(server_stream, endianness, &mut handler));
+
+	crate::Connection::from_unix_stream(client_stream, crate::SaslAuthType::Uid).unwrap()
+}
+
+/// Start a fake bus that uses the given handler on the given end of a unix socket pair.
+///
+/// The caller is responsible for connecting to the other end. Unlike the other functions of this module,
+/// this allows the test to choose how the connection is opened.
+pub(crate) fn spawn(
+	stream: std::os::unix::net::UnixStream,
+	mut handler: impl FnMut(&crate::proto::MessageHeader<'static>, Option<&crate::proto::Variant<'static>>) -> Vec<Message> + Send + 'static,
+) {
+	let _ = std::thread::spawn(move || serve(stream, crate::proto::Endianness::Little, &mut |header, body, _, _| {
+		handler(header, body).into_iter().map(|message| (message, vec![])).collect()
+	}));
+}
+
+fn serve(
+	stream: std::os::unix::net::UnixStream,
+	endianness: crate::proto::Endianness,
+	handler: &mut Handler<'_>,
+) {
+	use std::io::{BufRead, Read, Write};
+
+	let mut reader = std::io::BufReader::new(crate::fd::FdReader::new(crate::fd::Stream::Unix(stream.try_clone().unwrap())));
+	let mut stream = crate::fd::FdWriter::new(crate::fd::Stream::Unix(stream));
+
+	let mut line = vec![];
+	let _ = reader.read_until(b'\n', &mut line).unwrap();
+	assert!(line.starts_with(b"\0AUTH EXTERNAL "));
+	stream.write_all(b"OK 0123456789abcdef0123456789abcdef\r\n").unwrap();
+
+	line.clear();
+	let _ = reader.read_until(b'\n', &mut line).unwrap();
+	// Clients only negotiate file descriptor passing if they know that the stream is a unix socket.
+	if line == b"NEGOTIATE_UNIX_FD\r\n" {
+		stream.write_all(b"AGREE_UNIX_FD\r\n").unwrap();
+
+		line.clear();
+		let _ = reader.read_until(b'\n', &mut line).unwrap();
+	}
+	assert_eq!(line, b"BEGIN\r\n");
+
+	let mut last_serial = 0;
+	let mut buf = vec![];
+
+	loop {
+		let (header, body, request_endianness, fds) = match crate::proto::deserialize_message(&buf) {
+			Ok((header, body, read)) => {
+				let request_endianness = crate::proto::message_endianness(&buf).unwrap();
+				let fds = reader.get_mut().take_fds(header.unix_fds().unwrap_or_default() as usize);
+				let message = (header.into_owned(), body.map(crate::proto::Variant::into_owned), request_endianness, fds);
+				let _ = buf.drain(..read);
+				message
+			},
+
+			Err(crate::proto::DeserializeError::EndOfInput) => {
+				let mut chunk = [0_u8; 4096];
+				let read = match reader.read(&mut chunk) {
+					Ok(0) | Err(_) => return,
+					Ok(read) => read,
+				};
+				buf.extend_from_slice(&chunk[..read]);
+				continue;
+			},
+
+			Err(err) => panic!("{err}"),
+		};
+
+		let responses = match &header.r#type {
+			crate::proto::MessageType::MethodCall { member, .. } if member == "Hello" =>
+				vec![(method_return(&header, Some(crate::proto::Variant::String(UNIQUE_NAME.into()))), vec![])],
+
+			_ => handler(&header, body.as_ref(), request_endianness, fds),
+		};
+
+		for ((mut header, body), fds) in responses {
+			last_serial += 1;
+			header.serial = last_serial;
+			if !fds.is_empty() {
+				header.fields.to_mut().push(crate::proto::MessageHeaderField::UnixFds(fds.len().try_into().unwrap()));
+			}
+
+			let mut message = vec![];
+			crate::proto::serialize_message(&header, body.as_ref(), &mut message, endianness).unwrap();
+			stream.set_fds(&fds.iter().map(std::os::fd::AsFd::as_fd).collect::<Vec<_>>());
+			if stream.write_all(&message).is_err() {
+				return;
+			}
+		}
+	}
+}
+
+/// A `METHOD_RETURN` message in response to the given method call.
+pub(crate) fn method_return(request: &crate::proto::MessageHeader<'_>, body: Option<crate::proto::Variant<'static>>) -> Message {
+	let header = crate::proto::MessageHeader {
+		r#type: crate::proto::MessageType::MethodReturn {
+			reply_serial: request.serial,
+		},
+		flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+		body_len: 0,
+		serial: 0,
+		fields: vec![crate::proto::MessageHeaderField::Destination(UNIQUE_NAME.into())].into(),
+	};
+	(header, body)
+}
@@ -0,0 +1,437 @@
+//! Reading from and writing to the stream of a connection.
+//!
+//! File descriptors can only be passed over unix sockets, where they are attached to the data as `SCM_RIGHTS` ancillary messages.
+
+/// The maximum number of file descriptors that Linux allows in a single `SCM_RIGHTS` message, `SCM_MAX_FD`
+const MAX_FDS: usize = 253;
+
+/// The stream of a connection to a message bus.
+pub(crate) enum Stream {
+	/// A stream provided by the user. It can't be cloned, so the reader and writer share it.
+	Other(std::sync::Arc<std::sync::Mutex<dyn ReadWrite + Send>>),
+
+	Tcp(std::net::TcpStream),
+
+	Unix(std::os::unix::net::UnixStream),
+}
+
+impl Stream {
+	pub(crate) fn try_clone(&self) -> std::io::Result<Self> {
+		Ok(match self {
+			Stream::Other(stream) => Stream::Other(stream.clone()),
+			Stream::Tcp(stream) => Stream::Tcp(stream.try_clone()?),
+			Stream::Unix(stream) => Stream::Unix(stream.try_clone()?),
+		})
+	}
+
+	pub(crate) fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+		match self {
+			Stream::Other(_) => Err(not_a_socket()),
+			Stream::Tcp(stream) => stream.set_nonblocking(nonblocking),
+			Stream::Unix(stream) => stream.set_nonblocking(nonblocking),
+		}
+	}
+
+	pub(crate) fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+		match self {
+			Stream::Other(_) => Err(not_a_socket()),
+			Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+			Stream::Unix(stream) => stream.set_read_timeout(timeout),
+		}
+	}
+
+	pub(crate) fn set_write_timeout(&self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+		match self {
+			Stream::Other(_) => Err(not_a_socket()),
+			Stream::Tcp(stream) => stream.set_write_timeout(timeout),
+			Stream::Unix(stream) => stream.set_write_timeout(timeout),
+		}
+	}
+
+	pub(crate) fn peer_credentials(&self) -> std::io::Result<crate::conn::PeerCredentials> {
+		match self {
+			Stream::Other(_) | Stream::Tcp(_) =>
+				Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "peer credentials are only available for unix sockets")),
+			Stream::Unix(stream) => peer_credentials(std::os::fd::AsFd::as_fd(stream)),
+		}
+	}
+}
+
+/// The error for socket options that can't be set on a user-provided stream, since it might not be a socket.
+fn not_a_socket() -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::Unsupported, "this can only be set on connections over sockets")
+}
+
+/// Gets the credentials of the peer of a unix socket with `SO_PEERCRED`
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn peer_credentials(fd: std::os::fd::BorrowedFd<'_>) -> std::io::Result<crate::conn::PeerCredentials> {
+	// SAFETY: ucred is a plain C struct, for which all zeroes is a valid value.
+	let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+	let mut len: libc::socklen_t = std::mem::size_of_val(&cred).try_into().expect("ucred is small");
+
+	// SAFETY: cred and len are valid for writes, and len is the size of cred.
+	let result = unsafe {
+		libc::getsockopt(std::os::fd::AsRawFd::as_raw_fd(&fd), libc::SOL_SOCKET, libc::SO_PEERCRED, (&raw mut cred).cast(), &raw mut len)
+	};
+	if result != 0 {
+		return Err(std::io::Error::last_os_error());
+	}
+
+	Ok(crate::conn::PeerCredentials {
+		uid: cred.uid,
+		gid: cred.gid,
+		// The pid is 0 if the peer is in a different pid namespace.
+		pid: u32::try_from(cred.pid).ok().filter(|&pid| pid != 0),
+	})
+}
+
+/// Gets the credentials of the peer of a unix socket with `getpeereid`, which uses `LOCAL_PEERCRED`
+#[cfg(any(
+	target_os = "dragonfly",
+	target_os = "freebsd",
+	target_os = "ios",
+	target_os = "macos",
+	target_os = "netbsd",
+	target_os = "openbsd",
+))]
+fn peer_credentials(fd: std::os::fd::BorrowedFd<'_>) -> std::io::Result<crate::conn::PeerCredentials> {
+	let mut uid = 0;
+	let mut gid = 0;
+
+	// SAFETY: uid and gid are valid for writes.
+	let result = unsafe { libc::getpeereid(std::os::fd::AsRawFd::as_raw_fd(&fd), &raw mut uid, &raw mut gid) };
+	if result != 0 {
+		return Err(std::io::Error::last_os_error());
+	}
+
+	Ok(crate::conn::PeerCredentials { uid, gid, pid: None })
+}
+
+#[cfg(not(any(
+	target_os = "android",
+	target_os = "dragonfly",
+	target_os = "freebsd",
+	target_os = "ios",
+	target_os = "linux",
+	target_os = "macos",
+	target_os = "netbsd",
+	target_os = "openbsd",
+)))]
+fn peer_credentials(_fd: std::os::fd::BorrowedFd<'_>) -> std::io::Result<crate::conn::PeerCredentials> {
+	Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "peer credentials are not supported on this platform"))
+}
+
+/// What a file descriptor that was passed by systemd socket activation is.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum SocketKind {
+	/// A connected unix stream socket
+	Connected,
+
+	/// A unix stream socket that is listening for connections
+	Listening,
+
+	/// Anything else
+	Other,
+}
+
+/// Gets what the given file descriptor is.
+pub(crate) fn socket_kind(fd: std::os::fd::BorrowedFd<'_>) -> std::io::Result<SocketKind> {
+	let fd = std::os::fd::AsRawFd::as_raw_fd(&fd);
+
+	let getsockopt_int = |option| {
+		let mut value: libc::c_int = 0;
+		let mut len: libc::socklen_t = std::mem::size_of_val(&value).try_into().expect("c_int is small");
+
+		// SAFETY: value and len are valid for writes, and len is the size of value.
+		let result = unsafe { libc::getsockopt(fd, libc::SOL_SOCKET, option, (&raw mut value).cast(), &raw mut len) };
+		if result != 0 {
+			return Err(std::io::Error::last_os_error());
+		}
+
+		Ok(value)
+	};
+
+	let r#type = match getsockopt_int(libc::SO_TYPE) {
+		Ok(r#type) => r#type,
+		Err(err) if err.raw_os_error() == Some(libc::ENOTSOCK) => return Ok(SocketKind::Other),
+		Err(err) => return Err(err),
+	};
+	if r#type != libc::SOCK_STREAM {
+		return Ok(SocketKind::Other);
+	}
+
+	// SAFETY: sockaddr_storage is a plain C struct, for which all zeroes is a valid value.
+	let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+	let mut len: libc::socklen_t = std::mem::size_of_val(&addr).try_into().expect("sockaddr_storage is small");
+
+	// SAFETY: addr and len are valid for writes, and len is the size of addr.
+	let result = unsafe { libc::getsockname(fd, (&raw mut addr).cast(), &raw mut len) };
+	if result != 0 {
+		return Err(std::io::Error::last_os_error());
+	}
+	if libc::c_int::from(addr.ss_family) != libc::AF_UNIX {
+		return Ok(SocketKind::Other);
+	}
+
+	if getsockopt_int(libc::SO_ACCEPTCONN)? == 0 {
+		Ok(SocketKind::Connected)
+	}
+	else {
+		Ok(SocketKind::Listening)
+	}
+}
+
+/// Sets the close-on-exec flag of the given file descriptor, so that it is not inherited by processes that this process spawns.
+///
+/// Fails with `EBADF` if the file descriptor is not open.
+pub(crate) fn set_cloexec(fd: std::os::fd::RawFd) -> std::io::Result<()> {
+	// SAFETY: F_GETFD and F_SETFD only operate on the flags of the file descriptor, and fail if it is not open.
+	unsafe {
+		let flags = libc::fcntl(fd, libc::F_GETFD);
+		if flags == -1 || libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) == -1 {
+			return Err(std::io::Error::last_os_error());
+		}
+	}
+
+	Ok(())
+}
+
+impl std::os::fd::AsFd for Stream {
+	fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+		match self {
+			Stream::Other(_) => panic!("connections opened with Connection::from_stream do not have a file descriptor"),
+			Stream::Tcp(stream) => stream.as_fd(),
+			Stream::Unix(stream) => stream.as_fd(),
+		}
+	}
+}
+
+impl std::fmt::Debug for Stream {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Stream::Other(_) => f.debug_tuple("Other").finish_non_exhaustive(),
+			Stream::Tcp(stream) => f.debug_tuple("Tcp").field(stream).finish(),
+			Stream::Unix(stream) => f.debug_tuple("Unix").field(stream).finish(),
+		}
+	}
+}
+
+/// A stream that can be read from and written to.
+pub(crate) trait ReadWrite: std::io::Read + std::io::Write {}
+
+impl<T> ReadWrite for T where T: std::io::Read + std::io::Write {}
+
+/// Locks a user-provided stream. The stream is only ever locked for the duration of a single read or write,
+/// so a poisoned lock only means that a read or write panicked, and the stream is still usable.
+fn lock<'a>(stream: &'a std::sync::Mutex<dyn ReadWrite + Send>) -> std::sync::MutexGuard<'a, dyn ReadWrite + Send + 'static> {
+	stream.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Reads from a stream, and queues the file descriptors that are received along with the data if it is a unix socket.
+///
+/// The file descriptors of a D-Bus message are sent along with its first byte, so they are queued
+/// by the time the message has been read, after the file descriptors of all the messages before it.
+#[derive(Debug)]
+pub(crate) struct FdReader {
+	stream: Stream,
+	control_buf: Vec<u64>,
+	fds: std::collections::VecDeque<std::os::fd::OwnedFd>,
+	bytes_read: u64,
+}
+
+impl FdReader {
+	pub(crate) fn new(stream: Stream) -> Self {
+		FdReader {
+			control_buf: match stream { Stream::Other(_) | Stream::Tcp(_) => vec![], Stream::Unix(_) => control_buf(MAX_FDS) },
+			stream,
+			fds: Default::default(),
+			bytes_read: 0,
+		}
+	}
+
+	pub(crate) fn stream(&self) -> &Stream {
+		&self.stream
+	}
+
+	/// The number of bytes read from the stream since the reader was created or [`FdReader::reset_bytes_read`] was called.
+	pub(crate) fn bytes_read(&self) -> u64 {
+		self.bytes_read
+	}
+
+	pub(crate) fn reset_bytes_read(&mut self) {
+		self.bytes_read = 0;
+	}
+
+	/// Remove up to `n` file descriptors from the front of the queue.
+	pub(crate) fn take_fds(&mut self, n: usize) -> Vec<std::os::fd::OwnedFd> {
+		let n = n.min(self.fds.len());
+		self.fds.drain(..n).collect()
+	}
+
+	fn read_inner(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let stream = match &mut self.stream {
+			Stream::Other(stream) => return lock(stream).read(buf),
+			Stream::Tcp(stream) => return std::io::Read::read(stream, buf),
+			Stream::Unix(stream) => &*stream,
+		};
+
+		let mut iov = libc::iovec { iov_base: buf.as_mut_ptr().cast(), iov_len: buf.len() };
+
+		// SAFETY: msghdr is a plain C struct, for which all zeroes is a valid value.
+		let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+		msg.msg_iov = &raw mut iov;
+		msg.msg_iovlen = 1;
+		msg.msg_control = self.control_buf.as_mut_ptr().cast();
+		msg.msg_controllen = std::mem::size_of_val(&*self.control_buf) as _;
+
+		// SAFETY: msg points to buffers that are valid for the lengths it has for them.
+		let read = unsafe { libc::recvmsg(std::os::fd::AsRawFd::as_raw_fd(stream), &raw mut msg, libc::MSG_CMSG_CLOEXEC) };
+		let read: usize = read.try_into().map_err(|_| std::io::Error::last_os_error())?;
+
+		// SAFETY: recvmsg filled the control buffer with valid cmsghdrs up to msg.msg_controllen,
+		// and the file descriptors in SCM_RIGHTS messages are new and owned by this process.
+		unsafe {
+			let mut cmsg = libc::CMSG_FIRSTHDR(&raw const msg);
+			while !cmsg.is_null() {
+				if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+					let data = libc::CMSG_DATA(cmsg);
+					let data_len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+					for i in 0..(data_len / std::mem::size_of::<std::os::fd::RawFd>()) {
+						let mut fd = [0_u8; std::mem::size_of::<std::os::fd::RawFd>()];
+						std::ptr::copy_nonoverlapping(data.add(i * fd.len()), fd.as_mut_ptr(), fd.len());
+						self.fds.push_back(std::os::fd::FromRawFd::from_raw_fd(std::os::fd::RawFd::from_ne_bytes(fd)));
+					}
+				}
+
+				cmsg = libc::CMSG_NXTHDR(&raw const msg, cmsg);
+			}
+		}
+
+		if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+			return Err(std::io::Error::other("received more file descriptors than fit in the control buffer"));
+		}
+
+		Ok(read)
+	}
+}
+
+impl std::io::Read for FdReader {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let read = self.read_inner(buf)?;
+		self.bytes_read += read as u64;
+		Ok(read)
+	}
+}
+
+/// Writes to a stream, and sends the file descriptors set with [`FdWriter::set_fds`] along with the next write.
+///
+/// File descriptors must only be set if the stream is a unix socket.
+#[derive(Debug)]
+pub(crate) struct FdWriter {
+	stream: Stream,
+	fds: Vec<std::os::fd::RawFd>,
+	bytes_written: u64,
+}
+
+impl FdWriter {
+	pub(crate) fn new(stream: Stream) -> Self {
+		FdWriter {
+			stream,
+			fds: vec![],
+			bytes_written: 0,
+		}
+	}
+
+	pub(crate) fn stream(&self) -> &Stream {
+		&self.stream
+	}
+
+	/// Set the file descriptors to send along with the next write that writes at least one byte.
+	///
+	/// The caller must keep the file descriptors open until they have been sent or [`FdWriter::clear_fds`] has been called.
+	pub(crate) fn set_fds(&mut self, fds: &[std::os::fd::BorrowedFd<'_>]) {
+		debug_assert!(matches!(self.stream, Stream::Unix(_)), "file descriptors can only be sent over unix sockets");
+
+		self.fds.clear();
+		self.fds.extend(fds.iter().map(std::os::fd::AsRawFd::as_raw_fd));
+	}
+
+	pub(crate) fn clear_fds(&mut self) {
+		self.fds.clear();
+	}
+
+	/// The number of bytes written to the stream since the writer was created or [`FdWriter::reset_bytes_written`] was called.
+	pub(crate) fn bytes_written(&self) -> u64 {
+		self.bytes_written
+	}
+
+	pub(crate) fn reset_bytes_written(&mut self) {
+		self.bytes_written = 0;
+	}
+
+	fn write_vectored_inner(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+		let stream = match &mut self.stream {
+			Stream::Other(stream) => return lock(stream).write_vectored(bufs),
+			Stream::Tcp(stream) => return std::io::Write::write_vectored(stream, bufs),
+			Stream::Unix(stream) if self.fds.is_empty() => return std::io::Write::write_vectored(stream, bufs),
+			Stream::Unix(stream) => &*stream,
+		};
+
+		let mut control_buf = control_buf(self.fds.len());
+
+		// SAFETY: msghdr is a plain C struct, for which all zeroes is a valid value.
+		let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+		// IoSlice is guaranteed to be ABI-compatible with iovec on unix, and sendmsg does not write through msg_iov.
+		msg.msg_iov = bufs.as_ptr().cast_mut().cast();
+		msg.msg_iovlen = bufs.len() as _;
+		msg.msg_control = control_buf.as_mut_ptr().cast();
+		msg.msg_controllen = std::mem::size_of_val(&*control_buf) as _;
+
+		// SAFETY: The control buffer has space for a cmsghdr with self.fds.len() file descriptors,
+		// and msg points to buffers that are valid for the lengths it has for them.
+		let written = unsafe {
+			let fds_len = std::mem::size_of_val(&*self.fds);
+			let cmsg = libc::CMSG_FIRSTHDR(&raw const msg);
+			(*cmsg).cmsg_level = libc::SOL_SOCKET;
+			(*cmsg).cmsg_type = libc::SCM_RIGHTS;
+			(*cmsg).cmsg_len = libc::CMSG_LEN(fds_len.try_into().map_err(std::io::Error::other)?) as _;
+			std::ptr::copy_nonoverlapping(self.fds.as_ptr().cast::<u8>(), libc::CMSG_DATA(cmsg), fds_len);
+
+			libc::sendmsg(std::os::fd::AsRawFd::as_raw_fd(stream), &raw const msg, libc::MSG_NOSIGNAL)
+		};
+		let written: usize = written.try_into().map_err(|_| std::io::Error::last_os_error())?;
+
+		// The file descriptors were sent along with the bytes that were written.
+		self.fds.clear();
+
+		Ok(written)
+	}
+}
+
+impl std::io::Write for FdWriter {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.write_vectored(&[std::io::IoSlice::new(buf)])
+	}
+
+	fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+		let written = self.write_vectored_inner(bufs)?;
+		self.bytes_written += written as u64;
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		match &mut self.stream {
+			Stream::Other(stream) => lock(stream).flush(),
+			Stream::Tcp(stream) => std::io::Write::flush(stream),
+			Stream::Unix(stream) => std::io::Write::flush(stream),
+		}
+	}
+}
+
+/// A buffer for an `SCM_RIGHTS` control message with `num_fds` file descriptors, aligned for `cmsghdr`
+fn control_buf(num_fds: usize) -> Vec<u64> {
+	let fds_len: u32 = (num_fds * std::mem::size_of::<std::os::fd::RawFd>()).try_into().unwrap_or(u32::MAX);
+	// SAFETY: CMSG_SPACE only does arithmetic.
+	let len = unsafe { libc::CMSG_SPACE(fds_len) } as usize;
+	vec![0; len.div_ceil(std::mem::size_of::<u64>())]
+}
@@ -6,12 +6,33 @@
 	clippy::let_unit_value,
 	clippy::missing_errors_doc,
 	clippy::must_use_candidate,
+	clippy::too_many_lines,
+	clippy::unnecessary_debug_formatting,
 )]
 
 //! This is a pure Rust implementation of a D-Bus client.
 //!
 //! Create a client with [`Client::new`]
 //!
+//! Subscribe to signals with [`Client::signals`] and a [`MatchRule`]
+//!
+//! Watch the messages of other connections on the bus with [`Client::become_monitor`]
+//!
+//! To talk to another process directly without a message bus, accept its connections with [`Listener`] and create a client
+//! for each of them with [`Client::new_peer`]
+//!
+//! [`Connection`] and [`Client`] are `Send`, so a client can be moved to another thread or shared between threads behind a `Mutex`.
+//! They can also be split into halves that send and receive independently, with [`Connection::split`] and [`Client::split`]
+//! The error types are `Send` and `Sync`.
+//!
+//!
+//! # Features
+//!
+//! - `tracing`: Emit events with the `tracing` crate. Every sent and received message is a trace-level event with the message type, serial,
+//!   destination, member, body signature and length as fields, as is the completion of every method call made with [`Client`].
+//!   Every line of the SASL handshake is a debug-level event, with the authentication data redacted.
+//!   The fields are only evaluated for events that a subscriber is interested in.
+//!
 //!
 //! # Example
 //!
@@ -57,7 +78,7 @@
 //! # }
 //! ```
 //!
-//! ## Connect to the session bus and list all names manually
+//! ## Connect to the session bus and list all names without the macros
 //!
 //! ```rust
 //! # fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -71,19 +92,13 @@
 //!
 //! // List all names by calling the `org.freedesktop.DBus.ListNames` method
 //! // on the `/org/freedesktop/DBus` object at the destination `org.freedesktop.DBus`.
-//! let names = {
-//!     let body =
-//!         client.method_call(
-//!             "org.freedesktop.DBus",
-//!             dbus_pure::proto::ObjectPath("/org/freedesktop/DBus".into()),
-//!             "org.freedesktop.DBus",
-//!             "ListNames",
-//!             None,
-//!         )?
-//!         .ok_or("ListNames response does not have a body")?;
-//!     let body: Vec<String> = serde::Deserialize::deserialize(body)?;
-//!     body
-//! };
+//! let names: Vec<String> =
+//!     client.call0(
+//!         "org.freedesktop.DBus",
+//!         dbus_pure::proto::ObjectPath("/org/freedesktop/DBus".into()),
+//!         "org.freedesktop.DBus",
+//!         "ListNames",
+//!     )?;
 //!
 //! for name in names {
 //!     println!("{name}");
@@ -95,19 +110,111 @@
 
 pub use dbus_pure_proto as proto;
 
+mod bus;
+pub use bus::{
+	BusProxy,
+	ReleaseNameReply,
+	RequestNameFlags,
+	RequestNameReply,
+	StartServiceReply,
+};
+
 mod client;
 pub use client::{
 	Client,
+	ClientRecvHalf,
+	ClientSendHalf,
+	ClientStats,
 	CreateClientError,
+	MatchGuard,
 	MethodCallError,
+	PendingReply,
+	ReconnectError,
+	ReconnectPolicy,
 };
 
 mod conn;
 pub use conn::{
+	ActivationError,
 	BusPath,
 	ConnectError,
 	Connection,
+	ConnectionStats,
+	MessageCounts,
+	PeerCredentials,
 	RecvError,
+	RecvHalf,
 	SaslAuthType,
 	SendError,
+	SendHalf,
+	ServerGuid,
 };
+
+#[cfg(test)]
+mod fake_bus;
+
+mod fd;
+
+mod listener;
+pub use listener::{
+	AcceptError,
+	Listener,
+};
+
+mod match_rule;
+pub use match_rule::{
+	MatchMessageType,
+	MatchRule,
+};
+
+mod monitor;
+pub use monitor::{
+	Monitor,
+};
+
+#[doc(hidden)]
+pub mod private;
+
+mod signal;
+pub use signal::{
+	FromSignal,
+	PropertiesChanged,
+	RecvSignalError,
+	Signal,
+	SignalStream,
+};
+
+mod trace;
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_send_sync() {
+		fn assert_send<T>() where T: Send {}
+		fn assert_send_sync<T>() where T: Send + Sync {}
+
+		assert_send::<crate::Client>();
+		assert_send::<crate::ClientRecvHalf>();
+		assert_send::<crate::ClientSendHalf>();
+		assert_send::<crate::Connection>();
+		assert_send_sync::<crate::MatchGuard>();
+		assert_send_sync::<crate::MatchRule<'static>>();
+		assert_send::<crate::Monitor>();
+		assert_send::<crate::PendingReply>();
+		assert_send_sync::<crate::Listener>();
+		assert_send::<crate::RecvHalf>();
+		assert_send::<crate::SendHalf>();
+		assert_send::<crate::SignalStream<'static>>();
+		assert_send::<crate::SignalStream<'static, crate::PropertiesChanged<'static>>>();
+		assert_send_sync::<std::sync::Mutex<crate::Client>>();
+
+		assert_send_sync::<crate::AcceptError>();
+		assert_send_sync::<crate::ConnectError>();
+		assert_send_sync::<crate::CreateClientError>();
+		assert_send_sync::<crate::MethodCallError>();
+		assert_send_sync::<crate::ReconnectError>();
+		assert_send_sync::<crate::RecvError>();
+		assert_send_sync::<crate::RecvSignalError>();
+		assert_send_sync::<crate::SendError>();
+	}
+}
@@ -0,0 +1,414 @@
+/// A unix socket that accepts peer-to-peer connections, acting as the server of the SASL handshake.
+///
+/// There is no message bus between the two ends of an accepted connection. Use [`crate::Client::new_peer`] on both ends.
+pub struct Listener {
+	socket: std::os::unix::net::UnixListener,
+	guid: crate::conn::ServerGuid,
+	allowed_uid: Option<u32>,
+	handshake_timeout: Option<std::time::Duration>,
+}
+
+/// The file descriptor of the listening socket, to wait for it to become readable with `poll(2)` or an event loop
+/// before calling [`Listener::accept`].
+impl std::os::fd::AsFd for Listener {
+	fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+		self.socket.as_fd()
+	}
+}
+
+impl std::os::fd::AsRawFd for Listener {
+	fn as_raw_fd(&self) -> std::os::fd::RawFd {
+		self.socket.as_raw_fd()
+	}
+}
+
+impl Listener {
+	/// Listens on a new socket file at the given path. Fails if the file already exists.
+	///
+	/// The file is not removed when the listener is dropped.
+	pub fn bind(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+		let listener = std::os::unix::net::UnixListener::bind(path)?;
+		Listener::from_unix_listener(listener)
+	}
+
+	/// Listens on a socket with the given name in the abstract namespace.
+	///
+	/// Only Linux and Android support abstract sockets. This fails with [`std::io::ErrorKind::Unsupported`] on other platforms.
+	pub fn bind_abstract(name: &[u8]) -> std::io::Result<Self> {
+		let listener = bind_abstract(name)?;
+		Listener::from_unix_listener(listener)
+	}
+
+	/// Listens on the given socket, such as the one returned by [`crate::Connection::activation_listener`]
+	///
+	/// A new GUID is generated for the listener.
+	pub fn from_unix_listener(listener: std::os::unix::net::UnixListener) -> std::io::Result<Self> {
+		let guid = generate_guid()?;
+		Ok(Listener {
+			socket: listener,
+			guid,
+			allowed_uid: None,
+			handshake_timeout: None,
+		})
+	}
+
+	/// The GUID of this listener, which it sends to every client during the SASL handshake.
+	pub fn guid(&self) -> crate::conn::ServerGuid {
+		self.guid
+	}
+
+	/// Only accept clients that run as the given user, or `None` to accept clients that run as any user.
+	///
+	/// Clients always have to authenticate as the user that the kernel reports for the other end of the socket.
+	/// See [`crate::Connection::peer_credentials`]
+	///
+	/// Defaults to `None`.
+	pub fn set_allowed_uid(&mut self, uid: Option<u32>) {
+		self.allowed_uid = uid;
+	}
+
+	/// Set how long [`Listener::accept`] waits for each read and write of the SASL handshake, or `None` to wait indefinitely.
+	///
+	/// The handshake is performed on the thread that calls [`Listener::accept`], so without a timeout a client that connects
+	/// and then stops responding blocks it forever.
+	///
+	/// Defaults to `None`.
+	pub fn set_handshake_timeout(&mut self, timeout: Option<std::time::Duration>) {
+		self.handshake_timeout = timeout;
+	}
+
+	/// Accepts the next client and performs the SASL handshake with it.
+	///
+	/// The client must authenticate with the `EXTERNAL` mechanism. Unix file descriptors can be passed over the connection
+	/// if the client asks for it.
+	pub fn accept(&self) -> Result<crate::conn::Connection, AcceptError> {
+		let (stream, _) = self.socket.accept().map_err(AcceptError::Accept)?;
+		authenticate(stream, self.guid, self.allowed_uid, self.handshake_timeout)
+	}
+}
+
+/// An error from accepting a client with [`Listener::accept`].
+#[derive(Debug)]
+pub enum AcceptError {
+	Accept(std::io::Error),
+
+	/// The SASL handshake failed, or the client did not follow the protocol.
+	Authenticate(std::io::Error),
+
+	/// The client authenticated as a user other than the one that is allowed by [`Listener::set_allowed_uid`]
+	UidNotAllowed { uid: u32 },
+}
+
+impl std::fmt::Display for AcceptError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			AcceptError::Accept(_) => f.write_str("could not accept client"),
+			AcceptError::Authenticate(_) => f.write_str("could not authenticate client"),
+			AcceptError::UidNotAllowed { uid } => write!(f, "the client's user {uid} is not allowed to connect"),
+		}
+	}
+}
+
+impl std::error::Error for AcceptError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		#[allow(clippy::match_same_arms)]
+		match self {
+			AcceptError::Accept(err) => Some(err),
+			AcceptError::Authenticate(err) => Some(err),
+			AcceptError::UidNotAllowed { uid: _ } => None,
+		}
+	}
+}
+
+/// The longest line of the SASL handshake that is read from a client, so that a client cannot make the listener buffer an unbounded amount of data.
+const MAX_SASL_LINE_LEN: u64 = 16 * 1024;
+
+/// The state of the server side of the SASL handshake.
+enum SaslState {
+	/// Waiting for the client to send `AUTH`
+	Auth,
+
+	/// Sent `DATA` in response to `AUTH EXTERNAL` without an initial response, so waiting for the client to send the identity in `DATA`
+	Data,
+
+	/// Sent `OK`, so waiting for the client to send `BEGIN`
+	Begin,
+}
+
+/// Performs the server side of the SASL handshake with the client on the other end of the given stream.
+fn authenticate(
+	stream: std::os::unix::net::UnixStream,
+	guid: crate::conn::ServerGuid,
+	allowed_uid: Option<u32>,
+	handshake_timeout: Option<std::time::Duration>,
+) -> Result<crate::conn::Connection, AcceptError> {
+	use std::io::{BufRead, Read};
+
+	let stream = crate::fd::Stream::Unix(stream);
+	stream.set_read_timeout(handshake_timeout).map_err(AcceptError::Authenticate)?;
+	stream.set_write_timeout(handshake_timeout).map_err(AcceptError::Authenticate)?;
+
+	let peer_uid = stream.peer_credentials().map_err(AcceptError::Authenticate)?.uid;
+
+	let reader = stream.try_clone().map_err(AcceptError::Authenticate)?;
+	let mut reader = std::io::BufReader::new(crate::fd::FdReader::new(reader));
+	let mut read_buf = vec![];
+
+	let mut writer = crate::fd::FdWriter::new(stream);
+
+	let mut nul = [0_u8];
+	reader.read_exact(&mut nul).map_err(AcceptError::Authenticate)?;
+	if nul != [b'\0'] {
+		return Err(AcceptError::Authenticate(malformed_request()));
+	}
+
+	let ok_line = format!("OK {guid}\r\n");
+
+	let mut state = SaslState::Auth;
+	let mut unix_fds = false;
+
+	loop {
+		read_buf.clear();
+		let _ = (&mut reader).take(MAX_SASL_LINE_LEN).read_until(b'\n', &mut read_buf).map_err(AcceptError::Authenticate)?;
+		crate::trace::sasl_line("received", &read_buf);
+		let line = read_buf.strip_suffix(b"\r\n").ok_or_else(|| AcceptError::Authenticate(malformed_request()))?;
+
+		let identity = match (&state, line) {
+			(SaslState::Auth, b"AUTH EXTERNAL") => {
+				respond(&mut writer, b"DATA\r\n")?;
+				state = SaslState::Data;
+				continue;
+			},
+
+			(SaslState::Auth, line) if line.starts_with(b"AUTH EXTERNAL ") => &line[b"AUTH EXTERNAL ".len()..],
+
+			(SaslState::Data, b"DATA") => &b""[..],
+
+			(SaslState::Data, line) if line.starts_with(b"DATA ") => &line[b"DATA ".len()..],
+
+			(SaslState::Begin, b"NEGOTIATE_UNIX_FD") => {
+				respond(&mut writer, b"AGREE_UNIX_FD\r\n")?;
+				unix_fds = true;
+				continue;
+			},
+
+			(SaslState::Begin, b"BEGIN") => break,
+
+			(_, b"BEGIN") => return Err(AcceptError::Authenticate(malformed_request())),
+
+			// Other mechanisms are rejected, and cancelling or failing an attempt makes the client start over.
+			(_, line) if line.starts_with(b"AUTH") || line == b"CANCEL" || line.starts_with(b"ERROR") => {
+				respond(&mut writer, b"REJECTED EXTERNAL\r\n")?;
+				state = SaslState::Auth;
+				continue;
+			},
+
+			_ => {
+				respond(&mut writer, b"ERROR\r\n")?;
+				continue;
+			},
+		};
+
+		// An empty identity means the user that the kernel reports for the other end of the socket.
+		let uid = if identity.is_empty() { Some(peer_uid) } else { parse_uid(identity) };
+		if uid != Some(peer_uid) {
+			respond(&mut writer, b"REJECTED EXTERNAL\r\n")?;
+			state = SaslState::Auth;
+			continue;
+		}
+
+		if allowed_uid.is_some_and(|allowed_uid| allowed_uid != peer_uid) {
+			respond(&mut writer, b"REJECTED EXTERNAL\r\n")?;
+			return Err(AcceptError::UidNotAllowed { uid: peer_uid });
+		}
+
+		respond(&mut writer, ok_line.as_bytes())?;
+		state = SaslState::Begin;
+	}
+
+	// The timeouts of the connection are set separately with `Connection::set_read_timeout` and `Connection::set_write_timeout`
+	writer.stream().set_read_timeout(None).map_err(AcceptError::Authenticate)?;
+	writer.stream().set_write_timeout(None).map_err(AcceptError::Authenticate)?;
+
+	Ok(crate::conn::Connection::from_authenticated(reader, writer, unix_fds, guid))
+}
+
+fn respond(writer: &mut crate::fd::FdWriter, line: &[u8]) -> Result<(), AcceptError> {
+	use std::io::Write;
+
+	crate::trace::sasl_line("sent", line);
+	writer.write_all(line).map_err(AcceptError::Authenticate)?;
+	writer.flush().map_err(AcceptError::Authenticate)?;
+	Ok(())
+}
+
+/// Parses the user ID from the authorization identity of the SASL EXTERNAL mechanism, which is the hex encoding of its decimal digits.
+fn parse_uid(identity: &[u8]) -> Option<u32> {
+	if !identity.len().is_multiple_of(2) {
+		return None;
+	}
+
+	let mut uid = String::with_capacity(identity.len() / 2);
+	for hex in identity.chunks_exact(2) {
+		let hex = std::str::from_utf8(hex).ok()?;
+		if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+			return None;
+		}
+		uid.push(u8::from_str_radix(hex, 16).ok()?.into());
+	}
+
+	if !uid.bytes().all(|c| c.is_ascii_digit()) {
+		return None;
+	}
+	uid.parse().ok()
+}
+
+fn malformed_request() -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed request")
+}
+
+/// Generates a random GUID for a listener.
+fn generate_guid() -> std::io::Result<crate::conn::ServerGuid> {
+	use std::io::Read;
+
+	let mut guid = [0_u8; 16];
+	std::fs::File::open("/dev/urandom")?.read_exact(&mut guid)?;
+	Ok(crate::conn::ServerGuid(guid))
+}
+
+/// Listens on a unix socket with the given name in the abstract namespace.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn bind_abstract(name: &[u8]) -> std::io::Result<std::os::unix::net::UnixListener> {
+	#[cfg(target_os = "android")]
+	use std::os::android::net::SocketAddrExt;
+	#[cfg(target_os = "linux")]
+	use std::os::linux::net::SocketAddrExt;
+
+	let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+	std::os::unix::net::UnixListener::bind_addr(&addr)
+}
+
+/// Listens on a unix socket with the given name in the abstract namespace.
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn bind_abstract(_name: &[u8]) -> std::io::Result<std::os::unix::net::UnixListener> {
+	Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "abstract unix sockets are not supported on this platform"))
+}
+
+#[cfg(test)]
+mod tests {
+	/// Accepts a client on another thread, and answers one method call with the sum of its two `u32` parameters.
+	fn serve_one(listener: super::Listener) -> std::thread::JoinHandle<Result<(), super::AcceptError>> {
+		std::thread::spawn(move || {
+			let connection = listener.accept()?;
+			let mut client = crate::Client::new_peer(connection);
+
+			let (request, body) = client.recv().unwrap();
+			assert!(matches!(request.r#type, crate::proto::MessageType::MethodCall { .. }));
+			assert_eq!(request.member(), Some("Add"));
+			assert_eq!(request.sender(), None);
+			let Some(crate::proto::Variant::Tuple { elements }) = body else {
+				panic!("unexpected body {body:?}");
+			};
+			let [crate::proto::Variant::U32(a), crate::proto::Variant::U32(b)] = &elements[..] else {
+				panic!("unexpected parameters {elements:?}");
+			};
+
+			let response = crate::proto::MessageHeader {
+				r#type: crate::proto::MessageType::MethodReturn {
+					reply_serial: request.serial,
+				},
+				flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+				body_len: 0,
+				serial: 0,
+				fields: vec![].into(),
+			};
+			let _ = client.send(&response, Some(&crate::proto::Variant::U32(a + b))).unwrap();
+			Ok(())
+		})
+	}
+
+	fn add(client: &mut crate::Client) -> Result<Option<crate::proto::Variant<'static>>, crate::MethodCallError> {
+		client.method_call(
+			"org.example",
+			crate::proto::ObjectPath("/org/example".into()),
+			"org.example.Calculator",
+			"Add",
+			Some(&crate::proto::Variant::Tuple { elements: (&[crate::proto::Variant::U32(2), crate::proto::Variant::U32(3)][..]).into() }),
+		)
+	}
+
+	#[test]
+	fn test_method_call() {
+		let dir = std::env::temp_dir().join(format!("dbus-pure-test-listener-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir(&dir).unwrap();
+		let path = dir.join("socket");
+
+		let mut listener = super::Listener::bind(&path).unwrap();
+		listener.set_allowed_uid(Some(unsafe { libc::getuid() }));
+		let guid = listener.guid();
+		let server = serve_one(listener);
+
+		let connection = crate::Connection::new(crate::BusPath::UnixSocketFile(&path), crate::SaslAuthType::Uid).unwrap();
+		assert_eq!(connection.server_guid(), guid);
+		assert!(connection.supports_unix_fds());
+
+		let mut client = crate::Client::new_peer(connection);
+		assert_eq!(add(&mut client).unwrap(), Some(crate::proto::Variant::U32(5)));
+
+		server.join().unwrap().unwrap();
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_uid_not_allowed() {
+		let name = format!("dbus-pure-test-listener-{}", std::process::id());
+		let mut listener = super::Listener::bind_abstract(name.as_bytes()).unwrap();
+		let uid = unsafe { libc::getuid() };
+		listener.set_allowed_uid(Some(uid.wrapping_add(1)));
+		let server = serve_one(listener);
+
+		let stream = std::os::unix::net::UnixStream::connect_addr(&{
+			use std::os::linux::net::SocketAddrExt;
+			std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes()).unwrap()
+		}).unwrap();
+		let err = crate::Connection::from_unix_stream(stream, crate::SaslAuthType::Uid).err().unwrap();
+		assert!(matches!(err, crate::ConnectError::AuthRejected { offered } if offered == ["EXTERNAL"]));
+
+		let err = server.join().unwrap().unwrap_err();
+		assert!(matches!(err, super::AcceptError::UidNotAllowed { uid: actual } if actual == uid));
+	}
+
+	#[test]
+	fn test_handshake() {
+		use std::io::{BufRead, Write};
+
+		let name = format!("dbus-pure-test-listener-handshake-{}", std::process::id());
+		let listener = super::Listener::bind_abstract(name.as_bytes()).unwrap();
+		let guid = listener.guid();
+		let server = std::thread::spawn(move || listener.accept().map(|connection| connection.supports_unix_fds()));
+
+		let mut stream = std::os::unix::net::UnixStream::connect_addr(&{
+			use std::os::linux::net::SocketAddrExt;
+			std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes()).unwrap()
+		}).unwrap();
+		let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+		let mut exchange = |request: &[u8]| {
+			stream.write_all(request).unwrap();
+			let mut response = String::new();
+			let _ = reader.read_line(&mut response).unwrap();
+			response
+		};
+
+		assert_eq!(exchange(b"\0AUTH ANONYMOUS\r\n"), "REJECTED EXTERNAL\r\n");
+		assert_eq!(exchange(b"AUTH EXTERNAL 30\r\n"), if unsafe { libc::getuid() } == 0 { format!("OK {guid}\r\n") } else { "REJECTED EXTERNAL\r\n".to_owned() });
+		assert_eq!(exchange(b"CANCEL\r\n"), "REJECTED EXTERNAL\r\n");
+		assert_eq!(exchange(b"AUTH EXTERNAL\r\n"), "DATA\r\n");
+		assert_eq!(exchange(b"FOO\r\n"), "ERROR\r\n");
+		assert_eq!(exchange(b"DATA\r\n"), format!("OK {guid}\r\n"));
+		assert_eq!(exchange(b"NEGOTIATE_UNIX_FD\r\n"), "AGREE_UNIX_FD\r\n");
+		stream.write_all(b"BEGIN\r\n").unwrap();
+
+		assert!(server.join().unwrap().unwrap());
+	}
+}
@@ -0,0 +1,407 @@
+/// A builder for a match rule, which selects the messages that the message bus sends to a client. See [`crate::Client::add_match`]
+///
+/// A rule without any conditions matches every message. Each condition that is set narrows it down further.
+/// The rule is converted to the string that the bus expects with its `Display` impl, which takes care of quoting the values.
+///
+/// ```rust
+/// let rule =
+///     dbus_pure::MatchRule::new()
+///     .r#type(dbus_pure::MatchMessageType::Signal)
+///     .interface("org.freedesktop.DBus")
+///     .member("NameOwnerChanged")
+///     .arg(0, "org.example's.name");
+/// assert_eq!(
+///     rule.to_string(),
+///     r"type='signal',interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='org.example'\''s.name'",
+/// );
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MatchRule<'a> {
+	r#type: Option<MatchMessageType>,
+	sender: Option<std::borrow::Cow<'a, str>>,
+	interface: Option<std::borrow::Cow<'a, str>>,
+	member: Option<std::borrow::Cow<'a, str>>,
+	path: Option<crate::proto::ObjectPath<'a>>,
+	path_namespace: Option<crate::proto::ObjectPath<'a>>,
+	destination: Option<std::borrow::Cow<'a, str>>,
+	args: std::collections::BTreeMap<u8, std::borrow::Cow<'a, str>>,
+	arg0_namespace: Option<std::borrow::Cow<'a, str>>,
+	eavesdrop: Option<bool>,
+}
+
+/// The type of message that a [`MatchRule`] matches.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MatchMessageType {
+	/// `ERROR`
+	Error,
+
+	/// `METHOD_CALL`
+	MethodCall,
+
+	/// `METHOD_RETURN`
+	MethodReturn,
+
+	/// `SIGNAL`
+	Signal,
+}
+
+impl<'a> MatchRule<'a> {
+	/// The highest index of an argument that a rule can match. See [`MatchRule::arg`]
+	pub const MAX_ARG_INDEX: u8 = 63;
+
+	/// Start building a rule that matches every message.
+	pub fn new() -> Self {
+		Default::default()
+	}
+
+	/// Only match messages of the given type.
+	#[must_use]
+	pub fn r#type(mut self, r#type: MatchMessageType) -> Self {
+		self.r#type = Some(r#type);
+		self
+	}
+
+	/// Only match messages sent by the given bus name.
+	///
+	/// The bus also matches messages sent by the unique name that currently owns the given well-known name,
+	/// but [`MatchRule::matches`] only compares the name as-is.
+	#[must_use]
+	pub fn sender(mut self, sender: impl Into<std::borrow::Cow<'a, str>>) -> Self {
+		self.sender = Some(sender.into());
+		self
+	}
+
+	/// The bus name that the rule only matches messages sent by, if any.
+	pub(crate) fn sender_name(&self) -> Option<&str> {
+		self.sender.as_deref()
+	}
+
+	/// Only match messages with the given interface.
+	#[must_use]
+	pub fn interface(mut self, interface: impl Into<std::borrow::Cow<'a, str>>) -> Self {
+		self.interface = Some(interface.into());
+		self
+	}
+
+	/// Only match messages with the given member, ie method or signal name.
+	#[must_use]
+	pub fn member(mut self, member: impl Into<std::borrow::Cow<'a, str>>) -> Self {
+		self.member = Some(member.into());
+		self
+	}
+
+	/// Only match messages with the given object path.
+	#[must_use]
+	pub fn path(mut self, path: crate::proto::ObjectPath<'a>) -> Self {
+		self.path = Some(path);
+		self
+	}
+
+	/// Only match messages whose object path is the given path or one of its descendants.
+	#[must_use]
+	pub fn path_namespace(mut self, path_namespace: crate::proto::ObjectPath<'a>) -> Self {
+		self.path_namespace = Some(path_namespace);
+		self
+	}
+
+	/// Only match messages sent to the given unique name.
+	#[must_use]
+	pub fn destination(mut self, destination: impl Into<std::borrow::Cow<'a, str>>) -> Self {
+		self.destination = Some(destination.into());
+		self
+	}
+
+	/// Only match messages whose argument with the given index is a string equal to the given value.
+	///
+	/// # Panics
+	///
+	/// Panics if the index is greater than [`MatchRule::MAX_ARG_INDEX`]
+	#[must_use]
+	pub fn arg(mut self, index: u8, value: impl Into<std::borrow::Cow<'a, str>>) -> Self {
+		assert!(index <= Self::MAX_ARG_INDEX, "match rules can only match arguments 0 to {}, not {index}", Self::MAX_ARG_INDEX);
+		let _ = self.args.insert(index, value.into());
+		self
+	}
+
+	/// Only match messages whose first argument is a string that is the given bus name or starts with it followed by a `.`
+	#[must_use]
+	pub fn arg0_namespace(mut self, arg0_namespace: impl Into<std::borrow::Cow<'a, str>>) -> Self {
+		self.arg0_namespace = Some(arg0_namespace.into());
+		self
+	}
+
+	/// Set whether the bus should also send messages that match the rule but are addressed to other connections.
+	///
+	/// Most buses only allow this for privileged connections.
+	#[must_use]
+	pub fn eavesdrop(mut self, eavesdrop: bool) -> Self {
+		self.eavesdrop = Some(eavesdrop);
+		self
+	}
+
+	/// Whether the given message matches this rule.
+	///
+	/// This lets a client that receives the messages of several rules pick out the ones of a particular rule,
+	/// such as with [`crate::Client::recv_matching`]
+	pub fn matches(&self, header: &crate::proto::MessageHeader<'_>, body: Option<&crate::proto::Variant<'_>>) -> bool {
+		if let Some(r#type) = self.r#type {
+			let matches = matches!(
+				(r#type, &header.r#type),
+				(MatchMessageType::Error, crate::proto::MessageType::Error { .. }) |
+				(MatchMessageType::MethodCall, crate::proto::MessageType::MethodCall { .. }) |
+				(MatchMessageType::MethodReturn, crate::proto::MessageType::MethodReturn { .. }) |
+				(MatchMessageType::Signal, crate::proto::MessageType::Signal { .. })
+			);
+			if !matches {
+				return false;
+			}
+		}
+
+		let fields = [
+			(&self.sender, header.sender()),
+			(&self.interface, header.interface()),
+			(&self.member, header.member()),
+			(&self.destination, header.destination()),
+		];
+		if fields.into_iter().any(|(expected, actual)| expected.as_ref().is_some_and(|expected| actual != Some(&**expected))) {
+			return false;
+		}
+
+		let path = header.path().map(|path| &*path.0);
+
+		if let Some(expected) = &self.path {
+			if path != Some(&expected.0) {
+				return false;
+			}
+		}
+
+		if let Some(path_namespace) = &self.path_namespace {
+			let Some(path) = path else {
+				return false;
+			};
+			if !is_in_namespace(path, path_namespace.0.trim_end_matches('/'), '/') {
+				return false;
+			}
+		}
+
+		for (&index, expected) in &self.args {
+			if string_arg(body, index) != Some(&**expected) {
+				return false;
+			}
+		}
+
+		if let Some(arg0_namespace) = &self.arg0_namespace {
+			let Some(arg0) = string_arg(body, 0) else {
+				return false;
+			};
+			if !is_in_namespace(arg0, arg0_namespace, '.') {
+				return false;
+			}
+		}
+
+		true
+	}
+}
+
+impl std::fmt::Display for MatchRule<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let mut separator = "";
+		let mut write_condition = |f: &mut std::fmt::Formatter<'_>, key: std::fmt::Arguments<'_>, value: &str| -> std::fmt::Result {
+			write!(f, "{separator}{key}=")?;
+			separator = ",";
+
+			// Values are quoted, and a quote can't be escaped inside quotes. So a quote in the value ends the quoted part,
+			// is written escaped, and starts a new quoted part.
+			f.write_str("'")?;
+			let mut parts = value.split('\'');
+			if let Some(part) = parts.next() {
+				f.write_str(part)?;
+			}
+			for part in parts {
+				f.write_str(r"'\''")?;
+				f.write_str(part)?;
+			}
+			f.write_str("'")
+		};
+
+		if let Some(r#type) = self.r#type {
+			let r#type = match r#type {
+				MatchMessageType::Error => "error",
+				MatchMessageType::MethodCall => "method_call",
+				MatchMessageType::MethodReturn => "method_return",
+				MatchMessageType::Signal => "signal",
+			};
+			write_condition(f, format_args!("type"), r#type)?;
+		}
+
+		if let Some(sender) = &self.sender {
+			write_condition(f, format_args!("sender"), sender)?;
+		}
+
+		if let Some(interface) = &self.interface {
+			write_condition(f, format_args!("interface"), interface)?;
+		}
+
+		if let Some(member) = &self.member {
+			write_condition(f, format_args!("member"), member)?;
+		}
+
+		if let Some(path) = &self.path {
+			write_condition(f, format_args!("path"), &path.0)?;
+		}
+
+		if let Some(path_namespace) = &self.path_namespace {
+			write_condition(f, format_args!("path_namespace"), &path_namespace.0)?;
+		}
+
+		if let Some(destination) = &self.destination {
+			write_condition(f, format_args!("destination"), destination)?;
+		}
+
+		for (index, value) in &self.args {
+			write_condition(f, format_args!("arg{index}"), value)?;
+		}
+
+		if let Some(arg0_namespace) = &self.arg0_namespace {
+			write_condition(f, format_args!("arg0namespace"), arg0_namespace)?;
+		}
+
+		if let Some(eavesdrop) = self.eavesdrop {
+			write_condition(f, format_args!("eavesdrop"), if eavesdrop { "true" } else { "false" })?;
+		}
+
+		Ok(())
+	}
+}
+
+/// The argument of the message body with the given index, if it's a string.
+fn string_arg<'b>(body: Option<&'b crate::proto::Variant<'_>>, index: u8) -> Option<&'b str> {
+	let arg = match body? {
+		crate::proto::Variant::Tuple { elements } => elements.get(usize::from(index))?,
+		body if index == 0 => body,
+		_ => return None,
+	};
+	match arg {
+		crate::proto::Variant::String(arg) => Some(arg),
+		_ => None,
+	}
+}
+
+/// Whether the given name is the given namespace or one of the names under it, where the components of a name are separated by `separator`
+fn is_in_namespace(name: &str, namespace: &str, separator: char) -> bool {
+	match name.strip_prefix(namespace) {
+		Some(rest) => rest.is_empty() || rest.starts_with(separator),
+		None => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_to_string() {
+		assert_eq!(super::MatchRule::new().to_string(), "");
+
+		let rule =
+			super::MatchRule::new()
+			.r#type(super::MatchMessageType::Signal)
+			.sender("org.example")
+			.interface("org.example.Interface")
+			.member("Changed")
+			.path(crate::proto::ObjectPath("/org/example".into()))
+			.path_namespace(crate::proto::ObjectPath("/org".into()))
+			.destination(":1.5")
+			.arg(2, "two")
+			.arg(0, "zero")
+			.arg0_namespace("org.example")
+			.eavesdrop(true);
+		assert_eq!(
+			rule.to_string(),
+			"type='signal',sender='org.example',interface='org.example.Interface',member='Changed',path='/org/example',path_namespace='/org',\
+			destination=':1.5',arg0='zero',arg2='two',arg0namespace='org.example',eavesdrop='true'",
+		);
+
+		let rule = super::MatchRule::new().r#type(super::MatchMessageType::MethodReturn);
+		assert_eq!(rule.to_string(), "type='method_return'");
+	}
+
+	#[test]
+	fn test_escaping() {
+		let escaped = |value| super::MatchRule::new().arg(0, value).to_string();
+
+		assert_eq!(escaped(""), "arg0=''");
+		assert_eq!(escaped("it's"), r"arg0='it'\''s'");
+		assert_eq!(escaped("'"), r"arg0=''\'''");
+		assert_eq!(escaped("''"), r"arg0=''\'''\'''");
+
+		// Backslashes and commas inside quotes are literal.
+		assert_eq!(escaped(r"a\b,c=d"), r"arg0='a\b,c=d'");
+	}
+
+	#[test]
+	#[should_panic = "match rules can only match arguments 0 to 63, not 64"]
+	fn test_arg_index_out_of_range() {
+		let _ = super::MatchRule::new().arg(64, "");
+	}
+
+	#[test]
+	fn test_matches() {
+		let signal = |path: &'static str, member: &'static str| crate::proto::MessageHeader {
+			r#type: crate::proto::MessageType::Signal {
+				interface: "org.example.Interface".into(),
+				member: member.into(),
+				path: crate::proto::ObjectPath(path.into()),
+			},
+			flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+			body_len: 0,
+			serial: 1,
+			fields: vec![crate::proto::MessageHeaderField::Sender(":1.5".into())].into(),
+		};
+		let header = signal("/org/example/Object", "Changed");
+
+		assert!(super::MatchRule::new().matches(&header, None));
+
+		let rule = super::MatchRule::new().r#type(super::MatchMessageType::Signal).interface("org.example.Interface").member("Changed");
+		assert!(rule.matches(&header, None));
+		assert!(!rule.matches(&signal("/org/example/Object", "Removed"), None));
+		assert!(!super::MatchRule::new().r#type(super::MatchMessageType::MethodCall).matches(&header, None));
+
+		assert!(super::MatchRule::new().sender(":1.5").matches(&header, None));
+		assert!(!super::MatchRule::new().sender(":1.6").matches(&header, None));
+		assert!(!super::MatchRule::new().destination(":1.5").matches(&header, None));
+
+		let path = |path| super::MatchRule::new().path(crate::proto::ObjectPath(path));
+		assert!(path("/org/example/Object".into()).matches(&header, None));
+		assert!(!path("/org/example".into()).matches(&header, None));
+
+		let path_namespace = |path| super::MatchRule::new().path_namespace(crate::proto::ObjectPath(path));
+		assert!(path_namespace("/".into()).matches(&header, None));
+		assert!(path_namespace("/org/example".into()).matches(&header, None));
+		assert!(path_namespace("/org/example/Object".into()).matches(&header, None));
+		assert!(!path_namespace("/org/ex".into()).matches(&header, None));
+		assert!(!path_namespace("/org/example/Object/Child".into()).matches(&header, None));
+
+		let body = crate::proto::Variant::Tuple {
+			elements: vec![
+				crate::proto::Variant::String("org.example.Name".into()),
+				crate::proto::Variant::U32(1),
+				crate::proto::Variant::String("two".into()),
+			].into(),
+		};
+		assert!(super::MatchRule::new().arg(0, "org.example.Name").arg(2, "two").matches(&header, Some(&body)));
+		assert!(!super::MatchRule::new().arg(2, "three").matches(&header, Some(&body)));
+		assert!(!super::MatchRule::new().arg(1, "1").matches(&header, Some(&body)));
+		assert!(!super::MatchRule::new().arg(3, "").matches(&header, Some(&body)));
+		assert!(!super::MatchRule::new().arg(0, "").matches(&header, None));
+
+		// A body that isn't a tuple is the only argument.
+		let body = crate::proto::Variant::String("org.example".into());
+		assert!(super::MatchRule::new().arg(0, "org.example").matches(&header, Some(&body)));
+		assert!(!super::MatchRule::new().arg(1, "org.example").matches(&header, Some(&body)));
+
+		let arg0_namespace = |namespace| super::MatchRule::new().arg0_namespace(namespace);
+		assert!(arg0_namespace("org.example").matches(&header, Some(&body)));
+		assert!(arg0_namespace("org").matches(&header, Some(&body)));
+		assert!(!arg0_namespace("org.ex").matches(&header, Some(&body)));
+		assert!(!arg0_namespace("org.example.Name").matches(&header, Some(&body)));
+	}
+}
@@ -0,0 +1,134 @@
+/// A client that has become a monitor, created with [`crate::Client::become_monitor`]
+///
+/// A monitor receives every message on the bus that matches its rules, including method calls and responses
+/// between other connections and messages of unknown types. None of them are treated as responses to method calls of the monitor,
+/// and since a monitor can't send messages, none of them are replied to either.
+#[derive(Debug)]
+pub struct Monitor {
+	client: crate::Client,
+}
+
+impl Monitor {
+	pub(crate) fn new(client: crate::Client) -> Self {
+		Monitor {
+			client,
+		}
+	}
+
+	/// Receive the next message from the message bus.
+	///
+	/// Blocks until a message is received. Messages that were received by the client before it became a monitor are returned first.
+	///
+	/// Any file descriptors sent along with the message are closed.
+	pub fn recv(&mut self) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
+		self.client.recv()
+	}
+
+	/// Like [`Monitor::recv`], but returns `Ok(None)` if no message has been received yet instead of blocking,
+	/// when the connection is in non-blocking mode.
+	///
+	/// See [`crate::Client::try_recv`] for details.
+	pub fn try_recv(
+		&mut self,
+	) -> Result<Option<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>)>, crate::conn::RecvError> {
+		self.client.try_recv()
+	}
+
+	/// Like [`Monitor::recv`], but fails with [`crate::conn::RecvError::TimedOut`] if no message is received within the given timeout.
+	///
+	/// See [`crate::Client::recv_timeout`] for details.
+	pub fn recv_timeout(
+		&mut self,
+		timeout: std::time::Duration,
+	) -> Result<(crate::proto::MessageHeader<'static>, Option<crate::proto::Variant<'static>>), crate::conn::RecvError> {
+		self.client.recv_timeout(timeout)
+	}
+
+	/// Statistics about the messages received by this monitor.
+	pub fn stats(&self) -> crate::ClientStats {
+		self.client.stats()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_monitor() {
+		let message = |r#type, sender: &'static str, destination: &'static str, serial, body| (
+			crate::proto::MessageHeader {
+				r#type,
+				flags: crate::proto::message_flags::NONE,
+				body_len: 0,
+				serial,
+				fields: vec![
+					crate::proto::MessageHeaderField::Sender(sender.into()),
+					crate::proto::MessageHeaderField::Destination(destination.into()),
+				].into(),
+			},
+			Some(crate::proto::Variant::U32(body)),
+		);
+
+		let mut client = crate::fake_bus::client(move |header, body| match header.member().unwrap() {
+			"BecomeMonitor" => {
+				let rules: (Vec<String>, u32) = serde::Deserialize::deserialize(body.unwrap().clone()).unwrap();
+				assert_eq!(rules, (vec!["type='method_call'".to_owned(), "type='method_return'".to_owned()], 0));
+
+				vec![
+					crate::fake_bus::method_return(header, None),
+
+					// A method call between other connections, a response to it whose reply serial is the same as the serial
+					// of a method call of the monitor, and a message of an unknown type.
+					message(
+						crate::proto::MessageType::MethodCall {
+							member: "Frob".into(),
+							path: crate::proto::ObjectPath("/org/example".into()),
+						},
+						":1.8",
+						":1.7",
+						2,
+						1,
+					),
+					message(crate::proto::MessageType::MethodReturn { reply_serial: 2 }, ":1.7", ":1.8", 0, 2),
+					message(crate::proto::MessageType::Unknown { r#type: 5 }, ":1.7", ":1.8", 0, 3),
+				]
+			},
+
+			"Probe" => vec![],
+
+			member => panic!("unexpected method call {member}"),
+		});
+
+		let header = crate::client::method_call_header("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Probe").unwrap();
+		let pending = client.send_with_reply(&header, None).unwrap();
+		assert_eq!(pending.serial(), 2);
+
+		let mut monitor = client.become_monitor(&[
+			crate::MatchRule::new().r#type(crate::MatchMessageType::MethodCall),
+			crate::MatchRule::new().r#type(crate::MatchMessageType::MethodReturn),
+		]).unwrap();
+
+		let (header, body) = monitor.recv().unwrap();
+		assert!(matches!(header.r#type, crate::proto::MessageType::MethodCall { ref member, .. } if member == "Frob"), "{header:?}");
+		assert_eq!(body, Some(crate::proto::Variant::U32(1)));
+
+		// The response is not handed to the pending reply.
+		let (header, body) = monitor.recv_timeout(std::time::Duration::from_secs(1)).unwrap();
+		assert!(matches!(header.r#type, crate::proto::MessageType::MethodReturn { reply_serial: 2 }), "{header:?}");
+		assert_eq!(header.destination(), Some(":1.8"));
+		assert_eq!(body, Some(crate::proto::Variant::U32(2)));
+
+		let (header, body) = monitor.recv().unwrap();
+		assert!(matches!(header.r#type, crate::proto::MessageType::Unknown { r#type: 5 }), "{header:?}");
+		assert_eq!(body, Some(crate::proto::Variant::U32(3)));
+
+		let err = monitor.recv_timeout(std::time::Duration::from_millis(100)).unwrap_err();
+		assert!(matches!(err, crate::conn::RecvError::TimedOut), "{err:?}");
+
+		let stats = monitor.stats();
+		assert_eq!(stats.connection.received.method_calls, 1);
+		assert_eq!(stats.connection.received.unknown, 1);
+		assert_eq!(stats.queued_messages, 0);
+
+		drop(pending);
+	}
+}
@@ -0,0 +1,80 @@
+//! Items used by the code that `#[dbus_pure_macros::interface]` generates. They are not part of the public API.
+
+/// Stands in for the return type `T` of a method of an interface.
+///
+/// The generated code calls the methods of the traits in this module on a `&&Response<T>`, so the traits that are implemented for
+/// `&Response<T>` take precedence for the return types they apply to, and the ones implemented for `Response<T>` apply to all other return types.
+/// This way the generated code depends on the return type itself rather than how it's spelled.
+pub struct Response<T>(pub std::marker::PhantomData<fn() -> T>);
+
+/// A return type that implements [`crate::proto::ExpectedSignature`] has that signature.
+pub trait KnownSignature {
+	fn expected_signature(&self) -> Option<crate::proto::Signature>;
+}
+
+impl<T> KnownSignature for &Response<T> where T: crate::proto::ExpectedSignature {
+	fn expected_signature(&self) -> Option<crate::proto::Signature> {
+		<T as crate::proto::ExpectedSignature>::expected_signature()
+	}
+}
+
+/// Any other return type accepts a response of any signature.
+pub trait AnySignature {
+	fn expected_signature(&self) -> Option<crate::proto::Signature> {
+		None
+	}
+}
+
+impl<T> AnySignature for Response<T> {}
+
+/// A `Variant` return type is the response body as-is.
+pub trait ConvertVariant {
+	fn convert_body(&self, body: Option<crate::proto::Variant<'static>>) -> Result<crate::proto::Variant<'static>, crate::MethodCallError>;
+}
+
+impl ConvertVariant for &Response<crate::proto::Variant<'static>> {
+	fn convert_body(&self, body: Option<crate::proto::Variant<'static>>) -> Result<crate::proto::Variant<'static>, crate::MethodCallError> {
+		body.ok_or(crate::MethodCallError::UnexpectedResponse(None))
+	}
+}
+
+/// Any other return type is deserialized from the response body with serde.
+pub trait ConvertDeserialize<T> {
+	fn convert_body(&self, body: Option<crate::proto::Variant<'static>>) -> Result<T, crate::MethodCallError>;
+}
+
+impl<T> ConvertDeserialize<T> for Response<T> where T: serde::de::DeserializeOwned {
+	fn convert_body(&self, body: Option<crate::proto::Variant<'static>>) -> Result<T, crate::MethodCallError> {
+		let body = body.ok_or(crate::MethodCallError::UnexpectedResponse(None))?;
+		T::deserialize(body).map_err(|err| crate::MethodCallError::UnexpectedResponse(Some(err)))
+	}
+}
+
+/// Call the method with [`crate::Client::method_call_with_signature`] if the return type has an expected signature,
+/// and [`crate::Client::method_call`] otherwise.
+pub fn method_call(
+	client: &mut crate::Client,
+	destination: &str,
+	path: crate::proto::ObjectPath<'_>,
+	interface: &str,
+	member: &str,
+	parameters: Option<&crate::proto::Variant<'_>>,
+	expected_signature: Option<&crate::proto::Signature>,
+) -> Result<Option<crate::proto::Variant<'static>>, crate::MethodCallError> {
+	client.method_call_with_expected_signature(destination, path, interface, member, parameters, expected_signature)
+}
+
+/// Like [`method_call`], but for a method that sends or receives file descriptors.
+#[allow(clippy::too_many_arguments)]
+pub fn method_call_with_fds(
+	client: &mut crate::Client,
+	destination: &str,
+	path: crate::proto::ObjectPath<'_>,
+	interface: &str,
+	member: &str,
+	parameters: Option<&crate::proto::Variant<'_>>,
+	fds: Vec<std::os::fd::OwnedFd>,
+	expected_signature: Option<&crate::proto::Signature>,
+) -> Result<(Option<crate::proto::Variant<'static>>, Vec<std::os::fd::OwnedFd>), crate::MethodCallError> {
+	client.method_call_with_fds_and_expected_signature(destination, path, interface, member, parameters, fds, expected_signature)
+}
@@ -0,0 +1,441 @@
+/// A signal that was received by a [`SignalStream`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signal {
+	/// The bus name of the connection that sent the signal, if the message has one.
+	pub sender: Option<String>,
+
+	/// The path of the object that emitted the signal.
+	pub path: crate::proto::ObjectPath<'static>,
+
+	/// The interface of the signal.
+	pub interface: String,
+
+	/// The name of the signal.
+	pub member: String,
+
+	/// The body of the signal, ie its arguments.
+	pub body: Option<crate::proto::Variant<'static>>,
+}
+
+impl Signal {
+	fn new(header: crate::proto::MessageHeader<'static>, body: Option<crate::proto::Variant<'static>>) -> Self {
+		let sender = header.sender().map(ToOwned::to_owned);
+		let crate::proto::MessageType::Signal { interface, member, path } = header.r#type else {
+			unreachable!("only signals are received");
+		};
+		Signal {
+			sender,
+			path,
+			interface: interface.into_owned(),
+			member: member.into_owned(),
+			body,
+		}
+	}
+}
+
+/// A type that received signals can be converted to, to receive them as that type from a [`SignalStream`]
+pub trait FromSignal: Sized {
+	/// The error of receiving a signal as this type, which includes the error of receiving the signal itself.
+	type Error: From<crate::conn::RecvError>;
+
+	/// Convert the given signal to a value of this type.
+	fn from_signal(signal: Signal) -> Result<Self, Self::Error>;
+}
+
+impl FromSignal for Signal {
+	type Error = crate::conn::RecvError;
+
+	fn from_signal(signal: Signal) -> Result<Self, Self::Error> {
+		Ok(signal)
+	}
+}
+
+/// The `org.freedesktop.DBus.Properties.PropertiesChanged` signal, created with [`crate::Client::subscribe_properties_changed`]
+/// or converted from the body of the signal with [`crate::proto::FromVariant`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertiesChanged<'a> {
+	/// The interface whose properties changed.
+	pub interface_name: std::borrow::Cow<'a, str>,
+
+	/// The new values of the properties that changed, keyed by their names.
+	///
+	/// The values are unwrapped from the `Variant::Variant` that they're sent in.
+	pub changed_properties: std::collections::HashMap<std::borrow::Cow<'a, str>, crate::proto::Variant<'a>>,
+
+	/// The names of the properties that changed, but whose new values were not sent.
+	pub invalidated_properties: Vec<std::borrow::Cow<'a, str>>,
+}
+
+impl crate::proto::FromVariant for PropertiesChanged<'static> {
+	fn from_variant(variant: &crate::proto::Variant<'_>) -> Result<Self, crate::proto::FromVariantError> {
+		let (interface_name, changed_properties, invalidated_properties): (
+			String,
+			std::collections::HashMap<String, crate::proto::Variant<'static>>,
+			Vec<String>,
+		) = crate::proto::FromVariant::from_variant(variant)?;
+
+		Ok(PropertiesChanged {
+			interface_name: interface_name.into(),
+			changed_properties:
+				changed_properties.into_iter()
+				.map(|(name, value)| {
+					let value = match value {
+						crate::proto::Variant::Variant(value) => value.into_owned(),
+						value => value,
+					};
+					(name.into(), value)
+				})
+				.collect(),
+			invalidated_properties: invalidated_properties.into_iter().map(Into::into).collect(),
+		})
+	}
+}
+
+impl FromSignal for PropertiesChanged<'static> {
+	type Error = RecvSignalError;
+
+	fn from_signal(signal: Signal) -> Result<Self, Self::Error> {
+		let Some(body) = signal.body else {
+			return Err(RecvSignalError::Convert(crate::proto::FromVariantError::InvalidValue {
+				expected: "a body with signature sa{sv}as".into(),
+				actual: "no body".to_owned(),
+			}));
+		};
+
+		crate::proto::FromVariant::from_variant(&body).map_err(RecvSignalError::Convert)
+	}
+}
+
+/// An error from receiving a signal as a type that it needs to be converted to. See [`FromSignal`]
+#[derive(Debug)]
+pub enum RecvSignalError {
+	Convert(crate::proto::FromVariantError),
+	Recv(crate::conn::RecvError),
+}
+
+impl From<crate::conn::RecvError> for RecvSignalError {
+	fn from(err: crate::conn::RecvError) -> Self {
+		RecvSignalError::Recv(err)
+	}
+}
+
+impl std::fmt::Display for RecvSignalError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			RecvSignalError::Convert(_) => f.write_str("could not convert signal"),
+			RecvSignalError::Recv(_) => f.write_str("could not receive signal"),
+		}
+	}
+}
+
+impl std::error::Error for RecvSignalError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			RecvSignalError::Convert(err) => Some(err),
+			RecvSignalError::Recv(err) => Some(err),
+		}
+	}
+}
+
+/// The signals that match a match rule, created with [`crate::Client::signals`]
+///
+/// This is an iterator that receives the next matching signal every time it's advanced, and never ends.
+/// Messages that don't match are queued in the client as [`crate::Client::recv_matching`] does, so they're still returned
+/// from the other `recv` functions of the client. Call methods through [`SignalStream::client`] while the stream is alive.
+///
+/// The match rule is removed when the stream is dropped.
+///
+/// If the rule has a well-known name as its sender, it's replaced with the unique name that owns it when the stream is created,
+/// since signals are sent with the unique name of their sender. Creating the stream fails if the name has no owner.
+///
+/// The signals are returned as [`Signal`]s, or as another type that implements [`FromSignal`] like [`PropertiesChanged`]
+///
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// #
+/// let connection = dbus_pure::Connection::new(dbus_pure::BusPath::Session, dbus_pure::SaslAuthType::Uid)?;
+/// let mut client = dbus_pure::Client::new(connection)?;
+///
+/// let mut signals = client.signals(
+///     dbus_pure::MatchRule::new()
+///     .path(dbus_pure::proto::ObjectPath("/org/freedesktop/ScreenSaver".into()))
+///     .interface("org.freedesktop.ScreenSaver")
+///     .member("ActiveChanged"),
+/// )?;
+///
+/// while let Some(signal) = signals.next() {
+///     let body = signal?.body.ok_or("ActiveChanged signal does not have a body")?;
+///     let locked: bool = serde::Deserialize::deserialize(body)?;
+///     println!("Screen is {}", if locked { "locked" } else { "unlocked" });
+///
+///     // The client is still available to call methods in response to the signal.
+///     let _: Vec<String> =
+///         signals.client().call0(
+///             "org.freedesktop.DBus",
+///             dbus_pure::proto::ObjectPath("/org/freedesktop/DBus".into()),
+///             "org.freedesktop.DBus",
+///             "ListNames",
+///         )?;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SignalStream<'a, T = Signal> {
+	client: &'a mut crate::Client,
+	rule: crate::MatchRule<'a>,
+	_guard: crate::MatchGuard,
+	_signal: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<'a, T> SignalStream<'a, T> {
+	pub(crate) fn new(client: &'a mut crate::Client, rule: crate::MatchRule<'a>) -> Result<Self, crate::MethodCallError> {
+		let mut rule = rule.r#type(crate::MatchMessageType::Signal);
+
+		// Signals are sent with the unique name of their sender, so a well-known name would never match them locally.
+		if let Some(sender) = rule.sender_name().filter(|sender| !sender.starts_with(':')).map(ToOwned::to_owned) {
+			let owner = client.bus().get_name_owner(&sender)?;
+			rule = rule.sender(owner);
+		}
+
+		let guard = client.add_match(&rule)?;
+		Ok(SignalStream {
+			client,
+			rule,
+			_guard: guard,
+			_signal: Default::default(),
+		})
+	}
+
+	/// The client that receives the signals.
+	pub fn client(&mut self) -> &mut crate::Client {
+		self.client
+	}
+
+	/// Like [`Iterator::next`], but fails with [`crate::conn::RecvError::TimedOut`] if no matching signal is received within the given timeout.
+	///
+	/// See [`crate::Client::recv_timeout`] for details.
+	pub fn next_timeout(&mut self, timeout: std::time::Duration) -> Result<T, T::Error> where T: FromSignal {
+		let rule = &self.rule;
+		let (header, body) = self.client.recv_matching_timeout(timeout, |header, body| rule.matches(header, body))?;
+		T::from_signal(Signal::new(header, body))
+	}
+}
+
+impl<T> Iterator for SignalStream<'_, T> where T: FromSignal {
+	type Item = Result<T, T::Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let rule = &self.rule;
+		let result = self.client.recv_matching(|header, body| rule.matches(header, body));
+		Some(result.map_err(Into::into).and_then(|(header, body)| T::from_signal(Signal::new(header, body))))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_signals() {
+		let signal = |member: &'static str, body| (
+			crate::proto::MessageHeader {
+				r#type: crate::proto::MessageType::Signal {
+					interface: "org.example".into(),
+					member: member.into(),
+					path: crate::proto::ObjectPath("/org/example".into()),
+				},
+				flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+				body_len: 0,
+				serial: 0,
+				fields: vec![crate::proto::MessageHeaderField::Sender(":1.5".into())].into(),
+			},
+			Some(crate::proto::Variant::U32(body)),
+		);
+
+		let mut client = crate::fake_bus::client(move |header, _| match header.member().unwrap() {
+			"AddMatch" | "RemoveMatch" => vec![crate::fake_bus::method_return(header, None)],
+			"Emit" => vec![
+				signal("Changed", 1),
+				signal("Removed", 2),
+				signal("Changed", 3),
+				crate::fake_bus::method_return(header, None),
+			],
+			member => panic!("unexpected method call {member}"),
+		});
+
+		let mut signals = client.signals(crate::MatchRule::new().interface("org.example").member("Changed")).unwrap();
+
+		// The signals are received while waiting for the response, so they're queued.
+		let _ = signals.client().method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Emit", None).unwrap();
+
+		let signal = signals.next().unwrap().unwrap();
+		assert_eq!(signal, super::Signal {
+			sender: Some(":1.5".to_owned()),
+			path: crate::proto::ObjectPath("/org/example".into()),
+			interface: "org.example".to_owned(),
+			member: "Changed".to_owned(),
+			body: Some(crate::proto::Variant::U32(1)),
+		});
+		let signal = signals.next_timeout(std::time::Duration::from_millis(100)).unwrap();
+		assert_eq!(signal.body, Some(crate::proto::Variant::U32(3)));
+
+		let err = signals.next_timeout(std::time::Duration::from_millis(100)).unwrap_err();
+		assert!(matches!(err, crate::conn::RecvError::TimedOut), "{err:?}");
+		drop(signals);
+
+		// The signal that didn't match is left for the client.
+		let (header, body) = client.recv().unwrap();
+		assert_eq!(header.member(), Some("Removed"));
+		assert_eq!(body, Some(crate::proto::Variant::U32(2)));
+		assert_eq!(client.stats().queued_messages, 0);
+	}
+
+	#[test]
+	fn test_signals_well_known_sender() {
+		let rules = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+		let mut client = crate::fake_bus::client({
+			let rules = rules.clone();
+			move |header, body| match header.member().unwrap() {
+				"AddMatch" => {
+					rules.lock().unwrap().push(body.unwrap().as_string().unwrap().to_owned());
+					vec![crate::fake_bus::method_return(header, None)]
+				},
+				"RemoveMatch" => vec![crate::fake_bus::method_return(header, None)],
+				"GetNameOwner" => {
+					assert_eq!(body.unwrap().as_string(), Some("org.example.Service"));
+					vec![crate::fake_bus::method_return(header, Some(crate::proto::Variant::String(":1.5".into())))]
+				},
+				"Emit" => vec![
+					(
+						crate::proto::MessageHeader {
+							r#type: crate::proto::MessageType::Signal {
+								interface: "org.example".into(),
+								member: "Changed".into(),
+								path: crate::proto::ObjectPath("/org/example".into()),
+							},
+							flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+							body_len: 0,
+							serial: 0,
+							fields: vec![crate::proto::MessageHeaderField::Sender(":1.5".into())].into(),
+						},
+						None,
+					),
+					crate::fake_bus::method_return(header, None),
+				],
+				member => panic!("unexpected method call {member}"),
+			}
+		});
+
+		let mut signals = client.signals(crate::MatchRule::new().sender("org.example.Service").member("Changed")).unwrap();
+		assert_eq!(*rules.lock().unwrap(), ["type='signal',sender=':1.5',member='Changed'"]);
+
+		// The signal is sent by the unique name that owns the well-known name, and still matches.
+		let _ = signals.client().method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Emit", None).unwrap();
+		let signal = signals.next_timeout(std::time::Duration::from_secs(1)).unwrap();
+		assert_eq!(signal.sender.as_deref(), Some(":1.5"));
+		drop(signals);
+
+		assert_eq!(client.stats().queued_messages, 0);
+	}
+
+	#[test]
+	fn test_properties_changed_decode() {
+		// Captured with `dbus-monitor --binary` from signals emitted with `gdbus emit` and `dbus-send`
+		let mpris = b"l\x04\x01\x01\xf4\x00\x00\x00\x02\x00\x00\x00\x85\x00\x00\x00\x01\x01o\x00\x17\x00\x00\x00/org/mpris/MediaPlayer2\x00\x02\x01s\x00\x1f\x00\x00\x00org.freedesktop.DBus.Properties\x00\x08\x01g\x00\x08sa{sv}as\x00\x00\x00\x03\x01s\x00\x11\x00\x00\x00PropertiesChanged\x00\x00\x00\x00\x00\x00\x00\x07\x01s\x00\x04\x00\x00\x00:1.1\x00\x00\x00\x00\x1d\x00\x00\x00org.mpris.MediaPlayer2.Player\x00\x00\x00\xc8\x00\x00\x00\x0e\x00\x00\x00PlaybackStatus\x00\x01s\x00\x00\x00\x07\x00\x00\x00Playing\x00\x00\x00\x00\x00\x08\x00\x00\x00Metadata\x00\x05a{sv}\x00\x88\x00\x00\x00\x0d\x00\x00\x00mpris:trackid\x00\x01o\x00\x00\x00\x00\x1f\x00\x00\x00/org/mpris/MediaPlayer2/Track/1\x00\x00\x00\x00\x00\x0c\x00\x00\x00xesam:artist\x00\x02as\x00\x00\x00\x00\x0b\x00\x00\x00\x06\x00\x00\x00Artist\x00\x00\x0c\x00\x00\x00mpris:length\x00\x01x\x00\x00\x00\x00\x00\x00\x1cN\x0e\x00\x00\x00\x00\x00\x00\x00\x00";
+		let network_manager = b"l\x04\x01\x01N\x00\x00\x00\x02\x00\x00\x00\x8d\x00\x00\x00\x01\x01o\x00\x1f\x00\x00\x00/org/freedesktop/NetworkManager\x00\x02\x01s\x00\x1f\x00\x00\x00org.freedesktop.DBus.Properties\x00\x08\x01g\x00\x08sa{sv}as\x00\x00\x00\x03\x01s\x00\x11\x00\x00\x00PropertiesChanged\x00\x00\x00\x00\x00\x00\x00\x07\x01s\x00\x04\x00\x00\x00:1.2\x00\x00\x00\x00\x1e\x00\x00\x00org.freedesktop.NetworkManager\x00\x00\x00\x00\x00\x00\x22\x00\x00\x00\x11\x00\x00\x00ActiveConnections\x00\x00\x00\x05\x00\x00\x00State\x00";
+		let no_body = b"l\x04\x01\x01\x00\x00\x00\x00\x02\x00\x00\x00m\x00\x00\x00\x01\x01o\x00\x0c\x00\x00\x00/org/example\x00\x00\x00\x00\x02\x01s\x00\x1f\x00\x00\x00org.freedesktop.DBus.Properties\x00\x03\x01s\x00\x11\x00\x00\x00PropertiesChanged\x00\x00\x00\x00\x00\x00\x00\x07\x01s\x00\x04\x00\x00\x00:1.3\x00\x00\x00\x00";
+
+		let decode = |message: &[u8]| {
+			let (header, body, read) = crate::proto::deserialize_message(message).unwrap();
+			assert_eq!(read, message.len());
+			let signal = super::Signal::new(header.into_owned(), body.map(crate::proto::Variant::into_owned));
+			<super::PropertiesChanged<'static> as super::FromSignal>::from_signal(signal)
+		};
+
+		// Values that are containers are returned as-is, and an empty list of invalidated properties is empty.
+		let properties_changed = decode(mpris).unwrap();
+		assert_eq!(properties_changed.interface_name, "org.mpris.MediaPlayer2.Player");
+		assert_eq!(properties_changed.changed_properties.len(), 2);
+		assert_eq!(properties_changed.changed_properties["PlaybackStatus"], crate::proto::Variant::String("Playing".into()));
+		let metadata = &properties_changed.changed_properties["Metadata"];
+		assert_eq!(metadata.get("mpris:trackid"), Some(&crate::proto::Variant::ObjectPath(crate::proto::ObjectPath("/org/mpris/MediaPlayer2/Track/1".into()))));
+		assert_eq!(metadata.get("xesam:artist").and_then(crate::proto::Variant::as_array_string), Some(&["Artist".into()][..]));
+		assert_eq!(metadata.get("mpris:length"), Some(&crate::proto::Variant::I64(240_000_000)));
+		assert!(properties_changed.invalidated_properties.is_empty());
+
+		let properties_changed = decode(network_manager).unwrap();
+		assert_eq!(properties_changed, super::PropertiesChanged {
+			interface_name: "org.freedesktop.NetworkManager".into(),
+			changed_properties: Default::default(),
+			invalidated_properties: vec!["ActiveConnections".into(), "State".into()],
+		});
+
+		let err = decode(no_body).unwrap_err();
+		assert!(matches!(err, super::RecvSignalError::Convert(crate::proto::FromVariantError::InvalidValue { .. })), "{err:?}");
+	}
+
+	#[test]
+	fn test_subscribe_properties_changed() {
+		let properties_changed = |sender: &'static str, interface: &'static str, value| (
+			crate::proto::MessageHeader {
+				r#type: crate::proto::MessageType::Signal {
+					interface: "org.freedesktop.DBus.Properties".into(),
+					member: "PropertiesChanged".into(),
+					path: crate::proto::ObjectPath("/org/example".into()),
+				},
+				flags: crate::proto::message_flags::NO_REPLY_EXPECTED,
+				body_len: 0,
+				serial: 0,
+				fields: vec![crate::proto::MessageHeaderField::Sender(sender.into())].into(),
+			},
+			Some(crate::proto::Variant::tuple([
+				crate::proto::Variant::String(interface.into()),
+				crate::proto::Variant::from_map_iter(crate::proto::Signature::String, crate::proto::Signature::Variant, [(
+					crate::proto::Variant::String("Value".into()),
+					crate::proto::Variant::Variant(Box::new(crate::proto::Variant::U32(value)).into()),
+				)]).unwrap(),
+				crate::proto::Variant::ArrayString(vec![].into()),
+			])),
+		);
+
+		let rules = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+		let mut client = crate::fake_bus::client({
+			let rules = rules.clone();
+			move |header, body| match header.member().unwrap() {
+				"AddMatch" => {
+					rules.lock().unwrap().push(body.unwrap().as_string().unwrap().to_owned());
+					vec![crate::fake_bus::method_return(header, None)]
+				},
+				"RemoveMatch" => vec![crate::fake_bus::method_return(header, None)],
+				"GetNameOwner" => vec![crate::fake_bus::method_return(header, Some(crate::proto::Variant::String(":1.5".into())))],
+				"Emit" => vec![
+					properties_changed(":1.6", "org.example", 1),
+					properties_changed(":1.5", "org.example.Other", 2),
+					properties_changed(":1.5", "org.example", 3),
+					crate::fake_bus::method_return(header, None),
+				],
+				member => panic!("unexpected method call {member}"),
+			}
+		});
+
+		let mut stream =
+			client.subscribe_properties_changed("org.example", crate::proto::ObjectPath("/org/example".into()), Some("org.example"))
+			.unwrap();
+		assert_eq!(*rules.lock().unwrap(), [
+			"type='signal',sender=':1.5',interface='org.freedesktop.DBus.Properties',member='PropertiesChanged',path='/org/example',arg0='org.example'",
+		]);
+
+		let _ = stream.client().method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Emit", None).unwrap();
+
+		// Only the signal of the owner of the name for the given interface matches.
+		let properties_changed = stream.next().unwrap().unwrap();
+		assert_eq!(properties_changed.interface_name, "org.example");
+		assert_eq!(properties_changed.changed_properties["Value"], crate::proto::Variant::U32(3));
+
+		let err = stream.next_timeout(std::time::Duration::from_millis(100)).unwrap_err();
+		assert!(matches!(err, super::RecvSignalError::Recv(crate::conn::RecvError::TimedOut)), "{err:?}");
+		drop(stream);
+
+		assert_eq!(client.stats().queued_messages, 2);
+	}
+}
@@ -0,0 +1,200 @@
+//! Events emitted with the `tracing` crate when the `tracing` feature is enabled.
+//!
+//! The functions here do nothing if the feature is disabled. If it is enabled, the fields of an event are only evaluated
+//! if a subscriber is interested in the event, so nothing is formatted or allocated otherwise.
+
+/// Emit a trace event for a message that was sent or received.
+///
+/// `body_signature` is only called if the event is recorded, since computing the signature of a body might allocate.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn message(
+	direction: &'static str,
+	header: &crate::proto::MessageHeader<'_>,
+	body_signature: impl FnOnce() -> Option<crate::proto::Signature>,
+	len: usize,
+) {
+	#[cfg(feature = "tracing")]
+	tracing::trace!(
+		direction,
+		message_type = message_type(&header.r#type),
+		serial = header.serial,
+		reply_serial = header.reply_serial(),
+		destination = header.destination(),
+		member = header.member(),
+		body_signature = body_signature().map(tracing::field::display),
+		len,
+		"{direction} message",
+	);
+}
+
+/// Emit a trace event for a method call that was completed by receiving its response or failing.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn method_call<T>(
+	serial: u32,
+	request: &crate::proto::MessageHeader<'_>,
+	result: &Result<T, crate::client::MethodCallError>,
+) {
+	#[cfg(feature = "tracing")]
+	tracing::trace!(
+		serial,
+		destination = request.destination(),
+		interface = request.interface(),
+		member = request.member(),
+		error = result.as_ref().err().map(tracing::field::display),
+		"method call completed",
+	);
+}
+
+/// Emit a debug event for a line of the SASL handshake that was sent or received.
+///
+/// The authentication data in `AUTH` and `DATA` lines is redacted.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn sasl_line(direction: &'static str, line: &[u8]) {
+	#[cfg(feature = "tracing")]
+	tracing::debug!(
+		direction,
+		line = %SaslLine(line),
+		"{direction} SASL line",
+	);
+}
+
+#[cfg(feature = "tracing")]
+fn message_type(r#type: &crate::proto::MessageType<'_>) -> &'static str {
+	match r#type {
+		crate::proto::MessageType::Error { .. } => "error",
+		crate::proto::MessageType::MethodCall { .. } => "method_call",
+		crate::proto::MessageType::MethodReturn { .. } => "method_return",
+		crate::proto::MessageType::Signal { .. } => "signal",
+		crate::proto::MessageType::Unknown { .. } => "unknown",
+	}
+}
+
+/// Displays a line of the SASL handshake without its line ending, and with the authentication data redacted.
+#[cfg(feature = "tracing")]
+struct SaslLine<'a>(&'a [u8]);
+
+#[cfg(feature = "tracing")]
+impl std::fmt::Display for SaslLine<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let line = self.0.strip_suffix(b"\r\n").unwrap_or(self.0);
+
+		// `AUTH <mechanism> <initial response>` and `DATA <data>`
+		let redact_from =
+			if let Some(rest) = line.strip_prefix(b"AUTH ") {
+				rest.iter().position(|&b| b == b' ').map(|pos| b"AUTH ".len() + pos)
+			}
+			else if line.starts_with(b"DATA") {
+				Some(b"DATA".len())
+			}
+			else {
+				None
+			};
+
+		match redact_from {
+			Some(redact_from) if redact_from < line.len() => write!(f, "{} <redacted>", String::from_utf8_lossy(&line[..redact_from])),
+			_ => f.write_str(&String::from_utf8_lossy(line)),
+		}
+	}
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+	/// The fields of an event recorded by [`CapturingSubscriber`], formatted as strings.
+	type Fields = std::collections::BTreeMap<&'static str, String>;
+
+	/// A subscriber that records all events.
+	#[derive(Clone, Default)]
+	struct CapturingSubscriber(std::sync::Arc<std::sync::Mutex<Vec<(tracing::Level, Fields)>>>);
+
+	impl tracing::Subscriber for CapturingSubscriber {
+		fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+			true
+		}
+
+		fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+			tracing::span::Id::from_u64(1)
+		}
+
+		fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {
+		}
+
+		fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {
+		}
+
+		fn event(&self, event: &tracing::Event<'_>) {
+			struct Visitor(Fields);
+
+			impl tracing::field::Visit for Visitor {
+				fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+					let _ = self.0.insert(field.name(), value.to_owned());
+				}
+
+				fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+					let _ = self.0.insert(field.name(), format!("{value:?}"));
+				}
+			}
+
+			let mut visitor = Visitor(Default::default());
+			event.record(&mut visitor);
+			self.0.lock().unwrap().push((*event.metadata().level(), visitor.0));
+		}
+
+		fn enter(&self, _: &tracing::span::Id) {
+		}
+
+		fn exit(&self, _: &tracing::span::Id) {
+		}
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let subscriber = CapturingSubscriber::default();
+		tracing::subscriber::with_default(subscriber.clone(), || {
+			let mut client = crate::fake_bus::client(|request, _| vec![crate::fake_bus::method_return(request, Some(crate::proto::Variant::U32(5)))]);
+			let response = client.method_call("org.example", crate::proto::ObjectPath("/".into()), "org.example", "Call", None).unwrap();
+			assert_eq!(response, Some(crate::proto::Variant::U32(5)));
+		});
+		let events = std::mem::take(&mut *subscriber.0.lock().unwrap());
+
+		let sasl_lines: Vec<_> =
+			events.iter()
+			.filter(|(level, fields)| *level == tracing::Level::DEBUG && fields["message"].ends_with("SASL line"))
+			.map(|(_, fields)| (fields["direction"].as_str(), fields["line"].as_str()))
+			.collect();
+		assert_eq!(sasl_lines.first(), Some(&("sent", "AUTH EXTERNAL <redacted>")));
+		assert!(sasl_lines.iter().any(|&(direction, line)| direction == "received" && line.starts_with("OK ")));
+		assert_eq!(sasl_lines.last(), Some(&("sent", "BEGIN")));
+
+		let messages: Vec<_> =
+			events.iter()
+			.filter(|(level, fields)| *level == tracing::Level::TRACE && fields["message"].ends_with(" message"))
+			.map(|(_, fields)| fields)
+			.collect();
+		let [hello, hello_response, call, call_response] = &messages[..] else {
+			panic!("unexpected message events {messages:?}");
+		};
+		assert_eq!(hello["member"], "Hello");
+		assert_eq!(hello_response["message_type"], "method_return");
+
+		assert_eq!(call["direction"], "sent");
+		assert_eq!(call["message_type"], "method_call");
+		assert_eq!(call["destination"], "org.example");
+		assert_eq!(call["member"], "Call");
+		assert!(!call.contains_key("body_signature"));
+		assert!(call["len"].parse::<usize>().unwrap() > 0);
+
+		assert_eq!(call_response["direction"], "received");
+		assert_eq!(call_response["message_type"], "method_return");
+		assert_eq!(call_response["reply_serial"], call["serial"]);
+		assert_eq!(call_response["body_signature"], "u");
+		assert!(call_response["len"].parse::<usize>().unwrap() > 0);
+
+		let method_call =
+			events.iter()
+			.find(|(_, fields)| fields["message"] == "method call completed" && fields["member"] == "Call")
+			.map(|(_, fields)| fields)
+			.unwrap();
+		assert_eq!(method_call["serial"], call["serial"]);
+		assert!(!method_call.contains_key("error"));
+	}
+}